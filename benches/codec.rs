@@ -0,0 +1,105 @@
+//! Encoder and decoder throughput benchmarks.
+//!
+//! Run with `cargo bench --bench codec`.
+
+use bytes::BytesMut;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use iridium_stomp::{Frame, StompCodec, StompItem};
+use std::hint::black_box;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A typical small text SEND.
+fn small_frame() -> Frame {
+    Frame::new("SEND")
+        .header("destination", "/queue/orders")
+        .header("content-type", "application/json")
+        .set_body(br#"{"id":42,"status":"created"}"#.to_vec())
+}
+
+/// A frame with many headers, some needing escaping.
+fn header_heavy_frame() -> Frame {
+    let mut frame = Frame::new("SEND").header("destination", "/topic/events");
+    for i in 0..32 {
+        frame = frame.header(format!("x-attr-{}", i), format!("value:{}\\n", i));
+    }
+    frame.set_body(b"payload".to_vec())
+}
+
+/// A MESSAGE as a broker delivers it, with the usual header set.
+fn broker_message() -> Frame {
+    Frame::new("MESSAGE")
+        .header("destination", "/queue/orders")
+        .header("message-id", "ID:broker-1234-1:1:1:1")
+        .header("subscription", "sub-0")
+        .header("ack", "ID:broker-1234-1:1:1:1")
+        .header("content-type", "application/json")
+        .header("persistent", "true")
+        .header("priority", "4")
+        .header("timestamp", "1700000000000")
+        .set_body(br#"{"id":42,"status":"created"}"#.to_vec())
+}
+
+/// A 64 KiB binary body.
+fn large_binary_frame() -> Frame {
+    Frame::new("SEND")
+        .header("destination", "/queue/blobs")
+        .set_body(vec![0xabu8; 64 * 1024])
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, frame) in [
+        ("small", small_frame()),
+        ("header_heavy", header_heavy_frame()),
+        ("large_binary", large_binary_frame()),
+    ] {
+        group.throughput(Throughput::Bytes(frame.encoded_len() as u64));
+        // Reuse one codec and buffer, as `Framed` does
+        let mut codec = StompCodec::new();
+        let mut buf = BytesMut::new();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                buf.clear();
+                codec
+                    .encode(StompItem::Frame(black_box(frame.clone())), &mut buf)
+                    .unwrap();
+                black_box(buf.len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_encoded_len(c: &mut Criterion) {
+    let frame = header_heavy_frame();
+    c.bench_function("encoded_len/header_heavy", |b| {
+        b.iter(|| black_box(&frame).encoded_len())
+    });
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, frame) in [
+        ("small", small_frame()),
+        ("header_heavy", header_heavy_frame()),
+        ("broker_message", broker_message()),
+        ("large_binary", large_binary_frame()),
+    ] {
+        let mut wire = BytesMut::new();
+        StompCodec::new()
+            .encode(StompItem::Frame(frame), &mut wire)
+            .unwrap();
+        group.throughput(Throughput::Bytes(wire.len() as u64));
+        let mut codec = StompCodec::new();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut src = wire.clone();
+                black_box(codec.decode(&mut src).unwrap());
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_encoded_len, bench_decode);
+criterion_main!(benches);
@@ -19,10 +19,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Here we use `SubscriptionOptions` to optionally specify a durable
     // queue name (for brokers like RabbitMQ) or include broker-specific
     // headers (for example ActiveMQ's durable subscription headers).
-    let opts = SubscriptionOptions {
-        durable_queue: Some("/queue/example-durable".to_string()),
-        headers: vec![],
-    };
+    let opts = SubscriptionOptions::default().durable_queue("/queue/example-durable");
 
     let mut sub = conn
         .subscribe_with_options("/exchange/topic", AckMode::Client, opts)
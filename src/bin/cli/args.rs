@@ -1,11 +1,19 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use iridium_stomp::{AckMode, ConnectOptions, TlsOptions};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::commands::parse_duration;
+use super::exit_codes;
+use super::state::Verbosity;
 
 #[derive(Parser)]
 #[command(name = "stomp")]
 #[command(version)]
 #[command(about = "Interactive STOMP client CLI")]
 pub struct Cli {
-    /// STOMP broker address (host:port)
+    /// STOMP broker address (host:port, stomp://host:port/vhost, or
+    /// stomps://host:port/vhost for TLS)
     #[arg(short, long, default_value = "127.0.0.1:61613")]
     pub address: String,
 
@@ -17,6 +25,29 @@ pub struct Cli {
     #[arg(short, long, default_value = "guest")]
     pub passcode: String,
 
+    /// Connect over TLS (implied by a stomps:// address and the options below)
+    #[arg(long)]
+    pub tls: bool,
+
+    /// TLS: accept any broker certificate, for self-signed development
+    /// brokers only
+    #[arg(long, conflicts_with = "ca_cert")]
+    pub insecure: bool,
+
+    /// TLS: trust the CA certificates in this PEM file instead of the
+    /// bundled public roots
+    #[arg(long, value_name = "PEM")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// TLS: client certificate (PEM) for brokers that authenticate clients
+    /// by certificate
+    #[arg(long, value_name = "PEM", requires = "client_key")]
+    pub client_cert: Option<PathBuf>,
+
+    /// TLS: private key (PEM) of --client-cert
+    #[arg(long, value_name = "PEM", requires = "client_cert")]
+    pub client_key: Option<PathBuf>,
+
     /// Heartbeat settings (client-send,client-receive in ms)
     #[arg(long, default_value = "10000,10000")]
     pub heartbeat: String,
@@ -25,11 +56,182 @@ pub struct Cli {
     #[arg(short, long)]
     pub subscribe: Vec<String>,
 
+    /// Ack mode for subscriptions: auto, client, or client-individual.
+    /// With client modes, messages stay pending until acknowledged with `ack`
+    #[arg(long, default_value = "auto", value_parser = parse_ack_mode)]
+    pub ack: AckMode,
+
     /// Enable TUI mode with panels and live updates
     #[arg(long)]
     pub tui: bool,
 
+    /// TUI key binding file (default: ~/.config/iridium-stomp/keys.conf, if present)
+    #[arg(long, value_name = "FILE")]
+    pub keymap: Option<PathBuf>,
+
+    /// Bookmark file (default: ~/.config/iridium-stomp/bookmarks.conf)
+    #[arg(long, value_name = "FILE")]
+    pub bookmarks: Option<PathBuf>,
+
+    /// Run the shell commands in this file (one per line, e.g. `sub`,
+    /// `send`, `bookmark`) after connecting
+    #[arg(long, value_name = "FILE", conflicts_with = "pipe")]
+    pub init: Option<PathBuf>,
+
+    /// Send each stdin line (`destination<TAB>body` or a JSON object)
+    /// without prompting, printing only errors
+    #[arg(long, conflicts_with_all = ["tui", "subscribe", "summary"])]
+    pub pipe: bool,
+
     /// Show session summary on exit
     #[arg(long)]
     pub summary: bool,
+
+    /// Plain mode: exit after this long (e.g., 30s, 5m), printing the
+    /// session summary
+    #[arg(long, value_parser = parse_duration_arg, conflicts_with_all = ["tui", "pipe"])]
+    pub duration: Option<Duration>,
+
+    /// Plain mode: exit once this many messages have been received across
+    /// all subscriptions, printing the session summary
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["tui", "pipe"]
+    )]
+    pub message_limit: Option<u64>,
+
+    /// Plain mode: print only message bodies (no headers or prompts), for piping
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Plain mode: also print heartbeats, receipts, and frame sizes
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// One-shot commands run instead of the interactive session
+#[derive(Subcommand)]
+pub enum Command {
+    /// Measure broker round-trip latency by publishing probes to a
+    /// destination the client is subscribed to
+    Ping(PingArgs),
+}
+
+/// Arguments of `stomp ping`
+#[derive(Args)]
+pub struct PingArgs {
+    /// Destination to probe (default: a unique /queue/iridium-ping-* queue)
+    pub destination: Option<String>,
+
+    /// Number of probes to send
+    #[arg(short = 'n', long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
+
+    /// Pause between probes (e.g., 200ms, 1s)
+    #[arg(long, default_value = "200ms", value_parser = parse_duration_arg)]
+    pub interval: Duration,
+
+    /// How long to wait for each probe to come back
+    #[arg(long, default_value = "5s", value_parser = parse_duration_arg)]
+    pub timeout: Duration,
+}
+
+/// Parse a duration argument like `500ms`, `5s`, or `2m`
+fn parse_duration_arg(s: &str) -> Result<Duration, String> {
+    parse_duration(s).ok_or_else(|| format!("invalid duration '{}' (e.g., 5s, 500ms)", s))
+}
+
+/// Parse an ack mode argument
+fn parse_ack_mode(s: &str) -> Result<AckMode, String> {
+    match s {
+        "auto" => Ok(AckMode::Auto),
+        "client" => Ok(AckMode::Client),
+        "client-individual" => Ok(AckMode::ClientIndividual),
+        _ => Err(format!(
+            "invalid ack mode '{}' (auto, client, or client-individual)",
+            s
+        )),
+    }
+}
+
+/// Read a PEM file given on the command line
+fn read_pem(path: &Path, what: &str) -> Result<Vec<u8>, (String, u8)> {
+    std::fs::read(path).map_err(|e| {
+        (
+            format!("Cannot read {} {}: {}", what, path.display(), e),
+            exit_codes::NETWORK_ERROR,
+        )
+    })
+}
+
+impl Cli {
+    /// `--address` with a `stomps://` scheme replaced by `stomp://`, and
+    /// whether the scheme asked for TLS
+    fn split_address(&self) -> (String, bool) {
+        match self.address.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("stomps") => {
+                (format!("stomp://{}", rest), true)
+            }
+            _ => (self.address.clone(), false),
+        }
+    }
+
+    /// The broker address to connect to, without the `stomps://` scheme
+    pub fn broker_address(&self) -> String {
+        self.split_address().0
+    }
+
+    /// Whether to connect over TLS: `--tls`, any TLS option, or a
+    /// `stomps://` address
+    pub fn uses_tls(&self) -> bool {
+        self.tls
+            || self.insecure
+            || self.ca_cert.is_some()
+            || self.client_cert.is_some()
+            || self.split_address().1
+    }
+
+    /// Connection options with the TLS settings from the command line,
+    /// or an error if a certificate or key file cannot be read
+    pub fn connect_options(&self) -> Result<ConnectOptions, (String, u8)> {
+        let options = ConnectOptions::default();
+        if !self.uses_tls() {
+            return Ok(options);
+        }
+        let mut tls = TlsOptions::new();
+        if self.insecure {
+            tls = tls.danger_accept_invalid_certs();
+        }
+        if let Some(path) = &self.ca_cert {
+            tls = tls.ca_certificates_pem(read_pem(path, "CA certificate")?);
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+            tls = tls.client_certificate_pem(
+                read_pem(cert, "client certificate")?,
+                read_pem(key, "client key")?,
+            );
+        }
+        Ok(options.tls(tls))
+    }
+
+    /// Whether `--duration` or `--message-limit` ends the session on its own
+    pub fn auto_exit(&self) -> bool {
+        self.duration.is_some() || self.message_limit.is_some()
+    }
+
+    /// Output verbosity selected by `--quiet` / `--verbose`
+    pub fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
 }
@@ -0,0 +1,159 @@
+//! Named destinations, used as `@name` wherever a destination is typed.
+//!
+//! Bookmarks are kept in a small config file with one `name = destination`
+//! line each, for example:
+//!
+//! ```text
+//! # Production order flow
+//! orders = /queue/prod.eu-west-1.orders.v2
+//! audit = /topic/prod.audit.all
+//! ```
+//!
+//! The `bookmark` command rewrites the file, so comments do not survive
+//! it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Usage string for the bookmark command
+pub const BOOKMARK_USAGE: &str = "Usage: bookmark [<name> <destination> | --delete <name>]";
+
+/// Bookmarked destinations and the file they are saved to
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    /// Where changes are saved; `None` if there is no config directory
+    path: Option<PathBuf>,
+    /// Destinations by name, sorted for listing
+    entries: BTreeMap<String, String>,
+}
+
+impl Bookmarks {
+    /// Load bookmarks from `path` if given, otherwise from the default
+    /// location. A missing file is an empty set of bookmarks.
+    pub fn load(path: Option<&Path>) -> Result<Bookmarks, String> {
+        let path = path.map(Path::to_path_buf).or_else(default_path);
+        let text = match &path {
+            Some(p) if p.exists() => std::fs::read_to_string(p)
+                .map_err(|e| format!("Failed to read bookmarks {}: {}", p.display(), e))?,
+            _ => String::new(),
+        };
+        let entries = parse(&text).map_err(|e| match &path {
+            Some(p) => format!("{}: {}", p.display(), e),
+            None => e,
+        })?;
+        Ok(Bookmarks { path, entries })
+    }
+
+    /// Expand `@name` to its destination; anything else is returned as is
+    pub fn expand<'a>(&'a self, dest: &'a str) -> Result<&'a str, String> {
+        let Some(name) = dest.strip_prefix('@') else {
+            return Ok(dest);
+        };
+        self.entries.get(name).map(String::as_str).ok_or_else(|| {
+            format!(
+                "Unknown bookmark '@{}'. Type 'bookmark' to list them.",
+                name
+            )
+        })
+    }
+
+    /// Save `destination` as `name`, replacing any earlier bookmark
+    pub fn set(&mut self, name: &str, destination: &str) -> Result<(), String> {
+        check_name(name)?;
+        let previous = self
+            .entries
+            .insert(name.to_string(), destination.to_string());
+        self.save().inspect_err(|_| match previous {
+            Some(previous) => {
+                self.entries.insert(name.to_string(), previous);
+            }
+            None => {
+                self.entries.remove(name);
+            }
+        })
+    }
+
+    /// Delete the bookmark `name`
+    pub fn remove(&mut self, name: &str) -> Result<(), String> {
+        let Some(previous) = self.entries.remove(name) else {
+            return Err(format!("Unknown bookmark '@{}'", name));
+        };
+        self.save().inspect_err(|_| {
+            self.entries.insert(name.to_string(), previous);
+        })
+    }
+
+    /// `(name, destination)` pairs sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(n, d)| (n.as_str(), d.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write all bookmarks to the file, creating its directory if needed
+    fn save(&self) -> Result<(), String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or("Cannot save bookmarks: no config directory (set HOME or use --bookmarks)")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let mut text = String::from("# iridium-stomp bookmarks: name = destination\n");
+        for (name, destination) in &self.entries {
+            text.push_str(&format!("{} = {}\n", name, destination));
+        }
+        std::fs::write(path, text)
+            .map_err(|e| format!("Failed to write bookmarks {}: {}", path.display(), e))
+    }
+}
+
+/// Parse a bookmarks file. Only whole lines are comments, since `#` may
+/// appear in a destination.
+fn parse(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut entries = BTreeMap::new();
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, destination) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected 'name = destination'", lineno + 1))?;
+        let (name, destination) = (name.trim(), destination.trim());
+        check_name(name).map_err(|e| format!("line {}: {}", lineno + 1, e))?;
+        if destination.is_empty() {
+            return Err(format!("line {}: missing destination", lineno + 1));
+        }
+        entries.insert(name.to_string(), destination.to_string());
+    }
+    Ok(entries)
+}
+
+/// Names are used after `@` on the command line, so keep them to one word
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid bookmark name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        ))
+    }
+}
+
+/// `bookmarks.conf` in the iridium-stomp config directory
+pub fn default_path() -> Option<PathBuf> {
+    Some(super::config_dir()?.join("bookmarks.conf"))
+}
@@ -1,8 +1,35 @@
-use iridium_stomp::{Connection, Frame};
+use iridium_stomp::{
+    AckMode, BrokerProfile, ConnError, Connection, ConnectionEvent, Frame, ServerError, ServerInfo,
+    destination,
+};
 use std::io::Write;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
-use super::state::SharedState;
+use super::bookmarks::BOOKMARK_USAGE;
+use super::ping::{PingOptions, format_ms, ping, unique_destination};
+use super::state::{AppState, SharedState, Verbosity};
+use super::stats::{self, parse_stats_arg};
+
+/// Usage string for the send command
+const SEND_USAGE: &str =
+    "Usage: send [--confirm [--timeout <duration>]] [--repeat <n>] <destination> <message>";
+
+/// Usage string for the ping command
+const PING_USAGE: &str = "Usage: ping [-n|--count <count>] [destination]";
+
+/// Default time to wait for a receipt with `send --confirm`
+const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Flags accepted by the send command
+struct SendFlags {
+    /// Request a RECEIPT and wait for it
+    confirm: bool,
+    /// How long to wait for the receipt
+    timeout: Duration,
+    /// Number of messages to send, each with the template rendered anew
+    repeat: u32,
+}
 
 /// Result of executing a command
 pub enum CommandResult {
@@ -33,88 +60,62 @@ pub async fn execute_command(
         "quit" | "exit" | "q" => CommandResult::Quit,
 
         "send" => {
-            if parts.len() < 3 {
-                return CommandResult::Error("Usage: send <destination> <message>".to_string());
-            }
-            let dest = parts[1];
-            let msg = parts[2];
-
-            // Validate destination format
-            if !dest.starts_with('/') {
-                return CommandResult::Error(format!(
-                    "Invalid destination '{}'. Must start with / (e.g., /topic/test, /queue/test)",
-                    dest
-                ));
-            }
-
-            // Check if destination is known (subscribed) or matches common patterns
-            let is_known = {
-                let state = state.lock().await;
-                state.subscriptions.contains_key(dest)
+            let args = line.trim()[parts[0].len()..].trim_start();
+            let (flags, args) = match parse_send_flags(args) {
+                Ok(v) => v,
+                Err(e) => return CommandResult::Error(e),
             };
-            let is_common_pattern = dest.starts_with("/topic/")
-                || dest.starts_with("/queue/")
-                || dest.starts_with("/amq/")
-                || dest.starts_with("/exchange/");
-
-            let warning = if !is_known && !is_common_pattern {
-                Some(format!(
-                    "Warning: '{}' doesn't match common patterns (/topic/, /queue/, /amq/, /exchange/)",
-                    dest
-                ))
-            } else {
-                None
+            let mut args = args.splitn(2, ' ');
+            let (dest, msg) = match (args.next(), args.next()) {
+                (Some(d), Some(m)) if !d.is_empty() => (d, m),
+                _ => return CommandResult::Error(SEND_USAGE.to_string()),
             };
+            let dest = match expand_bookmark(&state, dest).await {
+                Ok(dest) => dest,
+                Err(e) => return CommandResult::Error(e),
+            };
+            let dest = dest.as_str();
 
-            let frame = Frame::new("SEND")
-                .header("destination", dest)
-                .header("content-type", "text/plain")
-                .set_body(msg.as_bytes().to_vec());
-            match conn.send_frame(frame).await {
-                Ok(_) => {
-                    if tui_mode {
-                        let mut state = state.lock().await;
-                        if let Some(warn) = warning {
-                            state.record_message("WARN", warn, vec![]);
-                        }
-                        state.record_message("SENT", format!("[{}] {}", dest, msg), vec![]);
-                    } else {
-                        if let Some(warn) = warning {
-                            eprintln!("{}", warn);
-                        }
-                        println!("Sent to {}", dest);
-                    }
-                    CommandResult::Ok
+            let warning = match check_destination(dest) {
+                Ok(warning) => warning,
+                Err(e) => return CommandResult::Error(e),
+            };
+
+            // Destinations we are subscribed to are known to work
+            let is_known = state.lock().await.subscriptions.contains_key(dest);
+            let mut warning = warning.filter(|_| !is_known);
+
+            for _ in 0..flags.repeat {
+                let body = match state.lock().await.template.render(msg) {
+                    Ok(body) => body,
+                    Err(e) => return CommandResult::Error(e),
+                };
+                // Warn about an unusual destination once, not per repeat
+                let result =
+                    send_message(conn, &state, dest, &body, &flags, warning.take(), tui_mode).await;
+                if !matches!(result, CommandResult::Ok) {
+                    return result;
                 }
-                Err(e) => CommandResult::Error(format!("Send error: {}", e)),
             }
+            CommandResult::Ok
         }
 
         "sub" | "subscribe" => {
             if parts.len() < 2 {
                 return CommandResult::Error("Usage: sub <destination>".to_string());
             }
-            let dest = parts[1];
-
-            // Validate destination format
-            if !dest.starts_with('/') {
-                return CommandResult::Error(format!(
-                    "Invalid destination '{}'. Must start with / (e.g., /topic/test, /queue/test)",
-                    dest
-                ));
-            }
+            let dest = match expand_bookmark(&state, parts[1]).await {
+                Ok(dest) => dest,
+                Err(e) => return CommandResult::Error(e),
+            };
+            let dest = dest.as_str();
 
-            // Warn for unusual patterns
-            let is_common_pattern = dest.starts_with("/topic/")
-                || dest.starts_with("/queue/")
-                || dest.starts_with("/amq/")
-                || dest.starts_with("/exchange/");
+            let warning = match check_destination(dest) {
+                Ok(warning) => warning,
+                Err(e) => return CommandResult::Error(e),
+            };
 
-            if !is_common_pattern {
-                let warn = format!(
-                    "Warning: '{}' doesn't match common patterns (/topic/, /queue/, /amq/, /exchange/)",
-                    dest
-                );
+            if let Some(warn) = warning {
                 if tui_mode {
                     let mut state = state.lock().await;
                     state.record_message("WARN", warn, vec![]);
@@ -132,6 +133,102 @@ pub async fn execute_command(
             CommandResult::Ok
         }
 
+        "ack" => {
+            if parts.len() < 2 {
+                return CommandResult::Error("Usage: ack <destination>".to_string());
+            }
+            let dest = match expand_bookmark(&state, parts[1]).await {
+                Ok(dest) => dest,
+                Err(e) => return CommandResult::Error(e),
+            };
+            acknowledge(conn, &state, &dest, tui_mode).await
+        }
+
+        "ping" => {
+            let args = line.trim()[parts[0].len()..].trim_start();
+            let (options, dest) = match parse_ping_args(args) {
+                Ok(v) => v,
+                Err(e) => return CommandResult::Error(e),
+            };
+            let dest = match dest {
+                Some(dest) => match expand_bookmark(&state, dest).await {
+                    Ok(dest) => dest,
+                    Err(e) => return CommandResult::Error(e),
+                },
+                None => unique_destination(),
+            };
+            if let Err(e) = check_destination(&dest) {
+                return CommandResult::Error(e);
+            }
+            if !tui_mode {
+                println!("PING {}: {} probes", dest, options.count);
+            }
+            let result = ping(conn, &dest, options, |seq, rtt| {
+                if tui_mode {
+                    return;
+                }
+                match rtt {
+                    Some(rtt) => println!("probe {}: {} ms", seq, format_ms(rtt)),
+                    None => println!("probe {}: timed out", seq),
+                }
+            })
+            .await;
+            match result {
+                Ok(report) => {
+                    let lines = report.summary_lines();
+                    if tui_mode {
+                        return CommandResult::Info(format!("ping {}: {}", dest, lines.join(", ")));
+                    }
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                    CommandResult::Ok
+                }
+                Err(e) => CommandResult::Error(e),
+            }
+        }
+
+        "bookmark" | "bookmarks" => {
+            let mut state = state.lock().await;
+            let msg = match (parts.get(1).copied(), parts.get(2).map(|d| d.trim())) {
+                (None, _) => {
+                    if state.bookmarks.is_empty() {
+                        "No bookmarks. Add one with: bookmark <name> <destination>".to_string()
+                    } else {
+                        let lines: Vec<String> = state
+                            .bookmarks
+                            .iter()
+                            .map(|(name, dest)| format!("@{} = {}", name, dest))
+                            .collect();
+                        lines.join(if tui_mode { " | " } else { "\n" })
+                    }
+                }
+                (Some("--delete"), Some(name)) if !name.is_empty() => {
+                    let name = name.trim_start_matches('@');
+                    if let Err(e) = state.bookmarks.remove(name) {
+                        return CommandResult::Error(e);
+                    }
+                    format!("Deleted bookmark @{}", name)
+                }
+                (Some(name), Some(dest)) if !dest.is_empty() && !name.starts_with("--") => {
+                    let name = name.trim_start_matches('@');
+                    if let Err(e) = check_destination(dest) {
+                        return CommandResult::Error(e);
+                    }
+                    if let Err(e) = state.bookmarks.set(name, dest) {
+                        return CommandResult::Error(e);
+                    }
+                    format!("Bookmarked {} as @{}", dest, name)
+                }
+                _ => return CommandResult::Error(BOOKMARK_USAGE.to_string()),
+            };
+            if tui_mode {
+                return CommandResult::Info(msg);
+            }
+            println!("{}", msg);
+            CommandResult::Ok
+        }
+
         "about" => {
             if tui_mode {
                 return CommandResult::Info(format!(
@@ -143,7 +240,63 @@ pub async fn execute_command(
             CommandResult::Ok
         }
 
+        "info" => {
+            let lines = server_info_lines(&conn.server_info().await);
+            if tui_mode {
+                return CommandResult::Info(lines.join(" | "));
+            }
+            for line in lines {
+                println!("{}", line);
+            }
+            CommandResult::Ok
+        }
+
+        "stats" => {
+            let interval = match parse_stats_arg(parts.get(1).copied()) {
+                Ok(interval) => interval,
+                Err(e) => return CommandResult::Error(e),
+            };
+            let msg = match interval {
+                None => {
+                    let stats = stats::collect(conn, &state).await;
+                    let started = state.lock().await.started;
+                    let lines = stats.lines(&stats::zero_sample(started));
+                    if tui_mode {
+                        return CommandResult::Info(lines.join(" | "));
+                    }
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                    return CommandResult::Ok;
+                }
+                Some(interval) => {
+                    let mut s = state.lock().await;
+                    if let Some(printer) = s.stats_printer.take() {
+                        printer.abort();
+                    }
+                    match interval {
+                        Some(interval) => {
+                            s.stats_printer = Some(stats::spawn_printer(
+                                conn,
+                                state.clone(),
+                                interval,
+                                tui_mode,
+                            ));
+                            format!("Printing stats every {:?}", interval)
+                        }
+                        None => "Stopped printing stats".to_string(),
+                    }
+                }
+            };
+            if tui_mode {
+                return CommandResult::Info(msg);
+            }
+            println!("{}", msg);
+            CommandResult::Ok
+        }
+
         "summary" => {
+            refresh_pending_acks(conn, &state).await;
             if parts.len() >= 2 {
                 // Write to file
                 let filename = parts[1];
@@ -173,6 +326,7 @@ pub async fn execute_command(
         }
 
         "report" => {
+            refresh_pending_acks(conn, &state).await;
             if parts.len() >= 2 {
                 // Write to file
                 let filename = parts[1];
@@ -203,6 +357,23 @@ pub async fn execute_command(
             CommandResult::Ok
         }
 
+        "export" => {
+            let Some(filename) = parts.get(1) else {
+                return CommandResult::Error("Usage: export <filename>".to_string());
+            };
+            let state = state.lock().await;
+            let count = state.visible_messages().len();
+            if let Err(e) = std::fs::write(filename, state.export_messages()) {
+                return CommandResult::Error(format!("Failed to write {}: {}", filename, e));
+            }
+            let msg = format!("Exported {} messages to {}", count, filename);
+            if tui_mode {
+                return CommandResult::Info(msg);
+            }
+            println!("{}", msg);
+            CommandResult::Ok
+        }
+
         "clear" => {
             let mut state = state.lock().await;
             state.clear_messages();
@@ -212,7 +383,8 @@ pub async fn execute_command(
         "help" | "?" => {
             if tui_mode {
                 return CommandResult::Info(
-                    "Commands: send, sub, summary <file>, report <file>, clear, quit".to_string(),
+                    "Commands: send, sub, ack, ping, bookmark, info, stats [interval|off], summary <file>, report <file>, export <file>, clear, quit (@name = bookmark)"
+                        .to_string(),
                 );
             }
             print_help();
@@ -226,16 +398,389 @@ pub async fn execute_command(
     }
 }
 
+/// Describe the broker and the endpoint the connection actually reached.
+fn server_info_lines(info: &ServerInfo) -> Vec<String> {
+    let or_unknown = |addr: Option<std::net::SocketAddr>| {
+        addr.map_or_else(|| "unknown".to_string(), |a| a.to_string())
+    };
+    #[cfg_attr(not(feature = "tls"), allow(unused_mut))]
+    let mut lines = vec![
+        format!(
+            "Server: {}",
+            info.server.as_deref().unwrap_or("(not reported)")
+        ),
+        format!("STOMP version: {}", info.version),
+        format!(
+            "Session: {}",
+            info.session.as_deref().unwrap_or("(not reported)")
+        ),
+        format!("Server heart-beat: {}", info.heart_beat),
+        format!("Broker endpoint: {}", or_unknown(info.peer_addr)),
+        format!("Local endpoint: {}", or_unknown(info.local_addr)),
+    ];
+    #[cfg(feature = "tls")]
+    match &info.tls {
+        Some(tls) => {
+            lines.push(format!(
+                "TLS: {} {}",
+                tls.protocol_version, tls.cipher_suite
+            ));
+            if let Some(alpn) = &tls.alpn_protocol {
+                lines.push(format!("ALPN: {}", alpn));
+            }
+            if let Some(cert) = &tls.peer_certificate {
+                lines.push(format!(
+                    "Certificate: {} (issuer {})",
+                    cert.subject, cert.issuer
+                ));
+            }
+        }
+        None => lines.push("TLS: not in use".to_string()),
+    }
+    lines
+}
+
+/// Text for a broker ERROR frame.
+///
+/// An ERROR whose `receipt-id` names a pending `send --confirm` is shown
+/// with that send's destination and elapsed time; the SENT entry is marked
+/// failed and the waiting command is released.
+pub fn describe_broker_error(state: &mut AppState, err: &ServerError) -> String {
+    let msg = match &err.body {
+        Some(body) => format!("{}: {}", err.message, body),
+        None => err.message.clone(),
+    };
+    let Some(pending) = err
+        .receipt_id
+        .as_deref()
+        .and_then(|id| state.pending_sends.remove(id))
+    else {
+        return msg;
+    };
+    if let Some(id) = &err.receipt_id {
+        state.mark_send_failed(id);
+    }
+    let _ = pending.rejected.send(err.message.clone());
+    format!(
+        "SEND to {} failed after {} ms: {}",
+        pending.destination,
+        pending.sent_at.elapsed().as_millis(),
+        msg
+    )
+}
+
+/// Warning text for connection events worth showing to the user.
+pub fn event_warning(event: &ConnectionEvent) -> Option<String> {
+    match event {
+        ConnectionEvent::SlowConsumer {
+            destination,
+            dropped,
+            buffered,
+            ..
+        } => Some(format!(
+            "Subscription to {} is falling behind: {} buffered, {} dropped so far",
+            destination, buffered, dropped
+        )),
+        _ => None,
+    }
+}
+
+/// Status line for reconnects, shown as `[...]` in plain mode.
+pub fn event_notice(event: &ConnectionEvent) -> Option<String> {
+    match event {
+        ConnectionEvent::Reconnecting { attempt } => {
+            Some(format!("RECONNECTING attempt={}", attempt))
+        }
+        ConnectionEvent::Reconnected { resubscribed, .. } if resubscribed.is_empty() => {
+            Some("RECONNECTED".to_string())
+        }
+        ConnectionEvent::Reconnected { resubscribed, .. } => Some(format!(
+            "RECONNECTED, resubscribed: {}",
+            resubscribed.join(", ")
+        )),
+        _ => None,
+    }
+}
+
+/// Replace a `@name` destination with its bookmark
+async fn expand_bookmark(state: &SharedState, dest: &str) -> Result<String, String> {
+    state
+        .lock()
+        .await
+        .bookmarks
+        .expand(dest)
+        .map(str::to_string)
+}
+
+/// Publish one rendered send body, waiting for its receipt with --confirm
+async fn send_message(
+    conn: &Connection,
+    state: &SharedState,
+    dest: &str,
+    msg: &str,
+    flags: &SendFlags,
+    warning: Option<String>,
+    tui_mode: bool,
+) -> CommandResult {
+    let verbosity = state.lock().await.verbosity;
+    let frame = Frame::new("SEND")
+        .header("destination", dest)
+        .header("content-type", "text/plain")
+        .set_body(msg.as_bytes().to_vec());
+    let frame_size = frame.encoded_len();
+    let started = Instant::now();
+    // With --confirm, register the receipt while holding the state
+    // lock so an ERROR answering it cannot be handled first
+    let (receipt_id, rejected) = if flags.confirm {
+        let mut state = state.lock().await;
+        match conn.send_frame_with_receipt(frame).await {
+            Ok(id) => {
+                let rejected = state.track_send(&id, dest, started);
+                (Some(id), Some(rejected))
+            }
+            Err(e) => return CommandResult::Error(format!("Send error: {}", e)),
+        }
+    } else {
+        if let Err(e) = conn.send_frame(frame).await {
+            return CommandResult::Error(format!("Send error: {}", e));
+        }
+        (None, None)
+    };
+
+    {
+        let mut state = state.lock().await;
+        if tui_mode {
+            if let Some(warn) = warning {
+                state.record_message("WARN", warn, vec![]);
+            }
+            let headers = receipt_id
+                .iter()
+                .map(|id| ("receipt".to_string(), id.clone()))
+                .collect();
+            state.record_message("SENT", format!("[{}] {}", dest, msg), headers);
+        } else {
+            if let Some(warn) = warning {
+                eprintln!("{}", warn);
+            }
+            match verbosity {
+                Verbosity::Quiet => {}
+                Verbosity::Normal => println!("Sent to {}", dest),
+                Verbosity::Verbose => {
+                    println!("Sent to {} ({} bytes on the wire)", dest, frame_size)
+                }
+            }
+        }
+    }
+
+    let (Some(receipt_id), Some(rejected)) = (receipt_id, rejected) else {
+        return CommandResult::Ok;
+    };
+    tokio::select! {
+        result = conn.wait_for_receipt(&receipt_id, flags.timeout) => match result {
+            Ok(()) => {
+                let rtt = started.elapsed();
+                let mut state = state.lock().await;
+                state.pending_sends.remove(&receipt_id);
+                state.record_receipt(dest, rtt);
+                if !tui_mode && verbosity != Verbosity::Quiet {
+                    println!("Receipt confirmed in {} ms", rtt.as_millis());
+                }
+                CommandResult::Ok
+            }
+            // Stays tracked so a late ERROR is still matched up
+            Err(ConnError::ReceiptTimeout(_)) => CommandResult::Error(format!(
+                "Sent to {} but no receipt within {} ms",
+                dest,
+                flags.timeout.as_millis()
+            )),
+            Err(ConnError::Receipt(_)) => {
+                state.lock().await.pending_sends.remove(&receipt_id);
+                CommandResult::Error(format!(
+                    "Sent to {} but the connection dropped before a receipt arrived",
+                    dest
+                ))
+            }
+            Err(e) => CommandResult::Error(format!("Send error: {}", e)),
+        },
+        // The broker error was already shown with this send's details
+        Ok(_) = rejected => CommandResult::Ok,
+    }
+}
+
+/// Validate a destination typed at the prompt.
+///
+/// Returns an error message for unusable destinations, or an optional
+/// warning for names that look unusual.
+fn check_destination(dest: &str) -> Result<Option<String>, String> {
+    let report = destination::validate(dest, BrokerProfile::Generic);
+    if let Some(err) = report.errors.first() {
+        return Err(format!("Invalid destination '{}': {}", dest, err));
+    }
+    Ok((!report.warnings.is_empty()).then(|| {
+        let warnings: Vec<String> = report.warnings.iter().map(|w| w.to_string()).collect();
+        format!("Warning: '{}': {}", dest, warnings.join("; "))
+    }))
+}
+
+/// Parse leading `--confirm` / `--timeout` / `--repeat` flags from send
+/// arguments.
+///
+/// Returns the flags and the remaining `<destination> <message>` text.
+fn parse_send_flags(mut args: &str) -> Result<(SendFlags, &str), String> {
+    let mut flags = SendFlags {
+        confirm: false,
+        timeout: DEFAULT_CONFIRM_TIMEOUT,
+        repeat: 1,
+    };
+    let mut timeout_given = false;
+
+    while args.starts_with("--") {
+        let (flag, rest) = args.split_once(' ').unwrap_or((args, ""));
+        args = rest.trim_start();
+        match flag {
+            "--confirm" => flags.confirm = true,
+            "--timeout" => {
+                let (value, rest) = args.split_once(' ').unwrap_or((args, ""));
+                args = rest.trim_start();
+                flags.timeout = parse_duration(value)
+                    .ok_or_else(|| format!("Invalid timeout '{}' (e.g., 5s, 500ms)", value))?;
+                timeout_given = true;
+            }
+            "--repeat" => {
+                let (value, rest) = args.split_once(' ').unwrap_or((args, ""));
+                args = rest.trim_start();
+                flags.repeat = value
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid repeat count '{}'", value))?;
+            }
+            _ => return Err(format!("Unknown send option '{}'. {}", flag, SEND_USAGE)),
+        }
+    }
+
+    if timeout_given && !flags.confirm {
+        return Err("--timeout requires --confirm".to_string());
+    }
+    Ok((flags, args))
+}
+
+/// Parse ping arguments: an optional `--count <n>` and an optional
+/// destination.
+fn parse_ping_args(args: &str) -> Result<(PingOptions, Option<&str>), String> {
+    let mut options = PingOptions::default();
+    let mut dest = None;
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "--count" | "-n" => {
+                let value = words.next().ok_or_else(|| PING_USAGE.to_string())?;
+                options.count = value
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid count '{}'", value))?;
+            }
+            _ if word.starts_with("--") => {
+                return Err(format!("Unknown ping option '{}'. {}", word, PING_USAGE));
+            }
+            _ if dest.is_none() => dest = Some(word),
+            _ => return Err(PING_USAGE.to_string()),
+        }
+    }
+    Ok((options, dest))
+}
+
+/// Parse a duration like `5s`, `500ms`, `2m`, or a bare number of seconds
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+    let value: u64 = num.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        // Minutes that overflow are as invalid as a bad number
+        "m" => value.checked_mul(60).map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+/// Update the unacked counts shown for each subscription
+async fn refresh_pending_acks(conn: &Connection, state: &SharedState) {
+    let pending = conn.pending_acks().await;
+    state.lock().await.update_pending_acks(&pending);
+}
+
+/// Acknowledge every message received on `dest` that has not been
+/// acknowledged yet, with as few ACK frames as the ack mode allows
+async fn acknowledge(
+    conn: &Connection,
+    state: &SharedState,
+    dest: &str,
+    tui_mode: bool,
+) -> CommandResult {
+    let (subscription_id, ids) = {
+        let mut s = state.lock().await;
+        if s.ack_mode == AckMode::Auto {
+            return CommandResult::Error(
+                "Subscriptions use ack mode auto; start with --ack client to acknowledge manually"
+                    .to_string(),
+            );
+        }
+        match s.subscriptions.get_mut(dest) {
+            Some(stats) if !stats.subscription_id.is_empty() => (
+                stats.subscription_id.clone(),
+                std::mem::take(&mut stats.unacked_ids),
+            ),
+            _ => return CommandResult::Error(format!("Not subscribed to {}", dest)),
+        }
+    };
+    if ids.is_empty() {
+        return CommandResult::Info(format!("Nothing to acknowledge on {}", dest));
+    }
+
+    if let Err(e) = conn.ack_batch(&subscription_id, &ids).await {
+        return CommandResult::Error(format!("ACK on {} failed: {}", dest, e));
+    }
+    if let Some(stats) = state.lock().await.subscriptions.get_mut(dest) {
+        stats.acked_count += ids.len() as u64;
+    }
+    refresh_pending_acks(conn, state).await;
+
+    let msg = format!("Acknowledged {} message(s) on {}", ids.len(), dest);
+    if tui_mode {
+        return CommandResult::Info(msg);
+    }
+    println!("{}", msg);
+    CommandResult::Ok
+}
+
 /// Print help text
 pub fn print_help() {
     println!("Commands:");
     println!("  send <destination> <message>  - Send a message");
+    println!(
+        "    --confirm [--timeout 5s]    - Wait for a broker receipt and show round-trip time"
+    );
+    println!("    --repeat <n>                - Send n messages, expanding templates each time");
+    println!(
+        "    Body templates: {{uuid}} {{now}} {{now_iso}} {{seq}} {{random}} {{random:1-100}}"
+    );
     println!("  sub <destination>             - Subscribe to a destination");
+    println!("  ack <destination>             - Acknowledge messages received (--ack client)");
+    println!("  ping [-n <count>] [destination] - Measure broker round-trip latency");
+    println!("  bookmark [<name> <destination>] - List or save bookmarks; use as @name");
+    println!("    --delete <name>             - Delete a bookmark");
+    println!("  info                          - Show broker and connection details");
+    println!("  stats [interval|off]          - Show traffic and health, or print every interval");
     println!("  about                         - Show copyright and license");
     println!("  summary [file]                - Print session summary (or save to file)");
     println!(
         "  report [file]                 - Full report with message history (or save to file)"
     );
+    println!("  export <file>                 - Write the messages shown to a file");
     println!("  clear                         - Clear message history");
     println!("  quit                          - Exit");
 }
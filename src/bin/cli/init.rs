@@ -0,0 +1,87 @@
+//! Startup commands run by `--init <file>` once connected.
+//!
+//! The file holds one shell command per line, as typed at the prompt, so a
+//! team can share its standard debugging setup:
+//!
+//! ```text
+//! # Order flow debugging
+//! bookmark orders /queue/prod.eu-west-1.orders.v2
+//! sub @orders
+//! sub /topic/prod.audit.all
+//! send /queue/debug.marker session started {{now_iso}}
+//! ```
+
+use iridium_stomp::Connection;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+use super::commands::{CommandResult, execute_command};
+use super::state::SharedState;
+
+/// Commands loaded from an init file
+#[derive(Debug)]
+pub struct InitFile {
+    /// Where the commands came from, for error messages
+    path: PathBuf,
+    /// `(line number, command)` pairs in file order
+    commands: Vec<(usize, String)>,
+}
+
+impl InitFile {
+    /// Read the commands in `path`. Blank lines and lines starting with
+    /// `#` are skipped; `quit` is refused, since ending the session is up
+    /// to `--duration` and `--message-limit`.
+    pub fn load(path: &Path) -> Result<InitFile, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read init file {}: {}", path.display(), e))?;
+        let mut commands = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command = line.split(' ').next().unwrap_or("");
+            if matches!(command, "quit" | "exit" | "q") {
+                return Err(format!(
+                    "{}: line {}: '{}' is not allowed in an init file",
+                    path.display(),
+                    lineno + 1,
+                    command
+                ));
+            }
+            commands.push((lineno + 1, line.to_string()));
+        }
+        Ok(InitFile {
+            path: path.to_path_buf(),
+            commands,
+        })
+    }
+
+    /// Run the commands in order, as if typed at the prompt. Output is
+    /// shown the same way; the first command that fails stops the run with
+    /// its line number.
+    pub async fn run(
+        &self,
+        conn: &Connection,
+        state: SharedState,
+        sub_tx: &mpsc::Sender<String>,
+        tui_mode: bool,
+    ) -> Result<(), String> {
+        for (lineno, line) in &self.commands {
+            match execute_command(line, conn, state.clone(), sub_tx, tui_mode).await {
+                CommandResult::Ok | CommandResult::Quit => {}
+                CommandResult::Info(msg) => {
+                    if tui_mode {
+                        state.lock().await.record_message("INFO", msg, vec![]);
+                    } else {
+                        println!("{}", msg);
+                    }
+                }
+                CommandResult::Error(msg) => {
+                    return Err(format!("{}: line {}: {}", self.path.display(), lineno, msg));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Remappable TUI key bindings.
+//!
+//! Bindings are read from a small config file with one `action = keys`
+//! line per remapped action, for example:
+//!
+//! ```text
+//! # Ctrl+Q is swallowed by some terminals
+//! quit = ctrl+x
+//! toggle_headers = f2, alt+h
+//! ```
+//!
+//! Actions not mentioned keep their default keys.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::{Path, PathBuf};
+
+/// A remappable TUI action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleHeaders,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ErrorScrollUp,
+    ErrorScrollDown,
+    SwitchPane,
+    TogglePause,
+    Bookmarks,
+    Help,
+}
+
+impl Action {
+    /// All actions, in help overlay order
+    pub const ALL: [Action; 12] = [
+        Action::Quit,
+        Action::ToggleHeaders,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::ErrorScrollUp,
+        Action::ErrorScrollDown,
+        Action::SwitchPane,
+        Action::TogglePause,
+        Action::Bookmarks,
+        Action::Help,
+    ];
+
+    /// Name used in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::ToggleHeaders => "toggle_headers",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::ErrorScrollUp => "error_scroll_up",
+            Action::ErrorScrollDown => "error_scroll_down",
+            Action::SwitchPane => "switch_pane",
+            Action::TogglePause => "toggle_pause",
+            Action::Bookmarks => "bookmarks",
+            Action::Help => "help",
+        }
+    }
+
+    /// Description shown in the help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::ToggleHeaders => "Toggle message header display",
+            Action::ScrollUp => "Scroll focused pane up",
+            Action::ScrollDown => "Scroll focused pane down",
+            Action::PageUp => "Scroll focused pane up 10 lines",
+            Action::PageDown => "Scroll focused pane down 10 lines",
+            Action::ErrorScrollUp => "Scroll errors up",
+            Action::ErrorScrollDown => "Scroll errors down",
+            Action::SwitchPane => "Switch focus between messages and errors",
+            Action::TogglePause => "Pause or resume the message pane",
+            Action::Bookmarks => "Pick a bookmarked destination",
+            Action::Help => "Show this help",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        let ctrl = |code| Key {
+            code,
+            modifiers: KeyModifiers::CONTROL,
+        };
+        let plain = |code| Key {
+            code,
+            modifiers: KeyModifiers::NONE,
+        };
+        match self {
+            Action::Quit => vec![ctrl(KeyCode::Char('q'))],
+            Action::ToggleHeaders => vec![ctrl(KeyCode::Char('h'))],
+            Action::ScrollUp => vec![ctrl(KeyCode::Up)],
+            Action::ScrollDown => vec![ctrl(KeyCode::Down)],
+            Action::PageUp => vec![plain(KeyCode::PageUp)],
+            Action::PageDown => vec![plain(KeyCode::PageDown)],
+            Action::ErrorScrollUp => vec![ctrl(KeyCode::Char('e'))],
+            Action::ErrorScrollDown => vec![ctrl(KeyCode::Char('d'))],
+            Action::SwitchPane => vec![plain(KeyCode::Tab)],
+            Action::TogglePause => vec![plain(KeyCode::Char(' '))],
+            Action::Bookmarks => vec![ctrl(KeyCode::Char('b'))],
+            Action::Help => vec![plain(KeyCode::F(1))],
+        }
+    }
+}
+
+/// A key with modifiers, e.g. `ctrl+q`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parse a key like `ctrl+q`, `alt+shift+up`, `f2`, or `tab`
+    fn parse(text: &str) -> Result<Key, String> {
+        let text = text.trim().to_ascii_lowercase();
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let name = parts.pop().filter(|n| !n.is_empty());
+        let Some(name) = name else {
+            return Err(format!("empty key in '{}'", text));
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for m in parts {
+            modifiers |= match m {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => return Err(format!("unknown modifier '{}'", other)),
+            };
+        }
+
+        let code = match name {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            f if f.len() > 1 && f.starts_with('f') => f[1..]
+                .parse::<u8>()
+                .ok()
+                .filter(|n| (1..=24).contains(n))
+                .map(KeyCode::F)
+                .ok_or_else(|| format!("unknown key '{}'", name))?,
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap_or_default()),
+            other => return Err(format!("unknown key '{}'", other)),
+        };
+        Ok(Key { code, modifiers })
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        // Terminals report Shift on uppercase letters, symbols and BackTab
+        // inconsistently, so it is ignored for those keys
+        let relevant = if matches!(event.code, KeyCode::Char(_) | KeyCode::BackTab) {
+            KeyModifiers::CONTROL | KeyModifiers::ALT
+        } else {
+            KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT
+        };
+        let code = match event.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            other => other,
+        };
+        code == self.code && (event.modifiers & relevant) == (self.modifiers & relevant)
+    }
+
+    /// Human-readable form, e.g. `Ctrl+Q`
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            label.push_str("Shift+");
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("Space"),
+            KeyCode::Char(c) => label.push(c.to_ascii_uppercase()),
+            KeyCode::F(n) => label.push_str(&format!("F{}", n)),
+            KeyCode::PageUp => label.push_str("Page Up"),
+            KeyCode::PageDown => label.push_str("Page Down"),
+            other => label.push_str(&format!("{:?}", other)),
+        }
+        label
+    }
+}
+
+/// Key bindings for the TUI
+pub struct KeyMap {
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .into_iter()
+                .map(|a| (a, a.default_keys()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Load bindings from `path` if given, otherwise from the default
+    /// location if that file exists
+    pub fn load(path: Option<&Path>) -> Result<KeyMap, String> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => match default_path().filter(|p| p.exists()) {
+                Some(p) => p,
+                None => return Ok(KeyMap::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read keymap {}: {}", path.display(), e))?;
+        KeyMap::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse a keymap config, starting from the defaults
+    fn parse(text: &str) -> Result<KeyMap, String> {
+        let mut map = KeyMap::default();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'action = keys'", lineno + 1))?;
+            let action = Action::from_name(name.trim())
+                .ok_or_else(|| format!("line {}: unknown action '{}'", lineno + 1, name.trim()))?;
+            let keys = keys
+                .split(',')
+                .map(Key::parse)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {}", lineno + 1, e))?;
+            if let Some((_, bound)) = map.bindings.iter_mut().find(|(a, _)| *a == action) {
+                *bound = keys;
+            }
+        }
+        Ok(map)
+    }
+
+    /// The action bound to `event`, if any
+    pub fn action_for(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k.matches(event)))
+            .map(|(action, _)| *action)
+    }
+
+    /// Label of the first key bound to `action`, for UI hints
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .and_then(|(_, keys)| keys.first())
+            .map(Key::label)
+            .unwrap_or_else(|| "unbound".to_string())
+    }
+
+    /// `(keys, description)` rows for the help overlay
+    pub fn help_rows(&self) -> Vec<(String, &'static str)> {
+        self.bindings
+            .iter()
+            .map(|(action, keys)| {
+                let labels: Vec<String> = keys.iter().map(Key::label).collect();
+                (labels.join(" / "), action.description())
+            })
+            .collect()
+    }
+}
+
+/// `keys.conf` in the iridium-stomp config directory
+pub fn default_path() -> Option<PathBuf> {
+    Some(super::config_dir()?.join("keys.conf"))
+}
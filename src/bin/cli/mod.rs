@@ -1,9 +1,29 @@
 pub mod args;
+pub mod bookmarks;
 pub mod commands;
+pub mod init;
+pub mod keymap;
+pub mod ping;
+pub mod pipe;
 pub mod plain;
+pub mod search;
+pub mod shutdown;
 pub mod state;
+pub mod stats;
+pub mod template;
 pub mod tui;
 
+use std::path::PathBuf;
+
+/// `$XDG_CONFIG_HOME/iridium-stomp`, falling back to
+/// `~/.config/iridium-stomp`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("iridium-stomp"))
+}
+
 /// Exit codes for different error conditions
 pub mod exit_codes {
     /// Successful execution
@@ -14,4 +34,6 @@ pub mod exit_codes {
     pub const AUTH_ERROR: u8 = 2;
     /// Protocol error (e.g., unexpected server response)
     pub const PROTOCOL_ERROR: u8 = 3;
+    /// `--message-limit` was not reached within `--duration`
+    pub const LIMIT_NOT_REACHED: u8 = 4;
 }
@@ -0,0 +1,204 @@
+use iridium_stomp::connection::AckMode;
+use iridium_stomp::{Connection, Frame};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::args::{Cli, PingArgs};
+use super::exit_codes;
+use super::shutdown::disconnect;
+
+/// Header carrying the probe number, used to match replies to probes
+const PING_SEQ_HEADER: &str = "ping-seq";
+
+/// Settings for a latency probe run
+#[derive(Debug, Clone, Copy)]
+pub struct PingOptions {
+    /// Number of probes to send
+    pub count: u32,
+    /// Pause between probes
+    pub interval: Duration,
+    /// How long to wait for each probe to come back
+    pub timeout: Duration,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            count: 10,
+            interval: Duration::from_millis(200),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Outcome of a latency probe run
+pub struct PingReport {
+    /// Destination the probes were published to
+    pub destination: String,
+    /// Number of probes sent
+    pub sent: u32,
+    /// Round-trip time of each probe that came back, in send order
+    pub samples: Vec<Duration>,
+}
+
+impl PingReport {
+    /// Number of probes that did not come back in time
+    pub fn lost(&self) -> u32 {
+        self.sent - self.samples.len() as u32
+    }
+
+    /// Summary in the style of `ping`: counts, then min/avg/p95/max
+    pub fn summary_lines(&self) -> Vec<String> {
+        let loss = (self.lost() * 100).checked_div(self.sent).unwrap_or(0);
+        let mut lines = vec![format!(
+            "{} sent, {} received, {}% lost",
+            self.sent,
+            self.samples.len(),
+            loss
+        )];
+        if !self.samples.is_empty() {
+            let mut sorted = self.samples.clone();
+            sorted.sort();
+            let avg = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+            // Nearest-rank percentile
+            let p95 = sorted[(sorted.len() * 95).div_ceil(100) - 1];
+            lines.push(format!(
+                "min/avg/p95/max = {}/{}/{}/{} ms",
+                format_ms(sorted[0]),
+                format_ms(avg),
+                format_ms(p95),
+                format_ms(sorted[sorted.len() - 1])
+            ));
+        }
+        lines
+    }
+}
+
+/// Format a duration as milliseconds with two decimals
+pub fn format_ms(d: Duration) -> String {
+    format!("{:.2}", d.as_secs_f64() * 1000.0)
+}
+
+/// A destination nobody else should be using, for probes when the user
+/// does not name one
+pub fn unique_destination() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("/queue/iridium-ping-{}-{}", std::process::id(), millis)
+}
+
+/// Measure broker round-trip latency.
+///
+/// Subscribes to `destination`, publishes `options.count` probes one at a
+/// time, and times how long each takes to come back. `on_reply` is called
+/// after each probe with its number and round-trip time (`None` if it timed
+/// out). The subscription is removed afterwards.
+pub async fn ping(
+    conn: &Connection,
+    destination: &str,
+    options: PingOptions,
+    mut on_reply: impl FnMut(u32, Option<Duration>),
+) -> Result<PingReport, String> {
+    let mut sub = conn
+        .subscribe(destination, AckMode::Auto)
+        .await
+        .map_err(|e| format!("Subscribe error: {}", e))?;
+
+    let mut report = PingReport {
+        destination: destination.to_string(),
+        sent: 0,
+        samples: Vec::new(),
+    };
+    for seq in 1..=options.count {
+        if seq > 1 {
+            tokio::time::sleep(options.interval).await;
+        }
+        let sent_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let probe = Frame::new("SEND")
+            .header("destination", destination)
+            .header("content-type", "text/plain")
+            .header(PING_SEQ_HEADER, seq.to_string())
+            .set_body(sent_at.to_string().into_bytes());
+
+        let started = Instant::now();
+        if let Err(e) = conn.send_frame(probe).await {
+            let _ = sub.unsubscribe().await;
+            return Err(format!("Send error: {}", e));
+        }
+        report.sent += 1;
+
+        // Skip late replies to earlier probes
+        let deadline = started + options.timeout;
+        let mut rtt = None;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            let Ok(frame) = sub.recv_timeout(remaining).await else {
+                break;
+            };
+            if frame.get_header(PING_SEQ_HEADER) == Some(seq.to_string().as_str()) {
+                rtt = Some(started.elapsed());
+                break;
+            }
+        }
+        if let Some(rtt) = rtt {
+            report.samples.push(rtt);
+        }
+        on_reply(seq, rtt);
+    }
+
+    let _ = sub.unsubscribe().await;
+    Ok(report)
+}
+
+/// Run `stomp ping`: connect, probe, print the statistics, and disconnect
+pub async fn run(cli: &Cli, args: &PingArgs) -> Result<(), (String, u8)> {
+    let conn = Connection::connect_with_options(
+        &cli.broker_address(),
+        &cli.login,
+        &cli.passcode,
+        &cli.heartbeat,
+        cli.connect_options()?,
+    )
+    .await
+    .map_err(|e| super::plain::format_connection_error_pub(&e, &cli.address))?;
+
+    let destination = args.destination.clone().unwrap_or_else(unique_destination);
+    let options = PingOptions {
+        count: args.count,
+        interval: args.interval,
+        timeout: args.timeout,
+    };
+    let quiet = cli.quiet;
+    if !quiet {
+        println!(
+            "PING {} via {}: {} probes",
+            destination, cli.address, options.count
+        );
+    }
+    let result = ping(&conn, &destination, options, |seq, rtt| {
+        if quiet {
+            return;
+        }
+        match rtt {
+            Some(rtt) => println!("probe {}: {} ms", seq, format_ms(rtt)),
+            None => println!("probe {}: timed out", seq),
+        }
+    })
+    .await;
+    disconnect(conn).await;
+
+    let report = result.map_err(|e| (e, exit_codes::PROTOCOL_ERROR))?;
+    if !quiet {
+        println!("--- {} ping statistics ---", report.destination);
+    }
+    for line in report.summary_lines() {
+        println!("{}", line);
+    }
+    if report.samples.is_empty() {
+        return Err(("No probes came back".to_string(), exit_codes::NETWORK_ERROR));
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use iridium_stomp::{Connection, Frame, ReceivedFrame};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use super::args::Cli;
+use super::bookmarks::Bookmarks;
+use super::exit_codes;
+use super::shutdown::shutdown_signal;
+
+/// How long to wait at the end of input for the queued frames to be
+/// written and the DISCONNECT confirmed
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+const LINE_FORMAT: &str = "expected destination<TAB>body or a JSON object";
+
+/// One parsed line of pipe input
+struct PipeMessage {
+    destination: String,
+    body: String,
+    headers: Vec<(String, String)>,
+}
+
+/// Build the SEND frame for one line of pipe input.
+///
+/// Lines starting with `{` are JSON objects with a `destination`, a `body`
+/// and optional `headers`; any other line is `destination<TAB>body`. The
+/// destination may be a `@bookmark`.
+pub fn parse_line(line: &str, bookmarks: &Bookmarks) -> Result<Frame, String> {
+    let PipeMessage {
+        destination,
+        body,
+        mut headers,
+    } = if line.trim_start().starts_with('{') {
+        parse_json(line)?
+    } else {
+        let (destination, body) = line.split_once('\t').ok_or(LINE_FORMAT)?;
+        PipeMessage {
+            destination: destination.to_string(),
+            body: body.to_string(),
+            headers: Vec::new(),
+        }
+    };
+    let destination = bookmarks.expand(destination.trim())?;
+    if destination.is_empty() {
+        return Err("empty destination".to_string());
+    }
+    if !headers.iter().any(|(name, _)| name == "content-type") {
+        headers.push(("content-type".to_string(), "text/plain".to_string()));
+    }
+    let mut frame = Frame::new("SEND").header("destination", destination);
+    for (name, value) in headers {
+        frame = frame.header(&name, &value);
+    }
+    Ok(frame.set_body(body.into_bytes()))
+}
+
+/// Split a JSON line into destination, body and headers. A body that is
+/// not a string is sent as compact JSON with `content-type:
+/// application/json`, unless the headers set another content type.
+fn parse_json(line: &str) -> Result<PipeMessage, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+    let Value::Object(mut object) = value else {
+        return Err(LINE_FORMAT.to_string());
+    };
+    let destination = match object.remove("destination") {
+        Some(Value::String(destination)) => destination,
+        _ => return Err("missing \"destination\" string".to_string()),
+    };
+    let mut headers = Vec::new();
+    match object.remove("headers") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(map)) => {
+            for (name, value) in map {
+                let value = match value {
+                    Value::String(s) => s,
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => {
+                        return Err(format!(
+                            "header '{}' must be a string, number or bool",
+                            name
+                        ));
+                    }
+                };
+                headers.push((name, value));
+            }
+        }
+        Some(_) => return Err("\"headers\" must be an object".to_string()),
+    }
+    let body = match object.remove("body") {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(body)) => body,
+        Some(body) => {
+            if !headers.iter().any(|(name, _)| name == "content-type") {
+                headers.push(("content-type".to_string(), "application/json".to_string()));
+            }
+            body.to_string()
+        }
+    };
+    if let Some(field) = object.keys().next() {
+        return Err(format!("unknown field \"{}\"", field));
+    }
+    Ok(PipeMessage {
+        destination,
+        body,
+        headers,
+    })
+}
+
+/// Run pipe mode: send one message per stdin line until end of input,
+/// printing nothing but errors
+pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
+    let bookmarks = Bookmarks::load(cli.bookmarks.as_deref()).map_err(|e| (e, 1))?;
+    let conn = Connection::connect_with_options(
+        &cli.broker_address(),
+        &cli.login,
+        &cli.passcode,
+        &cli.heartbeat,
+        cli.connect_options()?,
+    )
+    .await
+    .map_err(|e| super::plain::format_connection_error_pub(&e, &cli.address))?;
+
+    // Broker errors, such as a rejected destination, are the only output
+    let conn_err = conn.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = conn_err.next_frame().await {
+            if let ReceivedFrame::Error(err) = frame {
+                eprintln!("[BROKER ERROR] {}", err.message);
+            }
+        }
+    });
+
+    // SIGINT/SIGTERM stop reading; what was read is still sent
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut line_no = 0u64;
+    let mut skipped = 0u64;
+    let mut failure = None;
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            _ = &mut shutdown => break,
+        };
+        let line = match line {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                failure = Some((format!("Failed to read stdin: {}", e), 1));
+                break;
+            }
+        };
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = match parse_line(&line, &bookmarks) {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("line {}: {}", line_no, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        if let Err(e) = conn.send_frame(frame).await {
+            failure = Some((
+                format!("line {}: send error: {}", line_no, e),
+                exit_codes::NETWORK_ERROR,
+            ));
+            break;
+        }
+    }
+
+    let closed = conn.close_after_flush(FLUSH_TIMEOUT).await;
+    if let Some(failure) = failure {
+        return Err(failure);
+    }
+    closed.map_err(|e| super::plain::format_connection_error_pub(&e, &cli.address))?;
+    if skipped > 0 {
+        return Err((format!("{} line(s) skipped", skipped), 1));
+    }
+    Ok(())
+}
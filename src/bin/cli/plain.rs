@@ -1,15 +1,34 @@
-use iridium_stomp::connection::{AckMode, ConnError};
-use iridium_stomp::{ConnectOptions, Connection, Frame};
+use iridium_stomp::connection::ConnError;
+use iridium_stomp::{Connection, ConnectionEvent, Frame};
 use std::io::{self, BufRead, Write};
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use tokio::sync::{Notify, mpsc};
+use tokio::time::Instant;
 
 use super::args::Cli;
-use super::commands::{CommandResult, execute_command, print_help};
-use super::state::{SharedState, new_shared_state};
+use super::bookmarks::Bookmarks;
+use super::commands::{
+    CommandResult, describe_broker_error, event_notice, event_warning, execute_command, print_help,
+};
+use super::init::InitFile;
+use super::shutdown::{disconnect, shutdown_signal};
+use super::state::{BODY_PREVIEW_LEN, SharedState, Verbosity, new_recorder, new_shared_state};
 
 /// Run the CLI in plain (non-TUI) mode
 pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
-    println!("Connecting to {}...", cli.address);
+    let verbosity = cli.verbosity();
+    let quiet = verbosity == Verbosity::Quiet;
+    // A broken bookmark or init file fails before connecting
+    let bookmarks = Bookmarks::load(cli.bookmarks.as_deref()).map_err(|e| (e, 1))?;
+    let init = cli
+        .init
+        .as_deref()
+        .map(InitFile::load)
+        .transpose()
+        .map_err(|e| (e, 1))?;
+    if !quiet {
+        println!("Connecting to {}...", cli.address);
+    }
 
     // Parse heartbeat to get interval for state
     let hb_parts: Vec<&str> = cli.heartbeat.split(',').collect();
@@ -21,11 +40,19 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
     // Create heartbeat notification channel
     let (hb_tx, mut hb_rx) = mpsc::channel::<()>(16);
 
+    // Create connection event channel (slow consumer warnings)
+    let (event_tx, mut event_rx) = mpsc::channel::<ConnectionEvent>(16);
+
     // Build connection options
-    let options = ConnectOptions::default().with_heartbeat_notify(hb_tx);
+    let recorder = new_recorder();
+    let options = cli
+        .connect_options()?
+        .with_heartbeat_notify(hb_tx)
+        .with_event_notify(event_tx)
+        .recorder(recorder.clone());
 
     let conn = Connection::connect_with_options(
-        &cli.address,
+        &cli.broker_address(),
         &cli.login,
         &cli.passcode,
         &cli.heartbeat,
@@ -34,35 +61,81 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
     .await
     .map_err(|e| format_connection_error(&e, &cli.address))?;
 
-    println!("Connected.");
+    if !quiet {
+        println!("Connected.");
+    }
 
     // Create shared state
-    let state = new_shared_state(cli.address.clone(), cli.login.clone(), hb_interval);
+    let state = new_shared_state(
+        cli.address.clone(),
+        cli.login.clone(),
+        hb_interval,
+        recorder,
+    );
+    {
+        let mut s = state.lock().await;
+        s.verbosity = verbosity;
+        s.ack_mode = cli.ack;
+    }
 
     // Channel for new subscription requests
     let (sub_tx, mut sub_rx) = mpsc::channel::<String>(16);
 
+    // Woken on every message, for --message-limit
+    let received = Arc::new(Notify::new());
+
     // Subscribe to requested destinations
     for dest in &cli.subscribe {
-        subscribe_destination(&conn, dest, state.clone()).await?;
+        let dest = bookmarks.expand(dest).map_err(|e| (e, 1))?;
+        subscribe_destination(&conn, dest, state.clone(), received.clone()).await?;
     }
+    state.lock().await.bookmarks = bookmarks;
 
     // Spawn heartbeat monitor task
     let state_hb = state.clone();
     tokio::spawn(async move {
         while hb_rx.recv().await.is_some() {
-            let mut s = state_hb.lock().await;
-            s.record_heartbeat();
+            if verbosity == Verbosity::Verbose {
+                let count = state_hb.lock().await.recorder.stats().heartbeats;
+                println!("\n[HEARTBEAT] #{} received", count);
+                print_prompt(verbosity);
+            }
+        }
+    });
+
+    // Spawn task to report reconnects and connection events worth a warning
+    let state_ev = state.clone();
+    tokio::spawn(async move {
+        while let Some(event) = event_rx.recv().await {
+            // Reconnects are reported even in quiet mode, so an unattended
+            // session shows why it went silent
+            if let Some(notice) = event_notice(&event) {
+                eprintln!("\n[{}]", notice);
+                state_ev.lock().await.record_message("INFO", notice, vec![]);
+                print_prompt(verbosity);
+                continue;
+            }
+            let Some(warning) = event_warning(&event) else {
+                continue;
+            };
+            eprintln!("\n[WARN] {}", warning);
+            state_ev
+                .lock()
+                .await
+                .record_message("WARN", warning, vec![]);
+            print_prompt(verbosity);
         }
     });
 
     // Spawn task to handle new subscription requests
     let conn_sub = conn.clone();
     let state_sub = state.clone();
+    let received_sub = received.clone();
     tokio::spawn(async move {
         while let Some(dest) = sub_rx.recv().await {
-            if let Err((msg, _)) = subscribe_destination(&conn_sub, &dest, state_sub.clone()).await
-            {
+            let subscribed =
+                subscribe_destination(&conn_sub, &dest, state_sub.clone(), received_sub.clone());
+            if let Err((msg, _)) = subscribed.await {
                 eprintln!("{}", msg);
             }
         }
@@ -76,19 +149,14 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
             match conn_err.next_frame().await {
                 Some(iridium_stomp::ReceivedFrame::Error(err)) => {
                     let mut s = state_err.lock().await;
-                    let msg = if let Some(ref body) = err.body {
-                        format!("{}: {}", err.message, body)
-                    } else {
-                        err.message.clone()
-                    };
+                    let msg = describe_broker_error(&mut s, &err);
                     eprintln!("\n[BROKER ERROR] {}", msg);
                     // Print headers for additional context
                     for (k, v) in &err.frame.headers {
                         eprintln!("  {}: {}", k, v);
                     }
                     s.record_message("BROKER ERROR", msg, err.frame.headers.clone());
-                    print!("> ");
-                    let _ = io::stdout().flush();
+                    print_prompt(verbosity);
                 }
                 Some(iridium_stomp::ReceivedFrame::Frame(_)) => {
                     // Other frames are handled by subscription receivers
@@ -98,6 +166,13 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
         }
     });
 
+    if let Some(init) = &init
+        && let Err(e) = init.run(&conn, state.clone(), &sub_tx, false).await
+    {
+        disconnect(conn).await;
+        return Err((e, 1));
+    }
+
     // Channel to receive user commands from stdin reader
     let (cmd_tx, mut cmd_rx) = mpsc::channel::<String>(16);
 
@@ -117,31 +192,70 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
         }
     });
 
-    println!();
-    print_help();
-    println!();
+    if !quiet {
+        println!();
+        print_help();
+        println!();
+    }
+
+    // SIGINT/SIGTERM end the session the same way as `quit`
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    // --duration and --message-limit end the session on their own, so the
+    // end of stdin (as in CI, where it is often empty) does not
+    let deadline = cli.duration.map(|duration| Instant::now() + duration);
+    let mut stdin_open = true;
+    let mut outcome = Ok(());
 
     // Main command loop
     loop {
-        print!("> ");
-        let _ = io::stdout().flush();
+        print_prompt(verbosity);
 
-        let line = match cmd_rx.recv().await {
-            Some(l) => l,
-            None => break,
+        let line = tokio::select! {
+            line = cmd_rx.recv(), if stdin_open => line,
+            _ = &mut shutdown => {
+                if !quiet {
+                    println!();
+                }
+                break;
+            }
+            _ = message_limit_reached(&state, &received, cli.message_limit) => {
+                if !quiet {
+                    println!("\nMessage limit reached.");
+                }
+                break;
+            }
+            _ = sleep_until(deadline) => {
+                if !quiet {
+                    println!("\nDuration elapsed.");
+                }
+                if let Some(limit) = cli.message_limit {
+                    let count = state.lock().await.total_message_count();
+                    outcome = Err((
+                        format!(
+                            "Received {} of {} messages within {:?}",
+                            count,
+                            limit,
+                            cli.duration.unwrap_or_default()
+                        ),
+                        super::exit_codes::LIMIT_NOT_REACHED,
+                    ));
+                }
+                break;
+            }
+        };
+        let Some(line) = line else {
+            if cli.auto_exit() {
+                stdin_open = false;
+                continue;
+            }
+            break;
         };
 
         match execute_command(&line, &conn, state.clone(), &sub_tx, false).await {
             CommandResult::Ok => {}
-            CommandResult::Quit => {
-                println!("Disconnecting...");
-                if cli.summary {
-                    let s = state.lock().await;
-                    println!("{}", s.generate_summary());
-                }
-                conn.close().await;
-                break;
-            }
+            CommandResult::Quit => break,
             CommandResult::Info(msg) => {
                 println!("{}", msg);
             }
@@ -151,7 +265,41 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
         }
     }
 
-    Ok(())
+    if !quiet {
+        println!("Disconnecting...");
+    }
+    if cli.summary || cli.auto_exit() {
+        let pending = conn.pending_acks().await;
+        let mut s = state.lock().await;
+        s.update_pending_acks(&pending);
+        println!("{}", s.generate_summary());
+    }
+    disconnect(conn).await;
+
+    outcome
+}
+
+/// Wait until `limit` messages have been received across all
+/// subscriptions, or forever without a limit
+async fn message_limit_reached(state: &SharedState, received: &Notify, limit: Option<u64>) {
+    let Some(limit) = limit else {
+        return std::future::pending().await;
+    };
+    loop {
+        let notified = received.notified();
+        if state.lock().await.total_message_count() >= limit {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Wait until `deadline`, or forever without one
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Subscribe to a destination and spawn a message handler task
@@ -159,20 +307,24 @@ async fn subscribe_destination(
     conn: &Connection,
     dest: &str,
     state: SharedState,
+    received: Arc<Notify>,
 ) -> Result<(), (String, u8)> {
-    let sub = conn.subscribe(dest, AckMode::Auto).await.map_err(|e| {
+    let ack_mode = state.lock().await.ack_mode;
+    let sub = conn.subscribe(dest, ack_mode).await.map_err(|e| {
         (
             format!("Failed to subscribe to '{}': {}", dest, e),
             super::exit_codes::PROTOCOL_ERROR,
         )
     })?;
 
-    println!("Subscribed to: {}", dest);
-
     // Register in state
-    {
+    let verbosity = {
         let mut s = state.lock().await;
-        s.register_subscription(dest);
+        s.register_subscription(dest, sub.id());
+        s.verbosity
+    };
+    if verbosity != Verbosity::Quiet {
+        println!("Subscribed to: {}", dest);
     }
 
     // Spawn a task to print incoming messages for this subscription
@@ -182,6 +334,7 @@ async fn subscribe_destination(
     tokio::spawn(async move {
         while let Some(frame) = rx.recv().await {
             handle_message(&dest_clone, &frame, state_clone.clone()).await;
+            received.notify_waiters();
         }
     });
 
@@ -190,35 +343,127 @@ async fn subscribe_destination(
 
 /// Handle an incoming message
 async fn handle_message(dest: &str, frame: &Frame, state: SharedState) {
-    // Extract body
-    let body = if frame.body.is_empty() {
-        String::new()
-    } else {
-        match std::str::from_utf8(&frame.body) {
-            Ok(s) => s.to_string(),
-            Err(_) => format!("({} bytes, binary)", frame.body.len()),
-        }
-    };
+    let body = frame.body_preview(BODY_PREVIEW_LEN);
 
     // Record in state
-    {
+    let verbosity = {
         let mut s = state.lock().await;
         s.record_message(dest, body.clone(), frame.headers.clone());
+        s.verbosity
+    };
+
+    // Quiet mode writes the raw body only, one message per line
+    if verbosity == Verbosity::Quiet {
+        let mut out = io::stdout().lock();
+        let _ = out.write_all(&frame.body);
+        let _ = out.write_all(b"\n");
+        let _ = out.flush();
+        return;
     }
 
     // Print to console
-    println!("\n[{}] MESSAGE received:", dest);
+    if verbosity == Verbosity::Verbose {
+        println!(
+            "\n[{}] MESSAGE received ({} bytes on the wire):",
+            dest,
+            frame.encoded_len()
+        );
+    } else {
+        println!("\n[{}] MESSAGE received:", dest);
+    }
     for (k, v) in &frame.headers {
         println!("  {}: {}", k, v);
     }
-    if !frame.body.is_empty() {
-        match std::str::from_utf8(&frame.body) {
-            Ok(s) => println!("  Body: {}", s),
-            Err(_) => println!("  Body: ({} bytes, binary)", frame.body.len()),
+    // Multi-line bodies (including hex dumps and pretty-printed JSON) are
+    // indented under "Body:"
+    let body = display_body(frame, body);
+    let mut body_lines = body.lines();
+    if let Some(first) = body_lines.next() {
+        println!("  Body: {}", first);
+        for line in body_lines {
+            println!("        {}", line);
         }
     }
-    print!("> ");
-    let _ = io::stdout().flush();
+    print_prompt(verbosity);
+}
+
+/// Body text to print: complete JSON bodies, declared by `content-type` or
+/// sniffed from the body, are pretty-printed; anything else is the preview
+fn display_body(frame: &Frame, preview: String) -> String {
+    let is_json = frame.sniff_content_type().is_some_and(|ct| {
+        let media_type = ct.split(';').next().unwrap_or("").trim();
+        media_type.eq_ignore_ascii_case("application/json") || media_type.ends_with("+json")
+    });
+    if is_json && preview.len() == frame.body.len() {
+        pretty_json(&preview)
+    } else {
+        preview
+    }
+}
+
+/// Re-indent JSON text two spaces per level, keeping key order and string
+/// contents as they are. Malformed JSON is re-indented as far as it goes.
+fn pretty_json(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+    let mut chars = text.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                // Empty objects and arrays stay on one line
+                if let Some(close) = chars.next_if(|&c| c == '}' || c == ']') {
+                    out.push(close);
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Print the input prompt unless running quietly
+pub fn print_prompt(verbosity: Verbosity) {
+    if verbosity != Verbosity::Quiet {
+        print!("> ");
+        let _ = io::stdout().flush();
+    }
 }
 
 /// Format a connection error with user-friendly messaging (internal)
@@ -237,6 +482,18 @@ pub fn format_connection_error_pub(err: &ConnError, address: &str) -> (String, u
                 std::io::ErrorKind::TimedOut => {
                     format!("Connection timed out: {}", address)
                 }
+                // A TLS handshake rejected by either side
+                std::io::ErrorKind::InvalidData => {
+                    let hint = if io_err.to_string().contains("certificate") {
+                        "trust the broker's CA with --ca-cert, or use --insecure for a development broker"
+                    } else {
+                        "is the broker listening for TLS on this port?"
+                    };
+                    format!(
+                        "TLS handshake with {} failed: {} ({})",
+                        address, io_err, hint
+                    )
+                }
                 _ => {
                     format!("Connection failed: {}", io_err)
                 }
@@ -254,9 +511,60 @@ pub fn format_connection_error_pub(err: &ConnError, address: &str) -> (String, u
             format!("Protocol error: {}", msg),
             super::exit_codes::PROTOCOL_ERROR,
         ),
+        ConnError::HandshakeTimeout(timeout) => (
+            format!(
+                "Connection timed out: {} did not answer CONNECT within {:?}",
+                address, timeout
+            ),
+            super::exit_codes::NETWORK_ERROR,
+        ),
+        ConnError::Decode { source } => (
+            format!("Protocol error: invalid data from broker: {}", source),
+            super::exit_codes::PROTOCOL_ERROR,
+        ),
+        ConnError::SubscriptionNotFound(id) => (
+            format!("Subscription not found: {}", id),
+            super::exit_codes::PROTOCOL_ERROR,
+        ),
+        ConnError::ChannelClosed => (
+            "Connection closed".to_string(),
+            super::exit_codes::NETWORK_ERROR,
+        ),
         ConnError::ReceiptTimeout(id) => (
             format!("Receipt timeout: {}", id),
             super::exit_codes::PROTOCOL_ERROR,
         ),
+        ConnError::CloseTimeout(timeout) => (
+            format!(
+                "Close timeout: {} did not confirm DISCONNECT within {:?}",
+                address, timeout
+            ),
+            super::exit_codes::NETWORK_ERROR,
+        ),
+        ConnError::VersionMismatch { accepted, server } => (
+            format!(
+                "Unsupported STOMP version: server speaks {}, client accepts {}",
+                server, accepted
+            ),
+            super::exit_codes::PROTOCOL_ERROR,
+        ),
+        ConnError::Config(config_err) => (
+            format!("Invalid configuration: {}", config_err),
+            super::exit_codes::PROTOCOL_ERROR,
+        ),
+        ConnError::Receipt(receipt_err) => (
+            format!("Receipt failed: {}", receipt_err),
+            super::exit_codes::NETWORK_ERROR,
+        ),
+        ConnError::MessageInvalidated(id) => (
+            format!("Message {} invalidated by a server error", id),
+            super::exit_codes::NETWORK_ERROR,
+        ),
+        // `ConnError` is non-exhaustive; variants added later fall back to
+        // their `Display` text.
+        other => (
+            format!("Error: {}", other),
+            super::exit_codes::NETWORK_ERROR,
+        ),
     }
 }
@@ -0,0 +1,146 @@
+//! `/` search over the message pane.
+//!
+//! `/order-42` finds messages containing `order-42` (ignoring ASCII case)
+//! in their destination, body, or headers; `/re:order-\d+` takes a regular
+//! expression instead.
+
+use regex::Regex;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use super::state::DisplayMessage;
+
+/// Prefix that makes a query a regular expression
+const REGEX_PREFIX: &str = "re:";
+
+/// How a query matches text
+#[derive(Debug)]
+enum Pattern {
+    /// Substring, stored lowercased
+    Text(String),
+    Regex(Regex),
+}
+
+/// An active search and its matches in the visible messages
+#[derive(Debug)]
+pub struct Search {
+    /// The query as typed, without the leading `/`
+    pub query: String,
+    pattern: Pattern,
+    /// Indices of matching messages, oldest first
+    matches: Vec<usize>,
+    /// Position in `matches` of the current match, once one is shown
+    current: Option<usize>,
+}
+
+impl Search {
+    /// Parse a query; `re:` makes the rest a regular expression
+    pub fn new(query: &str) -> Result<Search, String> {
+        let pattern = match query.strip_prefix(REGEX_PREFIX) {
+            Some(re) => {
+                Pattern::Regex(Regex::new(re).map_err(|e| format!("Invalid search regex: {}", e))?)
+            }
+            None => Pattern::Text(query.to_ascii_lowercase()),
+        };
+        Ok(Search {
+            query: query.to_string(),
+            pattern,
+            matches: Vec::new(),
+            current: None,
+        })
+    }
+
+    /// Byte ranges of the matches in `text`, for highlighting
+    pub fn ranges(&self, text: &str) -> Vec<Range<usize>> {
+        match &self.pattern {
+            Pattern::Text(needle) if needle.is_empty() => Vec::new(),
+            // ASCII lowercasing keeps byte offsets the same
+            Pattern::Text(needle) => text
+                .to_ascii_lowercase()
+                .match_indices(needle.as_str())
+                .map(|(start, m)| start..start + m.len())
+                .collect(),
+            Pattern::Regex(re) => re
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+        }
+    }
+
+    fn is_match(&self, msg: &DisplayMessage) -> bool {
+        let hit = |text: &str| !self.ranges(text).is_empty();
+        hit(&msg.destination)
+            || hit(&msg.body)
+            || msg
+                .headers
+                .iter()
+                .any(|(k, v)| hit(&format!("{}: {}", k, v)))
+    }
+
+    /// Find the matches in `messages` again, e.g. after pausing swapped
+    /// the pane to a snapshot, and forget the current match
+    pub fn rescan(&mut self, messages: &VecDeque<DisplayMessage>) {
+        self.matches = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| self.is_match(msg))
+            .map(|(i, _)| i)
+            .collect();
+        self.current = None;
+    }
+
+    /// Keep the matches in step with the live buffer after `msg` was
+    /// appended at `index` and `dropped` messages fell off its front
+    pub fn pushed(&mut self, msg: &DisplayMessage, index: usize, dropped: usize) {
+        if dropped > 0 {
+            let gone = self.matches.iter().take_while(|&&i| i < dropped).count();
+            self.matches.drain(..gone);
+            for i in &mut self.matches {
+                *i -= dropped;
+            }
+            self.current = match self.current {
+                Some(c) if c >= gone => Some(c - gone),
+                _ => None,
+            };
+        }
+        if self.is_match(msg) {
+            self.matches.push(index);
+        }
+    }
+
+    /// Move to the next older match (`back`) or the next newer one,
+    /// wrapping around, and return its message index. The first move
+    /// goes to the newest match.
+    pub fn step(&mut self, back: bool) -> Option<usize> {
+        let last = self.matches.len().checked_sub(1)?;
+        let next = match self.current {
+            None => last,
+            Some(0) if back => last,
+            Some(c) if back => c - 1,
+            Some(c) if c >= last => 0,
+            Some(c) => c + 1,
+        };
+        self.current = Some(next);
+        Some(self.matches[next])
+    }
+
+    /// Message index of the current match
+    pub fn current_message(&self) -> Option<usize> {
+        self.current.map(|c| self.matches[c])
+    }
+
+    /// Whether the message at `index` matches
+    pub fn matches(&self, index: usize) -> bool {
+        self.matches.binary_search(&index).is_ok()
+    }
+
+    /// Counter for the message pane title, e.g. `match 3/17`
+    pub fn counter(&self) -> String {
+        match (self.current, self.matches.len()) {
+            (_, 0) => "no matches".to_string(),
+            (Some(c), total) => format!("match {}/{}", c + 1, total),
+            (None, total) => format!("{} matches", total),
+        }
+    }
+}
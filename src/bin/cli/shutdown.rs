@@ -0,0 +1,39 @@
+use iridium_stomp::{Connection, Frame};
+use std::time::Duration;
+
+/// How long to wait for the broker to confirm DISCONNECT before closing
+/// the connection anyway
+const DISCONNECT_RECEIPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Resolve when the process is asked to stop: SIGINT (Ctrl-C) or SIGTERM
+/// on Unix, Ctrl-C elsewhere
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Send DISCONNECT, wait briefly for the broker's receipt so everything
+/// sent before it is known to have been processed, then close
+pub async fn disconnect(conn: Connection) {
+    let _ = conn
+        .send_frame_confirmed(Frame::new("DISCONNECT"), DISCONNECT_RECEIPT_TIMEOUT)
+        .await;
+    conn.close().await;
+}
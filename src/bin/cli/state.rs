@@ -1,8 +1,13 @@
 use chrono::{DateTime, Local};
+use iridium_stomp::{AckMode, PendingAcks, ReportFormat, SessionRecorder, SessionReport};
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, oneshot};
+
+use super::bookmarks::Bookmarks;
+use super::search::Search;
+use super::template::TemplateVars;
 
 /// Maximum number of messages to keep in the ring buffer for display
 pub const MAX_MESSAGES: usize = 1000;
@@ -10,11 +15,47 @@ pub const MAX_MESSAGES: usize = 1000;
 /// Maximum number of errors to keep in the ring buffer for display
 pub const MAX_ERRORS: usize = 100;
 
+/// Maximum number of body bytes kept for display (text or hex dump)
+pub const BODY_PREVIEW_LEN: usize = 2048;
+
+/// How much plain mode prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Message bodies only
+    Quiet,
+    /// Messages with headers, prompts, and status lines
+    #[default]
+    Normal,
+    /// Normal output plus heartbeats, receipts, and frame sizes
+    Verbose,
+}
+
 /// Statistics for a single subscription destination
 #[derive(Debug, Clone, Default)]
 pub struct SubStats {
     /// Number of messages received on this destination
     pub message_count: u64,
+    /// Local id of the subscription
+    pub subscription_id: String,
+    /// Message ids received but not yet acknowledged with `ack`
+    pub unacked_ids: Vec<String>,
+    /// Number of messages acknowledged with `ack`
+    pub acked_count: u64,
+    /// Unacknowledged messages as last reported by the connection
+    pub pending: PendingAcks,
+}
+
+impl SubStats {
+    /// Unacked count, age of the oldest unacked message, and acked count,
+    /// e.g. `unacked 12, oldest 8.2s, acked 40`
+    pub fn ack_status(&self) -> String {
+        let mut status = format!("unacked {}", self.pending.count);
+        if let Some(age) = self.pending.oldest_age {
+            status.push_str(&format!(", oldest {:.1}s", age.as_secs_f64()));
+        }
+        status.push_str(&format!(", acked {}", self.acked_count));
+        status
+    }
 }
 
 /// A message to display in the TUI
@@ -30,10 +71,30 @@ pub struct DisplayMessage {
     pub headers: Vec<(String, String)>,
 }
 
+/// A `send --confirm` still waiting for its receipt
+#[derive(Debug)]
+pub struct PendingSend {
+    /// Destination of the SEND
+    pub destination: String,
+    /// When the SEND was written
+    pub sent_at: Instant,
+    /// Wakes the waiting command when the broker rejects the SEND
+    pub rejected: oneshot::Sender<String>,
+}
+
+/// Scrollable TUI pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Messages,
+    Errors,
+}
+
 /// Application state shared across all tasks
 pub struct AppState {
     /// Session start time
     pub start_time: DateTime<Local>,
+    /// Session start, for rates in `stats`
+    pub started: Instant,
 
     /// Connection info
     pub host: String,
@@ -42,27 +103,51 @@ pub struct AppState {
 
     /// Subscriptions: destination -> stats
     pub subscriptions: HashMap<String, SubStats>,
+    /// Ack mode used for new subscriptions (`--ack`)
+    pub ack_mode: AckMode,
 
-    /// Heartbeat tracking
-    pub heartbeat_count: u64,
-    pub last_heartbeat: Option<Instant>,
+    /// Messages, heartbeats, sends, receipts and reconnects counted by the
+    /// connection, and the message history for `report`
+    pub recorder: SessionRecorder,
 
-    /// Other counters
-    pub sent_count: u64,
+    /// Counters of the entries shown in the message pane
     pub error_count: u64,
     pub warning_count: u64,
     pub info_count: u64,
 
+    /// Sends awaiting a receipt, by receipt id
+    pub pending_sends: HashMap<String, PendingSend>,
+    /// Sequence counter and RNG behind send body templates
+    pub template: TemplateVars,
+    /// Destinations saved under `@name`
+    pub bookmarks: Bookmarks,
+    /// Task printing `stats <interval>`, if running
+    pub stats_printer: Option<tokio::task::AbortHandle>,
+
     /// Messages (ring buffer for display)
     pub messages: VecDeque<DisplayMessage>,
+    /// Snapshot shown in the message pane while it is paused; new messages
+    /// keep going into `messages`
+    pub paused: Option<VecDeque<DisplayMessage>>,
+    /// Messages recorded since the pane was paused
+    pub paused_missed: usize,
 
     /// Broker errors (separate ring buffer for error pane)
     pub errors: VecDeque<DisplayMessage>,
 
     /// UI state
+    pub verbosity: Verbosity,
     pub show_headers: bool,
     pub scroll_offset: usize,
     pub error_scroll_offset: usize,
+    /// Pane that scroll keys act on
+    pub focus: Pane,
+    /// Whether the key binding help overlay is shown
+    pub show_help: bool,
+    /// Selected row of the bookmark picker, if it is open
+    pub bookmark_picker: Option<usize>,
+    /// Active `/` search over the message pane
+    pub search: Option<Search>,
 
     /// Current input buffer
     pub input: String,
@@ -78,25 +163,42 @@ pub struct AppState {
 }
 
 impl AppState {
-    /// Create a new AppState with the given connection info
-    pub fn new(host: String, user: String, heartbeat_interval_ms: u32) -> Self {
+    /// Create a new AppState with the given connection info and the
+    /// recorder passed to the connection
+    pub fn new(
+        host: String,
+        user: String,
+        heartbeat_interval_ms: u32,
+        recorder: SessionRecorder,
+    ) -> Self {
         Self {
             start_time: Local::now(),
+            started: Instant::now(),
             host,
             user,
             heartbeat_interval_ms,
             subscriptions: HashMap::new(),
-            heartbeat_count: 0,
-            last_heartbeat: None,
-            sent_count: 0,
+            ack_mode: AckMode::Auto,
+            recorder,
             error_count: 0,
             warning_count: 0,
             info_count: 0,
+            pending_sends: HashMap::new(),
+            template: TemplateVars::default(),
+            bookmarks: Bookmarks::default(),
+            stats_printer: None,
             messages: VecDeque::with_capacity(MAX_MESSAGES),
+            paused: None,
+            paused_missed: 0,
             errors: VecDeque::with_capacity(MAX_ERRORS),
+            verbosity: Verbosity::Normal,
             show_headers: false,
             scroll_offset: 0,
             error_scroll_offset: 0,
+            focus: Pane::Messages,
+            show_help: false,
+            bookmark_picker: None,
+            search: None,
             input: String::new(),
             cursor_pos: 0,
             command_history: Vec::new(),
@@ -105,16 +207,10 @@ impl AppState {
         }
     }
 
-    /// Record a heartbeat
-    pub fn record_heartbeat(&mut self) {
-        self.heartbeat_count += 1;
-        self.last_heartbeat = Some(Instant::now());
-    }
-
     /// Get the heartbeat indicator character and whether it's "pulsing"
     /// Returns (indicator, is_pulsing)
     pub fn heartbeat_indicator(&self) -> (&'static str, bool) {
-        match self.last_heartbeat {
+        match self.recorder.stats().last_heartbeat {
             Some(last) => {
                 let elapsed = last.elapsed().as_millis() as u32;
                 // Pulse for 1 second after heartbeat
@@ -137,9 +233,9 @@ impl AppState {
         body: String,
         headers: Vec<(String, String)>,
     ) {
-        // Update counters based on message type
+        // Update counters based on message type; the recorder counts sends
         match destination {
-            "SENT" => self.sent_count += 1,
+            "SENT" => {}
             "ERROR" => self.error_count += 1,
             "BROKER ERROR" => {
                 // Broker errors go to the dedicated error pane
@@ -155,22 +251,39 @@ impl AppState {
                     .entry(destination.to_string())
                     .or_default();
                 stats.message_count += 1;
+                // Remember the message for `ack` unless the broker
+                // considers it acknowledged already
+                if self.ack_mode != AckMode::Auto
+                    && let Some((_, id)) = headers.iter().find(|(k, _)| k == "message-id")
+                {
+                    stats.unacked_ids.push(id.clone());
+                }
             }
         }
 
         // Add to message buffer
-        let msg = DisplayMessage {
+        self.push_message(DisplayMessage {
             timestamp: Local::now(),
             destination: destination.to_string(),
             body,
             headers,
-        };
+        });
+    }
 
+    /// Append to the message ring buffer, trimming it to `MAX_MESSAGES`
+    fn push_message(&mut self, msg: DisplayMessage) {
         self.messages.push_back(msg);
-
-        // Trim to max size
+        let mut dropped = 0;
         while self.messages.len() > MAX_MESSAGES {
             self.messages.pop_front();
+            dropped += 1;
+        }
+        if self.paused.is_some() {
+            self.paused_missed += 1;
+        } else if let Some(search) = &mut self.search
+            && let Some(msg) = self.messages.back()
+        {
+            search.pushed(msg, self.messages.len() - 1, dropped);
         }
     }
 
@@ -193,11 +306,68 @@ impl AppState {
         }
     }
 
-    /// Register a subscription destination
-    pub fn register_subscription(&mut self, destination: &str) {
+    /// Show a confirmed receipt and its round-trip time (the recorder
+    /// counts it)
+    pub fn record_receipt(&mut self, destination: &str, rtt: Duration) {
+        self.push_message(DisplayMessage {
+            timestamp: Local::now(),
+            destination: "RECEIPT".to_string(),
+            body: format!("[{}] confirmed in {} ms", destination, rtt.as_millis()),
+            headers: vec![],
+        });
+    }
+
+    /// Remember a `send --confirm` until its receipt (or an ERROR naming
+    /// it) arrives. The receiver yields the broker's error message.
+    pub fn track_send(
+        &mut self,
+        receipt_id: &str,
+        destination: &str,
+        sent_at: Instant,
+    ) -> oneshot::Receiver<String> {
+        let (rejected, rx) = oneshot::channel();
+        self.pending_sends.insert(
+            receipt_id.to_string(),
+            PendingSend {
+                destination: destination.to_string(),
+                sent_at,
+                rejected,
+            },
+        );
+        rx
+    }
+
+    /// Mark the SENT entry carrying `receipt_id` as failed
+    pub fn mark_send_failed(&mut self, receipt_id: &str) {
+        let sent = self.messages.iter_mut().rev().find(|msg| {
+            msg.destination == "SENT"
+                && msg
+                    .headers
+                    .iter()
+                    .any(|(k, v)| k == "receipt" && v == receipt_id)
+        });
+        if let Some(msg) = sent {
+            msg.destination = "FAILED".to_string();
+        }
+    }
+
+    /// Register a subscription destination and its subscription id
+    pub fn register_subscription(&mut self, destination: &str, subscription_id: &str) {
         self.subscriptions
             .entry(destination.to_string())
-            .or_default();
+            .or_default()
+            .subscription_id = subscription_id.to_string();
+    }
+
+    /// Store the unacknowledged messages reported by
+    /// `Connection::pending_acks()`, keyed by subscription id
+    pub fn update_pending_acks(&mut self, pending: &HashMap<String, PendingAcks>) {
+        for stats in self.subscriptions.values_mut() {
+            stats.pending = pending
+                .get(&stats.subscription_id)
+                .copied()
+                .unwrap_or_default();
+        }
     }
 
     /// Get total message count across all subscriptions
@@ -220,10 +390,151 @@ impl AppState {
         self.show_headers = !self.show_headers;
     }
 
+    /// Move focus to the other pane. The error pane can only take focus
+    /// while it is shown.
+    pub fn switch_pane(&mut self) {
+        self.focus = match self.focus {
+            Pane::Messages if !self.errors.is_empty() => Pane::Errors,
+            _ => Pane::Messages,
+        };
+    }
+
+    /// Pane that currently has focus, falling back to messages when the
+    /// error pane is hidden
+    pub fn focused_pane(&self) -> Pane {
+        if self.errors.is_empty() {
+            Pane::Messages
+        } else {
+            self.focus
+        }
+    }
+
+    /// Scroll `pane` up by `lines`
+    pub fn scroll_up(&mut self, pane: Pane, lines: usize) {
+        let offset = match pane {
+            Pane::Messages => &mut self.scroll_offset,
+            Pane::Errors => &mut self.error_scroll_offset,
+        };
+        *offset = offset.saturating_sub(lines);
+    }
+
+    /// Scroll `pane` down by `lines`
+    pub fn scroll_down(&mut self, pane: Pane, lines: usize) {
+        let visible = self.visible_messages().len();
+        let (offset, len) = match pane {
+            Pane::Messages => (&mut self.scroll_offset, visible),
+            Pane::Errors => (&mut self.error_scroll_offset, self.errors.len()),
+        };
+        *offset = (*offset + lines).min(len.saturating_sub(1));
+    }
+
     /// Clear message history
     pub fn clear_messages(&mut self) {
         self.messages.clear();
+        self.recorder.clear_history();
+        if let Some(snapshot) = &mut self.paused {
+            snapshot.clear();
+        }
         self.scroll_offset = 0;
+        self.rescan();
+    }
+
+    /// Freeze the message pane on what it shows now, or resume and jump
+    /// back to the live tail
+    pub fn toggle_pause(&mut self) {
+        if self.paused.take().is_none() {
+            self.paused = Some(self.messages.clone());
+        } else {
+            self.scroll_offset = 0;
+        }
+        self.paused_missed = 0;
+        self.rescan();
+    }
+
+    /// Search the message pane for `query` and show the newest match; an
+    /// empty query ends the search
+    pub fn start_search(&mut self, query: &str) -> Result<(), String> {
+        if query.is_empty() {
+            self.search = None;
+            return Ok(());
+        }
+        let mut search = Search::new(query)?;
+        search.rescan(self.visible_messages());
+        self.search = Some(search);
+        self.step_search(true);
+        Ok(())
+    }
+
+    /// Show the next older (`back`) or newer match, scrolled to the top of
+    /// the message pane
+    pub fn step_search(&mut self, back: bool) {
+        if let Some(index) = self.search.as_mut().and_then(|s| s.step(back)) {
+            self.scroll_offset = index;
+        }
+    }
+
+    /// Find the search's matches again after the visible messages changed
+    fn rescan(&mut self) {
+        if let Some(mut search) = self.search.take() {
+            search.rescan(self.visible_messages());
+            self.search = Some(search);
+        }
+    }
+
+    /// Open the bookmark picker on its first row, or close it
+    pub fn toggle_bookmark_picker(&mut self) {
+        self.bookmark_picker = match self.bookmark_picker {
+            None => Some(0),
+            Some(_) => None,
+        };
+    }
+
+    /// Move the bookmark picker selection by `delta` rows, stopping at the
+    /// ends
+    pub fn move_bookmark_selection(&mut self, delta: isize) {
+        let last = self.bookmarks.len().saturating_sub(1);
+        if let Some(selected) = &mut self.bookmark_picker {
+            *selected = selected.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    /// Close the picker and type `@name ` of the selected bookmark at the
+    /// cursor
+    pub fn pick_bookmark(&mut self) {
+        let Some(selected) = self.bookmark_picker.take() else {
+            return;
+        };
+        let Some((name, _)) = self.bookmarks.iter().nth(selected) else {
+            return;
+        };
+        let text = format!("@{} ", name);
+        self.input.insert_str(self.cursor_pos, &text);
+        self.cursor_pos += text.len();
+    }
+
+    /// Messages shown in the message pane: the paused snapshot, or the
+    /// live buffer
+    pub fn visible_messages(&self) -> &VecDeque<DisplayMessage> {
+        self.paused.as_ref().unwrap_or(&self.messages)
+    }
+
+    /// The visible messages with their headers, for `export`
+    pub fn export_messages(&self) -> String {
+        let mut out = String::new();
+        for msg in self.visible_messages() {
+            out.push_str(&format!(
+                "{} [{}]\n",
+                msg.timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+                msg.destination
+            ));
+            for (k, v) in &msg.headers {
+                out.push_str(&format!("{}: {}\n", k, v));
+            }
+            out.push('\n');
+            out.push_str(&msg.body);
+            out.push_str("\n\n");
+        }
+        out
     }
 
     /// Add a command to history
@@ -293,9 +604,28 @@ impl AppState {
         include_messages: bool,
         max_width: usize,
     ) -> String {
-        let end_time = Local::now();
-        let duration = end_time.signed_duration_since(self.start_time);
-        let total_secs = duration.num_seconds();
+        self.recorder.report().render(&SummaryFormat {
+            state: self,
+            include_messages,
+            max_width,
+        })
+    }
+}
+
+/// The CLI's session report: the recorder's counts with the connection
+/// details and the ack status of each subscription
+struct SummaryFormat<'a> {
+    state: &'a AppState,
+    include_messages: bool,
+    max_width: usize,
+}
+
+impl ReportFormat for SummaryFormat<'_> {
+    fn render(&self, report: &SessionReport) -> String {
+        let state = self.state;
+        let start_time = DateTime::<Local>::from(report.started);
+        let end_time = DateTime::<Local>::from(report.ended);
+        let total_secs = report.duration().as_secs();
         let mins = total_secs / 60;
         let secs = total_secs % 60;
 
@@ -309,11 +639,11 @@ impl AppState {
             "═══════════════════════════════════════════════════════════════════════════════"
                 .to_string(),
         );
-        lines.push(format!("  Host:       {}", self.host));
-        lines.push(format!("  User:       {}", self.user));
+        lines.push(format!("  Host:       {}", state.host));
+        lines.push(format!("  User:       {}", state.user));
         lines.push(format!(
             "  Started:    {}",
-            self.start_time.format("%Y-%m-%d %H:%M:%S")
+            start_time.format("%Y-%m-%d %H:%M:%S")
         ));
         lines.push(format!(
             "  Ended:      {}",
@@ -324,8 +654,8 @@ impl AppState {
         lines.push("  Subscriptions:".to_string());
 
         // Sort destinations by message count (descending)
-        let mut subs: Vec<_> = self.subscriptions.iter().collect();
-        subs.sort_by(|a, b| b.1.message_count.cmp(&a.1.message_count));
+        let mut subs: Vec<_> = state.subscriptions.iter().collect();
+        subs.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.message_count));
 
         let max_dest_len = subs
             .iter()
@@ -335,24 +665,38 @@ impl AppState {
             .min(40);
         for (dest, stats) in &subs {
             let dest_display = truncate_str(dest, max_dest_len);
-            lines.push(format!(
+            let mut line = format!(
                 "    {:width$} {:>6}",
                 dest_display,
                 stats.message_count,
                 width = max_dest_len
-            ));
+            );
+            if state.ack_mode != AckMode::Auto {
+                line.push_str(&format!("  ({})", stats.ack_status()));
+            }
+            lines.push(line);
         }
         lines.push(format!("    {:─>width$}", "", width = max_dest_len + 7));
         lines.push(format!(
             "    {:width$} {:>6}",
             "Total",
-            self.total_message_count(),
+            report.stats.received,
             width = max_dest_len
         ));
         lines.push(String::new());
-        lines.push(format!("  Heartbeats received: {}", self.heartbeat_count));
+        lines.push(format!(
+            "  Heartbeats received: {}",
+            report.stats.heartbeats
+        ));
+        if let Some(avg) = report.stats.average_receipt_rtt() {
+            lines.push(format!(
+                "  Receipts confirmed:  {} (avg {} ms)",
+                report.stats.receipts,
+                avg.as_millis()
+            ));
+        }
 
-        if include_messages && !self.messages.is_empty() {
+        if self.include_messages && !report.history.is_empty() {
             lines.push(String::new());
             lines.push(
                 "───────────────────────────────────────────────────────────────────────────────"
@@ -364,10 +708,11 @@ impl AppState {
                     .to_string(),
             );
 
-            for msg in &self.messages {
-                let time = msg.timestamp.format("%H:%M:%S").to_string();
-                let prefix = format!("  {} [{}] ", time, msg.destination);
-                let body_width = max_width.saturating_sub(prefix.len());
+            for msg in &report.history {
+                let time = DateTime::<Local>::from(msg.at).format("%H:%M:%S");
+                let destination = msg.destination.as_deref().unwrap_or("MESSAGE");
+                let prefix = format!("  {} [{}] ", time, destination);
+                let body_width = self.max_width.saturating_sub(prefix.len());
                 let body = truncate_str(&msg.body, body_width);
                 lines.push(format!("{}{}", prefix, body));
             }
@@ -396,7 +741,26 @@ fn truncate_str(s: &str, max_len: usize) -> String {
 /// Thread-safe shared state
 pub type SharedState = Arc<Mutex<AppState>>;
 
+/// Recorder for `ConnectOptions::recorder()`, keeping as many messages and
+/// errors as the panes show
+pub fn new_recorder() -> SessionRecorder {
+    SessionRecorder::new()
+        .history(MAX_MESSAGES)
+        .max_errors(MAX_ERRORS)
+        .body_preview(BODY_PREVIEW_LEN)
+}
+
 /// Create a new shared state
-pub fn new_shared_state(host: String, user: String, heartbeat_interval_ms: u32) -> SharedState {
-    Arc::new(Mutex::new(AppState::new(host, user, heartbeat_interval_ms)))
+pub fn new_shared_state(
+    host: String,
+    user: String,
+    heartbeat_interval_ms: u32,
+    recorder: SessionRecorder,
+) -> SharedState {
+    Arc::new(Mutex::new(AppState::new(
+        host,
+        user,
+        heartbeat_interval_ms,
+        recorder,
+    )))
 }
@@ -0,0 +1,275 @@
+use iridium_stomp::metrics::SIZE_BUCKETS;
+use iridium_stomp::{Connection, MessageStats};
+use std::time::{Duration, Instant};
+
+use super::ping::format_ms;
+use super::state::SharedState;
+
+/// Usage string for the stats command
+pub const STATS_USAGE: &str = "Usage: stats [<interval>|off]";
+
+/// Most content types listed in a report, the most frequent first
+const MAX_REPORTED_CONTENT_TYPES: usize = 5;
+
+/// Shortest interval accepted by `stats <interval>`
+const MIN_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counters behind a stats report, taken at one point in time
+#[derive(Debug, Clone, Copy)]
+pub struct StatsSample {
+    /// When the sample was taken
+    pub at: Instant,
+    /// Messages received on all subscriptions
+    pub received: u64,
+    /// SEND frames written to the broker
+    pub sent: u64,
+    /// Bytes read from the broker, frames and heartbeats
+    pub bytes_in: u64,
+    /// Their size on the wire
+    pub bytes_out: u64,
+}
+
+/// Everything a stats report shows
+pub struct Stats {
+    pub sample: StatsSample,
+    pub uptime: String,
+    pub reconnects: u64,
+    /// Messages waiting for an ACK, over all subscriptions
+    pub pending_acks: usize,
+    /// How long the oldest of them has waited
+    pub oldest_pending: Option<Duration>,
+    pub heartbeats: u64,
+    pub last_heartbeat: Option<Duration>,
+    /// Average and most recent receipt round trip (`send --confirm`)
+    pub receipt_rtt: Option<(Duration, Duration)>,
+    /// Body sizes and content types of the messages received
+    pub messages: MessageStats,
+}
+
+/// Collect the current stats from the connection and the session state
+pub async fn collect(conn: &Connection, state: &SharedState) -> Stats {
+    let pending = conn.pending_acks().await;
+    let bytes_in = conn.decode_stats().bytes;
+    let s = state.lock().await;
+    let session = s.recorder.stats();
+    Stats {
+        sample: StatsSample {
+            at: Instant::now(),
+            received: s.total_message_count(),
+            sent: session.sent,
+            bytes_in,
+            bytes_out: session.sent_bytes,
+        },
+        uptime: s.session_duration(),
+        reconnects: session.reconnects,
+        pending_acks: pending.values().map(|p| p.count).sum(),
+        oldest_pending: pending.values().filter_map(|p| p.oldest_age).max(),
+        heartbeats: session.heartbeats,
+        last_heartbeat: session.last_heartbeat.map(|at| at.elapsed()),
+        receipt_rtt: session.average_receipt_rtt().zip(session.last_receipt_rtt),
+        messages: conn.message_stats(),
+    }
+}
+
+/// A sample of all zeros at `at`, for rates since the session started
+pub fn zero_sample(at: Instant) -> StatsSample {
+    StatsSample {
+        at,
+        received: 0,
+        sent: 0,
+        bytes_in: 0,
+        bytes_out: 0,
+    }
+}
+
+/// `count` per second between two samples
+fn rate(count: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        count as f64 / elapsed.as_secs_f64()
+    }
+}
+
+/// A byte count with a binary unit, e.g. `1.5 KiB`
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Average, 95th percentile and largest body size, e.g.
+/// `avg 312 B, p95 <= 1.0 KiB, max 3.2 KiB`
+fn body_sizes(stats: &MessageStats) -> String {
+    let Some(average) = stats.average_body() else {
+        return "none yet".to_string();
+    };
+    let p95 = match stats.body_sizes.quantile(0.95) {
+        Some(Some(bound)) => format!("<= {}", format_bytes(bound as f64)),
+        _ => {
+            let largest_bucket = SIZE_BUCKETS[SIZE_BUCKETS.len() - 1];
+            format!("> {}", format_bytes(largest_bucket as f64))
+        }
+    };
+    format!(
+        "avg {}, p95 {}, max {}",
+        format_bytes(average),
+        p95,
+        format_bytes(stats.largest_body as f64)
+    )
+}
+
+/// The most frequent content types with their counts, e.g.
+/// `application/json 812, text/plain 40, untyped 3`
+fn content_types(stats: &MessageStats) -> String {
+    let mut counts: Vec<(&str, u64)> = stats
+        .content_types
+        .iter()
+        .map(|(content_type, &count)| (content_type.as_str(), count))
+        .collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    let mut others = stats.other_content_types;
+    if counts.len() > MAX_REPORTED_CONTENT_TYPES {
+        others += counts
+            .drain(MAX_REPORTED_CONTENT_TYPES..)
+            .map(|(_, count)| count)
+            .sum::<u64>();
+    }
+    let mut parts: Vec<String> = counts
+        .iter()
+        .map(|(content_type, count)| format!("{} {}", content_type, count))
+        .collect();
+    if others > 0 {
+        parts.push(format!("others {}", others));
+    }
+    if stats.untyped > 0 {
+        parts.push(format!("untyped {}", stats.untyped));
+    }
+    if parts.is_empty() {
+        "none yet".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+impl Stats {
+    /// Report lines, with rates measured since `since`
+    pub fn lines(&self, since: &StatsSample) -> Vec<String> {
+        let now = &self.sample;
+        let elapsed = now.at.saturating_duration_since(since.at);
+        let flow = |messages: u64, bytes: u64, total_messages: u64, total_bytes: u64| {
+            format!(
+                "{:.1} msg/s, {}/s ({} messages, {})",
+                rate(messages, elapsed),
+                format_bytes(rate(bytes, elapsed)),
+                total_messages,
+                format_bytes(total_bytes as f64),
+            )
+        };
+        let mut pending = format!("Pending acks: {}", self.pending_acks);
+        if let Some(age) = self.oldest_pending {
+            pending.push_str(&format!(" (oldest {:.1}s)", age.as_secs_f64()));
+        }
+        let mut heartbeats = format!("Heartbeats: {} received", self.heartbeats);
+        if let Some(age) = self.last_heartbeat {
+            heartbeats.push_str(&format!(", last {:.1}s ago", age.as_secs_f64()));
+        }
+        let rtt = match self.receipt_rtt {
+            Some((avg, last)) => format!(
+                "Receipt RTT: avg {} ms, last {} ms",
+                format_ms(avg),
+                format_ms(last)
+            ),
+            None => "Receipt RTT: none yet (send --confirm)".to_string(),
+        };
+        vec![
+            format!("Uptime: {}", self.uptime),
+            format!(
+                "In: {}",
+                flow(
+                    now.received.saturating_sub(since.received),
+                    now.bytes_in.saturating_sub(since.bytes_in),
+                    now.received,
+                    now.bytes_in,
+                )
+            ),
+            format!(
+                "Out: {}",
+                flow(
+                    now.sent.saturating_sub(since.sent),
+                    now.bytes_out.saturating_sub(since.bytes_out),
+                    now.sent,
+                    now.bytes_out,
+                )
+            ),
+            format!("Reconnects: {}", self.reconnects),
+            pending,
+            heartbeats,
+            rtt,
+            format!("Body sizes: {}", body_sizes(&self.messages)),
+            format!("Content types: {}", content_types(&self.messages)),
+        ]
+    }
+}
+
+/// Parse the argument of `stats`: `None` to print once, `Some(None)` to
+/// stop printing, `Some(Some(interval))` to print every `interval`
+pub fn parse_stats_arg(arg: Option<&str>) -> Result<Option<Option<Duration>>, String> {
+    match arg.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some("off") => Ok(Some(None)),
+        Some(interval) => match super::commands::parse_duration(interval) {
+            Some(d) if d >= MIN_STATS_INTERVAL => Ok(Some(Some(d))),
+            Some(_) => Err(format!(
+                "Stats interval must be at least {}s",
+                MIN_STATS_INTERVAL.as_secs()
+            )),
+            None => Err(STATS_USAGE.to_string()),
+        },
+    }
+}
+
+/// Print a one-line stats report every `interval` until aborted or the
+/// connection is gone. Plain mode prints to stderr, so the report never
+/// mixes with message bodies piped from stdout; the TUI shows it as INFO.
+pub fn spawn_printer(
+    conn: &Connection,
+    state: SharedState,
+    interval: Duration,
+    tui_mode: bool,
+) -> tokio::task::AbortHandle {
+    let conn = conn.downgrade();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        let mut last = None;
+        loop {
+            let Some(conn) = conn.upgrade() else {
+                return;
+            };
+            let stats = collect(&conn, &state).await;
+            drop(conn);
+            if let Some(since) = &last {
+                let line = stats.lines(since).join(" | ");
+                if tui_mode {
+                    state.lock().await.record_message("INFO", line, vec![]);
+                } else {
+                    eprintln!("\n[STATS] {}", line);
+                    super::plain::print_prompt(state.lock().await.verbosity);
+                }
+            }
+            last = Some(stats.sample);
+            ticker.tick().await;
+        }
+    })
+    .abort_handle()
+}
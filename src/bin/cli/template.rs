@@ -0,0 +1,184 @@
+use chrono::{SecondsFormat, Utc};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Variables understood in send bodies, for error messages
+const KNOWN_VARIABLES: &str = "uuid, now, now_iso, seq, random, random:<min>-<max>";
+
+/// Default upper bound of `{{random}}`
+const DEFAULT_RANDOM_MAX: u64 = 1_000_000;
+
+/// State behind the `{{...}}` variables of send bodies: the sequence
+/// counter and a random number generator, kept for the whole session
+pub struct TemplateVars {
+    /// Next value of `{{seq}}`
+    seq: u64,
+    /// xorshift64* state
+    rng: u64,
+}
+
+impl Default for TemplateVars {
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            seq: 1,
+            // Never zero, or xorshift would only ever produce zero
+            rng: (nanos ^ (u64::from(std::process::id()) << 32)) | 1,
+        }
+    }
+}
+
+impl TemplateVars {
+    /// Expand the variables in `template`.
+    ///
+    /// - `{{uuid}}`: a random version 4 UUID
+    /// - `{{now}}`: milliseconds since the Unix epoch
+    /// - `{{now_iso}}`: the current UTC time in RFC 3339 format
+    /// - `{{seq}}`: a counter starting at 1, advanced once per rendered body
+    /// - `{{random}}`: a random integer below 1000000
+    /// - `{{random:10-99}}`: a random integer between 10 and 99 inclusive
+    ///
+    /// Text without `{{` is returned unchanged. Unknown variables are an
+    /// error, so typos do not end up in published messages.
+    pub fn render(&mut self, template: &str) -> Result<String, String> {
+        let seq = self.seq;
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        let mut used_seq = false;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            let name = rest[start + 2..start + 2 + len].trim();
+            match name {
+                "uuid" => out.push_str(&self.uuid()),
+                "now" => out.push_str(&Utc::now().timestamp_millis().to_string()),
+                "now_iso" => out.push_str(&Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+                "seq" => {
+                    used_seq = true;
+                    out.push_str(&seq.to_string());
+                }
+                "random" => out.push_str(&self.below(DEFAULT_RANDOM_MAX).to_string()),
+                _ => match name.strip_prefix("random:") {
+                    Some(range) => {
+                        let (min, max) = parse_range(range)?;
+                        let value = min + self.below(max - min + 1);
+                        out.push_str(&value.to_string());
+                    }
+                    None => {
+                        return Err(format!(
+                            "Unknown template variable '{{{{{}}}}}' (known: {})",
+                            name, KNOWN_VARIABLES
+                        ));
+                    }
+                },
+            }
+            rest = &rest[start + 2 + len + 2..];
+        }
+        out.push_str(rest);
+        if used_seq {
+            self.seq += 1;
+        }
+        Ok(out)
+    }
+
+    /// Next pseudo-random number (xorshift64*)
+    fn next(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Pseudo-random number below `bound` (which must not be zero)
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    /// Random version 4 UUID
+    fn uuid(&mut self) -> String {
+        let hi = (self.next() & 0xffff_ffff_ffff_0fff) | 0x4000;
+        let lo = (self.next() & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000;
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            hi >> 32,
+            (hi >> 16) & 0xffff,
+            hi & 0xffff,
+            lo >> 48,
+            lo & 0xffff_ffff_ffff
+        )
+    }
+}
+
+/// Parse the `<min>-<max>` of `{{random:<min>-<max>}}`
+fn parse_range(range: &str) -> Result<(u64, u64), String> {
+    let invalid = || {
+        format!(
+            "Invalid range in '{{{{random:{}}}}}' (e.g., {{{{random:1-100}}}})",
+            range
+        )
+    };
+    let (min, max) = range.split_once('-').ok_or_else(invalid)?;
+    let min: u64 = min.trim().parse().map_err(|_| invalid())?;
+    let max: u64 = max.trim().parse().map_err(|_| invalid())?;
+    if min > max || max == u64::MAX {
+        return Err(invalid());
+    }
+    Ok((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_without_variables_is_unchanged() {
+        let mut vars = TemplateVars::default();
+        assert_eq!(vars.render("plain {text}").unwrap(), "plain {text}");
+        assert_eq!(vars.render("open {{seq").unwrap(), "open {{seq");
+    }
+
+    #[test]
+    fn seq_advances_once_per_body() {
+        let mut vars = TemplateVars::default();
+        assert_eq!(vars.render("{{seq}}/{{ seq }}").unwrap(), "1/1");
+        assert_eq!(vars.render("no counter").unwrap(), "no counter");
+        assert_eq!(vars.render("n={{seq}}").unwrap(), "n=2");
+    }
+
+    #[test]
+    fn random_stays_in_range() {
+        let mut vars = TemplateVars::default();
+        for _ in 0..100 {
+            let value: u64 = vars.render("{{random:10-12}}").unwrap().parse().unwrap();
+            assert!((10..=12).contains(&value), "{}", value);
+        }
+        assert_eq!(vars.render("{{random:7-7}}").unwrap(), "7");
+    }
+
+    #[test]
+    fn uuid_and_timestamps_have_their_format() {
+        let mut vars = TemplateVars::default();
+        let uuid = vars.render("{{uuid}}").unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.as_bytes()[14], b'4');
+        assert!(vars.render("{{now}}").unwrap().parse::<i64>().is_ok());
+        assert!(vars.render("{{now_iso}}").unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn unknown_variables_and_bad_ranges_are_errors() {
+        let mut vars = TemplateVars::default();
+        assert!(vars.render("{{nope}}").unwrap_err().contains("'{{nope}}'"));
+        for range in ["{{random:5-1}}", "{{random:a-b}}", "{{random:5}}"] {
+            assert!(
+                vars.render(range).unwrap_err().contains("Invalid range"),
+                "{}",
+                range
+            );
+        }
+    }
+}
@@ -4,42 +4,69 @@ use crossterm::{
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use iridium_stomp::connection::AckMode;
-use iridium_stomp::{ConnectOptions, Connection, Frame};
+use iridium_stomp::{Connection, ConnectionEvent, Frame};
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Row, Table, Wrap},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Row, Table, Wrap},
 };
 use std::io::{self, Stdout};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::{self, error::TrySendError};
 
 use super::args::Cli;
-use super::commands::{CommandResult, execute_command};
-use super::state::{SharedState, new_shared_state};
+use super::bookmarks::Bookmarks;
+use super::commands::{
+    CommandResult, describe_broker_error, event_notice, event_warning, execute_command,
+};
+use super::init::InitFile;
+use super::keymap::{Action, KeyMap};
+use super::search::Search;
+use super::shutdown::{disconnect, shutdown_signal};
+use super::state::{AppState, BODY_PREVIEW_LEN, Pane, SharedState, new_recorder, new_shared_state};
+
+/// Lines moved by the page up/down actions
+const PAGE_LINES: usize = 10;
 
 /// TUI Application
 pub struct App {
     conn: Connection,
     state: SharedState,
+    keymap: KeyMap,
     should_quit: bool,
+    /// Set when SIGTERM (or SIGINT from outside the terminal) arrives
+    terminate: Arc<AtomicBool>,
 }
 
 impl App {
-    fn new(conn: Connection, state: SharedState) -> Self {
+    fn new(conn: Connection, state: SharedState, keymap: KeyMap) -> Self {
         Self {
             conn,
             state,
+            keymap,
             should_quit: false,
+            terminate: Arc::new(AtomicBool::new(false)),
         }
     }
 }
 
 /// Run the CLI in TUI mode
 pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
+    // Load key bindings first so a broken config fails before connecting
+    let keymap = KeyMap::load(cli.keymap.as_deref()).map_err(|e| (e, 1))?;
+    let bookmarks = Bookmarks::load(cli.bookmarks.as_deref()).map_err(|e| (e, 1))?;
+    let init = cli
+        .init
+        .as_deref()
+        .map(InitFile::load)
+        .transpose()
+        .map_err(|e| (e, 1))?;
+
     // Parse heartbeat to get interval for state
     let hb_parts: Vec<&str> = cli.heartbeat.split(',').collect();
     let hb_interval = hb_parts
@@ -47,14 +74,18 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
         .and_then(|s| s.trim().parse::<u32>().ok())
         .unwrap_or(10000);
 
-    // Create heartbeat notification channel
-    let (hb_tx, mut hb_rx) = mpsc::channel::<()>(16);
+    // Create connection event channel (slow consumer warnings)
+    let (event_tx, mut event_rx) = mpsc::channel::<ConnectionEvent>(16);
 
     // Build connection options
-    let options = ConnectOptions::default().with_heartbeat_notify(hb_tx);
+    let recorder = new_recorder();
+    let options = cli
+        .connect_options()?
+        .with_event_notify(event_tx)
+        .recorder(recorder.clone());
 
     let conn = Connection::connect_with_options(
-        &cli.address,
+        &cli.broker_address(),
         &cli.login,
         &cli.passcode,
         &cli.heartbeat,
@@ -64,22 +95,36 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
     .map_err(|e| super::plain::format_connection_error_pub(&e, &cli.address))?;
 
     // Create shared state
-    let state = new_shared_state(cli.address.clone(), cli.login.clone(), hb_interval);
+    let state = new_shared_state(
+        cli.address.clone(),
+        cli.login.clone(),
+        hb_interval,
+        recorder,
+    );
+    state.lock().await.ack_mode = cli.ack;
 
     // Channel for new subscription requests
     let (sub_tx, mut sub_rx) = mpsc::channel::<String>(16);
 
     // Subscribe to requested destinations
     for dest in &cli.subscribe {
+        let dest = bookmarks.expand(dest).map_err(|e| (e, 1))?;
         subscribe_destination(&conn, dest, state.clone()).await?;
     }
+    state.lock().await.bookmarks = bookmarks;
 
-    // Spawn heartbeat monitor task
-    let state_hb = state.clone();
+    // Spawn task to report reconnects and connection events worth a warning
+    let state_ev = state.clone();
     tokio::spawn(async move {
-        while hb_rx.recv().await.is_some() {
-            let mut s = state_hb.lock().await;
-            s.record_heartbeat();
+        while let Some(event) = event_rx.recv().await {
+            if let Some(notice) = event_notice(&event) {
+                state_ev.lock().await.record_message("INFO", notice, vec![]);
+            } else if let Some(warning) = event_warning(&event) {
+                state_ev
+                    .lock()
+                    .await
+                    .record_message("WARN", warning, vec![]);
+            }
         }
     });
 
@@ -109,11 +154,7 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
             match conn_err.next_frame().await {
                 Some(iridium_stomp::ReceivedFrame::Error(err)) => {
                     let mut s = state_err.lock().await;
-                    let msg = if let Some(ref body) = err.body {
-                        format!("{}: {}", err.message, body)
-                    } else {
-                        err.message.clone()
-                    };
+                    let msg = describe_broker_error(&mut s, &err);
                     // Include error frame headers for context when user toggles header display
                     s.record_message("BROKER ERROR", msg, err.frame.headers.clone());
                 }
@@ -125,6 +166,13 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
         }
     });
 
+    if let Some(init) = &init
+        && let Err(e) = init.run(&conn, state.clone(), &sub_tx, true).await
+    {
+        disconnect(conn).await;
+        return Err((e, 1));
+    }
+
     // Setup terminal
     enable_raw_mode().map_err(|e| (format!("Failed to enable raw mode: {}", e), 1))?;
     let mut stdout = io::stdout();
@@ -135,10 +183,41 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
         Terminal::new(backend).map_err(|e| (format!("Failed to create terminal: {}", e), 1))?;
 
     // Create app
-    let app = App::new(conn.clone(), state.clone());
+    let app = App::new(conn.clone(), state.clone(), keymap);
+
+    // Raw mode turns Ctrl+C into a key press, but signals sent by other
+    // processes still need to end the session cleanly
+    let terminate = app.terminate.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        terminate.store(true, Ordering::Relaxed);
+    });
+
+    // Spawn task to run entered commands in order, so a slow one such as
+    // `ping` doesn't freeze the screen; results land in the message pane
+    let (cmd_tx, mut cmd_rx) = mpsc::channel::<String>(16);
+    let conn_cmd = conn.clone();
+    let state_cmd = state.clone();
+    let terminate = app.terminate.clone();
+    tokio::spawn(async move {
+        while let Some(input) = cmd_rx.recv().await {
+            match execute_command(&input, &conn_cmd, state_cmd.clone(), &sub_tx, true).await {
+                CommandResult::Ok => {}
+                CommandResult::Quit => terminate.store(true, Ordering::Relaxed),
+                CommandResult::Info(msg) => {
+                    let mut s = state_cmd.lock().await;
+                    s.record_message("INFO", msg, vec![]);
+                }
+                CommandResult::Error(msg) => {
+                    let mut s = state_cmd.lock().await;
+                    s.record_message("ERROR", msg, vec![]);
+                }
+            }
+        }
+    });
 
     // Run the main loop
-    let result = run_app(&mut terminal, app, &sub_tx).await;
+    let result = run_app(&mut terminal, app, &cmd_tx).await;
 
     // Restore terminal
     disable_raw_mode().ok();
@@ -147,12 +226,14 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
 
     // Print summary if requested
     if cli.summary {
-        let s = state.lock().await;
+        let pending = conn.pending_acks().await;
+        let mut s = state.lock().await;
+        s.update_pending_acks(&pending);
         println!("{}", s.generate_summary());
     }
 
-    // Close connection
-    conn.close().await;
+    // Disconnect gracefully
+    disconnect(conn).await;
 
     result
 }
@@ -160,14 +241,16 @@ pub async fn run(cli: &Cli) -> Result<(), (String, u8)> {
 async fn run_app(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     mut app: App,
-    sub_tx: &mpsc::Sender<String>,
+    cmd_tx: &mpsc::Sender<String>,
 ) -> Result<(), (String, u8)> {
-    loop {
+    while !app.should_quit {
         // Draw UI
         {
-            let state = app.state.lock().await;
+            let pending = app.conn.pending_acks().await;
+            let mut state = app.state.lock().await;
+            state.update_pending_acks(&pending);
             terminal
-                .draw(|f| ui(f, &state))
+                .draw(|f| ui(f, &state, &app.keymap))
                 .map_err(|e| (format!("Draw error: {}", e), 1))?;
         }
 
@@ -175,57 +258,105 @@ async fn run_app(
         let has_event = event::poll(Duration::from_millis(100))
             .map_err(|e| (format!("Event poll error: {}", e), 1))?;
 
+        if app.terminate.load(Ordering::Relaxed) {
+            app.should_quit = true;
+            continue;
+        }
+
         if has_event {
             let evt = event::read().map_err(|e| (format!("Event read error: {}", e), 1))?;
 
             if let Event::Key(key) = evt {
-                match key.code {
-                    KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.should_quit = true;
-                    }
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.should_quit = true;
-                    }
-                    KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        let mut state = app.state.lock().await;
-                        state.toggle_headers();
+                // Ctrl+C always quits, whatever the keymap says
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    app.should_quit = true;
+                    continue;
+                }
+
+                {
+                    let mut state = app.state.lock().await;
+                    if state.show_help {
+                        // Any key dismisses the help overlay
+                        state.show_help = false;
+                        continue;
                     }
-                    KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        let mut state = app.state.lock().await;
-                        if state.scroll_offset > 0 {
-                            state.scroll_offset -= 1;
+                    if state.bookmark_picker.is_some() {
+                        match key.code {
+                            KeyCode::Up => state.move_bookmark_selection(-1),
+                            KeyCode::Down => state.move_bookmark_selection(1),
+                            KeyCode::Enter => state.pick_bookmark(),
+                            KeyCode::Esc => state.bookmark_picker = None,
+                            _ if app.keymap.action_for(&key) == Some(Action::Bookmarks) => {
+                                state.bookmark_picker = None;
+                            }
+                            _ => {}
                         }
+                        continue;
                     }
-                    KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        let mut state = app.state.lock().await;
-                        let max_scroll = state.messages.len().saturating_sub(1);
-                        if state.scroll_offset < max_scroll {
-                            state.scroll_offset += 1;
+                    // Space types a space while a command is being entered
+                    let typing = key.code == KeyCode::Char(' ') && !state.input.is_empty();
+                    if let Some(action) = app.keymap.action_for(&key).filter(|_| !typing) {
+                        match action {
+                            Action::Quit => app.should_quit = true,
+                            Action::ToggleHeaders => state.toggle_headers(),
+                            Action::ScrollUp => {
+                                let pane = state.focused_pane();
+                                state.scroll_up(pane, 1);
+                            }
+                            Action::ScrollDown => {
+                                let pane = state.focused_pane();
+                                state.scroll_down(pane, 1);
+                            }
+                            Action::PageUp => {
+                                let pane = state.focused_pane();
+                                state.scroll_up(pane, PAGE_LINES);
+                            }
+                            Action::PageDown => {
+                                let pane = state.focused_pane();
+                                state.scroll_down(pane, PAGE_LINES);
+                            }
+                            Action::ErrorScrollUp => state.scroll_up(Pane::Errors, 1),
+                            Action::ErrorScrollDown => state.scroll_down(Pane::Errors, 1),
+                            Action::SwitchPane => state.switch_pane(),
+                            Action::TogglePause => state.toggle_pause(),
+                            Action::Bookmarks => state.toggle_bookmark_picker(),
+                            Action::Help => state.show_help = true,
                         }
+                        continue;
                     }
-                    KeyCode::PageUp => {
-                        let mut state = app.state.lock().await;
-                        state.scroll_offset = state.scroll_offset.saturating_sub(10);
-                    }
-                    KeyCode::PageDown => {
-                        let mut state = app.state.lock().await;
-                        let max_scroll = state.messages.len().saturating_sub(1);
-                        state.scroll_offset = (state.scroll_offset + 10).min(max_scroll);
+                    // `?` opens help unless it is being typed into a command
+                    if key.code == KeyCode::Char('?') && state.input.is_empty() {
+                        state.show_help = true;
+                        continue;
                     }
-                    // Error pane scrolling: Ctrl+E (up) and Ctrl+D (down)
-                    KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        let mut state = app.state.lock().await;
-                        if state.error_scroll_offset > 0 {
-                            state.error_scroll_offset -= 1;
-                        }
-                    }
-                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        let mut state = app.state.lock().await;
-                        let max_scroll = state.errors.len().saturating_sub(1);
-                        if state.error_scroll_offset < max_scroll {
-                            state.error_scroll_offset += 1;
+                    // During a search `n`/`N` step through the matches and
+                    // Esc ends it, unless a command is being entered
+                    let plain = !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+                    if state.search.is_some() && state.input.is_empty() && plain {
+                        let handled = match key.code {
+                            KeyCode::Char('n') => {
+                                state.step_search(true);
+                                true
+                            }
+                            KeyCode::Char('N') => {
+                                state.step_search(false);
+                                true
+                            }
+                            KeyCode::Esc => {
+                                state.search = None;
+                                true
+                            }
+                            _ => false,
+                        };
+                        if handled {
+                            continue;
                         }
                     }
+                }
+
+                match key.code {
                     KeyCode::Up if key.modifiers.is_empty() => {
                         let mut state = app.state.lock().await;
                         state.history_prev();
@@ -243,28 +374,23 @@ async fn run_app(
                             state.cursor_pos = 0;
                             input
                         };
-                        if !input.is_empty() {
-                            match execute_command(
-                                &input,
-                                &app.conn,
-                                app.state.clone(),
-                                sub_tx,
-                                true,
-                            )
-                            .await
-                            {
-                                CommandResult::Ok => {}
-                                CommandResult::Quit => {
-                                    app.should_quit = true;
-                                }
-                                CommandResult::Info(msg) => {
-                                    let mut state = app.state.lock().await;
-                                    state.record_message("INFO", msg, vec![]);
-                                }
-                                CommandResult::Error(msg) => {
-                                    let mut state = app.state.lock().await;
-                                    state.record_message("ERROR", msg, vec![]);
+                        if let Some(query) = input.strip_prefix('/') {
+                            let mut state = app.state.lock().await;
+                            if let Err(msg) = state.start_search(query) {
+                                state.record_message("ERROR", msg, vec![]);
+                            }
+                        } else if !input.is_empty() {
+                            // Never wait here: a full queue would freeze the screen
+                            let refused = match cmd_tx.try_send(input) {
+                                Ok(()) => None,
+                                Err(TrySendError::Full(_)) => {
+                                    Some("Too many commands waiting, try again shortly")
                                 }
+                                Err(TrySendError::Closed(_)) => Some("Command runner stopped"),
+                            };
+                            if let Some(msg) = refused {
+                                let mut state = app.state.lock().await;
+                                state.record_message("ERROR", msg.to_string(), vec![]);
                             }
                         }
                     }
@@ -318,16 +444,12 @@ async fn run_app(
                 }
             }
         }
-
-        if app.should_quit {
-            break;
-        }
     }
 
     Ok(())
 }
 
-fn ui(f: &mut ratatui::Frame, state: &super::state::AppState) {
+fn ui(f: &mut ratatui::Frame, state: &AppState, keymap: &KeyMap) {
     let size = f.area();
 
     // Main layout: header, subscriptions, content area, input
@@ -350,7 +472,7 @@ fn ui(f: &mut ratatui::Frame, state: &super::state::AppState) {
     // Content area: split between messages and errors if there are errors
     if state.errors.is_empty() {
         // No errors - full space for messages
-        render_messages(f, chunks[2], state);
+        render_messages(f, chunks[2], state, keymap);
     } else {
         // Split content area: messages on left (70%), errors on right (30%)
         let content_chunks = Layout::default()
@@ -358,15 +480,33 @@ fn ui(f: &mut ratatui::Frame, state: &super::state::AppState) {
             .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
             .split(chunks[2]);
 
-        render_messages(f, content_chunks[0], state);
-        render_errors(f, content_chunks[1], state);
+        render_messages(f, content_chunks[0], state, keymap);
+        render_errors(f, content_chunks[1], state, keymap);
     }
 
     // Input bar
     render_input(f, chunks[3], state);
+
+    if let Some(selected) = state.bookmark_picker {
+        render_bookmarks(f, size, state, selected);
+    }
+    if state.show_help {
+        render_help(f, size, keymap);
+    }
 }
 
-fn render_header(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppState) {
+/// Border for a scrollable pane: thick when it has focus and both panes are
+/// shown, so it is clear which pane the scroll keys act on
+fn pane_block(state: &AppState, pane: Pane) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL);
+    if !state.errors.is_empty() && state.focused_pane() == pane {
+        block.border_type(BorderType::Thick)
+    } else {
+        block
+    }
+}
+
+fn render_header(f: &mut ratatui::Frame, area: Rect, state: &AppState) {
     let (hb_indicator, is_pulsing) = state.heartbeat_indicator();
     let hb_secs = state.heartbeat_interval_ms / 1000;
 
@@ -397,26 +537,42 @@ fn render_header(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppSt
     f.render_widget(header, area);
 }
 
-fn render_counts(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppState) {
+fn render_counts(f: &mut ratatui::Frame, area: Rect, state: &AppState) {
     let mut rows: Vec<Row> = Vec::new();
 
     // Add subscription counts (sorted by destination)
     let mut sorted_subs: Vec<_> = state.subscriptions.iter().collect();
     sorted_subs.sort_by(|a, b| a.0.cmp(b.0));
     for (dest, stats) in sorted_subs {
+        let label = if state.ack_mode == AckMode::Auto {
+            dest.clone()
+        } else {
+            format!("{} ({})", dest, stats.ack_status())
+        };
         rows.push(
-            Row::new(vec![dest.clone(), stats.message_count.to_string()])
+            Row::new(vec![label, stats.message_count.to_string()])
                 .style(Style::default().fg(Color::Green)),
         );
     }
 
     // Add other counts if non-zero
-    if state.sent_count > 0 {
+    let session = state.recorder.stats();
+    if session.sent > 0 {
         rows.push(
-            Row::new(vec!["Sent".to_string(), state.sent_count.to_string()])
+            Row::new(vec!["Sent".to_string(), session.sent.to_string()])
                 .style(Style::default().fg(Color::Blue)),
         );
     }
+    if session.receipts > 0 {
+        let label = match session.last_receipt_rtt {
+            Some(rtt) => format!("Receipts (last {} ms)", rtt.as_millis()),
+            None => "Receipts".to_string(),
+        };
+        rows.push(
+            Row::new(vec![label, session.receipts.to_string()])
+                .style(Style::default().fg(Color::Magenta)),
+        );
+    }
     if state.info_count > 0 {
         rows.push(
             Row::new(vec!["Info".to_string(), state.info_count.to_string()])
@@ -441,7 +597,7 @@ fn render_counts(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppSt
 
     // Add total row
     let total = state.total_message_count()
-        + state.sent_count
+        + session.sent
         + state.info_count
         + state.warning_count
         + state.error_count;
@@ -470,26 +626,53 @@ fn render_counts(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppSt
 // - Add Home/End keys to jump to top/bottom
 // - Consider vim-style j/k navigation
 // - Add search/filter functionality
-fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppState) {
-    let header_hint = if state.show_headers {
-        "[^H] hide headers"
-    } else {
-        "[^H] show headers"
+fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &AppState, keymap: &KeyMap) {
+    let header_hint = format!(
+        "[{}] {} headers",
+        keymap.label(Action::ToggleHeaders),
+        if state.show_headers { "hide" } else { "show" }
+    );
+
+    let pause_hint = match state.paused {
+        Some(_) => format!(
+            "PAUSED (+{}) [{}] resume",
+            state.paused_missed,
+            keymap.label(Action::TogglePause)
+        ),
+        None => format!("[{}] pause", keymap.label(Action::TogglePause)),
     };
 
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .title(format!(" Messages {} ", header_hint));
+    let search_hint = match &state.search {
+        Some(search) => format!("/{} {} [n/N] ", search.query, search.counter()),
+        None => String::new(),
+    };
+
+    let mut block = pane_block(state, Pane::Messages).title(format!(
+        " Messages {} {} {}[{}] help ",
+        header_hint,
+        pause_hint,
+        search_hint,
+        keymap.label(Action::Help)
+    ));
+    if state.paused.is_some() {
+        block = block.border_style(Style::default().fg(Color::Yellow));
+    }
 
     let inner = block.inner(area);
     f.render_widget(block, area);
 
     // Calculate visible messages
     let visible_height = inner.height as usize;
-    let total_messages = state.messages.len();
-
-    // Auto-scroll to bottom unless user has scrolled up
-    let scroll_offset = if state.scroll_offset == 0 && total_messages > visible_height {
+    let messages = state.visible_messages();
+    let total_messages = messages.len();
+
+    // Auto-scroll to bottom unless user has scrolled up, or the current
+    // search match is the first message
+    let current_match = state.search.as_ref().and_then(Search::current_message);
+    let scroll_offset = if state.scroll_offset == 0
+        && total_messages > visible_height
+        && current_match != Some(0)
+    {
         total_messages.saturating_sub(visible_height)
     } else {
         state.scroll_offset
@@ -497,7 +680,7 @@ fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::App
 
     let mut lines: Vec<Line> = Vec::new();
 
-    for (i, msg) in state.messages.iter().enumerate() {
+    for (i, msg) in messages.iter().enumerate() {
         if i < scroll_offset {
             continue;
         }
@@ -506,10 +689,16 @@ fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::App
         }
 
         let time = msg.timestamp.format("%H:%M:%S").to_string();
+        let search = state.search.as_ref().filter(|s| s.matches(i));
+        let time_style = if current_match == Some(i) {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
 
         // Color and style based on message type
         let (dest_style, body_style, max_body_len) = match msg.destination.as_str() {
-            "ERROR" | "BROKER ERROR" => (
+            "ERROR" | "BROKER ERROR" | "FAILED" => (
                 Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
                 Style::default().fg(Color::Red),
                 200, // Show more of error messages
@@ -525,6 +714,11 @@ fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::App
                 80,
             ),
             "SENT" => (Style::default().fg(Color::Blue), Style::default(), 60),
+            "RECEIPT" => (
+                Style::default().fg(Color::Magenta),
+                Style::default().fg(Color::DarkGray),
+                80,
+            ),
             _ => (Style::default().fg(Color::Cyan), Style::default(), 60),
         };
 
@@ -541,13 +735,11 @@ fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::App
             msg.body.clone()
         };
 
-        lines.push(Line::from(vec![
-            Span::styled(time, Style::default().fg(Color::DarkGray)),
-            Span::raw(" ["),
-            Span::styled(dest_display, dest_style),
-            Span::raw("] "),
-            Span::styled(body_display, body_style),
-        ]));
+        let mut spans = vec![Span::styled(time, time_style), Span::raw(" [")];
+        spans.extend(highlighted(dest_display, dest_style, search));
+        spans.push(Span::raw("] "));
+        spans.extend(highlighted(body_display, body_style, search));
+        lines.push(Line::from(spans));
 
         // Show headers if toggled
         if state.show_headers && !msg.headers.is_empty() {
@@ -561,10 +753,11 @@ fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::App
                 } else {
                     header_line
                 };
-                lines.push(Line::from(vec![Span::styled(
+                lines.push(Line::from(highlighted(
                     truncated,
                     Style::default().fg(Color::DarkGray),
-                )]));
+                    search,
+                )));
             }
         }
     }
@@ -573,12 +766,35 @@ fn render_messages(f: &mut ratatui::Frame, area: Rect, state: &super::state::App
     f.render_widget(paragraph, inner);
 }
 
-fn render_errors(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppState) {
-    let block = Block::default()
-        .borders(Borders::ALL)
+/// `text` in `style`, with the search's matches in it highlighted
+fn highlighted(text: String, style: Style, search: Option<&Search>) -> Vec<Span<'static>> {
+    let ranges = search.map(|s| s.ranges(&text)).unwrap_or_default();
+    if ranges.is_empty() {
+        return vec![Span::styled(text, style)];
+    }
+    let match_style = style.bg(Color::Yellow).fg(Color::Black);
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for range in ranges {
+        if range.start > pos {
+            spans.push(Span::styled(text[pos..range.start].to_string(), style));
+        }
+        spans.push(Span::styled(text[range.clone()].to_string(), match_style));
+        pos = range.end;
+    }
+    if pos < text.len() {
+        spans.push(Span::styled(text[pos..].to_string(), style));
+    }
+    spans
+}
+
+fn render_errors(f: &mut ratatui::Frame, area: Rect, state: &AppState, keymap: &KeyMap) {
+    let block = pane_block(state, Pane::Errors)
         .title(format!(
-            " Broker Errors ({}) [^E/^D scroll] ",
-            state.errors.len()
+            " Broker Errors ({}) [{}/{} scroll] ",
+            state.errors.len(),
+            keymap.label(Action::ErrorScrollUp),
+            keymap.label(Action::ErrorScrollDown)
         ))
         .style(Style::default().fg(Color::Red));
 
@@ -684,7 +900,7 @@ fn render_errors(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppSt
     f.render_widget(paragraph, inner);
 }
 
-fn render_input(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppState) {
+fn render_input(f: &mut ratatui::Frame, area: Rect, state: &AppState) {
     let input_text = format!("> {}", state.input);
 
     let input = Paragraph::new(input_text.as_str())
@@ -701,13 +917,116 @@ fn render_input(f: &mut ratatui::Frame, area: Rect, state: &super::state::AppSta
     }
 }
 
+/// Centered popup listing the current key bindings
+fn render_help(f: &mut ratatui::Frame, area: Rect, keymap: &KeyMap) {
+    let mut rows: Vec<Row> = keymap
+        .help_rows()
+        .into_iter()
+        .map(|(keys, description)| Row::new(vec![keys, description.to_string()]))
+        .collect();
+    rows.push(Row::new(vec![
+        "Ctrl+C".to_string(),
+        "Quit (always bound)".to_string(),
+    ]));
+    rows.push(Row::new(vec![
+        "?".to_string(),
+        "Show this help (when the input is empty)".to_string(),
+    ]));
+    rows.push(Row::new(vec![
+        "/text, /re:regex".to_string(),
+        "Search messages (typed as a command)".to_string(),
+    ]));
+    rows.push(Row::new(vec![
+        "n / N".to_string(),
+        "Older / newer match (when the input is empty)".to_string(),
+    ]));
+    rows.push(Row::new(vec![
+        "Esc".to_string(),
+        "End the search (when the input is empty)".to_string(),
+    ]));
+
+    let width = area.width.min(72);
+    let height = area.height.min(rows.len() as u16 + 4);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let widths = [Constraint::Length(24), Constraint::Min(10)];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Key", "Action"])
+                .style(Style::default().add_modifier(Modifier::BOLD))
+                .bottom_margin(1),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Key bindings (any key to close) "),
+        );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(table, popup);
+}
+
+/// Centered popup listing bookmarks, with the selected one highlighted
+fn render_bookmarks(f: &mut ratatui::Frame, area: Rect, state: &AppState, selected: usize) {
+    let mut rows: Vec<Row> = state
+        .bookmarks
+        .iter()
+        .enumerate()
+        .map(|(i, (name, destination))| {
+            let row = Row::new(vec![format!("@{}", name), destination.to_string()]);
+            if i == selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![
+            String::new(),
+            "No bookmarks yet: bookmark <name> <destination>".to_string(),
+        ]));
+    }
+
+    let width = area.width.min(80);
+    let height = area.height.min(rows.len() as u16 + 4);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let widths = [Constraint::Length(20), Constraint::Min(10)];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Name", "Destination"])
+                .style(Style::default().add_modifier(Modifier::BOLD))
+                .bottom_margin(1),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Bookmarks (Enter to insert, Esc to close) "),
+        );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(table, popup);
+}
+
 /// Subscribe to a destination and spawn a message handler task
 async fn subscribe_destination(
     conn: &Connection,
     dest: &str,
     state: SharedState,
 ) -> Result<(), (String, u8)> {
-    let sub = conn.subscribe(dest, AckMode::Auto).await.map_err(|e| {
+    let ack_mode = state.lock().await.ack_mode;
+    let sub = conn.subscribe(dest, ack_mode).await.map_err(|e| {
         (
             format!("Failed to subscribe to '{}': {}", dest, e),
             super::exit_codes::PROTOCOL_ERROR,
@@ -717,7 +1036,7 @@ async fn subscribe_destination(
     // Register in state
     {
         let mut s = state.lock().await;
-        s.register_subscription(dest);
+        s.register_subscription(dest, sub.id());
     }
 
     // Spawn a task to receive incoming messages for this subscription
@@ -735,15 +1054,7 @@ async fn subscribe_destination(
 
 /// Handle an incoming message
 async fn handle_message(dest: &str, frame: &Frame, state: SharedState) {
-    // Extract body
-    let body = if frame.body.is_empty() {
-        String::new()
-    } else {
-        match std::str::from_utf8(&frame.body) {
-            Ok(s) => s.to_string(),
-            Err(_) => format!("({} bytes, binary)", frame.body.len()),
-        }
-    };
+    let body = frame.body_preview(BODY_PREVIEW_LEN);
 
     // Record in state
     let mut s = state.lock().await;
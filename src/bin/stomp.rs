@@ -3,14 +3,18 @@ use std::process::ExitCode;
 
 mod cli;
 
-use cli::args::Cli;
+use cli::args::{Cli, Command};
 use cli::exit_codes;
 
 #[tokio::main]
 async fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    let result = if cli.tui {
+    let result = if let Some(Command::Ping(args)) = &cli.command {
+        cli::ping::run(&cli, args).await
+    } else if cli.pipe {
+        cli::pipe::run(&cli).await
+    } else if cli.tui {
         cli::tui::run(&cli).await
     } else {
         cli::plain::run(&cli).await
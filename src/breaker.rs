@@ -0,0 +1,212 @@
+//! Circuit breaker for reconnects that keep failing.
+//!
+//! See `ConnectOptions::circuit_breaker()`.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Notify, mpsc};
+
+use crate::events::{self, ConnectionEvent};
+
+/// When to stop reconnecting for a while, set with
+/// `ConnectOptions::circuit_breaker()`.
+///
+/// A broker that accepts the TCP connection but rejects or drops every
+/// session (bad credentials, a protocol mismatch, a broker shutting down)
+/// would otherwise be retried every 1 to 30 seconds forever. Once
+/// `failures` sessions have failed within `window`, the circuit opens: the
+/// connection emits `ConnectionEvent::CircuitOpened` and makes no attempt
+/// for `cool_down`, or until `Connection::retry_now()` is called. It then
+/// emits `ConnectionEvent::CircuitClosed` and tries again; another
+/// `failures` failures open it again.
+///
+/// A failed session is a handshake that did not complete, or a session
+/// that ended before it was stable (up for at least 5 seconds, or longer
+/// than the current backoff). A broker that cannot be reached at all does
+/// not count; reconnects back off to 30 seconds as usual. A stable session
+/// clears the count.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::{CircuitBreaker, ConnectOptions};
+/// use std::time::Duration;
+///
+/// // 5 failed sessions within a minute: pause for 10 minutes
+/// let options = ConnectOptions::default().circuit_breaker(CircuitBreaker::new(
+///     5,
+///     Duration::from_secs(60),
+///     Duration::from_secs(600),
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    /// Failed sessions within `window` that open the circuit.
+    pub failures: u32,
+    /// How far back failures are counted.
+    pub window: Duration,
+    /// How long the circuit stays open.
+    pub cool_down: Duration,
+}
+
+impl CircuitBreaker {
+    /// Open the circuit for `cool_down` after `failures` failed sessions
+    /// within `window`.
+    pub fn new(failures: u32, window: Duration, cool_down: Duration) -> Self {
+        Self {
+            failures,
+            window,
+            cool_down,
+        }
+    }
+}
+
+/// Whether the circuit is open, shared by the background task and
+/// `Connection::retry_now()`.
+#[derive(Debug, Default)]
+pub(crate) struct CircuitSwitch {
+    open: AtomicBool,
+    retry: Notify,
+}
+
+impl CircuitSwitch {
+    pub(crate) fn is_open(&self) -> bool {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    /// End the cool-down now. Returns whether the circuit was open.
+    pub(crate) fn retry_now(&self) -> bool {
+        if !self.is_open() {
+            return false;
+        }
+        self.retry.notify_waiters();
+        true
+    }
+}
+
+/// The background task's side of the circuit breaker: the failures within
+/// the window.
+pub(crate) struct Circuit {
+    config: Option<CircuitBreaker>,
+    failures: VecDeque<Instant>,
+    switch: Arc<CircuitSwitch>,
+}
+
+impl Circuit {
+    pub(crate) fn new(config: Option<CircuitBreaker>, switch: Arc<CircuitSwitch>) -> Self {
+        Self {
+            config,
+            failures: VecDeque::new(),
+            switch,
+        }
+    }
+
+    /// Wait before the next reconnect attempt. A failed session is counted
+    /// and, if it opens the circuit, the wait is the cool-down instead of
+    /// `backoff`; anything else clears the count.
+    pub(crate) async fn pause(
+        &mut self,
+        backoff: Duration,
+        failed: bool,
+        event_tx: &Option<mpsc::Sender<ConnectionEvent>>,
+    ) {
+        let Some(config) = self.config else {
+            tokio::time::sleep(backoff).await;
+            return;
+        };
+        if !failed {
+            self.failures.clear();
+            tokio::time::sleep(backoff).await;
+            return;
+        }
+        let now = Instant::now();
+        self.failures.push_back(now);
+        while self
+            .failures
+            .front()
+            .is_some_and(|&at| now.duration_since(at) > config.window)
+        {
+            self.failures.pop_front();
+        }
+        if (self.failures.len() as u32) < config.failures {
+            tokio::time::sleep(backoff).await;
+            return;
+        }
+
+        let failures = self.failures.len() as u32;
+        self.failures.clear();
+        // Created before the circuit is seen open, so `retry_now()` cannot
+        // slip in between
+        let retry = self.switch.retry.notified();
+        self.switch.open.store(true, Ordering::SeqCst);
+        tracing::warn!(
+            failures,
+            cool_down_secs = config.cool_down.as_secs(),
+            "circuit breaker open: {} failed sessions, pausing reconnects",
+            failures,
+        );
+        events::emit(
+            event_tx,
+            ConnectionEvent::CircuitOpened {
+                failures,
+                cool_down: config.cool_down,
+            },
+        );
+        let manual = tokio::select! {
+            _ = tokio::time::sleep(config.cool_down) => false,
+            _ = retry => true,
+        };
+        self.switch.open.store(false, Ordering::SeqCst);
+        tracing::info!(manual, "circuit breaker closed, reconnecting");
+        events::emit(event_tx, ConnectionEvent::CircuitClosed { manual });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit(failures: u32) -> (Circuit, Arc<CircuitSwitch>) {
+        let switch = Arc::new(CircuitSwitch::default());
+        let config =
+            CircuitBreaker::new(failures, Duration::from_secs(60), Duration::from_secs(3600));
+        (Circuit::new(Some(config), switch.clone()), switch)
+    }
+
+    #[tokio::test]
+    async fn stable_session_clears_failures() {
+        let (mut circuit, _) = circuit(2);
+        circuit.pause(Duration::ZERO, true, &None).await;
+        circuit.pause(Duration::ZERO, false, &None).await;
+        assert!(circuit.failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn retry_now_ends_cool_down() {
+        let (mut circuit, switch) = circuit(1);
+        let (tx, mut rx) = mpsc::channel(4);
+        let events = Some(tx);
+        let pause = tokio::spawn(async move {
+            circuit.pause(Duration::ZERO, true, &events).await;
+        });
+        assert_eq!(
+            rx.recv().await,
+            Some(ConnectionEvent::CircuitOpened {
+                failures: 1,
+                cool_down: Duration::from_secs(3600),
+            })
+        );
+        assert!(switch.is_open());
+        assert!(switch.retry_now());
+        assert_eq!(
+            rx.recv().await,
+            Some(ConnectionEvent::CircuitClosed { manual: true })
+        );
+        pause.await.unwrap();
+        assert!(!switch.is_open());
+        assert!(!switch.retry_now());
+    }
+}
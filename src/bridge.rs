@@ -0,0 +1,376 @@
+//! Relaying messages from one connection to another.
+//!
+//! A [`Bridge`] subscribes to a destination on a source connection and
+//! republishes each message to a destination on a target connection,
+//! for example while migrating from one broker to another or to merge
+//! several brokers' traffic into one. A message is ACKed on the source only
+//! once it has been forwarded, so a failure leaves it with the source
+//! broker to redeliver.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::connection::{AckMode, ConnError, Connection, NackOptions};
+use crate::frame::Frame;
+use crate::message::Message;
+
+/// Headers of a MESSAGE that describe its delivery on the source broker
+/// and are not forwarded.
+const SOURCE_HEADERS: &[&str] = &[
+    "destination",
+    "message-id",
+    "subscription",
+    "ack",
+    "content-length",
+];
+
+type Filter = Arc<dyn Fn(&Message) -> bool + Send + Sync>;
+
+/// A change applied to the headers of every forwarded message.
+#[derive(Debug, Clone)]
+enum HeaderMapping {
+    Rename { from: String, to: String },
+    Drop(String),
+    Set { name: String, value: String },
+}
+
+impl HeaderMapping {
+    fn apply(&self, headers: &mut Vec<(String, String)>) {
+        match self {
+            HeaderMapping::Rename { from, to } => {
+                for (name, _) in headers.iter_mut().filter(|(name, _)| name == from) {
+                    name.clone_from(to);
+                }
+            }
+            HeaderMapping::Drop(drop) => headers.retain(|(name, _)| name != drop),
+            HeaderMapping::Set { name, value } => {
+                headers.retain(|(n, _)| n != name);
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+    }
+}
+
+/// Counts of what a [`Bridge`] did, returned by [`Bridge::metrics()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BridgeMetrics {
+    /// Messages published to the target and ACKed on the source.
+    pub forwarded: u64,
+    /// Messages rejected by the filter, ACKed without being forwarded.
+    pub skipped: u64,
+    /// Messages that could not be forwarded and were NACKed on the source,
+    /// or that had no `message-id` to settle them with.
+    pub failed: u64,
+    /// Body bytes of the forwarded messages.
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct BridgeCounters {
+    forwarded: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Forwards messages from a destination on one connection to a destination
+/// on another.
+///
+/// [`run`](Self::run) subscribes to the source destination in
+/// `client-individual` ack mode and, for each message:
+///
+/// 1. ACKs it without forwarding if the [`filter`](Self::filter) rejects it,
+/// 2. builds a SEND frame for the target destination with the message's
+///    body and headers, minus the ones describing its delivery on the
+///    source (`destination`, `message-id`, `subscription`, `ack` and
+///    `content-length`), after applying the header mappings,
+/// 3. publishes it on the target connection, waiting for the broker's
+///    RECEIPT if [`confirm`](Self::confirm) is set,
+/// 4. ACKs it on the source once published, or NACKs it with the
+///    [`failure_nack`](Self::failure_nack) options if publishing failed.
+///
+/// Header mappings are applied in the order they were added, before the
+/// source headers are removed, so renaming `message-id` keeps the original
+/// id on the forwarded message.
+///
+/// Messages are forwarded one at a time, in order. Delivery is
+/// at-least-once: a message forwarded just before the source connection
+/// drops is redelivered by the source broker and forwarded again. Without
+/// `confirm`, "published" means queued on the target connection, so a
+/// message can also be lost if the target connection fails before writing
+/// it.
+///
+/// Counters are shared between clones of a bridge, so a clone kept aside
+/// reports on a running one.
+///
+/// # Example
+///
+/// ```ignore
+/// use iridium_stomp::{Bridge, Connection};
+/// use std::time::Duration;
+///
+/// let old = Connection::connect("old-broker:61613", "guest", "guest", "10000,10000").await?;
+/// let new = Connection::connect("new-broker:61613", "guest", "guest", "10000,10000").await?;
+///
+/// let bridge = Bridge::new("/queue/orders", "/queue/orders.v2")
+///     .rename_header("message-id", "x-original-message-id")
+///     .set_header("x-migrated-from", "old-broker")
+///     .filter(|msg| msg.header("type") != Some("heartbeat"))
+///     .confirm(Duration::from_secs(5));
+///
+/// let metrics = bridge.clone();
+/// tokio::spawn(async move { bridge.run(&old, &new).await });
+/// // later
+/// println!("forwarded {}", metrics.metrics().forwarded);
+/// ```
+#[derive(Clone)]
+pub struct Bridge {
+    source: String,
+    target: String,
+    subscribe_headers: Vec<(String, String)>,
+    mappings: Vec<HeaderMapping>,
+    filter: Option<Filter>,
+    confirm: Option<Duration>,
+    failure_nack: NackOptions,
+    counters: Arc<BridgeCounters>,
+}
+
+impl Bridge {
+    /// Create a bridge from `source` on the source connection to `target`
+    /// on the target connection.
+    pub fn new(source: &str, target: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            target: target.to_string(),
+            subscribe_headers: Vec::new(),
+            mappings: Vec::new(),
+            filter: None,
+            confirm: None,
+            failure_nack: NackOptions::default(),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Add a header to the SUBSCRIBE on the source, such as a `selector`
+    /// that leaves unwanted messages with the source broker.
+    pub fn subscribe_header(mut self, name: &str, value: &str) -> Self {
+        self.subscribe_headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Forward the `from` header as `to`.
+    pub fn rename_header(mut self, from: &str, to: &str) -> Self {
+        self.mappings.push(HeaderMapping::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+        self
+    }
+
+    /// Do not forward the `name` header.
+    pub fn drop_header(mut self, name: &str) -> Self {
+        self.mappings.push(HeaderMapping::Drop(name.to_string()));
+        self
+    }
+
+    /// Forward every message with `name` set to `value`, replacing the
+    /// message's own `name` header if it has one.
+    pub fn set_header(mut self, name: &str, value: &str) -> Self {
+        self.mappings.push(HeaderMapping::Set {
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Forward only the messages for which `filter` returns `true`. The
+    /// others are ACKed on the source and counted as skipped.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Message) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Request a RECEIPT for every forwarded message and ACK it on the
+    /// source only once the receipt arrives within `timeout`. Off by
+    /// default.
+    pub fn confirm(mut self, timeout: Duration) -> Self {
+        self.confirm = Some(timeout);
+        self
+    }
+
+    /// NACK messages that could not be forwarded with `options`. The
+    /// default requeues them, as a failed publish is usually down to the
+    /// target being unavailable for a while; pass `requeue: false` to have
+    /// the source broker dead-letter them instead of redelivering them
+    /// until the target accepts them.
+    pub fn failure_nack(mut self, options: NackOptions) -> Self {
+        self.failure_nack = options;
+        self
+    }
+
+    /// Counts of messages handled so far by this bridge and its clones.
+    pub fn metrics(&self) -> BridgeMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        BridgeMetrics {
+            forwarded: load(&self.counters.forwarded),
+            skipped: load(&self.counters.skipped),
+            failed: load(&self.counters.failed),
+            bytes: load(&self.counters.bytes),
+        }
+    }
+
+    /// Forward messages from `source` to `target` until either connection
+    /// is closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of a failed subscribe on `source`.
+    pub async fn run(&self, source: &Connection, target: &Connection) -> Result<(), ConnError> {
+        let mut source_closed = source.shutdown_signal();
+        let mut target_closed = target.shutdown_signal();
+        let mut sub = source
+            .subscribe_with_headers(
+                &self.source,
+                AckMode::ClientIndividual,
+                self.subscribe_headers.clone(),
+            )
+            .await?;
+        loop {
+            let frame = tokio::select! {
+                frame = sub.recv() => match frame {
+                    Some(frame) => frame,
+                    None => break,
+                },
+                _ = source_closed.recv() => break,
+                _ = target_closed.recv() => break,
+            };
+            let msg = Message::from_frame(frame)
+                .with_subscription_destination(sub.destination_name().clone());
+            let Some(message_id) = msg.message_id().map(str::to_string) else {
+                tracing::warn!(
+                    source = %self.source,
+                    "MESSAGE without message-id cannot be acknowledged, not forwarding",
+                );
+                self.counters.failed.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            if self.filter.as_ref().is_some_and(|filter| !filter(&msg)) {
+                self.counters.skipped.fetch_add(1, Ordering::Relaxed);
+                if let Err(e) = sub.ack(&message_id).await {
+                    tracing::warn!(
+                        source = %self.source,
+                        message_id = %message_id,
+                        error = %e,
+                        "failed to ACK skipped message",
+                    );
+                }
+                continue;
+            }
+
+            let frame = self.forward_frame(msg.into_frame());
+            let bytes = frame.body.len() as u64;
+            let published = match self.confirm {
+                Some(timeout) => target.send_frame_confirmed(frame, timeout).await,
+                None => target.send_frame(frame).await,
+            };
+            let settled = match published {
+                Ok(()) => {
+                    self.counters.forwarded.fetch_add(1, Ordering::Relaxed);
+                    self.counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+                    sub.ack(&message_id).await
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        source = %self.source,
+                        target = %self.target,
+                        message_id = %message_id,
+                        error = %e,
+                        "failed to forward message, sending NACK",
+                    );
+                    self.counters.failed.fetch_add(1, Ordering::Relaxed);
+                    sub.nack_with_options(&message_id, self.failure_nack.clone())
+                        .await
+                }
+            };
+            if let Err(e) = settled {
+                tracing::warn!(
+                    source = %self.source,
+                    message_id = %message_id,
+                    error = %e,
+                    "failed to settle bridged message",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The SEND frame that forwards `message` to the target destination.
+    fn forward_frame(&self, message: Frame) -> Frame {
+        let mut headers = message.headers;
+        for mapping in &self.mappings {
+            mapping.apply(&mut headers);
+        }
+        headers.retain(|(name, _)| !SOURCE_HEADERS.contains(&name.as_str()));
+        let mut frame = Frame::new("SEND")
+            .header("destination", &self.target)
+            .set_body(message.body);
+        frame.headers.extend(headers);
+        frame
+    }
+}
+
+impl std::fmt::Debug for Bridge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bridge")
+            .field("source", &self.source)
+            .field("target", &self.target)
+            .field("subscribe_headers", &self.subscribe_headers)
+            .field("mappings", &self.mappings)
+            .field("filter", &self.filter.is_some())
+            .field("confirm", &self.confirm)
+            .field("failure_nack", &self.failure_nack)
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_frame_maps_headers_and_drops_source_headers() {
+        let bridge = Bridge::new("/queue/old", "/queue/new")
+            .rename_header("message-id", "x-original-id")
+            .drop_header("x-internal")
+            .set_header("priority", "9");
+        let message = Frame::new("MESSAGE")
+            .header("destination", "/queue/old")
+            .header("message-id", "m1")
+            .header("subscription", "sub-1")
+            .header("ack", "a1")
+            .header("content-type", "text/plain")
+            .header("x-internal", "yes")
+            .header("priority", "4")
+            .header("content-length", "5")
+            .set_body("hello");
+
+        let send = bridge.forward_frame(message);
+        assert_eq!(send.command, "SEND");
+        assert_eq!(
+            send.headers,
+            vec![
+                ("destination".to_string(), "/queue/new".to_string()),
+                ("x-original-id".to_string(), "m1".to_string()),
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("priority".to_string(), "9".to_string()),
+            ]
+        );
+        assert_eq!(send.body, b"hello");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::subscription::{PendingOverflow, SubscriptionOptions};
+
+/// Identifies the broker family a connection talks to.
+///
+/// STOMP leaves many features (durable and shared subscriptions, destination
+/// naming) to the broker. Helpers that need broker-specific conventions,
+/// such as `Connection::subscribe_shared()`, take a `BrokerProfile` to pick
+/// the right mechanism.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum BrokerProfile {
+    /// No broker-specific conventions; only portable STOMP features.
+    #[default]
+    Generic,
+    /// RabbitMQ with the STOMP plugin.
+    RabbitMq,
+    /// ActiveMQ Classic.
+    ActiveMq,
+    /// ActiveMQ Artemis.
+    Artemis,
+}
+
+impl BrokerProfile {
+    /// Build the subscription for a member of consumer group `group` reading
+    /// the topic `destination`.
+    ///
+    /// All subscribers using the same `group` and `destination` share one
+    /// broker-side queue, so each message is delivered to exactly one member
+    /// of the group:
+    ///
+    /// - **RabbitMQ**: subscribes to the `/topic/...` destination with an
+    ///   `x-queue-name` of `<group>.<topic>`, declared durable and not
+    ///   auto-deleted, so members attach to the same queue bound to
+    ///   `amq.topic`.
+    /// - **ActiveMQ**: uses virtual topics. `/topic/VirtualTopic.<name>` is
+    ///   consumed from `/queue/Consumer.<group>.VirtualTopic.<name>`.
+    /// - **Artemis**: uses a fully qualified queue name,
+    ///   `<destination>::<group>`, on a multicast address.
+    ///
+    /// Returns a description of the problem when the broker has no shared
+    /// subscription mechanism (`Generic`) or the destination does not fit the
+    /// broker's convention.
+    pub(crate) fn shared_subscription(
+        &self,
+        group: &str,
+        destination: &str,
+    ) -> Result<SubscriptionOptions, String> {
+        if group.is_empty() {
+            return Err("consumer group name must not be empty".into());
+        }
+
+        match self {
+            BrokerProfile::Generic => Err(
+                "shared subscriptions are broker-specific; choose a BrokerProfile other than Generic"
+                    .into(),
+            ),
+            BrokerProfile::RabbitMq => {
+                let topic = destination.strip_prefix("/topic/").ok_or_else(|| {
+                    format!(
+                        "RabbitMQ shared subscriptions require a /topic/ destination, got '{}'",
+                        destination
+                    )
+                })?;
+                Ok(SubscriptionOptions {
+                    headers: vec![
+                        ("x-queue-name".to_string(), format!("{}.{}", group, topic)),
+                        ("durable".to_string(), "true".to_string()),
+                        ("auto-delete".to_string(), "false".to_string()),
+                    ],
+                    durable_queue: None,
+                    ordered: false,
+                    max_pending: None,
+                    pending_overflow: PendingOverflow::default(),
+                })
+            }
+            BrokerProfile::ActiveMq => {
+                let topic = destination
+                    .strip_prefix("/topic/")
+                    .filter(|t| t.starts_with("VirtualTopic."))
+                    .ok_or_else(|| {
+                        format!(
+                            "ActiveMQ shared subscriptions require a /topic/VirtualTopic.* destination, got '{}'",
+                            destination
+                        )
+                    })?;
+                Ok(SubscriptionOptions {
+                    headers: Vec::new(),
+                    durable_queue: Some(format!("/queue/Consumer.{}.{}", group, topic)),
+                    ordered: false,
+                    max_pending: None,
+                    pending_overflow: PendingOverflow::default(),
+                })
+            }
+            BrokerProfile::Artemis => Ok(SubscriptionOptions {
+                headers: vec![("subscription-type".to_string(), "MULTICAST".to_string())],
+                durable_queue: Some(format!("{}::{}", destination, group)),
+                ordered: false,
+                max_pending: None,
+                pending_overflow: PendingOverflow::default(),
+            }),
+        }
+    }
+
+    /// Describe how to consume from a temporary reply queue called `name`.
+    ///
+    /// - **RabbitMQ**: `/temp-queue/<name>`. The broker creates the queue
+    ///   when it first appears in a `reply-to` header and delivers replies
+    ///   with `subscription:/temp-queue/<name>`; no SUBSCRIBE is sent.
+    /// - **ActiveMQ**: subscribes to `/temp-queue/<name>`, a temporary
+    ///   destination that lives as long as the connection.
+    /// - **Artemis**: subscribes to an auto-created anycast queue `<name>`,
+    ///   which the broker deletes once its last consumer leaves.
+    ///
+    /// Returns a description of the problem for `Generic`, which has no
+    /// portable temporary destinations.
+    pub(crate) fn temp_queue(&self, name: &str) -> Result<TempQueue, String> {
+        match self {
+            BrokerProfile::Generic => Err(
+                "temporary queues are broker-specific; choose a BrokerProfile other than Generic"
+                    .into(),
+            ),
+            BrokerProfile::RabbitMq => Ok(TempQueue {
+                destination: format!("/temp-queue/{}", name),
+                headers: Vec::new(),
+                implicit: true,
+            }),
+            BrokerProfile::ActiveMq => Ok(TempQueue {
+                destination: format!("/temp-queue/{}", name),
+                headers: Vec::new(),
+                implicit: false,
+            }),
+            BrokerProfile::Artemis => Ok(TempQueue {
+                destination: name.to_string(),
+                headers: vec![("subscription-type".to_string(), "ANYCAST".to_string())],
+                implicit: false,
+            }),
+        }
+    }
+
+    /// SUBSCRIBE headers limiting the broker to `count` unacknowledged
+    /// messages in flight to the subscriber.
+    ///
+    /// - **RabbitMQ**: `prefetch-count`.
+    /// - **ActiveMQ**: `activemq.prefetchSize`.
+    ///
+    /// Returns a description of the problem for `Generic`, and for Artemis,
+    /// whose STOMP consumers are limited by bytes (`consumer-window-size`)
+    /// rather than messages.
+    pub(crate) fn prefetch_headers(&self, count: u32) -> Result<Vec<(String, String)>, String> {
+        let name = match self {
+            BrokerProfile::RabbitMq => "prefetch-count",
+            BrokerProfile::ActiveMq => "activemq.prefetchSize",
+            BrokerProfile::Artemis => {
+                return Err(
+                    "Artemis limits STOMP consumers by bytes; set the consumer-window-size header instead"
+                        .into(),
+                );
+            }
+            BrokerProfile::Generic => {
+                return Err(
+                    "prefetch is broker-specific; choose a BrokerProfile other than Generic".into(),
+                );
+            }
+        };
+        Ok(vec![(name.to_string(), count.to_string())])
+    }
+
+    /// SUBSCRIBE headers that make a topic subscription durable under
+    /// `name`, so messages published while the subscriber is away are kept
+    /// for it.
+    ///
+    /// - **RabbitMQ**: a durable, not auto-deleted queue named `name`
+    ///   (`x-queue-name`).
+    /// - **ActiveMQ**: `activemq.subscriptionName`; the connection also
+    ///   needs a `client-id` (`ConnectOptions::client_id()`).
+    /// - **Artemis**: `durable-subscription-name`; the connection also
+    ///   needs a `client-id`.
+    ///
+    /// Returns a description of the problem for `Generic` or an empty name.
+    pub(crate) fn durable_subscription_headers(
+        &self,
+        name: &str,
+    ) -> Result<Vec<(String, String)>, String> {
+        if name.is_empty() {
+            return Err("durable subscription name must not be empty".into());
+        }
+        let header = |k: &str, v: &str| (k.to_string(), v.to_string());
+        match self {
+            BrokerProfile::Generic => Err(
+                "durable subscriptions are broker-specific; choose a BrokerProfile other than Generic"
+                    .into(),
+            ),
+            BrokerProfile::RabbitMq => Ok(vec![
+                header("x-queue-name", name),
+                header("durable", "true"),
+                header("auto-delete", "false"),
+            ]),
+            BrokerProfile::ActiveMq => Ok(vec![header("activemq.subscriptionName", name)]),
+            BrokerProfile::Artemis => Ok(vec![header("durable-subscription-name", name)]),
+        }
+    }
+}
+
+/// A temporary reply queue as consumed on a particular broker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TempQueue {
+    /// The destination to subscribe to and to put into `reply-to` headers.
+    pub(crate) destination: String,
+    /// Extra SUBSCRIBE headers.
+    pub(crate) headers: Vec<(String, String)>,
+    /// The broker delivers without a SUBSCRIBE frame.
+    pub(crate) implicit: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rabbitmq_uses_named_queue_on_topic() {
+        let opts = BrokerProfile::RabbitMq
+            .shared_subscription("billing", "/topic/orders.created")
+            .unwrap();
+        assert_eq!(opts.durable_queue, None);
+        assert!(opts.headers.contains(&(
+            "x-queue-name".to_string(),
+            "billing.orders.created".to_string()
+        )));
+        assert!(
+            opts.headers
+                .contains(&("auto-delete".to_string(), "false".to_string()))
+        );
+    }
+
+    #[test]
+    fn activemq_maps_virtual_topic_to_consumer_queue() {
+        let opts = BrokerProfile::ActiveMq
+            .shared_subscription("billing", "/topic/VirtualTopic.orders")
+            .unwrap();
+        assert_eq!(
+            opts.durable_queue.as_deref(),
+            Some("/queue/Consumer.billing.VirtualTopic.orders")
+        );
+        assert!(
+            BrokerProfile::ActiveMq
+                .shared_subscription("billing", "/topic/orders")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn artemis_uses_fully_qualified_queue_name() {
+        let opts = BrokerProfile::Artemis
+            .shared_subscription("billing", "orders")
+            .unwrap();
+        assert_eq!(opts.durable_queue.as_deref(), Some("orders::billing"));
+    }
+
+    #[test]
+    fn generic_and_empty_group_are_rejected() {
+        assert!(
+            BrokerProfile::Generic
+                .shared_subscription("billing", "/topic/orders")
+                .is_err()
+        );
+        assert!(
+            BrokerProfile::RabbitMq
+                .shared_subscription("", "/topic/orders")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn temp_queues_follow_broker_conventions() {
+        let rabbit = BrokerProfile::RabbitMq.temp_queue("replies-1").unwrap();
+        assert_eq!(rabbit.destination, "/temp-queue/replies-1");
+        assert!(rabbit.implicit);
+
+        let activemq = BrokerProfile::ActiveMq.temp_queue("replies-1").unwrap();
+        assert_eq!(activemq.destination, "/temp-queue/replies-1");
+        assert!(!activemq.implicit);
+
+        let artemis = BrokerProfile::Artemis.temp_queue("replies-1").unwrap();
+        assert_eq!(artemis.destination, "replies-1");
+        assert!(
+            artemis
+                .headers
+                .contains(&("subscription-type".to_string(), "ANYCAST".to_string()))
+        );
+
+        assert!(BrokerProfile::Generic.temp_queue("replies-1").is_err());
+    }
+
+    #[test]
+    fn prefetch_and_durable_headers_follow_broker_conventions() {
+        assert_eq!(
+            BrokerProfile::RabbitMq.prefetch_headers(10).unwrap(),
+            vec![("prefetch-count".to_string(), "10".to_string())]
+        );
+        assert_eq!(
+            BrokerProfile::ActiveMq.prefetch_headers(10).unwrap(),
+            vec![("activemq.prefetchSize".to_string(), "10".to_string())]
+        );
+        assert!(BrokerProfile::Artemis.prefetch_headers(10).is_err());
+        assert!(BrokerProfile::Generic.prefetch_headers(10).is_err());
+
+        assert_eq!(
+            BrokerProfile::Artemis
+                .durable_subscription_headers("orders")
+                .unwrap(),
+            vec![(
+                "durable-subscription-name".to_string(),
+                "orders".to_string()
+            )]
+        );
+        assert!(
+            BrokerProfile::RabbitMq
+                .durable_subscription_headers("orders")
+                .unwrap()
+                .contains(&("x-queue-name".to_string(), "orders".to_string()))
+        );
+        assert!(
+            BrokerProfile::Generic
+                .durable_subscription_headers("orders")
+                .is_err()
+        );
+        assert!(
+            BrokerProfile::ActiveMq
+                .durable_subscription_headers("")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Queue browsing: read the messages on a queue without consuming them.
+//!
+//! See `Connection::browse()`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::stream::{Stream, StreamExt};
+use tokio::time::{Instant, Sleep};
+
+use crate::diagnostics;
+use crate::frame::Frame;
+use crate::subscription::Subscription;
+
+/// Options for `Connection::browse()`.
+#[derive(Debug, Clone, Default)]
+pub struct BrowseOptions {
+    /// Extra headers for the SUBSCRIBE frame, such as a `selector`.
+    pub headers: Vec<(String, String)>,
+
+    /// Stop after this many messages. Unlimited if `None`.
+    pub max_messages: Option<usize>,
+
+    /// Stop when no message arrives for this long. Needed for brokers that
+    /// do not mark the end of a browse; without it such a browse ends only
+    /// when the connection closes.
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Why a browse ended, returned by [`Browse::end`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowseEnd {
+    /// The broker sent its end-of-browse marker: every message on the
+    /// queue has been seen.
+    Exhausted,
+    /// `BrowseOptions::max_messages` were received.
+    Limit,
+    /// Nothing arrived for `BrowseOptions::idle_timeout`.
+    IdleTimeout,
+    /// The subscription closed, for example because the connection was
+    /// closed.
+    Closed,
+}
+
+/// A finite stream of the messages on a queue, returned by
+/// `Connection::browse()`.
+///
+/// Yields each message once and then `None`; [`end`](Self::end) tells why
+/// it stopped. Browsed messages stay on the queue and are never
+/// acknowledged. The broker-side subscription is removed when the browse
+/// ends or the `Browse` is dropped.
+pub struct Browse {
+    subscription: Option<Subscription>,
+    max_messages: Option<usize>,
+    idle_timeout: Option<Duration>,
+    idle: Option<Pin<Box<Sleep>>>,
+    received: usize,
+    end: Option<BrowseEnd>,
+}
+
+impl Browse {
+    pub(crate) fn new(subscription: Subscription, options: &BrowseOptions) -> Self {
+        Self {
+            subscription: Some(subscription),
+            max_messages: options.max_messages,
+            idle_timeout: options.idle_timeout,
+            idle: options
+                .idle_timeout
+                .map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            received: 0,
+            end: None,
+        }
+    }
+
+    /// Wait for the next message, or `None` once the browse has ended.
+    pub async fn recv(&mut self) -> Option<Frame> {
+        self.next().await
+    }
+
+    /// Why the browse ended, or `None` while it is still running.
+    pub fn end(&self) -> Option<BrowseEnd> {
+        self.end
+    }
+
+    /// Number of messages received so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Record why the browse ended and unsubscribe in the background.
+    fn finish(&mut self, end: BrowseEnd) {
+        self.end.get_or_insert(end);
+        self.unsubscribe();
+    }
+
+    fn unsubscribe(&mut self) {
+        let Some(subscription) = self.subscription.take() else {
+            return;
+        };
+        // Without a runtime (e.g. dropped after it shut down) there is no
+        // connection left to unsubscribe from.
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                let destination = subscription.destination().to_string();
+                if let Err(e) = subscription.unsubscribe().await {
+                    diagnostics::swallowed(
+                        "unsubscribe dropped browse",
+                        Some("UNSUBSCRIBE"),
+                        Some(&destination),
+                        &e,
+                    );
+                }
+            });
+        }
+    }
+}
+
+impl Stream for Browse {
+    type Item = Frame;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Frame>> {
+        // All fields are `Unpin` (the timer is boxed)
+        let this = self.get_mut();
+        if this.max_messages.is_some_and(|max| this.received >= max) {
+            this.finish(BrowseEnd::Limit);
+        }
+        let Some(subscription) = this.subscription.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match Pin::new(subscription).poll_next(cx) {
+            Poll::Ready(Some(frame)) if frame.get_header("browser") == Some("end") => {
+                this.finish(BrowseEnd::Exhausted);
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(frame)) => {
+                this.received += 1;
+                if let (Some(idle), Some(timeout)) = (&mut this.idle, this.idle_timeout) {
+                    idle.as_mut().reset(Instant::now() + timeout);
+                }
+                if this.max_messages.is_some_and(|max| this.received >= max) {
+                    this.finish(BrowseEnd::Limit);
+                }
+                Poll::Ready(Some(frame))
+            }
+            Poll::Ready(None) => {
+                this.finish(BrowseEnd::Closed);
+                Poll::Ready(None)
+            }
+            Poll::Pending => {
+                let idle = this
+                    .idle
+                    .as_mut()
+                    .is_some_and(|idle| idle.as_mut().poll(cx).is_ready());
+                if idle {
+                    this.finish(BrowseEnd::IdleTimeout);
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Browse {
+    fn drop(&mut self) {
+        self.unsubscribe();
+    }
+}
+
+impl std::fmt::Debug for Browse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Browse")
+            .field(
+                "destination",
+                &self.subscription.as_ref().map(Subscription::destination),
+            )
+            .field("received", &self.received)
+            .field("end", &self.end)
+            .finish()
+    }
+}
@@ -0,0 +1,269 @@
+//! Simulated broker misbehavior for testing, enabled with
+//! `ConnectOptions::chaos()`.
+//!
+//! The API is always available, but only takes effect in debug builds or
+//! with the `testing` feature, so it cannot misbehave in a release build by
+//! accident. The connection task wraps the read half of each session in a
+//! `ChaosStream`, which drops, duplicates or delays inbound MESSAGE frames
+//! before they are dispatched. Applications can then check their ACK
+//! handling and idempotency against lost, repeated and late messages
+//! without a proxy in front of the broker.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use futures::stream::Stream;
+use tokio::time::Sleep;
+
+use crate::codec::StompItem;
+use crate::frame::Frame;
+
+/// Which inbound MESSAGE frames to drop, duplicate or delay.
+///
+/// Each rate is a fraction from `0.0` to `1.0` (clamped) and is rolled
+/// independently per message. Dropped messages never reach a subscription
+/// and are not tracked for ACK, as if the broker had lost them; with a
+/// `client` ack mode the broker redelivers them after the next reconnect.
+/// A duplicate is delivered right after the original, with the same
+/// `message-id`.
+///
+/// # Example
+/// ```ignore
+/// let chaos = Chaos::new()
+///     .drop_rate(0.05)
+///     .duplicate_rate(0.02)
+///     .delay(0.1, Duration::from_millis(200))
+///     .seed(42);
+/// let options = ConnectOptions::default().chaos(chaos);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    drop_rate: f64,
+    duplicate_rate: f64,
+    delay_rate: f64,
+    delay: Duration,
+    /// SplitMix64 state, shared by clones so sessions continue the sequence.
+    state: Arc<AtomicU64>,
+}
+
+impl Default for Chaos {
+    fn default() -> Self {
+        use std::hash::BuildHasher;
+        Self {
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            delay_rate: 0.0,
+            delay: Duration::ZERO,
+            state: Arc::new(AtomicU64::new(
+                std::collections::hash_map::RandomState::new().hash_one(()),
+            )),
+        }
+    }
+}
+
+/// Clamp a rate to `0.0..=1.0`, treating NaN as `0.0`.
+fn clamp_rate(rate: f64) -> f64 {
+    if rate.is_nan() {
+        0.0
+    } else {
+        rate.clamp(0.0, 1.0)
+    }
+}
+
+impl Chaos {
+    /// No misbehavior until rates are set; randomly seeded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop `rate` of inbound messages.
+    pub fn drop_rate(mut self, rate: f64) -> Self {
+        self.drop_rate = clamp_rate(rate);
+        self
+    }
+
+    /// Deliver `rate` of inbound messages twice.
+    pub fn duplicate_rate(mut self, rate: f64) -> Self {
+        self.duplicate_rate = clamp_rate(rate);
+        self
+    }
+
+    /// Hold `rate` of inbound messages back for `delay`.
+    ///
+    /// The session reads nothing while a message is held back, so frames
+    /// behind it arrive late too and order is kept, as with a slow network.
+    /// Keep `delay` well below the negotiated heartbeat interval.
+    pub fn delay(mut self, rate: f64, delay: Duration) -> Self {
+        self.delay_rate = clamp_rate(rate);
+        self.delay = delay;
+        self
+    }
+
+    /// Make the sequence of decisions repeatable.
+    pub fn seed(self, seed: u64) -> Self {
+        self.state.store(seed, Ordering::Relaxed);
+        self
+    }
+
+    /// Return `true` with probability `rate`.
+    fn roll(&self, rate: f64) -> bool {
+        if rate == 0.0 {
+            return false;
+        }
+        const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut z = self
+            .state
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // The top 53 bits as a fraction in [0, 1)
+        ((z >> 11) as f64 / (1u64 << 53) as f64) < rate
+    }
+}
+
+/// The read half of a session with `Chaos` applied to MESSAGE frames.
+/// Passes everything through unchanged without one.
+pub(crate) struct ChaosStream<S> {
+    inner: S,
+    chaos: Option<Chaos>,
+    /// Message being held back and when it is released.
+    delayed: Option<(Pin<Box<Sleep>>, Frame)>,
+    /// Copy to deliver after the message just delivered.
+    duplicate: Option<Frame>,
+}
+
+/// Whether `Chaos` takes effect in this build.
+pub(crate) const ENABLED: bool = cfg!(any(debug_assertions, feature = "testing"));
+
+impl<S> ChaosStream<S> {
+    /// Wrap `inner`, ignoring `chaos` in builds where it is inert.
+    pub(crate) fn new(inner: S, chaos: Option<Chaos>) -> Self {
+        Self {
+            inner,
+            chaos: chaos.filter(|_| ENABLED),
+            delayed: None,
+            duplicate: None,
+        }
+    }
+}
+
+impl<S, E> Stream for ChaosStream<S>
+where
+    S: Stream<Item = Result<StompItem, E>> + Unpin,
+{
+    type Item = Result<StompItem, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some((sleep, _)) = &mut this.delayed {
+            ready!(sleep.as_mut().poll(cx));
+            let (_, frame) = this.delayed.take().expect("checked above");
+            return Poll::Ready(Some(Ok(StompItem::Frame(frame))));
+        }
+        if let Some(frame) = this.duplicate.take() {
+            return Poll::Ready(Some(Ok(StompItem::Frame(frame))));
+        }
+        loop {
+            let item = ready!(Pin::new(&mut this.inner).poll_next(cx));
+            let Some(chaos) = &this.chaos else {
+                return Poll::Ready(item);
+            };
+            let frame = match item {
+                Some(Ok(StompItem::Frame(frame))) if frame.command == "MESSAGE" => frame,
+                other => return Poll::Ready(other),
+            };
+            if chaos.roll(chaos.drop_rate) {
+                tracing::debug!(message_id = ?frame.get_header("message-id"), "chaos: dropping message");
+                continue;
+            }
+            if chaos.roll(chaos.duplicate_rate) {
+                tracing::debug!(message_id = ?frame.get_header("message-id"), "chaos: duplicating message");
+                this.duplicate = Some(frame.clone());
+            }
+            if chaos.roll(chaos.delay_rate) {
+                tracing::debug!(message_id = ?frame.get_header("message-id"), "chaos: delaying message");
+                this.delayed = Some((Box::pin(tokio::time::sleep(chaos.delay)), frame));
+                return Pin::new(this).poll_next(cx);
+            }
+            return Poll::Ready(Some(Ok(StompItem::Frame(frame))));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use std::convert::Infallible;
+    use std::time::Instant;
+
+    fn message(id: usize) -> Result<StompItem, Infallible> {
+        Ok(StompItem::Frame(
+            Frame::new("MESSAGE").header("message-id", id.to_string()),
+        ))
+    }
+
+    /// The message ids delivered for `count` inbound messages.
+    async fn delivered(chaos: Chaos, count: usize) -> Vec<String> {
+        let inner = futures::stream::iter((0..count).map(message));
+        ChaosStream::new(inner, Some(chaos))
+            .filter_map(|item| async move {
+                match item {
+                    Ok(StompItem::Frame(f)) => f.get_header("message-id").map(str::to_string),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn drops_and_duplicates_about_the_configured_fraction() {
+        let dropped = delivered(Chaos::new().drop_rate(0.2).seed(1), 10_000).await;
+        assert!((7_600..8_400).contains(&dropped.len()), "{}", dropped.len());
+
+        let doubled = delivered(Chaos::new().duplicate_rate(0.2).seed(1), 10_000).await;
+        assert!(
+            (11_600..12_400).contains(&doubled.len()),
+            "{}",
+            doubled.len()
+        );
+        // Each duplicate follows its original
+        let mut unique = doubled.clone();
+        unique.dedup();
+        assert_eq!(unique.len(), 10_000);
+    }
+
+    #[tokio::test]
+    async fn same_seed_same_decisions() {
+        let chaos = || Chaos::new().drop_rate(0.5).duplicate_rate(0.5).seed(7);
+        assert_eq!(delivered(chaos(), 100).await, delivered(chaos(), 100).await);
+    }
+
+    #[tokio::test]
+    async fn delayed_messages_keep_their_order() {
+        let chaos = Chaos::new().delay(1.0, Duration::from_millis(20));
+        let started = Instant::now();
+        let ids = delivered(chaos, 3).await;
+        assert_eq!(ids, ["0", "1", "2"]);
+        assert!(started.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[tokio::test]
+    async fn other_frames_pass_through() {
+        let inner = futures::stream::iter(vec![
+            Ok::<_, Infallible>(StompItem::Heartbeat),
+            Ok(StompItem::Frame(Frame::new("RECEIPT"))),
+        ]);
+        let items: Vec<_> = ChaosStream::new(inner, Some(Chaos::new().drop_rate(1.0)))
+            .collect()
+            .await;
+        assert_eq!(items.len(), 2);
+    }
+}
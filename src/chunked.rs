@@ -0,0 +1,128 @@
+//! Chunked writing of large frames, enabled with
+//! `ConnectOptions::write_chunk_size()`.
+//!
+//! The connection task splits the transport into a read half and a write
+//! half after the handshake. A frame larger than the chunk size is encoded
+//! up front and written one slice per turn of the task's select loop, so
+//! inbound frames, heartbeat checks and receipts are handled while a large
+//! body is still going out. Other outbound frames wait until it is done:
+//! STOMP cannot interleave frames.
+
+use std::future;
+use std::io;
+
+use bytes::BytesMut;
+use tokio::io::{AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio_util::codec::{Encoder, Framed, FramedParts, FramedWrite};
+
+use crate::codec::{StompCodec, StompItem};
+use crate::inflight::{InflightLimiter, releases_on_flush};
+use crate::transport::Transport;
+
+/// Write half of a session.
+pub(crate) type FrameSink = FramedWrite<WriteHalf<Transport>, StompCodec>;
+
+/// Read half of a session. A `Framed` rather than a `FramedRead`, since
+/// only `Framed::from_parts()` decodes bytes already buffered before it
+/// reads the socket again.
+pub(crate) type FrameStream = Framed<ReadHalf<Transport>, StompCodec>;
+
+/// Split a connected `Framed` into independent halves.
+///
+/// The read half keeps the handshake codec (its decode state and counters)
+/// and any bytes already read past the CONNECTED frame; the write half
+/// encodes with `writer_codec`, escaping headers only if the handshake
+/// codec does (not on a STOMP 1.0 session).
+pub(crate) fn split(
+    framed: Framed<Transport, StompCodec>,
+    writer_codec: StompCodec,
+) -> (FrameSink, FrameStream) {
+    let parts = framed.into_parts();
+    let writer_codec = writer_codec.with_header_escaping(parts.codec.header_escaping());
+    let (read, write) = tokio::io::split(parts.io);
+    let mut read_parts = FramedParts::new::<StompItem>(read, parts.codec);
+    read_parts.read_buf = parts.read_buf;
+    (
+        FramedWrite::new(write, writer_codec),
+        Framed::from_parts(read_parts),
+    )
+}
+
+/// A large frame being written a slice at a time.
+///
+/// Dropping it, written or not, gives back the in-flight permit of a SEND
+/// without a receipt, as the writer does for frames it flushes whole.
+#[derive(Debug)]
+pub(crate) struct ChunkedWrite {
+    bytes: BytesMut,
+    written: usize,
+    chunk_size: usize,
+    inflight: Option<InflightLimiter>,
+}
+
+impl ChunkedWrite {
+    /// Encode `item` for writing in `chunk_size` slices.
+    pub(crate) fn new(
+        sink: &mut FrameSink,
+        item: StompItem,
+        chunk_size: usize,
+        inflight: Option<&InflightLimiter>,
+    ) -> io::Result<Self> {
+        let mut chunked = Self {
+            bytes: BytesMut::new(),
+            written: 0,
+            chunk_size,
+            inflight: inflight.filter(|_| releases_on_flush(&item)).cloned(),
+        };
+        sink.encoder_mut().encode(item, &mut chunked.bytes)?;
+        Ok(chunked)
+    }
+
+    /// Write the next slice, or flush once everything is written. Returns
+    /// `true` when the frame is fully written and flushed.
+    ///
+    /// Cancel safe: progress is recorded as soon as a write returns, so the
+    /// select loop can drop this future between slices.
+    async fn write_next(&mut self, sink: &mut FrameSink) -> io::Result<bool> {
+        if self.written == self.bytes.len() {
+            sink.get_mut().flush().await?;
+            return Ok(true);
+        }
+        let end = (self.written + self.chunk_size).min(self.bytes.len());
+        let n = sink.get_mut().write(&self.bytes[self.written..end]).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        self.written += n;
+        Ok(false)
+    }
+
+    /// Write the rest of the frame at once, before something else must be
+    /// written to the socket.
+    pub(crate) async fn finish(&mut self, sink: &mut FrameSink) -> io::Result<()> {
+        let rest = &self.bytes[self.written..];
+        sink.get_mut().write_all(rest).await?;
+        self.written = self.bytes.len();
+        sink.get_mut().flush().await
+    }
+}
+
+impl Drop for ChunkedWrite {
+    fn drop(&mut self) {
+        if let Some(inflight) = &self.inflight {
+            inflight.release_flushed(1);
+        }
+    }
+}
+
+/// Write the next slice of `chunked`; never completes while it is `None`,
+/// so it can sit in the select loop unconditionally.
+pub(crate) async fn write_chunk(
+    chunked: &mut Option<ChunkedWrite>,
+    sink: &mut FrameSink,
+) -> io::Result<bool> {
+    match chunked {
+        Some(chunked) => chunked.write_next(sink).await,
+        None => future::pending().await,
+    }
+}
@@ -1,9 +1,14 @@
 use bytes::{Buf, BufMut, BytesMut};
 use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio_util::codec::{Decoder, Encoder};
 
 use crate::frame::Frame;
-use crate::parser::{parse_frame_slice, unescape_header_value};
+use crate::parser::{
+    ParseErrorKind, Terminator, consume_trailing_eol, declared_body_len, parse_frame_head,
+    parse_frame_raw, unescape_header_value, unescape_header_value_lenient,
+};
 
 /// Escape a STOMP 1.2 header value for wire transmission.
 ///
@@ -12,18 +17,279 @@ use crate::parser::{parse_frame_slice, unescape_header_value};
 /// - carriage return (0x0d) → `\r`
 /// - line feed (0x0a) → `\n`
 /// - colon (0x3a) → `\c` (primarily for header names, but we escape in values too for safety)
-fn escape_header_value(input: &str) -> String {
-    let mut result = String::with_capacity(input.len());
+///
+/// The escaped text is appended to `out`, which the encoder reuses across
+/// headers to avoid allocating a new `String` for each one.
+fn escape_header_value_into(input: &str, out: &mut String) {
     for ch in input.chars() {
         match ch {
-            '\\' => result.push_str("\\\\"),
-            '\r' => result.push_str("\\r"),
-            '\n' => result.push_str("\\n"),
-            ':' => result.push_str("\\c"),
-            _ => result.push(ch),
+            '\\' => out.push_str("\\\\"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            ':' => out.push_str("\\c"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Length of `input` after `escape_header_value_into`.
+fn escaped_len(input: &str) -> usize {
+    input.len()
+        + input
+            .bytes()
+            .filter(|b| matches!(b, b'\\' | b'\r' | b'\n' | b':'))
+            .count()
+}
+
+/// Header names brokers put on almost every frame.
+///
+/// Decoded keys matching one of these are copied from the static name,
+/// skipping the escape scan and UTF-8 validation. None of them contains a
+/// character that needs escaping, so a byte-exact match is always correct.
+const WELL_KNOWN_HEADERS: &[&str] = &[
+    "destination",
+    "message-id",
+    "subscription",
+    "ack",
+    "content-type",
+    "content-length",
+    "receipt",
+    "receipt-id",
+    "id",
+    "message",
+    "version",
+    "heart-beat",
+    "server",
+    "session",
+    "persistent",
+    "priority",
+    "expires",
+    "timestamp",
+    "redelivered",
+    "correlation-id",
+    "reply-to",
+    "transaction",
+];
+
+/// Look up a raw header name in `WELL_KNOWN_HEADERS`.
+fn well_known_header(raw: &[u8]) -> Option<&'static str> {
+    WELL_KNOWN_HEADERS
+        .iter()
+        .copied()
+        .find(|name| name.as_bytes() == raw)
+}
+
+/// A decode failure, classified for `DecodeErrorStats`.
+type DecodeError = (DecodeErrorKind, io::Error);
+
+/// The kinds of decode failure counted in `DecodeErrorStats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeErrorKind {
+    InvalidUtf8,
+    InvalidEscape,
+    MalformedHeader,
+    ContentLength,
+    InterleavedHeartbeat,
+}
+
+fn invalid_data(kind: DecodeErrorKind, message: String) -> DecodeError {
+    (kind, io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Unescape and validate a raw header key or value (`what`) into an owned
+/// string, allocating once. Text without escapes is copied directly.
+///
+/// Also returns whether the lenient policy had to keep an invalid escape.
+/// With no policy (a STOMP 1.0 session) backslashes are plain text.
+fn decode_header_text(
+    raw: &[u8],
+    what: &str,
+    escapes: Option<EscapePolicy>,
+) -> Result<(String, bool), DecodeError> {
+    let utf8_error = |e: std::str::Utf8Error| {
+        invalid_data(
+            DecodeErrorKind::InvalidUtf8,
+            format!("invalid utf8 in header {}: {}", what, e),
+        )
+    };
+    let Some(escapes) = escapes.filter(|_| raw.contains(&b'\\')) else {
+        return std::str::from_utf8(raw)
+            .map(|text| (text.to_string(), false))
+            .map_err(utf8_error);
+    };
+    let (unescaped, lenient) = match escapes {
+        EscapePolicy::Strict => {
+            let unescaped = unescape_header_value(raw).map_err(|e| {
+                invalid_data(
+                    DecodeErrorKind::InvalidEscape,
+                    format!("invalid escape in header {}: {}", what, e),
+                )
+            })?;
+            (unescaped, false)
+        }
+        EscapePolicy::Lenient => {
+            let (unescaped, passed_through) = unescape_header_value_lenient(raw);
+            (unescaped, passed_through > 0)
+        }
+    };
+    String::from_utf8(unescaped)
+        .map(|text| (text, lenient))
+        .map_err(|e| utf8_error(e.utf8_error()))
+}
+
+/// How the decoder treats backslash sequences in headers that STOMP 1.2
+/// does not define, such as `\t` or a trailing `\`.
+///
+/// Set it with `StompCodec::with_escape_policy()` or, for a connection,
+/// `ConnectOptions::escape_policy()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EscapePolicy {
+    /// Reject the frame with an `InvalidData` error, as the spec requires.
+    /// On a connection the error ends the session.
+    #[default]
+    Strict,
+    /// Keep the backslash and the character after it as they are. Frames
+    /// that need this are counted in `DecodeStats::permissive`. For legacy
+    /// brokers that send Windows paths or regular expressions in headers
+    /// unescaped.
+    Lenient,
+}
+
+/// Controls when the encoder adds a `content-length` header.
+///
+/// A `content-length` header already present on the frame is always sent
+/// as-is; the policy only decides whether the encoder adds one. Set it per
+/// connection with `ConnectOptions::content_length_policy()`, or per send
+/// with `SendOptions::content_length_policy()` (which encodes the frame as
+/// `StompItem::FrameWithContentLength`); the per-send setting wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ContentLengthPolicy {
+    /// Add `content-length` to every frame.
+    AlwaysEmit,
+    /// Add `content-length` only when the body contains NUL bytes or is not
+    /// valid UTF-8, since such bodies cannot be delimited by the NUL
+    /// terminator alone.
+    #[default]
+    Auto,
+    /// Never add `content-length`. Bodies containing NUL bytes will be
+    /// truncated by the receiver unless the frame carries the header itself.
+    Never,
+}
+
+/// How the encoder treats the names of outgoing headers, for brokers that
+/// are picky about them.
+///
+/// STOMP header names are case-sensitive, and a receiver uses the first of
+/// repeated headers, but brokers differ in how strictly they follow either
+/// rule. Everything is off by default, so frames are written as built. Set
+/// it with `StompCodec::with_header_policy()` or, for a connection,
+/// `ConnectOptions::header_policy()`.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::codec::{HeaderPolicy, StompCodec};
+///
+/// let codec = StompCodec::new().with_header_policy(HeaderPolicy::strict());
+/// let lowercase_only = HeaderPolicy {
+///     lowercase_names: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HeaderPolicy {
+    /// Write header names in lowercase, so `Content-Type` is sent as
+    /// `content-type`. Only ASCII letters are changed.
+    pub lowercase_names: bool,
+    /// Send only the first of repeated headers that define the frame
+    /// (`content-length`, `content-type`, `destination`, `id`, `ack`,
+    /// `subscription`, `message-id`, `receipt`, `transaction`), comparing
+    /// names without regard to case. Other repeated headers are sent as
+    /// they are.
+    pub dedupe_protected: bool,
+    /// Refuse to encode a frame with a control character (such as NUL or
+    /// a tab) in a header name. The encoder fails with `InvalidInput`; a
+    /// connection checks frames before queueing them and returns
+    /// `ConnError::Protocol` instead.
+    pub reject_control_chars: bool,
+}
+
+impl HeaderPolicy {
+    /// Lowercase names, drop repeated protected headers and reject control
+    /// characters.
+    pub fn strict() -> Self {
+        Self {
+            lowercase_names: true,
+            dedupe_protected: true,
+            reject_control_chars: true,
+        }
+    }
+
+    /// Check `frame`'s header names against `reject_control_chars`,
+    /// describing the first offending name.
+    pub(crate) fn check(&self, frame: &Frame) -> Result<(), String> {
+        if !self.reject_control_chars {
+            return Ok(());
+        }
+        match frame
+            .headers
+            .iter()
+            .find(|(name, _)| name.chars().any(char::is_control))
+        {
+            Some((name, _)) => Err(format!(
+                "header name {:?} contains a control character",
+                name
+            )),
+            None => Ok(()),
         }
     }
-    result
+}
+
+/// Headers `HeaderPolicy::dedupe_protected` sends only once.
+const PROTECTED_HEADERS: [&str; 9] = [
+    "content-length",
+    "content-type",
+    "destination",
+    "id",
+    "ack",
+    "subscription",
+    "message-id",
+    "receipt",
+    "transaction",
+];
+
+/// Whether the encoder adds a `content-length` header to `frame` under
+/// `policy`.
+fn needs_content_length(frame: &Frame, policy: ContentLengthPolicy) -> bool {
+    let has_cl = frame
+        .headers
+        .iter()
+        .any(|(k, _)| k.eq_ignore_ascii_case("content-length"));
+    if has_cl {
+        return false;
+    }
+    match policy {
+        ContentLengthPolicy::AlwaysEmit => true,
+        ContentLengthPolicy::Auto => {
+            frame.body.contains(&0) || std::str::from_utf8(&frame.body).is_err()
+        }
+        ContentLengthPolicy::Never => false,
+    }
+}
+
+/// Number of bytes `StompCodec` writes when encoding `frame` under `policy`.
+pub(crate) fn encoded_len(frame: &Frame, policy: ContentLengthPolicy) -> usize {
+    // command + LF
+    let mut len = frame.command.len() + 1;
+    for (k, v) in &frame.headers {
+        // key + ':' + value + LF
+        len += escaped_len(k) + 1 + escaped_len(v) + 1;
+    }
+    if needs_content_length(frame, policy) {
+        len += "content-length:".len() + frame.body.len().to_string().len() + 1;
+    }
+    // blank line + body + NUL
+    len + 1 + frame.body.len() + 1
 }
 
 /// (parser-based implementation uses `src` directly; header parsing is
@@ -31,13 +297,27 @@ fn escape_header_value(input: &str) -> String {
 /// Items produced or consumed by the codec.
 ///
 /// A `StompItem` is either a decoded `Frame` or a `Heartbeat` marker
-/// representing a single LF received on the wire.
+/// representing a single LF received on the wire. Outbound frames can also
+/// carry their own `ContentLengthPolicy`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StompItem {
     /// A decoded STOMP frame (command + headers + body)
     Frame(Frame),
-    /// A single heartbeat pulse (LF)
+    /// A single heartbeat pulse (LF, or CRLF)
     Heartbeat,
+    /// A frame to encode under its own `ContentLengthPolicy` instead of
+    /// the codec's. Only encoded, never produced by the decoder.
+    FrameWithContentLength(Frame, ContentLengthPolicy),
+}
+
+impl StompItem {
+    /// The frame this item carries, if it is not a heartbeat.
+    pub fn frame(&self) -> Option<&Frame> {
+        match self {
+            StompItem::Frame(frame) | StompItem::FrameWithContentLength(frame, _) => Some(frame),
+            StompItem::Heartbeat => None,
+        }
+    }
 }
 
 /// `StompCodec` implements `tokio_util::codec::{Decoder, Encoder}` for the
@@ -48,14 +328,260 @@ pub enum StompItem {
 /// - Support both NUL-terminated frames and frames using the `content-length`
 ///   header (STOMP 1.2) for binary bodies containing NUL bytes.
 /// - Encode `StompItem` back into bytes for the wire format and emit
-///   `content-length` when necessary.
+///   `content-length` according to the codec's `ContentLengthPolicy`.
 pub struct StompCodec {
-    // No internal buffer: we parse directly from the provided `src` buffer
+    // No read buffer: we parse directly from the provided `src` buffer.
+    /// Scratch space reused for escaping header names and values on encode.
+    scratch: String,
+    /// When to add `content-length` to encoded frames.
+    content_length_policy: ContentLengthPolicy,
+    /// How undefined escape sequences in decoded headers are handled.
+    escape_policy: EscapePolicy,
+    /// How the names of encoded headers are written.
+    header_policy: HeaderPolicy,
+    /// Escape encoded headers and unescape decoded ones. STOMP 1.0 has no
+    /// header escaping.
+    header_escaping: bool,
+    /// Terminator styles seen on decoded frames.
+    terminators: TerminatorStats,
+    /// Decode counters, possibly shared with the connection.
+    counters: Arc<DecodeCounters>,
+    /// The last frame ended in NUL CR at the end of the buffer, so an LF
+    /// at the start of the next read belongs to it, not a heartbeat.
+    pending_lf: bool,
+    /// Encode heartbeats as CRLF instead of a bare LF.
+    crlf_heartbeats: bool,
+    /// Most body bytes kept of a decoded frame; the rest is read and
+    /// discarded as it arrives.
+    body_capture_limit: Option<usize>,
+    /// Frame whose body outgrew `body_capture_limit`, being read to its end.
+    truncating: Option<TruncatedFrame>,
+    /// A frame was decoded with a truncated body since `take_truncated()`.
+    truncated: bool,
+    /// Where earlier reads ended within the incomplete frame at the start
+    /// of the buffer, to tell a heartbeat sent in the middle of the frame
+    /// from the frame's own line breaks.
+    read_boundaries: Vec<usize>,
+}
+
+/// Most read boundaries remembered for one incomplete frame. The head of
+/// the frame, where an interleaved heartbeat is recognised, comes first.
+const MAX_READ_BOUNDARIES: usize = 64;
+
+/// A frame whose body is being discarded after the capture limit.
+struct TruncatedFrame {
+    /// The frame with the body kept so far.
+    frame: Frame,
+    /// Body bytes still to discard when the frame has a content-length;
+    /// otherwise the body runs to the next NUL.
+    remaining: Option<usize>,
+}
+
+/// Counts of the ways decoded frames were terminated.
+///
+/// STOMP frames end with a NUL byte, which brokers may follow with an LF or
+/// a CRLF. The codec accepts all three; these counts show which a broker
+/// actually uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TerminatorStats {
+    /// Frames ending in a bare NUL.
+    pub nul: u64,
+    /// Frames ending in NUL LF.
+    pub nul_lf: u64,
+    /// Frames ending in NUL CRLF.
+    pub nul_crlf: u64,
 }
 
 impl StompCodec {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            scratch: String::new(),
+            content_length_policy: ContentLengthPolicy::default(),
+            escape_policy: EscapePolicy::default(),
+            header_policy: HeaderPolicy::default(),
+            header_escaping: true,
+            terminators: TerminatorStats::default(),
+            counters: Arc::default(),
+            pending_lf: false,
+            crlf_heartbeats: false,
+            body_capture_limit: None,
+            truncating: None,
+            truncated: false,
+            read_boundaries: Vec::new(),
+        }
+    }
+
+    /// Keep at most `limit` body bytes of decoded frames, reading and
+    /// discarding the rest as it arrives so an oversized body is never
+    /// buffered whole. `None` keeps whole bodies.
+    pub(crate) fn set_body_capture_limit(&mut self, limit: Option<usize>) {
+        self.body_capture_limit = limit;
+    }
+
+    /// Turn header escaping on or off for the rest of the session, once
+    /// the negotiated version is known.
+    pub(crate) fn set_header_escaping(&mut self, enabled: bool) {
+        self.header_escaping = enabled;
+    }
+
+    /// Whether headers are escaped and unescaped.
+    pub(crate) fn header_escaping(&self) -> bool {
+        self.header_escaping
+    }
+
+    /// Whether a frame was decoded with a truncated body since the last
+    /// call.
+    pub(crate) fn take_truncated(&mut self) -> bool {
+        std::mem::take(&mut self.truncated)
+    }
+
+    /// Terminator styles seen on the frames decoded so far.
+    pub fn terminator_stats(&self) -> TerminatorStats {
+        self.terminators
+    }
+
+    /// Frames, heartbeats, bytes and errors decoded so far.
+    pub fn decode_stats(&self) -> DecodeStats {
+        self.counters.snapshot()
+    }
+
+    /// Count into `counters` instead of counters of the codec's own, so the
+    /// totals outlive the codec (the connection keeps them across
+    /// reconnects).
+    pub(crate) fn with_decode_counters(mut self, counters: Arc<DecodeCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    /// Set when the encoder adds `content-length` (builder style).
+    ///
+    /// Items sent as `StompItem::FrameWithContentLength` override this.
+    pub fn with_content_length_policy(mut self, policy: ContentLengthPolicy) -> Self {
+        self.content_length_policy = policy;
+        self
+    }
+
+    /// Set how undefined escape sequences in decoded headers are handled
+    /// (builder style). Strict by default.
+    pub fn with_escape_policy(mut self, policy: EscapePolicy) -> Self {
+        self.escape_policy = policy;
+        self
+    }
+
+    /// Set how the names of encoded headers are written (builder style).
+    /// Frames are written as built by default.
+    pub fn with_header_policy(mut self, policy: HeaderPolicy) -> Self {
+        self.header_policy = policy;
+        self
+    }
+
+    /// Escape encoded headers and unescape decoded ones (builder style).
+    ///
+    /// On by default, as STOMP 1.1 and 1.2 require. Turn it off for a
+    /// STOMP 1.0 peer, which sends and expects backslashes as plain text.
+    pub fn with_header_escaping(mut self, enabled: bool) -> Self {
+        self.header_escaping = enabled;
+        self
+    }
+
+    /// Encode heartbeats as CRLF instead of a bare LF (builder style).
+    ///
+    /// Decoding accepts both either way.
+    pub fn with_crlf_heartbeats(mut self, crlf: bool) -> Self {
+        self.crlf_heartbeats = crlf;
+        self
+    }
+}
+
+/// Counts of what a `StompCodec` decoded.
+///
+/// Returned by `StompCodec::decode_stats()` and, accumulated over every
+/// session including reconnects, by `Connection::decode_stats()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Frames decoded.
+    pub frames: u64,
+    /// Heartbeats (single LFs or CRLFs) decoded.
+    pub heartbeats: u64,
+    /// Bytes consumed by decoded frames and heartbeats.
+    pub bytes: u64,
+    /// Frames accepted only by a lenient parsing rule: a body without a
+    /// command line, a `content-length` header that is not lowercase or
+    /// has surrounding whitespace, or, with `EscapePolicy::Lenient`, a
+    /// header with an undefined escape sequence. A non-zero count means the
+    /// broker sends something slightly off-spec.
+    pub permissive: u64,
+    /// Decode errors, by kind.
+    pub errors: DecodeErrorStats,
+}
+
+/// Decode errors by kind, part of `DecodeStats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeErrorStats {
+    /// Command or header text that is not valid UTF-8.
+    pub invalid_utf8: u64,
+    /// Header text with an invalid escape sequence.
+    pub invalid_escape: u64,
+    /// Header lines without a `:` separator.
+    pub malformed_header: u64,
+    /// Unusable `content-length` headers, or bodies not followed by NUL.
+    pub content_length: u64,
+    /// Heartbeats sent in the middle of a frame, which STOMP forbids.
+    pub interleaved_heartbeat: u64,
+}
+
+impl DecodeErrorStats {
+    /// Decode errors of every kind.
+    pub fn total(&self) -> u64 {
+        self.invalid_utf8
+            + self.invalid_escape
+            + self.malformed_header
+            + self.content_length
+            + self.interleaved_heartbeat
+    }
+}
+
+/// Shared, lock-free counters behind `DecodeStats`.
+#[derive(Debug, Default)]
+pub(crate) struct DecodeCounters {
+    frames: AtomicU64,
+    heartbeats: AtomicU64,
+    bytes: AtomicU64,
+    permissive: AtomicU64,
+    invalid_utf8: AtomicU64,
+    invalid_escape: AtomicU64,
+    malformed_header: AtomicU64,
+    content_length: AtomicU64,
+    interleaved_heartbeat: AtomicU64,
+}
+
+impl DecodeCounters {
+    pub(crate) fn snapshot(&self) -> DecodeStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        DecodeStats {
+            frames: load(&self.frames),
+            heartbeats: load(&self.heartbeats),
+            bytes: load(&self.bytes),
+            permissive: load(&self.permissive),
+            errors: DecodeErrorStats {
+                invalid_utf8: load(&self.invalid_utf8),
+                invalid_escape: load(&self.invalid_escape),
+                malformed_header: load(&self.malformed_header),
+                content_length: load(&self.content_length),
+                interleaved_heartbeat: load(&self.interleaved_heartbeat),
+            },
+        }
+    }
+
+    fn error(&self, kind: DecodeErrorKind) {
+        let counter = match kind {
+            DecodeErrorKind::InvalidUtf8 => &self.invalid_utf8,
+            DecodeErrorKind::InvalidEscape => &self.invalid_escape,
+            DecodeErrorKind::MalformedHeader => &self.malformed_header,
+            DecodeErrorKind::ContentLength => &self.content_length,
+            DecodeErrorKind::InterleavedHeartbeat => &self.interleaved_heartbeat,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
     }
 }
 
@@ -84,77 +610,279 @@ impl Decoder for StompCodec {
     /// - `Err(io::Error)` on protocol or data errors (invalid UTF-8, malformed
     ///   frames, missing NUL after a content-length body, etc.).
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // Move any newly-received bytes from the provided `src` into our
-        // internal buffer. We keep a separate buffer so parsing can proceed
-        // across arbitrary chunk boundaries without relying on indexes into
-        // heartbeat: single LF
-        if let Some(&b'\n') = src.chunk().first() {
-            src.advance(1);
+        self.decode_item(src).map_err(|(kind, e)| {
+            self.counters.error(kind);
+            e
+        })
+    }
+}
+
+impl StompCodec {
+    /// `Decoder::decode` with errors classified for the statistics.
+    fn decode_item(&mut self, src: &mut BytesMut) -> Result<Option<StompItem>, DecodeError> {
+        // Bytes stay in `src` until a whole item has been parsed from it, so
+        // parsing can proceed across arbitrary chunk boundaries without
+        // relying on indexes into earlier reads: an incomplete frame is
+        // simply parsed again from the start once more bytes arrive.
+
+        // LF completing a NUL CRLF terminator split across reads
+        if self.pending_lf && !src.is_empty() {
+            self.pending_lf = false;
+            if src[0] == b'\n' {
+                src.advance(1);
+                self.counters.bytes.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // heartbeat: single LF, or CRLF from peers and gateways that use
+        // CRLF line endings
+        let heartbeat_len = match src.chunk() {
+            [b'\n', ..] => 1,
+            [b'\r', b'\n', ..] => 2,
+            // Wait for the LF
+            [b'\r'] => return Ok(None),
+            _ => 0,
+        };
+        if heartbeat_len > 0 {
+            src.advance(heartbeat_len);
+            self.counters.heartbeats.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .bytes
+                .fetch_add(heartbeat_len as u64, Ordering::Relaxed);
             return Ok(Some(StompItem::Heartbeat));
         }
 
+        if self.truncating.is_some() {
+            return self.discard_truncated_body(src);
+        }
+
         let chunk = src.chunk();
-        match parse_frame_slice(chunk) {
-            Ok(Some((cmd_bytes, headers, body, consumed))) => {
-                // advance src by consumed
+        match parse_frame_raw(chunk) {
+            Ok(Some(raw)) => {
+                self.read_boundaries.clear();
+                // build owned Frame straight from the borrowed slices
+                let body = raw.body.map(<[u8]>::to_vec).unwrap_or_default();
+                let (frame, lenient_escapes) = self.build_frame(raw.command, raw.headers, body)?;
+                self.count_terminator(raw.terminator);
+                if raw.permissive || lenient_escapes {
+                    self.counters.permissive.fetch_add(1, Ordering::Relaxed);
+                }
+                let consumed = raw.consumed;
                 src.advance(consumed);
-
-                // build owned Frame
-                let command = String::from_utf8(cmd_bytes).map_err(|e| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("invalid utf8 in command: {}", e),
-                    )
-                })?;
-                // convert headers Vec<(Vec<u8>,Vec<u8>)> -> Vec<(String,String)>
-                // and unescape per STOMP 1.2 spec
-                let mut hdrs: Vec<(String, String)> = Vec::new();
-                for (k, v) in headers {
-                    // Unescape header key
-                    let k_unescaped = unescape_header_value(&k).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("invalid escape in header key: {}", e),
-                        )
-                    })?;
-                    let ks = String::from_utf8(k_unescaped).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("invalid utf8 in header key: {}", e),
-                        )
-                    })?;
-                    // Unescape header value
-                    let v_unescaped = unescape_header_value(&v).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("invalid escape in header value: {}", e),
-                        )
-                    })?;
-                    let vs = String::from_utf8(v_unescaped).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            format!("invalid utf8 in header value: {}", e),
-                        )
-                    })?;
-                    hdrs.push((ks, vs));
+                self.counters.frames.fetch_add(1, Ordering::Relaxed);
+                self.counters
+                    .bytes
+                    .fetch_add(consumed as u64, Ordering::Relaxed);
+                Ok(Some(StompItem::Frame(frame)))
+            }
+            Ok(None) => {
+                let item = self.start_truncating(src)?;
+                if item.is_none() && self.truncating.is_none() {
+                    self.note_read_boundary(src.len());
                 }
+                Ok(item)
+            }
+            Err(_) if self.interleaved_heartbeat(chunk).is_some() => {
+                Err(self.interleaved_heartbeat_error(chunk))
+            }
+            Err(e) => {
+                self.read_boundaries.clear();
+                Err(parse_error(e))
+            }
+        }
+    }
 
-                let body = body.unwrap_or_default();
+    /// Remember that a read ended `len` bytes into an incomplete frame.
+    fn note_read_boundary(&mut self, len: usize) {
+        if len > 0
+            && self.read_boundaries.len() < MAX_READ_BOUNDARIES
+            && self.read_boundaries.last().is_none_or(|&last| last < len)
+        {
+            self.read_boundaries.push(len);
+        }
+    }
 
-                let frame = Frame {
-                    command,
-                    headers: hdrs,
-                    body,
-                };
-                Ok(Some(StompItem::Frame(frame)))
+    /// Find a heartbeat the peer sent in the middle of the frame at the
+    /// start of `buf`, which failed to parse: an LF that starts a read and,
+    /// when removed, makes the frame valid.
+    ///
+    /// Returns the offset of the LF. Only consulted for frames that fail to
+    /// parse, so whether a frame is accepted never depends on how its bytes
+    /// were split into reads.
+    fn interleaved_heartbeat(&self, buf: &[u8]) -> Option<usize> {
+        self.read_boundaries.iter().copied().find(|&at| {
+            buf.get(at) == Some(&b'\n')
+                && parse_frame_raw(&[&buf[..at], &buf[at + 1..]].concat()).is_ok()
+        })
+    }
+
+    /// The error for a frame with a heartbeat inside it. The session ends
+    /// with it, since the rest of the stream can no longer be trusted.
+    fn interleaved_heartbeat_error(&mut self, buf: &[u8]) -> DecodeError {
+        let at = self.interleaved_heartbeat(buf).unwrap_or_default();
+        self.read_boundaries.clear();
+        let command = buf
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|end| String::from_utf8_lossy(&buf[..end.min(at)]).into_owned())
+            .unwrap_or_default();
+        invalid_data(
+            DecodeErrorKind::InterleavedHeartbeat,
+            format!(
+                "parse error: heartbeat inside a frame: LF at byte {} of a {:?} frame, \
+                 where a read ended; STOMP only allows heartbeats between frames",
+                at, command
+            ),
+        )
+    }
+
+    /// Build an owned frame from parsed slices, unescaping headers per the
+    /// STOMP 1.2 spec. Also returns whether the lenient escape policy had
+    /// to keep an invalid escape.
+    fn build_frame(
+        &self,
+        command: &[u8],
+        headers: Vec<(&[u8], &[u8])>,
+        body: Vec<u8>,
+    ) -> Result<(Frame, bool), DecodeError> {
+        let command = std::str::from_utf8(command)
+            .map_err(|e| {
+                invalid_data(
+                    DecodeErrorKind::InvalidUtf8,
+                    format!("invalid utf8 in command: {}", e),
+                )
+            })?
+            .to_string();
+        let mut hdrs: Vec<(String, String)> = Vec::with_capacity(headers.len());
+        let mut lenient_escapes = false;
+        let escapes = self.header_escaping.then_some(self.escape_policy);
+        for (k, v) in headers {
+            let ks = match well_known_header(k) {
+                Some(name) => name.to_string(),
+                None => {
+                    let (ks, lenient) = decode_header_text(k, "key", escapes)?;
+                    lenient_escapes |= lenient;
+                    ks
+                }
+            };
+            let (vs, lenient) = decode_header_text(v, "value", escapes)?;
+            lenient_escapes |= lenient;
+            hdrs.push((ks, vs));
+        }
+        let frame = Frame {
+            command,
+            headers: hdrs,
+            body,
+            seq: None,
+            destination: None,
+        };
+        Ok((frame, lenient_escapes))
+    }
+
+    fn count_terminator(&mut self, terminator: Terminator) {
+        match terminator {
+            Terminator::Nul => self.terminators.nul += 1,
+            Terminator::NulLf => self.terminators.nul_lf += 1,
+            Terminator::NulCrLf => self.terminators.nul_crlf += 1,
+            Terminator::NulCr => {
+                self.terminators.nul_crlf += 1;
+                self.pending_lf = true;
             }
-            Ok(None) => Ok(None),
-            Err(e) => Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("parse error: {}", e),
-            )),
         }
     }
+
+    /// With a body capture limit, stop buffering an incomplete frame once
+    /// more body bytes than the limit have arrived: keep the frame with
+    /// the first `limit` of them and discard the rest as it comes in.
+    fn start_truncating(&mut self, src: &mut BytesMut) -> Result<Option<StompItem>, DecodeError> {
+        let Some(limit) = self.body_capture_limit else {
+            return Ok(None);
+        };
+        let Some(head) = parse_frame_head(src.chunk()).map_err(parse_error)? else {
+            return Ok(None);
+        };
+        if src.len() - head.body_start <= limit {
+            return Ok(None);
+        }
+        let declared = declared_body_len(&head.headers).map_err(parse_error)?;
+        let body_end = head.body_start + limit;
+        let body = src[head.body_start..body_end].to_vec();
+        let (frame, lenient_escapes) = self.build_frame(head.command, head.headers, body)?;
+        if lenient_escapes {
+            self.counters.permissive.fetch_add(1, Ordering::Relaxed);
+        }
+        src.advance(body_end);
+        self.counters
+            .bytes
+            .fetch_add(body_end as u64, Ordering::Relaxed);
+        self.truncating = Some(TruncatedFrame {
+            frame,
+            remaining: declared.map(|len| len.saturating_sub(limit)),
+        });
+        self.read_boundaries.clear();
+        self.discard_truncated_body(src)
+    }
+
+    /// Discard the body of the frame being truncated up to its terminator,
+    /// then return the frame.
+    fn discard_truncated_body(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<StompItem>, DecodeError> {
+        let Some(truncating) = &mut self.truncating else {
+            return Ok(None);
+        };
+        let nul = match &mut truncating.remaining {
+            Some(remaining) => {
+                let skip = (*remaining).min(src.len());
+                src.advance(skip);
+                *remaining -= skip;
+                self.counters
+                    .bytes
+                    .fetch_add(skip as u64, Ordering::Relaxed);
+                if *remaining > 0 || src.is_empty() {
+                    return Ok(None);
+                }
+                if src[0] != 0 {
+                    self.truncating = None;
+                    return Err(invalid_data(
+                        DecodeErrorKind::ContentLength,
+                        "parse error: missing NUL terminator after content-length body".to_string(),
+                    ));
+                }
+                0
+            }
+            None => match src.iter().position(|&b| b == 0) {
+                Some(nul) => nul,
+                None => {
+                    self.counters
+                        .bytes
+                        .fetch_add(src.len() as u64, Ordering::Relaxed);
+                    src.clear();
+                    return Ok(None);
+                }
+            },
+        };
+        let (consumed, terminator) = consume_trailing_eol(src, nul + 1);
+        src.advance(consumed);
+        self.counters
+            .bytes
+            .fetch_add(consumed as u64, Ordering::Relaxed);
+        self.counters.frames.fetch_add(1, Ordering::Relaxed);
+        self.count_terminator(terminator);
+        self.truncated = true;
+        let frame = self.truncating.take().map(|t| t.frame);
+        Ok(frame.map(StompItem::Frame))
+    }
+}
+
+/// Classify a parser error for the statistics.
+fn parse_error((kind, e): (ParseErrorKind, String)) -> DecodeError {
+    let kind = match kind {
+        ParseErrorKind::MalformedHeader => DecodeErrorKind::MalformedHeader,
+        ParseErrorKind::ContentLength => DecodeErrorKind::ContentLength,
+    };
+    invalid_data(kind, format!("parse error: {}", e))
 }
 
 impl Encoder<StompItem> for StompCodec {
@@ -176,40 +904,84 @@ impl Encoder<StompItem> for StompCodec {
     fn encode(&mut self, item: StompItem, dst: &mut BytesMut) -> Result<(), Self::Error> {
         match item {
             StompItem::Heartbeat => {
+                if self.crlf_heartbeats {
+                    dst.put_u8(b'\r');
+                }
                 dst.put_u8(b'\n');
             }
             StompItem::Frame(frame) => {
-                dst.extend_from_slice(frame.command.as_bytes());
-                dst.put_u8(b'\n');
+                self.encode_frame(&frame, self.content_length_policy, dst)?;
+            }
+            StompItem::FrameWithContentLength(frame, content_length_policy) => {
+                self.encode_frame(&frame, content_length_policy, dst)?;
+            }
+        }
 
-                let mut headers = frame.headers;
-                let has_cl = headers
-                    .iter()
-                    .any(|(k, _)| k.to_lowercase() == "content-length");
-                if !has_cl {
-                    let include_cl =
-                        frame.body.contains(&0) || std::str::from_utf8(&frame.body).is_err();
-                    if include_cl {
-                        headers.push(("content-length".to_string(), frame.body.len().to_string()));
-                    }
-                }
+        Ok(())
+    }
+}
 
-                for (k, v) in headers {
-                    // Escape header name and value per STOMP 1.2 spec
-                    let escaped_key = escape_header_value(&k);
-                    let escaped_val = escape_header_value(&v);
-                    dst.extend_from_slice(escaped_key.as_bytes());
-                    dst.put_u8(b':');
-                    dst.extend_from_slice(escaped_val.as_bytes());
-                    dst.put_u8(b'\n');
-                }
+impl StompCodec {
+    /// Encode `frame`, adding `content-length` according to
+    /// `content_length_policy`.
+    fn encode_frame(
+        &mut self,
+        frame: &Frame,
+        content_length_policy: ContentLengthPolicy,
+        dst: &mut BytesMut,
+    ) -> io::Result<()> {
+        let policy = self.header_policy;
+        policy
+            .check(frame)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // Reserve the exact size up front so `dst` grows at most once
+        dst.reserve(encoded_len(frame, content_length_policy));
+        let include_cl = needs_content_length(frame, content_length_policy);
 
-                dst.put_slice(b"\n");
-                dst.extend_from_slice(&frame.body);
-                dst.put_u8(0);
+        dst.extend_from_slice(frame.command.as_bytes());
+        dst.put_u8(b'\n');
+
+        // Protected headers already written, by index
+        let mut written = [false; PROTECTED_HEADERS.len()];
+        for (k, v) in &frame.headers {
+            if policy.dedupe_protected
+                && let Some(i) = PROTECTED_HEADERS
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(k))
+            {
+                if written[i] {
+                    continue;
+                }
+                written[i] = true;
+            }
+            // Escape header name and value per STOMP 1.2 spec
+            self.scratch.clear();
+            if self.header_escaping {
+                escape_header_value_into(k, &mut self.scratch);
+            } else {
+                self.scratch.push_str(k);
+            }
+            if policy.lowercase_names {
+                self.scratch.make_ascii_lowercase();
             }
+            self.scratch.push(':');
+            if self.header_escaping {
+                escape_header_value_into(v, &mut self.scratch);
+            } else {
+                self.scratch.push_str(v);
+            }
+            self.scratch.push('\n');
+            dst.extend_from_slice(self.scratch.as_bytes());
+        }
+        if include_cl {
+            dst.extend_from_slice(b"content-length:");
+            dst.extend_from_slice(frame.body.len().to_string().as_bytes());
+            dst.put_u8(b'\n');
         }
 
+        dst.put_slice(b"\n");
+        dst.extend_from_slice(&frame.body);
+        dst.put_u8(0);
         Ok(())
     }
 }
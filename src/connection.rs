@@ -1,15 +1,45 @@
 use futures::{SinkExt, StreamExt, future};
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::net::TcpStream;
-use tokio::sync::{Mutex, broadcast, mpsc, oneshot};
+use tokio::sync::{Mutex, Notify, OwnedSemaphorePermit, broadcast, mpsc, oneshot};
 use tokio_util::codec::Framed;
 
-use crate::codec::{StompCodec, StompItem};
+use crate::address::BrokerAddress;
+use crate::breaker::{Circuit, CircuitBreaker, CircuitSwitch};
+use crate::broker::BrokerProfile;
+use crate::chaos::{Chaos, ChaosStream};
+use crate::chunked::{ChunkedWrite, FrameSink, FrameStream, write_chunk};
+use crate::codec::{
+    ContentLengthPolicy, DecodeCounters, DecodeStats, EscapePolicy, HeaderPolicy, StompCodec,
+    StompItem,
+};
+use crate::destination::{Destination, DestinationTable};
+use crate::diagnostics::{swallowed, swallowed_frame};
+use crate::events::{self, ConnectionEvent};
 use crate::frame::Frame;
+use crate::inflight::{InflightLimiter, releases_on_flush};
+use crate::metrics::{MessageCounters, MessageStats};
+use crate::outbox::Outbox;
+use crate::producer::Producer;
+use crate::protocol::ProtocolState;
+use crate::recorder::SessionRecorder;
+use crate::retry::RetryPolicy;
+use crate::sampling::{MessageSampler, SampleGate};
+use crate::send::{SendOptions, SendResult};
+use crate::sequence::{PublisherSequence, Sequencer};
+use crate::subscription::{PendingLimit, PendingOverflow, StageOutcome, Stages, run_stages};
+use crate::tap::{TAP_CAPACITY, Tap};
+use crate::task::{self, TaskLabels};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsInfo, TlsOptions};
+use crate::trace::{TRACEPARENT_HEADER, TraceContext, TraceContextProvider};
+use crate::transport::{Connector, TcpOptions, Transport};
 
 /// Configuration for STOMP heartbeat intervals.
 ///
@@ -127,33 +157,552 @@ pub(crate) struct SubscriptionEntry {
     pub(crate) sender: mpsc::Sender<Frame>,
     pub(crate) ack: String,
     pub(crate) headers: Vec<(String, String)>,
+    /// Deliver with backpressure instead of dropping when the channel is
+    /// full (`SubscriptionOptions::ordered`).
+    pub(crate) ordered: bool,
+    /// Limit on the messages tracked in the pending map
+    /// (`SubscriptionOptions::max_pending`).
+    pub(crate) pending_limit: Option<PendingLimit>,
+    /// Messages evicted or NACKed by `pending_limit`.
+    pub(crate) pending_evicted: u64,
+    /// Messages dropped because the channel was full.
+    pub(crate) dropped: u64,
+    /// The channel was found full and has not drained since; reported with
+    /// `ConnectionEvent::SlowConsumer`.
+    pub(crate) slow: bool,
+    /// The broker delivers to this subscription without a SUBSCRIBE
+    /// (RabbitMQ temporary reply queues), so no SUBSCRIBE or UNSUBSCRIBE
+    /// frames are ever sent for it.
+    pub(crate) implicit: bool,
+    /// Steps run on each message before it is queued
+    /// (`Subscription::map()` and friends).
+    pub(crate) stages: Stages,
+    /// Connection-level ERRORs that invalidated messages of this
+    /// subscription (`Subscription::recv_result()`).
+    pub(crate) errors: broadcast::Sender<ServerError>,
 }
 
 /// Alias for the subscription dispatch map: destination -> list of
-/// `SubscriptionEntry`.
-pub(crate) type Subscriptions = HashMap<String, Vec<SubscriptionEntry>>;
-
-/// Alias for the pending map: subscription_id -> queue of (message-id, Frame).
-pub(crate) type PendingMap = HashMap<String, VecDeque<(String, Frame)>>;
+/// `SubscriptionEntry`. Keys come from the connection's `DestinationTable`.
+pub(crate) type Subscriptions = HashMap<Destination, Vec<SubscriptionEntry>>;
+
+/// Alias for the pending map: subscription_id -> queue of (message-id,
+/// subscribed destination, delivery time).
+pub(crate) type PendingMap = HashMap<String, VecDeque<(String, Destination, Instant)>>;
+
+/// Alias for the invalidated map: subscription_id -> message-ids delivered
+/// before a connection-level ERROR and not acknowledged since.
+pub(crate) type Invalidated = HashMap<String, HashSet<String>>;
+
+/// Most ERRORs a subscription keeps for `Subscription::recv_result()`.
+const SUBSCRIPTION_ERROR_CAPACITY: usize = 4;
+
+/// Handle a connection-level ERROR: the broker ends the session after it,
+/// so the messages still waiting for an ACK or NACK can no longer be
+/// acknowledged. Move them from `pending` to `invalidated` and pass the
+/// error to each subscription that had any.
+async fn invalidate_pending(
+    error: &Frame,
+    pending: &Mutex<PendingMap>,
+    invalidated: &Mutex<Invalidated>,
+    subscriptions: &Mutex<Subscriptions>,
+) {
+    let drained: Vec<_> = pending.lock().await.drain().collect();
+    if drained.is_empty() {
+        return;
+    }
+    let error = ServerError::from_frame(error.clone());
+    {
+        let mut invalidated = invalidated.lock().await;
+        for (sub_id, queue) in &drained {
+            invalidated
+                .entry(sub_id.clone())
+                .or_default()
+                .extend(queue.iter().map(|(id, _, _)| id.clone()));
+        }
+    }
+    let map = subscriptions.lock().await;
+    for (destination, entries) in map.iter() {
+        for entry in entries {
+            if drained.iter().any(|(sub_id, _)| *sub_id == entry.id)
+                && let Err(e) = entry.errors.send(error.clone())
+            {
+                swallowed("report lost acks", Some("ERROR"), Some(destination), &e);
+            }
+        }
+    }
+}
 
 /// Internal type for resubscribe snapshot entries: (destination, id, ack, headers)
-pub(crate) type ResubEntry = (String, String, String, Vec<(String, String)>);
+pub(crate) type ResubEntry = (Destination, String, String, Vec<(String, String)>);
+
+/// Snapshot the subscriptions to send at the start of a session. Implicit
+/// subscriptions are never sent.
+fn resubscribe_entries(map: &Subscriptions) -> Vec<ResubEntry> {
+    let mut v: Vec<ResubEntry> = Vec::new();
+    for (dest, vec) in map.iter() {
+        for entry in vec.iter().filter(|entry| !entry.implicit) {
+            v.push((
+                dest.clone(),
+                entry.id.clone(),
+                entry.ack.clone(),
+                entry.headers.clone(),
+            ));
+        }
+    }
+    v
+}
+
+/// A receipt requested by the client that the server has not yet confirmed.
+pub(crate) struct PendingReceipt {
+    /// Notified when the matching RECEIPT frame arrives, or with an error
+    /// when the connection is lost first.
+    pub(crate) sender: oneshot::Sender<Result<(), ReceiptError>>,
+    /// When the receipt was first registered.
+    pub(crate) sent_at: Instant,
+    /// In-flight permit of the SEND that requested the receipt, released
+    /// when this entry is dropped (see `ConnectOptions::max_inflight_sends()`).
+    pub(crate) permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PendingReceipt {
+    pub(crate) fn new(sender: oneshot::Sender<Result<(), ReceiptError>>) -> Self {
+        Self {
+            sender,
+            sent_at: Instant::now(),
+            permit: None,
+        }
+    }
+}
+
+/// A request from `Connection::update_heartbeat()` to re-run the handshake
+/// with a new `heart-beat` header.
+pub(crate) struct Reconfigure {
+    /// Client heartbeat header value for the new session.
+    heartbeat: String,
+    /// Notified once the new session is established and resubscribed.
+    done: oneshot::Sender<()>,
+}
+
+/// A request from `Connection::close_after_flush()` to write everything
+/// queued, DISCONNECT and stop the background task.
+pub(crate) struct CloseRequest {
+    /// When to give up waiting for the queue to drain and the RECEIPT.
+    deadline: tokio::time::Instant,
+    /// Notified with `true` once the broker confirmed the DISCONNECT.
+    done: oneshot::Sender<bool>,
+}
+
+/// Capacity of the channel feeding `Connection::next_frame()`.
+const INBOUND_CAPACITY: usize = 32;
+
+/// Capacity of the channel feeding the writer. Also the number of frames
+/// that can be sent before `ConnectionBuilder::start()`.
+const OUTBOUND_CAPACITY: usize = 32;
+
+/// Sending side of the channel behind `Connection::next_frame()`. Counts
+/// the frames it queues for `Connection::inbound_len()`.
+#[derive(Clone)]
+struct InboundTx {
+    tx: mpsc::Sender<Frame>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl InboundTx {
+    /// Queue `frame`, waiting for room.
+    async fn send(&self, frame: Frame) {
+        // Count first so the reader never sees a frame it cannot uncount
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        if self.tx.send(frame).await.is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Queue `frame` if there is room, otherwise drop it.
+    fn try_send(&self, frame: Frame) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        if self.tx.try_send(frame).is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Receiving side of the channel behind `Connection::next_frame()`.
+pub(crate) struct Inbound {
+    rx: mpsc::Receiver<Frame>,
+    /// A frame returned by `Connection::peek_frame()` and not yet consumed.
+    peeked: Option<Frame>,
+}
+
+impl Inbound {
+    fn new(rx: mpsc::Receiver<Frame>) -> Self {
+        Self { rx, peeked: None }
+    }
+}
+
+/// How long `Connection::update_heartbeat()` waits for the broker to
+/// confirm the DISCONNECT before dropping the transport anyway.
+const RECONFIGURE_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Default for `ConnectOptions::handshake_timeout()`.
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default for `ConnectOptions::handshake_error_body_limit()`.
+const DEFAULT_HANDSHAKE_ERROR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Outcome of one `Connection::send_with_retry()` attempt.
+enum SendAttempt {
+    /// Queued, or confirmed by a RECEIPT when the policy asks for one.
+    Sent,
+    /// Failed in a way worth retrying.
+    Retry(ConnError),
+    /// Failed for good.
+    Fail(ConnError),
+}
+
+/// How often the connection task inspects outstanding receipts.
+const RECEIPT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default age after which unwaited receipts are discarded.
+const DEFAULT_RECEIPT_TTL: Duration = Duration::from_secs(300);
+
+/// Default for `ConnectOptions::heartbeat_grace_multiplier()`: the broker
+/// may miss one heartbeat before the connection is dropped.
+const DEFAULT_HEARTBEAT_GRACE: f64 = 2.0;
+
+/// Upper bound for `ConnectOptions::heartbeat_grace_multiplier()` and
+/// `MonitorOptions::late_after`, so scaling a negotiated interval by it
+/// cannot overflow a `Duration`.
+pub(crate) const MAX_HEARTBEAT_GRACE: f64 = 100.0;
+
+/// Default for `ConnectOptions::connect_attempt_timeout()`.
+const DEFAULT_CONNECT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the outbox drainer waits for the RECEIPT of each entry.
+const OUTBOX_RECEIPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause before the outbox drainer sends an unconfirmed entry again.
+const OUTBOX_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Largest `ConnectOptions::heartbeat_jitter()`.
+const MAX_HEARTBEAT_JITTER: f64 = 0.5;
+
+/// Upper bound on the bytes the writer queues before flushing. Items that
+/// are already waiting in the outbound channel are written together and
+/// flushed once, up to this size.
+const MAX_WRITE_BATCH_BYTES: usize = 64 * 1024;
+
+/// Approximate wire size of an outbound item, for write batching.
+fn outbound_len(item: &StompItem) -> usize {
+    item.frame().map_or(1, Frame::encoded_len)
+}
+
+/// Count a SEND frame about to be written with the session recorder.
+fn record_sent(recorder: &Option<SessionRecorder>, item: &StompItem) {
+    if let (Some(recorder), Some(frame)) = (recorder, item.frame())
+        && frame.command == "SEND"
+    {
+        recorder.record_sent(outbound_len(item));
+    }
+}
+
+/// Alias for pending receipt map: receipt-id -> pending receipt to notify when received.
+pub(crate) type PendingReceipts = HashMap<String, PendingReceipt>;
+
+/// Snapshot of receipts awaiting confirmation from the server.
+///
+/// Returned by `Connection::outstanding_receipts()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutstandingReceipts {
+    /// Number of receipts not yet confirmed.
+    pub count: usize,
+    /// Age of the oldest unconfirmed receipt, or `None` if there are none.
+    pub oldest_age: Option<Duration>,
+    /// Total receipts discarded by TTL cleanup because nobody was waiting
+    /// for them (see `ConnectOptions::receipt_ttl()`).
+    pub expired: u64,
+}
+
+impl OutstandingReceipts {
+    /// Summarize a pending receipt map.
+    pub(crate) fn of(receipts: &PendingReceipts, expired: u64) -> Self {
+        Self {
+            count: receipts.len(),
+            oldest_age: receipts.values().map(|r| r.sent_at.elapsed()).max(),
+            expired,
+        }
+    }
+}
+
+/// Snapshot of messages on one subscription that the application has not
+/// acknowledged yet.
+///
+/// Returned by `Connection::pending_acks()` and `Subscription::pending_acks()`.
+/// Only `client` and `client-individual` subscriptions track messages;
+/// `auto` subscriptions always report zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PendingAcks {
+    /// Number of delivered messages not yet ACKed or NACKed.
+    pub count: usize,
+    /// Time since the oldest of them was delivered, or `None` if there are
+    /// none.
+    pub oldest_age: Option<Duration>,
+    /// Messages no longer tracked because the subscription's
+    /// `max_pending` limit was reached (evicted or NACKed).
+    pub evicted: u64,
+}
+
+impl PendingAcks {
+    /// Summarize the pending queue of one subscription.
+    pub(crate) fn of(queue: &VecDeque<(String, Destination, Instant)>) -> Self {
+        Self {
+            count: queue.len(),
+            oldest_age: queue.iter().map(|(_, _, at)| at.elapsed()).max(),
+            evicted: 0,
+        }
+    }
+}
+
+/// Wait until subscription `sub_id` tracks fewer than `max` pending
+/// messages, or is gone, or `close_after_flush()` is waiting to be handled.
+/// Returns `false` if the connection is shut down meanwhile.
+async fn wait_for_pending_room(
+    pending: &Mutex<PendingMap>,
+    released: &Notify,
+    subscriptions: &Mutex<Subscriptions>,
+    sub_id: &str,
+    max: usize,
+    shutdown: &mut broadcast::Receiver<()>,
+    close_rx: &mpsc::Receiver<CloseRequest>,
+) -> bool {
+    loop {
+        let notified = released.notified();
+        if !close_rx.is_empty() {
+            return true;
+        }
+        let sender = subscriptions
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .find(|entry| entry.id == sub_id)
+            .map(|entry| entry.sender.clone());
+        let Some(sender) = sender else {
+            return true;
+        };
+        if pending.lock().await.get(sub_id).map_or(0, VecDeque::len) < max {
+            return true;
+        }
+        // A dropped subscriber never settles its messages
+        tokio::select! {
+            _ = notified => {}
+            _ = sender.closed() => return true,
+            _ = shutdown.recv() => return false,
+        }
+    }
+}
+
+/// Remove the oldest messages of a pending queue until there is room for
+/// one more under `max`. Returns the ids of the messages removed.
+fn evict_oldest_pending(
+    queue: &mut VecDeque<(String, Destination, Instant)>,
+    max: usize,
+) -> Vec<String> {
+    let excess = (queue.len() + 1).saturating_sub(max);
+    queue.drain(..excess).map(|(id, _, _)| id).collect()
+}
+
+/// Resolve every outstanding receipt with `ReceiptError::ConnectionLost`
+/// and clear the map. Returns the number of receipts failed.
+///
+/// Called when a session ends: receipts requested on it will never be
+/// confirmed, so waiters are released instead of running into their
+/// timeouts.
+pub(crate) fn fail_receipts(receipts: &mut PendingReceipts) -> usize {
+    let failed = receipts.len();
+    for (receipt_id, pending) in receipts.drain() {
+        let error = ReceiptError::ConnectionLost { receipt_id };
+        if let Err(Err(error)) = pending.sender.send(Err(error)) {
+            swallowed("fail receipt", Some("RECEIPT"), None, &error);
+        }
+    }
+    failed
+}
+
+/// Send the entries of `outbox` one at a time, oldest first, removing each
+/// once its RECEIPT arrives. Runs until the connection is closed; between
+/// entries it does not keep the connection alive.
+async fn drain_outbox(conn: WeakConnection, outbox: Outbox, mut shutdown: broadcast::Receiver<()>) {
+    loop {
+        let Some(entry) = outbox.front() else {
+            tokio::select! {
+                _ = shutdown.recv() => return,
+                _ = outbox.pushed() => continue,
+            }
+        };
+        let Some(conn) = conn.upgrade() else {
+            return;
+        };
+        let result = tokio::select! {
+            _ = shutdown.recv() => return,
+            result = conn.send_frame_confirmed(entry.frame, OUTBOX_RECEIPT_TIMEOUT) => result,
+        };
+        drop(conn);
+        match result {
+            Ok(()) => {
+                // Removing may compact the log, which waits for the disk
+                let removing = outbox.clone();
+                let removed = tokio::task::spawn_blocking(move || removing.remove(entry.id))
+                    .await
+                    .map_err(std::io::Error::other);
+                if let Err(e) = removed.and_then(|removed| removed) {
+                    tracing::warn!(id = entry.id, error = %e, "failed to mark outbox entry as sent");
+                }
+            }
+            Err(ConnError::ChannelClosed) => return,
+            Err(e) => {
+                tracing::debug!(id = entry.id, error = %e, "outbox entry not confirmed; retrying");
+                tokio::select! {
+                    _ = shutdown.recv() => return,
+                    _ = tokio::time::sleep(OUTBOX_RETRY_DELAY) => {}
+                }
+            }
+        }
+    }
+}
+
+/// Remove receipts older than `ttl` that no caller is waiting for.
+///
+/// Receipts requested with `send_frame_with_receipt()` but never passed to
+/// `wait_for_receipt()` would otherwise stay in the map forever. Entries
+/// with a live waiter are kept; their own timeout cleans them up. Returns
+/// the number of entries removed.
+pub(crate) fn expire_receipts(receipts: &mut PendingReceipts, ttl: Duration) -> usize {
+    let before = receipts.len();
+    receipts.retain(|_, r| !(r.sender.is_closed() && r.sent_at.elapsed() > ttl));
+    before - receipts.len()
+}
+
+/// CONNECT headers that are set through dedicated `ConnectOptions` fields
+/// or connect parameters and cannot be overridden with `header()`.
+const PROTECTED_CONNECT_HEADERS: [&str; 6] = [
+    "accept-version",
+    "host",
+    "login",
+    "passcode",
+    "heart-beat",
+    "client-id",
+];
+
+/// A connection configuration rejected before any connection attempt.
+///
+/// Returned by `ConnectOptions::validate()` and `ConnectOptions::build()`,
+/// and by `Connection::connect_with_options()` as `ConnError::Config`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// A custom header would replace a CONNECT header that has its own
+    /// option (`accept-version`, `host`, `login`, `passcode`, `heart-beat`,
+    /// `client-id`).
+    #[error("custom header '{0}' overrides a protected CONNECT header; use the dedicated option")]
+    ProtectedHeaderOverride(String),
+    /// The virtual host is set but empty.
+    #[error("virtual host must not be empty")]
+    EmptyHost,
+    /// The `heartbeat` argument of `Connection::connect()` or
+    /// `connect_with_options()` is not two comma-separated millisecond
+    /// counts. `ConnectOptions` holds no heart-beat value, so
+    /// `ConnectOptions::validate()` never returns this.
+    #[error("invalid heart-beat '{0}': expected \"<send_ms>,<receive_ms>\"")]
+    InvalidHeartbeat(String),
+    /// TLS is required (`ConnectOptions::require_tls()`) but no TLS settings
+    /// were given, or the crate was built without the `tls` feature.
+    #[error("TLS is required but not configured")]
+    MissingTlsConfig,
+    /// The broker address is a URI this crate cannot use: not `stomp://`,
+    /// without a host, with credentials, or with a malformed vhost.
+    #[error("invalid broker address {0}")]
+    InvalidAddress(String),
+    /// `max_inflight_sends` is zero, which would block every send.
+    #[error("max_inflight_sends must be at least 1")]
+    ZeroInflightSends,
+    /// `write_chunk_size` is zero, so no large frame could be written.
+    #[error("write_chunk_size must be at least 1")]
+    ZeroWriteChunkSize,
+    /// `heartbeat_grace_multiplier` is outside `1.0..=100.0` or not a
+    /// number. Below 1 the watchdog would give up before a heartbeat is even
+    /// due; above 100 the grace period could overflow.
+    #[error("heartbeat_grace_multiplier must be between 1 and 100")]
+    InvalidHeartbeatGrace,
+    /// `MonitorOptions::late_after` is outside `1.0..=100.0` or not a
+    /// number. Returned by `Connection::monitor()`.
+    #[error("late_after must be between 1 and 100")]
+    InvalidLateAfter,
+    /// `heartbeat_jitter` is outside `0.0..=0.5`.
+    #[error("heartbeat_jitter must be between 0 and 0.5")]
+    InvalidHeartbeatJitter,
+    /// `connect_attempt_timeout` is zero, so no address could be reached.
+    #[error("connect_attempt_timeout must be greater than zero")]
+    ZeroConnectAttemptTimeout,
+    /// The circuit breaker opens after zero failures, so the connection
+    /// would never try to reconnect.
+    #[error("circuit breaker failures must be at least 1")]
+    ZeroCircuitBreakerFailures,
+}
 
-/// Alias for pending receipt map: receipt-id -> oneshot sender to notify when received.
-pub(crate) type PendingReceipts = HashMap<String, oneshot::Sender<()>>;
+/// Why a requested receipt will never be confirmed.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReceiptError {
+    /// The connection dropped (or was closed) before the RECEIPT arrived.
+    /// The frame may or may not have reached the broker.
+    #[error("connection lost before RECEIPT for '{receipt_id}' arrived")]
+    ConnectionLost {
+        /// The receipt that was outstanding.
+        receipt_id: String,
+    },
+}
 
 /// Errors returned by `Connection` operations.
+///
+/// Underlying errors are kept as the `source()`, and [`code`](Self::code)
+/// gives a stable identifier for each variant to match on, for example in
+/// logs or metrics, without depending on the `Display` text.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ConnError {
     /// I/O-level error
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
-    /// Protocol-level error
+    /// The request cannot be carried out on this connection: a destination
+    /// failed validation, a frame broke strict protocol rules, the
+    /// negotiated version lacks the feature, and the like.
     #[error("protocol error: {0}")]
     Protocol(String),
+    /// The broker did not answer CONNECT within the handshake timeout.
+    #[error("handshake timeout: no CONNECTED frame within {0:?}")]
+    HandshakeTimeout(Duration),
+    /// The bytes received during the handshake are not valid STOMP.
+    #[error("could not decode frame from broker: {source}")]
+    Decode {
+        /// The codec error.
+        #[source]
+        source: std::io::Error,
+    },
+    /// No subscription with this id exists on the connection.
+    #[error("subscription not found: '{0}'")]
+    SubscriptionNotFound(String),
+    /// The connection has been closed: its background task no longer
+    /// accepts frames or confirms receipts.
+    #[error("connection closed")]
+    ChannelClosed,
     /// Receipt timeout error
     #[error("receipt timeout: no RECEIPT received for '{0}' within timeout")]
     ReceiptTimeout(String),
+    /// `Connection::close_after_flush()` could not write the queued frames
+    /// and have the broker confirm the DISCONNECT within its timeout.
+    #[error("close timeout: queued frames not flushed and confirmed within {0:?}")]
+    CloseTimeout(Duration),
     /// Server rejected the connection (e.g., authentication failure)
     ///
     /// This error is returned when the server sends an ERROR frame in response
@@ -161,6 +710,167 @@ pub enum ConnError {
     /// unauthorized access, or broker configuration issues.
     #[error("server rejected connection: {0}")]
     ServerRejected(ServerError),
+    /// The server negotiated a STOMP version the client did not offer.
+    ///
+    /// Returned when the `version` header of the CONNECTED frame is not one
+    /// of the versions listed in `accept-version`. A server that omits the
+    /// header is treated as speaking STOMP 1.0, per the specification.
+    #[error("version mismatch: server negotiated '{server}', client accepts '{accepted}'")]
+    VersionMismatch {
+        /// The `accept-version` value sent by the client.
+        accepted: String,
+        /// The version reported by the server.
+        server: String,
+    },
+    /// The connection configuration is invalid; nothing was sent.
+    #[error("invalid configuration: {0}")]
+    Config(#[from] ConfigError),
+    /// A receipt can no longer be confirmed.
+    #[error("receipt failed: {0}")]
+    Receipt(#[from] ReceiptError),
+    /// The message was delivered before a connection-level ERROR from the
+    /// broker, which ended its session; nothing was sent, since the broker
+    /// would ignore or reject an ACK or NACK for it.
+    #[error("message '{0}' was invalidated by a server error")]
+    MessageInvalidated(String),
+}
+
+impl ConnError {
+    /// A stable, machine-readable identifier for the kind of error, such as
+    /// `"handshake_timeout"` or `"channel_closed"`.
+    ///
+    /// Codes never change once published, unlike the `Display` text.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use iridium_stomp::ConnError;
+    ///
+    /// let err = ConnError::SubscriptionNotFound("7".to_string());
+    /// assert_eq!(err.code(), "subscription_not_found");
+    /// assert_eq!(err.to_string(), "subscription not found: '7'");
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConnError::Io(_) => "io",
+            ConnError::Protocol(_) => "protocol",
+            ConnError::HandshakeTimeout(_) => "handshake_timeout",
+            ConnError::Decode { .. } => "decode",
+            ConnError::SubscriptionNotFound(_) => "subscription_not_found",
+            ConnError::ChannelClosed => "channel_closed",
+            ConnError::ReceiptTimeout(_) => "receipt_timeout",
+            ConnError::CloseTimeout(_) => "close_timeout",
+            ConnError::ServerRejected(_) => "server_rejected",
+            ConnError::VersionMismatch { .. } => "version_mismatch",
+            ConnError::Config(_) => "config",
+            ConnError::Receipt(_) => "receipt",
+            ConnError::MessageInvalidated(_) => "message_invalidated",
+        }
+    }
+}
+
+/// Details about the broker reported in the CONNECTED frame, plus the
+/// socket endpoints of the connection that received it.
+///
+/// Available via `Connection::server_info()` once the handshake has
+/// completed. The values are refreshed after each successful reconnect.
+/// The endpoint addresses show which node actually answered when the
+/// configured address resolves to several hosts or sits behind a load
+/// balancer.
+///
+/// Fields may be added in future releases (`tls` exists only with the
+/// `tls` feature), so the struct cannot be built or destructured
+/// exhaustively outside the crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ServerInfo {
+    /// Negotiated STOMP protocol version (e.g. "1.2").
+    pub version: String,
+
+    /// The `server` header, if present (e.g. "RabbitMQ/3.12.0").
+    pub server: Option<String>,
+
+    /// The `session` header, if present.
+    pub session: Option<String>,
+
+    /// The server's raw `heart-beat` header value ("0,0" when absent).
+    pub heart_beat: String,
+
+    /// Remote address of the socket, i.e. the resolved broker (or load
+    /// balancer) endpoint. `None` if it could not be determined.
+    pub peer_addr: Option<SocketAddr>,
+
+    /// Local address of the socket. `None` if it could not be determined.
+    pub local_addr: Option<SocketAddr>,
+
+    /// Negotiated TLS session details; `None` for plain TCP connections.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsInfo>,
+}
+
+impl ServerInfo {
+    /// Placeholder until the first CONNECTED frame arrives: an empty
+    /// version and no server details.
+    pub(crate) fn unconnected() -> Self {
+        Self {
+            version: String::new(),
+            server: None,
+            session: None,
+            heart_beat: "0,0".to_string(),
+            peer_addr: None,
+            local_addr: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Build a `ServerInfo` from a CONNECTED frame.
+    ///
+    /// A missing `version` header is reported as "1.0" since STOMP 1.0
+    /// servers do not send it.
+    pub fn from_connected(frame: &Frame) -> Self {
+        Self {
+            version: frame.get_header("version").unwrap_or("1.0").to_string(),
+            server: frame.get_header("server").map(|s| s.to_string()),
+            session: frame.get_header("session").map(|s| s.to_string()),
+            heart_beat: frame.get_header("heart-beat").unwrap_or("0,0").to_string(),
+            peer_addr: None,
+            local_addr: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Record the endpoints (and TLS session) of the transport the
+    /// handshake completed on.
+    fn with_endpoints(mut self, transport: &Transport) -> Self {
+        self.peer_addr = transport.tcp().peer_addr().ok();
+        self.local_addr = transport.tcp().local_addr().ok();
+        #[cfg(feature = "tls")]
+        {
+            self.tls = match transport {
+                Transport::Tls(stream) => Some(TlsInfo::of(stream)),
+                Transport::Plain(_) => None,
+            };
+        }
+        self
+    }
+
+    /// Returns `true` if the negotiated version supports NACK, which STOMP
+    /// 1.0 lacks.
+    pub fn supports_nack(&self) -> bool {
+        self.version != "1.0"
+    }
+}
+
+/// Check that the version negotiated by the server is one the client offered.
+///
+/// `accept_version` is the comma-separated `accept-version` header sent in
+/// CONNECT; `server_version` is the `version` header from CONNECTED.
+pub(crate) fn is_version_accepted(accept_version: &str, server_version: &str) -> bool {
+    accept_version
+        .split(',')
+        .any(|v| v.trim() == server_version.trim())
 }
 
 /// Represents an ERROR frame received from the STOMP server.
@@ -321,6 +1031,69 @@ impl AckMode {
     }
 }
 
+/// The text of a body cut at `limit` bytes, with a marker. A character
+/// split by the cut is dropped.
+fn truncated_body(body: &[u8], limit: usize) -> String {
+    let text = match std::str::from_utf8(body) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&body[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    format!("{}... [truncated after {} bytes]", text, limit)
+}
+
+/// Options controlling how a negatively-acknowledged message is handled.
+///
+/// Used with `nack_with_options()` and `nack_all()` on `Connection` and
+/// `Subscription`. The default requeues the message,
+/// matching the behavior of a bare NACK on most brokers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NackOptions {
+    /// Whether the broker should redeliver the message. Sent as the
+    /// `requeue` header, which RabbitMQ honors; `false` dead-letters or
+    /// discards the message.
+    pub requeue: bool,
+
+    /// Optional human-readable reason, sent as the `reason` header for
+    /// broker-side logging. Brokers that do not understand it ignore it.
+    pub reason: Option<String>,
+}
+
+impl Default for NackOptions {
+    fn default() -> Self {
+        Self {
+            requeue: true,
+            reason: None,
+        }
+    }
+}
+
+impl NackOptions {
+    /// Headers to add to the NACK frame for these options.
+    fn to_headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![("requeue".to_string(), self.requeue.to_string())];
+        if let Some(reason) = &self.reason {
+            headers.push(("reason".to_string(), reason.clone()));
+        }
+        headers
+    }
+}
+
+/// Options for `Connection::execute_with_options()`.
+///
+/// The default sends the frames as they are and does not wait for the
+/// broker.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecuteOptions {
+    /// Wrap the frames in BEGIN and COMMIT of the transaction with this id.
+    /// The caller is responsible for its uniqueness, as with
+    /// `Connection::begin()`.
+    pub transaction: Option<String>,
+
+    /// Request a receipt on the last frame (the COMMIT, in a transaction)
+    /// and wait this long for it.
+    pub receipt_timeout: Option<Duration>,
+}
+
 /// Options for customizing the STOMP CONNECT frame.
 ///
 /// Use this struct with `Connection::connect_with_options()` to set custom
@@ -329,17 +1102,18 @@ impl AckMode {
 ///
 /// # Validation
 ///
-/// This struct performs minimal validation. Values are passed to the broker
-/// as-is, and invalid configurations will be rejected by the broker at
-/// connection time. Empty strings are technically accepted but may cause
-/// broker-specific errors.
+/// `Connection::connect_with_options()` calls `validate()` before the first
+/// connection attempt and fails with `ConnError::Config` on contradictory
+/// settings. Call `validate()` or `build()` yourself to check a
+/// configuration earlier, for example while loading it at startup. Other
+/// values are passed to the broker as-is.
 ///
 /// # Custom Headers
 ///
 /// Custom headers added via `header()` cannot override critical STOMP headers
 /// (`accept-version`, `host`, `login`, `passcode`, `heart-beat`, `client-id`).
-/// Such headers are silently ignored. Use the dedicated builder methods to
-/// set these values.
+/// Such headers are rejected with `ConfigError::ProtectedHeaderOverride`. Use
+/// the dedicated builder methods to set these values.
 ///
 /// # Example
 ///
@@ -368,31 +1142,189 @@ pub struct ConnectOptions {
     /// Client ID for durable subscriptions (required by ActiveMQ, etc.).
     pub client_id: Option<String>,
 
-    /// Virtual host header value. Defaults to "/" if not set.
+    /// Virtual host header value. When not set, the vhost of a
+    /// `stomp://host:port/vhost` address is used, then the host name of the
+    /// address, then "/" (see `Connection::connect_with_options()`).
     pub host: Option<String>,
 
     /// Additional custom headers to include in the CONNECT frame.
-    /// Note: Headers that would override critical STOMP headers are ignored.
+    /// Note: Headers that would override critical STOMP headers are rejected
+    /// by `validate()`.
     pub headers: Vec<(String, String)>,
 
+    /// Open the session with the `STOMP` command instead of `CONNECT`.
+    pub stomp_command: bool,
+
     /// Optional channel to receive heartbeat notifications.
     /// When set, the connection will send a `()` on this channel each time
     /// a heartbeat is received from the server.
     pub heartbeat_tx: Option<mpsc::Sender<()>>,
+
+    /// Optional channel to receive `ConnectionEvent` notifications from the
+    /// background task.
+    pub event_tx: Option<mpsc::Sender<ConnectionEvent>>,
+
+    /// Optional provider of the current trace context, injected as
+    /// `traceparent`/`tracestate` headers on outgoing SEND frames.
+    pub trace_context: Option<TraceContextProvider>,
+
+    /// Age after which unconfirmed receipts trigger a
+    /// `ConnectionEvent::ReceiptsStalled` warning. Disabled if `None`.
+    pub receipt_warning_after: Option<Duration>,
+
+    /// Age after which receipts nobody is waiting for are discarded.
+    /// Defaults to 5 minutes if `None`.
+    pub receipt_ttl: Option<Duration>,
+
+    /// Reconnect when nothing at all has been received for this long,
+    /// whatever heartbeats were negotiated. Disabled if `None`.
+    pub read_timeout: Option<Duration>,
+
+    /// When the encoder adds `content-length` to outgoing frames.
+    pub content_length_policy: ContentLengthPolicy,
+
+    /// How undefined escape sequences in received headers are handled.
+    pub escape_policy: EscapePolicy,
+
+    /// How the names of outgoing headers are written.
+    pub header_policy: HeaderPolicy,
+
+    /// Reject illegal frame sequences client-side before sending them.
+    pub strict_protocol: bool,
+
+    /// Copy MESSAGE frames a subscription has taken to `next_frame()` only
+    /// if its queue has room, instead of waiting for room.
+    pub best_effort_mirror: bool,
+
+    /// Optional sampler receiving a fraction of inbound MESSAGE frames.
+    pub message_sampler: Option<MessageSampler>,
+
+    /// Broker whose naming rules destinations are checked against before
+    /// subscribing or sending. Disabled if `None`.
+    pub destination_validation: Option<BrokerProfile>,
+
+    /// Stamp outgoing SEND frames with a publisher id and sequence number.
+    /// Disabled if `None`.
+    pub publisher_sequence: Option<PublisherSequence>,
+
+    /// Record counts, broker errors and recent messages for session
+    /// reports. Disabled if `None`.
+    pub recorder: Option<SessionRecorder>,
+
+    /// Drop, duplicate or delay inbound MESSAGE frames on purpose. Ignored
+    /// in release builds without the `testing` feature. Disabled if `None`.
+    pub chaos: Option<Chaos>,
+
+    /// Connect over TLS with these settings. Plain TCP if `None`.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsOptions>,
+
+    /// Refuse to connect without TLS settings.
+    pub require_tls: bool,
+
+    /// Most SEND frames awaiting their receipt or flush at once. Unlimited
+    /// if `None`.
+    pub max_inflight_sends: Option<usize>,
+
+    /// Write frames larger than this many bytes in slices of this size.
+    /// Frames are written whole if `None`.
+    pub write_chunk_size: Option<usize>,
+
+    /// How many negotiated receive intervals may pass without inbound data
+    /// before the connection is dropped. 2.0 if `None`.
+    pub heartbeat_grace_multiplier: Option<f64>,
+
+    /// Vary each outgoing heartbeat interval randomly by up to this fraction
+    /// either way. No jitter if `None`.
+    pub heartbeat_jitter: Option<f64>,
+
+    /// How long each address the broker's host name resolves to gets to
+    /// accept the TCP connection before the next one is tried. 5 seconds
+    /// if `None`.
+    pub connect_attempt_timeout: Option<Duration>,
+
+    /// File-backed store for `Connection::send_durable()`, drained by the
+    /// connection. Disabled if `None`.
+    pub outbox: Option<Outbox>,
+
+    /// Leave the `login` and `passcode` headers out of the CONNECT frame,
+    /// for anonymous access or authentication by TLS client certificate.
+    pub omit_credentials: bool,
+
+    /// Send heartbeats as CRLF instead of a bare LF.
+    pub crlf_heartbeats: bool,
+
+    /// Most bytes kept of the body of a frame received during the CONNECT
+    /// handshake, such as an ERROR rejecting the connection. 64 KiB if
+    /// `None`.
+    pub handshake_error_body_limit: Option<usize>,
+
+    /// How long to wait for CONNECTED after sending CONNECT. 30 seconds if
+    /// `None`.
+    pub handshake_timeout: Option<Duration>,
+
+    /// Socket settings for the TCP connection to the broker.
+    /// `TCP_NODELAY` is set by default.
+    pub tcp: TcpOptions,
+
+    /// Pause reconnecting after repeated failed sessions. Disabled if
+    /// `None`.
+    pub circuit_breaker: Option<CircuitBreaker>,
 }
 
 impl std::fmt::Debug for ConnectOptions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ConnectOptions")
+        let mut debug = f.debug_struct("ConnectOptions");
+        debug
             .field("accept_version", &self.accept_version)
             .field("client_id", &self.client_id)
             .field("host", &self.host)
             .field("headers", &self.headers)
+            .field("stomp_command", &self.stomp_command)
             .field(
                 "heartbeat_tx",
                 &self.heartbeat_tx.as_ref().map(|_| "Some(...)"),
             )
-            .finish()
+            .field("event_tx", &self.event_tx.as_ref().map(|_| "Some(...)"))
+            .field(
+                "trace_context",
+                &self.trace_context.as_ref().map(|_| "Some(...)"),
+            )
+            .field("receipt_warning_after", &self.receipt_warning_after)
+            .field("receipt_ttl", &self.receipt_ttl)
+            .field("read_timeout", &self.read_timeout)
+            .field("content_length_policy", &self.content_length_policy)
+            .field("escape_policy", &self.escape_policy)
+            .field("header_policy", &self.header_policy)
+            .field("strict_protocol", &self.strict_protocol)
+            .field("best_effort_mirror", &self.best_effort_mirror)
+            .field("message_sampler", &self.message_sampler)
+            .field("destination_validation", &self.destination_validation)
+            .field("publisher_sequence", &self.publisher_sequence)
+            .field("recorder", &self.recorder.as_ref().map(|_| "Some(...)"));
+        debug.field("chaos", &self.chaos);
+        #[cfg(feature = "tls")]
+        debug.field("tls", &self.tls);
+        debug.field("require_tls", &self.require_tls);
+        debug.field("max_inflight_sends", &self.max_inflight_sends);
+        debug.field("write_chunk_size", &self.write_chunk_size);
+        debug.field(
+            "heartbeat_grace_multiplier",
+            &self.heartbeat_grace_multiplier,
+        );
+        debug.field("heartbeat_jitter", &self.heartbeat_jitter);
+        debug.field("connect_attempt_timeout", &self.connect_attempt_timeout);
+        debug.field("outbox", &self.outbox);
+        debug.field("omit_credentials", &self.omit_credentials);
+        debug.field("crlf_heartbeats", &self.crlf_heartbeats);
+        debug.field(
+            "handshake_error_body_limit",
+            &self.handshake_error_body_limit,
+        );
+        debug.field("handshake_timeout", &self.handshake_timeout);
+        debug.field("tcp", &self.tcp);
+        debug.field("circuit_breaker", &self.circuit_breaker);
+        debug.finish()
     }
 }
 
@@ -405,6 +1337,12 @@ impl ConnectOptions {
     /// Set the STOMP version(s) to accept (builder style).
     ///
     /// Examples: "1.2", "1.1,1.2", "1.0,1.1,1.2"
+    ///
+    /// The `version` header of the CONNECTED frame must match one of these
+    /// values, otherwise the connection fails with `ConnError::VersionMismatch`.
+    /// Offer older versions only if your application can live without their
+    /// missing features (for example, NACK is unavailable on STOMP 1.0).
+    /// Headers of a STOMP 1.0 session are sent and read without escaping.
     pub fn accept_version(mut self, version: impl Into<String>) -> Self {
         self.accept_version = Some(version.into());
         self
@@ -420,7 +1358,8 @@ impl ConnectOptions {
 
     /// Set the virtual host (builder style).
     ///
-    /// Defaults to "/" if not set.
+    /// Takes precedence over a vhost derived from the broker address, for
+    /// the first session and every reconnect.
     pub fn host(mut self, host: impl Into<String>) -> Self {
         self.host = Some(host.into());
         self
@@ -432,6 +1371,19 @@ impl ConnectOptions {
         self
     }
 
+    /// Open the session with the `STOMP` command instead of `CONNECT`
+    /// (builder style).
+    ///
+    /// STOMP 1.1 and 1.2 define `STOMP` as the preferred handshake command
+    /// because it cannot be mistaken for a 1.0 `CONNECT`, and some servers
+    /// expect it. STOMP 1.0 servers do not understand it, so leave this
+    /// off when `accept_version` includes "1.0". Applies to reconnects as
+    /// well. Defaults to `CONNECT`.
+    pub fn use_stomp_command(mut self, enabled: bool) -> Self {
+        self.stomp_command = enabled;
+        self
+    }
+
     /// Set a channel to receive heartbeat notifications (builder style).
     ///
     /// When set, the connection will send a `()` on this channel each time
@@ -464,32 +1416,560 @@ impl ConnectOptions {
         self.heartbeat_tx = Some(tx);
         self
     }
-}
 
-/// Parse the STOMP `heart-beat` header value (format: "cx,cy").
-///
-/// Parameters
-/// - `header`: header string from the server or client (for example
-///   "10000,10000"). The values represent milliseconds.
-///
-/// Returns a tuple `(cx, cy)` where each value is the heartbeat interval in
-/// milliseconds. Missing or invalid fields default to `0`.
-pub fn parse_heartbeat_header(header: &str) -> (u64, u64) {
-    let mut parts = header.split(',');
-    let cx = parts
-        .next()
-        .and_then(|s| s.trim().parse::<u64>().ok())
-        .unwrap_or(0);
-    let cy = parts
-        .next()
-        .and_then(|s| s.trim().parse::<u64>().ok())
-        .unwrap_or(0);
-    (cx, cy)
-}
+    /// Set a channel to receive connection events (builder style).
+    ///
+    /// Events report actions the connection took on its own, such as
+    /// removing a subscription whose receiver was dropped. Like heartbeat
+    /// notifications, events are delivered with `try_send()` and dropped if
+    /// the channel is full.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use tokio::sync::mpsc;
+    /// use iridium_stomp::{ConnectOptions, ConnectionEvent};
+    ///
+    /// let (tx, mut rx) = mpsc::channel(64);
+    /// let options = ConnectOptions::default().with_event_notify(tx);
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some(event) = rx.recv().await {
+    ///         println!("connection event: {:?}", event);
+    ///     }
+    /// });
+    /// ```
+    pub fn with_event_notify(mut self, tx: mpsc::Sender<ConnectionEvent>) -> Self {
+        self.event_tx = Some(tx);
+        self
+    }
 
-/// Negotiate heartbeat intervals between client and server.
-///
-/// Parameters
+    /// Propagate distributed tracing context on outgoing messages
+    /// (builder style).
+    ///
+    /// `provider` is called for every SEND frame, on the task that sends it,
+    /// and should return the context of the current span. When it returns
+    /// `Some`, `traceparent` (and `tracestate`, if set) headers are added
+    /// unless the frame already carries a `traceparent`. Consumers read the
+    /// context back with `Message::trace_context()`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use iridium_stomp::{ConnectOptions, TraceContext};
+    ///
+    /// let options = ConnectOptions::default().with_trace_context(|| {
+    ///     // Convert from your tracer's active span, e.g. OpenTelemetry
+    ///     let span = opentelemetry::trace::get_active_span(|s| s.span_context().clone());
+    ///     TraceContext::new(
+    ///         &span.trace_id().to_string(),
+    ///         &span.span_id().to_string(),
+    ///         span.is_sampled(),
+    ///     )
+    /// });
+    /// ```
+    pub fn with_trace_context<F>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> Option<TraceContext> + Send + Sync + 'static,
+    {
+        self.trace_context = Some(Arc::new(provider));
+        self
+    }
+
+    /// Warn when receipts go unconfirmed for longer than `age` (builder
+    /// style).
+    ///
+    /// The connection checks outstanding receipts once per second. When the
+    /// oldest one is older than `age`, a warning is logged and
+    /// `ConnectionEvent::ReceiptsStalled` is emitted (once per stall, until
+    /// the backlog drains below the threshold again). This helps publishers
+    /// that rely on receipts notice a broker that has stopped answering.
+    pub fn receipt_warning_after(mut self, age: Duration) -> Self {
+        self.receipt_warning_after = Some(age);
+        self
+    }
+
+    /// Set how long to keep receipts that nobody is waiting for (builder
+    /// style).
+    ///
+    /// A receipt requested with `send_frame_with_receipt()` is tracked until
+    /// the broker confirms it. If the caller never calls
+    /// `wait_for_receipt()` and the broker never answers, the entry would
+    /// be kept forever. The connection discards such entries once they are
+    /// older than `ttl` and counts them in
+    /// `OutstandingReceipts::expired`. Receipts with an active waiter are
+    /// never discarded. Defaults to 5 minutes.
+    pub fn receipt_ttl(mut self, ttl: Duration) -> Self {
+        self.receipt_ttl = Some(ttl);
+        self
+    }
+
+    /// Reconnect after `timeout` without any inbound bytes (builder style).
+    ///
+    /// The heartbeat watchdog only runs when the server agrees to send
+    /// heartbeats, so with `0,0` (or a broker that refuses them) a broker
+    /// that dies without closing the socket is never noticed. This timeout
+    /// applies regardless of heartbeat negotiation: frames and heartbeats
+    /// both count as activity, and when neither arrives for `timeout` the
+    /// connection is closed, `ConnectionEvent::ReadTimeout` is emitted, and
+    /// the usual reconnect logic takes over. Choose a value comfortably
+    /// above the longest expected quiet period. Disabled by default.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set when the encoder adds a `content-length` header (builder style).
+    ///
+    /// The default, `ContentLengthPolicy::Auto`, adds it only for bodies
+    /// that contain NUL bytes or are not valid UTF-8. Some brokers (older
+    /// ActiveMQ configurations, for example) mishandle `content-length` on
+    /// text frames, while others require it everywhere. Individual frames
+    /// can override this with `SendOptions::content_length_policy()`.
+    pub fn content_length_policy(mut self, policy: ContentLengthPolicy) -> Self {
+        self.content_length_policy = policy;
+        self
+    }
+
+    /// Never add `content-length` automatically (builder style).
+    ///
+    /// Shorthand for `content_length_policy(ContentLengthPolicy::Never)`.
+    pub fn disable_content_length_auto(self) -> Self {
+        self.content_length_policy(ContentLengthPolicy::Never)
+    }
+
+    /// Set how undefined escape sequences in received headers are handled
+    /// (builder style).
+    ///
+    /// STOMP 1.2 defines only `\r`, `\n`, `\c` and `\\`, and by default a
+    /// header with any other backslash sequence is a decode error that ends
+    /// the session. Some legacy brokers send literal backslashes, such as
+    /// Windows paths, unescaped, so the connection never stays up. With
+    /// `EscapePolicy::Lenient` such backslashes are kept as they are and
+    /// the frames are counted in `Connection::decode_stats()` as
+    /// `permissive`.
+    pub fn escape_policy(mut self, policy: EscapePolicy) -> Self {
+        self.escape_policy = policy;
+        self
+    }
+
+    /// Set how the names of outgoing headers are written (builder style).
+    ///
+    /// For brokers that mishandle header names that are not lowercase or
+    /// that repeat a header such as `destination`. With
+    /// `reject_control_chars` set, sends of a frame with a control
+    /// character in a header name fail with `ConnError::Protocol` before
+    /// anything is queued. See [`HeaderPolicy`].
+    pub fn header_policy(mut self, policy: HeaderPolicy) -> Self {
+        self.header_policy = policy;
+        self
+    }
+
+    /// Copy MESSAGE frames a subscription has taken to `next_frame()` only
+    /// if its queue has room (builder style).
+    ///
+    /// Every MESSAGE is also queued for `next_frame()`, and once that queue
+    /// holds `Connection::inbound_capacity()` frames the connection waits
+    /// for room before reading more from the broker. An application that
+    /// consumes only through subscriptions and never calls `next_frame()`
+    /// stalls after that many messages unless this is set; with it, copies
+    /// of frames a subscription has taken are dropped instead. Frames no
+    /// subscription took are still queued without loss.
+    pub fn best_effort_mirror(mut self, enabled: bool) -> Self {
+        self.best_effort_mirror = enabled;
+        self
+    }
+
+    /// Validate outgoing frames against the STOMP session state (builder
+    /// style).
+    ///
+    /// When enabled, the connection tracks active subscriptions and open
+    /// transactions and refuses frames the broker would reject, such as an
+    /// ACK for an unknown subscription, a COMMIT of a transaction that was
+    /// never begun, a manually sent CONNECT, or anything sent after
+    /// DISCONNECT. The offending call returns `ConnError::Protocol` with a
+    /// description of the mistake and nothing is sent, instead of the broker
+    /// answering with an ERROR and closing the connection.
+    ///
+    /// Disabled by default.
+    pub fn strict_protocol(mut self, enabled: bool) -> Self {
+        self.strict_protocol = enabled;
+        self
+    }
+
+    /// Inspect a fraction of inbound messages (builder style).
+    ///
+    /// `callback` receives a copy of every sampled MESSAGE frame, with all
+    /// headers and the body, for uses such as payload auditing or schema
+    /// drift detection. `rate` is the fraction of messages to sample, from
+    /// `0.0` to `1.0`; `0.01` samples every hundredth message.
+    ///
+    /// The callback runs on its own task, off the connection's read path.
+    /// Samples that arrive while it is still busy are dropped, so keep it
+    /// quick or hand heavy work to another task.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = ConnectOptions::default().on_message_sample(0.01, |frame| {
+    ///     if serde_json::from_slice::<Order>(&frame.body).is_err() {
+    ///         tracing::warn!(destination = ?frame.get_header("destination"), "schema drift");
+    ///     }
+    /// });
+    /// ```
+    pub fn on_message_sample<F>(mut self, rate: f64, callback: F) -> Self
+    where
+        F: Fn(Frame) + Send + Sync + 'static,
+    {
+        self.message_sampler = Some(MessageSampler::new(rate, callback));
+        self
+    }
+
+    /// Check destinations against `broker`'s naming rules before use
+    /// (builder style).
+    ///
+    /// `subscribe` and SEND frames passed to `send_frame` are validated with
+    /// `destination::validate()`. Destinations with errors are refused with
+    /// `ConnError::Protocol` and nothing is sent; warnings are logged.
+    /// Disabled by default.
+    pub fn validate_destinations(mut self, broker: BrokerProfile) -> Self {
+        self.destination_validation = Some(broker);
+        self
+    }
+
+    /// Stamp every outgoing SEND frame with `publisher-id` and
+    /// `publisher-seq` headers (builder style).
+    ///
+    /// The counter is loaded from the sequence's `SequenceStore` when
+    /// connecting, so numbering continues across restarts when a persistent
+    /// store is used. Consumers can drop re-sent copies with
+    /// `DuplicateFilter`. If the store cannot be read, `connect` fails with
+    /// `ConnError::Io`; if it cannot be written, `send_frame` does.
+    pub fn publisher_sequence(mut self, sequence: PublisherSequence) -> Self {
+        self.publisher_sequence = Some(sequence);
+        self
+    }
+
+    /// Record this connection's messages, heartbeats, receipts, reconnects
+    /// and broker errors with `recorder` (builder style).
+    ///
+    /// Keep a clone of the recorder to take a `SessionReport` at any time,
+    /// also after the connection is closed. Counts carry on over
+    /// reconnects.
+    pub fn recorder(mut self, recorder: SessionRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Simulate a misbehaving broker by dropping, duplicating or delaying
+    /// inbound MESSAGE frames (builder style).
+    ///
+    /// Meant for checking ACK handling and idempotency in tests; it only
+    /// takes effect in debug builds or with the `testing` feature, and is
+    /// ignored with a warning otherwise. See [`Chaos`] for what each setting
+    /// does.
+    pub fn chaos(mut self, chaos: Chaos) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// Connect over TLS (builder style). Requires the `tls` feature.
+    ///
+    /// The TLS handshake runs on every connection attempt, including
+    /// reconnects. Certificate problems on the initial connection are
+    /// returned as `ConnError::Io` with `io::ErrorKind::InvalidData`
+    /// instead of being retried, since retrying cannot fix them.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Limit the SEND frames in flight to `max` (builder style).
+    ///
+    /// A SEND that requests a receipt is in flight until the RECEIPT
+    /// arrives, or until the receipt fails, times out, or expires; any other
+    /// SEND until the writer has flushed it to the socket. Once `max` sends
+    /// are in flight, `send_frame()` and the other send methods wait for one
+    /// to complete, so a fast publisher cannot build up an unbounded
+    /// backlog of unconfirmed messages on a slow broker. See also
+    /// `Connection::inflight_sends()` and `Connection::drain_sends()`.
+    /// `validate()` rejects zero with `ConfigError::ZeroInflightSends`.
+    pub fn max_inflight_sends(mut self, max: usize) -> Self {
+        self.max_inflight_sends = Some(max);
+        self
+    }
+
+    /// Write frames larger than `bytes` in slices of `bytes` (builder
+    /// style).
+    ///
+    /// A multi-megabyte SEND otherwise occupies the connection task until
+    /// the whole frame is on the socket: inbound frames and heartbeats are
+    /// not read meanwhile, so a slow link can trip the heartbeat watchdog or
+    /// `read_timeout()`. With a chunk size the task writes one slice at a
+    /// time and handles inbound traffic in between. Other outbound frames,
+    /// ACKs and heartbeats included, still wait for the large frame to
+    /// finish, since STOMP cannot interleave frames; the slices themselves
+    /// keep the broker's heartbeat deadline satisfied. `validate()` rejects
+    /// zero with `ConfigError::ZeroWriteChunkSize`.
+    pub fn write_chunk_size(mut self, bytes: usize) -> Self {
+        self.write_chunk_size = Some(bytes);
+        self
+    }
+
+    /// Drop the connection after `multiplier` negotiated receive intervals
+    /// without inbound data (builder style).
+    ///
+    /// The heartbeat watchdog reconnects when nothing, neither a frame nor a
+    /// heartbeat, has arrived for this long. The default of 2.0 tolerates
+    /// one missed heartbeat; raise it for brokers that send late under load,
+    /// or lower it towards 1.0 to notice a dead broker sooner. With a
+    /// multiplier close to 1.0 a heartbeat that is only slightly late drops
+    /// the connection. `validate()` rejects values below 1.0 or above 100.0
+    /// with `ConfigError::InvalidHeartbeatGrace`.
+    pub fn heartbeat_grace_multiplier(mut self, multiplier: f64) -> Self {
+        self.heartbeat_grace_multiplier = Some(multiplier);
+        self
+    }
+
+    /// Vary the interval before each outgoing heartbeat randomly by up to
+    /// `fraction` of the negotiated interval either way (builder style).
+    ///
+    /// Many clients started together otherwise send their heartbeats in
+    /// lockstep; jitter spreads them out. A heartbeat may then be sent up
+    /// to `fraction` late, so make sure the broker's own tolerance covers
+    /// it (most allow at least one and a half intervals). `validate()`
+    /// rejects values outside `0.0..=0.5` with
+    /// `ConfigError::InvalidHeartbeatJitter`.
+    pub fn heartbeat_jitter(mut self, fraction: f64) -> Self {
+        self.heartbeat_jitter = Some(fraction);
+        self
+    }
+
+    /// Give each resolved address of the broker `timeout` to accept the TCP
+    /// connection before trying the next one (builder style).
+    ///
+    /// A host name can resolve to several IPv4 and IPv6 addresses. They are
+    /// tried one at a time, alternating between address families, starting
+    /// with the address that last worked, so a single unreachable address
+    /// costs at most `timeout` instead of the operating system's much
+    /// longer connect timeout. `ServerInfo::peer_addr` shows which address
+    /// answered. The default is 5 seconds. `validate()` rejects a zero
+    /// timeout with `ConfigError::ZeroConnectAttemptTimeout`.
+    pub fn connect_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Store messages passed to `Connection::send_durable()` in `outbox`
+    /// until the broker confirms them (builder style).
+    ///
+    /// The connection sends whatever the outbox holds as soon as it is
+    /// established, including entries left over from a previous run, and
+    /// keeps at it across reconnects. See [`Outbox`] for the delivery
+    /// guarantees.
+    pub fn outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = Some(outbox);
+        self
+    }
+
+    /// Send CONNECT without `login` and `passcode` headers (builder style).
+    ///
+    /// For brokers that authenticate by TLS client certificate (SASL
+    /// EXTERNAL) or allow anonymous access, some of which reject a CONNECT
+    /// that carries credentials. The `login` and `passcode` arguments of
+    /// `connect_with_options()` are then ignored.
+    pub fn omit_credentials(mut self) -> Self {
+        self.omit_credentials = true;
+        self
+    }
+
+    /// Send heartbeats as CRLF instead of a bare LF (builder style).
+    ///
+    /// STOMP allows either as an end-of-line. Heartbeats received as CRLF
+    /// are understood regardless; use this for peers or gateways that
+    /// expect CRLF line endings themselves.
+    pub fn crlf_heartbeats(mut self) -> Self {
+        self.crlf_heartbeats = true;
+        self
+    }
+
+    /// Keep at most `bytes` of the body of a frame received during the
+    /// CONNECT handshake (builder style).
+    ///
+    /// A broker rejecting the connection explains why in the body of an
+    /// ERROR frame. The rest of an oversized body is read and discarded
+    /// as it arrives rather than buffered, and `ServerError::body` ends
+    /// with a `[truncated after N bytes]` marker. Headers are always kept
+    /// whole. The default is 64 KiB.
+    pub fn handshake_error_body_limit(mut self, bytes: usize) -> Self {
+        self.handshake_error_body_limit = Some(bytes);
+        self
+    }
+
+    /// Give up on a broker that does not answer CONNECT within `timeout`
+    /// (builder style).
+    ///
+    /// A broker that accepts the socket but never answers fails the attempt
+    /// with `ConnError::HandshakeTimeout`, which is retried like any other
+    /// handshake failure. The default is 30 seconds.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Tune the TCP socket: `TCP_NODELAY`, keepalive and buffer sizes
+    /// (builder style). Applied to every connection, including reconnects.
+    pub fn tcp(mut self, tcp: TcpOptions) -> Self {
+        self.tcp = tcp;
+        self
+    }
+
+    /// Pause reconnecting after repeated failed sessions (builder style).
+    ///
+    /// Without a breaker a broker that accepts the connection but ends
+    /// every session at once is retried every 1 to 30 seconds forever. See
+    /// [`CircuitBreaker`] for what counts as a failure and what happens
+    /// while the circuit is open. `validate()` rejects a breaker with zero
+    /// `failures` with `ConfigError::ZeroCircuitBreakerFailures`.
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Refuse to connect unless TLS is configured (builder style).
+    ///
+    /// Guards against a deployment that forgot its TLS settings silently
+    /// talking plain TCP: `validate()` then fails with
+    /// `ConfigError::MissingTlsConfig`, as it always does when the crate
+    /// was built without the `tls` feature.
+    pub fn require_tls(mut self) -> Self {
+        self.require_tls = true;
+        self
+    }
+
+    /// Check the options for settings that cannot work.
+    ///
+    /// # Errors
+    ///
+    /// - `ConfigError::ProtectedHeaderOverride` if a custom header names a
+    ///   CONNECT header that has a dedicated option.
+    /// - `ConfigError::EmptyHost` if the virtual host is set to `""`.
+    /// - `ConfigError::MissingTlsConfig` if `require_tls()` is set without
+    ///   TLS settings.
+    /// - `ConfigError::ZeroInflightSends` if `max_inflight_sends` is 0.
+    /// - `ConfigError::ZeroWriteChunkSize` if `write_chunk_size` is 0.
+    /// - `ConfigError::InvalidHeartbeatGrace` if `heartbeat_grace_multiplier`
+    ///   is not between 1 and 100.
+    /// - `ConfigError::InvalidHeartbeatJitter` if `heartbeat_jitter` is not
+    ///   between 0 and 0.5.
+    /// - `ConfigError::ZeroConnectAttemptTimeout` if `connect_attempt_timeout`
+    ///   is zero.
+    /// - `ConfigError::ZeroCircuitBreakerFailures` if the circuit breaker
+    ///   opens after 0 failures.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some((name, _)) = self.headers.iter().find(|(name, _)| {
+            PROTECTED_CONNECT_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+        }) {
+            return Err(ConfigError::ProtectedHeaderOverride(name.clone()));
+        }
+        if self
+            .host
+            .as_deref()
+            .is_some_and(|host| host.trim().is_empty())
+        {
+            return Err(ConfigError::EmptyHost);
+        }
+        #[cfg(feature = "tls")]
+        let has_tls = self.tls.is_some();
+        #[cfg(not(feature = "tls"))]
+        let has_tls = false;
+        if self.require_tls && !has_tls {
+            return Err(ConfigError::MissingTlsConfig);
+        }
+        if self.max_inflight_sends == Some(0) {
+            return Err(ConfigError::ZeroInflightSends);
+        }
+        if self.write_chunk_size == Some(0) {
+            return Err(ConfigError::ZeroWriteChunkSize);
+        }
+        if self
+            .heartbeat_grace_multiplier
+            .is_some_and(|m| !(1.0..=MAX_HEARTBEAT_GRACE).contains(&m))
+        {
+            return Err(ConfigError::InvalidHeartbeatGrace);
+        }
+        if self
+            .heartbeat_jitter
+            .is_some_and(|j| !(0.0..=MAX_HEARTBEAT_JITTER).contains(&j))
+        {
+            return Err(ConfigError::InvalidHeartbeatJitter);
+        }
+        if self.connect_attempt_timeout == Some(Duration::ZERO) {
+            return Err(ConfigError::ZeroConnectAttemptTimeout);
+        }
+        if self.circuit_breaker.is_some_and(|b| b.failures == 0) {
+            return Err(ConfigError::ZeroCircuitBreakerFailures);
+        }
+        Ok(())
+    }
+
+    /// Finish building: validate the options and return them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use iridium_stomp::{ConfigError, ConnectOptions};
+    ///
+    /// let err = ConnectOptions::new().header("login", "admin").build().unwrap_err();
+    /// assert_eq!(err, ConfigError::ProtectedHeaderOverride("login".to_string()));
+    /// ```
+    pub fn build(self) -> Result<Self, ConfigError> {
+        self.validate()?;
+        Ok(self)
+    }
+}
+
+/// Check that a client `heart-beat` value is two comma-separated
+/// millisecond counts, e.g. "10000,10000".
+fn validate_heartbeat(value: &str) -> Result<(), ConfigError> {
+    let mut parts = value.split(',');
+    let valid = matches!(
+        (parts.next(), parts.next(), parts.next()),
+        (Some(cx), Some(cy), None)
+            if cx.trim().parse::<u32>().is_ok() && cy.trim().parse::<u32>().is_ok()
+    );
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidHeartbeat(value.to_string()))
+    }
+}
+
+/// Parse the STOMP `heart-beat` header value (format: "cx,cy").
+///
+/// Parameters
+/// - `header`: header string from the server or client (for example
+///   "10000,10000"). The values represent milliseconds.
+///
+/// Returns a tuple `(cx, cy)` where each value is the heartbeat interval in
+/// milliseconds. Missing or invalid fields default to `0`.
+pub fn parse_heartbeat_header(header: &str) -> (u64, u64) {
+    let mut parts = header.split(',');
+    let cx = parts
+        .next()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let cy = parts
+        .next()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    (cx, cy)
+}
+
+/// Negotiate heartbeat intervals between client and server.
+///
+/// Parameters
 /// - `client_out`: client's desired outgoing heartbeat interval in
 ///   milliseconds (how often the client will send heartbeats).
 /// - `client_in`: client's desired incoming heartbeat interval in
@@ -523,6 +2003,30 @@ pub fn negotiate_heartbeats(
     (outgoing, incoming)
 }
 
+/// How long the watchdog waits for inbound data before dropping the
+/// connection, given the negotiated receive interval.
+fn heartbeat_grace_period(recv_interval: Duration, multiplier: f64) -> Duration {
+    recv_interval.mul_f64(multiplier)
+}
+
+/// The wait before the next outgoing heartbeat: `interval` moved by up to
+/// `jitter` of itself either way, by the uniformly distributed `random`.
+fn jittered_interval(interval: Duration, jitter: f64, random: u64) -> Duration {
+    if jitter == 0.0 {
+        return interval;
+    }
+    // The top 53 bits as a fraction in [0, 1), then scaled to [-1, 1)
+    let unit = (random >> 11) as f64 / (1u64 << 53) as f64;
+    interval.mul_f64(1.0 + jitter * (2.0 * unit - 1.0))
+}
+
+/// A random number for heartbeat jitter. `RandomState` is seeded randomly
+/// per process and advanced per instance, which is plenty here.
+fn jitter_random() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(())
+}
+
 /// Extract the destination from an ERROR frame.
 ///
 /// Tries multiple strategies:
@@ -588,7 +2092,7 @@ fn extract_subscription_id_from_error(frame: &Frame) -> Option<String> {
 async fn lookup_destination_by_sub_id(
     sub_id: &str,
     subscriptions: &Arc<Mutex<Subscriptions>>,
-) -> Option<String> {
+) -> Option<Destination> {
     let map = subscriptions.lock().await;
     for (dest, entries) in map.iter() {
         for entry in entries {
@@ -608,9 +2112,14 @@ async fn lookup_destination_by_sub_id(
 #[derive(Clone)]
 pub struct Connection {
     outbound_tx: mpsc::Sender<StompItem>,
+    /// Held while queueing frames on `outbound_tx`, so the frames of one
+    /// `execute()` batch are written back to back.
+    writer_lane: Arc<Mutex<()>>,
     /// The inbound receiver is shared behind a mutex so the `Connection`
     /// handle may be cloned and callers can call `next_frame` concurrently.
-    inbound_rx: Arc<Mutex<mpsc::Receiver<Frame>>>,
+    inbound_rx: Arc<Mutex<Inbound>>,
+    /// Frames waiting to be returned by `next_frame`, including a peeked one.
+    inbound_queued: Arc<AtomicUsize>,
     shutdown_tx: broadcast::Sender<()>,
     /// Map of destination -> list of (subscription id, sender) for dispatching
     /// inbound MESSAGE frames to subscribers.
@@ -625,12 +2134,160 @@ pub struct Connection {
     /// For `client-individual` the ACK/NACK applies only to the single
     /// message.
     pending: Arc<Mutex<PendingMap>>,
+    /// Signalled whenever the application settles pending messages, for a
+    /// reader waiting under `PendingOverflow::StopDelivering`.
+    pending_released: Arc<Notify>,
+    /// Messages whose ACK or NACK fails with
+    /// `ConnError::MessageInvalidated`, per subscription.
+    invalidated: Arc<Mutex<Invalidated>>,
     /// Pending receipt confirmations.
     ///
     /// When a frame is sent with a `receipt` header, the receipt-id is stored
     /// here with a oneshot sender. When the server responds with a RECEIPT
     /// frame, the sender is notified.
     pending_receipts: Arc<Mutex<PendingReceipts>>,
+    /// Number of receipts discarded by TTL cleanup.
+    expired_receipts: Arc<AtomicU64>,
+    /// Number of inbound frames with an unhandled command.
+    unknown_frames: Arc<AtomicU64>,
+    /// Codec decode counters, shared by the codecs of every session.
+    decode_counters: Arc<DecodeCounters>,
+    /// Details from the most recent CONNECTED frame.
+    server_info: Arc<Mutex<ServerInfo>>,
+    /// Provider of trace context injected on outgoing SEND frames.
+    trace_context: Option<TraceContextProvider>,
+    /// Session state for `ConnectOptions::strict_protocol()` validation.
+    protocol: Option<Arc<Mutex<ProtocolState>>>,
+    /// Broker profile for `ConnectOptions::validate_destinations()`.
+    destination_validation: Option<BrokerProfile>,
+    /// From `ConnectOptions::header_policy()`, checked before queueing.
+    header_policy: HeaderPolicy,
+    /// Stamper for `ConnectOptions::publisher_sequence()`.
+    sequencer: Option<Arc<Sequencer>>,
+    /// Limiter for `ConnectOptions::max_inflight_sends()`.
+    inflight: Option<InflightLimiter>,
+    /// Requests to re-run the handshake, handled by the background task.
+    reconfigure_tx: mpsc::Sender<Reconfigure>,
+    /// Requests to flush and close, handled by the background task.
+    close_tx: mpsc::Sender<CloseRequest>,
+    /// Listener from `ConnectOptions::with_event_notify()`.
+    event_tx: Option<mpsc::Sender<ConnectionEvent>>,
+    /// Store from `ConnectOptions::outbox()`.
+    outbox: Option<Outbox>,
+    /// Set once `ConnectionBuilder::start()` has connected. Subscriptions
+    /// made before that are sent by the first session.
+    started: Arc<AtomicBool>,
+    /// Destination names shared by the subscriptions of this connection.
+    destinations: Arc<DestinationTable>,
+    /// Shared by every handle; the connection shuts down when the last one
+    /// is dropped. `None` only inside a `WeakConnection`.
+    lifeline: Option<Arc<Lifeline>>,
+    /// Broker address and login, for naming background tasks.
+    task_labels: TaskLabels,
+    /// Body sizes and content types of received messages.
+    message_counters: Arc<MessageCounters>,
+    /// Open while `ConnectOptions::circuit_breaker()` pauses reconnects.
+    circuit: Arc<CircuitSwitch>,
+    /// Source of `tap()` streams; `None` once the background task ended.
+    tap_tx: Arc<std::sync::Mutex<Option<broadcast::Sender<Frame>>>>,
+}
+
+/// Shuts the connection down when dropped, that is when the last
+/// `Connection` handle goes away.
+struct Lifeline {
+    shutdown_tx: broadcast::Sender<()>,
+    inflight: Option<InflightLimiter>,
+}
+
+impl Drop for Lifeline {
+    fn drop(&mut self) {
+        // As in `Connection::close()`
+        if let Some(inflight) = &self.inflight {
+            inflight.close();
+        }
+        let _ = self.shutdown_tx.send(());
+    }
+}
+
+/// A handle to a `Connection` that does not keep it alive.
+///
+/// Obtained with `Connection::downgrade()`. Once every `Connection` handle
+/// is dropped the background task shuts down, as if `close()` had been
+/// called, and `upgrade()` returns `None`. `Subscription`s hold one of
+/// these, so a subscription left behind does not keep the connection
+/// running.
+#[derive(Clone)]
+pub struct WeakConnection {
+    /// The connection's shared state, without its lifeline.
+    conn: Connection,
+    lifeline: Weak<Lifeline>,
+}
+
+impl WeakConnection {
+    /// Broker address and login, for naming background tasks.
+    pub(crate) fn task_labels(&self) -> &TaskLabels {
+        &self.conn.task_labels
+    }
+
+    /// A `Connection` handle, unless every one has been dropped.
+    pub fn upgrade(&self) -> Option<Connection> {
+        let lifeline = self.lifeline.upgrade()?;
+        Some(Connection {
+            lifeline: Some(lifeline),
+            ..self.conn.clone()
+        })
+    }
+}
+
+impl std::fmt::Debug for WeakConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeakConnection")
+            .field("alive", &(self.lifeline.strong_count() > 0))
+            .finish()
+    }
+}
+
+/// A `Connection` that has not connected yet.
+///
+/// Returned by `Connection::builder()`. Set up subscriptions and other
+/// consumers on `connection()`, then call `start()` to connect.
+pub struct ConnectionBuilder {
+    conn: Connection,
+    start: Pin<Box<dyn Future<Output = Result<(), ConnError>> + Send>>,
+}
+
+impl ConnectionBuilder {
+    /// The connection being set up. Clones of it keep working once
+    /// `start()` has connected.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Connect and start the background I/O task, retrying unreachable
+    /// brokers with backoff like `Connection::connect_with_options()`.
+    ///
+    /// # Errors
+    ///
+    /// The errors of `Connection::connect_with_options()`. Clones of the
+    /// connection fail with `ConnError::ChannelClosed` afterwards.
+    pub async fn start(self) -> Result<Connection, ConnError> {
+        self.start.await?;
+        let conn = self.conn;
+        if let Some(outbox) = conn.outbox.clone() {
+            task::spawn(
+                "outbox",
+                &conn.task_labels,
+                drain_outbox(conn.downgrade(), outbox, conn.shutdown_tx.subscribe()),
+            );
+        }
+        Ok(conn)
+    }
+}
+
+impl std::fmt::Debug for ConnectionBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionBuilder").finish_non_exhaustive()
+    }
 }
 
 impl Connection {
@@ -673,7 +2330,7 @@ impl Connection {
     /// credentials and heartbeat header string (e.g. "10000,10000").
     ///
     /// This is a convenience wrapper around `connect_with_options()` that uses
-    /// default options (STOMP 1.2, host="/", no client-id).
+    /// default options (STOMP 1.2, host derived from `addr`, no client-id).
     ///
     /// If the broker is unreachable, this method retries with exponential
     /// backoff (1s → 2s → 4s → … → 30s cap). Authentication errors
@@ -681,7 +2338,8 @@ impl Connection {
     /// [`connect_with_options`](Self::connect_with_options) for full details.
     ///
     /// Parameters
-    /// - `addr`: TCP address (host:port) of the STOMP server.
+    /// - `addr`: address of the STOMP server, `host:port` or
+    ///   `stomp://host:port/vhost`.
     /// - `login`: login username for STOMP `CONNECT`.
     /// - `passcode`: passcode for STOMP `CONNECT`.
     /// - `client_hb`: client's `heart-beat` header value ("cx,cy" in
@@ -708,7 +2366,8 @@ impl Connection {
     /// versions, or add custom CONNECT headers.
     ///
     /// Parameters
-    /// - `addr`: TCP address (host:port) of the STOMP server.
+    /// - `addr`: address of the STOMP server, `host:port` or
+    ///   `stomp://host:port/vhost`.
     /// - `login`: login username for STOMP `CONNECT`.
     /// - `passcode`: passcode for STOMP `CONNECT`.
     /// - `client_hb`: client's `heart-beat` header value ("cx,cy" in
@@ -727,10 +2386,30 @@ impl Connection {
     /// Returns an error immediately (no retry) if:
     /// - The server rejects the connection, e.g., due to invalid credentials
     ///   (`ConnError::ServerRejected`)
+    /// - The server negotiates a STOMP version not listed in
+    ///   `accept_version` (`ConnError::VersionMismatch`)
+    /// - The options, heart-beat value or address are invalid
+    ///   (`ConnError::Config`)
     ///
     /// All other errors (TCP refused, connection closed mid-handshake, I/O
     /// failures) are retried with backoff.
     ///
+    /// # Virtual host
+    ///
+    /// The CONNECT `host` header is, in order of precedence:
+    ///
+    /// 1. `ConnectOptions::host()`, if set;
+    /// 2. the vhost of a `stomp://host:port/vhost` address (percent-decoded,
+    ///    so `/%2F` means `/`);
+    /// 3. the host name of the address, e.g. `broker.example.com` for
+    ///    `broker.example.com:61613`;
+    /// 4. `/` when the address is an IP literal.
+    ///
+    /// The same value is sent on every reconnect. RabbitMQ treats `host` as
+    /// the name of a vhost and refuses unknown ones, so when reaching it by
+    /// host name set the vhost explicitly (for the default one,
+    /// `.host("/")` or a `stomp://rabbit:61613/%2F` address).
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -755,536 +2434,332 @@ impl Connection {
         client_hb: &str,
         options: ConnectOptions,
     ) -> Result<Self, ConnError> {
-        let (out_tx, mut out_rx) = mpsc::channel::<StompItem>(32);
-        let (in_tx, in_rx) = mpsc::channel::<Frame>(32);
-        let subscriptions: Arc<Mutex<Subscriptions>> = Arc::new(Mutex::new(HashMap::new()));
-        let sub_id_counter = Arc::new(AtomicU64::new(1));
-        let (shutdown_tx, _) = broadcast::channel::<()>(1);
-        let pending: Arc<Mutex<PendingMap>> = Arc::new(Mutex::new(HashMap::new()));
-        let pending_clone = pending.clone();
-        let pending_receipts: Arc<Mutex<PendingReceipts>> = Arc::new(Mutex::new(HashMap::new()));
-        let pending_receipts_clone = pending_receipts.clone();
-
-        let addr = addr.to_string();
-        let login = login.to_string();
-        let passcode = passcode.to_string();
-        let client_hb = client_hb.to_string();
-
-        // Extract options into owned values for the spawned task
-        let accept_version = options.accept_version.unwrap_or_else(|| "1.2".to_string());
-        let host = options.host.unwrap_or_else(|| "/".to_string());
-        let client_id = options.client_id;
-        let custom_headers = options.headers;
-        let heartbeat_notify_tx = options.heartbeat_tx;
-
-        // Perform initial connection and STOMP handshake before spawning
-        // background task. Retries with exponential backoff on I/O and
-        // protocol errors (broker unreachable or crashing mid-handshake)
-        // using the same strategy as reconnection. Only ServerRejected
-        // (authentication failure) fails immediately.
-        let mut backoff_secs: u64 = 1;
-        let (framed, send_interval, recv_interval) = loop {
-            let stream = match TcpStream::connect(&addr).await {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::warn!(
-                        addr = %addr,
-                        error = %e,
-                        backoff_secs,
-                        "initial connect failed, retrying in {}s",
-                        backoff_secs,
-                    );
-                    tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-                    backoff_secs = (backoff_secs * 2).min(30);
-                    continue;
-                }
-            };
-            let mut framed = Framed::new(stream, StompCodec::new());
-
-            let connect = Self::build_connect_frame(
-                &accept_version,
-                &host,
-                &login,
-                &passcode,
-                &client_hb,
-                &client_id,
-                &custom_headers,
-            );
-
-            if let Err(e) = framed.send(StompItem::Frame(connect)).await {
-                tracing::warn!(
-                    addr = %addr,
-                    error = %e,
-                    backoff_secs,
-                    "failed to send CONNECT frame, retrying in {}s",
-                    backoff_secs,
-                );
-                tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-                backoff_secs = (backoff_secs * 2).min(30);
-                continue;
-            }
+        Self::builder(addr, login, passcode, client_hb, options)?
+            .start()
+            .await
+    }
 
-            match Self::await_connected_response(&mut framed).await {
-                Ok(server_hb) => {
-                    tracing::info!(addr = %addr, "connected to broker");
-                    let (cx, cy) = parse_heartbeat_header(&client_hb);
-                    let (sx, sy) = parse_heartbeat_header(&server_hb);
-                    let (si, ri) = negotiate_heartbeats(cx, cy, sx, sy);
-                    break (framed, si, ri);
-                }
-                // Auth errors fail immediately — bad config should not be retried
-                Err(e @ ConnError::ServerRejected(_)) => {
-                    return Err(e);
-                }
-                // I/O and protocol errors during handshake (e.g., broker
-                // crashed or closed mid-handshake) — retry with backoff
-                Err(e) => {
-                    tracing::warn!(
-                        addr = %addr,
-                        error = %e,
-                        backoff_secs,
-                        "handshake failed, retrying in {}s",
-                        backoff_secs,
-                    );
-                    tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-                    backoff_secs = (backoff_secs * 2).min(30);
-                    continue;
-                }
-            }
-        };
-
-        // Now spawn background task for ongoing I/O and reconnection
-        let shutdown_tx_clone = shutdown_tx.clone();
-        let subscriptions_clone = subscriptions.clone();
-
-        tokio::spawn(async move {
-            let mut backoff_secs: u64 = 1;
-
-            // Use the already-established connection for the first iteration
-            let mut current_framed = Some(framed);
-            let mut current_send_interval = send_interval;
-            let mut current_recv_interval = recv_interval;
-
-            // Track subscription errors across reconnections. If a subscription
-            // receives too many consecutive errors, we remove it to prevent
-            // error loops (e.g., Artemis sending repeated permission errors).
-            let mut subscription_errors: HashMap<String, u32> = HashMap::new();
-            // Track subscription IDs that have been abandoned so we can ignore
-            // subsequent errors for them.
-            let mut abandoned_sub_ids: std::collections::HashSet<String> =
-                std::collections::HashSet::new();
-            const SUBSCRIPTION_ERROR_THRESHOLD: u32 = 3;
-
-            loop {
-                let mut shutdown_sub = shutdown_tx_clone.subscribe();
-
-                // Check for shutdown before attempting connection
-                tokio::select! {
-                    biased;
-                    _ = shutdown_sub.recv() => break,
-                    _ = future::ready(()) => {},
-                }
-
-                // Either use existing connection or establish new one (reconnect)
-                let framed = if let Some(f) = current_framed.take() {
-                    f
-                } else {
-                    // Reconnection attempt
-                    match TcpStream::connect(&addr).await {
-                        Ok(stream) => {
-                            let mut framed = Framed::new(stream, StompCodec::new());
-
-                            let connect = Self::build_connect_frame(
-                                &accept_version,
-                                &host,
-                                &login,
-                                &passcode,
-                                &client_hb,
-                                &client_id,
-                                &custom_headers,
-                            );
-
-                            if let Err(e) = framed.send(StompItem::Frame(connect)).await {
-                                tracing::warn!(
-                                    addr = %addr,
-                                    error = %e,
-                                    backoff_secs,
-                                    "reconnect: failed to send CONNECT frame, retrying in {}s",
-                                    backoff_secs,
-                                );
-                                tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-                                backoff_secs = (backoff_secs * 2).min(30);
-                                continue;
-                            }
-
-                            match Self::await_connected_response(&mut framed).await {
-                                Ok(server_hb) => {
-                                    tracing::info!(addr = %addr, "reconnected to broker");
-                                    let (cx, cy) = parse_heartbeat_header(&client_hb);
-                                    let (sx, sy) = parse_heartbeat_header(&server_hb);
-                                    let (si, ri) = negotiate_heartbeats(cx, cy, sx, sy);
-                                    current_send_interval = si;
-                                    current_recv_interval = ri;
-                                    framed
-                                }
-                                Err(e) => {
-                                    tracing::warn!(
-                                        addr = %addr,
-                                        error = %e,
-                                        backoff_secs,
-                                        "reconnect: handshake failed, retrying in {}s",
-                                        backoff_secs,
-                                    );
-                                    tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-                                    backoff_secs = (backoff_secs * 2).min(30);
-                                    continue;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            tracing::warn!(
-                                addr = %addr,
-                                error = %e,
-                                backoff_secs,
-                                "reconnect: broker unreachable, retrying in {}s",
-                                backoff_secs,
-                            );
-                            tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-                            backoff_secs = (backoff_secs * 2).min(30);
-                            continue;
-                        }
-                    }
-                };
-
-                let (send_interval, recv_interval) = (current_send_interval, current_recv_interval);
-
-                let last_received = Arc::new(AtomicU64::new(current_millis()));
-                let writer_last_sent = Arc::new(AtomicU64::new(current_millis()));
-
-                let (mut sink, mut stream) = framed.split();
-                let in_tx = in_tx.clone();
-                let subscriptions = subscriptions_clone.clone();
-
-                // Clear pending message map on reconnect — messages that were
-                // outstanding before the disconnect are considered lost and
-                // will be redelivered by the server as appropriate.
-                {
-                    let mut p = pending_clone.lock().await;
-                    p.clear();
-                }
-
-                // Resubscribe any existing subscriptions after reconnect.
-                // We snapshot the subscription entries while holding the lock
-                // and then issue SUBSCRIBE frames using the sink.
-                let subs_snapshot: Vec<ResubEntry> = {
-                    let map = subscriptions.lock().await;
-                    let mut v: Vec<ResubEntry> = Vec::new();
-                    for (dest, vec) in map.iter() {
-                        for entry in vec.iter() {
-                            v.push((
-                                dest.clone(),
-                                entry.id.clone(),
-                                entry.ack.clone(),
-                                entry.headers.clone(),
-                            ));
-                        }
-                    }
-                    v
-                };
-
-                for (dest, id, ack, headers) in subs_snapshot {
-                    let mut sf = Frame::new("SUBSCRIBE");
-                    sf = sf
-                        .header("id", &id)
-                        .header("destination", &dest)
-                        .header("ack", &ack);
-                    for (k, v) in headers {
-                        sf = sf.header(&k, &v);
-                    }
-                    let _ = sink.send(StompItem::Frame(sf)).await;
-                }
+    /// Connect to `addr` only to watch whether the broker is alive.
+    ///
+    /// Performs the CONNECT handshake and exchanges heartbeats, reconnecting
+    /// like any connection, but never subscribes or sends data: a small
+    /// probe for dashboards watching many brokers. The returned
+    /// [`Monitor`](crate::Monitor) reports the broker's health (up, late
+    /// with its heartbeats, or down), the handshake time and the server
+    /// details, as a snapshot or as a stream of changes.
+    ///
+    /// Connection errors are handled as by `connect_with_options()`: the
+    /// first connect is retried while the broker is unreachable, and a
+    /// rejected CONNECT is returned as an error.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use iridium_stomp::{Connection, Heartbeat, MonitorOptions};
+    ///
+    /// let options = MonitorOptions {
+    ///     credentials: Some(("monitor".into(), "secret".into())),
+    ///     heartbeat: Heartbeat::new(5000, 5000),
+    ///     ..Default::default()
+    /// };
+    /// let mut monitor = Connection::monitor("broker-1:61613", options).await?;
+    /// while let Some(status) = monitor.changed().await {
+    ///     println!("broker-1 is {} ({:?} handshake)", status.health.as_str(), status.handshake);
+    /// }
+    /// ```
+    pub async fn monitor(
+        addr: &str,
+        options: crate::monitor::MonitorOptions,
+    ) -> Result<crate::monitor::Monitor, ConnError> {
+        crate::monitor::Monitor::start(addr, options).await
+    }
 
-                let mut hb_tick = match send_interval {
-                    Some(d) => tokio::time::interval(d),
-                    None => tokio::time::interval(Duration::from_secs(86400)),
-                };
-                let watchdog_half = recv_interval.map(|d| d / 2);
+    /// Set up a connection without connecting yet.
+    ///
+    /// The returned `ConnectionBuilder` holds a complete `Connection` whose
+    /// I/O has not started: subscriptions, routers and listeners can be set
+    /// up on `ConnectionBuilder::connection()` first, and
+    /// `ConnectionBuilder::start()` then connects exactly like
+    /// `connect_with_options()`. Subscriptions made before `start()` are
+    /// sent as part of the first session, so no message arrives before
+    /// there is a consumer for it.
+    ///
+    /// Up to 32 frames sent before `start()` are queued and written once
+    /// connected; sending more fails with `ConnError::Protocol`, as does
+    /// waiting for a receipt (`wait_for_receipt()`,
+    /// `send_frame_confirmed()`, `SubscriptionBuilder::receipt()`, and the
+    /// receipt options of `send_with_retry()` and `execute_with_options()`)
+    /// until then.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnError::Config` if the options, heart-beat value or
+    /// address are invalid. Connection errors are reported by `start()`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let builder = Connection::builder(
+    ///     "localhost:61613",
+    ///     "guest",
+    ///     "guest",
+    ///     Connection::DEFAULT_HEARTBEAT,
+    ///     ConnectOptions::default(),
+    /// )?;
+    /// let mut orders = builder
+    ///     .connection()
+    ///     .subscribe("/queue/orders", AckMode::Auto)
+    ///     .await?;
+    /// let conn = builder.start().await?;
+    /// ```
+    // Same error type as `connect_with_options()`, which forwards it
+    #[allow(clippy::result_large_err)]
+    pub fn builder(
+        addr: &str,
+        login: &str,
+        passcode: &str,
+        client_hb: &str,
+        options: ConnectOptions,
+    ) -> Result<ConnectionBuilder, ConnError> {
+        options.validate()?;
+        validate_heartbeat(client_hb)?;
+        let address = BrokerAddress::parse(addr)?;
+
+        let (out_tx, out_rx) = mpsc::channel::<StompItem>(OUTBOUND_CAPACITY);
+        let (in_tx, in_rx) = mpsc::channel::<Frame>(INBOUND_CAPACITY);
+        let inbound_queued = Arc::new(AtomicUsize::new(0));
+        let in_tx = InboundTx {
+            tx: in_tx,
+            queued: inbound_queued.clone(),
+        };
+        let subscriptions: Arc<Mutex<Subscriptions>> = Arc::new(Mutex::new(HashMap::new()));
+        let sub_id_counter = Arc::new(AtomicU64::new(1));
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let (reconfigure_tx, reconfigure_rx) = mpsc::channel::<Reconfigure>(1);
+        let (close_tx, close_rx) = mpsc::channel::<CloseRequest>(1);
+        let pending: Arc<Mutex<PendingMap>> = Arc::new(Mutex::new(HashMap::new()));
+        let destinations: Arc<DestinationTable> = Arc::default();
+        let pending_released: Arc<Notify> = Arc::default();
+        let invalidated: Arc<Mutex<Invalidated>> = Arc::default();
+        let pending_receipts: Arc<Mutex<PendingReceipts>> = Arc::new(Mutex::new(HashMap::new()));
 
-                let conn_start = tokio::time::Instant::now();
+        let addr = address.socket.clone();
+        let (login, passcode) = if options.omit_credentials {
+            (None, None)
+        } else {
+            (Some(login.to_string()), Some(passcode.to_string()))
+        };
+        let task_labels = TaskLabels::new(&addr, login.as_deref());
 
-                'conn: loop {
-                    tokio::select! {
-                        _ = shutdown_sub.recv() => { let _ = sink.close().await; break 'conn; }
-                        maybe = out_rx.recv() => {
-                            match maybe {
-                                Some(item) => if sink.send(item).await.is_err() { break 'conn } else { writer_last_sent.store(current_millis(), Ordering::SeqCst); }
-                                None => break 'conn,
-                            }
-                        }
-                        item = stream.next() => {
-                            match item {
-                                Some(Ok(StompItem::Heartbeat)) => {
-                                    last_received.store(current_millis(), Ordering::SeqCst);
-                                    if let Some(ref tx) = heartbeat_notify_tx {
-                                        let _ = tx.try_send(());
-                                    }
-                                }
-                                Some(Ok(StompItem::Frame(f))) => {
-                                    last_received.store(current_millis(), Ordering::SeqCst);
-                                    // Dispatch MESSAGE frames to any matching subscribers.
-                                    if f.command == "MESSAGE" {
-                                        // try to find destination, subscription and message-id headers
-                                        let mut dest_opt: Option<String> = None;
-                                        let mut sub_opt: Option<String> = None;
-                                        let mut msg_id_opt: Option<String> = None;
-                                        for (k, v) in &f.headers {
-                                            let kl = k.to_lowercase();
-                                            if kl == "destination" {
-                                                dest_opt = Some(v.clone());
-                                            } else if kl == "subscription" {
-                                                sub_opt = Some(v.clone());
-                                            } else if kl == "message-id" {
-                                                msg_id_opt = Some(v.clone());
-                                            }
-                                        }
-
-                                        // Determine whether we need to track this message as pending
-                                        let mut need_pending = false;
-                                        if let Some(sub_id) = &sub_opt {
-                                            let map = subscriptions.lock().await;
-                                            for (_dest, vec) in map.iter() {
-                                                for entry in vec.iter() {
-                                                    if &entry.id == sub_id && entry.ack != "auto" {
-                                                        need_pending = true;
-                                                    }
-                                                }
-                                            }
-                                        } else if let Some(dest) = &dest_opt {
-                                            let map = subscriptions.lock().await;
-                                            if let Some(vec) = map.get(dest) {
-                                                for entry in vec.iter() {
-                                                    if entry.ack != "auto" {
-                                                        need_pending = true;
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                        }
-
-                                        // If required, add to pending map (per-subscription) before
-                                        // delivery so ACK/NACK requests from the application can
-                                        // reference the message. We require a `message-id` header
-                                        // to track messages; if missing, we cannot support ACK/NACK.
-                                        if let Some(msg_id) = msg_id_opt.clone().filter(|_| need_pending) {
-                                            // If the server provided a subscription id in the
-                                            // MESSAGE, store pending under that subscription.
-                                            if let Some(sub_id) = &sub_opt {
-                                                let mut p = pending_clone.lock().await;
-                                                let q = p
-                                                    .entry(sub_id.clone())
-                                                    .or_insert_with(VecDeque::new);
-                                                q.push_back((msg_id.clone(), f.clone()));
-                                            } else if let Some(dest) = &dest_opt {
-                                                // Destination-based delivery: add the message to
-                                                // the pending queue for each matching
-                                                // subscription on that destination.
-                                                let map = subscriptions.lock().await;
-                                                if let Some(vec) = map.get(dest) {
-                                                    let mut p = pending_clone.lock().await;
-                                                    for entry in vec.iter() {
-                                                        let q = p
-                                                            .entry(entry.id.clone())
-                                                            .or_insert_with(VecDeque::new);
-                                                        q.push_back((msg_id.clone(), f.clone()));
-                                                    }
-                                                }
-                                            }
-                                        }
-
-                                        // Deliver to subscribers.
-                                        if let Some(sub_id) = sub_opt {
-                                            let mut map = subscriptions.lock().await;
-                                            for (_dest, vec) in map.iter_mut() {
-                                                vec.retain(|entry| {
-                                                    if entry.id == sub_id {
-                                                        let _ = entry.sender.try_send(f.clone());
-                                                        true
-                                                    } else {
-                                                        true
-                                                    }
-                                                });
-                                            }
-                                        } else if let Some(dest) = dest_opt {
-                                            let mut map = subscriptions.lock().await;
-                                            if let Some(vec) = map.get_mut(&dest) {
-                                                vec.retain(|entry| entry.sender.try_send(f.clone()).is_ok());
-                                            }
-                                        }
-                                    } else if f.command == "RECEIPT" {
-                                        // Handle RECEIPT frame: notify any waiting callers
-                                        if let Some(receipt_id) = f.get_header("receipt-id") {
-                                            let mut receipts = pending_receipts_clone.lock().await;
-                                            if let Some(sender) = receipts.remove(receipt_id) {
-                                                let _ = sender.send(());
-                                            }
-                                        }
-                                        // Don't forward RECEIPT frames to inbound channel
-                                        continue;
-                                    } else if f.command == "ERROR" {
-                                        // Track subscription-related errors. If we see repeated
-                                        // errors for the same destination, remove the subscription
-                                        // to prevent error loops.
-                                        //
-                                        // First, check if this error is for an already-abandoned
-                                        // subscription (Artemis keeps sending errors after we abandon).
-                                        let sub_id = extract_subscription_id_from_error(&f);
-                                        if let Some(ref id) = sub_id
-                                            && abandoned_sub_ids.contains(id)
-                                        {
-                                            // Skip this error - subscription already abandoned
-                                            continue;
-                                        }
-
-                                        // Try to identify the destination:
-                                        // 1. Extract directly from ERROR frame
-                                        // 2. Look up by subscription ID (Artemis uses "subscription N")
-                                        let dest = if let Some(d) = extract_destination_from_error(&f)
-                                        {
-                                            Some(d)
-                                        } else if let Some(ref id) = sub_id {
-                                            lookup_destination_by_sub_id(id, &subscriptions).await
-                                        } else {
-                                            None
-                                        };
-
-                                        if let Some(dest) = dest {
-                                            let count = {
-                                                let c = subscription_errors
-                                                    .entry(dest.clone())
-                                                    .or_insert(0);
-                                                *c += 1;
-                                                *c
-                                            };
-
-                                            if count >= SUBSCRIPTION_ERROR_THRESHOLD {
-                                                // Remove the subscription from auto-resubscribe
-                                                let mut map = subscriptions.lock().await;
-                                                if map.remove(&dest).is_some() {
-                                                    // Track the subscription ID as abandoned
-                                                    if let Some(id) = sub_id {
-                                                        abandoned_sub_ids.insert(id);
-                                                    }
-                                                    // Send abandonment notification
-                                                    let msg = format!(
-                                                        "Subscription abandoned: {} errors for {}",
-                                                        count, dest
-                                                    );
-                                                    let abandon_frame = Frame::new("ERROR")
-                                                        .header("message", &msg)
-                                                        .header("destination", &dest)
-                                                        .header("x-abandoned", "true");
-                                                    let _ = in_tx.send(abandon_frame).await;
-                                                }
-                                            }
-                                        }
-                                    }
-
-                                    let _ = in_tx.send(f).await;
-                                }
-                                Some(Err(_)) | None => break 'conn,
-                            }
-                        }
-                        _ = hb_tick.tick() => {
-                            if let Some(dur) = send_interval {
-                                let last = writer_last_sent.load(Ordering::SeqCst);
-                                if current_millis().saturating_sub(last) >= dur.as_millis() as u64 {
-                                    if sink.send(StompItem::Heartbeat).await.is_err() { break 'conn; }
-                                    writer_last_sent.store(current_millis(), Ordering::SeqCst);
-                                }
-                            }
-                        }
-                        _ = async { if let Some(interval) = watchdog_half { tokio::time::sleep(interval).await } else { future::pending::<()>().await } } => {
-                            if let Some(recv_dur) = recv_interval {
-                                let last = last_received.load(Ordering::SeqCst);
-                                if current_millis().saturating_sub(last) > (recv_dur.as_millis() as u64 * 2) {
-                                    let _ = sink.close().await; break 'conn;
-                                }
-                            }
-                        }
-                    }
-                }
+        if options.chaos.is_some() && !crate::chaos::ENABLED {
+            tracing::warn!(
+                "ConnectOptions::chaos() is ignored in release builds without the `testing` feature"
+            );
+        }
+        let inflight = options.max_inflight_sends.map(InflightLimiter::new);
+        let sequencer = match &options.publisher_sequence {
+            Some(sequence) => Some(Arc::new(sequence.open()?)),
+            None => None,
+        };
+        let sample_gate = options
+            .message_sampler
+            .as_ref()
+            .map(|sampler| sampler.spawn(&task_labels));
+        let expired_receipts = Arc::new(AtomicU64::new(0));
+        let unknown_frames = Arc::new(AtomicU64::new(0));
+        let message_counters: Arc<MessageCounters> = Arc::default();
+        let decode_counters = Arc::new(DecodeCounters::default());
+
+        let mut connector = Connector::new(
+            options
+                .connect_attempt_timeout
+                .unwrap_or(DEFAULT_CONNECT_ATTEMPT_TIMEOUT),
+        );
+        connector.tcp = options.tcp.clone();
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &options.tls {
+            connector.tls = Some(tls.setup(&addr).map_err(|e| {
+                ConnError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+            })?);
+        }
 
-                if shutdown_sub.try_recv().is_ok() {
-                    break;
-                }
-                let stable_duration = conn_start.elapsed();
-                if stable_duration >= Duration::from_secs(backoff_secs.max(5)) {
-                    // Connection was stable — reset backoff
-                    backoff_secs = 1;
-                    tracing::info!(
-                        addr = %addr,
-                        stable_secs = stable_duration.as_secs(),
-                        "connection dropped after stable session, reconnecting in 1s",
-                    );
-                } else {
-                    // Connection died quickly — increase backoff
-                    backoff_secs = (backoff_secs * 2).min(30);
-                    tracing::warn!(
-                        addr = %addr,
-                        stable_secs = stable_duration.as_secs(),
-                        backoff_secs,
-                        "connection dropped quickly, reconnecting in {}s",
-                        backoff_secs,
-                    );
-                }
-                tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
-            }
-        });
+        let server_info = Arc::new(Mutex::new(ServerInfo::unconnected()));
+        let protocol = options
+            .strict_protocol
+            .then(|| Arc::new(Mutex::new(ProtocolState::new())));
+        let started = Arc::new(AtomicBool::new(false));
+        let circuit_switch: Arc<CircuitSwitch> = Arc::default();
+        let (tap_tx, _) = broadcast::channel::<Frame>(TAP_CAPACITY);
+        let tap_tx_shared = Arc::new(std::sync::Mutex::new(Some(tap_tx.clone())));
+
+        let handshake = Handshake {
+            connector,
+            addr,
+            command: if options.stomp_command {
+                "STOMP"
+            } else {
+                "CONNECT"
+            },
+            accept_version: options.accept_version.unwrap_or_else(|| "1.2".to_string()),
+            host: address.virtual_host(options.host.as_deref()),
+            login,
+            passcode,
+            client_id: options.client_id,
+            custom_headers: options.headers,
+            content_length_policy: options.content_length_policy,
+            escape_policy: options.escape_policy,
+            header_policy: options.header_policy,
+            decode_counters: decode_counters.clone(),
+            error_body_limit: options
+                .handshake_error_body_limit
+                .unwrap_or(DEFAULT_HANDSHAKE_ERROR_BODY_LIMIT),
+            timeout: options
+                .handshake_timeout
+                .unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT),
+        };
+        let mut session_task = SessionTask {
+            handshake,
+            client_hb: client_hb.to_string(),
+            out_rx,
+            in_tx,
+            reconfigure_rx,
+            close_rx,
+            shutdown_sub: shutdown_tx.subscribe(),
+            subscriptions: subscriptions.clone(),
+            pending: pending.clone(),
+            pending_released: pending_released.clone(),
+            invalidated: invalidated.clone(),
+            pending_receipts: pending_receipts.clone(),
+            destinations: destinations.clone(),
+            server_info: server_info.clone(),
+            protocol: protocol.clone(),
+            inflight: inflight.clone(),
+            started: started.clone(),
+            expired_receipts: expired_receipts.clone(),
+            unknown_frames: unknown_frames.clone(),
+            message_counters: message_counters.clone(),
+            tap_tx,
+            tap_tx_shared: tap_tx_shared.clone(),
+            circuit: Circuit::new(options.circuit_breaker, circuit_switch.clone()),
+            sample_gate,
+            heartbeat_notify_tx: options.heartbeat_tx,
+            recorder: options.recorder,
+            event_tx: options.event_tx.clone(),
+            receipt_warning_after: options.receipt_warning_after,
+            receipt_ttl: options.receipt_ttl.unwrap_or(DEFAULT_RECEIPT_TTL),
+            read_timeout: options.read_timeout,
+            best_effort_mirror: options.best_effort_mirror,
+            crlf_heartbeats: options.crlf_heartbeats,
+            chaos: options.chaos,
+            write_chunk_size: options.write_chunk_size,
+            heartbeat_grace: options
+                .heartbeat_grace_multiplier
+                .unwrap_or(DEFAULT_HEARTBEAT_GRACE),
+            heartbeat_jitter: options.heartbeat_jitter.unwrap_or(0.0),
+            backoff_secs: 1,
+            reconfigured: None,
+            subscription_errors: HashMap::new(),
+            abandoned_sub_ids: HashSet::new(),
+            receipts_stalled: false,
+            closing: false,
+            reconnect_attempt: 0,
+            inbound_seq: 0,
+            resubscribe_receipt: None,
+            initial_subs: None,
+        };
 
-        Ok(Connection {
+        let conn = Connection {
             outbound_tx: out_tx,
-            inbound_rx: Arc::new(Mutex::new(in_rx)),
-            shutdown_tx,
+            writer_lane: Arc::default(),
+            inbound_rx: Arc::new(Mutex::new(Inbound::new(in_rx))),
+            inbound_queued,
+            shutdown_tx: shutdown_tx.clone(),
             subscriptions,
             sub_id_counter,
             pending,
+            pending_released,
+            invalidated,
             pending_receipts,
+            expired_receipts,
+            unknown_frames,
+            decode_counters,
+            server_info,
+            trace_context: options.trace_context,
+            protocol,
+            destination_validation: options.destination_validation,
+            header_policy: options.header_policy,
+            sequencer,
+            inflight: inflight.clone(),
+            reconfigure_tx,
+            close_tx,
+            event_tx: options.event_tx,
+            outbox: options.outbox,
+            started,
+            destinations,
+            lifeline: Some(Arc::new(Lifeline {
+                shutdown_tx,
+                inflight,
+            })),
+            task_labels: task_labels.clone(),
+            message_counters,
+            circuit: circuit_switch,
+            tap_tx: tap_tx_shared,
+        };
+
+        // Connect and hand the session to the background task, which
+        // handles I/O and reconnection from then on
+        let start = async move {
+            let (framed, info) = session_task
+                .handshake
+                .open_first(&session_task.client_hb)
+                .await?;
+            let intervals = session_task.first_session(info).await;
+            task::spawn(
+                "connection",
+                &task_labels,
+                session_task.run(framed, intervals),
+            );
+            Ok(())
+        };
+
+        Ok(ConnectionBuilder {
+            conn,
+            start: Box::pin(start),
         })
     }
 
-    /// Build a CONNECT frame with all specified headers.
+    /// Build the handshake frame (`CONNECT` or `STOMP`) with all specified
+    /// headers.
+    #[allow(clippy::too_many_arguments)]
     fn build_connect_frame(
+        command: &str,
         accept_version: &str,
         host: &str,
-        login: &str,
-        passcode: &str,
+        login: Option<&str>,
+        passcode: Option<&str>,
         heartbeat: &str,
         client_id: &Option<String>,
         custom_headers: &[(String, String)],
     ) -> Frame {
-        let mut connect = Frame::new("CONNECT")
+        let mut connect = Frame::new(command)
             .header("accept-version", accept_version)
-            .header("host", host)
-            .header("login", login)
-            .header("passcode", passcode)
-            .header("heart-beat", heartbeat);
+            .header("host", host);
+        if let Some(login) = login {
+            connect = connect.header("login", login);
+        }
+        if let Some(passcode) = passcode {
+            connect = connect.header("passcode", passcode);
+        }
+        connect = connect.header("heart-beat", heartbeat);
 
         if let Some(id) = client_id {
             connect = connect.header("client-id", id);
         }
 
-        // Reserved headers that custom_headers cannot override
-        let reserved = [
-            "accept-version",
-            "host",
-            "login",
-            "passcode",
-            "heart-beat",
-            "client-id",
-        ];
-
+        // ConnectOptions::validate() rejects these up front; never let a
+        // custom header replace them regardless
         for (k, v) in custom_headers {
-            if !reserved.contains(&k.to_lowercase().as_str()) {
+            if !PROTECTED_CONNECT_HEADERS.contains(&k.to_lowercase().as_str()) {
                 connect = connect.header(k, v);
             }
         }
@@ -1294,35 +2769,81 @@ impl Connection {
 
     /// Wait for CONNECTED or ERROR response from the server.
     ///
-    /// Returns the server's heartbeat header value on success, or an error
-    /// if the server sends an ERROR frame or closes the connection.
+    /// Returns the `ServerInfo` parsed from the CONNECTED frame on success, or
+    /// an error if the server sends an ERROR frame or undecodable bytes,
+    /// negotiates a version not listed in `accept_version`, closes the
+    /// connection, or does not answer within `timeout`.
+    ///
+    /// Bodies of frames received meanwhile are cut at `body_limit` bytes, so
+    /// a misbehaving server cannot make the client buffer an unbounded ERROR
+    /// body. Once connected, the codec escapes headers unless the server
+    /// negotiated STOMP 1.0.
     async fn await_connected_response(
-        framed: &mut Framed<TcpStream, StompCodec>,
-    ) -> Result<String, ConnError> {
+        framed: &mut Framed<Transport, StompCodec>,
+        accept_version: &str,
+        body_limit: usize,
+        timeout: Duration,
+    ) -> Result<ServerInfo, ConnError> {
+        framed.codec_mut().set_body_capture_limit(Some(body_limit));
+        let result = tokio::time::timeout(
+            timeout,
+            Self::read_connected_response(framed, accept_version, body_limit),
+        )
+        .await
+        .unwrap_or(Err(ConnError::HandshakeTimeout(timeout)));
+        framed.codec_mut().set_body_capture_limit(None);
+        // STOMP 1.0 has no header escaping: backslashes are plain text
+        if let Ok(info) = &result {
+            framed
+                .codec_mut()
+                .set_header_escaping(info.version.trim() != "1.0");
+        }
+        result
+    }
+
+    async fn read_connected_response(
+        framed: &mut Framed<Transport, StompCodec>,
+        accept_version: &str,
+        body_limit: usize,
+    ) -> Result<ServerInfo, ConnError> {
         loop {
             match framed.next().await {
                 Some(Ok(StompItem::Frame(f))) => {
                     if f.command == "CONNECTED" {
-                        // Extract heartbeat from server
-                        let server_hb = f.get_header("heart-beat").unwrap_or("0,0").to_string();
-                        return Ok(server_hb);
+                        let info = ServerInfo::from_connected(&f);
+                        if !is_version_accepted(accept_version, &info.version) {
+                            return Err(ConnError::VersionMismatch {
+                                accepted: accept_version.to_string(),
+                                server: info.version,
+                            });
+                        }
+                        return Ok(info);
                     } else if f.command == "ERROR" {
                         // Server rejected connection (e.g., invalid credentials)
-                        return Err(ConnError::ServerRejected(ServerError::from_frame(f)));
+                        let mut err = ServerError::from_frame(f);
+                        if framed.codec_mut().take_truncated() {
+                            err.body = Some(truncated_body(&err.frame.body, body_limit));
+                        }
+                        return Err(ConnError::ServerRejected(err));
                     }
                     // Ignore other frames during CONNECT phase
                 }
-                Some(Ok(StompItem::Heartbeat)) => {
+                Some(Ok(_)) => {
                     // Ignore heartbeats during handshake
                     continue;
                 }
+                // The codec reports malformed input as InvalidData
+                Some(Err(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    return Err(ConnError::Decode { source: e });
+                }
                 Some(Err(e)) => {
                     return Err(ConnError::Io(e));
                 }
                 None => {
-                    return Err(ConnError::Protocol(
-                        "connection closed before CONNECTED received".to_string(),
-                    ));
+                    return Err(ConnError::Io(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "connection closed before CONNECTED received",
+                    )));
                 }
             }
         }
@@ -1345,125 +2866,537 @@ impl Connection {
     }
 
     pub async fn send_frame(&self, frame: Frame) -> Result<(), ConnError> {
-        // Send a frame to the background writer task.
-        //
-        // Parameters
-        // - `frame`: ownership of the `Frame` to send. The frame is converted
-        //   into a `StompItem::Frame` and sent over the internal mpsc channel.
-        self.outbound_tx
-            .send(StompItem::Frame(frame))
-            .await
-            .map_err(|_| ConnError::Protocol("send channel closed".into()))
+        self.send_frame_encoded(frame, None).await
     }
 
-    /// Generate a unique receipt ID.
-    fn generate_receipt_id() -> String {
-        static RECEIPT_COUNTER: AtomicU64 = AtomicU64::new(1);
-        format!("rcpt-{}", RECEIPT_COUNTER.fetch_add(1, Ordering::SeqCst))
+    /// Send a frame to the background writer task, encoded under
+    /// `content_length_policy` instead of the connection's policy when set.
+    async fn send_frame_encoded(
+        &self,
+        frame: Frame,
+        content_length_policy: Option<ContentLengthPolicy>,
+    ) -> Result<(), ConnError> {
+        let frame = self.prepare_outbound(frame).await?;
+        let permit = self.acquire_send_permit(&frame).await?;
+        let receipt = frame.get_header("receipt").map(str::to_string);
+        let item = match content_length_policy {
+            Some(policy) => StompItem::FrameWithContentLength(frame, policy),
+            None => StompItem::Frame(frame),
+        };
+        self.enqueue_items([item]).await?;
+        self.hold_send_permit(permit, receipt).await;
+        Ok(())
     }
 
-    /// Send a frame with a receipt request and return the receipt ID.
-    ///
-    /// This method adds a unique `receipt` header to the frame and registers
-    /// the receipt ID for tracking. Use `wait_for_receipt()` to wait for the
-    /// server's RECEIPT response.
+    /// A [`Producer`] for sending to `destination` with headers set once,
+    /// such as the content type, persistence and time to live.
     ///
-    /// # Parameters
-    /// - `frame`: the frame to send. A `receipt` header will be added.
+    /// # Example
+    /// ```ignore
+    /// let orders = conn.producer("/queue/orders").content_type("text/plain");
+    /// orders.send("order 1").await?;
+    /// ```
+    pub fn producer(&self, destination: &str) -> Producer {
+        Producer::new(self.clone(), self.destinations.intern(destination))
+    }
+
+    /// Send a frame like [`send_frame`](Self::send_frame), applying
+    /// `options` first.
     ///
-    /// # Returns
-    /// The generated receipt ID that can be used with `wait_for_receipt()`.
+    /// With [`SendOptions::auto_message_id`], a SEND frame without the id
+    /// header gets a generated id, and the id the frame carries is returned
+    /// in the [`SendResult`] so it can be logged or correlated with
+    /// downstream processing.
     ///
     /// # Example
     /// ```ignore
-    /// let receipt_id = conn.send_frame_with_receipt(frame).await?;
-    /// conn.wait_for_receipt(&receipt_id, Duration::from_secs(5)).await?;
+    /// use iridium_stomp::{IdStyle, SendOptions};
+    ///
+    /// let options = SendOptions::new().auto_message_id(IdStyle::Uuid);
+    /// let result = conn.send_with_options(frame, &options).await?;
+    /// tracing::info!(message_id = result.message_id(), "order published");
     /// ```
-    pub async fn send_frame_with_receipt(&self, frame: Frame) -> Result<String, ConnError> {
-        let receipt_id = Self::generate_receipt_id();
-
-        // Create the oneshot channel for notification
-        let (tx, _rx) = oneshot::channel();
+    pub async fn send_with_options(
+        &self,
+        frame: Frame,
+        options: &SendOptions,
+    ) -> Result<SendResult, ConnError> {
+        let (frame, message_id) = options.apply(frame);
+        self.send_frame_encoded(frame, options.content_length_policy)
+            .await?;
+        Ok(SendResult { message_id })
+    }
 
-        // Register the pending receipt
-        {
-            let mut receipts = self.pending_receipts.lock().await;
-            receipts.insert(receipt_id.clone(), tx);
+    /// Wait for an in-flight permit when `frame` is a SEND and
+    /// `ConnectOptions::max_inflight_sends()` is set.
+    async fn acquire_send_permit(
+        &self,
+        frame: &Frame,
+    ) -> Result<Option<OwnedSemaphorePermit>, ConnError> {
+        match &self.inflight {
+            Some(inflight) if frame.command == "SEND" => Ok(Some(inflight.acquire().await?)),
+            _ => Ok(None),
         }
+    }
 
-        // Add receipt header and send the frame
-        let frame_with_receipt = frame.receipt(&receipt_id);
-        self.send_frame(frame_with_receipt).await?;
+    /// Keep the permit of a queued SEND until it completes: a receipt the
+    /// connection tracks holds it until it settles, a SEND without a
+    /// receipt leaves it to the writer to release on flush, and a receipt
+    /// that is not tracked (or already settled) releases it now.
+    async fn hold_send_permit(
+        &self,
+        permit: Option<OwnedSemaphorePermit>,
+        receipt: Option<String>,
+    ) {
+        let Some(permit) = permit else { return };
+        match receipt {
+            None => permit.forget(),
+            Some(receipt_id) => {
+                if let Some(pending) = self.pending_receipts.lock().await.get_mut(&receipt_id) {
+                    pending.permit = Some(permit);
+                }
+            }
+        }
+    }
 
-        Ok(receipt_id)
+    /// Number of SEND frames in flight: awaiting their receipt, or queued
+    /// and not yet flushed when they requested none. Always 0 unless
+    /// `ConnectOptions::max_inflight_sends()` is set.
+    pub fn inflight_sends(&self) -> usize {
+        self.inflight.as_ref().map_or(0, InflightLimiter::in_flight)
     }
 
-    /// Wait for a receipt confirmation from the server.
+    /// Wait until no SEND is in flight, for example before `close()` so
+    /// that every message has been flushed and every requested receipt has
+    /// settled. Sends started meanwhile wait until the drain completes;
+    /// wrap the call in `tokio::time::timeout` to bound it.
     ///
-    /// This method blocks until the server sends a RECEIPT frame with the
-    /// matching receipt-id, or until the timeout expires.
+    /// Returns at once unless `ConnectOptions::max_inflight_sends()` is set,
+    /// and fails if the connection is closed while waiting.
     ///
-    /// # Parameters
-    /// - `receipt_id`: the receipt ID returned by `send_frame_with_receipt()`.
-    /// - `timeout`: maximum time to wait for the receipt.
+    /// # Example
     ///
-    /// # Returns
-    /// `Ok(())` if the receipt was received, or `Err(ConnError::ReceiptTimeout)`
-    /// if the timeout expired.
+    /// ```ignore
+    /// for order in orders {
+    ///     conn.send_frame_with_receipt(order).await?;
+    /// }
+    /// tokio::time::timeout(Duration::from_secs(10), conn.drain_sends()).await??;
+    /// conn.close().await;
+    /// ```
+    pub async fn drain_sends(&self) -> Result<(), ConnError> {
+        match &self.inflight {
+            Some(inflight) => inflight.drain().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Send a frame, retrying transient failures according to `policy`.
+    ///
+    /// The frame is validated and stamped once; every attempt sends the
+    /// same frame (with a fresh `receipt` header when the policy confirms
+    /// delivery). Each retry is reported as `ConnectionEvent::SendRetry`.
+    /// When the attempts or the deadline run out, the error from the last
+    /// attempt is returned. See [`RetryPolicy`] for what counts as
+    /// transient.
     ///
     /// # Example
+    ///
     /// ```ignore
-    /// let receipt_id = conn.send_frame_with_receipt(frame).await?;
-    /// conn.wait_for_receipt(&receipt_id, Duration::from_secs(5)).await?;
-    /// println!("Message confirmed!");
+    /// let policy = RetryPolicy::new()
+    ///     .deadline(Duration::from_secs(30))
+    ///     .confirm(Duration::from_secs(2));
+    /// conn.send_with_retry(frame, policy).await?;
     /// ```
-    pub async fn wait_for_receipt(
+    pub async fn send_with_retry(
         &self,
-        receipt_id: &str,
-        timeout: Duration,
+        frame: Frame,
+        policy: RetryPolicy,
     ) -> Result<(), ConnError> {
-        // Get the receiver for this receipt
-        let rx = {
-            let mut receipts = self.pending_receipts.lock().await;
-            // Re-create the oneshot channel and swap out the sender
-            let (tx, rx) = oneshot::channel();
-            if let Some(old_tx) = receipts.insert(receipt_id.to_string(), tx) {
-                // Drop the old sender - this is expected if called after send_frame_with_receipt
-                drop(old_tx);
+        if policy.receipt_timeout.is_some() {
+            self.check_started("a receipt")
+                .map_err(ConnError::Protocol)?;
+        }
+        let frame = self.prepare_outbound(frame).await?;
+        let destination = frame.get_header("destination").map(str::to_string);
+        let started = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let remaining = policy
+                .deadline
+                .map(|deadline| deadline.saturating_sub(started.elapsed()));
+            let error = match self.send_attempt(&frame, &policy, remaining).await {
+                SendAttempt::Sent => return Ok(()),
+                SendAttempt::Fail(error) => return Err(error),
+                SendAttempt::Retry(error) => error,
+            };
+
+            let delay = policy.delay_after(attempt);
+            let past_deadline = policy
+                .deadline
+                .is_some_and(|deadline| started.elapsed() + delay >= deadline);
+            if attempt >= policy.max_attempts || past_deadline {
+                return Err(error);
             }
-            rx
+            tracing::debug!(
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                error = %error,
+                "send failed, retrying",
+            );
+            events::emit(
+                &self.event_tx,
+                ConnectionEvent::SendRetry {
+                    destination: destination.clone(),
+                    attempt,
+                    delay,
+                    reason: error.to_string(),
+                },
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// One attempt of `send_with_retry()`.
+    async fn send_attempt(
+        &self,
+        frame: &Frame,
+        policy: &RetryPolicy,
+        remaining: Option<Duration>,
+    ) -> SendAttempt {
+        let permit = match self.acquire_send_permit(frame).await {
+            Ok(permit) => permit,
+            Err(e) => return SendAttempt::Fail(e),
+        };
+        let Some(receipt_timeout) = policy.receipt_timeout else {
+            let attempt = self.enqueue_attempt(frame.clone()).await;
+            if matches!(attempt, SendAttempt::Sent) {
+                let receipt = frame.get_header("receipt").map(str::to_string);
+                self.hold_send_permit(permit, receipt).await;
+            }
+            return attempt;
         };
 
-        // Wait for the receipt with timeout
-        match tokio::time::timeout(timeout, rx).await {
-            Ok(Ok(())) => Ok(()),
-            Ok(Err(_)) => {
-                // Channel was closed without receiving - connection likely dropped
-                Err(ConnError::Protocol(
-                    "receipt channel closed unexpectedly".into(),
-                ))
+        let receipt_id = Self::generate_receipt_id();
+        let (tx, rx) = oneshot::channel();
+        self.pending_receipts
+            .lock()
+            .await
+            .insert(receipt_id.clone(), PendingReceipt::new(tx));
+        match self
+            .enqueue_attempt(frame.clone().receipt(&receipt_id))
+            .await
+        {
+            SendAttempt::Sent => {
+                self.hold_send_permit(permit, Some(receipt_id.clone()))
+                    .await
+            }
+            failed => {
+                self.pending_receipts.lock().await.remove(&receipt_id);
+                return failed;
             }
+        }
+
+        let wait = remaining.map_or(receipt_timeout, |r| r.min(receipt_timeout));
+        match tokio::time::timeout(wait, rx).await {
+            Ok(Ok(Ok(()))) => SendAttempt::Sent,
+            Ok(Ok(Err(e))) => SendAttempt::Retry(e.into()),
+            Ok(Err(_)) => SendAttempt::Retry(ConnError::ChannelClosed),
             Err(_) => {
-                // Timeout expired - clean up the pending receipt
-                let mut receipts = self.pending_receipts.lock().await;
-                receipts.remove(receipt_id);
-                Err(ConnError::ReceiptTimeout(receipt_id.to_string()))
+                self.pending_receipts.lock().await.remove(&receipt_id);
+                SendAttempt::Retry(ConnError::ReceiptTimeout(receipt_id))
             }
         }
     }
 
-    /// Send a frame and wait for server confirmation via RECEIPT.
-    ///
-    /// This is a convenience method that combines `send_frame_with_receipt()`
-    /// and `wait_for_receipt()`. Use this when you want to ensure a frame
-    /// was processed by the server before continuing.
-    ///
-    /// # Parameters
-    /// - `frame`: the frame to send.
-    /// - `timeout`: maximum time to wait for the receipt.
-    ///
-    /// # Returns
-    /// `Ok(())` if the frame was sent and receipt confirmed, or an error if
+    /// Queue `frame` without waiting for room. A full queue (frames piling
+    /// up while reconnecting) is worth retrying; a closed one is not.
+    async fn enqueue_attempt(&self, frame: Frame) -> SendAttempt {
+        // Waits for a batch being queued, but not for room in the queue
+        let _lane = self.writer_lane.lock().await;
+        let effect = self.protocol_effect(&frame);
+        match self.outbound_tx.try_send(StompItem::Frame(frame)) {
+            Ok(()) => {
+                self.record_protocol(effect).await;
+                SendAttempt::Sent
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => SendAttempt::Retry(ConnError::Protocol(
+                "send queue full while reconnecting".into(),
+            )),
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                SendAttempt::Fail(ConnError::ChannelClosed)
+            }
+        }
+    }
+
+    /// Queue `frames` for the writer back to back: frames queued by other
+    /// callers never land between them.
+    async fn enqueue(&self, frames: impl IntoIterator<Item = Frame>) -> Result<(), ConnError> {
+        self.enqueue_items(frames.into_iter().map(StompItem::Frame))
+            .await
+    }
+
+    /// [`enqueue`](Self::enqueue) for items that may carry their own
+    /// `ContentLengthPolicy`.
+    async fn enqueue_items(
+        &self,
+        items: impl IntoIterator<Item = StompItem>,
+    ) -> Result<(), ConnError> {
+        let _lane = self.writer_lane.lock().await;
+        for item in items {
+            self.queue_item(item).await?;
+        }
+        Ok(())
+    }
+
+    /// Put `item` on the writer's queue. Before `ConnectionBuilder::start()`
+    /// nothing empties the queue, so a full one is an error rather than a
+    /// wait that would never end.
+    async fn queue_item(&self, item: StompItem) -> Result<(), ConnError> {
+        let effect = item.frame().and_then(|frame| self.protocol_effect(frame));
+        if self.started.load(Ordering::SeqCst) {
+            self.outbound_tx
+                .send(item)
+                .await
+                .map_err(|_| ConnError::ChannelClosed)?;
+        } else {
+            self.outbound_tx.try_send(item).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => ConnError::Protocol(format!(
+                    "at most {} frames can be sent before the connection is started",
+                    OUTBOUND_CAPACITY
+                )),
+                mpsc::error::TrySendError::Closed(_) => ConnError::ChannelClosed,
+            })?;
+        }
+        self.record_protocol(effect).await;
+        Ok(())
+    }
+
+    /// Refuse to wait for `receipt` before `ConnectionBuilder::start()`:
+    /// no receipt can arrive until then.
+    fn check_started(&self, receipt: &str) -> Result<(), String> {
+        if self.started.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        Err(format!(
+            "cannot wait for {} before the connection is started",
+            receipt
+        ))
+    }
+
+    /// Apply per-frame processing shared by all send paths: SEND frames get
+    /// trace context headers when a provider is set, are checked against
+    /// destination and protocol validation, and get sequence headers when
+    /// a publisher sequence is configured. Header names are checked against
+    /// the header policy, which the encoder would otherwise refuse, ending
+    /// the session.
+    async fn prepare_outbound(&self, frame: Frame) -> Result<Frame, ConnError> {
+        let frame = self.check_outbound(frame).map_err(ConnError::Protocol)?;
+        self.check_protocol(&frame).await?;
+        self.stamp(frame).await
+    }
+
+    /// The checks of [`prepare_outbound`](Self::prepare_outbound) that do
+    /// not depend on the frames queued before this one.
+    fn check_outbound(&self, frame: Frame) -> Result<Frame, String> {
+        let frame = self.inject_trace_context(frame);
+        self.header_policy.check(&frame)?;
+        if frame.command == "SEND"
+            && let Some(dest) = frame.get_header("destination")
+        {
+            self.check_destination(dest)?;
+        }
+        Ok(frame)
+    }
+
+    /// Add sequence headers when a publisher sequence is configured. Done
+    /// last so frames refused by the checks do not use up a number.
+    async fn stamp(&self, frame: Frame) -> Result<Frame, ConnError> {
+        match &self.sequencer {
+            Some(sequencer) => Ok(sequencer.stamp(frame).await?),
+            None => Ok(frame),
+        }
+    }
+
+    /// Add trace context headers to a SEND frame if a provider is configured
+    /// and the frame does not already carry a `traceparent`.
+    fn inject_trace_context(&self, frame: Frame) -> Frame {
+        let Some(provider) = &self.trace_context else {
+            return frame;
+        };
+        if frame.command != "SEND" || frame.get_header(TRACEPARENT_HEADER).is_some() {
+            return frame;
+        }
+        match provider() {
+            Some(ctx) => ctx.inject(frame),
+            None => frame,
+        }
+    }
+
+    /// Validate `frame` against the session state when strict protocol
+    /// checking is enabled. The frame is recorded once it is queued.
+    async fn check_protocol(&self, frame: &Frame) -> Result<(), ConnError> {
+        self.check_protocol_all([frame]).await
+    }
+
+    /// Validate `frames`, to be queued together in this order, against the
+    /// session state when strict protocol checking is enabled.
+    async fn check_protocol_all<'a>(
+        &self,
+        frames: impl IntoIterator<Item = &'a Frame>,
+    ) -> Result<(), ConnError> {
+        let Some(protocol) = &self.protocol else {
+            return Ok(());
+        };
+        protocol
+            .lock()
+            .await
+            .check_all(frames)
+            .map_err(|e| ConnError::Protocol(format!("strict protocol: {}", e)))
+    }
+
+    /// A copy of `frame` to record in the strict protocol state once it is
+    /// queued, if checking is enabled and the frame changes the state.
+    fn protocol_effect(&self, frame: &Frame) -> Option<Frame> {
+        self.protocol.as_ref()?;
+        ProtocolState::changes_state(frame).then(|| frame.clone())
+    }
+
+    /// Record a queued frame from [`protocol_effect`](Self::protocol_effect).
+    async fn record_protocol(&self, effect: Option<Frame>) {
+        if let (Some(protocol), Some(frame)) = (&self.protocol, effect) {
+            protocol.lock().await.record(&frame);
+        }
+    }
+
+    /// Validate `destination` when destination validation is enabled.
+    /// Warnings are logged; errors are returned as a description.
+    fn check_destination(&self, destination: &str) -> Result<(), String> {
+        let Some(broker) = self.destination_validation else {
+            return Ok(());
+        };
+        let report = crate::destination::validate(destination, broker);
+        for warning in &report.warnings {
+            tracing::warn!(destination, "suspicious destination: {}", warning);
+        }
+        match report.errors.first() {
+            Some(error) => Err(format!("invalid destination '{}': {}", destination, error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Generate a unique receipt ID.
+    fn generate_receipt_id() -> String {
+        static RECEIPT_COUNTER: AtomicU64 = AtomicU64::new(1);
+        format!("rcpt-{}", RECEIPT_COUNTER.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Send a frame with a receipt request and return the receipt ID.
+    ///
+    /// This method adds a unique `receipt` header to the frame and registers
+    /// the receipt ID for tracking. Use `wait_for_receipt()` to wait for the
+    /// server's RECEIPT response.
+    ///
+    /// # Parameters
+    /// - `frame`: the frame to send. A `receipt` header will be added.
+    ///
+    /// # Returns
+    /// The generated receipt ID that can be used with `wait_for_receipt()`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let receipt_id = conn.send_frame_with_receipt(frame).await?;
+    /// conn.wait_for_receipt(&receipt_id, Duration::from_secs(5)).await?;
+    /// ```
+    pub async fn send_frame_with_receipt(&self, frame: Frame) -> Result<String, ConnError> {
+        let receipt_id = Self::generate_receipt_id();
+
+        // Create the oneshot channel for notification
+        let (tx, _rx) = oneshot::channel();
+
+        // Register the pending receipt
+        {
+            let mut receipts = self.pending_receipts.lock().await;
+            receipts.insert(receipt_id.clone(), PendingReceipt::new(tx));
+        }
+
+        // Add receipt header and send the frame
+        let frame_with_receipt = frame.receipt(&receipt_id);
+        self.send_frame(frame_with_receipt).await?;
+
+        Ok(receipt_id)
+    }
+
+    /// Wait for a receipt confirmation from the server.
+    ///
+    /// This method blocks until the server sends a RECEIPT frame with the
+    /// matching receipt-id, or until the timeout expires.
+    ///
+    /// # Parameters
+    /// - `receipt_id`: the receipt ID returned by `send_frame_with_receipt()`.
+    /// - `timeout`: maximum time to wait for the receipt.
+    ///
+    /// # Returns
+    /// `Ok(())` if the receipt was received, or `Err(ConnError::ReceiptTimeout)`
+    /// if the timeout expired.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let receipt_id = conn.send_frame_with_receipt(frame).await?;
+    /// conn.wait_for_receipt(&receipt_id, Duration::from_secs(5)).await?;
+    /// println!("Message confirmed!");
+    /// ```
+    pub async fn wait_for_receipt(
+        &self,
+        receipt_id: &str,
+        timeout: Duration,
+    ) -> Result<(), ConnError> {
+        self.check_started("a receipt")
+            .map_err(ConnError::Protocol)?;
+        // Get the receiver for this receipt
+        let rx = {
+            let mut receipts = self.pending_receipts.lock().await;
+            // Re-create the oneshot channel and swap out the sender, keeping
+            // the original registration time if the receipt was already known
+            let (tx, rx) = oneshot::channel();
+            match receipts.get_mut(receipt_id) {
+                // Dropping the old sender is expected if called after send_frame_with_receipt
+                Some(pending) => pending.sender = tx,
+                None => {
+                    receipts.insert(receipt_id.to_string(), PendingReceipt::new(tx));
+                }
+            }
+            rx
+        };
+
+        // Wait for the receipt with timeout
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result.map_err(ConnError::from),
+            Ok(Err(_)) => {
+                // Channel was closed without receiving - connection likely dropped
+                Err(ConnError::ChannelClosed)
+            }
+            Err(_) => {
+                // Timeout expired - clean up the pending receipt
+                let mut receipts = self.pending_receipts.lock().await;
+                receipts.remove(receipt_id);
+                Err(ConnError::ReceiptTimeout(receipt_id.to_string()))
+            }
+        }
+    }
+
+    /// Send a frame and wait for server confirmation via RECEIPT.
+    ///
+    /// This is a convenience method that combines `send_frame_with_receipt()`
+    /// and `wait_for_receipt()`. Use this when you want to ensure a frame
+    /// was processed by the server before continuing.
+    ///
+    /// # Parameters
+    /// - `frame`: the frame to send.
+    /// - `timeout`: maximum time to wait for the receipt.
+    ///
+    /// # Returns
+    /// `Ok(())` if the frame was sent and receipt confirmed, or an error if
     /// sending failed or the receipt timed out.
     ///
     /// # Example
@@ -1480,6 +3413,8 @@ impl Connection {
         frame: Frame,
         timeout: Duration,
     ) -> Result<(), ConnError> {
+        self.check_started("a receipt")
+            .map_err(ConnError::Protocol)?;
         let receipt_id = Self::generate_receipt_id();
 
         // Create the oneshot channel for notification
@@ -1488,7 +3423,7 @@ impl Connection {
         // Register the pending receipt before sending
         {
             let mut receipts = self.pending_receipts.lock().await;
-            receipts.insert(receipt_id.clone(), tx);
+            receipts.insert(receipt_id.clone(), PendingReceipt::new(tx));
         }
 
         // Add receipt header and send the frame
@@ -1497,10 +3432,8 @@ impl Connection {
 
         // Wait for the receipt with timeout
         match tokio::time::timeout(timeout, rx).await {
-            Ok(Ok(())) => Ok(()),
-            Ok(Err(_)) => Err(ConnError::Protocol(
-                "receipt channel closed unexpectedly".into(),
-            )),
+            Ok(Ok(result)) => result.map_err(ConnError::from),
+            Ok(Err(_)) => Err(ConnError::ChannelClosed),
             Err(_) => {
                 // Timeout expired - clean up
                 let mut receipts = self.pending_receipts.lock().await;
@@ -1510,6 +3443,211 @@ impl Connection {
         }
     }
 
+    /// Send `frames` in order, with no frame from another sender in between.
+    ///
+    /// Equivalent to `execute_with_options(frames, ExecuteOptions::default())`.
+    pub async fn execute(&self, frames: Vec<Frame>) -> Result<(), ConnError> {
+        self.execute_with_options(frames, ExecuteOptions::default())
+            .await
+    }
+
+    /// Send `frames` in order as one batch, for setup sequences that must
+    /// not interleave with concurrent sends.
+    ///
+    /// Every frame gets the same processing as with
+    /// [`send_frame`](Self::send_frame) and all of them are checked before
+    /// the first is queued, so a frame refused by validation sends nothing.
+    /// Frames are sent as given: a SUBSCRIBE sent this way is not tracked
+    /// as a subscription.
+    ///
+    /// With `options.transaction`, the batch is wrapped in BEGIN and COMMIT
+    /// and its SEND, ACK and NACK frames get the `transaction` header. With
+    /// `options.receipt_timeout`, the last frame requests a receipt and the
+    /// call waits for it; as the broker handles frames in order, the
+    /// receipt confirms the whole batch.
+    ///
+    /// # Errors
+    ///
+    /// - `ConnError::Protocol` if a frame fails validation.
+    /// - `ConnError::ReceiptTimeout` if the receipt does not arrive in time.
+    /// - `ConnError::ChannelClosed` if the connection is closed. If this
+    ///   happens after part of a transaction was queued, an ABORT for it is
+    ///   sent as well.
+    ///
+    /// # Example
+    /// ```ignore
+    /// use iridium_stomp::ExecuteOptions;
+    ///
+    /// let frames = vec![
+    ///     Frame::new("SEND").header("destination", "/queue/setup").set_body(b"reset".to_vec()),
+    ///     Frame::new("SEND").header("destination", "/queue/setup").set_body(b"load".to_vec()),
+    /// ];
+    /// let options = ExecuteOptions {
+    ///     transaction: Some("setup-1".into()),
+    ///     receipt_timeout: Some(Duration::from_secs(5)),
+    /// };
+    /// conn.execute_with_options(frames, options).await?;
+    /// ```
+    pub async fn execute_with_options(
+        &self,
+        frames: Vec<Frame>,
+        options: ExecuteOptions,
+    ) -> Result<(), ConnError> {
+        if options.receipt_timeout.is_some() {
+            self.check_started("a receipt")
+                .map_err(ConnError::Protocol)?;
+        }
+        let mut batch = Vec::with_capacity(frames.len() + 2);
+        if let Some(tx) = &options.transaction {
+            batch.push(Frame::new("BEGIN").header("transaction", tx));
+        }
+        for frame in frames {
+            let frame = match &options.transaction {
+                Some(tx)
+                    if matches!(frame.command.as_str(), "SEND" | "ACK" | "NACK")
+                        && frame.get_header("transaction").is_none() =>
+                {
+                    frame.header("transaction", tx)
+                }
+                _ => frame,
+            };
+            batch.push(frame);
+        }
+        if let Some(tx) = &options.transaction {
+            batch.push(Frame::new("COMMIT").header("transaction", tx));
+        }
+        let receipt_id = match (options.receipt_timeout, batch.pop()) {
+            (_, None) => return Ok(()),
+            (Some(_), Some(last)) => {
+                let receipt_id = Self::generate_receipt_id();
+                batch.push(last.receipt(&receipt_id));
+                Some(receipt_id)
+            }
+            (None, Some(last)) => {
+                batch.push(last);
+                None
+            }
+        };
+
+        let mut checked = Vec::with_capacity(batch.len());
+        for frame in batch {
+            checked.push(self.check_outbound(frame).map_err(ConnError::Protocol)?);
+        }
+        self.check_protocol_all(&checked).await?;
+        let mut prepared = Vec::with_capacity(checked.len());
+        for frame in checked {
+            prepared.push(self.stamp(frame).await?);
+        }
+        let rx = match &receipt_id {
+            Some(receipt_id) => {
+                let (tx, rx) = oneshot::channel();
+                self.pending_receipts
+                    .lock()
+                    .await
+                    .insert(receipt_id.clone(), PendingReceipt::new(tx));
+                Some(rx)
+            }
+            None => None,
+        };
+        let mut queued = 0;
+        let result = {
+            // In-flight permits are taken one frame at a time, as the
+            // writer frees them for the earlier frames of the batch
+            let _lane = self.writer_lane.lock().await;
+            let mut result = Ok(());
+            for frame in prepared {
+                let permit = match self.acquire_send_permit(&frame).await {
+                    Ok(permit) => permit,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                };
+                let receipt = frame.get_header("receipt").map(str::to_string);
+                if let Err(e) = self.queue_item(StompItem::Frame(frame)).await {
+                    result = Err(e);
+                    break;
+                }
+                self.hold_send_permit(permit, receipt).await;
+                queued += 1;
+            }
+            result
+        };
+        if let Err(e) = result {
+            if let Some(receipt_id) = &receipt_id {
+                self.pending_receipts.lock().await.remove(receipt_id);
+            }
+            // Have the broker discard the part of the transaction it got
+            if queued > 0
+                && let Some(tx) = &options.transaction
+                && let Err(abort_err) = self
+                    .send_frame(Frame::new("ABORT").header("transaction", tx))
+                    .await
+            {
+                swallowed("abort partial batch", Some("ABORT"), None, &abort_err);
+            }
+            return Err(e);
+        }
+
+        let (Some(receipt_id), Some(rx), Some(timeout)) = (receipt_id, rx, options.receipt_timeout)
+        else {
+            return Ok(());
+        };
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result.map_err(ConnError::from),
+            Ok(Err(_)) => Err(ConnError::ChannelClosed),
+            Err(_) => {
+                self.pending_receipts.lock().await.remove(&receipt_id);
+                Err(ConnError::ReceiptTimeout(receipt_id))
+            }
+        }
+    }
+
+    /// Store a SEND frame in the outbox and return its entry id.
+    ///
+    /// The frame gets the same validation and headers as with
+    /// [`send_frame`](Self::send_frame), is flushed to the outbox file, and
+    /// is then sent in the background until the broker confirms it, across
+    /// reconnects and process restarts. Returning `Ok` means the frame is
+    /// on disk, not that it has been delivered; watch
+    /// [`Outbox::len`] to see the backlog shrink.
+    ///
+    /// # Errors
+    ///
+    /// - `ConnError::Protocol` if no outbox is configured with
+    ///   `ConnectOptions::outbox()`, if the frame is not a SEND, or if it
+    ///   fails validation.
+    /// - `ConnError::Io` if the outbox file cannot be written.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let frame = Frame::new("SEND")
+    ///     .header("destination", "/queue/orders")
+    ///     .set_body(b"order data".to_vec());
+    ///
+    /// let id = conn.send_durable(frame).await?;
+    /// ```
+    pub async fn send_durable(&self, frame: Frame) -> Result<u64, ConnError> {
+        let Some(outbox) = &self.outbox else {
+            return Err(ConnError::Protocol(
+                "no outbox configured; see ConnectOptions::outbox()".into(),
+            ));
+        };
+        if frame.command != "SEND" {
+            return Err(ConnError::Protocol(format!(
+                "only SEND frames can be stored in the outbox, not {}",
+                frame.command
+            )));
+        }
+        let frame = self.prepare_outbound(frame).await?;
+        // The push waits for the disk
+        let outbox = outbox.clone();
+        let id = tokio::task::spawn_blocking(move || outbox.push(frame))
+            .await
+            .map_err(std::io::Error::other)??;
+        Ok(id)
+    }
+
     /// Subscribe to a destination.
     ///
     /// Parameters
@@ -1531,22 +3669,39 @@ impl Connection {
         ack: AckMode,
         extra_headers: Vec<(String, String)>,
     ) -> Result<crate::subscription::Subscription, ConnError> {
-        let id = self
-            .sub_id_counter
-            .fetch_add(1, Ordering::SeqCst)
-            .to_string();
-        let (tx, rx) = mpsc::channel::<Frame>(16);
-        {
-            let mut map = self.subscriptions.lock().await;
-            map.entry(destination.to_string())
-                .or_insert_with(Vec::new)
-                .push(SubscriptionEntry {
-                    id: id.clone(),
-                    sender: tx.clone(),
-                    ack: ack.as_str().to_string(),
-                    headers: extra_headers.clone(),
-                });
-        }
+        self.subscribe_entry(destination, ack, extra_headers, false, None, false, None)
+            .await
+    }
+
+    /// Shared implementation of the `subscribe*` methods.
+    ///
+    /// An `implicit` subscription is only registered locally; no SUBSCRIBE
+    /// frame is sent for it. With `receipt`, the SUBSCRIBE asks for a
+    /// receipt and this waits up to that long for it, unsubscribing again
+    /// if it does not arrive. The receipt header is not kept for
+    /// resubscribes.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn subscribe_entry(
+        &self,
+        destination: &str,
+        ack: AckMode,
+        extra_headers: Vec<(String, String)>,
+        ordered: bool,
+        pending_limit: Option<PendingLimit>,
+        implicit: bool,
+        receipt: Option<Duration>,
+    ) -> Result<crate::subscription::Subscription, ConnError> {
+        self.check_destination(destination)
+            .map_err(ConnError::Protocol)?;
+        // Brokers deliver to implicit subscriptions with the destination as
+        // the `subscription` header, so that doubles as the local id.
+        let id = if implicit {
+            destination.to_string()
+        } else {
+            self.sub_id_counter
+                .fetch_add(1, Ordering::SeqCst)
+                .to_string()
+        };
 
         let mut f = Frame::new("SUBSCRIBE");
         f = f
@@ -1556,20 +3711,122 @@ impl Connection {
         for (k, v) in &extra_headers {
             f = f.header(k, v);
         }
-        self.outbound_tx
-            .send(StompItem::Frame(f))
-            .await
-            .map_err(|_| ConnError::Protocol("send channel closed".into()))?;
+        // Also sent again on every reconnect, so refuse it now
+        self.header_policy.check(&f).map_err(ConnError::Protocol)?;
+        if !implicit {
+            self.check_protocol(&f).await?;
+        }
 
-        Ok(crate::subscription::Subscription::new(
-            id,
-            destination.to_string(),
-            rx,
-            self.clone(),
-        ))
-    }
+        if receipt.is_some() && !implicit {
+            self.check_started("a SUBSCRIBE receipt")
+                .map_err(ConnError::Protocol)?;
+        }
 
-    /// Convenience wrapper without extra headers.
+        let (tx, rx) = mpsc::channel::<Frame>(16);
+        let (errors_tx, errors_rx) = broadcast::channel(SUBSCRIPTION_ERROR_CAPACITY);
+        let stages = Stages::default();
+        let name = self.destinations.intern(destination);
+        // Until the connection is started, the first session sends the
+        // SUBSCRIBE; checked under the lock the session snapshots under
+        let send_now = {
+            let mut map = self.subscriptions.lock().await;
+            map.entry(name.clone())
+                .or_insert_with(Vec::new)
+                .push(SubscriptionEntry {
+                    id: id.clone(),
+                    sender: tx.clone(),
+                    ack: ack.as_str().to_string(),
+                    headers: extra_headers,
+                    ordered,
+                    pending_limit,
+                    pending_evicted: 0,
+                    dropped: 0,
+                    slow: false,
+                    implicit,
+                    stages: stages.clone(),
+                    errors: errors_tx,
+                });
+            self.started.load(Ordering::SeqCst)
+        };
+
+        let receipt = match receipt {
+            Some(timeout) if !implicit => {
+                let receipt_id = Self::generate_receipt_id();
+                let (tx, rx) = oneshot::channel();
+                self.pending_receipts
+                    .lock()
+                    .await
+                    .insert(receipt_id.clone(), PendingReceipt::new(tx));
+                f = f.receipt(&receipt_id);
+                Some((receipt_id, rx, timeout))
+            }
+            _ => None,
+        };
+
+        if !implicit {
+            if send_now {
+                self.enqueue([f]).await?;
+            } else {
+                // The first session sends it; record it as queued now
+                let effect = self.protocol_effect(&f);
+                self.record_protocol(effect).await;
+            }
+        }
+
+        if let Some((receipt_id, rx, timeout)) = receipt {
+            let confirmed = match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(result)) => result.map_err(ConnError::from),
+                Ok(Err(_)) => Err(ConnError::ChannelClosed),
+                Err(_) => {
+                    self.pending_receipts.lock().await.remove(&receipt_id);
+                    Err(ConnError::ReceiptTimeout(receipt_id))
+                }
+            };
+            if let Err(e) = confirmed {
+                if let Err(unsub) = self.unsubscribe(&id).await {
+                    swallowed(
+                        "unsubscribe unconfirmed subscription",
+                        Some("UNSUBSCRIBE"),
+                        Some(name.as_str()),
+                        &unsub,
+                    );
+                }
+                return Err(e);
+            }
+        }
+
+        Ok(crate::subscription::Subscription::new(
+            id,
+            name,
+            rx,
+            errors_rx,
+            self.downgrade(),
+            stages,
+        ))
+    }
+
+    /// Start building a subscription to `destination`.
+    ///
+    /// The returned [`SubscriptionBuilder`](crate::SubscriptionBuilder)
+    /// names every subscription setting (ack mode, prefetch, durable name,
+    /// extra headers, ordered delivery, a SUBSCRIBE receipt) and subscribes
+    /// on `start()`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let sub = conn
+    ///     .subscription("/queue/orders")
+    ///     .ack(AckMode::ClientIndividual)
+    ///     .header("selector", "priority > 5")
+    ///     .receipt(true)
+    ///     .start()
+    ///     .await?;
+    /// ```
+    pub fn subscription(&self, destination: &str) -> crate::subscription::SubscriptionBuilder {
+        crate::subscription::SubscriptionBuilder::new(self.clone(), destination)
+    }
+
+    /// Convenience wrapper without extra headers.
     pub async fn subscribe(
         &self,
         destination: &str,
@@ -1595,16 +3852,237 @@ impl Connection {
             .as_deref()
             .unwrap_or(destination)
             .to_string();
-        self.subscribe_with_headers(&dest, ack, options.headers)
-            .await
+        let pending_limit = PendingLimit::new(options.max_pending, options.pending_overflow);
+        self.subscribe_entry(
+            &dest,
+            ack,
+            options.headers,
+            options.ordered,
+            pending_limit,
+            false,
+            None,
+        )
+        .await
+    }
+
+    /// Join consumer group `group` on the topic `destination`.
+    ///
+    /// Every process that calls `subscribe_shared` with the same group and
+    /// destination attaches to one broker-side queue, so the group's members
+    /// load-balance the topic instead of each receiving every message. The
+    /// mechanism depends on `broker`; see `BrokerProfile` for the
+    /// destination conventions each broker requires.
+    ///
+    /// The resulting subscription is resubscribed on reconnect like any
+    /// other.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnError::Protocol` if `broker` is `BrokerProfile::Generic`,
+    /// `group` is empty, or `destination` does not match the broker's
+    /// convention.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use iridium_stomp::{AckMode, BrokerProfile};
+    ///
+    /// let sub = conn
+    ///     .subscribe_shared("billing", "/topic/orders", AckMode::Client, BrokerProfile::RabbitMq)
+    ///     .await?;
+    /// ```
+    pub async fn subscribe_shared(
+        &self,
+        group: &str,
+        destination: &str,
+        ack: AckMode,
+        broker: BrokerProfile,
+    ) -> Result<crate::subscription::Subscription, ConnError> {
+        let options = broker
+            .shared_subscription(group, destination)
+            .map_err(ConnError::Protocol)?;
+        self.subscribe_with_options(destination, ack, options).await
+    }
+
+    /// Subscribe to a new temporary reply queue named after `prefix`.
+    ///
+    /// A unique queue name is built from `prefix`, and the queue is created
+    /// and consumed the way `broker` expects (see `BrokerProfile`). Put
+    /// `TempSubscription::reply_to()` into the `reply-to` header of outbound
+    /// requests; replies arrive on the returned subscription, which
+    /// dereferences to a regular `Subscription`. Dropping it unsubscribes.
+    ///
+    /// Messages are auto-acknowledged. The subscription is restored on
+    /// reconnect, but the broker may have discarded the queue and any
+    /// replies in flight along with the old connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnError::Protocol` if `broker` is `BrokerProfile::Generic`
+    /// or `prefix` is empty.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use iridium_stomp::{BrokerProfile, Frame};
+    ///
+    /// let mut replies = conn.subscribe_temp("rpc", BrokerProfile::RabbitMq).await?;
+    /// conn.send_frame(
+    ///     Frame::new("SEND")
+    ///         .header("destination", "/queue/rpc.requests")
+    ///         .header("reply-to", replies.reply_to())
+    ///         .set_body(b"ping".to_vec()),
+    /// )
+    /// .await?;
+    /// let reply = replies.recv_timeout(Duration::from_secs(5)).await?;
+    /// ```
+    pub async fn subscribe_temp(
+        &self,
+        prefix: &str,
+        broker: BrokerProfile,
+    ) -> Result<crate::subscription::TempSubscription, ConnError> {
+        if prefix.is_empty() {
+            return Err(ConnError::Protocol(
+                "temporary queue prefix must not be empty".into(),
+            ));
+        }
+        let queue = broker
+            .temp_queue(&Self::generate_temp_queue_name(prefix))
+            .map_err(ConnError::Protocol)?;
+        let subscription = self
+            .subscribe_entry(
+                &queue.destination,
+                AckMode::Auto,
+                queue.headers,
+                false,
+                None,
+                queue.implicit,
+                None,
+            )
+            .await?;
+        Ok(crate::subscription::TempSubscription::new(
+            queue.destination,
+            subscription,
+        ))
+    }
+
+    /// Browse the messages on the queue `destination` without consuming
+    /// them.
+    ///
+    /// Subscribes with the `browser:true` header understood by ActiveMQ,
+    /// which delivers a copy of every message currently on the queue and
+    /// then a MESSAGE with `browser:end` to mark the end. The returned
+    /// [`Browse`](crate::Browse) yields the messages and ends at that
+    /// marker, after `max_messages`, or after `idle_timeout` without a
+    /// message, whichever comes first; browsed messages are never
+    /// acknowledged. Brokers that ignore the header deliver, and consume,
+    /// messages as for a regular auto-ack subscription, so check that yours
+    /// supports browsing before pointing it at a production queue.
+    ///
+    /// If the connection drops during a browse it is restarted on
+    /// reconnect, so messages may be seen twice.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use iridium_stomp::{BrowseEnd, BrowseOptions};
+    ///
+    /// let options = BrowseOptions {
+    ///     idle_timeout: Some(Duration::from_secs(2)),
+    ///     ..Default::default()
+    /// };
+    /// let mut browse = conn.browse("/queue/orders", options).await?;
+    /// while let Some(frame) = browse.recv().await {
+    ///     println!("{}", frame.body_preview(80));
+    /// }
+    /// assert_eq!(browse.end(), Some(BrowseEnd::Exhausted));
+    /// ```
+    pub async fn browse(
+        &self,
+        destination: &str,
+        options: crate::browse::BrowseOptions,
+    ) -> Result<crate::browse::Browse, ConnError> {
+        let mut headers = vec![("browser".to_string(), "true".to_string())];
+        headers.extend(options.headers.iter().cloned());
+        let subscription = self
+            .subscribe_entry(
+                destination,
+                AckMode::Auto,
+                headers,
+                false,
+                None,
+                false,
+                None,
+            )
+            .await?;
+        Ok(crate::browse::Browse::new(subscription, &options))
+    }
+
+    /// Generate a temporary queue name that is unique across processes.
+    fn generate_temp_queue_name(prefix: &str) -> String {
+        static TEMP_QUEUE_COUNTER: AtomicU64 = AtomicU64::new(1);
+        format!(
+            "{}-{}-{}-{}",
+            prefix,
+            std::process::id(),
+            current_millis(),
+            TEMP_QUEUE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        )
+    }
+
+    /// Run `router` as a message-driven service on this connection.
+    ///
+    /// Subscribes to every destination named by the router's routes and
+    /// dispatches each message to the best-fitting handler, ACKing it when
+    /// the handler succeeds and NACKing it when no route fits. Messages of
+    /// handlers that return `Err` or panic are settled by the router's
+    /// `FailurePolicy` and reported as `ConnectionEvent::HandlerFailed`;
+    /// handlers that overrun the router's processing deadline are
+    /// cancelled and their messages NACKed. See `Router` for how routes
+    /// are matched.
+    ///
+    /// Returns once the connection is closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnError::Protocol` if the router names no destination, or
+    /// the error of a failed subscribe.
+    pub async fn serve(&self, router: crate::router::Router) -> Result<(), ConnError> {
+        let mut shutdown = self.shutdown_tx.subscribe();
+        let dispatch = router.start(self, self.event_tx.clone()).await?;
+        tokio::select! {
+            _ = dispatch => {}
+            _ = shutdown.recv() => {}
+        }
+        Ok(())
+    }
+
+    /// A receiver that fires once `close()` is called.
+    pub(crate) fn shutdown_signal(&self) -> broadcast::Receiver<()> {
+        self.shutdown_tx.subscribe()
     }
 
     /// Unsubscribe a previously created subscription by its local subscription id.
+    ///
+    /// Returns `ConnError::SubscriptionNotFound` if no subscription has
+    /// this id.
     pub async fn unsubscribe(&self, subscription_id: &str) -> Result<(), ConnError> {
+        let implicit = self
+            .subscriptions
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .any(|entry| entry.id == subscription_id && entry.implicit);
+        let f = Frame::new("UNSUBSCRIBE").header("id", subscription_id);
+        if !implicit {
+            self.check_protocol(&f).await?;
+        }
+
         let mut found = false;
-        {
+        let started = {
             let mut map = self.subscriptions.lock().await;
-            let mut remove_keys: Vec<String> = Vec::new();
+            let mut remove_keys: Vec<Destination> = Vec::new();
             for (dest, vec) in map.iter_mut() {
                 if let Some(pos) = vec.iter().position(|entry| entry.id == subscription_id) {
                     vec.remove(pos);
@@ -1617,19 +4095,75 @@ impl Connection {
             for k in remove_keys {
                 map.remove(&k);
             }
-        }
+            self.started.load(Ordering::SeqCst)
+        };
 
         if !found {
-            return Err(ConnError::Protocol("subscription id not found".into()));
+            return Err(ConnError::SubscriptionNotFound(subscription_id.to_string()));
+        }
+        self.invalidated.lock().await.remove(subscription_id);
+        // A reader waiting for this subscription's pending messages to be
+        // settled can carry on
+        self.pending_released.notify_one();
+        // Before the connection is started the SUBSCRIBE was never sent
+        if implicit || !started {
+            return Ok(());
         }
 
-        let mut f = Frame::new("UNSUBSCRIBE");
-        f = f.header("id", subscription_id);
-        self.outbound_tx
-            .send(StompItem::Frame(f))
-            .await
-            .map_err(|_| ConnError::Protocol("send channel closed".into()))?;
+        self.enqueue([f]).await?;
+
+        Ok(())
+    }
 
+    /// Replace the extra SUBSCRIBE headers of an existing subscription.
+    ///
+    /// Sends UNSUBSCRIBE followed by a SUBSCRIBE with the same id,
+    /// destination and ack mode but `headers` as the extra headers (e.g. a
+    /// new `selector` or `prefetch-count`). The new headers are also used
+    /// when resubscribing after a reconnect. Messages already delivered
+    /// locally stay in the subscription's channel; ACKs still pending for
+    /// the old SUBSCRIBE are dropped since the broker releases those
+    /// messages on UNSUBSCRIBE.
+    pub(crate) async fn update_subscription_headers(
+        &self,
+        subscription_id: &str,
+        headers: Vec<(String, String)>,
+    ) -> Result<(), ConnError> {
+        let unsubscribe = Frame::new("UNSUBSCRIBE").header("id", subscription_id);
+        let (mut subscribe, started) = {
+            let mut map = self.subscriptions.lock().await;
+            let Some((dest, entry)) = map.iter_mut().find_map(|(dest, vec)| {
+                vec.iter_mut()
+                    .find(|entry| entry.id == subscription_id)
+                    .map(|entry| (dest, entry))
+            }) else {
+                return Err(ConnError::SubscriptionNotFound(subscription_id.to_string()));
+            };
+            if entry.implicit {
+                return Err(ConnError::Protocol(
+                    "temporary reply subscriptions have no SUBSCRIBE to update".into(),
+                ));
+            }
+            entry.headers = headers.clone();
+            let subscribe = Frame::new("SUBSCRIBE")
+                .header("id", subscription_id)
+                .header("destination", dest.as_str())
+                .header("ack", &entry.ack);
+            (subscribe, self.started.load(Ordering::SeqCst))
+        };
+        // Before the connection is started the first session sends the
+        // new headers
+        if !started {
+            return Ok(());
+        }
+        for (k, v) in &headers {
+            subscribe = subscribe.header(k, v);
+        }
+        self.check_protocol_all([&unsubscribe, &subscribe]).await?;
+        self.pending.lock().await.remove(subscription_id);
+        self.pending_released.notify_one();
+
+        self.enqueue([unsubscribe, subscribe]).await?;
         Ok(())
     }
 
@@ -1660,12 +4194,18 @@ impl Connection {
     ///   `subscription=<subscription_id>` headers.
     #[allow(clippy::collapsible_if, clippy::collapsible_else_if)]
     pub async fn ack(&self, subscription_id: &str, message_id: &str) -> Result<(), ConnError> {
+        let f = Frame::new("ACK")
+            .header("id", message_id)
+            .header("subscription", subscription_id);
+        self.check_protocol(&f).await?;
+        self.check_invalidated(subscription_id, message_id).await?;
+
         // Remove from the local pending queue according to subscription ack mode.
         let mut removed_any = false;
         {
             let mut p = self.pending.lock().await;
             if let Some(queue) = p.get_mut(subscription_id) {
-                if let Some(pos) = queue.iter().position(|(mid, _)| mid == message_id) {
+                if let Some(pos) = queue.iter().position(|(mid, _, _)| mid == message_id) {
                     // Determine ack mode for this subscription (default to client).
                     let mut ack_mode = "client".to_string();
                     {
@@ -1697,23 +4237,162 @@ impl Connection {
                 }
             }
         }
+        if removed_any {
+            self.pending_released.notify_one();
+        }
 
         // Send ACK to server (include subscription header for clarity)
-        let mut f = Frame::new("ACK");
-        f = f
-            .header("id", message_id)
-            .header("subscription", subscription_id);
-        self.outbound_tx
-            .send(StompItem::Frame(f))
-            .await
-            .map_err(|_| ConnError::Protocol("send channel closed".into()))?;
+        self.enqueue([f]).await?;
 
         // If message wasn't found locally, still send ACK to server; server
         // may ignore or treat it as no-op.
-        let _ = removed_any;
         Ok(())
     }
 
+    /// Acknowledge a batch of messages with as few ACK frames as possible.
+    ///
+    /// For `client` subscriptions a single cumulative ACK is sent for the
+    /// message in `message_ids` that was delivered last, which also
+    /// acknowledges every earlier message on the subscription, including
+    /// any not listed in `message_ids`. For `client-individual` (and `auto`)
+    /// subscriptions each message is acknowledged with its own ACK frame.
+    ///
+    /// Does nothing if `message_ids` is empty. Messages invalidated by a
+    /// connection-level ERROR are left out and the rest acknowledged; the
+    /// result is then `ConnError::MessageInvalidated` for the first of them.
+    pub async fn ack_batch<S: AsRef<str>>(
+        &self,
+        subscription_id: &str,
+        message_ids: &[S],
+    ) -> Result<(), ConnError> {
+        let invalidated = self
+            .take_invalidated(subscription_id, message_ids.iter().map(AsRef::as_ref))
+            .await;
+        let valid: Vec<&str> = message_ids
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|id| !invalidated.iter().any(|i| i == id))
+            .collect();
+        self.ack_valid(subscription_id, &valid).await?;
+        match invalidated.into_iter().next() {
+            Some(id) => Err(ConnError::MessageInvalidated(id)),
+            None => Ok(()),
+        }
+    }
+
+    /// `ack_batch` for messages known not to be invalidated.
+    async fn ack_valid(
+        &self,
+        subscription_id: &str,
+        message_ids: &[&str],
+    ) -> Result<(), ConnError> {
+        let Some(fallback_last) = message_ids.last() else {
+            return Ok(());
+        };
+
+        if !self.is_cumulative(subscription_id).await {
+            for id in message_ids {
+                self.ack(subscription_id, id).await?;
+            }
+            return Ok(());
+        }
+
+        // Pick the most recently delivered message among the batch. If none
+        // are tracked locally, fall back to the last id given.
+        let last = {
+            let p = self.pending.lock().await;
+            p.get(subscription_id).and_then(|queue| {
+                queue
+                    .iter()
+                    .rev()
+                    .find(|(mid, _, _)| message_ids.contains(&mid.as_str()))
+                    .map(|(mid, _, _)| mid.clone())
+            })
+        };
+        let last = last.unwrap_or_else(|| fallback_last.to_string());
+        self.ack(subscription_id, &last).await
+    }
+
+    /// Acknowledge every message delivered on `subscription_id` that is
+    /// still waiting for an ACK or NACK, for example when draining before
+    /// shutdown.
+    ///
+    /// A `client` subscription gets a single cumulative ACK for the most
+    /// recently delivered message; a `client-individual` subscription gets
+    /// one ACK per message. Returns how many messages were acknowledged,
+    /// which is 0 (and nothing is sent) when none are pending. Messages
+    /// delivered while this runs are only covered by a cumulative ACK.
+    pub async fn ack_all(&self, subscription_id: &str) -> Result<usize, ConnError> {
+        let ids = self.pending_ids(subscription_id).await;
+        let Some(last) = ids.last() else {
+            return Ok(0);
+        };
+        if self.is_cumulative(subscription_id).await {
+            self.ack(subscription_id, last).await?;
+        } else {
+            for id in &ids {
+                self.ack(subscription_id, id).await?;
+            }
+        }
+        Ok(ids.len())
+    }
+
+    /// Message ids pending on `subscription_id`, oldest first.
+    async fn pending_ids(&self, subscription_id: &str) -> Vec<String> {
+        let p = self.pending.lock().await;
+        p.get(subscription_id)
+            .map(|queue| queue.iter().map(|(mid, _, _)| mid.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether `subscription_id` uses `client` (cumulative) ack mode.
+    async fn is_cumulative(&self, subscription_id: &str) -> bool {
+        let map = self.subscriptions.lock().await;
+        map.values()
+            .flatten()
+            .find(|entry| entry.id == subscription_id)
+            .is_some_and(|entry| entry.ack == "client")
+    }
+
+    /// Remove and return those of `message_ids` that were invalidated by a
+    /// connection-level ERROR, in the order given.
+    async fn take_invalidated<'a>(
+        &self,
+        subscription_id: &str,
+        message_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        let mut invalidated = self.invalidated.lock().await;
+        let Some(ids) = invalidated.get_mut(subscription_id) else {
+            return Vec::new();
+        };
+        let taken: Vec<String> = message_ids
+            .into_iter()
+            .filter(|id| ids.remove(*id))
+            .map(str::to_string)
+            .collect();
+        if ids.is_empty() {
+            invalidated.remove(subscription_id);
+        }
+        taken
+    }
+
+    /// Fail with `ConnError::MessageInvalidated` if `message_id` was
+    /// invalidated by a connection-level ERROR. Reported once per message.
+    async fn check_invalidated(
+        &self,
+        subscription_id: &str,
+        message_id: &str,
+    ) -> Result<(), ConnError> {
+        match self
+            .take_invalidated(subscription_id, [message_id])
+            .await
+            .pop()
+        {
+            Some(id) => Err(ConnError::MessageInvalidated(id)),
+            None => Ok(()),
+        }
+    }
+
     /// Negative-acknowledge a message (NACK).
     ///
     /// Parameters
@@ -1725,14 +4404,106 @@ impl Connection {
     ///   subscription used `client` ack mode, otherwise only the single
     ///   message). Sends a `NACK` frame to the server with `id` and
     ///   `subscription` headers.
-    #[allow(clippy::collapsible_if, clippy::collapsible_else_if)]
+    /// - Returns `ConnError::Protocol` without sending anything if the
+    ///   negotiated version is STOMP 1.0, which has no NACK frame.
     pub async fn nack(&self, subscription_id: &str, message_id: &str) -> Result<(), ConnError> {
+        self.send_nack(subscription_id, message_id, Vec::new())
+            .await
+    }
+
+    /// Negative-acknowledge a message with broker requeue controls.
+    ///
+    /// Behaves like [`nack`](Self::nack) but adds the headers described by
+    /// `options` to the NACK frame. With RabbitMQ, `requeue: false` routes
+    /// the message to the queue's dead-letter exchange (or discards it)
+    /// instead of redelivering it.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use iridium_stomp::NackOptions;
+    ///
+    /// // Poison message: do not redeliver
+    /// conn.nack_with_options(
+    ///     sub.id(),
+    ///     message_id,
+    ///     NackOptions { requeue: false, reason: Some("invalid payload".into()) },
+    /// ).await?;
+    /// ```
+    pub async fn nack_with_options(
+        &self,
+        subscription_id: &str,
+        message_id: &str,
+        options: NackOptions,
+    ) -> Result<(), ConnError> {
+        self.send_nack(subscription_id, message_id, options.to_headers())
+            .await
+    }
+
+    /// Negative-acknowledge every message delivered on `subscription_id`
+    /// that is still waiting for an ACK or NACK, for example when
+    /// processing has been abandoned after an error.
+    ///
+    /// Sends NACK frames the way [`ack_all`](Self::ack_all) sends ACKs:
+    /// one cumulative NACK for a `client` subscription, one per message
+    /// otherwise. `options` is added to every NACK frame. Returns how many
+    /// messages were NACKed; fails with `ConnError::Protocol` on STOMP 1.0.
+    pub async fn nack_all(
+        &self,
+        subscription_id: &str,
+        options: NackOptions,
+    ) -> Result<usize, ConnError> {
+        let ids = self.pending_ids(subscription_id).await;
+        let Some(last) = ids.last() else {
+            return Ok(0);
+        };
+        let headers = options.to_headers();
+        if self.is_cumulative(subscription_id).await {
+            self.send_nack(subscription_id, last, headers).await?;
+        } else {
+            for id in &ids {
+                self.send_nack(subscription_id, id, headers.clone()).await?;
+            }
+        }
+        Ok(ids.len())
+    }
+
+    /// Shared implementation of `nack` and `nack_with_options`.
+    #[allow(clippy::collapsible_if, clippy::collapsible_else_if)]
+    async fn send_nack(
+        &self,
+        subscription_id: &str,
+        message_id: &str,
+        extra_headers: Vec<(String, String)>,
+    ) -> Result<(), ConnError> {
+        // NACK does not exist in STOMP 1.0; refuse rather than send a frame
+        // the broker will reject.
+        {
+            let info = self.server_info.lock().await;
+            if !info.supports_nack() {
+                return Err(ConnError::Protocol(format!(
+                    "NACK is not supported by negotiated STOMP version {}",
+                    info.version
+                )));
+            }
+        }
+
+        let mut f = Frame::new("NACK");
+        f = f
+            .header("id", message_id)
+            .header("subscription", subscription_id);
+        for (k, v) in extra_headers {
+            f = f.header(k, v);
+        }
+        self.check_protocol(&f).await?;
+        self.check_invalidated(subscription_id, message_id).await?;
+
         // Mirror ack removal semantics for pending map.
         let mut removed_any = false;
         {
             let mut p = self.pending.lock().await;
             if let Some(queue) = p.get_mut(subscription_id) {
-                if let Some(pos) = queue.iter().position(|(mid, _)| mid == message_id) {
+                if let Some(pos) = queue.iter().position(|(mid, _, _)| mid == message_id) {
                     let mut ack_mode = "client".to_string();
                     {
                         let map = self.subscriptions.lock().await;
@@ -1761,17 +4532,12 @@ impl Connection {
                 }
             }
         }
+        if removed_any {
+            self.pending_released.notify_one();
+        }
 
-        let mut f = Frame::new("NACK");
-        f = f
-            .header("id", message_id)
-            .header("subscription", subscription_id);
-        self.outbound_tx
-            .send(StompItem::Frame(f))
-            .await
-            .map_err(|_| ConnError::Protocol("send channel closed".into()))?;
+        self.enqueue([f]).await?;
 
-        let _ = removed_any;
         Ok(())
     }
 
@@ -1782,10 +4548,8 @@ impl Connection {
         transaction_id: &str,
     ) -> Result<(), ConnError> {
         let f = Frame::new(command).header("transaction", transaction_id);
-        self.outbound_tx
-            .send(StompItem::Frame(f))
-            .await
-            .map_err(|_| ConnError::Protocol("send channel closed".into()))
+        self.check_protocol(&f).await?;
+        self.enqueue([f]).await
     }
 
     /// Begin a transaction.
@@ -1851,22 +4615,1727 @@ impl Connection {
     /// }
     /// ```
     pub async fn next_frame(&self) -> Option<ReceivedFrame> {
-        let mut rx = self.inbound_rx.lock().await;
-        let frame = rx.recv().await?;
+        let frame = {
+            let mut inbound = self.inbound_rx.lock().await;
+            match inbound.peeked.take() {
+                Some(frame) => frame,
+                None => inbound.rx.recv().await?,
+            }
+        };
+        let _ = self
+            .inbound_queued
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        Some(Self::received(frame))
+    }
+
+    /// Wait for the next frame and return a copy of it without consuming
+    /// it: the following `next_frame()` returns the same frame.
+    ///
+    /// Returns `None` if the connection has been closed.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Defer to another consumer when the next frame is not ours
+    /// if let Some(ReceivedFrame::Frame(frame)) = conn.peek_frame().await
+    ///     && frame.get_header("destination") == Some("/queue/orders")
+    /// {
+    ///     let frame = conn.next_frame().await;
+    /// }
+    /// ```
+    pub async fn peek_frame(&self) -> Option<ReceivedFrame> {
+        let mut inbound = self.inbound_rx.lock().await;
+        if inbound.peeked.is_none() {
+            inbound.peeked = Some(inbound.rx.recv().await?);
+        }
+        inbound.peeked.clone().map(Self::received)
+    }
+
+    /// Number of frames waiting to be returned by `next_frame()`.
+    ///
+    /// The queue holds at most `inbound_capacity()` frames. A queue that
+    /// stays near capacity means the application is falling behind: the
+    /// connection then waits before reading more from the broker, or with
+    /// `ConnectOptions::best_effort_mirror()` stops copying frames already
+    /// delivered to a `Subscription` here. Batch consumers can use the depth
+    /// to size their batches.
+    pub fn inbound_len(&self) -> usize {
+        self.inbound_queued.load(Ordering::Relaxed)
+    }
 
-        // Convert ERROR frames to ServerError for better ergonomics
+    /// Maximum number of frames queued for `next_frame()`.
+    pub fn inbound_capacity(&self) -> usize {
+        INBOUND_CAPACITY
+    }
+
+    /// Wrap an inbound frame, converting ERROR frames to `ServerError` for
+    /// better ergonomics.
+    fn received(frame: Frame) -> ReceivedFrame {
         if frame.command == "ERROR" {
-            Some(ReceivedFrame::Error(ServerError::from_frame(frame)))
+            ReceivedFrame::Error(ServerError::from_frame(frame))
         } else {
-            Some(ReceivedFrame::Frame(frame))
+            ReceivedFrame::Frame(frame)
+        }
+    }
+
+    /// Returns the number of receipts awaiting confirmation, the age of the
+    /// oldest one, and how many were discarded by TTL cleanup.
+    ///
+    /// A growing count or age means the broker is not answering receipts;
+    /// see `ConnectOptions::receipt_warning_after()` to be notified.
+    pub async fn outstanding_receipts(&self) -> OutstandingReceipts {
+        OutstandingReceipts::of(
+            &*self.pending_receipts.lock().await,
+            self.expired_receipts.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Returns the unacknowledged messages of every subscription that has
+    /// any, keyed by subscription id.
+    ///
+    /// A growing count or age means the application is falling behind on
+    /// its ACKs; the broker will not deliver past its prefetch limit until
+    /// they are sent. Pending messages are forgotten on reconnect, since
+    /// the broker redelivers them.
+    pub async fn pending_acks(&self) -> HashMap<String, PendingAcks> {
+        let mut acks: HashMap<String, PendingAcks> = self
+            .pending
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|(id, queue)| (id.clone(), PendingAcks::of(queue)))
+            .collect();
+        for entry in self.subscriptions.lock().await.values().flatten() {
+            if let Some(pending) = acks.get_mut(&entry.id) {
+                pending.evicted = entry.pending_evicted;
+            }
+        }
+        acks
+    }
+
+    /// Returns the unacknowledged messages of one subscription.
+    pub(crate) async fn pending_acks_for(&self, subscription_id: &str) -> PendingAcks {
+        let mut pending = self
+            .pending
+            .lock()
+            .await
+            .get(subscription_id)
+            .map(PendingAcks::of)
+            .unwrap_or_default();
+        pending.evicted = self
+            .subscriptions
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .find(|entry| entry.id == subscription_id)
+            .map_or(0, |entry| entry.pending_evicted);
+        pending
+    }
+
+    /// Number of frames received with a command the client does not handle
+    /// (see `ConnectionEvent::UnknownFrame`).
+    pub fn unknown_frame_count(&self) -> u64 {
+        self.unknown_frames.load(Ordering::Relaxed)
+    }
+
+    /// Frames, heartbeats, bytes, lenient decodes and decode errors seen by
+    /// the codec, over every session of this connection.
+    ///
+    /// A non-zero `permissive` count shows the broker relies on the
+    /// parser's lenient fallbacks; decode errors end the session and
+    /// trigger a reconnect.
+    pub fn decode_stats(&self) -> DecodeStats {
+        self.decode_counters.snapshot()
+    }
+
+    /// Body sizes and content types of the MESSAGE frames received, over
+    /// every session of this connection, to watch payload growth or plan
+    /// capacity.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let stats = conn.message_stats();
+    /// if let Some(Some(p95)) = stats.body_sizes.quantile(0.95) {
+    ///     println!("95% of bodies are at most {} bytes", p95);
+    /// }
+    /// for (content_type, count) in &stats.content_types {
+    ///     println!("{}: {}", content_type, count);
+    /// }
+    /// ```
+    pub fn message_stats(&self) -> MessageStats {
+        self.message_counters.snapshot()
+    }
+
+    /// Returns details from the broker's most recent CONNECTED frame,
+    /// including the negotiated STOMP version, and the socket endpoints of
+    /// the current connection.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let info = conn.server_info().await;
+    /// println!("connected to {:?} using STOMP {}", info.server, info.version);
+    /// if let Some(peer) = info.peer_addr {
+    ///     println!("answered by {}", peer);
+    /// }
+    /// ```
+    pub async fn server_info(&self) -> ServerInfo {
+        self.server_info.lock().await.clone()
+    }
+
+    /// Watch every MESSAGE this connection receives, without taking part
+    /// in acknowledging it.
+    ///
+    /// For auditing and metrics alongside the real consumers: each message
+    /// is copied to every tap after it has been dispatched to its
+    /// subscriptions, including messages no subscription took. A tap
+    /// never holds up delivery; one that falls behind skips the oldest
+    /// messages and counts them in `Tap::missed()`. Taps see messages
+    /// received from the moment they are created and end when the
+    /// connection closes.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use futures::StreamExt;
+    ///
+    /// let mut audit = conn.tap();
+    /// tokio::spawn(async move {
+    ///     while let Some(frame) = audit.next().await {
+    ///         log_message(&frame);
+    ///     }
+    /// });
+    /// ```
+    pub fn tap(&self) -> Tap {
+        match &*self.tap_tx.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(tx) => Tap::new(tx.subscribe()),
+            None => Tap::new(broadcast::channel(1).1),
+        }
+    }
+
+    /// Whether reconnects are paused by `ConnectOptions::circuit_breaker()`.
+    pub fn circuit_open(&self) -> bool {
+        self.circuit.is_open()
+    }
+
+    /// End the circuit breaker's cool-down and reconnect now, for example
+    /// once an operator has fixed the broker's configuration.
+    ///
+    /// Returns `false`, and does nothing, if the circuit is not open.
+    /// `ConnectionEvent::CircuitClosed` reports `manual: true`; the next
+    /// `failures` failed sessions open the circuit again.
+    pub fn retry_now(&self) -> bool {
+        self.circuit.retry_now()
+    }
+
+    /// Re-run the handshake with new heartbeat settings.
+    ///
+    /// Sends DISCONNECT, waits briefly for the broker's RECEIPT, then
+    /// reconnects straight away with `heartbeat` as the client `heart-beat`
+    /// header and renegotiates the intervals. Subscriptions are restored by
+    /// the usual resubscribe on reconnect and existing `Subscription` handles
+    /// keep working. The new settings also apply to every later reconnect.
+    ///
+    /// Returns once the new session is established. As with any reconnect,
+    /// un-ACKed messages are redelivered by the broker and messages that
+    /// arrive while the old session is closing are lost for `auto`
+    /// subscriptions. If the broker cannot be reached the call waits while
+    /// the connection retries; wrap it in `tokio::time::timeout` to bound it.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// conn.update_heartbeat(Heartbeat::new(2000, 2000)).await?;
+    /// ```
+    pub async fn update_heartbeat(&self, heartbeat: Heartbeat) -> Result<(), ConnError> {
+        let (done, connected) = oneshot::channel();
+        self.reconfigure_tx
+            .send(Reconfigure {
+                heartbeat: heartbeat.to_string(),
+                done,
+            })
+            .await
+            .map_err(|_| ConnError::ChannelClosed)?;
+        connected.await.map_err(|_| ConnError::ChannelClosed)
+    }
+
+    /// A handle that does not keep the connection alive.
+    ///
+    /// Dropping the last `Connection` handle shuts the connection down as
+    /// `close()` does; `WeakConnection::upgrade()` then returns `None`.
+    pub fn downgrade(&self) -> WeakConnection {
+        WeakConnection {
+            conn: Connection {
+                lifeline: None,
+                ..self.clone()
+            },
+            lifeline: self
+                .lifeline
+                .as_ref()
+                .map_or_else(Weak::new, Arc::downgrade),
+        }
+    }
+
+    /// Close the connection and stop its background task.
+    ///
+    /// Frames still queued, such as ACKs from `Subscription::ack()`, may
+    /// never be written, so the broker can redeliver their messages on the
+    /// next connection. Use [`close_after_flush`](Self::close_after_flush)
+    /// to write them first.
+    pub async fn close(self) {
+        if let Some(protocol) = &self.protocol {
+            protocol.lock().await.closed();
+        }
+        // Sends waiting for an in-flight permit would otherwise wait forever
+        if let Some(inflight) = &self.inflight {
+            inflight.close();
+        }
+        // Signal the background task to shutdown by broadcasting on the
+        // shutdown channel. Consumers may await task termination separately
+        // if needed.
+        let _ = self.shutdown_tx.send(());
+    }
+
+    /// Write every queued frame, DISCONNECT, then close the connection.
+    ///
+    /// From the moment the background task takes the request, sends, ACKs
+    /// and NACKs on every handle of this connection fail with
+    /// `ConnError::ChannelClosed`. Everything queued before that is written
+    /// to the broker, followed by a DISCONNECT with a receipt; the transport
+    /// is dropped once the broker confirms it. This way ACKs queued just
+    /// before shutting down are not lost and redelivered on the next
+    /// connection.
+    ///
+    /// Messages arriving while the queue drains are discarded, as on any
+    /// disconnect, and receipts requested earlier are still confirmed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnError::CloseTimeout` if the queue was not written and
+    /// the DISCONNECT confirmed within `timeout`, for example while the
+    /// connection is reconnecting. The connection is closed either way.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// sub.ack(&last_id).await?;
+    /// conn.close_after_flush(Duration::from_secs(5)).await?;
+    /// ```
+    pub async fn close_after_flush(self, timeout: Duration) -> Result<(), ConnError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let (done, confirmed) = oneshot::channel();
+        let request = CloseRequest { deadline, done };
+        let confirmed = tokio::time::timeout_at(deadline, async {
+            if self.close_tx.send(request).await.is_err() {
+                return false;
+            }
+            // A reader waiting for pending room stops to take the request
+            self.pending_released.notify_one();
+            confirmed.await.unwrap_or(false)
+        })
+        .await
+        .unwrap_or(false);
+        self.close().await;
+        if confirmed {
+            Ok(())
+        } else {
+            Err(ConnError::CloseTimeout(timeout))
+        }
+    }
+}
+
+/// How the connection task opens a session: connect, send the handshake
+/// frame and wait for CONNECTED.
+struct Handshake {
+    connector: Connector,
+    addr: String,
+    /// `CONNECT` or `STOMP`.
+    command: &'static str,
+    accept_version: String,
+    host: String,
+    login: Option<String>,
+    passcode: Option<String>,
+    client_id: Option<String>,
+    custom_headers: Vec<(String, String)>,
+    content_length_policy: ContentLengthPolicy,
+    escape_policy: EscapePolicy,
+    header_policy: HeaderPolicy,
+    decode_counters: Arc<DecodeCounters>,
+    /// Most body bytes kept of an ERROR frame answering the handshake.
+    error_body_limit: usize,
+    /// How long to wait for CONNECTED.
+    timeout: Duration,
+}
+
+/// The step at which a handshake failed.
+#[derive(Error, Debug)]
+enum HandshakeError {
+    /// The broker could not be reached.
+    #[error("{0}")]
+    Connect(std::io::Error),
+    /// The handshake frame could not be written.
+    #[error("{0}")]
+    Send(std::io::Error),
+    /// The broker did not answer with an acceptable CONNECTED frame.
+    #[error("{0}")]
+    Response(ConnError),
+}
+
+impl Handshake {
+    /// Open a session announcing `client_hb`, and return it with what the
+    /// broker reported about itself.
+    async fn open(
+        &self,
+        client_hb: &str,
+    ) -> Result<(Framed<Transport, StompCodec>, ServerInfo), HandshakeError> {
+        let stream = self
+            .connector
+            .connect(&self.addr)
+            .await
+            .map_err(HandshakeError::Connect)?;
+        let mut framed = Framed::new(
+            stream,
+            StompCodec::new()
+                .with_content_length_policy(self.content_length_policy)
+                .with_escape_policy(self.escape_policy)
+                .with_header_policy(self.header_policy)
+                .with_decode_counters(self.decode_counters.clone()),
+        );
+
+        let connect = Connection::build_connect_frame(
+            self.command,
+            &self.accept_version,
+            &self.host,
+            self.login.as_deref(),
+            self.passcode.as_deref(),
+            client_hb,
+            &self.client_id,
+            &self.custom_headers,
+        );
+        framed
+            .send(StompItem::Frame(connect))
+            .await
+            .map_err(HandshakeError::Send)?;
+
+        let info = Connection::await_connected_response(
+            &mut framed,
+            &self.accept_version,
+            self.error_body_limit,
+            self.timeout,
+        )
+        .await
+        .map_err(HandshakeError::Response)?;
+        let info = info.with_endpoints(framed.get_ref());
+        Ok((framed, info))
+    }
+
+    /// Open the first session, retrying with exponential backoff on I/O
+    /// and protocol errors (broker unreachable or crashing mid-handshake)
+    /// like reconnection does. A rejected TLS handshake, `ServerRejected`
+    /// (authentication failure) and `VersionMismatch` fail immediately.
+    async fn open_first(
+        &self,
+        client_hb: &str,
+    ) -> Result<(Framed<Transport, StompCodec>, ServerInfo), ConnError> {
+        let mut backoff_secs: u64 = 1;
+        loop {
+            let error = match self.open(client_hb).await {
+                Ok((framed, info)) => {
+                    tracing::info!(addr = %self.addr, version = %info.version, "connected to broker");
+                    return Ok((framed, info));
+                }
+                // A rejected TLS handshake (e.g. an untrusted certificate)
+                // will fail the same way on every attempt
+                Err(HandshakeError::Connect(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    return Err(ConnError::Io(e));
+                }
+                // Auth errors and version mismatches fail immediately — bad
+                // config should not be retried
+                Err(HandshakeError::Response(
+                    e @ (ConnError::ServerRejected(_) | ConnError::VersionMismatch { .. }),
+                )) => {
+                    return Err(e);
+                }
+                Err(e) => e,
+            };
+            let failed = match error {
+                HandshakeError::Connect(_) => "initial connect failed",
+                HandshakeError::Send(_) => "failed to send CONNECT frame",
+                // I/O and protocol errors during handshake (e.g., broker
+                // crashed or closed mid-handshake) — retry with backoff
+                HandshakeError::Response(_) => "handshake failed",
+            };
+            tracing::warn!(
+                addr = %self.addr,
+                error = %error,
+                backoff_secs,
+                "{}, retrying in {}s",
+                failed,
+                backoff_secs,
+            );
+            tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
+            backoff_secs = (backoff_secs * 2).min(30);
+        }
+    }
+}
+
+/// The heartbeat intervals negotiated from the client's `client_hb` and the
+/// server's `heart-beat` header: `(send, receive)`.
+fn session_heartbeats(client_hb: &str, info: &ServerInfo) -> (Option<Duration>, Option<Duration>) {
+    let (cx, cy) = parse_heartbeat_header(client_hb);
+    let (sx, sy) = parse_heartbeat_header(&info.heart_beat);
+    negotiate_heartbeats(cx, cy, sx, sy)
+}
+
+/// Sleep for `wait`, or forever if it is `None`.
+async fn sleep_for(wait: Option<Duration>) {
+    match wait {
+        Some(wait) => tokio::time::sleep(wait).await,
+        None => future::pending::<()>().await,
+    }
+}
+
+/// Consecutive errors for one destination after which its subscription is
+/// abandoned, so a broker repeating an error (e.g. Artemis sending
+/// permission errors) cannot cause an error loop.
+const SUBSCRIPTION_ERROR_THRESHOLD: u32 = 3;
+
+/// The connection's background task: runs one session after another,
+/// reconnecting with backoff, until the connection shuts down.
+///
+/// `Connection::builder()` sets it up and `ConnectionBuilder::start()`
+/// opens the first session and spawns `run()`.
+struct SessionTask {
+    handshake: Handshake,
+    /// Replaced by `update_heartbeat()`; read on every handshake.
+    client_hb: String,
+    out_rx: mpsc::Receiver<StompItem>,
+    in_tx: InboundTx,
+    reconfigure_rx: mpsc::Receiver<Reconfigure>,
+    close_rx: mpsc::Receiver<CloseRequest>,
+    shutdown_sub: broadcast::Receiver<()>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+    pending: Arc<Mutex<PendingMap>>,
+    pending_released: Arc<Notify>,
+    invalidated: Arc<Mutex<Invalidated>>,
+    pending_receipts: Arc<Mutex<PendingReceipts>>,
+    destinations: Arc<DestinationTable>,
+    server_info: Arc<Mutex<ServerInfo>>,
+    protocol: Option<Arc<Mutex<ProtocolState>>>,
+    inflight: Option<InflightLimiter>,
+    started: Arc<AtomicBool>,
+    expired_receipts: Arc<AtomicU64>,
+    unknown_frames: Arc<AtomicU64>,
+    message_counters: Arc<MessageCounters>,
+    tap_tx: broadcast::Sender<Frame>,
+    /// The connection's handle on `tap_tx`, cleared when the task ends.
+    tap_tx_shared: Arc<std::sync::Mutex<Option<broadcast::Sender<Frame>>>>,
+    circuit: Circuit,
+    sample_gate: Option<SampleGate>,
+    heartbeat_notify_tx: Option<mpsc::Sender<()>>,
+    recorder: Option<SessionRecorder>,
+    event_tx: Option<mpsc::Sender<ConnectionEvent>>,
+    receipt_warning_after: Option<Duration>,
+    receipt_ttl: Duration,
+    read_timeout: Option<Duration>,
+    best_effort_mirror: bool,
+    crlf_heartbeats: bool,
+    chaos: Option<Chaos>,
+    write_chunk_size: Option<usize>,
+    heartbeat_grace: f64,
+    heartbeat_jitter: f64,
+    /// Seconds to wait before the next reconnect attempt.
+    backoff_secs: u64,
+    /// Caller of `update_heartbeat()` waiting for the new session.
+    reconfigured: Option<oneshot::Sender<()>>,
+    /// Errors per destination across reconnections.
+    subscription_errors: HashMap<Destination, u32>,
+    /// Subscriptions abandoned for their errors, whose further errors are
+    /// ignored.
+    abandoned_sub_ids: HashSet<String>,
+    /// Whether a ReceiptsStalled warning is active, so it is emitted once
+    /// per stall rather than on every check.
+    receipts_stalled: bool,
+    /// Set when the session ended because the connection was closed.
+    closing: bool,
+    /// Reconnect attempts since the last session ended.
+    reconnect_attempt: u32,
+    /// Sequence number of the last frame received (`Frame::seq()`).
+    inbound_seq: u64,
+    /// Receipt requested on the last SUBSCRIBE sent again after a
+    /// reconnect, with what `ConnectionEvent::Reconnected` reports.
+    resubscribe_receipt: Option<(String, u32, Vec<String>)>,
+    /// Subscriptions made before the first session, sent with it.
+    initial_subs: Option<Vec<ResubEntry>>,
+}
+
+/// One connected session of a `SessionTask`.
+struct LiveSession {
+    sink: FrameSink,
+    stream: ChaosStream<FrameStream>,
+    /// Large frame being written a slice at a time, if any.
+    chunked: Option<ChunkedWrite>,
+    /// When something was last received, in `current_millis()`.
+    last_received: u64,
+    /// When something was last written, in `current_millis()`.
+    last_sent: u64,
+    /// Negotiated interval between outgoing heartbeats.
+    send_interval: Option<Duration>,
+    /// Idle time after which the next heartbeat is sent.
+    heartbeat_due: Option<Duration>,
+    /// Silence from the broker after which the session is dropped.
+    grace_period: Option<Duration>,
+    receipt_tick: tokio::time::Interval,
+    /// Subscriptions reported at their pending limit.
+    pending_limited: HashSet<String>,
+    started: tokio::time::Instant,
+}
+
+impl LiveSession {
+    /// The idle time after which to send the next heartbeat.
+    fn next_heartbeat(&self, jitter: f64) -> Option<Duration> {
+        self.send_interval
+            .map(|d| jittered_interval(d, jitter, jitter_random()))
+    }
+
+    /// Write `frame` at once, after finishing any chunked frame in
+    /// progress. Breaks if the transport failed.
+    async fn send_now(&mut self, frame: Frame) -> ControlFlow<()> {
+        if let Some(mut large) = self.chunked.take()
+            && large.finish(&mut self.sink).await.is_err()
+        {
+            return ControlFlow::Break(());
+        }
+        if self.sink.send(StompItem::Frame(frame)).await.is_err() {
+            return ControlFlow::Break(());
+        }
+        self.last_sent = current_millis();
+        ControlFlow::Continue(())
+    }
+
+    /// Close the transport, ending the session.
+    async fn close(&mut self) {
+        if let Err(e) = self.sink.close().await {
+            swallowed("close transport", None, None, &e);
+        }
+    }
+}
+
+impl SessionTask {
+    /// Take over the first session, opened by `ConnectionBuilder::start()`.
+    /// Returns the heartbeat intervals negotiated for it.
+    async fn first_session(&mut self, info: ServerInfo) -> (Option<Duration>, Option<Duration>) {
+        let intervals = session_heartbeats(&self.client_hb, &info);
+        *self.server_info.lock().await = info;
+        // Subscriptions made so far are sent with the first session; from
+        // now on `subscribe_entry()` sends them itself
+        let map = self.subscriptions.lock().await;
+        self.started.store(true, Ordering::SeqCst);
+        self.initial_subs = Some(resubscribe_entries(&map));
+        drop(map);
+        // Subscribed again before the task is spawned, so a shutdown
+        // signalled before it first runs, or while it is reconnecting or
+        // backing off, is not missed
+        self.shutdown_sub = self.shutdown_sub.resubscribe();
+        intervals
+    }
+
+    /// Run sessions until the connection shuts down, starting with the
+    /// already open `framed`.
+    async fn run(
+        mut self,
+        framed: Framed<Transport, StompCodec>,
+        intervals: (Option<Duration>, Option<Duration>),
+    ) {
+        // Use the already-established connection for the first iteration
+        let mut next = Some((framed, intervals));
+        loop {
+            // Check for shutdown before attempting connection
+            tokio::select! {
+                biased;
+                _ = self.shutdown_sub.recv() => break,
+                _ = future::ready(()) => {},
+            }
+
+            let (framed, intervals) = match next.take() {
+                Some(session) => session,
+                None => match self.reconnect().await {
+                    Some(session) => session,
+                    None => break,
+                },
+            };
+            let lasted = self.run_session(framed, intervals).await;
+
+            // Receipts requested on the lost session will never be
+            // confirmed; release their waiters now
+            let failed = fail_receipts(&mut *self.pending_receipts.lock().await);
+            if failed > 0 {
+                tracing::debug!(failed, "failed receipts outstanding when the session ended");
+            }
+
+            if self.closing || self.shutdown_sub.try_recv().is_ok() {
+                break;
+            }
+            if self.reconfigured.is_some() {
+                // Deliberate reconnect: no backoff
+                self.backoff_secs = 1;
+                continue;
+            }
+            let stable = lasted >= Duration::from_secs(self.backoff_secs.max(5));
+            if stable {
+                // Connection was stable — reset backoff
+                self.backoff_secs = 1;
+                tracing::info!(
+                    addr = %self.handshake.addr,
+                    stable_secs = lasted.as_secs(),
+                    "connection dropped after stable session, reconnecting in 1s",
+                );
+            } else {
+                // Connection died quickly — increase backoff
+                self.backoff_secs = (self.backoff_secs * 2).min(30);
+                tracing::warn!(
+                    addr = %self.handshake.addr,
+                    stable_secs = lasted.as_secs(),
+                    backoff_secs = self.backoff_secs,
+                    "connection dropped quickly, reconnecting in {}s",
+                    self.backoff_secs,
+                );
+            }
+            let backoff = Duration::from_secs(self.backoff_secs);
+            tokio::select! {
+                _ = self.shutdown_sub.recv() => break,
+                _ = self.circuit.pause(backoff, !stable, &self.event_tx) => {}
+            }
+        }
+        // No more messages can arrive; end every subscription stream
+        self.subscriptions.lock().await.clear();
+        // and every tap
+        *self.tap_tx_shared.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Open a new session after the last one ended, retrying with backoff.
+    /// Returns the session and its heartbeat intervals, or `None` if the
+    /// connection shut down meanwhile.
+    async fn reconnect(
+        &mut self,
+    ) -> Option<(
+        Framed<Transport, StompCodec>,
+        (Option<Duration>, Option<Duration>),
+    )> {
+        loop {
+            tokio::select! {
+                biased;
+                _ = self.shutdown_sub.recv() => return None,
+                _ = future::ready(()) => {},
+            }
+            self.reconnect_attempt += 1;
+            events::emit(
+                &self.event_tx,
+                ConnectionEvent::Reconnecting {
+                    attempt: self.reconnect_attempt,
+                },
+            );
+            let error = match self.handshake.open(&self.client_hb).await {
+                Ok((framed, info)) => {
+                    tracing::info!(
+                        addr = %self.handshake.addr,
+                        version = %info.version,
+                        "reconnected to broker",
+                    );
+                    let intervals = session_heartbeats(&self.client_hb, &info);
+                    *self.server_info.lock().await = info;
+                    if let Some(protocol) = &self.protocol {
+                        protocol.lock().await.reconnected();
+                    }
+                    return Some((framed, intervals));
+                }
+                Err(e) => e,
+            };
+            let backoff = Duration::from_secs(self.backoff_secs);
+            let failed = match error {
+                HandshakeError::Connect(_) => "broker unreachable",
+                HandshakeError::Send(_) => "failed to send CONNECT frame",
+                HandshakeError::Response(_) => "handshake failed",
+            };
+            tracing::warn!(
+                addr = %self.handshake.addr,
+                error = %error,
+                backoff_secs = self.backoff_secs,
+                "reconnect: {}, retrying in {}s",
+                failed,
+                self.backoff_secs,
+            );
+            // An unreachable broker does not count against the circuit
+            // breaker; a failed handshake does
+            if let HandshakeError::Connect(_) = error {
+                tokio::select! {
+                    _ = self.shutdown_sub.recv() => return None,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+            } else {
+                tokio::select! {
+                    _ = self.shutdown_sub.recv() => return None,
+                    _ = self.circuit.pause(backoff, true, &self.event_tx) => {}
+                }
+            }
+            self.backoff_secs = (self.backoff_secs * 2).min(30);
+        }
+    }
+
+    /// Run one session on `framed` until it ends, and return how long it
+    /// lasted.
+    async fn run_session(
+        &mut self,
+        framed: Framed<Transport, StompCodec>,
+        (send_interval, recv_interval): (Option<Duration>, Option<Duration>),
+    ) -> Duration {
+        let now = current_millis();
+        let (mut sink, stream) = crate::chunked::split(
+            framed,
+            StompCodec::new()
+                .with_content_length_policy(self.handshake.content_length_policy)
+                .with_header_policy(self.handshake.header_policy)
+                .with_crlf_heartbeats(self.crlf_heartbeats),
+        );
+
+        // Clear pending message map on reconnect — messages that were
+        // outstanding before the disconnect are considered lost and
+        // will be redelivered by the server as appropriate.
+        self.pending.lock().await.clear();
+        self.resubscribe(&mut sink).await;
+        if let Some(done) = self.reconfigured.take()
+            && done.send(()).is_err()
+        {
+            swallowed("confirm reconfigure", None, None, &"caller is gone");
+        }
+
+        let mut live = LiveSession {
+            sink,
+            stream: ChaosStream::new(stream, self.chaos.clone()),
+            chunked: None,
+            last_received: now,
+            last_sent: now,
+            send_interval,
+            heartbeat_due: None,
+            grace_period: recv_interval.map(|d| heartbeat_grace_period(d, self.heartbeat_grace)),
+            receipt_tick: tokio::time::interval(RECEIPT_CHECK_INTERVAL),
+            pending_limited: HashSet::new(),
+            started: tokio::time::Instant::now(),
+        };
+        live.heartbeat_due = live.next_heartbeat(self.heartbeat_jitter);
+        while self.step(&mut live).await.is_continue() {}
+        live.started.elapsed()
+    }
+
+    /// Send SUBSCRIBE for every subscription at the start of a session: the
+    /// ones made before the first session, or all of them after a
+    /// reconnect.
+    async fn resubscribe(&mut self, sink: &mut FrameSink) {
+        // We snapshot the subscription entries while holding the lock
+        // and then issue SUBSCRIBE frames using the sink.
+        let subs_snapshot = match self.initial_subs.take() {
+            Some(entries) => entries,
+            None => resubscribe_entries(&*self.subscriptions.lock().await),
+        };
+
+        // After a reconnect the last SUBSCRIBE asks for a receipt,
+        // which confirms all of them as the broker handles frames
+        // in order
+        let mut resubscribed: Vec<String> = Vec::new();
+        let mut receipt_id = None;
+        let last = subs_snapshot.len();
+        for (n, (dest, id, ack, headers)) in subs_snapshot.into_iter().enumerate() {
+            let mut sf = Frame::new("SUBSCRIBE");
+            sf = sf
+                .header("id", &id)
+                .header("destination", dest.as_str())
+                .header("ack", &ack);
+            for (k, v) in headers {
+                sf = sf.header(&k, &v);
+            }
+            if self.reconnect_attempt > 0 && n + 1 == last {
+                let id = Connection::generate_receipt_id();
+                sf = sf.receipt(&id);
+                receipt_id = Some(id);
+            }
+            if let Err(e) = sink.send(StompItem::Frame(sf)).await {
+                swallowed("resubscribe", Some("SUBSCRIBE"), Some(&dest), &e);
+            }
+            if !resubscribed.iter().any(|name| *name == *dest) {
+                resubscribed.push(dest.to_string());
+            }
+        }
+        if self.reconnect_attempt > 0 {
+            if let Some(recorder) = &self.recorder {
+                recorder.record_reconnect();
+            }
+            match receipt_id {
+                Some(id) => {
+                    self.resubscribe_receipt = Some((id, self.reconnect_attempt, resubscribed))
+                }
+                None => events::emit(
+                    &self.event_tx,
+                    ConnectionEvent::Reconnected {
+                        attempts: self.reconnect_attempt,
+                        resubscribed,
+                    },
+                ),
+            }
+        }
+        self.reconnect_attempt = 0;
+    }
+
+    /// Wait for the next thing to happen in a session and handle it.
+    /// Breaks when the session is over.
+    async fn step(&mut self, live: &mut LiveSession) -> ControlFlow<()> {
+        // Both heartbeat timers sleep until their deadline given the last
+        // activity and are re-armed on every step. A heartbeat cannot go
+        // inside a frame; the slices of a chunked frame count as activity
+        // instead.
+        let idle_out = Duration::from_millis(current_millis().saturating_sub(live.last_sent));
+        let heartbeat_wait = live
+            .heartbeat_due
+            .filter(|_| live.chunked.is_none())
+            .map(|due| due.saturating_sub(idle_out));
+        let idle_in = Duration::from_millis(current_millis().saturating_sub(live.last_received));
+        let watchdog_wait = live.grace_period.map(|grace| grace.saturating_sub(idle_in));
+        // The read timeout sleeps likewise, given the last inbound activity
+        let read_wait = self
+            .read_timeout
+            .map(|timeout| timeout.saturating_sub(idle_in));
+        tokio::select! {
+            _ = self.shutdown_sub.recv() => {
+                live.close().await;
+                self.closing = true;
+                ControlFlow::Break(())
+            }
+            Some(request) = self.reconfigure_rx.recv() => {
+                self.reconfigure(live, request).await;
+                ControlFlow::Break(())
+            }
+            Some(request) = self.close_rx.recv() => {
+                self.close(live, request).await;
+                ControlFlow::Break(())
+            }
+            // Nothing else may be written until a chunked frame is complete
+            maybe = self.out_rx.recv(), if live.chunked.is_none() => match maybe {
+                Some(item) => self.write_batch(live, item).await,
+                None => ControlFlow::Break(()),
+            },
+            result = write_chunk(&mut live.chunked, &mut live.sink) => match result {
+                Ok(done) => {
+                    if done {
+                        live.chunked = None;
+                    }
+                    live.last_sent = current_millis();
+                    ControlFlow::Continue(())
+                }
+                Err(_) => ControlFlow::Break(()),
+            },
+            item = live.stream.next() => match item {
+                Some(Ok(StompItem::Heartbeat)) => {
+                    self.on_heartbeat(live);
+                    ControlFlow::Continue(())
+                }
+                Some(Ok(StompItem::Frame(f))) => self.on_frame(live, f).await,
+                // Only ever encoded, never decoded
+                Some(Ok(StompItem::FrameWithContentLength(..))) => ControlFlow::Continue(()),
+                Some(Err(_)) | None => ControlFlow::Break(()),
+            },
+            _ = sleep_for(heartbeat_wait) => {
+                if let Some(due) = live.heartbeat_due
+                    && current_millis().saturating_sub(live.last_sent) >= due.as_millis() as u64
+                {
+                    if live.sink.send(StompItem::Heartbeat).await.is_err() {
+                        return ControlFlow::Break(());
+                    }
+                    live.last_sent = current_millis();
+                    live.heartbeat_due = live.next_heartbeat(self.heartbeat_jitter);
+                }
+                ControlFlow::Continue(())
+            }
+            _ = live.receipt_tick.tick() => {
+                self.check_receipts().await;
+                ControlFlow::Continue(())
+            }
+            _ = sleep_for(watchdog_wait) => {
+                if let Some(grace) = live.grace_period
+                    && current_millis().saturating_sub(live.last_received) >= grace.as_millis() as u64
+                {
+                    live.close().await;
+                    return ControlFlow::Break(());
+                }
+                ControlFlow::Continue(())
+            }
+            _ = sleep_for(read_wait) => {
+                let idle = Duration::from_millis(current_millis().saturating_sub(live.last_received));
+                if let Some(timeout) = self.read_timeout
+                    && idle >= timeout
+                {
+                    tracing::warn!(
+                        addr = %self.handshake.addr,
+                        idle_ms = idle.as_millis() as u64,
+                        "read timeout: nothing received from broker, reconnecting",
+                    );
+                    events::emit(&self.event_tx, ConnectionEvent::ReadTimeout { idle });
+                    live.close().await;
+                    return ControlFlow::Break(());
+                }
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    /// Clean DISCONNECT for `update_heartbeat()`: write what is already
+    /// queued, then give the broker a moment to confirm it has processed
+    /// everything. Frames arriving meanwhile are discarded, as on any
+    /// disconnect.
+    async fn reconfigure(&mut self, live: &mut LiveSession, request: Reconfigure) {
+        if let Some(mut large) = live.chunked.take()
+            && let Err(e) = large.finish(&mut live.sink).await
+        {
+            swallowed("finish large frame", Some("SEND"), None, &e);
+        }
+        let mut flushed = 0;
+        while let Ok(item) = self.out_rx.try_recv() {
+            flushed += usize::from(releases_on_flush(&item));
+            record_sent(&self.recorder, &item);
+            if live.sink.feed(item).await.is_err() {
+                break;
+            }
+        }
+        if let Some(inflight) = &self.inflight {
+            inflight.release_flushed(flushed);
+        }
+        let receipt_id = Connection::generate_receipt_id();
+        let disconnect = Frame::new("DISCONNECT").receipt(&receipt_id);
+        if live.sink.send(StompItem::Frame(disconnect)).await.is_ok() {
+            let confirmed = tokio::time::timeout(RECONFIGURE_DISCONNECT_TIMEOUT, async {
+                while let Some(Ok(item)) = live.stream.next().await {
+                    if let StompItem::Frame(f) = item
+                        && f.command == "RECEIPT"
+                        && f.get_header("receipt-id") == Some(receipt_id.as_str())
+                    {
+                        break;
+                    }
+                }
+            })
+            .await;
+            if let Err(e) = confirmed {
+                swallowed("confirm DISCONNECT", Some("DISCONNECT"), None, &e);
+            }
+        }
+        live.close().await;
+        tracing::info!(
+            addr = %self.handshake.addr,
+            heartbeat = %request.heartbeat,
+            "reconnecting with new heartbeat settings",
+        );
+        self.client_hb = request.heartbeat;
+        self.reconfigured = Some(request.done);
+    }
+
+    /// Close for `close_after_flush()`: refuse new frames, write everything
+    /// queued before them, then DISCONNECT and wait for the broker to
+    /// confirm it. Receipts confirmed meanwhile still reach their callers;
+    /// other frames are discarded.
+    async fn close(&mut self, live: &mut LiveSession, request: CloseRequest) {
+        self.out_rx.close();
+        let confirmed = tokio::time::timeout_at(request.deadline, async {
+            if let Some(mut large) = live.chunked.take()
+                && large.finish(&mut live.sink).await.is_err()
+            {
+                return false;
+            }
+            let mut flushed = 0;
+            let mut written = true;
+            while let Some(item) = self.out_rx.recv().await {
+                flushed += usize::from(releases_on_flush(&item));
+                record_sent(&self.recorder, &item);
+                if live.sink.feed(item).await.is_err() {
+                    written = false;
+                    break;
+                }
+            }
+            if let Some(inflight) = &self.inflight {
+                inflight.release_flushed(flushed);
+            }
+            let receipt_id = Connection::generate_receipt_id();
+            let disconnect = Frame::new("DISCONNECT").receipt(&receipt_id);
+            if !written || live.sink.send(StompItem::Frame(disconnect)).await.is_err() {
+                return false;
+            }
+            while let Some(Ok(item)) = live.stream.next().await {
+                let StompItem::Frame(f) = item else { continue };
+                if f.command != "RECEIPT" {
+                    continue;
+                }
+                match f.get_header("receipt-id") {
+                    Some(id) if id == receipt_id => return true,
+                    Some(id) => {
+                        let mut receipts = self.pending_receipts.lock().await;
+                        if let Some(pending) = receipts.remove(id)
+                            && pending.sender.send(Ok(())).is_err()
+                        {
+                            swallowed("confirm receipt", Some("RECEIPT"), None, &"waiter is gone");
+                        }
+                    }
+                    None => {}
+                }
+            }
+            false
+        })
+        .await
+        .unwrap_or(false);
+        live.close().await;
+        if request.done.send(confirmed).is_err() {
+            swallowed("confirm close", None, None, &"caller is gone");
+        }
+        self.closing = true;
+    }
+
+    /// Write `item` and everything already queued behind it, flushing
+    /// once, so bursts of small frames (ack storms, fast publishers) share
+    /// a syscall. A frame above the chunk size ends the batch and is then
+    /// written in slices.
+    async fn write_batch(&mut self, live: &mut LiveSession, item: StompItem) -> ControlFlow<()> {
+        let write_chunk_size = self.write_chunk_size;
+        let is_large =
+            |item: &StompItem| write_chunk_size.is_some_and(|size| outbound_len(item) > size);
+        let mut large = None;
+        let mut batch_bytes = 0;
+        let mut flushed = 0;
+        let mut written = true;
+        let mut next = Some(item);
+        while let Some(item) = next.take() {
+            record_sent(&self.recorder, &item);
+            if is_large(&item) {
+                large = Some(item);
+                break;
+            }
+            batch_bytes += outbound_len(&item);
+            flushed += usize::from(releases_on_flush(&item));
+            written = live.sink.feed(item).await.is_ok();
+            if written && batch_bytes < MAX_WRITE_BATCH_BYTES {
+                next = self.out_rx.try_recv().ok();
+            }
+        }
+        written = written && live.sink.flush().await.is_ok();
+        // Frames that failed to write are lost with the session, so their
+        // permits are released too
+        if let Some(inflight) = &self.inflight {
+            inflight.release_flushed(flushed);
+        }
+        if let (Some(item), Some(size)) = (large, write_chunk_size) {
+            match ChunkedWrite::new(&mut live.sink, item, size, self.inflight.as_ref()) {
+                Ok(large) => live.chunked = Some(large),
+                Err(_) => written = false,
+            }
+        }
+        if !written {
+            return ControlFlow::Break(());
+        }
+        live.last_sent = current_millis();
+        ControlFlow::Continue(())
+    }
+
+    fn on_heartbeat(&mut self, live: &mut LiveSession) {
+        live.last_received = current_millis();
+        if let Some(recorder) = &self.recorder {
+            recorder.record_heartbeat();
+        }
+        if let Some(ref tx) = self.heartbeat_notify_tx
+            && let Err(e) = tx.try_send(())
+        {
+            swallowed("notify heartbeat", None, None, &e);
+        }
+    }
+
+    /// Handle a frame from the broker and pass it on to `next_frame()`
+    /// unless it was for the connection itself.
+    async fn on_frame(&mut self, live: &mut LiveSession, mut f: Frame) -> ControlFlow<()> {
+        live.last_received = current_millis();
+        self.inbound_seq += 1;
+        f.seq = Some(self.inbound_seq);
+        match f.command.as_str() {
+            // Dispatch MESSAGE frames to any matching subscribers.
+            "MESSAGE" => return self.on_message(live, f).await,
+            "RECEIPT" => {
+                self.on_receipt(&f).await;
+                // Don't forward RECEIPT frames to inbound channel
+                return ControlFlow::Continue(());
+            }
+            "ERROR" => {
+                if !self.on_error(&f).await {
+                    return ControlFlow::Continue(());
+                }
+            }
+            "CONNECTED" => {}
+            _ => {
+                self.unknown_frames.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(command = %f.command, "received frame with unhandled command");
+                events::emit(
+                    &self.event_tx,
+                    ConnectionEvent::UnknownFrame {
+                        command: f.command.clone(),
+                    },
+                );
+            }
+        }
+        self.in_tx.send(f).await;
+        ControlFlow::Continue(())
+    }
+
+    /// Track a MESSAGE for acknowledgement and deliver it to its
+    /// subscriptions, the tap and `next_frame()`.
+    async fn on_message(&mut self, live: &mut LiveSession, mut f: Frame) -> ControlFlow<()> {
+        self.message_counters.record(&f);
+        if let Some(recorder) = &self.recorder {
+            recorder.record_message(&f);
+        }
+        if let Some(gate) = &mut self.sample_gate {
+            gate.offer(&f);
+        }
+        // try to find destination, subscription and message-id headers
+        let mut dest_opt: Option<Destination> = None;
+        let mut sub_opt: Option<String> = None;
+        let mut msg_id_opt: Option<String> = None;
+        for (k, v) in &f.headers {
+            let kl = k.to_lowercase();
+            if kl == "destination" {
+                dest_opt = Some(self.destinations.intern(v));
+            } else if kl == "subscription" {
+                sub_opt = Some(v.clone());
+            } else if kl == "message-id" {
+                msg_id_opt = Some(v.clone());
+            }
+        }
+        // Messages share the name with the subscriptions
+        f.destination = dest_opt.clone();
+
+        // Subscriptions that track this message until it is acknowledged,
+        // with their destination and pending limit
+        let mut tracked: Vec<(String, Destination, Option<PendingLimit>)> = Vec::new();
+        if let Some(sub_id) = &sub_opt {
+            let map = self.subscriptions.lock().await;
+            for (dest, vec) in map.iter() {
+                for entry in vec.iter() {
+                    if &entry.id == sub_id && entry.ack != "auto" {
+                        tracked.push((entry.id.clone(), dest.clone(), entry.pending_limit));
+                    }
+                }
+            }
+        } else if let Some(dest) = &dest_opt {
+            // Destination-based delivery: track the message for each
+            // subscription on that destination.
+            let map = self.subscriptions.lock().await;
+            if let Some((name, vec)) = map.get_key_value(dest.as_str())
+                && vec.iter().any(|entry| entry.ack != "auto")
+            {
+                tracked.extend(
+                    vec.iter()
+                        .map(|entry| (entry.id.clone(), name.clone(), entry.pending_limit)),
+                );
+            }
+        }
+
+        // A redelivery may reuse the message-id of a message invalidated
+        // by an ERROR
+        if let (Some(sub_id), Some(msg_id)) = (&sub_opt, &msg_id_opt) {
+            let mut invalidated = self.invalidated.lock().await;
+            if let Some(ids) = invalidated.get_mut(sub_id)
+                && ids.remove(msg_id)
+                && ids.is_empty()
+            {
+                invalidated.remove(sub_id);
+            }
+        }
+
+        // Add to the pending map (per-subscription) before delivery so
+        // ACK/NACK requests from the application can reference the
+        // message. We require a `message-id` header to track messages; if
+        // missing, we cannot support ACK/NACK.
+        if let Some(msg_id) = &msg_id_opt {
+            for (sub_id, dest, limit) in tracked {
+                if let Some(limit) = limit {
+                    self.make_pending_room(live, &sub_id, &dest, limit).await?;
+                }
+                self.pending
+                    .lock()
+                    .await
+                    .entry(sub_id)
+                    .or_default()
+                    .push_back((msg_id.clone(), dest, Instant::now()));
+            }
+        }
+
+        // Deliver to subscribers. Entries whose receiver has been dropped
+        // are collected and cleaned up below; a full channel only drops
+        // this frame, unless the subscription is ordered.
+        let mut closed: Vec<ClosedSubscription> = Vec::new();
+        let mut blocked: Vec<BlockedDelivery> = Vec::new();
+        let mut filtered: Vec<String> = Vec::new();
+        let mut slow: Vec<ConnectionEvent> = Vec::new();
+        let mut delivered = false;
+        {
+            let mut map = self.subscriptions.lock().await;
+            if let Some(sub_id) = sub_opt {
+                for (dest, vec) in map.iter_mut() {
+                    vec.retain_mut(|entry| {
+                        if entry.id != sub_id {
+                            return true;
+                        }
+                        delivered = true;
+                        dispatch_to_subscriber(
+                            entry,
+                            &f,
+                            dest,
+                            &mut closed,
+                            &mut blocked,
+                            &mut filtered,
+                            &mut slow,
+                        )
+                    });
+                }
+            } else if let Some(dest) = dest_opt
+                && let Some(vec) = map.get_mut(dest.as_str())
+            {
+                delivered = !vec.is_empty();
+                vec.retain_mut(|entry| {
+                    dispatch_to_subscriber(
+                        entry,
+                        &f,
+                        &dest,
+                        &mut closed,
+                        &mut blocked,
+                        &mut filtered,
+                        &mut slow,
+                    )
+                });
+            }
+            if !closed.is_empty() {
+                map.retain(|_, vec| !vec.is_empty());
+            }
+        }
+        for event in slow {
+            events::emit(&self.event_tx, event);
+        }
+
+        // ACK messages a `client-individual` filter rejected, which would
+        // otherwise never be acknowledged
+        if let Some(msg_id) = &msg_id_opt {
+            for sub_id in filtered {
+                if let Some(queue) = self.pending.lock().await.get_mut(&sub_id) {
+                    queue.retain(|(id, _, _)| id != msg_id);
+                }
+                let ack = Frame::new("ACK")
+                    .header("id", msg_id)
+                    .header("subscription", &sub_id);
+                live.send_now(ack).await?;
+            }
+        }
+
+        // Ordered subscriptions with a full channel: wait for room,
+        // holding up the reader (and with it further deliveries) until the
+        // consumer catches up.
+        for (sub_id, dest, sender, frame) in blocked {
+            if sender.send(frame).await.is_err() {
+                let mut map = self.subscriptions.lock().await;
+                let mut implicit = false;
+                if let Some(vec) = map.get_mut(dest.as_str()) {
+                    vec.retain(|entry| {
+                        implicit |= entry.id == sub_id && entry.implicit;
+                        entry.id != sub_id
+                    });
+                }
+                map.retain(|_, vec| !vec.is_empty());
+                closed.push((sub_id, dest, implicit));
+            }
+        }
+
+        for (sub_id, dest, implicit) in closed {
+            tracing::info!(
+                subscription_id = %sub_id,
+                destination = %dest,
+                "subscriber dropped, unsubscribing",
+            );
+            self.pending.lock().await.remove(&sub_id);
+            if let Some(protocol) = &self.protocol {
+                protocol.lock().await.forget_subscription(&sub_id);
+            }
+            if !implicit {
+                live.send_now(Frame::new("UNSUBSCRIBE").header("id", &sub_id))
+                    .await?;
+            }
+            events::emit(
+                &self.event_tx,
+                ConnectionEvent::SubscriptionDropped {
+                    subscription_id: sub_id,
+                    destination: dest,
+                },
+            );
+        }
+
+        if self.tap_tx.receiver_count() > 0
+            && let Err(e) = self.tap_tx.send(f.clone())
+        {
+            swallowed_frame("mirror to tap", &f, &e);
+        }
+
+        // With `best_effort_mirror()`, a subscription's messages reach
+        // `next_frame()` only if there is room
+        if delivered && self.best_effort_mirror {
+            self.in_tx.try_send(f);
+        } else {
+            self.in_tx.send(f).await;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Make room for one more pending message of subscription `sub_id`
+    /// under `limit`, as its overflow policy says: wait for the application
+    /// to settle some, or evict (and possibly NACK) the oldest. Breaks if
+    /// the session ends meanwhile.
+    async fn make_pending_room(
+        &mut self,
+        live: &mut LiveSession,
+        sub_id: &str,
+        dest: &Destination,
+        limit: PendingLimit,
+    ) -> ControlFlow<()> {
+        let queued = self
+            .pending
+            .lock()
+            .await
+            .get(sub_id)
+            .map_or(0, VecDeque::len);
+        if queued < limit.max {
+            if queued <= limit.max / 2 {
+                live.pending_limited.remove(sub_id);
+            }
+            return ControlFlow::Continue(());
+        }
+        if live.pending_limited.insert(sub_id.to_string()) {
+            let evicted = self
+                .subscriptions
+                .lock()
+                .await
+                .values()
+                .flatten()
+                .find(|entry| entry.id == sub_id)
+                .map_or(0, |entry| entry.pending_evicted);
+            tracing::warn!(
+                subscription_id = %sub_id,
+                destination = %dest,
+                max = limit.max,
+                policy = limit.overflow.as_str(),
+                "pending limit reached: messages are not being acknowledged",
+            );
+            events::emit(
+                &self.event_tx,
+                ConnectionEvent::PendingLimitReached {
+                    subscription_id: sub_id.to_string(),
+                    destination: dest.to_string(),
+                    max: limit.max,
+                    policy: limit.overflow,
+                    evicted,
+                },
+            );
+        }
+        if limit.overflow == PendingOverflow::StopDelivering {
+            let room = wait_for_pending_room(
+                &self.pending,
+                &self.pending_released,
+                &self.subscriptions,
+                sub_id,
+                limit.max,
+                &mut self.shutdown_sub,
+                &self.close_rx,
+            );
+            if !room.await {
+                live.close().await;
+                self.closing = true;
+                return ControlFlow::Break(());
+            }
+            return ControlFlow::Continue(());
+        }
+        let evicted = {
+            let mut p = self.pending.lock().await;
+            evict_oldest_pending(p.entry(sub_id.to_string()).or_default(), limit.max)
+        };
+        let nack = limit.overflow == PendingOverflow::NackOldest
+            && self.server_info.lock().await.supports_nack();
+        for id in &evicted {
+            tracing::warn!(
+                subscription_id = %sub_id,
+                destination = %dest,
+                message_id = %id,
+                "pending limit reached, {} oldest unacknowledged message",
+                if nack { "NACKing" } else { "evicting" },
+            );
+            if nack {
+                let frame = Frame::new("NACK")
+                    .header("id", id)
+                    .header("subscription", sub_id);
+                live.send_now(frame).await?;
+            }
+        }
+        let mut map = self.subscriptions.lock().await;
+        if let Some(entry) = map
+            .get_mut(dest)
+            .and_then(|vec| vec.iter_mut().find(|entry| entry.id == sub_id))
+        {
+            entry.pending_evicted += evicted.len() as u64;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Notify whoever waits for the receipt a RECEIPT frame confirms.
+    async fn on_receipt(&mut self, f: &Frame) {
+        let Some(receipt_id) = f.get_header("receipt-id") else {
+            return;
+        };
+        if self
+            .resubscribe_receipt
+            .as_ref()
+            .is_some_and(|(id, _, _)| id == receipt_id)
+            && let Some((_, attempts, resubscribed)) = self.resubscribe_receipt.take()
+        {
+            tracing::info!(
+                addr = %self.handshake.addr,
+                attempts,
+                destinations = resubscribed.len(),
+                "broker confirmed subscriptions after reconnect",
+            );
+            events::emit(
+                &self.event_tx,
+                ConnectionEvent::Reconnected {
+                    attempts,
+                    resubscribed,
+                },
+            );
+            return;
+        }
+        let mut receipts = self.pending_receipts.lock().await;
+        if let Some(pending) = receipts.remove(receipt_id) {
+            if let Some(recorder) = &self.recorder {
+                recorder.record_receipt(pending.sent_at.elapsed());
+            }
+            if pending.sender.send(Ok(())).is_err() {
+                swallowed("confirm receipt", Some("RECEIPT"), None, &"waiter is gone");
+            }
+        }
+    }
+
+    /// Account for an ERROR frame. Returns whether to pass it on to
+    /// `next_frame()`.
+    async fn on_error(&mut self, f: &Frame) -> bool {
+        if let Some(recorder) = &self.recorder {
+            recorder.record_error(f);
+        }
+        // Track subscription-related errors. If we see repeated errors for
+        // the same destination, remove the subscription to prevent error
+        // loops.
+        //
+        // First, check if this error is for an already-abandoned
+        // subscription (Artemis keeps sending errors after we abandon).
+        let sub_id = extract_subscription_id_from_error(f);
+        if let Some(ref id) = sub_id
+            && self.abandoned_sub_ids.contains(id)
+        {
+            // Skip this error - subscription already abandoned
+            return false;
+        }
+
+        // Try to identify the destination:
+        // 1. Extract directly from ERROR frame
+        // 2. Look up by subscription ID (Artemis uses "subscription N")
+        let dest = if let Some(d) = extract_destination_from_error(f) {
+            Some(self.destinations.intern(&d))
+        } else if let Some(ref id) = sub_id {
+            lookup_destination_by_sub_id(id, &self.subscriptions).await
+        } else {
+            None
+        };
+
+        let Some(dest) = dest else {
+            invalidate_pending(f, &self.pending, &self.invalidated, &self.subscriptions).await;
+            return true;
+        };
+        let count = {
+            let c = self.subscription_errors.entry(dest.clone()).or_insert(0);
+            *c += 1;
+            *c
+        };
+        if count >= SUBSCRIPTION_ERROR_THRESHOLD {
+            // Remove the subscription from auto-resubscribe
+            let mut map = self.subscriptions.lock().await;
+            if map.remove(dest.as_str()).is_some() {
+                // Track the subscription ID as abandoned
+                if let Some(id) = sub_id {
+                    self.abandoned_sub_ids.insert(id);
+                }
+                // Send abandonment notification
+                let msg = format!("Subscription abandoned: {} errors for {}", count, dest);
+                let abandon_frame = Frame::new("ERROR")
+                    .header("message", &msg)
+                    .header("destination", dest.as_str())
+                    .header("x-abandoned", "true");
+                self.in_tx.send(abandon_frame).await;
+            }
+        }
+        true
+    }
+
+    /// Discard receipts nobody waits for any more, and warn once when the
+    /// oldest outstanding one gets too old.
+    async fn check_receipts(&mut self) {
+        let outstanding = {
+            let mut receipts = self.pending_receipts.lock().await;
+            let removed = expire_receipts(&mut receipts, self.receipt_ttl);
+            if removed > 0 {
+                tracing::debug!(
+                    removed,
+                    "discarded unwaited receipts older than {:?}",
+                    self.receipt_ttl
+                );
+                self.expired_receipts
+                    .fetch_add(removed as u64, Ordering::Relaxed);
+            }
+            OutstandingReceipts::of(&receipts, self.expired_receipts.load(Ordering::Relaxed))
+        };
+        if let Some(threshold) = self.receipt_warning_after {
+            let stalled = outstanding.oldest_age.is_some_and(|age| age > threshold);
+            if stalled && !self.receipts_stalled {
+                let oldest_age = outstanding.oldest_age.unwrap_or_default();
+                tracing::warn!(
+                    count = outstanding.count,
+                    oldest_ms = oldest_age.as_millis() as u64,
+                    "receipts unconfirmed for longer than {:?}",
+                    threshold,
+                );
+                events::emit(
+                    &self.event_tx,
+                    ConnectionEvent::ReceiptsStalled {
+                        count: outstanding.count,
+                        oldest_age,
+                    },
+                );
+            }
+            self.receipts_stalled = stalled;
+        }
+    }
+}
+
+/// A subscription whose receiver was dropped during dispatch:
+/// `(subscription id, destination, implicit)`.
+type ClosedSubscription = (String, String, bool);
+
+/// An ordered subscription whose channel was full during dispatch:
+/// `(subscription id, destination, sender, frame to deliver)`.
+type BlockedDelivery = (String, String, mpsc::Sender<Frame>, Frame);
+
+/// Deliver `frame` to one subscriber from the dispatch loop and return
+/// whether the entry should be kept.
+///
+/// The subscription's steps run first; the ids of `client-individual`
+/// subscriptions whose filter rejected the frame are recorded in
+/// `filtered` so the caller can ACK it. Dropped receivers are recorded in
+/// `closed`. Ordered subscriptions whose channel is full are recorded in
+/// `blocked` so the caller can wait for room once the subscription map is
+/// unlocked. Subscriptions that have just fallen behind are reported in
+/// `slow`.
+fn dispatch_to_subscriber(
+    entry: &mut SubscriptionEntry,
+    frame: &Frame,
+    dest: &str,
+    closed: &mut Vec<ClosedSubscription>,
+    blocked: &mut Vec<BlockedDelivery>,
+    filtered: &mut Vec<String>,
+    slow: &mut Vec<ConnectionEvent>,
+) -> bool {
+    let frame = match run_stages(&entry.stages, frame.clone()) {
+        StageOutcome::Deliver(frame) => frame,
+        StageOutcome::Filtered => {
+            if entry.ack == AckMode::ClientIndividual.as_str() {
+                filtered.push(entry.id.clone());
+            }
+            return true;
+        }
+        StageOutcome::Panicked => {
+            tracing::warn!(
+                subscription_id = %entry.id,
+                destination = %dest,
+                message_id = frame.get_header("message-id").unwrap_or(""),
+                "subscription step panicked, dropping message",
+            );
+            return true;
+        }
+    };
+    let full = entry.sender.capacity() == 0 && !entry.sender.is_closed();
+    if entry.ordered && full {
+        blocked.push((
+            entry.id.clone(),
+            dest.to_string(),
+            entry.sender.clone(),
+            frame,
+        ));
+    } else if !offer_to_subscriber(entry, frame) {
+        closed.push((entry.id.clone(), dest.to_string(), entry.implicit));
+        return false;
+    }
+    check_consumer_health(entry, dest, full, slow);
+    true
+}
+
+/// Offer a frame to a subscriber without blocking the connection task.
+///
+/// Returns `false` only when the subscriber's receiver has been dropped,
+/// meaning the entry should be removed. A full channel drops the frame,
+/// counts it in `entry.dropped`, and keeps the subscription.
+fn offer_to_subscriber(entry: &mut SubscriptionEntry, frame: Frame) -> bool {
+    match entry.sender.try_send(frame) {
+        Ok(()) => true,
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            entry.dropped += 1;
+            true
         }
+        Err(mpsc::error::TrySendError::Closed(_)) => false,
     }
+}
 
-    pub async fn close(self) {
-        // Signal the background task to shutdown by broadcasting on the
-        // shutdown channel. Consumers may await task termination separately
-        // if needed.
-        let _ = self.shutdown_tx.send(());
+/// Report a subscription as slow the first time a delivery finds its
+/// channel full, and re-arm the report once it has drained to half its
+/// capacity, so a consumer that keeps up again and later stalls is reported
+/// again.
+fn check_consumer_health(
+    entry: &mut SubscriptionEntry,
+    dest: &str,
+    full: bool,
+    slow: &mut Vec<ConnectionEvent>,
+) {
+    let capacity = entry.sender.max_capacity();
+    let buffered = capacity - entry.sender.capacity();
+    if full && !entry.slow {
+        entry.slow = true;
+        tracing::warn!(
+            subscription_id = %entry.id,
+            destination = %dest,
+            dropped = entry.dropped,
+            buffered,
+            "slow consumer: subscription buffer is full",
+        );
+        slow.push(ConnectionEvent::SlowConsumer {
+            subscription_id: entry.id.clone(),
+            destination: dest.to_string(),
+            dropped: entry.dropped,
+            buffered,
+        });
+    } else if entry.slow && buffered <= capacity / 2 {
+        entry.slow = false;
     }
 }
 
@@ -1883,6 +6352,56 @@ mod tests {
     use super::*;
     use tokio::sync::mpsc;
 
+    // Helper to assemble a Connection around test channels without a broker
+    fn test_connection(
+        out_tx: mpsc::Sender<StompItem>,
+        in_rx: mpsc::Receiver<Frame>,
+        shutdown_tx: broadcast::Sender<()>,
+        subscriptions: Arc<Mutex<Subscriptions>>,
+        sub_id_counter: Arc<AtomicU64>,
+        pending: Arc<Mutex<PendingMap>>,
+    ) -> Connection {
+        Connection {
+            outbound_tx: out_tx,
+            writer_lane: Arc::default(),
+            inbound_rx: Arc::new(Mutex::new(Inbound::new(in_rx))),
+            inbound_queued: Arc::new(AtomicUsize::new(0)),
+            shutdown_tx: shutdown_tx.clone(),
+            subscriptions,
+            sub_id_counter,
+            pending,
+            pending_released: Arc::default(),
+            invalidated: Arc::default(),
+            pending_receipts: Arc::new(Mutex::new(HashMap::new())),
+            expired_receipts: Arc::new(AtomicU64::new(0)),
+            unknown_frames: Arc::new(AtomicU64::new(0)),
+            decode_counters: Arc::default(),
+            server_info: Arc::new(Mutex::new(ServerInfo::from_connected(
+                &Frame::new("CONNECTED").header("version", "1.2"),
+            ))),
+            trace_context: None,
+            protocol: None,
+            destination_validation: None,
+            header_policy: HeaderPolicy::default(),
+            sequencer: None,
+            inflight: None,
+            reconfigure_tx: mpsc::channel(1).0,
+            close_tx: mpsc::channel(1).0,
+            event_tx: None,
+            outbox: None,
+            started: Arc::new(AtomicBool::new(true)),
+            destinations: Arc::default(),
+            lifeline: Some(Arc::new(Lifeline {
+                shutdown_tx,
+                inflight: None,
+            })),
+            task_labels: TaskLabels::new("test", None),
+            message_counters: Arc::default(),
+            circuit: Arc::default(),
+            tap_tx: Arc::default(),
+        }
+    }
+
     // Helper to build a MESSAGE frame with given message-id and subscription/destination headers
     fn make_message(
         message_id: &str,
@@ -1917,12 +6436,20 @@ mod tests {
         {
             let mut map = subscriptions.lock().await;
             map.insert(
-                "/queue/x".to_string(),
+                "/queue/x".into(),
                 vec![SubscriptionEntry {
                     id: "s1".to_string(),
                     sender: sub_sender,
                     ack: "client".to_string(),
                     headers: Vec::new(),
+                    ordered: false,
+                    pending_limit: None,
+                    pending_evicted: 0,
+                    dropped: 0,
+                    slow: false,
+                    implicit: false,
+                    stages: Default::default(),
+                    errors: broadcast::channel(1).0,
                 }],
             );
         }
@@ -1933,28 +6460,30 @@ mod tests {
             let mut q = VecDeque::new();
             q.push_back((
                 "m1".to_string(),
-                make_message("m1", Some("s1"), Some("/queue/x")),
+                Destination::new("/queue/x"),
+                Instant::now(),
             ));
             q.push_back((
                 "m2".to_string(),
-                make_message("m2", Some("s1"), Some("/queue/x")),
+                Destination::new("/queue/x"),
+                Instant::now(),
             ));
             q.push_back((
                 "m3".to_string(),
-                make_message("m3", Some("s1"), Some("/queue/x")),
+                Destination::new("/queue/x"),
+                Instant::now(),
             ));
             p.insert("s1".to_string(), q);
         }
 
-        let conn = Connection {
-            outbound_tx: out_tx,
-            inbound_rx: Arc::new(Mutex::new(in_rx)),
+        let conn = test_connection(
+            out_tx,
+            in_rx,
             shutdown_tx,
-            subscriptions: subscriptions.clone(),
+            subscriptions.clone(),
             sub_id_counter,
-            pending: pending.clone(),
-            pending_receipts: Arc::new(Mutex::new(HashMap::new())),
-        };
+            pending.clone(),
+        );
 
         // ack m2 cumulatively: should remove m1 and m2, leaving m3
         conn.ack("s1", "m2").await.expect("ack failed");
@@ -1995,12 +6524,20 @@ mod tests {
         {
             let mut map = subscriptions.lock().await;
             map.insert(
-                "/queue/y".to_string(),
+                "/queue/y".into(),
                 vec![SubscriptionEntry {
                     id: "s2".to_string(),
                     sender: sub_sender,
                     ack: "client-individual".to_string(),
                     headers: Vec::new(),
+                    ordered: false,
+                    pending_limit: None,
+                    pending_evicted: 0,
+                    dropped: 0,
+                    slow: false,
+                    implicit: false,
+                    stages: Default::default(),
+                    errors: broadcast::channel(1).0,
                 }],
             );
         }
@@ -2011,28 +6548,30 @@ mod tests {
             let mut q = VecDeque::new();
             q.push_back((
                 "a".to_string(),
-                make_message("a", Some("s2"), Some("/queue/y")),
+                Destination::new("/queue/y"),
+                Instant::now(),
             ));
             q.push_back((
                 "b".to_string(),
-                make_message("b", Some("s2"), Some("/queue/y")),
+                Destination::new("/queue/y"),
+                Instant::now(),
             ));
             q.push_back((
                 "c".to_string(),
-                make_message("c", Some("s2"), Some("/queue/y")),
+                Destination::new("/queue/y"),
+                Instant::now(),
             ));
             p.insert("s2".to_string(), q);
         }
 
-        let conn = Connection {
-            outbound_tx: out_tx,
-            inbound_rx: Arc::new(Mutex::new(in_rx)),
+        let conn = test_connection(
+            out_tx,
+            in_rx,
             shutdown_tx,
-            subscriptions: subscriptions.clone(),
+            subscriptions.clone(),
             sub_id_counter,
-            pending: pending.clone(),
-            pending_receipts: Arc::new(Mutex::new(HashMap::new())),
-        };
+            pending.clone(),
+        );
 
         // ack only 'b' individually
         conn.ack("s2", "b").await.expect("ack failed");
@@ -2057,6 +6596,85 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_connection_error_invalidates_pending_messages() {
+        let (out_tx, mut out_rx) = mpsc::channel::<StompItem>(8);
+        let (_in_tx, in_rx) = mpsc::channel::<Frame>(8);
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let subscriptions: Arc<Mutex<Subscriptions>> = Arc::new(Mutex::new(HashMap::new()));
+        let pending: Arc<Mutex<PendingMap>> = Arc::new(Mutex::new(HashMap::new()));
+
+        let (sub_sender, _sub_rx) = mpsc::channel::<Frame>(4);
+        let (errors_tx, mut errors_rx) = broadcast::channel(1);
+        subscriptions.lock().await.insert(
+            "/queue/y".into(),
+            vec![SubscriptionEntry {
+                id: "s2".to_string(),
+                sender: sub_sender,
+                ack: "client-individual".to_string(),
+                headers: Vec::new(),
+                ordered: false,
+                pending_limit: None,
+                pending_evicted: 0,
+                dropped: 0,
+                slow: false,
+                implicit: false,
+                stages: Default::default(),
+                errors: errors_tx,
+            }],
+        );
+        {
+            let mut p = pending.lock().await;
+            let q = p.entry("s2".to_string()).or_default();
+            for id in ["a", "b", "c"] {
+                q.push_back((id.to_string(), Destination::new("/queue/y"), Instant::now()));
+            }
+        }
+        let conn = test_connection(
+            out_tx,
+            in_rx,
+            shutdown_tx,
+            subscriptions.clone(),
+            Arc::new(AtomicU64::new(1)),
+            pending.clone(),
+        );
+
+        let error = Frame::new("ERROR").header("message", "internal error");
+        invalidate_pending(&error, &pending, &conn.invalidated, &subscriptions).await;
+        assert!(pending.lock().await.is_empty());
+        let reported = errors_rx.try_recv().expect("error not passed on");
+        assert_eq!(reported.message, "internal error");
+
+        // Refused once, without sending anything
+        let result = conn.ack("s2", "a").await;
+        assert!(
+            matches!(&result, Err(ConnError::MessageInvalidated(id)) if id == "a"),
+            "{:?}",
+            result
+        );
+        let result = conn.nack("s2", "b").await;
+        assert!(
+            matches!(&result, Err(ConnError::MessageInvalidated(id)) if id == "b"),
+            "{:?}",
+            result
+        );
+        assert!(out_rx.try_recv().is_err());
+
+        // The rest of a batch is still acknowledged
+        let result = conn.ack_batch("s2", &["c", "d"]).await;
+        assert!(
+            matches!(&result, Err(ConnError::MessageInvalidated(id)) if id == "c"),
+            "{:?}",
+            result
+        );
+        match out_rx.try_recv() {
+            Ok(StompItem::Frame(f)) => assert_eq!(f.get_header("id"), Some("d")),
+            other => panic!("expected ACK for d, got {:?}", other.is_ok()),
+        }
+        assert!(out_rx.try_recv().is_err());
+        assert!(conn.invalidated.lock().await.is_empty());
+    }
+
     #[tokio::test]
     async fn test_subscription_receive_delivers_message() {
         // setup channels
@@ -2069,15 +6687,14 @@ mod tests {
 
         let sub_id_counter = Arc::new(AtomicU64::new(1));
 
-        let conn = Connection {
-            outbound_tx: out_tx,
-            inbound_rx: Arc::new(Mutex::new(in_rx)),
+        let conn = test_connection(
+            out_tx,
+            in_rx,
             shutdown_tx,
-            subscriptions: subscriptions.clone(),
+            subscriptions.clone(),
             sub_id_counter,
-            pending: pending.clone(),
-            pending_receipts: Arc::new(Mutex::new(HashMap::new())),
-        };
+            pending.clone(),
+        );
 
         // subscribe
         let subscription = conn
@@ -2124,15 +6741,14 @@ mod tests {
 
         let sub_id_counter = Arc::new(AtomicU64::new(1));
 
-        let conn = Connection {
-            outbound_tx: out_tx,
-            inbound_rx: Arc::new(Mutex::new(in_rx)),
+        let conn = test_connection(
+            out_tx,
+            in_rx,
             shutdown_tx,
-            subscriptions: subscriptions.clone(),
+            subscriptions.clone(),
             sub_id_counter,
-            pending: pending.clone(),
-            pending_receipts: Arc::new(Mutex::new(HashMap::new())),
-        };
+            pending.clone(),
+        );
 
         // subscribe with client ack
         let subscription = conn
@@ -2151,7 +6767,8 @@ mod tests {
             let mut q = VecDeque::new();
             q.push_back((
                 "mid-1".to_string(),
-                make_message("mid-1", Some(&sub_id), Some("/queue/ack")),
+                Destination::new("/queue/ack"),
+                Instant::now(),
             ));
             p.insert(sub_id.clone(), q);
         }
@@ -2186,15 +6803,14 @@ mod tests {
         let pending: Arc<Mutex<PendingMap>> = Arc::new(Mutex::new(HashMap::new()));
         let sub_id_counter = Arc::new(AtomicU64::new(1));
 
-        let conn = Connection {
-            outbound_tx: out_tx,
-            inbound_rx: Arc::new(Mutex::new(in_rx)),
+        let conn = test_connection(
+            out_tx,
+            in_rx,
             shutdown_tx,
             subscriptions,
             sub_id_counter,
             pending,
-            pending_receipts: Arc::new(Mutex::new(HashMap::new())),
-        };
+        );
 
         (conn, out_rx)
     }
@@ -2271,6 +6887,27 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_send_options_content_length_policy_reaches_writer() {
+        let (conn, mut out_rx) = setup_test_connection();
+        let options = SendOptions::new().content_length_policy(ContentLengthPolicy::Never);
+
+        conn.send_with_options(
+            Frame::new("SEND").header("destination", "/queue/legacy"),
+            &options,
+        )
+        .await
+        .expect("send failed");
+
+        match out_rx.recv().await {
+            Some(StompItem::FrameWithContentLength(f, policy)) => {
+                assert_eq!(f.get_header("destination"), Some("/queue/legacy"));
+                assert_eq!(policy, ContentLengthPolicy::Never);
+            }
+            other => panic!("unexpected outbound item: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_extract_destination_from_error_header() {
         // When ERROR frame has destination header, extract it directly
@@ -2279,7 +6916,7 @@ mod tests {
             .header("destination", "/topic/test.restricted");
 
         let dest = extract_destination_from_error(&frame);
-        assert_eq!(dest, Some("/topic/test.restricted".to_string()));
+        assert_eq!(dest.as_deref(), Some("/topic/test.restricted"));
     }
 
     #[test]
@@ -2291,7 +6928,7 @@ mod tests {
         );
 
         let dest = extract_destination_from_error(&frame);
-        assert_eq!(dest, Some("/topic/test.restricted".to_string()));
+        assert_eq!(dest.as_deref(), Some("/topic/test.restricted"));
     }
 
     #[test]
@@ -2363,22 +7000,545 @@ mod tests {
         {
             let mut map = subscriptions.lock().await;
             map.insert(
-                "/topic/test.restricted".to_string(),
+                "/topic/test.restricted".into(),
                 vec![SubscriptionEntry {
                     id: "1".to_string(),
                     sender,
                     ack: "auto".to_string(),
                     headers: Vec::new(),
+                    ordered: false,
+                    pending_limit: None,
+                    pending_evicted: 0,
+                    dropped: 0,
+                    slow: false,
+                    implicit: false,
+                    stages: Default::default(),
+                    errors: broadcast::channel(1).0,
                 }],
             );
         }
 
         // Should find the destination
         let dest = lookup_destination_by_sub_id("1", &subscriptions).await;
-        assert_eq!(dest, Some("/topic/test.restricted".to_string()));
+        assert_eq!(dest.as_deref(), Some("/topic/test.restricted"));
 
         // Should not find non-existent subscription
         let dest = lookup_destination_by_sub_id("999", &subscriptions).await;
         assert_eq!(dest, None);
     }
+
+    #[test]
+    fn test_is_version_accepted() {
+        assert!(is_version_accepted("1.2", "1.2"));
+        assert!(is_version_accepted("1.0,1.1,1.2", "1.1"));
+        assert!(is_version_accepted("1.1, 1.2", "1.2"));
+        assert!(!is_version_accepted("1.2", "1.0"));
+        assert!(!is_version_accepted("1.1,1.2", "1.0"));
+    }
+
+    #[test]
+    fn test_server_info_missing_version_is_1_0() {
+        let info = ServerInfo::from_connected(&Frame::new("CONNECTED"));
+        assert_eq!(info.version, "1.0");
+        assert_eq!(info.heart_beat, "0,0");
+        assert!(!info.supports_nack());
+    }
+
+    #[tokio::test]
+    async fn test_nack_refused_on_stomp_1_0() {
+        let (conn, mut out_rx) = setup_test_connection();
+        *conn.server_info.lock().await =
+            ServerInfo::from_connected(&Frame::new("CONNECTED").header("version", "1.0"));
+
+        let result = conn.nack("s1", "m1").await;
+        assert!(matches!(result, Err(ConnError::Protocol(_))));
+        assert!(out_rx.try_recv().is_err(), "no NACK frame should be sent");
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_unknown_id() {
+        let (conn, mut out_rx) = setup_test_connection();
+
+        let result = conn.unsubscribe("missing").await;
+        assert!(matches!(result, Err(ConnError::SubscriptionNotFound(ref id)) if id == "missing"));
+        assert!(
+            out_rx.try_recv().is_err(),
+            "no UNSUBSCRIBE frame should be sent"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_share_interned_destination() {
+        let (conn, _out_rx) = setup_test_connection();
+
+        let a = conn.subscribe("/queue/x", AckMode::Auto).await.unwrap();
+        let b = conn.subscribe("/queue/x", AckMode::Auto).await.unwrap();
+        assert!(std::ptr::eq(a.destination(), b.destination()));
+        let map = conn.subscriptions.lock().await;
+        let (key, entries) = map.get_key_value("/queue/x").unwrap();
+        assert!(std::ptr::eq(key.as_str(), a.destination()));
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_offer_to_subscriber_detects_closed_receiver() {
+        let (sender, rx) = mpsc::channel::<Frame>(1);
+        let mut entry = SubscriptionEntry {
+            id: "1".to_string(),
+            sender,
+            ack: "auto".to_string(),
+            headers: Vec::new(),
+            ordered: false,
+            pending_limit: None,
+            pending_evicted: 0,
+            dropped: 0,
+            slow: false,
+            implicit: false,
+            stages: Default::default(),
+            errors: broadcast::channel(1).0,
+        };
+        let f = make_message("m1", Some("1"), Some("/queue/x"));
+
+        // Open channel accepts the frame
+        assert!(offer_to_subscriber(&mut entry, f.clone()));
+        // Full channel drops (and counts) the frame but keeps the entry
+        assert!(offer_to_subscriber(&mut entry, f.clone()));
+        assert_eq!(entry.dropped, 1);
+        // Dropped receiver marks the entry for removal
+        drop(rx);
+        assert!(!offer_to_subscriber(&mut entry, f.clone()));
+    }
+
+    #[tokio::test]
+    async fn test_nack_with_options_sets_requeue_headers() {
+        let (conn, mut out_rx) = setup_test_connection();
+
+        conn.nack_with_options(
+            "s1",
+            "m1",
+            NackOptions {
+                requeue: false,
+                reason: Some("bad payload".to_string()),
+            },
+        )
+        .await
+        .expect("nack failed");
+
+        if let Some(StompItem::Frame(f)) = out_rx.recv().await {
+            assert_eq!(f.command, "NACK");
+            assert_eq!(f.get_header("id"), Some("m1"));
+            assert_eq!(f.get_header("subscription"), Some("s1"));
+            assert_eq!(f.get_header("requeue"), Some("false"));
+            assert_eq!(f.get_header("reason"), Some("bad payload"));
+        } else {
+            panic!("no outbound frame sent")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plain_nack_has_no_requeue_header() {
+        let (conn, mut out_rx) = setup_test_connection();
+
+        conn.nack("s1", "m1").await.expect("nack failed");
+
+        if let Some(StompItem::Frame(f)) = out_rx.recv().await {
+            assert_eq!(f.command, "NACK");
+            assert_eq!(f.get_header("requeue"), None);
+        } else {
+            panic!("no outbound frame sent")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_injects_trace_context() {
+        let (mut conn, mut out_rx) = setup_test_connection();
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        conn.trace_context = Some(Arc::new(move || {
+            TraceContext::parse(traceparent).map(|c| c.with_tracestate("vendor=1"))
+        }));
+
+        conn.send("/queue/a", "x").await.expect("send failed");
+        conn.send_frame(
+            Frame::new("SEND")
+                .header("destination", "/queue/a")
+                .header("traceparent", "explicit"),
+        )
+        .await
+        .expect("send failed");
+        conn.begin("tx1").await.expect("begin failed");
+
+        let frames: Vec<Frame> = std::iter::from_fn(|| match out_rx.try_recv() {
+            Ok(StompItem::Frame(f)) => Some(f),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].get_header("traceparent"), Some(traceparent));
+        assert_eq!(frames[0].get_header("tracestate"), Some("vendor=1"));
+        // An explicit traceparent is left alone
+        assert_eq!(frames[1].get_header("traceparent"), Some("explicit"));
+        assert_eq!(frames[1].get_header("tracestate"), None);
+        // Only SEND frames are decorated
+        assert_eq!(frames[2].get_header("traceparent"), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_recv_helpers() {
+        use crate::subscription::{RecvTimeoutError, TryRecvError};
+
+        let (conn, _out_rx) = setup_test_connection();
+        let mut sub = conn
+            .subscribe("/queue/poll", AckMode::Auto)
+            .await
+            .expect("subscribe failed");
+
+        assert_eq!(sub.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(
+            sub.recv_timeout(Duration::from_millis(10)).await,
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let sender = {
+            let map = conn.subscriptions.lock().await;
+            map.get("/queue/poll").expect("missing subscription")[0]
+                .sender
+                .clone()
+        };
+        for id in ["m1", "m2", "m3"] {
+            sender
+                .try_send(make_message(id, Some("1"), Some("/queue/poll")))
+                .expect("send to subscription failed");
+        }
+
+        let first = sub.try_recv().expect("buffered message");
+        assert_eq!(first.get_header("message-id"), Some("m1"));
+        let second = sub
+            .recv_timeout(Duration::from_secs(1))
+            .await
+            .expect("message within timeout");
+        assert_eq!(second.get_header("message-id"), Some("m2"));
+        let third = sub.recv().await.expect("message");
+        assert_eq!(third.get_header("message-id"), Some("m3"));
+
+        // Closing every sender closes the subscription
+        drop(sender);
+        conn.subscriptions.lock().await.clear();
+        assert_eq!(
+            sub.recv_timeout(Duration::from_secs(1)).await,
+            Err(RecvTimeoutError::Closed)
+        );
+        assert!(sub.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ack_batch_sends_single_cumulative_ack() {
+        let (conn, mut out_rx) = setup_test_connection();
+        let sub = conn
+            .subscribe("/queue/batch", AckMode::Client)
+            .await
+            .expect("subscribe failed");
+        while out_rx.try_recv().is_ok() {}
+
+        {
+            let mut p = conn.pending.lock().await;
+            let q: VecDeque<(String, Destination, Instant)> = ["m1", "m2", "m3", "m4"]
+                .iter()
+                .map(|id| {
+                    (
+                        id.to_string(),
+                        Destination::new("/queue/batch"),
+                        Instant::now(),
+                    )
+                })
+                .collect();
+            p.insert(sub.id().to_string(), q);
+        }
+
+        // Order of ids in the slice does not matter; m3 was delivered last
+        sub.ack_batch(&["m3", "m1", "m2"])
+            .await
+            .expect("ack_batch failed");
+
+        match out_rx.try_recv() {
+            Ok(StompItem::Frame(f)) => {
+                assert_eq!(f.command, "ACK");
+                assert_eq!(f.get_header("id"), Some("m3"));
+            }
+            _ => panic!("expected one ACK frame"),
+        }
+        assert!(out_rx.try_recv().is_err(), "expected a single ACK");
+
+        let p = conn.pending.lock().await;
+        let remaining: Vec<&str> = p[sub.id()].iter().map(|(m, _, _)| m.as_str()).collect();
+        assert_eq!(remaining, vec!["m4"]);
+    }
+
+    #[tokio::test]
+    async fn test_ack_batch_individual_mode_acks_each() {
+        let (conn, mut out_rx) = setup_test_connection();
+        let sub = conn
+            .subscribe("/queue/batch", AckMode::ClientIndividual)
+            .await
+            .expect("subscribe failed");
+        while out_rx.try_recv().is_ok() {}
+
+        sub.ack_batch(&["m1", "m2"])
+            .await
+            .expect("ack_batch failed");
+
+        let acked: Vec<String> = std::iter::from_fn(|| match out_rx.try_recv() {
+            Ok(StompItem::Frame(f)) => f.get_header("id").map(str::to_string),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(acked, vec!["m1", "m2"]);
+    }
+
+    #[tokio::test]
+    async fn test_recv_batch_stops_at_max_or_deadline() {
+        let (conn, _out_rx) = setup_test_connection();
+        let mut sub = conn
+            .subscribe("/queue/batch", AckMode::Auto)
+            .await
+            .expect("subscribe failed");
+
+        let sender = {
+            let map = conn.subscriptions.lock().await;
+            map.get("/queue/batch").expect("missing subscription")[0]
+                .sender
+                .clone()
+        };
+        for id in ["m1", "m2", "m3"] {
+            sender
+                .try_send(make_message(id, Some("1"), Some("/queue/batch")))
+                .expect("send to subscription failed");
+        }
+
+        let batch = sub.recv_batch(2, Duration::from_secs(1)).await;
+        assert_eq!(batch.len(), 2);
+
+        let start = std::time::Instant::now();
+        let batch = sub.recv_batch(10, Duration::from_millis(50)).await;
+        assert_eq!(batch.len(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_outstanding_receipts_tracks_count_and_age() {
+        let (conn, _out_rx) = setup_test_connection();
+        assert_eq!(
+            conn.outstanding_receipts().await,
+            OutstandingReceipts::default()
+        );
+
+        let first = conn
+            .send_frame_with_receipt(Frame::new("SEND").header("destination", "/queue/a"))
+            .await
+            .expect("send failed");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        conn.send_frame_with_receipt(Frame::new("SEND").header("destination", "/queue/a"))
+            .await
+            .expect("send failed");
+
+        let outstanding = conn.outstanding_receipts().await;
+        assert_eq!(outstanding.count, 2);
+        assert!(outstanding.oldest_age.expect("oldest age") >= Duration::from_millis(20));
+
+        // Waiting re-registers the sender but keeps the original age
+        let _ = conn
+            .wait_for_receipt(&first, Duration::from_millis(1))
+            .await;
+        let outstanding = conn.outstanding_receipts().await;
+        assert_eq!(outstanding.count, 1);
+
+        // The age of the one left is measured from its send and keeps growing
+        let age = outstanding.oldest_age.expect("oldest age");
+        assert!(age > Duration::ZERO);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let later = conn.outstanding_receipts().await.oldest_age;
+        assert!(later.expect("oldest age") >= age + Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_pending_acks_reports_count_and_oldest_age() {
+        let (conn, _out_rx) = setup_test_connection();
+        let sub = conn
+            .subscribe("/queue/lag", AckMode::ClientIndividual)
+            .await
+            .expect("subscribe failed");
+        assert!(conn.pending_acks().await.is_empty());
+        assert_eq!(sub.pending_acks().await, PendingAcks::default());
+
+        {
+            let mut p = conn.pending.lock().await;
+            let delivered = Instant::now() - Duration::from_secs(5);
+            let q = ["m1", "m2"]
+                .iter()
+                .map(|id| (id.to_string(), Destination::new("/queue/lag"), delivered))
+                .collect();
+            p.insert(sub.id().to_string(), q);
+        }
+
+        let pending = sub.pending_acks().await;
+        assert_eq!(pending.count, 2);
+        assert!(pending.oldest_age.expect("oldest age") >= Duration::from_secs(5));
+        assert_eq!(conn.pending_acks().await[sub.id()].count, 2);
+
+        sub.ack("m1").await.expect("ack failed");
+        sub.ack("m2").await.expect("ack failed");
+        assert!(conn.pending_acks().await.is_empty());
+        assert_eq!(sub.pending_acks().await, PendingAcks::default());
+    }
+
+    #[test]
+    fn test_expire_receipts_keeps_waited_and_fresh_entries() {
+        let mut receipts: PendingReceipts = HashMap::new();
+        let old = Instant::now() - Duration::from_secs(10);
+
+        // Abandoned and old: removed
+        let (tx, rx) = oneshot::channel();
+        drop(rx);
+        receipts.insert(
+            "abandoned".into(),
+            PendingReceipt {
+                sender: tx,
+                sent_at: old,
+                permit: None,
+            },
+        );
+
+        // Old but someone is still waiting: kept
+        let (tx, _waiting_rx) = oneshot::channel();
+        receipts.insert(
+            "waited".into(),
+            PendingReceipt {
+                sender: tx,
+                sent_at: old,
+                permit: None,
+            },
+        );
+
+        // Abandoned but fresh: kept
+        let (tx, rx) = oneshot::channel();
+        drop(rx);
+        receipts.insert("fresh".into(), PendingReceipt::new(tx));
+
+        assert_eq!(expire_receipts(&mut receipts, Duration::from_secs(5)), 1);
+        assert!(!receipts.contains_key("abandoned"));
+        assert!(receipts.contains_key("waited"));
+        assert!(receipts.contains_key("fresh"));
+    }
+
+    #[tokio::test]
+    async fn test_fail_receipts_releases_waiters() {
+        let mut receipts: PendingReceipts = HashMap::new();
+        let (tx, rx) = oneshot::channel();
+        receipts.insert("rcpt-1".into(), PendingReceipt::new(tx));
+        let (tx, abandoned) = oneshot::channel();
+        drop(abandoned);
+        receipts.insert("rcpt-2".into(), PendingReceipt::new(tx));
+
+        assert_eq!(fail_receipts(&mut receipts), 2);
+        assert!(receipts.is_empty());
+        assert_eq!(
+            rx.await.unwrap(),
+            Err(ReceiptError::ConnectionLost {
+                receipt_id: "rcpt-1".into()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_protocol_rejects_before_sending() {
+        let (mut conn, mut out_rx) = setup_test_connection();
+        conn.protocol = Some(Arc::new(Mutex::new(ProtocolState::new())));
+
+        // ACK for a subscription that does not exist
+        let err = conn.ack("99", "m1").await.unwrap_err();
+        assert!(err.to_string().contains("unknown subscription"), "{}", err);
+        // COMMIT of a transaction that was never begun
+        assert!(conn.commit("tx1").await.is_err());
+        assert!(out_rx.try_recv().is_err(), "nothing should be sent");
+
+        let sub = conn.subscribe("/queue/a", AckMode::Client).await.unwrap();
+        assert!(matches!(out_rx.try_recv(), Ok(StompItem::Frame(f)) if f.command == "SUBSCRIBE"));
+        conn.ack(sub.id(), "m1").await.unwrap();
+        assert!(matches!(out_rx.try_recv(), Ok(StompItem::Frame(f)) if f.command == "ACK"));
+
+        conn.begin("tx1").await.unwrap();
+        conn.commit("tx1").await.unwrap();
+        assert_eq!(
+            out_rx.try_recv().ok().and_then(|item| match item {
+                StompItem::Frame(f) => Some(f.command),
+                _ => None,
+            }),
+            Some("BEGIN".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_protocol_records_only_queued_frames() {
+        let (mut conn, mut out_rx) = setup_test_connection();
+        conn.protocol = Some(Arc::new(Mutex::new(ProtocolState::new())));
+        // Not started: a full queue refuses frames instead of waiting
+        conn.started.store(false, Ordering::SeqCst);
+        for _ in 0..8 {
+            conn.send("/queue/a", "x").await.unwrap();
+        }
+        assert!(conn.begin("tx1").await.is_err());
+
+        // The refused BEGIN did not open the transaction
+        while out_rx.try_recv().is_ok() {}
+        assert!(conn.commit("tx1").await.is_err());
+        conn.begin("tx1").await.unwrap();
+        conn.commit("tx1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lenient_by_default() {
+        let (conn, mut out_rx) = setup_test_connection();
+        conn.ack("99", "m1").await.unwrap();
+        conn.commit("tx1").await.unwrap();
+        assert!(matches!(out_rx.try_recv(), Ok(StompItem::Frame(f)) if f.command == "ACK"));
+    }
+
+    #[tokio::test]
+    async fn test_destination_validation_refuses_invalid_destinations() {
+        let (mut conn, mut out_rx) = setup_test_connection();
+        conn.destination_validation = Some(BrokerProfile::RabbitMq);
+
+        let err = conn.send("queue/a", "x").await.unwrap_err();
+        assert!(err.to_string().contains("must start with '/'"), "{}", err);
+        assert!(conn.subscribe("/queues/a", AckMode::Auto).await.is_err());
+        assert!(out_rx.try_recv().is_err(), "nothing should be sent");
+
+        conn.send("/queue/a", "x").await.unwrap();
+        assert!(matches!(out_rx.try_recv(), Ok(StompItem::Frame(f)) if f.command == "SEND"));
+    }
+
+    #[test]
+    fn test_jittered_interval_stays_within_bounds() {
+        let interval = Duration::from_millis(1000);
+        assert_eq!(jittered_interval(interval, 0.0, u64::MAX), interval);
+        assert_eq!(
+            jittered_interval(interval, 0.2, 0),
+            Duration::from_millis(800)
+        );
+        assert_eq!(jittered_interval(interval, 0.2, 1 << 63), interval);
+        let longest = jittered_interval(interval, 0.2, u64::MAX);
+        assert!(longest <= Duration::from_millis(1200) && longest > Duration::from_millis(1199));
+    }
+
+    #[test]
+    fn test_heartbeat_grace_period() {
+        let interval = Duration::from_millis(200);
+        assert_eq!(
+            heartbeat_grace_period(interval, DEFAULT_HEARTBEAT_GRACE),
+            Duration::from_millis(400)
+        );
+        assert_eq!(
+            heartbeat_grace_period(interval, 1.25),
+            Duration::from_millis(250)
+        );
+    }
 }
@@ -0,0 +1,414 @@
+//! Destination names and their validation.
+//!
+//! [`Destination`] is a shared, cheaply cloned destination name. A
+//! connection interns the destinations it subscribes to and those of the
+//! messages it receives, so every subscription, pending message and
+//! `Message` on a destination refers to the same string.
+//!
+//! STOMP treats destinations as opaque strings, but every broker has its own
+//! naming rules and a mistyped destination usually surfaces only as an ERROR
+//! frame (or, worse, as messages silently going nowhere). [`validate`]
+//! checks a destination against a [`BrokerProfile`] up front.
+//!
+//! Validation is also available on the connection: enable it with
+//! `ConnectOptions::validate_destinations()` to have `subscribe` and
+//! `send` refuse destinations with errors and log warnings.
+
+use crate::broker::BrokerProfile;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// A destination name, shared between its copies.
+///
+/// Cloning is cheap: copies point at the same string. Dereferences to
+/// `str` and compares, hashes and orders like the name itself, so a map
+/// keyed by `Destination` can be looked up with a `&str`.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::Destination;
+///
+/// let orders = Destination::new("/queue/orders");
+/// assert_eq!(orders, "/queue/orders");
+/// assert!(orders.starts_with("/queue/"));
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Destination(Arc<str>);
+
+impl Destination {
+    /// A destination with its own copy of `name`. Destinations handed out
+    /// by a connection share one copy per name instead.
+    pub fn new(name: &str) -> Self {
+        Self(Arc::from(name))
+    }
+
+    /// The destination name.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Destination {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Destination {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Destination {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl From<&str> for Destination {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Destination {
+    fn from(name: String) -> Self {
+        Self(Arc::from(name))
+    }
+}
+
+impl PartialEq<str> for Destination {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Destination {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+/// Table size below which released names are never pruned.
+const MIN_PRUNE_AT: usize = 64;
+
+/// The destinations a connection has handed out, so each name is stored
+/// once however many subscriptions, pending messages and frames use it.
+#[derive(Debug, Default)]
+pub(crate) struct DestinationTable {
+    inner: Mutex<TableInner>,
+}
+
+#[derive(Debug, Default)]
+struct TableInner {
+    names: HashSet<Destination>,
+    /// Size at which the next new name first drops the unused ones.
+    prune_at: usize,
+}
+
+impl DestinationTable {
+    /// The shared `Destination` for `name`, added to the table if new.
+    ///
+    /// Names nothing else refers to any more are dropped when a new name
+    /// finds the table at twice its size after the last pruning, so the
+    /// cost of the scan is spread over the names added since.
+    pub(crate) fn intern(&self, name: &str) -> Destination {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(destination) = inner.names.get(name) {
+            return destination.clone();
+        }
+        if inner.names.len() >= inner.prune_at.max(MIN_PRUNE_AT) {
+            inner
+                .names
+                .retain(|destination| Arc::strong_count(&destination.0) > 1);
+            inner.prune_at = inner.names.len() * 2;
+        }
+        let destination = Destination::new(name);
+        inner.names.insert(destination.clone());
+        destination
+    }
+
+    /// Number of names in the table.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .names
+            .len()
+    }
+}
+
+/// A problem found by [`validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum DestinationIssue {
+    /// The destination is the empty string.
+    #[error("destination is empty")]
+    Empty,
+    /// The destination does not start with `/`.
+    #[error("destination must start with '/' (e.g. /queue/orders, /topic/events)")]
+    MissingLeadingSlash,
+    /// The destination starts with a prefix the broker does not recognize.
+    #[error("unknown prefix '{prefix}' (expected one of {})", expected.join(", "))]
+    UnknownPrefix {
+        /// The leading path segment, e.g. `/queues/`.
+        prefix: String,
+        /// The prefixes the broker recognizes.
+        expected: Vec<&'static str>,
+    },
+    /// The destination is a bare prefix with no name after it.
+    #[error("destination '{0}' has no name after the prefix")]
+    EmptyName(String),
+    /// The destination contains a control character (NUL, CR, LF, ...).
+    #[error("illegal control character {0:?} in destination")]
+    IllegalCharacter(char),
+    /// The destination contains whitespace, which is rarely intended.
+    #[error("destination contains whitespace")]
+    Whitespace,
+}
+
+/// Result of [`validate`]: errors make the destination unusable, warnings
+/// flag names that are legal but probably a mistake.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DestinationReport {
+    /// Problems the broker would reject.
+    pub errors: Vec<DestinationIssue>,
+    /// Suspicious but possibly intended names.
+    pub warnings: Vec<DestinationIssue>,
+}
+
+impl DestinationReport {
+    /// `true` if there are no errors (warnings are allowed).
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// `true` if there are neither errors nor warnings.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+/// Destination prefixes each broker recognizes. An empty list means any
+/// name is accepted.
+fn known_prefixes(broker: BrokerProfile) -> &'static [&'static str] {
+    match broker {
+        BrokerProfile::Generic => &[
+            "/queue/",
+            "/topic/",
+            "/amq/",
+            "/exchange/",
+            "/temp-queue/",
+            "/temp-topic/",
+        ],
+        BrokerProfile::RabbitMq => &[
+            "/queue/",
+            "/topic/",
+            "/exchange/",
+            "/amq/queue/",
+            "/temp-queue/",
+            "/reply-queue/",
+        ],
+        BrokerProfile::ActiveMq => &["/queue/", "/topic/", "/temp-queue/", "/temp-topic/"],
+        BrokerProfile::Artemis => &[],
+    }
+}
+
+/// Check `destination` against the naming rules of `broker`.
+///
+/// - Empty destinations and control characters are always errors.
+/// - A missing leading `/` is an error, except on Artemis, whose addresses
+///   are plain names.
+/// - An unrecognized prefix is an error on RabbitMQ, which rejects it, and
+///   a warning elsewhere.
+/// - A bare prefix such as `/queue/` is an error.
+/// - Whitespace is a warning.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::BrokerProfile;
+/// use iridium_stomp::destination::{self, DestinationIssue};
+///
+/// assert!(destination::validate("/queue/orders", BrokerProfile::RabbitMq).is_clean());
+///
+/// let report = destination::validate("queue/orders", BrokerProfile::Generic);
+/// assert_eq!(report.errors, vec![DestinationIssue::MissingLeadingSlash]);
+/// ```
+pub fn validate(destination: &str, broker: BrokerProfile) -> DestinationReport {
+    let mut report = DestinationReport::default();
+
+    if destination.is_empty() {
+        report.errors.push(DestinationIssue::Empty);
+        return report;
+    }
+
+    if let Some(ch) = destination.chars().find(|c| c.is_control()) {
+        report.errors.push(DestinationIssue::IllegalCharacter(ch));
+    }
+    if destination
+        .chars()
+        .any(|c| c.is_whitespace() && !c.is_control())
+    {
+        report.warnings.push(DestinationIssue::Whitespace);
+    }
+
+    let prefixes = known_prefixes(broker);
+    if prefixes.is_empty() {
+        return report;
+    }
+
+    if !destination.starts_with('/') {
+        report.errors.push(DestinationIssue::MissingLeadingSlash);
+        return report;
+    }
+
+    match prefixes.iter().find(|p| destination.starts_with(*p)) {
+        Some(prefix) if destination.len() == prefix.len() => {
+            report
+                .errors
+                .push(DestinationIssue::EmptyName(destination.to_string()));
+        }
+        Some(_) => {}
+        None => {
+            let end = destination[1..]
+                .find('/')
+                .map_or(destination.len(), |i| i + 2);
+            let issue = DestinationIssue::UnknownPrefix {
+                prefix: destination[..end].to_string(),
+                expected: prefixes.to_vec(),
+            };
+            if broker == BrokerProfile::RabbitMq {
+                report.errors.push(issue);
+            } else {
+                report.warnings.push(issue);
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_common_destinations() {
+        for dest in [
+            "/queue/a",
+            "/topic/a.b",
+            "/exchange/amq.topic/x",
+            "/amq/queue/q",
+        ] {
+            assert!(
+                validate(dest, BrokerProfile::Generic).is_clean(),
+                "{} rejected",
+                dest
+            );
+        }
+        assert!(validate("/amq/queue/q", BrokerProfile::RabbitMq).is_clean());
+        assert!(validate("orders::billing", BrokerProfile::Artemis).is_clean());
+    }
+
+    #[test]
+    fn reports_structural_errors() {
+        assert_eq!(
+            validate("", BrokerProfile::Artemis).errors,
+            vec![DestinationIssue::Empty]
+        );
+        assert_eq!(
+            validate("queue/a", BrokerProfile::Generic).errors,
+            vec![DestinationIssue::MissingLeadingSlash]
+        );
+        assert_eq!(
+            validate("/queue/", BrokerProfile::ActiveMq).errors,
+            vec![DestinationIssue::EmptyName("/queue/".into())]
+        );
+        assert_eq!(
+            validate("/queue/a\nb", BrokerProfile::Generic).errors,
+            vec![DestinationIssue::IllegalCharacter('\n')]
+        );
+    }
+
+    #[test]
+    fn unknown_prefix_severity_depends_on_broker() {
+        let generic = validate("/queues/a", BrokerProfile::Generic);
+        assert!(generic.is_valid());
+        assert!(matches!(
+            &generic.warnings[..],
+            [DestinationIssue::UnknownPrefix { prefix, .. }] if prefix == "/queues/"
+        ));
+
+        let rabbit = validate("/queues/a", BrokerProfile::RabbitMq);
+        assert!(!rabbit.is_valid());
+    }
+
+    #[test]
+    fn table_shares_names_until_unused() {
+        let table = DestinationTable::default();
+        let first = table.intern("/queue/a");
+        let second = table.intern("/queue/a");
+        assert!(Arc::ptr_eq(&first.0, &second.0));
+        let other = table.intern("/queue/b");
+        assert_eq!(table.len(), 2);
+
+        // Unused names stay until the table reaches the pruning size
+        drop(other);
+        let _third = table.intern("/queue/a");
+        assert_eq!(table.len(), 2);
+        let released: Vec<_> = (0..MIN_PRUNE_AT - 2)
+            .map(|i| table.intern(&format!("/queue/r{i}")))
+            .collect();
+        assert_eq!(table.len(), MIN_PRUNE_AT);
+        drop(released);
+        // The next new name drops them, and the table may then double
+        let _c = table.intern("/queue/c");
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn table_prunes_only_when_doubled() {
+        let table = DestinationTable::default();
+        let live: Vec<_> = (0..MIN_PRUNE_AT)
+            .map(|i| table.intern(&format!("/queue/live{i}")))
+            .collect();
+        // Nothing to drop: the next pruning waits for twice the size
+        let _extra = table.intern("/queue/extra");
+        for i in 0..MIN_PRUNE_AT - 1 {
+            drop(table.intern(&format!("/queue/gone{i}")));
+        }
+        assert_eq!(table.len(), 2 * MIN_PRUNE_AT);
+        let _last = table.intern("/queue/last");
+        assert_eq!(table.len(), MIN_PRUNE_AT + 2);
+        drop(live);
+    }
+
+    #[test]
+    fn whitespace_is_a_warning() {
+        let report = validate("/queue/my orders", BrokerProfile::Generic);
+        assert!(report.is_valid());
+        assert_eq!(report.warnings, vec![DestinationIssue::Whitespace]);
+    }
+}
@@ -0,0 +1,123 @@
+//! Logging for errors the client recovers from without reporting them to
+//! the caller.
+//!
+//! Some failures are not worth failing a call over, or have no caller to
+//! report to: a frame written as the connection drops (the reconnect takes
+//! over), an event listener whose channel is full, a receipt waiter that
+//! has already given up, an unsubscribe issued when a subscription is
+//! dropped. Each is logged through `tracing` under the
+//! `iridium_stomp::swallowed` target, with the operation and, where there
+//! is one, the frame's command and destination, at the level set with
+//! [`set_error_log_level`].
+//!
+//! # Example
+//!
+//! ```
+//! use iridium_stomp::{ErrorLogLevel, set_error_log_level};
+//!
+//! // Show them next to the client's other warnings while investigating
+//! set_error_log_level(ErrorLogLevel::Warn);
+//! ```
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::frame::Frame;
+
+/// Level at which swallowed errors are logged, set with
+/// [`set_error_log_level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorLogLevel {
+    /// Not logged at all.
+    Off,
+    /// `tracing::Level::TRACE`.
+    Trace,
+    /// `tracing::Level::DEBUG`, the default: most of these errors are
+    /// expected around disconnects.
+    #[default]
+    Debug,
+    /// `tracing::Level::INFO`.
+    Info,
+    /// `tracing::Level::WARN`.
+    Warn,
+}
+
+impl ErrorLogLevel {
+    const ALL: [ErrorLogLevel; 5] = [
+        ErrorLogLevel::Off,
+        ErrorLogLevel::Trace,
+        ErrorLogLevel::Debug,
+        ErrorLogLevel::Info,
+        ErrorLogLevel::Warn,
+    ];
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(ErrorLogLevel::Debug as u8);
+
+/// Set the level at which swallowed errors are logged, for every
+/// connection in the process.
+pub fn set_error_log_level(level: ErrorLogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// The level at which swallowed errors are logged.
+pub fn error_log_level() -> ErrorLogLevel {
+    ErrorLogLevel::ALL[LEVEL.load(Ordering::Relaxed) as usize]
+}
+
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            ErrorLogLevel::Off => {}
+            ErrorLogLevel::Trace => tracing::trace!(target: "iridium_stomp::swallowed", $($arg)+),
+            ErrorLogLevel::Debug => tracing::debug!(target: "iridium_stomp::swallowed", $($arg)+),
+            ErrorLogLevel::Info => tracing::info!(target: "iridium_stomp::swallowed", $($arg)+),
+            ErrorLogLevel::Warn => tracing::warn!(target: "iridium_stomp::swallowed", $($arg)+),
+        }
+    };
+}
+
+/// Log an error that `operation` ignored. `command` and `destination`
+/// describe the frame involved, if any.
+pub(crate) fn swallowed(
+    operation: &str,
+    command: Option<&str>,
+    destination: Option<&str>,
+    error: &dyn Display,
+) {
+    log_at!(
+        error_log_level(),
+        operation,
+        command = command.unwrap_or("-"),
+        destination = destination.unwrap_or("-"),
+        error = %error,
+        "{} failed: {}",
+        operation,
+        error,
+    );
+}
+
+/// [`swallowed`] for an error involving `frame`.
+pub(crate) fn swallowed_frame(operation: &str, frame: &Frame, error: &dyn Display) {
+    swallowed(
+        operation,
+        Some(&frame.command),
+        frame.get_header("destination"),
+        error,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_round_trips() {
+        for level in ErrorLogLevel::ALL {
+            set_error_log_level(level);
+            assert_eq!(error_log_level(), level);
+            swallowed("test", None, None, &"ignored");
+        }
+        set_error_log_level(ErrorLogLevel::default());
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::diagnostics;
+use crate::router::FailurePolicy;
+use crate::subscription::PendingOverflow;
+
+/// Notable occurrences inside a `Connection`'s background task.
+///
+/// Register a channel with `ConnectOptions::with_event_notify()` to receive
+/// these. Events describe things the library handled on its own (for
+/// example, cleaning up a subscription whose receiver was dropped) so that
+/// applications can log or react to them.
+///
+/// New variants may be added in future releases, so match with a wildcard
+/// arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// The receiving side of a subscription was dropped. The subscription
+    /// was removed locally and an UNSUBSCRIBE frame was sent to the broker.
+    SubscriptionDropped {
+        /// The local subscription id.
+        subscription_id: String,
+        /// The destination the subscription was listening to.
+        destination: String,
+    },
+
+    /// Receipts have gone unconfirmed for longer than the threshold set with
+    /// `ConnectOptions::receipt_warning_after()`. Emitted once per stall.
+    ReceiptsStalled {
+        /// Number of receipts awaiting confirmation.
+        count: usize,
+        /// Age of the oldest unconfirmed receipt.
+        oldest_age: Duration,
+    },
+
+    /// The broker sent a frame whose command the client does not handle
+    /// (anything other than MESSAGE, RECEIPT, ERROR, or CONNECTED). The
+    /// frame is still forwarded to `next_frame()`. Usually a protocol
+    /// extension or a broker bug.
+    UnknownFrame {
+        /// The frame's command.
+        command: String,
+    },
+
+    /// A subscriber is not keeping up: a MESSAGE arrived while its channel
+    /// was full. Unordered subscriptions drop such messages; ordered ones
+    /// hold up the connection until there is room. Emitted once per
+    /// episode; the subscription is reported again only after its buffer
+    /// has drained to half full.
+    SlowConsumer {
+        /// The local subscription id.
+        subscription_id: String,
+        /// The destination the subscription is listening to.
+        destination: String,
+        /// Messages dropped for this subscription so far (always 0 for
+        /// ordered subscriptions).
+        dropped: u64,
+        /// Messages waiting in the subscription's channel.
+        buffered: usize,
+    },
+
+    /// A subscription tracks as many unacknowledged messages as its
+    /// `SubscriptionOptions::max_pending` allows and another one arrived;
+    /// `policy` says what happens to it. Emitted once per episode; the
+    /// subscription is reported again only after it has settled half of
+    /// its pending messages.
+    PendingLimitReached {
+        /// The local subscription id.
+        subscription_id: String,
+        /// The destination the subscription is listening to.
+        destination: String,
+        /// The subscription's `max_pending`.
+        max: usize,
+        /// The subscription's `pending_overflow`.
+        policy: PendingOverflow,
+        /// Messages evicted or NACKed for this subscription so far.
+        evicted: u64,
+    },
+
+    /// Nothing was received from the broker for longer than the limit set
+    /// with `ConnectOptions::read_timeout()`. The connection was closed and
+    /// will be re-established.
+    ReadTimeout {
+        /// How long the connection had been silent.
+        idle: Duration,
+    },
+
+    /// An attempt by `Connection::send_with_retry()` failed transiently
+    /// and the frame will be sent again after `delay`.
+    SendRetry {
+        /// The frame's destination, if it has one.
+        destination: Option<String>,
+        /// Number of the attempt that failed, starting at 1.
+        attempt: u32,
+        /// Wait before the next attempt.
+        delay: Duration,
+        /// Why the attempt failed.
+        reason: String,
+    },
+
+    /// The session ended and the connection is about to try to
+    /// re-establish it. Emitted before every attempt until one succeeds.
+    Reconnecting {
+        /// Number of this attempt since the session ended, starting at 1.
+        attempt: u32,
+    },
+
+    /// The connection was re-established and the broker has confirmed the
+    /// subscriptions sent again on the new session (a receipt is requested
+    /// on the last SUBSCRIBE). Without subscriptions this follows the
+    /// handshake directly.
+    Reconnected {
+        /// Number of attempts it took.
+        attempts: u32,
+        /// The destinations subscribed to again, without duplicates.
+        resubscribed: Vec<String>,
+    },
+
+    /// Sessions kept failing, as configured with
+    /// `ConnectOptions::circuit_breaker()`. No reconnect is attempted
+    /// until the cool-down ends or `Connection::retry_now()` is called.
+    CircuitOpened {
+        /// Failed sessions within the window.
+        failures: u32,
+        /// How long reconnects are paused.
+        cool_down: Duration,
+    },
+
+    /// The circuit breaker's cool-down ended and reconnect attempts resume.
+    CircuitClosed {
+        /// Whether it was cut short by `Connection::retry_now()`.
+        manual: bool,
+    },
+
+    /// A `Router` handler returned `Err` or panicked. The message was
+    /// settled according to `policy` and the router carried on.
+    HandlerFailed {
+        /// The destination of the route's subscription.
+        destination: String,
+        /// The failed message's `message-id`.
+        message_id: String,
+        /// The handler's error, or the panic message.
+        error: String,
+        /// Whether the handler panicked rather than returning `Err`.
+        panicked: bool,
+        /// How the message was settled.
+        policy: FailurePolicy,
+    },
+    /// A `Router` handler ran past the deadline set with
+    /// `Router::processing_deadline()` (and any extensions). The handler
+    /// was cancelled and the message NACKed.
+    HandlerTimedOut {
+        /// The destination of the route's subscription.
+        destination: String,
+        /// The message's `message-id`.
+        message_id: String,
+        /// How long the handler ran.
+        elapsed: Duration,
+    },
+}
+
+/// Deliver an event to the registered listener, if any.
+///
+/// Uses `try_send()` so the connection task never blocks on a slow
+/// listener; events are dropped, and logged, when the channel is full.
+pub(crate) fn emit(tx: &Option<mpsc::Sender<ConnectionEvent>>, event: ConnectionEvent) {
+    if let Some(tx) = tx
+        && let Err(e) = tx.try_send(event)
+    {
+        diagnostics::swallowed("deliver connection event", None, None, &e);
+    }
+}
@@ -1,10 +1,17 @@
+use crate::codec::ContentLengthPolicy;
+use crate::destination::Destination;
 use std::fmt;
 
 /// A simple representation of a STOMP frame.
 ///
 /// `Frame` contains the command (e.g. "SEND", "MESSAGE"), an ordered list
 /// of headers (key/value pairs) and the raw body bytes.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// `PartialEq` and `Hash` compare headers exactly, including their order,
+/// but ignore [`seq`](Frame::seq). Use [`semantically_eq`](Frame::semantically_eq) or
+/// [`canonicalize`](Frame::canonicalize) when header order and name case
+/// should not matter.
+#[derive(Debug, Clone)]
 pub struct Frame {
     /// STOMP command (e.g. CONNECT, SEND, SUBSCRIBE)
     pub command: String,
@@ -12,6 +19,28 @@ pub struct Frame {
     pub headers: Vec<(String, String)>,
     /// Raw body bytes
     pub body: Vec<u8>,
+    /// Set by the connection as it reads the frame; see `seq()`.
+    pub(crate) seq: Option<u64>,
+    /// The `destination` header of a MESSAGE read by a connection, shared
+    /// through its destination table. Checked against the header before
+    /// use, since `headers` may have been changed since.
+    pub(crate) destination: Option<Destination>,
+}
+
+impl PartialEq for Frame {
+    fn eq(&self, other: &Self) -> bool {
+        self.command == other.command && self.headers == other.headers && self.body == other.body
+    }
+}
+
+impl Eq for Frame {}
+
+impl std::hash::Hash for Frame {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.command.hash(state);
+        self.headers.hash(state);
+        self.body.hash(state);
+    }
 }
 
 impl Frame {
@@ -25,6 +54,8 @@ impl Frame {
             command: command.into(),
             headers: Vec::new(),
             body: Vec::new(),
+            seq: None,
+            destination: None,
         }
     }
 
@@ -74,6 +105,20 @@ impl Frame {
         self.header("receipt", id)
     }
 
+    /// Position of the frame among those a `Connection` received, counting
+    /// from 1 across reconnects. `None` for frames built locally.
+    pub fn seq(&self) -> Option<u64> {
+        self.seq
+    }
+
+    /// The shared name of the `destination` header, if the connection
+    /// interned it and the header still holds it.
+    pub(crate) fn shared_destination(&self) -> Option<&Destination> {
+        self.destination
+            .as_ref()
+            .filter(|destination| self.get_header("destination") == Some(destination.as_str()))
+    }
+
     /// Get the value of a header by name.
     ///
     /// Returns the first header value matching the given key (case-sensitive),
@@ -84,6 +129,256 @@ impl Frame {
             .find(|(k, _)| k == key)
             .map(|(_, v)| v.as_str())
     }
+
+    /// Number of bytes this frame occupies on the wire.
+    ///
+    /// Matches exactly what `StompCodec` writes: the command, escaped
+    /// headers (plus the `content-length` header the encoder adds for
+    /// binary bodies), the blank separator line, the body, and the
+    /// terminating NUL. Useful for enforcing size limits before sending.
+    ///
+    /// Assumes the default `ContentLengthPolicy::Auto`; a connection-wide or
+    /// per-send policy is not taken into account.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use iridium_stomp::Frame;
+    ///
+    /// let frame = Frame::new("SEND")
+    ///     .header("destination", "/queue/a")
+    ///     .set_body(b"hi".to_vec());
+    /// // "SEND\n" + "destination:/queue/a\n" + "\n" + "hi" + "\0"
+    /// assert_eq!(frame.encoded_len(), 5 + 21 + 1 + 2 + 1);
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        crate::codec::encoded_len(self, ContentLengthPolicy::default())
+    }
+
+    /// Return a canonical copy of this frame.
+    ///
+    /// Header names are lowercased, repeated headers keep only their first
+    /// occurrence (the STOMP 1.2 rule for repeated entries), and the
+    /// remaining headers are sorted by name. The command and body are
+    /// unchanged. Two frames that mean the same thing canonicalize to equal
+    /// values, so the result can be compared or hashed for deduplication.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use iridium_stomp::Frame;
+    ///
+    /// let frame = Frame::new("SEND")
+    ///     .header("Destination", "/queue/a")
+    ///     .header("content-type", "text/plain")
+    ///     .header("destination", "/queue/ignored");
+    /// let canonical = frame.canonicalize();
+    /// assert_eq!(
+    ///     canonical.headers,
+    ///     vec![
+    ///         ("content-type".to_string(), "text/plain".to_string()),
+    ///         ("destination".to_string(), "/queue/a".to_string()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn canonicalize(&self) -> Frame {
+        Frame {
+            command: self.command.clone(),
+            headers: self.canonical_headers(),
+            body: self.body.clone(),
+            seq: self.seq,
+            destination: self.destination.clone(),
+        }
+    }
+
+    /// Lowercased, first-wins, name-sorted copy of the headers.
+    fn canonical_headers(&self) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = Vec::with_capacity(self.headers.len());
+        for (k, v) in &self.headers {
+            let name = k.to_ascii_lowercase();
+            if !headers.iter().any(|(seen, _)| *seen == name) {
+                headers.push((name, v.clone()));
+            }
+        }
+        headers.sort_by(|a, b| a.0.cmp(&b.0));
+        headers
+    }
+
+    /// Compare two frames by meaning rather than representation.
+    ///
+    /// Equivalent to comparing `self.canonicalize()` with
+    /// `other.canonicalize()`: header order, header name case, and repeated
+    /// headers after the first are ignored. Useful in fixture-based tests
+    /// where the exact header order is not significant.
+    pub fn semantically_eq(&self, other: &Frame) -> bool {
+        self.command == other.command
+            && self.body == other.body
+            && self.canonical_headers() == other.canonical_headers()
+    }
+
+    /// Produce a printable preview of the body, showing at most `max_len`
+    /// bytes.
+    ///
+    /// Valid UTF-8 bodies are returned as text. Anything else is rendered as
+    /// a hex dump, 16 bytes per line with an ASCII column, so binary
+    /// payloads can be inspected without corrupting a terminal. A trailing
+    /// `...` marks a truncated text body; a truncated hex dump ends with a
+    /// line giving the total size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use iridium_stomp::Frame;
+    ///
+    /// let text = Frame::new("MESSAGE").set_body(b"hello world".to_vec());
+    /// assert_eq!(text.body_preview(5), "hello...");
+    ///
+    /// let binary = Frame::new("MESSAGE").set_body(vec![0xde, 0xad, 0xbe, 0xef]);
+    /// assert_eq!(
+    ///     binary.body_preview(64),
+    ///     "00000000  de ad be ef                                      |....|"
+    /// );
+    /// ```
+    pub fn body_preview(&self, max_len: usize) -> String {
+        match std::str::from_utf8(&self.body) {
+            Ok(text) => {
+                if text.len() <= max_len {
+                    return text.to_string();
+                }
+                let mut end = max_len;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                format!("{}...", &text[..end])
+            }
+            Err(_) => {
+                let shown = &self.body[..self.body.len().min(max_len)];
+                let mut lines: Vec<String> = shown
+                    .chunks(16)
+                    .enumerate()
+                    .map(|(i, chunk)| hex_dump_line(i * 16, chunk))
+                    .collect();
+                if shown.len() < self.body.len() {
+                    lines.push(format!("... ({} bytes total)", self.body.len()));
+                }
+                lines.join("\n")
+            }
+        }
+    }
+
+    /// The media type of the body: the `content-type` header if there is
+    /// one, otherwise a guess from the body, or `None` for an empty body
+    /// without a header.
+    ///
+    /// Bodies starting with the magic number of a common binary format are
+    /// reported as `image/png`, `image/jpeg`, `image/gif`,
+    /// `application/pdf`, `application/gzip` or `application/zip`. UTF-8
+    /// bodies enclosed in `{}` or `[]` are `application/json`, those
+    /// enclosed in `<>` are `application/xml`, and other UTF-8 text without
+    /// control characters is `text/plain`. Anything else is
+    /// `application/octet-stream`. The body is not parsed, so a guess of
+    /// JSON or XML does not mean the body is well-formed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use iridium_stomp::Frame;
+    ///
+    /// let json = Frame::new("MESSAGE").set_body(br#"{"id": 7}"#.to_vec());
+    /// assert_eq!(json.sniff_content_type(), Some("application/json"));
+    ///
+    /// let declared = json.clone().header("content-type", "application/vnd.order+json");
+    /// assert_eq!(declared.sniff_content_type(), Some("application/vnd.order+json"));
+    /// ```
+    pub fn sniff_content_type(&self) -> Option<&str> {
+        if let Some(content_type) = self.get_header("content-type") {
+            return Some(content_type);
+        }
+        (!self.body.is_empty()).then(|| sniff_body(&self.body))
+    }
+
+    /// Encode the body as standard base64 (RFC 4648, with padding).
+    ///
+    /// Useful for logging or exporting binary payloads in a text-safe form.
+    pub fn body_base64(&self) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut out = String::with_capacity(self.body.len().div_ceil(3) * 4);
+        for chunk in self.body.chunks(3) {
+            let b = [
+                chunk[0],
+                chunk.get(1).copied().unwrap_or(0),
+                chunk.get(2).copied().unwrap_or(0),
+            ];
+            let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Magic numbers of common binary formats and their media types.
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+];
+
+/// Guess the media type of a non-empty body.
+fn sniff_body(body: &[u8]) -> &'static str {
+    if let Some((_, media_type)) = MAGIC_NUMBERS
+        .iter()
+        .find(|(magic, _)| body.starts_with(magic))
+    {
+        return media_type;
+    }
+    let Ok(text) = std::str::from_utf8(body) else {
+        return "application/octet-stream";
+    };
+    let trimmed = text.trim_start_matches('\u{feff}').trim();
+    let (first, last) = (trimmed.chars().next(), trimmed.chars().last());
+    if matches!(
+        (first, last),
+        (Some('{'), Some('}')) | (Some('['), Some(']'))
+    ) {
+        "application/json"
+    } else if first == Some('<') && last == Some('>') {
+        "application/xml"
+    } else if text
+        .chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+    {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Format one hex dump line: offset, up to 16 hex bytes, and an ASCII column.
+fn hex_dump_line(offset: usize, chunk: &[u8]) -> String {
+    let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = chunk
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{:08x}  {:<47}  |{}|", offset, hex.join(" "), ascii)
 }
 
 impl fmt::Display for Frame {
@@ -95,3 +390,154 @@ impl fmt::Display for Frame {
         writeln!(f, "Body ({} bytes)", self.body.len())
     }
 }
+
+/// Build a [`Frame`] tersely, for tests and small tools.
+///
+/// Takes the command as a bare word, then `name: value` headers, then an
+/// optional `; body: expr`. Well-known header names are written as
+/// identifiers with `_` for `-` and checked at compile time; any other
+/// header is written as a string literal. Values may be anything that
+/// implements `ToString`, and the body anything `Frame::set_body` accepts.
+///
+/// ```
+/// use iridium_stomp::{Frame, frame};
+///
+/// let payload = r#"{"id":42}"#;
+/// let frame = frame!(SEND,
+///     destination: "/queue/x",
+///     content_type: "application/json",
+///     priority: 4,
+///     "x-tenant": "acme";
+///     body: payload
+/// );
+/// assert_eq!(
+///     frame,
+///     Frame::new("SEND")
+///         .header("destination", "/queue/x")
+///         .header("content-type", "application/json")
+///         .header("priority", "4")
+///         .header("x-tenant", "acme")
+///         .set_body(payload)
+/// );
+/// ```
+///
+/// A misspelled well-known name does not compile:
+///
+/// ```compile_fail
+/// let frame = iridium_stomp::frame!(SEND, destinaton: "/queue/x");
+/// ```
+#[macro_export]
+macro_rules! frame {
+    ($command:ident $(, $name:tt : $value:expr)* $(,)? $(; body: $body:expr)? $(;)?) => {{
+        let frame = $crate::Frame::new(stringify!($command));
+        $(
+            let frame = frame.header(
+                $crate::__frame_header!($name),
+                ::std::string::ToString::to_string(&$value),
+            );
+        )*
+        $(
+            let frame = frame.set_body($body);
+        )?
+        frame
+    }};
+}
+
+/// Header name for an identifier or string literal in [`frame!`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __frame_header {
+    (accept_version) => {
+        "accept-version"
+    };
+    (ack) => {
+        "ack"
+    };
+    (content_length) => {
+        "content-length"
+    };
+    (content_type) => {
+        "content-type"
+    };
+    (destination) => {
+        "destination"
+    };
+    (expires) => {
+        "expires"
+    };
+    (heart_beat) => {
+        "heart-beat"
+    };
+    (host) => {
+        "host"
+    };
+    (id) => {
+        "id"
+    };
+    (login) => {
+        "login"
+    };
+    (message) => {
+        "message"
+    };
+    (message_id) => {
+        "message-id"
+    };
+    (passcode) => {
+        "passcode"
+    };
+    (persistent) => {
+        "persistent"
+    };
+    (priority) => {
+        "priority"
+    };
+    (receipt) => {
+        "receipt"
+    };
+    (receipt_id) => {
+        "receipt-id"
+    };
+    (reply_to) => {
+        "reply-to"
+    };
+    (server) => {
+        "server"
+    };
+    (session) => {
+        "session"
+    };
+    (subscription) => {
+        "subscription"
+    };
+    (transaction) => {
+        "transaction"
+    };
+    (version) => {
+        "version"
+    };
+    ($name:literal) => {
+        $name
+    };
+    ($name:ident) => {
+        compile_error!(concat!(
+            "unknown STOMP header `",
+            stringify!($name),
+            "`; write other headers as string literals"
+        ))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seq_is_ignored_by_equality() {
+        let mut received = Frame::new("MESSAGE").header("message-id", "m1");
+        received.seq = Some(7);
+        assert_eq!(received, Frame::new("MESSAGE").header("message-id", "m1"));
+        assert_eq!(received.seq(), Some(7));
+        assert_eq!(Frame::new("MESSAGE").seq(), None);
+    }
+}
@@ -0,0 +1,73 @@
+//! Limit on SEND frames in flight, set with
+//! `ConnectOptions::max_inflight_sends()`.
+//!
+//! Every SEND takes a permit before it is queued. A SEND that requests a
+//! receipt keeps its permit in the pending receipt until the RECEIPT
+//! arrives (or the receipt fails, times out, or expires); any other SEND
+//! gives it back once the writer has flushed it.
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::codec::StompItem;
+use crate::connection::ConnError;
+
+/// Semaphore-based limiter shared by a connection and its writer.
+#[derive(Debug, Clone)]
+pub(crate) struct InflightLimiter {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl InflightLimiter {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// Wait for a free permit. Fails once the connection is closed.
+    pub(crate) async fn acquire(&self) -> Result<OwnedSemaphorePermit, ConnError> {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| ConnError::ChannelClosed)
+    }
+
+    /// Give back the permits of flushed SEND frames that did not request a
+    /// receipt. Their permits were forgotten when they were queued.
+    pub(crate) fn release_flushed(&self, count: usize) {
+        if count > 0 {
+            self.semaphore.add_permits(count);
+        }
+    }
+
+    /// Number of SEND frames holding a permit.
+    pub(crate) fn in_flight(&self) -> usize {
+        self.max - self.semaphore.available_permits().min(self.max)
+    }
+
+    /// Wait until no SEND holds a permit.
+    pub(crate) async fn drain(&self) -> Result<(), ConnError> {
+        let all = u32::try_from(self.max).unwrap_or(u32::MAX);
+        self.semaphore
+            .acquire_many(all)
+            .await
+            .map(drop)
+            .map_err(|_| ConnError::ChannelClosed)
+    }
+
+    /// Wake every waiter with an error; called when the connection closes.
+    pub(crate) fn close(&self) {
+        self.semaphore.close();
+    }
+}
+
+/// Whether `item` is a SEND whose permit the writer gives back on flush.
+pub(crate) fn releases_on_flush(item: &StompItem) -> bool {
+    item.frame()
+        .is_some_and(|f| f.command == "SEND" && f.get_header("receipt").is_none())
+}
@@ -3,27 +3,153 @@
 //! Additional user-facing guides from the `docs/` directory are exposed as
 //! rustdoc modules so they appear on docs.rs. See the `subscriptions_docs`
 //! module for information about durable subscriptions and `SubscriptionOptions`.
+mod address;
+mod breaker;
+pub mod bridge;
+pub mod broker;
+pub mod browse;
+pub mod chaos;
+mod chunked;
 pub mod codec;
 pub mod connection;
+pub mod destination;
+pub mod diagnostics;
+pub mod events;
 pub mod frame;
+mod inflight;
+pub mod message;
+pub mod metrics;
+pub mod monitor;
+pub mod outbox;
 pub mod parser;
+pub mod producer;
+mod protocol;
+pub mod recorder;
+pub mod retry;
+pub mod router;
+pub mod sampling;
+pub mod send;
+pub mod sequence;
 pub mod subscription;
+pub mod tap;
+mod task;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "testing")]
+pub mod testvectors;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod trace;
+mod transport;
+
+/// Re-export `BrokerProfile` for broker-specific helpers such as
+/// `Connection::subscribe_shared()`.
+pub use broker::BrokerProfile;
 
 /// Re-export the codec types (`StompCodec`, `StompItem`) for easy use with
 /// `tokio_util::codec::Framed` and tests.
-pub use codec::{StompCodec, StompItem};
+pub use codec::{
+    ContentLengthPolicy, DecodeErrorStats, DecodeStats, EscapePolicy, HeaderPolicy, StompCodec,
+    StompItem, TerminatorStats,
+};
 
-/// Re-export the high-level `Connection`, `AckMode`, `ConnectOptions`, `ConnError`,
-/// `Heartbeat`, `ReceivedFrame`, `ServerError`, and the heartbeat helper functions.
+/// Re-export the high-level `Connection`, `ConnectionBuilder`, `AckMode`,
+/// `ConnectOptions`, `ConfigError`, `ConnError`, `ExecuteOptions`,
+/// `Heartbeat`, `NackOptions`, `OutstandingReceipts`, `PendingAcks`,
+/// `ReceiptError`, `ReceivedFrame`, `ServerError`, `ServerInfo`,
+/// `WeakConnection`, and the heartbeat helper functions.
 pub use connection::{
-    AckMode, ConnError, ConnectOptions, Connection, Heartbeat, ReceivedFrame, ServerError,
-    negotiate_heartbeats, parse_heartbeat_header,
+    AckMode, ConfigError, ConnError, ConnectOptions, Connection, ConnectionBuilder, ExecuteOptions,
+    Heartbeat, NackOptions, OutstandingReceipts, PendingAcks, ReceiptError, ReceivedFrame,
+    ServerError, ServerInfo, WeakConnection, negotiate_heartbeats, parse_heartbeat_header,
 };
 
+/// Re-export `ConnectionEvent` for use with `ConnectOptions::with_event_notify()`.
+pub use events::ConnectionEvent;
+
 /// Re-export the `Frame` type used to construct/send and receive frames.
 pub use frame::Frame;
-pub use subscription::Subscription;
-pub use subscription::SubscriptionOptions;
+
+/// Re-export the shared destination name type.
+pub use destination::Destination;
+
+/// Re-export the statistics returned by `Connection::message_stats()`.
+pub use metrics::{MessageStats, SizeHistogram};
+
+/// Re-export `Message` for typed access to received MESSAGE frames.
+pub use message::{Expiration, Message};
+pub use subscription::{
+    PauseHandle, RecvTimeoutError, Subscription, SubscriptionResults, TempSubscription,
+    TryRecvError,
+};
+pub use subscription::{PendingOverflow, SubscriptionBuilder, SubscriptionOptions};
+
+/// Re-export the queue browsing types for `Connection::browse()`.
+pub use browse::{Browse, BrowseEnd, BrowseOptions};
+
+/// Re-export the liveness types for `Connection::monitor()`.
+pub use monitor::{Health, Monitor, MonitorOptions, MonitorStatus};
+
+/// Re-export the store-and-forward types for `ConnectOptions::outbox()`.
+pub use outbox::{Outbox, OutboxEntry, OutboxStats};
+
+/// Re-export the relay types for moving messages between connections.
+pub use bridge::{Bridge, BridgeMetrics};
+
+/// Re-export the level control for logging of errors the client ignores.
+pub use diagnostics::{ErrorLogLevel, error_log_level, set_error_log_level};
+
+/// Re-export `Producer`, returned by `Connection::producer()`.
+pub use producer::Producer;
+
+/// Re-export the session recording types for `ConnectOptions::recorder()`.
+pub use recorder::{
+    RecordedFrame, ReportFormat, SessionRecorder, SessionReport, SessionStats, TextFormat,
+};
+
+/// Re-export the JSON session report format (requires the `json` feature).
+#[cfg(feature = "json")]
+pub use recorder::JsonFormat;
+
+/// Re-export the message mirror returned by `Connection::tap()`.
+pub use tap::Tap;
+
+/// Re-export `RetryPolicy` for `Connection::send_with_retry()`.
+pub use retry::RetryPolicy;
+
+/// Re-export the simulated broker misbehavior configured via
+/// `ConnectOptions::chaos()`.
+pub use chaos::Chaos;
+
+/// Re-export the options for `Connection::send_with_options()`.
+pub use send::{IdStyle, SendOptions, SendResult};
+
+/// Re-export `Router` for `Connection::serve()`.
+pub use router::{FailurePolicy, Router};
+
+/// Re-export the message sampling hook configured via
+/// `ConnectOptions::on_message_sample()`.
+pub use sampling::{MessageSampler, SampleCallback};
+
+/// Re-export the publisher sequence types configured via
+/// `ConnectOptions::publisher_sequence()`.
+pub use sequence::{
+    DuplicateFilter, FileSequenceStore, MemorySequenceStore, PublisherSequence, SequenceStore,
+};
+
+/// Re-export the reconnect limits for `ConnectOptions::circuit_breaker()`.
+pub use breaker::CircuitBreaker;
+
+/// Re-export the socket settings for `ConnectOptions::tcp()`.
+pub use transport::TcpOptions;
+
+/// Re-export the TLS settings and session details (requires the `tls`
+/// feature).
+#[cfg(feature = "tls")]
+pub use tls::{PeerCertificate, TlsInfo, TlsOptions};
+
+/// Re-export the W3C trace context types used for tracing propagation.
+pub use trace::{TraceContext, TraceContextProvider};
 
 // Expose the repository `docs/subscriptions.md` as a public rustdoc page so it
 // appears alongside the API docs on docs.rs / rustdoc. The module is empty and
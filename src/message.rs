@@ -0,0 +1,305 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::broker::BrokerProfile;
+use crate::destination::Destination;
+use crate::frame::Frame;
+use crate::sequence::{PUBLISHER_ID_HEADER, PUBLISHER_SEQ_HEADER};
+use crate::trace::TraceContext;
+
+/// A MESSAGE frame received from a subscription, with typed accessors.
+///
+/// Subscriptions yield raw `Frame`s; wrap one in a `Message` (via
+/// `Message::from_frame()` or `From<Frame>`) for convenient access to the
+/// standard MESSAGE headers and to cross-cutting metadata such as the
+/// distributed trace context.
+///
+/// # Example
+///
+/// ```ignore
+/// use futures::StreamExt;
+/// use iridium_stomp::Message;
+///
+/// while let Some(frame) = sub.next().await {
+///     let msg = Message::from(frame);
+///     if let Some(ctx) = msg.trace_context() {
+///         println!("continuing trace {}", ctx.trace_id());
+///     }
+/// }
+/// ```
+///
+/// Two messages are equal when their frames are; a processing deadline
+/// or subscription destination does not take part in the comparison.
+#[derive(Debug, Clone)]
+pub struct Message {
+    frame: Frame,
+    /// The `destination` header, shared with the connection's other
+    /// copies of the name when the frame came from one.
+    destination: Option<Destination>,
+    /// Set by a `Router` with a processing deadline.
+    deadline: Option<Deadline>,
+    /// Set when the message is taken from a subscription by a `Router`
+    /// or `Bridge`.
+    subscribed_to: Option<Destination>,
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.frame == other.frame
+    }
+}
+
+impl Eq for Message {}
+
+/// The point at which a `Router` gives up on a handler, shared between the
+/// router and the `Message` passed to the handler so the handler can push
+/// it back.
+#[derive(Debug, Clone)]
+pub(crate) struct Deadline(Arc<Mutex<Instant>>);
+
+impl Deadline {
+    pub(crate) fn new(at: Instant) -> Self {
+        Self(Arc::new(Mutex::new(at)))
+    }
+
+    pub(crate) fn get(&self) -> Instant {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move the deadline to `by` from now, unless it is already later.
+    fn extend(&self, by: Duration) -> Instant {
+        let mut at = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *at = (*at).max(Instant::now() + by);
+        *at
+    }
+}
+
+impl Message {
+    /// Wrap a received frame.
+    pub fn from_frame(frame: Frame) -> Self {
+        let destination = frame
+            .shared_destination()
+            .cloned()
+            .or_else(|| frame.get_header("destination").map(Destination::new));
+        Self {
+            frame,
+            destination,
+            deadline: None,
+            subscribed_to: None,
+        }
+    }
+
+    /// Record the destination of the subscription the message arrived on.
+    pub(crate) fn with_subscription_destination(mut self, destination: Destination) -> Self {
+        self.subscribed_to = Some(destination);
+        self
+    }
+
+    /// Attach the processing deadline of a `Router` handler.
+    pub(crate) fn with_deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// The underlying frame.
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Unwrap into the underlying frame.
+    pub fn into_frame(self) -> Frame {
+        self.frame
+    }
+
+    /// The message body.
+    pub fn body(&self) -> &[u8] {
+        &self.frame.body
+    }
+
+    /// Get a header value by name (first match, case-sensitive).
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.frame.get_header(key)
+    }
+
+    /// Position of the message among the frames its connection received,
+    /// or `None` if it did not come from a `Connection`. See `Frame::seq()`.
+    ///
+    /// Numbers increase strictly in the order frames arrived on the wire,
+    /// but a subscription sees gaps wherever frames for other
+    /// subscriptions (or receipts and errors) came in between. Handles from
+    /// `Subscription::into_shared()` can use it to restore arrival order.
+    pub fn seq(&self) -> Option<u64> {
+        self.frame.seq()
+    }
+
+    /// The `destination` header. Messages from one connection share the
+    /// name with its subscriptions and each other.
+    pub fn destination(&self) -> Option<&Destination> {
+        self.destination.as_ref()
+    }
+
+    /// The destination of the subscription the message arrived on, when
+    /// it was handed over by a `Router` or `Bridge`. Unlike the
+    /// `destination` header this is the subscribed name, so it tells
+    /// wildcard subscriptions apart.
+    pub fn subscribed_to(&self) -> Option<&Destination> {
+        self.subscribed_to.as_ref()
+    }
+
+    /// The `message-id` header, used with `ack()` and `nack()`.
+    pub fn message_id(&self) -> Option<&str> {
+        self.header("message-id")
+    }
+
+    /// The `subscription` header identifying the local subscription.
+    pub fn subscription(&self) -> Option<&str> {
+        self.header("subscription")
+    }
+
+    /// When the router stops waiting for the handler of this message, if
+    /// it runs under `Router::processing_deadline()`.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.as_ref().map(Deadline::get)
+    }
+
+    /// Give the handler of this message at least `by` more time, counted
+    /// from now, and return the new deadline.
+    ///
+    /// For handlers that make progress but need longer than the router's
+    /// `processing_deadline()`, for example one batch at a time. The
+    /// message stays pending (neither ACKed nor NACKed) meanwhile.
+    /// Returns `None`, and does nothing, when the message has no deadline.
+    /// Clones of the message share the deadline.
+    pub fn extend(&self, by: Duration) -> Option<Instant> {
+        self.deadline.as_ref().map(|deadline| deadline.extend(by))
+    }
+
+    /// The W3C trace context propagated by the publisher, if the message
+    /// carries a valid `traceparent` header.
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        TraceContext::from_frame(&self.frame)
+    }
+
+    /// The `publisher-id` header stamped by a publisher sequence.
+    pub fn publisher_id(&self) -> Option<&str> {
+        self.header(PUBLISHER_ID_HEADER)
+    }
+
+    /// The `publisher-seq` header stamped by a publisher sequence, if present
+    /// and numeric.
+    pub fn publisher_seq(&self) -> Option<u64> {
+        self.header(PUBLISHER_SEQ_HEADER)?.parse().ok()
+    }
+
+    /// Whether the broker has delivered this message before.
+    ///
+    /// - **RabbitMQ** and **Artemis**: the `redelivered` header.
+    /// - **ActiveMQ**: a non-zero `redeliveryCounter` header.
+    /// - **Generic**: whichever of these is present.
+    ///
+    /// Returns `None` when the broker did not say.
+    pub fn redelivered(&self, broker: BrokerProfile) -> Option<bool> {
+        let flag = || parse_bool(self.header("redelivered")?);
+        let counter = || {
+            self.header("redeliveryCounter")?
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .map(|n| n > 0)
+        };
+        match broker {
+            BrokerProfile::RabbitMq | BrokerProfile::Artemis => flag(),
+            BrokerProfile::ActiveMq => counter(),
+            _ => flag().or_else(counter),
+        }
+    }
+
+    /// When the message expires.
+    ///
+    /// - **RabbitMQ**: the `expiration` header, a time-to-live in
+    ///   milliseconds ([`Expiration::After`]).
+    /// - **ActiveMQ** and **Artemis**: the `expires` header, milliseconds
+    ///   since the Unix epoch ([`Expiration::At`]); `0` means never.
+    /// - **Generic**: whichever of these is present.
+    ///
+    /// Returns `None` when the message does not expire.
+    pub fn expiration(&self, broker: BrokerProfile) -> Option<Expiration> {
+        let ttl = || {
+            let ms: u64 = self.header("expiration")?.trim().parse().ok()?;
+            Some(Expiration::After(Duration::from_millis(ms)))
+        };
+        let expires = || {
+            let ms: u64 = self.header("expires")?.trim().parse().ok()?;
+            (ms > 0).then(|| Expiration::At(UNIX_EPOCH + Duration::from_millis(ms)))
+        };
+        match broker {
+            BrokerProfile::RabbitMq => ttl(),
+            BrokerProfile::ActiveMq | BrokerProfile::Artemis => expires(),
+            _ => expires().or_else(ttl),
+        }
+    }
+
+    /// Whether the message was published as persistent, from the
+    /// `persistent` header. All supported brokers use the same spelling, so
+    /// this takes no `BrokerProfile`.
+    pub fn persistent(&self) -> Option<bool> {
+        parse_bool(self.header("persistent")?)
+    }
+
+    /// The message priority, from the `priority` header all supported
+    /// brokers use (0-9 for ActiveMQ and Artemis, 0-255 for RabbitMQ).
+    pub fn priority(&self) -> Option<u8> {
+        self.header("priority")?.trim().parse().ok()
+    }
+
+    /// Where a dead-lettered message was originally sent.
+    ///
+    /// - **RabbitMQ**: the `x-first-death-queue` header, the name of the
+    ///   queue the message was first dead-lettered from.
+    /// - **ActiveMQ**: the `original-destination` header.
+    /// - **Artemis**: the `_AMQ_ORIG_ADDRESS` header.
+    /// - **Generic**: whichever of these is present.
+    ///
+    /// Returns `None` for messages that were not dead-lettered.
+    pub fn original_destination(&self, broker: BrokerProfile) -> Option<&str> {
+        let names: &[&str] = match broker {
+            BrokerProfile::RabbitMq => &["x-first-death-queue"],
+            BrokerProfile::ActiveMq => &["original-destination"],
+            BrokerProfile::Artemis => &["_AMQ_ORIG_ADDRESS"],
+            _ => &[
+                "original-destination",
+                "_AMQ_ORIG_ADDRESS",
+                "x-first-death-queue",
+            ],
+        };
+        names.iter().find_map(|name| self.header(name))
+    }
+}
+
+impl From<Frame> for Message {
+    fn from(frame: Frame) -> Self {
+        Self::from_frame(frame)
+    }
+}
+
+/// When a message expires, as reported by [`Message::expiration()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiration {
+    /// At a point in time.
+    At(SystemTime),
+    /// After a time-to-live, counted by the broker from when the message
+    /// was enqueued.
+    After(Duration),
+}
+
+/// Parse a `true`/`false` header value, ignoring case.
+fn parse_bool(value: &str) -> Option<bool> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,208 @@
+//! Statistics about the messages a connection receives: how large their
+//! bodies are and what content types they carry.
+//!
+//! See `Connection::message_stats()`.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::frame::Frame;
+
+/// Upper bounds, in bytes, of the buckets of a [`SizeHistogram`]. Bodies
+/// larger than the last bound are counted in a final, unbounded bucket.
+pub const SIZE_BUCKETS: [usize; 8] = [
+    64,
+    256,
+    1024,
+    4 * 1024,
+    16 * 1024,
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+];
+
+/// Most distinct content types counted by name; further ones are counted
+/// together in `MessageStats::other_content_types`, so a broker sending
+/// many cannot grow the statistics without bound.
+const MAX_CONTENT_TYPES: usize = 32;
+
+/// Message body sizes, counted in the buckets of [`SIZE_BUCKETS`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeHistogram {
+    counts: [u64; SIZE_BUCKETS.len() + 1],
+}
+
+impl SizeHistogram {
+    /// Count a body of `len` bytes.
+    fn record(&mut self, len: usize) {
+        let bucket = SIZE_BUCKETS
+            .iter()
+            .position(|&bound| len <= bound)
+            .unwrap_or(SIZE_BUCKETS.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// Each bucket's upper bound in bytes (`None` for the last, unbounded
+    /// one) with the number of bodies in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<usize>, u64)> + '_ {
+        SIZE_BUCKETS
+            .iter()
+            .map(|&bound| Some(bound))
+            .chain([None])
+            .zip(self.counts.iter().copied())
+    }
+
+    /// Number of bodies counted.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Upper bound of the bucket holding the `q` quantile (`0.0` to `1.0`)
+    /// of body sizes, e.g. `quantile(0.95)`. `Some(None)` when it falls in
+    /// the unbounded bucket; `None` when nothing has been counted.
+    pub fn quantile(&self, q: f64) -> Option<Option<usize>> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets().find_map(|(bound, count)| {
+            seen += count;
+            (seen >= rank).then_some(bound)
+        })
+    }
+}
+
+/// Body sizes and content types of the MESSAGE frames received on a
+/// connection, over every session. Returned by
+/// `Connection::message_stats()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageStats {
+    /// MESSAGE frames received.
+    pub messages: u64,
+    /// Their body bytes in total.
+    pub body_bytes: u64,
+    /// The largest body received.
+    pub largest_body: usize,
+    /// How the body sizes are distributed.
+    pub body_sizes: SizeHistogram,
+    /// Messages per media type: the `content-type` header in lowercase,
+    /// without parameters such as `charset`.
+    pub content_types: BTreeMap<String, u64>,
+    /// Messages without a `content-type` header.
+    pub untyped: u64,
+    /// Messages whose content type arrived after 32 others had been seen.
+    pub other_content_types: u64,
+}
+
+impl MessageStats {
+    /// Average body size, or `None` before the first message.
+    pub fn average_body(&self) -> Option<f64> {
+        (self.messages > 0).then(|| self.body_bytes as f64 / self.messages as f64)
+    }
+
+    fn record(&mut self, frame: &Frame) {
+        let len = frame.body.len();
+        self.messages += 1;
+        self.body_bytes += len as u64;
+        self.largest_body = self.largest_body.max(len);
+        self.body_sizes.record(len);
+        let Some(content_type) = frame.get_header("content-type") else {
+            self.untyped += 1;
+            return;
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if let Some(count) = self.content_types.get_mut(&media_type) {
+            *count += 1;
+        } else if self.content_types.len() < MAX_CONTENT_TYPES {
+            self.content_types.insert(media_type, 1);
+        } else {
+            self.other_content_types += 1;
+        }
+    }
+}
+
+/// Collects `MessageStats` in the connection's dispatch loop.
+#[derive(Debug, Default)]
+pub(crate) struct MessageCounters {
+    stats: Mutex<MessageStats>,
+}
+
+impl MessageCounters {
+    pub(crate) fn record(&self, frame: &Frame) {
+        self.stats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(frame);
+    }
+
+    pub(crate) fn snapshot(&self) -> MessageStats {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(len: usize, content_type: Option<&str>) -> Frame {
+        let frame = Frame::new("MESSAGE").set_body(vec![b'x'; len]);
+        match content_type {
+            Some(content_type) => frame.header("content-type", content_type),
+            None => frame,
+        }
+    }
+
+    #[test]
+    fn sizes_land_in_their_buckets() {
+        let counters = MessageCounters::default();
+        for len in [0, 64, 65, 1024, 2_000_000] {
+            counters.record(&message(len, None));
+        }
+        let stats = counters.snapshot();
+        assert_eq!(stats.messages, 5);
+        assert_eq!(stats.body_bytes, 2_001_153);
+        assert_eq!(stats.largest_body, 2_000_000);
+        let buckets: Vec<_> = stats.body_sizes.buckets().collect();
+        assert_eq!(buckets[0], (Some(64), 2));
+        assert_eq!(buckets[1], (Some(256), 1));
+        assert_eq!(buckets[2], (Some(1024), 1));
+        assert_eq!(buckets[8], (None, 1));
+        assert_eq!(stats.body_sizes.count(), 5);
+    }
+
+    #[test]
+    fn quantiles_report_bucket_bounds() {
+        let mut histogram = SizeHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for _ in 0..9 {
+            histogram.record(100);
+        }
+        histogram.record(5_000_000);
+        assert_eq!(histogram.quantile(0.5), Some(Some(256)));
+        assert_eq!(histogram.quantile(0.9), Some(Some(256)));
+        assert_eq!(histogram.quantile(0.95), Some(None));
+    }
+
+    #[test]
+    fn content_types_are_normalized_and_capped() {
+        let counters = MessageCounters::default();
+        counters.record(&message(1, Some("application/json")));
+        counters.record(&message(1, Some("Application/JSON; charset=utf-8")));
+        counters.record(&message(1, None));
+        for n in 0..40 {
+            counters.record(&message(1, Some(&format!("application/x-{}", n))));
+        }
+        let stats = counters.snapshot();
+        assert_eq!(stats.content_types["application/json"], 2);
+        assert_eq!(stats.untyped, 1);
+        assert_eq!(stats.content_types.len(), MAX_CONTENT_TYPES);
+        assert_eq!(stats.other_content_types, 9);
+    }
+}
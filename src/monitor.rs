@@ -0,0 +1,327 @@
+//! Heartbeat-only connections for watching broker liveness.
+//!
+//! See `Connection::monitor()`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use crate::connection::{
+    ConfigError, ConnError, ConnectOptions, Connection, Heartbeat, MAX_HEARTBEAT_GRACE, ServerInfo,
+    WeakConnection, negotiate_heartbeats, parse_heartbeat_header,
+};
+use crate::diagnostics;
+use crate::events::{self, ConnectionEvent};
+
+/// Silence, in negotiated heartbeat intervals, after which a broker is
+/// reported as `Health::Late`.
+const DEFAULT_LATE_AFTER: f64 = 1.5;
+
+/// Health changes queued for `Monitor::changed()` before older ones are
+/// dropped.
+const CHANGES_CAPACITY: usize = 16;
+
+/// Options for `Connection::monitor()`.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorOptions {
+    /// Login and passcode for the CONNECT frame. Connects without
+    /// credentials if `None`.
+    pub credentials: Option<(String, String)>,
+
+    /// Heart-beat intervals offered to the broker (10 seconds each way by
+    /// default). A broker that sends no heartbeats is never reported late.
+    pub heartbeat: Heartbeat,
+
+    /// How many negotiated heartbeat intervals the broker may stay silent
+    /// before it is reported as `Health::Late`. 1.5 if `None`; values
+    /// outside `1.0..=100.0` are rejected with `ConfigError::InvalidLateAfter`.
+    /// The connection itself gives up after `heartbeat_grace_multiplier`
+    /// intervals and reconnects, which is reported as `Health::Down`.
+    pub late_after: Option<f64>,
+
+    /// Options for the underlying connection: TLS, virtual host, timeouts
+    /// and so on. Listeners registered with `with_heartbeat_notify()` and
+    /// `with_event_notify()` still receive their notifications.
+    pub connect: ConnectOptions,
+}
+
+/// Liveness of a monitored broker, as reported by `Monitor::status()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// Connected, and the broker's heartbeats arrive on time.
+    Up,
+    /// Connected, but the broker has been silent for longer than
+    /// `MonitorOptions::late_after` heartbeat intervals.
+    Late,
+    /// The session ended and the connection is trying to re-establish it.
+    /// Reported when the first reconnect attempt starts; during the backoff
+    /// before it the broker shows as `Late`.
+    Down,
+}
+
+impl Health {
+    /// Lowercase name for logs and dashboards: `up`, `late` or `down`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Health::Up => "up",
+            Health::Late => "late",
+            Health::Down => "down",
+        }
+    }
+}
+
+/// A snapshot of a monitored broker.
+#[derive(Debug, Clone)]
+pub struct MonitorStatus {
+    /// Current liveness.
+    pub health: Health,
+    /// How long the last successful connect took, from opening the socket
+    /// to CONNECTED. For the first session this includes any retries made
+    /// while the broker was unreachable.
+    pub handshake: Duration,
+    /// How often the broker agreed to send heartbeats, or `None` if it
+    /// sends none.
+    pub heartbeat_interval: Option<Duration>,
+    /// Time since the broker was last heard from (a heartbeat or the
+    /// handshake).
+    pub silent_for: Duration,
+    /// Heartbeats received from the broker, across reconnects.
+    pub heartbeats: u64,
+    /// Times the session was re-established.
+    pub reconnects: u32,
+    /// What the broker reported in its last CONNECTED frame.
+    pub server: ServerInfo,
+}
+
+/// A connection that only exchanges heartbeats, returned by
+/// `Connection::monitor()`.
+///
+/// It never subscribes or sends data, so it can watch many brokers cheaply.
+/// Read the current state with [`status`](Self::status), or wait for the
+/// next change of health or reconnect with [`changed`](Self::changed).
+pub struct Monitor {
+    conn: Connection,
+    state: Arc<Mutex<State>>,
+    changes: mpsc::Receiver<MonitorStatus>,
+}
+
+/// What the watcher task tracks, shared with the `Monitor`.
+#[derive(Debug)]
+struct State {
+    health: Health,
+    handshake: Duration,
+    heartbeat_interval: Option<Duration>,
+    last_heard: Instant,
+    heartbeats: u64,
+    reconnects: u32,
+    server: ServerInfo,
+}
+
+impl State {
+    fn snapshot(&self) -> MonitorStatus {
+        MonitorStatus {
+            health: self.health,
+            handshake: self.handshake,
+            heartbeat_interval: self.heartbeat_interval,
+            silent_for: self.last_heard.elapsed(),
+            heartbeats: self.heartbeats,
+            reconnects: self.reconnects,
+            server: self.server.clone(),
+        }
+    }
+}
+
+impl Monitor {
+    /// Connect to `addr` and start watching it. See `Connection::monitor()`.
+    pub(crate) async fn start(addr: &str, options: MonitorOptions) -> Result<Self, ConnError> {
+        let MonitorOptions {
+            credentials,
+            heartbeat,
+            late_after,
+            mut connect,
+        } = options;
+        let late_after = late_after.unwrap_or(DEFAULT_LATE_AFTER);
+        if !(1.0..=MAX_HEARTBEAT_GRACE).contains(&late_after) {
+            return Err(ConfigError::InvalidLateAfter.into());
+        }
+        let forward_heartbeats = connect.heartbeat_tx.take();
+        let forward_events = connect.event_tx.take();
+        let (heartbeat_tx, heartbeat_rx) = mpsc::channel(16);
+        let (event_tx, event_rx) = mpsc::channel(16);
+        connect = connect
+            .with_heartbeat_notify(heartbeat_tx)
+            .with_event_notify(event_tx);
+        let (login, passcode) = match credentials {
+            Some(credentials) => credentials,
+            None => {
+                connect = connect.omit_credentials();
+                (String::new(), String::new())
+            }
+        };
+
+        let started = Instant::now();
+        let conn = Connection::connect_with_options(
+            addr,
+            &login,
+            &passcode,
+            &heartbeat.to_string(),
+            connect,
+        )
+        .await?;
+        let server = conn.server_info().await;
+        let state = Arc::new(Mutex::new(State {
+            health: Health::Up,
+            handshake: started.elapsed(),
+            heartbeat_interval: broker_interval(heartbeat, &server),
+            last_heard: Instant::now(),
+            heartbeats: 0,
+            reconnects: 0,
+            server,
+        }));
+
+        let (changes_tx, changes) = mpsc::channel(CHANGES_CAPACITY);
+        let watcher = Watcher {
+            conn: conn.downgrade(),
+            heartbeat,
+            late_after,
+            state: state.clone(),
+            changes: changes_tx,
+            forward_heartbeats,
+            forward_events,
+        };
+        let labels = watcher.conn.task_labels().clone();
+        crate::task::spawn("monitor", &labels, watcher.run(heartbeat_rx, event_rx));
+
+        Ok(Self {
+            conn,
+            state,
+            changes,
+        })
+    }
+
+    /// The broker's current state.
+    pub fn status(&self) -> MonitorStatus {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .snapshot()
+    }
+
+    /// Wait until the health changes or the session is re-established, and
+    /// return the state at that moment. Returns `None` once the monitor's
+    /// connection has closed. Up to 16 changes wait to be collected; later
+    /// ones are dropped until there is room, but `status()` is always
+    /// current.
+    pub async fn changed(&mut self) -> Option<MonitorStatus> {
+        self.changes.recv().await
+    }
+
+    /// Disconnect from the broker.
+    pub async fn close(self) {
+        self.conn.close().await;
+    }
+}
+
+/// The task that turns heartbeat and connection notifications into health.
+struct Watcher {
+    conn: WeakConnection,
+    heartbeat: Heartbeat,
+    late_after: f64,
+    state: Arc<Mutex<State>>,
+    changes: mpsc::Sender<MonitorStatus>,
+    forward_heartbeats: Option<mpsc::Sender<()>>,
+    forward_events: Option<mpsc::Sender<ConnectionEvent>>,
+}
+
+impl Watcher {
+    async fn run(
+        self,
+        mut heartbeats: mpsc::Receiver<()>,
+        mut events: mpsc::Receiver<ConnectionEvent>,
+    ) {
+        // Start of the reconnect attempt in progress, to time its handshake
+        let mut attempt_started = Instant::now();
+        let mut check = tokio::time::interval(Duration::from_millis(100));
+        loop {
+            tokio::select! {
+                heartbeat = heartbeats.recv() => {
+                    if heartbeat.is_none() {
+                        break;
+                    }
+                    if let Some(tx) = &self.forward_heartbeats
+                        && let Err(e) = tx.try_send(())
+                    {
+                        diagnostics::swallowed("forward heartbeat", None, None, &e);
+                    }
+                    let mut state = self.lock();
+                    state.heartbeats += 1;
+                    state.last_heard = Instant::now();
+                    if state.health == Health::Late {
+                        self.set_health(&mut state, Health::Up);
+                    }
+                }
+                event = events.recv() => {
+                    let Some(event) = event else { break };
+                    events::emit(&self.forward_events, event.clone());
+                    match event {
+                        ConnectionEvent::Reconnecting { .. } => {
+                            attempt_started = Instant::now();
+                            let mut state = self.lock();
+                            if state.health != Health::Down {
+                                self.set_health(&mut state, Health::Down);
+                            }
+                        }
+                        ConnectionEvent::Reconnected { .. } => {
+                            let handshake = attempt_started.elapsed();
+                            let Some(conn) = self.conn.upgrade() else { break };
+                            let server = conn.server_info().await;
+                            let mut state = self.lock();
+                            state.handshake = handshake;
+                            state.heartbeat_interval = broker_interval(self.heartbeat, &server);
+                            state.server = server;
+                            state.reconnects += 1;
+                            state.last_heard = Instant::now();
+                            self.set_health(&mut state, Health::Up);
+                        }
+                        _ => {}
+                    }
+                }
+                _ = check.tick() => {
+                    let mut state = self.lock();
+                    let late = state.heartbeat_interval.is_some_and(|interval| {
+                        state.last_heard.elapsed() > interval.mul_f64(self.late_after)
+                    });
+                    if late && state.health == Health::Up {
+                        self.set_health(&mut state, Health::Late);
+                    }
+                }
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record `health` and report the new state to `Monitor::changed()`.
+    fn set_health(&self, state: &mut State, health: Health) {
+        state.health = health;
+        if let Err(e) = self.changes.try_send(state.snapshot()) {
+            diagnostics::swallowed("report monitor status", None, None, &e);
+        }
+    }
+}
+
+/// How often the broker sends heartbeats, from what the client offered and
+/// the broker's `heart-beat` header.
+fn broker_interval(offered: Heartbeat, server: &ServerInfo) -> Option<Duration> {
+    let (server_out, server_in) = parse_heartbeat_header(&server.heart_beat);
+    negotiate_heartbeats(
+        offered.send_ms.into(),
+        offered.receive_ms.into(),
+        server_out,
+        server_in,
+    )
+    .1
+}
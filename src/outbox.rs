@@ -0,0 +1,525 @@
+use crate::codec::{ContentLengthPolicy, StompCodec, StompItem};
+use crate::frame::Frame;
+use bytes::BytesMut;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Acknowledged records the log may hold before it is compacted
+/// automatically (as long as they also outnumber the waiting entries).
+const AUTO_COMPACT_AFTER: usize = 1024;
+
+/// One SEND frame waiting in an [`Outbox`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    /// Id of the entry. Ids increase with every push and are kept across
+    /// restarts and compaction.
+    pub id: u64,
+    /// The frame as it will be sent. A `receipt` header is added for each
+    /// attempt.
+    pub frame: Frame,
+}
+
+/// Size of an [`Outbox`] and of its log file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutboxStats {
+    /// Entries waiting to be sent.
+    pub pending: usize,
+    /// Entries that were sent (or removed) but are still recorded in the
+    /// log. [`Outbox::compact`] drops them.
+    pub acknowledged: usize,
+    /// Size of the log file in bytes.
+    pub file_bytes: u64,
+}
+
+/// A file-backed queue of outgoing SEND frames (store-and-forward).
+///
+/// Registered with `ConnectOptions::outbox()`. `Connection::send_durable()`
+/// appends a frame to the log and flushes it to disk before returning; the
+/// connection then sends the entries one at a time, oldest first, and
+/// removes each one once the broker has confirmed it with a RECEIPT. An
+/// entry that could not be confirmed, because the connection dropped or
+/// the receipt timed out, is sent again, including after a restart: the
+/// connection starts with whatever the log still holds. Delivery is
+/// therefore at least once; pair it with `PublisherSequence` if consumers
+/// need to discard the copies.
+///
+/// The log is append-only. Removed entries are only marked as such, and
+/// the file is rewritten without them by [`compact`](Self::compact), which
+/// also runs on its own once removed entries clearly outnumber waiting
+/// ones. A record cut short by a crash at the end of the file is ignored
+/// on [`open`](Self::open).
+///
+/// Clones share the same log. Give every connection (and every process)
+/// its own file; two connections draining the same outbox send every
+/// entry twice.
+///
+/// # Example
+///
+/// ```no_run
+/// use iridium_stomp::{ConnectOptions, Outbox};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let outbox = Outbox::open("/var/lib/orders/outbox.log")?;
+/// println!("{} message(s) left from the last run", outbox.len());
+/// let options = ConnectOptions::default().outbox(outbox);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Outbox {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: PathBuf,
+    state: Mutex<State>,
+    /// Woken when an entry is pushed.
+    pushed: Notify,
+}
+
+struct State {
+    file: File,
+    entries: VecDeque<OutboxEntry>,
+    next_id: u64,
+    acknowledged: usize,
+    file_bytes: u64,
+}
+
+impl Outbox {
+    /// Open the log at `path`, creating it if it does not exist, and load
+    /// the entries it still holds.
+    ///
+    /// # Errors
+    ///
+    /// I/O errors, and `io::ErrorKind::InvalidData` if a record is damaged
+    /// rather than torn off at the end of the file.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let log = parse_log(&data)?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // Drop a torn record so new ones start on a record boundary
+        if log.valid_len < data.len() as u64 {
+            file.set_len(log.valid_len)?;
+        }
+        Ok(Self {
+            inner: Arc::new(Inner {
+                path,
+                state: Mutex::new(State {
+                    file,
+                    entries: log.entries,
+                    next_id: log.next_id,
+                    acknowledged: log.acknowledged,
+                    file_bytes: log.valid_len,
+                }),
+                pushed: Notify::new(),
+            }),
+        })
+    }
+
+    /// The path of the log file.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Append `frame` to the log and return its id.
+    ///
+    /// The record is flushed to disk before this returns, so this blocks;
+    /// `Connection::send_durable()` runs it on the blocking thread pool. A
+    /// `receipt` header is removed, and a `content-length` header is added
+    /// if the frame has none. Use `Connection::send_durable()` rather than
+    /// calling this directly, so the frame goes through the connection's
+    /// validation and stamping first.
+    pub fn push(&self, mut frame: Frame) -> io::Result<u64> {
+        frame.headers.retain(|(name, _)| name != "receipt");
+        if frame.get_header("content-length").is_none() {
+            let len = frame.body.len().to_string();
+            frame = frame.header("content-length", len);
+        }
+        let encoded = encode(&frame)?;
+
+        let mut state = self.state();
+        let id = state.next_id;
+        let mut record = format!("+{} {}\n", id, encoded.len()).into_bytes();
+        record.extend_from_slice(&encoded);
+        record.push(b'\n');
+        state.file.write_all(&record)?;
+        state.file.sync_data()?;
+        state.next_id += 1;
+        state.file_bytes += record.len() as u64;
+        state.entries.push_back(OutboxEntry { id, frame });
+        drop(state);
+
+        self.inner.pushed.notify_one();
+        Ok(id)
+    }
+
+    /// Remove the entry `id` without sending it. Returns `false` if there
+    /// is no such entry.
+    ///
+    /// Useful for a message the broker keeps rejecting, which would
+    /// otherwise hold up the entries behind it.
+    pub fn remove(&self, id: u64) -> io::Result<bool> {
+        let mut state = self.state();
+        let Some(index) = state.entries.iter().position(|entry| entry.id == id) else {
+            return Ok(false);
+        };
+        // Not synced: losing the mark only means sending the entry again
+        let record = format!("-{}\n", id);
+        state.file.write_all(record.as_bytes())?;
+        state.entries.remove(index);
+        state.acknowledged += 1;
+        state.file_bytes += record.len() as u64;
+        if state.acknowledged >= AUTO_COMPACT_AFTER && state.acknowledged > state.entries.len() {
+            self.compact_locked(&mut state)?;
+        }
+        Ok(true)
+    }
+
+    /// Rewrite the log with only the entries still waiting.
+    ///
+    /// The new log is written next to the old one and renamed over it, so
+    /// a crash during compaction leaves one or the other intact.
+    pub fn compact(&self) -> io::Result<()> {
+        let mut state = self.state();
+        self.compact_locked(&mut state)
+    }
+
+    /// The entries waiting to be sent, oldest first.
+    pub fn backlog(&self) -> Vec<OutboxEntry> {
+        self.state().entries.iter().cloned().collect()
+    }
+
+    /// Number of entries waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    /// Whether no entries are waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.state().entries.is_empty()
+    }
+
+    /// Current size of the outbox and its log.
+    pub fn stats(&self) -> OutboxStats {
+        let state = self.state();
+        OutboxStats {
+            pending: state.entries.len(),
+            acknowledged: state.acknowledged,
+            file_bytes: state.file_bytes,
+        }
+    }
+
+    /// The oldest waiting entry.
+    pub(crate) fn front(&self) -> Option<OutboxEntry> {
+        self.state().entries.front().cloned()
+    }
+
+    /// Wait until an entry is pushed. A push made since the last call
+    /// completes this immediately.
+    pub(crate) async fn pushed(&self) {
+        self.inner.pushed.notified().await;
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn compact_locked(&self, state: &mut State) -> io::Result<()> {
+        // Keep ids increasing even when no entry is left to imply them
+        let mut data = format!("={}\n", state.next_id).into_bytes();
+        for entry in &state.entries {
+            let encoded = encode(&entry.frame)?;
+            data.extend_from_slice(format!("+{} {}\n", entry.id, encoded.len()).as_bytes());
+            data.extend_from_slice(&encoded);
+            data.push(b'\n');
+        }
+        let mut tmp = self.inner.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp, &self.inner.path)?;
+
+        state.file = OpenOptions::new().append(true).open(&self.inner.path)?;
+        state.acknowledged = 0;
+        state.file_bytes = data.len() as u64;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outbox")
+            .field("path", &self.inner.path)
+            .field("pending", &self.len())
+            .finish()
+    }
+}
+
+/// Entries recovered from a log file.
+struct ParsedLog {
+    entries: VecDeque<OutboxEntry>,
+    next_id: u64,
+    acknowledged: usize,
+    /// Length of the file up to the end of the last complete record.
+    valid_len: u64,
+}
+
+/// Read `+<id> <len>` records (followed by `len` bytes of encoded frame and
+/// a newline), `-<id>` removal marks, and the `=<id>` record compaction
+/// writes with the next id to assign. Parsing stops quietly at a record
+/// torn by a crash: the last bytes of the data, holding less than a whole
+/// frame. A record whose length runs past a complete frame is damaged.
+fn parse_log(data: &[u8]) -> io::Result<ParsedLog> {
+    let mut log = ParsedLog {
+        entries: VecDeque::new(),
+        next_id: 1,
+        acknowledged: 0,
+        valid_len: 0,
+    };
+    let mut pos = 0;
+    while pos < data.len() {
+        let Some(line_len) = data[pos..].iter().position(|&b| b == b'\n') else {
+            break;
+        };
+        let line = std::str::from_utf8(&data[pos..pos + line_len])
+            .map_err(|_| malformed(pos, "record header is not UTF-8"))?;
+        let mut next = pos + line_len + 1;
+        if let Some(record) = line.strip_prefix('+') {
+            let (id, len) = record
+                .split_once(' ')
+                .and_then(|(id, len)| Some((id.parse::<u64>().ok()?, len.parse::<usize>().ok()?)))
+                .ok_or_else(|| malformed(pos, "bad entry header"))?;
+            let end = next
+                .checked_add(len)
+                .ok_or_else(|| malformed(pos, "entry length out of range"))?;
+            if end > data.len() && !incomplete_frame(&data[next..]) {
+                return Err(malformed(pos, "entry length runs past its frame"));
+            }
+            if end >= data.len() {
+                break;
+            }
+            if data[end] != b'\n' {
+                return Err(malformed(pos, "entry length does not match"));
+            }
+            let frame = decode(&data[next..end]).ok_or_else(|| malformed(pos, "bad frame"))?;
+            log.entries.push_back(OutboxEntry { id, frame });
+            log.next_id = log.next_id.max(id + 1);
+            next = end + 1;
+        } else if let Some(id) = line.strip_prefix('=') {
+            let id: u64 = id.parse().map_err(|_| malformed(pos, "bad next id"))?;
+            log.next_id = log.next_id.max(id);
+        } else if let Some(id) = line.strip_prefix('-') {
+            let id: u64 = id.parse().map_err(|_| malformed(pos, "bad removal mark"))?;
+            if let Some(index) = log.entries.iter().position(|entry| entry.id == id) {
+                log.entries.remove(index);
+                log.acknowledged += 1;
+            }
+        } else {
+            return Err(malformed(pos, "unknown record"));
+        }
+        pos = next;
+        log.valid_len = pos as u64;
+    }
+    Ok(log)
+}
+
+fn malformed(offset: usize, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed outbox record at byte {}: {}", offset, reason),
+    )
+}
+
+fn encode(frame: &Frame) -> io::Result<BytesMut> {
+    let mut buf = BytesMut::new();
+    StompCodec::new()
+        .with_content_length_policy(ContentLengthPolicy::AlwaysEmit)
+        .encode(StompItem::Frame(frame.clone()), &mut buf)?;
+    Ok(buf)
+}
+
+/// Whether `data` is the start of an encoded frame cut short, as a torn
+/// write leaves it.
+fn incomplete_frame(data: &[u8]) -> bool {
+    let mut buf = BytesMut::from(data);
+    matches!(StompCodec::new().decode(&mut buf), Ok(None))
+}
+
+fn decode(data: &[u8]) -> Option<Frame> {
+    let mut buf = BytesMut::from(data);
+    match StompCodec::new().decode(&mut buf) {
+        Ok(Some(StompItem::Frame(frame))) if buf.is_empty() => Some(frame),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("iridium-outbox-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn send(body: &str) -> Frame {
+        Frame::new("SEND")
+            .header("destination", "/queue/a:b")
+            .set_body(body.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn entries_survive_reopening() {
+        let path = temp_path("reopen");
+        let outbox = Outbox::open(&path).unwrap();
+        let first = outbox.push(send("one").receipt("r-1")).unwrap();
+        let second = outbox.push(send("two")).unwrap();
+        assert!(outbox.remove(first).unwrap());
+        assert!(!outbox.remove(first).unwrap());
+        drop(outbox);
+
+        let outbox = Outbox::open(&path).unwrap();
+        let backlog = outbox.backlog();
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(backlog[0].id, second);
+        assert_eq!(
+            backlog[0].frame.get_header("destination"),
+            Some("/queue/a:b")
+        );
+        assert_eq!(backlog[0].frame.body, b"two");
+        assert_eq!(outbox.stats().acknowledged, 1);
+        // Ids keep increasing after a restart
+        assert_eq!(outbox.push(send("three")).unwrap(), second + 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn receipt_header_is_not_stored() {
+        let path = temp_path("receipt");
+        let outbox = Outbox::open(&path).unwrap();
+        outbox.push(send("one").receipt("r-1")).unwrap();
+        assert_eq!(outbox.backlog()[0].frame.get_header("receipt"), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn torn_final_record_is_dropped() {
+        let path = temp_path("torn");
+        let outbox = Outbox::open(&path).unwrap();
+        outbox.push(send("kept")).unwrap();
+        outbox.push(send("torn")).unwrap();
+        drop(outbox);
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 4]).unwrap();
+
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.len(), 1);
+        let id = outbox.push(send("after")).unwrap();
+        drop(outbox);
+        let outbox = Outbox::open(&path).unwrap();
+        let bodies: Vec<_> = outbox.backlog().into_iter().map(|e| e.frame.body).collect();
+        assert_eq!(bodies, vec![b"kept".to_vec(), b"after".to_vec()]);
+        assert_eq!(outbox.backlog()[1].id, id);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn damaged_record_is_an_error() {
+        let path = temp_path("damaged");
+        std::fs::write(&path, b"?what\n+1 3\nabc\n").unwrap();
+        let err = Outbox::open(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn damaged_length_is_an_error() {
+        let path = temp_path("length");
+        for data in [
+            &b"+1 18446744073709551615\nabc\n"[..],
+            b"+1 99999\nSEND\n\nabc\0\n",
+        ] {
+            std::fs::write(&path, data).unwrap();
+            let err = Outbox::open(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overlong_record_before_others_is_an_error() {
+        let path = temp_path("overlong");
+        let outbox = Outbox::open(&path).unwrap();
+        outbox.push(send("first")).unwrap();
+        outbox.push(send("second")).unwrap();
+        drop(outbox);
+        let data = std::fs::read(&path).unwrap();
+        let text = String::from_utf8(data.clone()).unwrap();
+        let header = text.lines().find(|line| line.starts_with("+1 ")).unwrap();
+        let damaged = text.replacen(header, "+1 100000", 1);
+        std::fs::write(&path, &damaged).unwrap();
+
+        let err = Outbox::open(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // Nothing was cut off the file
+        assert_eq!(std::fs::read(&path).unwrap(), damaged.as_bytes());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compact_drops_removed_entries() {
+        let path = temp_path("compact");
+        let outbox = Outbox::open(&path).unwrap();
+        let ids: Vec<_> = (0..10)
+            .map(|i| outbox.push(send(&i.to_string())).unwrap())
+            .collect();
+        for id in &ids[..9] {
+            outbox.remove(*id).unwrap();
+        }
+        let before = outbox.stats();
+        outbox.compact().unwrap();
+        let after = outbox.stats();
+        assert_eq!(after.pending, 1);
+        assert_eq!(after.acknowledged, 0);
+        assert!(after.file_bytes < before.file_bytes);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), after.file_bytes);
+        drop(outbox);
+
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.backlog()[0].id, ids[9]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compact_keeps_ids_increasing_when_empty() {
+        let path = temp_path("compact-empty");
+        let outbox = Outbox::open(&path).unwrap();
+        let mut last = 0;
+        for _ in 0..3 {
+            last = outbox.push(send("x")).unwrap();
+            outbox.remove(last).unwrap();
+        }
+        outbox.compact().unwrap();
+        drop(outbox);
+
+        let outbox = Outbox::open(&path).unwrap();
+        assert!(outbox.is_empty());
+        assert_eq!(outbox.push(send("y")).unwrap(), last + 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
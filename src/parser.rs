@@ -38,6 +38,42 @@ pub fn unescape_header_value(input: &[u8]) -> Result<Vec<u8>, String> {
     Ok(result)
 }
 
+/// Unescape a header value, passing through what the STOMP 1.2 rules
+/// reject.
+///
+/// Like [`unescape_header_value`], except that a backslash not followed by
+/// `r`, `n`, `c` or `\` (including one at the end of the value) is kept
+/// as a literal backslash. Returns the value and how many such backslashes
+/// were kept.
+pub fn unescape_header_value_lenient(input: &[u8]) -> (Vec<u8>, usize) {
+    let mut result = Vec::with_capacity(input.len());
+    let mut passed_through = 0;
+    let mut i = 0;
+    while i < input.len() {
+        let escaped = match (input[i], input.get(i + 1)) {
+            (b'\\', Some(b'\\')) => Some(b'\\'),
+            (b'\\', Some(b'n')) => Some(b'\n'),
+            (b'\\', Some(b'r')) => Some(b'\r'),
+            (b'\\', Some(b'c')) => Some(b':'),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                result.push(byte);
+                i += 2;
+            }
+            None => {
+                if input[i] == b'\\' {
+                    passed_through += 1;
+                }
+                result.push(input[i]);
+                i += 1;
+            }
+        }
+    }
+    (result, passed_through)
+}
+
 /// Minimal helper: extract optional content-length header value from a header list.
 ///
 /// Returns:
@@ -47,7 +83,11 @@ pub fn unescape_header_value(input: &[u8]) -> Result<Vec<u8>, String> {
 type ParseResult =
     Result<Option<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>, usize)>, String>;
 
-fn get_content_length(headers: &[(Vec<u8>, Vec<u8>)]) -> Result<Option<usize>, String> {
+/// Find the content-length header, if any.
+///
+/// Returns the length and whether it was only accepted leniently: the key
+/// is not lowercase or the value is surrounded by whitespace.
+fn get_content_length(headers: &[(&[u8], &[u8])]) -> Result<Option<(usize, bool)>, String> {
     for (k, v) in headers {
         if k.eq_ignore_ascii_case(&b"content-length"[..]) {
             let s =
@@ -56,8 +96,9 @@ fn get_content_length(headers: &[(Vec<u8>, Vec<u8>)]) -> Result<Option<usize>, S
             if trimmed.is_empty() {
                 return Err("empty content-length".to_string());
             }
+            let lenient = *k != b"content-length" || trimmed.len() != s.len();
             match trimmed.parse::<usize>() {
-                Ok(n) => return Ok(Some(n)),
+                Ok(n) => return Ok(Some((n, lenient))),
                 Err(e) => return Err(format!("invalid content-length '{}': {}", trimmed, e)),
             }
         }
@@ -65,50 +106,126 @@ fn get_content_length(headers: &[(Vec<u8>, Vec<u8>)]) -> Result<Option<usize>, S
     Ok(None)
 }
 
+/// Why `parse_frame_raw` rejected its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParseErrorKind {
+    /// A header line without a `:` separator.
+    MalformedHeader,
+    /// An unusable content-length header, or a body not followed by NUL.
+    ContentLength,
+}
+
+/// What followed a frame's NUL terminator on the wire.
+///
+/// Brokers differ here: some send a bare NUL, others append an LF or a
+/// CRLF as a courtesy newline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Terminator {
+    /// NUL only.
+    Nul,
+    /// NUL followed by LF.
+    NulLf,
+    /// NUL followed by CRLF.
+    NulCrLf,
+    /// NUL followed by a CR at the end of the input; the LF may arrive with
+    /// the next read.
+    NulCr,
+}
+
+/// Consume the optional EOL after the NUL that ends at `pos`.
+///
+/// Returns the position after it and the terminator style seen.
+pub(crate) fn consume_trailing_eol(input: &[u8], pos: usize) -> (usize, Terminator) {
+    match &input[pos..] {
+        [b'\n', ..] => (pos + 1, Terminator::NulLf),
+        [b'\r', b'\n', ..] => (pos + 2, Terminator::NulCrLf),
+        [b'\r'] => (pos + 1, Terminator::NulCr),
+        _ => (pos, Terminator::Nul),
+    }
+}
+
+/// A frame parsed by `parse_frame_raw`, borrowing from the input buffer.
+///
+/// Header keys and values are still escaped. The codec builds its owned
+/// `Frame` straight from these slices, so no intermediate copies are made.
+pub(crate) struct RawFrame<'a> {
+    pub(crate) command: &'a [u8],
+    pub(crate) headers: Vec<(&'a [u8], &'a [u8])>,
+    pub(crate) body: Option<&'a [u8]>,
+    pub(crate) consumed: usize,
+    pub(crate) terminator: Terminator,
+    /// The frame was only accepted by a lenient rule: a NUL-terminated body
+    /// without a command line, or a non-canonical content-length header.
+    pub(crate) permissive: bool,
+}
+
 /// Parse a single STOMP frame from a raw byte slice.
 ///
 /// Returns Ok(Some((command, headers, body, consumed_bytes))) when a full frame
 /// was parsed and how many bytes were consumed. Returns Ok(None) when more
 /// bytes are required. Returns Err on protocol errors.
+///
+/// Heartbeats (LF or CRLF) before the frame are skipped and included in
+/// the consumed bytes. A heartbeat inside the frame is not recognised as
+/// one: STOMP only allows them between frames.
 pub fn parse_frame_slice(input: &[u8]) -> ParseResult {
-    let mut pos = 0usize;
-    let len = input.len();
+    let skipped = skip_heartbeats(input);
+    let raw = parse_frame_raw(&input[skipped..]).map_err(|(_, e)| e)?;
+    Ok(raw.map(|raw| {
+        (
+            raw.command.to_vec(),
+            raw.headers
+                .into_iter()
+                .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                .collect(),
+            raw.body.map(<[u8]>::to_vec),
+            skipped + raw.consumed,
+        )
+    }))
+}
 
-    // skip any leading LF heartbeats
-    while pos < len && input[pos] == b'\n' {
-        // treat a single LF as a heartbeat frame (handled by caller if desired)
-        // but we skip leading LFs here; the codec will detect heartbeat earlier
-        pos += 1;
+/// Length of the heartbeats (LF or CRLF) at the start of `input`.
+fn skip_heartbeats(input: &[u8]) -> usize {
+    let mut pos = 0;
+    loop {
+        match &input[pos..] {
+            [b'\n', ..] => pos += 1,
+            [b'\r', b'\n', ..] => pos += 2,
+            _ => return pos,
+        }
     }
+}
 
-    // parse command line: find next LF; if no LF, fall back to NUL-only frame
-    let cmd_end_opt = input[pos..].iter().position(|&b| b == b'\n');
-    let mut command: Vec<u8>;
-    if let Some(cmd_end_rel) = cmd_end_opt {
-        command = input[pos..pos + cmd_end_rel].to_vec();
-        // strip trailing CR if present
-        if command.last() == Some(&b'\r') {
-            // remove trailing CR
-            command.pop();
-        }
-        pos += cmd_end_rel + 1;
-    } else {
-        // No newline found: if there's a NUL in the remaining bytes, treat
-        // this as a bare NUL-terminated body with empty command/headers.
-        if let Some(nul_rel) = input[pos..].iter().position(|&b| b == 0) {
-            let body = input[pos..pos + nul_rel].to_vec();
-            pos += nul_rel + 1;
-            if pos < len && input[pos] == b'\n' {
-                pos += 1;
-            }
-            let body_opt = if body.is_empty() { None } else { Some(body) };
-            return Ok(Some((Vec::new(), Vec::new(), body_opt, pos)));
-        }
+/// The command line and headers of a frame, borrowing from the input.
+pub(crate) struct RawHead<'a> {
+    pub(crate) command: &'a [u8],
+    pub(crate) headers: Vec<(&'a [u8], &'a [u8])>,
+    /// Offset of the first body byte.
+    pub(crate) body_start: usize,
+}
+
+/// Parse the command line and headers of the frame at the start of
+/// `input`, which must not start with a heartbeat.
+///
+/// Returns Ok(None) until the blank line ending the headers has arrived.
+pub(crate) fn parse_frame_head(
+    input: &[u8],
+) -> Result<Option<RawHead<'_>>, (ParseErrorKind, String)> {
+    let mut pos = 0usize;
+    let len = input.len();
+
+    let Some(cmd_end_rel) = input[pos..].iter().position(|&b| b == b'\n') else {
         return Ok(None);
+    };
+    let mut command = &input[pos..pos + cmd_end_rel];
+    // strip trailing CR if present
+    if let Some(stripped) = command.strip_suffix(b"\r") {
+        command = stripped;
     }
+    pos += cmd_end_rel + 1;
 
     // parse headers until an empty line (LF) is found
-    let mut headers: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    let mut headers: Vec<(&[u8], &[u8])> = Vec::new();
     loop {
         if pos >= len {
             return Ok(None);
@@ -117,6 +234,10 @@ pub fn parse_frame_slice(input: &[u8]) -> ParseResult {
             pos += 1; // consume blank line
             break;
         }
+        if input[pos..].starts_with(b"\r\n") {
+            pos += 2; // blank line with CRLF ending
+            break;
+        }
         // find end of header line
         let line_end_rel = match input[pos..].iter().position(|&b| b == b'\n') {
             Some(i) => i,
@@ -129,37 +250,93 @@ pub fn parse_frame_slice(input: &[u8]) -> ParseResult {
         }
         // find ':' separator
         if let Some(colon) = line.iter().position(|&b| b == b':') {
-            let key = line[..colon].to_vec();
-            let val = line[colon + 1..].to_vec();
-            headers.push((key, val));
+            headers.push((&line[..colon], &line[colon + 1..]));
         } else {
-            return Err(format!(
-                "malformed header line: {:?}",
-                String::from_utf8_lossy(line)
+            return Err((
+                ParseErrorKind::MalformedHeader,
+                format!("malformed header line: {:?}", String::from_utf8_lossy(line)),
             ));
         }
         pos += line_end_rel + 1;
     }
+    Ok(Some(RawHead {
+        command,
+        headers,
+        body_start: pos,
+    }))
+}
+
+/// The body length announced by a frame's content-length header, if any.
+pub(crate) fn declared_body_len(
+    headers: &[(&[u8], &[u8])],
+) -> Result<Option<usize>, (ParseErrorKind, String)> {
+    get_content_length(headers)
+        .map(|cl| cl.map(|(n, _)| n))
+        .map_err(|e| (ParseErrorKind::ContentLength, e))
+}
+
+/// Borrowing variant of `parse_frame_slice` used by the codec.
+pub(crate) fn parse_frame_raw(
+    input: &[u8],
+) -> Result<Option<RawFrame<'_>>, (ParseErrorKind, String)> {
+    let len = input.len();
+    // Heartbeats are consumed before a frame is parsed: the codec decodes
+    // them as items and `parse_frame_slice` skips them
+
+    // No newline found: if there's a NUL in the remaining bytes, treat this
+    // as a bare NUL-terminated body with empty command/headers.
+    if !input.contains(&b'\n') {
+        if let Some(nul) = input.iter().position(|&b| b == 0) {
+            let body = &input[..nul];
+            let (consumed, terminator) = consume_trailing_eol(input, nul + 1);
+            return Ok(Some(RawFrame {
+                command: &[],
+                headers: Vec::new(),
+                body: (!body.is_empty()).then_some(body),
+                consumed,
+                terminator,
+                permissive: true,
+            }));
+        }
+        return Ok(None);
+    }
+
+    let Some(RawHead {
+        command,
+        headers,
+        body_start,
+    }) = parse_frame_head(input)?
+    else {
+        return Ok(None);
+    };
+    let mut pos = body_start;
 
     // determine body strategy
     match get_content_length(&headers) {
-        Ok(Some(content_len)) => {
+        Ok(Some((content_len, lenient))) => {
             // need content_len bytes, plus terminating NUL
             if pos + content_len + 1 > len {
                 Ok(None)
             } else {
-                let body = input[pos..pos + content_len].to_vec();
+                let body = &input[pos..pos + content_len];
                 pos += content_len;
                 // next must be NUL
                 if pos >= len || input[pos] != 0 {
-                    Err("missing NUL terminator after content-length body".to_string())
+                    Err((
+                        ParseErrorKind::ContentLength,
+                        "missing NUL terminator after content-length body".to_string(),
+                    ))
                 } else {
-                    pos += 1;
-                    // optional trailing LF
-                    if pos < len && input[pos] == b'\n' {
-                        pos += 1;
-                    }
-                    Ok(Some((command, headers, Some(body), pos)))
+                    // optional trailing LF or CRLF
+                    let (consumed, terminator) = consume_trailing_eol(input, pos + 1);
+                    Ok(Some(RawFrame {
+                        command,
+                        headers,
+                        body: Some(body),
+                        consumed,
+                        terminator,
+                        permissive: lenient,
+                    }))
                 }
             }
         }
@@ -167,18 +344,21 @@ pub fn parse_frame_slice(input: &[u8]) -> ParseResult {
             // NUL-terminated body: find NUL
             match input[pos..].iter().position(|&b| b == 0) {
                 Some(nul_rel) => {
-                    let body = input[pos..pos + nul_rel].to_vec();
-                    pos += nul_rel + 1;
-                    // optional trailing LF
-                    if pos < len && input[pos] == b'\n' {
-                        pos += 1;
-                    }
-                    let body_opt = if body.is_empty() { None } else { Some(body) };
-                    Ok(Some((command, headers, body_opt, pos)))
+                    let body = &input[pos..pos + nul_rel];
+                    // optional trailing LF or CRLF
+                    let (consumed, terminator) = consume_trailing_eol(input, pos + nul_rel + 1);
+                    Ok(Some(RawFrame {
+                        command,
+                        headers,
+                        body: (!body.is_empty()).then_some(body),
+                        consumed,
+                        terminator,
+                        permissive: false,
+                    }))
                 }
                 None => Ok(None),
             }
         }
-        Err(e) => Err(e),
+        Err(e) => Err((ParseErrorKind::ContentLength, e)),
     }
 }
@@ -0,0 +1,308 @@
+use crate::frame::Frame;
+use std::collections::HashSet;
+
+/// Client-side tracking of the STOMP session, used to reject illegal frame
+/// sequences before they reach the broker.
+///
+/// Enabled with `ConnectOptions::strict_protocol()`. A `Connection` handle
+/// only exists once the CONNECTED frame has been received, so validation
+/// starts in the connected state and ends once DISCONNECT is sent or the
+/// connection is closed. Frames queued while reconnecting are held until the
+/// new handshake completes, so they never precede CONNECTED on the wire.
+///
+/// The checks cover mistakes a broker would answer with an ERROR frame (and
+/// usually a dropped connection):
+///
+/// - handshake or server-only commands sent by the client (CONNECT, STOMP,
+///   CONNECTED, MESSAGE, RECEIPT, ERROR) and unknown commands;
+/// - anything sent after DISCONNECT or `close()`;
+/// - missing required headers (`destination` on SEND and SUBSCRIBE, `id` on
+///   SUBSCRIBE, UNSUBSCRIBE, ACK and NACK, `transaction` on BEGIN, COMMIT
+///   and ABORT);
+/// - SUBSCRIBE reusing an active id, and UNSUBSCRIBE, ACK or NACK naming a
+///   subscription that is not active;
+/// - BEGIN of an already open transaction, and COMMIT, ABORT or a
+///   `transaction` header naming one that was never begun.
+///
+/// A frame is checked before it is queued, and only recorded once it has
+/// been queued, so a frame that never reaches the writer leaves the state
+/// as it was.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ProtocolState {
+    subscriptions: HashSet<String>,
+    transactions: HashSet<String>,
+    disconnected: bool,
+}
+
+impl ProtocolState {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validate `frame` against the current session without recording it.
+    /// Returns a description of the violation if it is illegal.
+    pub(crate) fn check(&self, frame: &Frame) -> Result<(), String> {
+        let command = frame.command.as_str();
+        if self.disconnected {
+            return Err(format!("{} sent after DISCONNECT", command));
+        }
+
+        let require = |name: &str| {
+            frame
+                .get_header(name)
+                .ok_or_else(|| format!("{} frame is missing the '{}' header", command, name))
+        };
+
+        match command {
+            "CONNECT" | "STOMP" => {
+                return Err(format!(
+                    "{} is sent by the connection during the handshake; do not send it manually",
+                    command
+                ));
+            }
+            "CONNECTED" | "MESSAGE" | "RECEIPT" | "ERROR" => {
+                return Err(format!("{} is a server frame and cannot be sent", command));
+            }
+            "SEND" => {
+                require("destination")?;
+            }
+            "SUBSCRIBE" => {
+                require("destination")?;
+                let id = require("id")?;
+                if self.subscriptions.contains(id) {
+                    return Err(format!("subscription id '{}' is already in use", id));
+                }
+            }
+            "UNSUBSCRIBE" => {
+                let id = require("id")?;
+                if !self.subscriptions.contains(id) {
+                    return Err(format!("UNSUBSCRIBE for unknown subscription '{}'", id));
+                }
+            }
+            "ACK" | "NACK" => {
+                require("id")?;
+                match frame.get_header("subscription") {
+                    Some(sub) if !self.subscriptions.contains(sub) => {
+                        return Err(format!("{} for unknown subscription '{}'", command, sub));
+                    }
+                    None if self.subscriptions.is_empty() => {
+                        return Err(format!("{} sent with no active subscription", command));
+                    }
+                    _ => {}
+                }
+            }
+            "BEGIN" => {
+                let tx = require("transaction")?;
+                if self.transactions.contains(tx) {
+                    return Err(format!("transaction '{}' has already begun", tx));
+                }
+            }
+            "COMMIT" | "ABORT" => {
+                let tx = require("transaction")?;
+                if !self.transactions.contains(tx) {
+                    return Err(format!(
+                        "{} of transaction '{}' that was never begun",
+                        command, tx
+                    ));
+                }
+            }
+            "DISCONNECT" => {}
+            other => return Err(format!("unknown command '{}'", other)),
+        }
+
+        // SEND, ACK and NACK may take part in a transaction
+        if matches!(command, "SEND" | "ACK" | "NACK")
+            && let Some(tx) = frame.get_header("transaction")
+            && !self.transactions.contains(tx)
+        {
+            return Err(format!(
+                "{} references transaction '{}' that was never begun",
+                command, tx
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate `frames` as if each were recorded before the next is
+    /// checked, for frames queued together (such as BEGIN, SEND and COMMIT
+    /// of one batch).
+    pub(crate) fn check_all<'a>(
+        &self,
+        frames: impl IntoIterator<Item = &'a Frame>,
+    ) -> Result<(), String> {
+        let mut projected = self.clone();
+        for frame in frames {
+            projected.check(frame)?;
+            projected.record(frame);
+        }
+        Ok(())
+    }
+
+    /// Whether recording `frame` changes the state.
+    pub(crate) fn changes_state(frame: &Frame) -> bool {
+        matches!(
+            frame.command.as_str(),
+            "SUBSCRIBE" | "UNSUBSCRIBE" | "BEGIN" | "COMMIT" | "ABORT" | "DISCONNECT"
+        )
+    }
+
+    /// Record the effect of a frame that passed validation and has been
+    /// queued (for example a newly begun transaction).
+    pub(crate) fn record(&mut self, frame: &Frame) {
+        let header = |name: &str| frame.get_header(name).unwrap_or_default().to_string();
+        match frame.command.as_str() {
+            "SUBSCRIBE" => {
+                self.subscriptions.insert(header("id"));
+            }
+            "UNSUBSCRIBE" => {
+                self.subscriptions.remove(&header("id"));
+            }
+            "BEGIN" => {
+                self.transactions.insert(header("transaction"));
+            }
+            "COMMIT" | "ABORT" => {
+                self.transactions.remove(&header("transaction"));
+            }
+            "DISCONNECT" => self.disconnected = true,
+            _ => {}
+        }
+    }
+
+    /// Forget a subscription removed without an UNSUBSCRIBE from this
+    /// handle (for example when its receiver was dropped).
+    pub(crate) fn forget_subscription(&mut self, id: &str) {
+        self.subscriptions.remove(id);
+    }
+
+    /// Reset per-session state after a reconnect. The broker discards open
+    /// transactions when the old session ends; subscriptions are restored
+    /// by the connection and stay active.
+    pub(crate) fn reconnected(&mut self) {
+        self.transactions.clear();
+    }
+
+    /// Mark the session as ended by `Connection::close()`.
+    pub(crate) fn closed(&mut self) {
+        self.disconnected = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check `frame` and record it, as queueing it would.
+    fn send(state: &mut ProtocolState, frame: &Frame) -> Result<(), String> {
+        state.check(frame)?;
+        state.record(frame);
+        Ok(())
+    }
+
+    fn subscribe(id: &str) -> Frame {
+        Frame::new("SUBSCRIBE")
+            .header("id", id)
+            .header("destination", "/queue/a")
+    }
+
+    #[test]
+    fn rejects_handshake_and_server_frames() {
+        let state = ProtocolState::new();
+        for cmd in [
+            "CONNECT",
+            "STOMP",
+            "CONNECTED",
+            "MESSAGE",
+            "RECEIPT",
+            "ERROR",
+            "FOO",
+        ] {
+            assert!(state.check(&Frame::new(cmd)).is_err(), "{} accepted", cmd);
+        }
+    }
+
+    #[test]
+    fn tracks_subscriptions_for_ack() {
+        let mut state = ProtocolState::new();
+        let ack = Frame::new("ACK")
+            .header("id", "m1")
+            .header("subscription", "1");
+        assert!(state.check(&ack).is_err());
+        assert!(state.check(&Frame::new("ACK").header("id", "m1")).is_err());
+
+        send(&mut state, &subscribe("1")).unwrap();
+        assert!(state.check(&subscribe("1")).is_err());
+        send(&mut state, &ack).unwrap();
+
+        send(&mut state, &Frame::new("UNSUBSCRIBE").header("id", "1")).unwrap();
+        assert!(state.check(&ack).is_err());
+        assert!(
+            state
+                .check(&Frame::new("UNSUBSCRIBE").header("id", "1"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tracks_transactions() {
+        let mut state = ProtocolState::new();
+        let begin = Frame::new("BEGIN").header("transaction", "tx1");
+        let commit = Frame::new("COMMIT").header("transaction", "tx1");
+        let in_tx = Frame::new("SEND")
+            .header("destination", "/queue/a")
+            .header("transaction", "tx1");
+
+        assert!(state.check(&commit).is_err());
+        assert!(state.check(&in_tx).is_err());
+        send(&mut state, &begin).unwrap();
+        assert!(state.check(&begin).is_err());
+        send(&mut state, &in_tx).unwrap();
+        send(&mut state, &commit).unwrap();
+        assert!(state.check(&commit).is_err());
+
+        send(&mut state, &begin).unwrap();
+        state.reconnected();
+        assert!(state.check(&commit).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_headers_and_frames_after_disconnect() {
+        let mut state = ProtocolState::new();
+        let err = state.check(&Frame::new("SEND")).unwrap_err();
+        assert!(err.contains("destination"), "{}", err);
+
+        send(&mut state, &Frame::new("DISCONNECT")).unwrap();
+        let after = Frame::new("SEND").header("destination", "/queue/a");
+        assert!(
+            state
+                .check(&after)
+                .unwrap_err()
+                .contains("after DISCONNECT")
+        );
+    }
+
+    #[test]
+    fn checking_does_not_record() {
+        let mut state = ProtocolState::new();
+        let begin = Frame::new("BEGIN").header("transaction", "tx1");
+        state.check(&begin).unwrap();
+        // Never queued, so the transaction was not begun
+        state.check(&begin).unwrap();
+        assert!(
+            state
+                .check(&Frame::new("COMMIT").header("transaction", "tx1"))
+                .is_err()
+        );
+
+        let batch = [
+            begin.clone(),
+            Frame::new("SEND")
+                .header("destination", "/queue/a")
+                .header("transaction", "tx1"),
+            Frame::new("COMMIT").header("transaction", "tx1"),
+        ];
+        state.check_all(&batch).unwrap();
+        assert!(state.check_all(&batch[1..]).is_err());
+        send(&mut state, &begin).unwrap();
+        assert!(state.check_all(&batch).is_err());
+    }
+}
@@ -0,0 +1,681 @@
+//! A record of what happened on a connection, for session reports.
+//!
+//! Attach a [`SessionRecorder`] with `ConnectOptions::recorder()` and the
+//! connection counts the messages, heartbeats, receipts, reconnects and
+//! broker errors it handles, optionally keeping the most recent messages.
+//! [`SessionRecorder::report()`] takes a [`SessionReport`] that renders as
+//! text, as JSON with the `json` feature, or in any format implementing
+//! [`ReportFormat`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use iridium_stomp::{ConnectOptions, Connection, SessionRecorder};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let recorder = SessionRecorder::new().history(500);
+//! let options = ConnectOptions::default().recorder(recorder.clone());
+//! let conn = Connection::connect_with_options(
+//!     "localhost:61613", "guest", "guest", "10000,10000", options,
+//! ).await?;
+//! // ...
+//! std::fs::write("session.txt", recorder.report().to_text())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::frame::Frame;
+
+/// Default for `SessionRecorder::max_errors()`.
+const DEFAULT_MAX_ERRORS: usize = 100;
+
+/// Default for `SessionRecorder::body_preview()`.
+const DEFAULT_BODY_PREVIEW: usize = 2048;
+
+/// Most distinct destinations counted by name; messages to further ones are
+/// counted together in `SessionReport::other_destinations`, so a session
+/// touching many destinations cannot grow the record without bound.
+const MAX_DESTINATIONS: usize = 256;
+
+/// Width of the rule lines in `TextFormat` output.
+const RULE_WIDTH: usize = 79;
+
+/// Counters of a [`SessionRecorder`], cheap to take as often as needed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    /// MESSAGE frames received.
+    pub received: u64,
+    /// Their body bytes in total.
+    pub received_bytes: u64,
+    /// SEND frames written to the broker.
+    pub sent: u64,
+    /// Their encoded size in total, headers included.
+    pub sent_bytes: u64,
+    /// Heartbeats received from the broker.
+    pub heartbeats: u64,
+    /// When the last heartbeat arrived.
+    pub last_heartbeat: Option<Instant>,
+    /// Times the connection was re-established.
+    pub reconnects: u64,
+    /// ERROR frames received from the broker.
+    pub errors: u64,
+    /// Receipts the broker confirmed.
+    pub receipts: u64,
+    /// Sum of their round-trip times.
+    pub receipt_rtt_total: Duration,
+    /// Round-trip time of the most recent receipt.
+    pub last_receipt_rtt: Option<Duration>,
+}
+
+impl SessionStats {
+    /// Average receipt round-trip time, or `None` before the first receipt.
+    pub fn average_receipt_rtt(&self) -> Option<Duration> {
+        (self.receipts > 0).then(|| {
+            let nanos = self.receipt_rtt_total.as_nanos() / u128::from(self.receipts);
+            Duration::from_nanos(nanos as u64)
+        })
+    }
+}
+
+/// A frame kept by a [`SessionRecorder`]: a received message or a broker
+/// error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedFrame {
+    /// When the frame arrived.
+    pub at: SystemTime,
+    /// The frame's `destination` header, if it has one.
+    pub destination: Option<String>,
+    /// The frame's headers.
+    pub headers: Vec<(String, String)>,
+    /// The body as text, or a hex dump for binary bodies, cut at
+    /// `SessionRecorder::body_preview()` bytes (see `Frame::body_preview()`).
+    pub body: String,
+    /// Size of the whole body in bytes.
+    pub body_len: usize,
+}
+
+impl RecordedFrame {
+    fn new(frame: &Frame, body_preview: usize) -> Self {
+        Self {
+            at: SystemTime::now(),
+            destination: frame.get_header("destination").map(str::to_string),
+            headers: frame.headers.clone(),
+            body: frame.body_preview(body_preview),
+            body_len: frame.body.len(),
+        }
+    }
+}
+
+/// Everything a [`SessionRecorder`] has recorded, taken at one point in
+/// time by [`SessionRecorder::report()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionReport {
+    /// When the recorder was created.
+    pub started: SystemTime,
+    /// When the report was taken.
+    pub ended: SystemTime,
+    /// The counters.
+    pub stats: SessionStats,
+    /// Messages received per destination.
+    pub destinations: BTreeMap<String, u64>,
+    /// Messages received on a destination first seen after 256 others.
+    pub other_destinations: u64,
+    /// The most recent broker errors, oldest first.
+    pub errors: Vec<RecordedFrame>,
+    /// The most recent messages, oldest first. Empty unless
+    /// `SessionRecorder::history()` was set.
+    pub history: Vec<RecordedFrame>,
+}
+
+impl SessionReport {
+    /// How long the session had been recorded when the report was taken.
+    pub fn duration(&self) -> Duration {
+        self.ended.duration_since(self.started).unwrap_or_default()
+    }
+
+    /// Render the report with `format`.
+    pub fn render(&self, format: &dyn ReportFormat) -> String {
+        format.render(self)
+    }
+
+    /// The report as text, message history included.
+    pub fn to_text(&self) -> String {
+        self.render(&TextFormat::new())
+    }
+
+    /// The report as a JSON object. Requires the `json` feature.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        self.render(&JsonFormat)
+    }
+}
+
+/// Renders a [`SessionReport`], e.g. for writing it to a file.
+///
+/// [`TextFormat`] and, with the `json` feature, `JsonFormat` are built in;
+/// implement this for other formats or layouts.
+pub trait ReportFormat {
+    /// Render `report`.
+    fn render(&self, report: &SessionReport) -> String;
+}
+
+/// Human-readable report: counters, messages per destination, recent
+/// errors and the message history, one line per frame. Times are UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextFormat {
+    history: bool,
+    max_width: usize,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        Self {
+            history: true,
+            max_width: 80,
+        }
+    }
+}
+
+impl TextFormat {
+    /// The message history included, lines cut at 80 characters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include the message history (default true).
+    pub fn history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Cut error and history lines at `width` characters (default 80).
+    pub fn max_width(mut self, width: usize) -> Self {
+        self.max_width = width;
+        self
+    }
+
+    /// One line for a recorded frame: time, destination and body.
+    fn frame_line(&self, frame: &RecordedFrame, label: &str) -> String {
+        let prefix = format!(
+            "  {} [{}] ",
+            &format_timestamp(frame.at)[11..19],
+            frame.destination.as_deref().unwrap_or(label)
+        );
+        let body = frame.body.replace('\n', " ");
+        let width = self.max_width.saturating_sub(prefix.chars().count());
+        format!("{}{}", prefix, truncate(&body, width))
+    }
+}
+
+impl ReportFormat for TextFormat {
+    fn render(&self, report: &SessionReport) -> String {
+        let stats = &report.stats;
+        let heavy = "═".repeat(RULE_WIDTH);
+        let light = "─".repeat(RULE_WIDTH);
+        let mut lines = vec![
+            heavy.clone(),
+            "  Session Report".to_string(),
+            heavy.clone(),
+            format!("  Started:    {}", format_timestamp(report.started)),
+            format!("  Ended:      {}", format_timestamp(report.ended)),
+            format!("  Duration:   {}", format_duration(report.duration())),
+            String::new(),
+            format!(
+                "  Received:   {} messages, {} body bytes",
+                stats.received, stats.received_bytes
+            ),
+            format!(
+                "  Sent:       {} messages, {} bytes",
+                stats.sent, stats.sent_bytes
+            ),
+            format!("  Heartbeats: {}", stats.heartbeats),
+            format!("  Reconnects: {}", stats.reconnects),
+            format!("  Errors:     {}", stats.errors),
+        ];
+        if let Some(average) = stats.average_receipt_rtt() {
+            lines.push(format!(
+                "  Receipts:   {} (avg {} ms)",
+                stats.receipts,
+                average.as_millis()
+            ));
+        }
+        if !report.destinations.is_empty() {
+            lines.push(String::new());
+            lines.push("  Destinations:".to_string());
+            let mut destinations: Vec<_> = report.destinations.iter().collect();
+            destinations.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+            let width = destinations
+                .iter()
+                .map(|(d, _)| d.chars().count())
+                .max()
+                .unwrap_or(0)
+                .min(40);
+            for (destination, count) in destinations {
+                lines.push(format!(
+                    "    {:width$} {:>6}",
+                    truncate(destination, width),
+                    count,
+                    width = width
+                ));
+            }
+            if report.other_destinations > 0 {
+                lines.push(format!(
+                    "    {:width$} {:>6}",
+                    "(others)",
+                    report.other_destinations,
+                    width = width
+                ));
+            }
+        }
+        for (title, frames, label) in [
+            ("Recent Errors", &report.errors, "ERROR"),
+            ("Message History", &report.history, "MESSAGE"),
+        ] {
+            if frames.is_empty() || (label == "MESSAGE" && !self.history) {
+                continue;
+            }
+            lines.push(String::new());
+            lines.push(light.clone());
+            lines.push(format!("  {}", title));
+            lines.push(light.clone());
+            lines.extend(frames.iter().map(|frame| self.frame_line(frame, label)));
+        }
+        lines.push(heavy);
+        lines.join("\n")
+    }
+}
+
+/// The report as one JSON object. Times are RFC 3339 strings in UTC and
+/// durations are milliseconds. Requires the `json` feature.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonFormat;
+
+#[cfg(feature = "json")]
+impl JsonFormat {
+    fn frames(frames: &[RecordedFrame]) -> serde_json::Value {
+        frames
+            .iter()
+            .map(|frame| {
+                serde_json::json!({
+                    "at": format_timestamp(frame.at),
+                    "destination": frame.destination,
+                    "headers": frame.headers,
+                    "body": frame.body,
+                    "body_len": frame.body_len,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "json")]
+impl ReportFormat for JsonFormat {
+    fn render(&self, report: &SessionReport) -> String {
+        let stats = &report.stats;
+        let millis = |d: Duration| d.as_millis() as u64;
+        serde_json::json!({
+            "started": format_timestamp(report.started),
+            "ended": format_timestamp(report.ended),
+            "duration_ms": millis(report.duration()),
+            "received": stats.received,
+            "received_bytes": stats.received_bytes,
+            "sent": stats.sent,
+            "sent_bytes": stats.sent_bytes,
+            "heartbeats": stats.heartbeats,
+            "reconnects": stats.reconnects,
+            "errors": stats.errors,
+            "receipts": stats.receipts,
+            "average_receipt_rtt_ms": stats.average_receipt_rtt().map(millis),
+            "last_receipt_rtt_ms": stats.last_receipt_rtt.map(millis),
+            "destinations": report.destinations,
+            "other_destinations": report.other_destinations,
+            "recent_errors": Self::frames(&report.errors),
+            "history": Self::frames(&report.history),
+        })
+        .to_string()
+    }
+}
+
+#[derive(Debug)]
+struct Recording {
+    started: SystemTime,
+    stats: SessionStats,
+    destinations: BTreeMap<String, u64>,
+    other_destinations: u64,
+    errors: VecDeque<RecordedFrame>,
+    history: VecDeque<RecordedFrame>,
+}
+
+/// Records counts, broker errors and optionally the most recent messages of
+/// a connection. Attach it with `ConnectOptions::recorder()`; clones share
+/// the same record.
+///
+/// The connection records every MESSAGE, ERROR, heartbeat and confirmed
+/// receipt it receives, the SEND frames it writes and each reconnect. The
+/// `record_*` methods are public too, for frames handled outside a
+/// `Connection`.
+#[derive(Debug, Clone)]
+pub struct SessionRecorder {
+    recording: Arc<Mutex<Recording>>,
+    history: usize,
+    max_errors: usize,
+    body_preview: usize,
+}
+
+impl Default for SessionRecorder {
+    fn default() -> Self {
+        Self {
+            recording: Arc::new(Mutex::new(Recording {
+                started: SystemTime::now(),
+                stats: SessionStats::default(),
+                destinations: BTreeMap::new(),
+                other_destinations: 0,
+                errors: VecDeque::new(),
+                history: VecDeque::new(),
+            })),
+            history: 0,
+            max_errors: DEFAULT_MAX_ERRORS,
+            body_preview: DEFAULT_BODY_PREVIEW,
+        }
+    }
+}
+
+impl SessionRecorder {
+    /// A recorder keeping counts and the last 100 broker errors, but no
+    /// message history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the last `messages` received messages (default 0, none).
+    pub fn history(mut self, messages: usize) -> Self {
+        self.history = messages;
+        self
+    }
+
+    /// Keep the last `errors` broker errors (default 100).
+    pub fn max_errors(mut self, errors: usize) -> Self {
+        self.max_errors = errors;
+        self
+    }
+
+    /// Keep at most `bytes` of each recorded body (default 2 KiB).
+    pub fn body_preview(mut self, bytes: usize) -> Self {
+        self.body_preview = bytes;
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recording> {
+        self.recording
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Record a received MESSAGE frame.
+    pub fn record_message(&self, frame: &Frame) {
+        let kept = (self.history > 0).then(|| RecordedFrame::new(frame, self.body_preview));
+        let mut recording = self.lock();
+        recording.stats.received += 1;
+        recording.stats.received_bytes += frame.body.len() as u64;
+        if let Some(destination) = frame.get_header("destination") {
+            if let Some(count) = recording.destinations.get_mut(destination) {
+                *count += 1;
+            } else if recording.destinations.len() < MAX_DESTINATIONS {
+                recording.destinations.insert(destination.to_string(), 1);
+            } else {
+                recording.other_destinations += 1;
+            }
+        }
+        if let Some(kept) = kept {
+            push_bounded(&mut recording.history, kept, self.history);
+        }
+    }
+
+    /// Record an ERROR frame from the broker.
+    pub fn record_error(&self, frame: &Frame) {
+        let kept = (self.max_errors > 0).then(|| RecordedFrame::new(frame, self.body_preview));
+        let mut recording = self.lock();
+        recording.stats.errors += 1;
+        if let Some(kept) = kept {
+            push_bounded(&mut recording.errors, kept, self.max_errors);
+        }
+    }
+
+    /// Record a SEND frame of `bytes` bytes written to the broker.
+    pub fn record_sent(&self, bytes: usize) {
+        let mut recording = self.lock();
+        recording.stats.sent += 1;
+        recording.stats.sent_bytes += bytes as u64;
+    }
+
+    /// Record a heartbeat from the broker.
+    pub fn record_heartbeat(&self) {
+        let mut recording = self.lock();
+        recording.stats.heartbeats += 1;
+        recording.stats.last_heartbeat = Some(Instant::now());
+    }
+
+    /// Record a receipt confirmed `rtt` after its frame was sent.
+    pub fn record_receipt(&self, rtt: Duration) {
+        let mut recording = self.lock();
+        recording.stats.receipts += 1;
+        recording.stats.receipt_rtt_total += rtt;
+        recording.stats.last_receipt_rtt = Some(rtt);
+    }
+
+    /// Record that the connection was re-established.
+    pub fn record_reconnect(&self) {
+        self.lock().stats.reconnects += 1;
+    }
+
+    /// The counters so far.
+    pub fn stats(&self) -> SessionStats {
+        self.lock().stats
+    }
+
+    /// Everything recorded so far.
+    pub fn report(&self) -> SessionReport {
+        let recording = self.lock();
+        SessionReport {
+            started: recording.started,
+            ended: SystemTime::now(),
+            stats: recording.stats,
+            destinations: recording.destinations.clone(),
+            other_destinations: recording.other_destinations,
+            errors: recording.errors.iter().cloned().collect(),
+            history: recording.history.iter().cloned().collect(),
+        }
+    }
+
+    /// Forget the message history, keeping the counts and errors.
+    pub fn clear_history(&self) {
+        self.lock().history.clear();
+    }
+}
+
+/// Append `frame`, dropping the oldest entries beyond `max`.
+fn push_bounded(frames: &mut VecDeque<RecordedFrame>, frame: RecordedFrame, max: usize) {
+    frames.push_back(frame);
+    while frames.len() > max {
+        frames.pop_front();
+    }
+}
+
+/// `text` cut at `max` characters, ending in `...` when cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    if max <= 3 {
+        return ".".repeat(max);
+    }
+    let kept: String = text.chars().take(max - 3).collect();
+    format!("{}...", kept)
+}
+
+/// `d` as `1h 2m 3s`, `2m 3s` or `3s`.
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, mins, secs)
+    } else if mins > 0 {
+        format!("{}m {}s", mins, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// `at` in RFC 3339 form in UTC with milliseconds, e.g.
+/// `2025-01-31T09:05:00.250Z`. Times before 1970 are shown as the epoch.
+fn format_timestamp(at: SystemTime) -> String {
+    let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Year, month and day of the `days`th day after 1970-01-01, in the
+/// proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(destination: &str, body: &str) -> Frame {
+        Frame::new("MESSAGE")
+            .header("destination", destination)
+            .header("message-id", "m1")
+            .set_body(body.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339_utc() {
+        let at = UNIX_EPOCH + Duration::from_millis(1_738_314_300_250);
+        assert_eq!(format_timestamp(at), "2025-01-31T09:05:00.250Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(format_timestamp(leap_day), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn history_and_errors_are_bounded() {
+        let recorder = SessionRecorder::new().history(2).max_errors(1);
+        for i in 0..5 {
+            recorder.record_message(&message("/queue/a", &format!("body {}", i)));
+        }
+        recorder.record_message(&message("/queue/b", "last"));
+        recorder.record_error(&Frame::new("ERROR").set_body(b"first".to_vec()));
+        recorder.record_error(&Frame::new("ERROR").set_body(b"second".to_vec()));
+
+        let report = recorder.report();
+        assert_eq!(report.stats.received, 6);
+        assert_eq!(report.stats.errors, 2);
+        assert_eq!(report.destinations["/queue/a"], 5);
+        assert_eq!(report.destinations["/queue/b"], 1);
+        let bodies: Vec<&str> = report.history.iter().map(|f| f.body.as_str()).collect();
+        assert_eq!(bodies, ["body 4", "last"]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].body, "second");
+    }
+
+    #[test]
+    fn no_history_by_default() {
+        let recorder = SessionRecorder::new();
+        recorder.record_message(&message("/queue/a", "hello"));
+        assert!(recorder.report().history.is_empty());
+        assert_eq!(recorder.stats().received_bytes, 5);
+    }
+
+    #[test]
+    fn destinations_are_bounded() {
+        let recorder = SessionRecorder::new();
+        for n in 0..MAX_DESTINATIONS + 3 {
+            recorder.record_message(&message(&format!("/queue/{}", n), "x"));
+        }
+        recorder.record_message(&message("/queue/0", "x"));
+        let report = recorder.report();
+        assert_eq!(report.destinations.len(), MAX_DESTINATIONS);
+        assert_eq!(report.destinations["/queue/0"], 2);
+        assert_eq!(report.other_destinations, 3);
+        assert!(report.to_text().contains("(others)"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_escapes_strings() {
+        let recorder = SessionRecorder::new().history(1);
+        recorder.record_message(&message("/queue/\"q\"", "line\nnext\u{1}"));
+        recorder.record_receipt(Duration::from_millis(4));
+        let json = recorder.report().to_json();
+        assert!(
+            json.contains(r#""destinations":{"/queue/\"q\"":1}"#),
+            "{}",
+            json
+        );
+        assert!(json.contains(r#""body":"line\nnext\u0001""#), "{}", json);
+        assert!(json.contains(r#""average_receipt_rtt_ms":4"#), "{}", json);
+        assert!(json.contains(r#""recent_errors":[]"#), "{}", json);
+    }
+
+    #[test]
+    fn text_lists_destinations_and_history() {
+        let recorder = SessionRecorder::new().history(10);
+        recorder.record_message(&message("/queue/a", "hello"));
+        recorder.record_heartbeat();
+        let text = recorder.report().to_text();
+        assert!(
+            text.contains("Received:   1 messages, 5 body bytes"),
+            "{}",
+            text
+        );
+        assert!(text.contains("Heartbeats: 1"), "{}", text);
+        assert!(text.contains("/queue/a      1"), "{}", text);
+        assert!(text.contains("[/queue/a] hello"), "{}", text);
+        let without = recorder.report().render(&TextFormat::new().history(false));
+        assert!(!without.contains("Message History"));
+    }
+
+    struct CountOnly;
+
+    impl ReportFormat for CountOnly {
+        fn render(&self, report: &SessionReport) -> String {
+            report.stats.received.to_string()
+        }
+    }
+
+    #[test]
+    fn custom_formats_render_reports() {
+        let recorder = SessionRecorder::new();
+        recorder.record_message(&message("/queue/a", "x"));
+        assert_eq!(recorder.report().render(&CountOnly), "1");
+    }
+}
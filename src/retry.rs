@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+/// How `Connection::send_with_retry()` retries a frame that could not be
+/// delivered.
+///
+/// An attempt fails transiently when the outbound queue is full (for
+/// example while the connection is reconnecting and frames pile up) or,
+/// with [`confirm`](Self::confirm), when no RECEIPT arrives in time or the
+/// connection drops before it does. Transient failures are retried after a
+/// delay that doubles from `initial_backoff` up to `max_backoff`, until
+/// `max_attempts` attempts have been made or the next attempt would start
+/// after the `deadline`. Other errors, such as a closed connection or a
+/// frame refused by validation, are returned straight away.
+///
+/// A retried SEND whose receipt merely went missing may reach the broker
+/// twice. Combine confirmed retries with
+/// `ConnectOptions::publisher_sequence()`: the frame is stamped once, so
+/// every attempt carries the same sequence number and consumers can drop
+/// the copy.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new()
+///     .max_attempts(10)
+///     .backoff(Duration::from_millis(200), Duration::from_secs(5))
+///     .deadline(Duration::from_secs(30))
+///     .confirm(Duration::from_secs(2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) deadline: Option<Duration>,
+    pub(crate) receipt_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    /// Five attempts, backing off from 100ms to 5s, no deadline, no receipt.
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            deadline: None,
+            receipt_timeout: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give up after `attempts` attempts in total (minimum 1).
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `initial` before the first retry, doubling the delay for each
+    /// further retry up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Give up once `deadline` has passed since the call started. A
+    /// receipt wait is cut short at the deadline.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Request a RECEIPT for each attempt and wait up to `timeout` for it.
+    /// Without this an attempt succeeds once the frame is queued.
+    pub fn confirm(mut self, timeout: Duration) -> Self {
+        self.receipt_timeout = Some(timeout);
+        self
+    }
+
+    /// Delay before retrying after failed attempt number `attempt`
+    /// (starting at 1).
+    pub(crate) fn delay_after(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let policy =
+            RetryPolicy::new().backoff(Duration::from_millis(100), Duration::from_millis(500));
+        let delays: Vec<u64> = (1..=5)
+            .map(|attempt| policy.delay_after(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.delay_after(100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn max_attempts_is_at_least_one() {
+        assert_eq!(RetryPolicy::new().max_attempts(0).max_attempts, 1);
+    }
+}
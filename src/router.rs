@@ -0,0 +1,536 @@
+//! Message-driven services: route inbound MESSAGE frames to async handlers.
+//!
+//! A [`Router`] maps destinations and content types to handlers. Passing it
+//! to `Connection::serve()` subscribes to every routed destination and runs
+//! the matching handler for each message: the message is ACKed when the
+//! handler returns `Ok`, and settled according to the route's
+//! [`FailurePolicy`] when it returns `Err` or panics.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, BoxFuture, FutureExt};
+use tokio::sync::mpsc;
+
+use crate::connection::{AckMode, ConnError, Connection, NackOptions};
+use crate::events::{self, ConnectionEvent};
+use crate::message::{Deadline, Message};
+use crate::subscription::Subscription;
+
+/// An async handler registered with a [`Router`].
+type Handler = Arc<dyn Fn(Message) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// How a [`Router`] settles a message whose handler returned `Err` or
+/// panicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// NACK the message so the broker redelivers or dead-letters it.
+    #[default]
+    Nack,
+    /// ACK the message, dropping it. For handlers whose failures are not
+    /// worth retrying, such as malformed input.
+    Ack,
+}
+
+#[derive(Clone)]
+struct Route {
+    destination: Option<String>,
+    content_type: Option<String>,
+    handler: Handler,
+    /// Overrides the router's policy for this route.
+    failure_policy: Option<FailurePolicy>,
+}
+
+impl Route {
+    /// How well this route fits a message from `destination` with
+    /// `content_type`, or `None` if it does not apply. Routes naming both
+    /// fit best, then content-type-only routes, then destination-only ones.
+    fn score(&self, destination: &str, content_type: Option<&str>) -> Option<u8> {
+        if self
+            .destination
+            .as_deref()
+            .is_some_and(|d| d != destination)
+        {
+            return None;
+        }
+        let content_type_matches = match &self.content_type {
+            None => false,
+            Some(wanted) => content_type.is_some_and(|ct| media_type_eq(wanted, ct)),
+        };
+        match (&self.destination, &self.content_type) {
+            (Some(_), Some(_)) if content_type_matches => Some(3),
+            (None, Some(_)) if content_type_matches => Some(2),
+            (Some(_), None) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Compare media types, ignoring parameters such as `charset` and case.
+fn media_type_eq(a: &str, b: &str) -> bool {
+    let media_type = |s: &str| {
+        s.split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase()
+    };
+    media_type(a) == media_type(b)
+}
+
+/// Routes inbound messages to async handlers by destination and content
+/// type.
+///
+/// Each destination named by a route is subscribed to once, in
+/// `client-individual` ack mode, so wildcard destinations understood by the
+/// broker (such as RabbitMQ's `/topic/orders.*`) work as patterns. For
+/// every message the best-fitting route runs:
+///
+/// 1. a route registered for the subscription's destination and the
+///    message's `content-type`,
+/// 2. a content-type route registered for any destination,
+/// 3. a route registered for the destination with any content type.
+///
+/// Among equally specific routes the first registered wins. Content types
+/// are compared without parameters, so `application/json; charset=utf-8`
+/// matches `application/json`. Messages without a `content-type` header
+/// only reach content-type routes when
+/// [`sniff_content_type`](Self::sniff_content_type) is on. Messages no route
+/// fits are NACKed without requeueing, since no redelivery would find a
+/// route either; [`unrouted`](Self::unrouted) changes that.
+///
+/// When a handler returns `Err` or panics, the message is NACKed, or ACKed
+/// if [`failure_policy`](Self::failure_policy) or the route's
+/// [`on_failure`](Self::on_failure) says so, and a
+/// `ConnectionEvent::HandlerFailed` is emitted. A panicking handler does not
+/// stop the router.
+///
+/// With a [`processing_deadline`](Self::processing_deadline), a handler
+/// that has not finished in time is cancelled, its message NACKed, and a
+/// `ConnectionEvent::HandlerTimedOut` emitted, so a stuck handler cannot
+/// hold on to a message indefinitely. Handlers that need longer push the
+/// deadline back with `Message::extend()`.
+///
+/// Messages of one destination are handled one at a time, in order;
+/// different destinations are handled concurrently.
+///
+/// # Example
+///
+/// ```ignore
+/// use iridium_stomp::{FailurePolicy, Message, Router};
+///
+/// let router = Router::new()
+///     .route("/queue/orders", |msg: Message| async move {
+///         println!("order: {:?}", msg.body());
+///         Ok::<_, String>(())
+///     })
+///     .route_content_type("/queue/orders", "application/json", |msg: Message| async move {
+///         let order: Order = serde_json::from_slice(msg.body())?;
+///         process(order).await
+///     })
+///     // malformed orders would fail again; drop them instead of retrying
+///     .on_failure(FailurePolicy::Ack);
+///
+/// conn.serve(router).await?;
+/// ```
+#[derive(Default, Clone)]
+pub struct Router {
+    routes: Vec<Route>,
+    failure_policy: FailurePolicy,
+    sniff_content_type: bool,
+    processing_deadline: Option<Duration>,
+    /// How messages no route fits are NACKed; see `unrouted()`.
+    unrouted: Option<NackOptions>,
+}
+
+impl Router {
+    /// Create a router without routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle messages from `destination`, whatever their content type.
+    pub fn route<F, Fut, E>(self, destination: &str, handler: F) -> Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.add(Some(destination), None, handler)
+    }
+
+    /// Handle messages from `destination` whose `content-type` is
+    /// `content_type`.
+    pub fn route_content_type<F, Fut, E>(
+        self,
+        destination: &str,
+        content_type: &str,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.add(Some(destination), Some(content_type), handler)
+    }
+
+    /// Handle messages whose `content-type` is `content_type`, from any
+    /// routed destination.
+    ///
+    /// Content-type routes do not subscribe to anything themselves; they
+    /// apply to the destinations named by other routes.
+    pub fn content_type<F, Fut, E>(self, content_type: &str, handler: F) -> Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.add(None, Some(content_type), handler)
+    }
+
+    /// Settle messages of failed handlers with `policy` (default
+    /// [`FailurePolicy::Nack`]), unless their route overrides it.
+    pub fn failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    /// Settle messages of the most recently added route with `policy` when
+    /// its handler fails, overriding the router's
+    /// [`failure_policy`](Self::failure_policy). Has no effect before the
+    /// first route is added.
+    pub fn on_failure(mut self, policy: FailurePolicy) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.failure_policy = Some(policy);
+        }
+        self
+    }
+
+    /// Route messages without a `content-type` header by the media type
+    /// guessed from their body (see [`Frame::sniff_content_type`]), so
+    /// that, say, an untyped JSON body reaches an `application/json` route.
+    /// Off by default.
+    ///
+    /// [`Frame::sniff_content_type`]: crate::Frame::sniff_content_type
+    pub fn sniff_content_type(mut self, enabled: bool) -> Self {
+        self.sniff_content_type = enabled;
+        self
+    }
+
+    /// Give each handler `deadline` to finish with its message, counted
+    /// from when it starts. Handlers may extend it with
+    /// `Message::extend()`. No deadline by default.
+    pub fn processing_deadline(mut self, deadline: Duration) -> Self {
+        self.processing_deadline = Some(deadline);
+        self
+    }
+
+    /// NACK messages no route fits with `options`. By default they are
+    /// NACKed with `requeue: false` and the reason `no route`, so the broker
+    /// dead-letters or discards them instead of redelivering them forever.
+    pub fn unrouted(mut self, options: NackOptions) -> Self {
+        self.unrouted = Some(options);
+        self
+    }
+
+    /// The NACK options for messages no route fits.
+    fn unrouted_options(&self) -> NackOptions {
+        self.unrouted.clone().unwrap_or_else(|| NackOptions {
+            requeue: false,
+            reason: Some("no route".to_string()),
+        })
+    }
+
+    fn add<F, Fut, E>(
+        mut self,
+        destination: Option<&str>,
+        content_type: Option<&str>,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        let handler: Handler = Arc::new(move |msg| {
+            handler(msg)
+                .map(|result| result.map_err(|e| e.to_string()))
+                .boxed()
+        });
+        self.routes.push(Route {
+            destination: destination.map(str::to_string),
+            content_type: content_type.map(str::to_string),
+            handler,
+            failure_policy: None,
+        });
+        self
+    }
+
+    /// The distinct destinations named by the routes, in registration
+    /// order.
+    fn destinations(&self) -> Vec<String> {
+        let mut destinations: Vec<String> = Vec::new();
+        for route in &self.routes {
+            if let Some(d) = &route.destination
+                && !destinations.contains(d)
+            {
+                destinations.push(d.clone());
+            }
+        }
+        destinations
+    }
+
+    /// The best-fitting route for a message, if any.
+    fn select(&self, destination: &str, content_type: Option<&str>) -> Option<&Route> {
+        let mut best: Option<(u8, &Route)> = None;
+        for route in &self.routes {
+            if let Some(score) = route.score(destination, content_type)
+                && best.is_none_or(|(b, _)| score > b)
+            {
+                best = Some((score, route));
+            }
+        }
+        best.map(|(_, route)| route)
+    }
+
+    /// Subscribe to every routed destination and return the future that
+    /// dispatches their messages until the subscriptions end. Handler
+    /// failures are reported on `event_tx`.
+    pub(crate) async fn start(
+        self,
+        conn: &Connection,
+        event_tx: Option<mpsc::Sender<ConnectionEvent>>,
+    ) -> Result<impl Future<Output = ()> + use<>, ConnError> {
+        let destinations = self.destinations();
+        if destinations.is_empty() {
+            return Err(ConnError::Protocol(
+                "router has no destination routes to subscribe to".into(),
+            ));
+        }
+        let mut subscriptions = Vec::with_capacity(destinations.len());
+        for destination in &destinations {
+            subscriptions.push(
+                conn.subscribe(destination, AckMode::ClientIndividual)
+                    .await?,
+            );
+        }
+        let router = Arc::new(self);
+        Ok(future::join_all(
+            subscriptions
+                .into_iter()
+                .map(move |sub| dispatch(router.clone(), sub, event_tx.clone())),
+        )
+        .map(|_| ()))
+    }
+}
+
+impl std::fmt::Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field("destinations", &self.destinations())
+            .field("routes", &self.routes.len())
+            .field("failure_policy", &self.failure_policy)
+            .field("sniff_content_type", &self.sniff_content_type)
+            .field("processing_deadline", &self.processing_deadline)
+            .field("unrouted", &self.unrouted_options())
+            .finish()
+    }
+}
+
+/// Run the routed handler for each message of `sub`, then ACK or NACK it.
+async fn dispatch(
+    router: Arc<Router>,
+    mut sub: Subscription,
+    event_tx: Option<mpsc::Sender<ConnectionEvent>>,
+) {
+    while let Some(frame) = sub.recv().await {
+        let msg = Message::from_frame(frame)
+            .with_subscription_destination(sub.destination_name().clone());
+        let Some(message_id) = msg.message_id().map(str::to_string) else {
+            tracing::warn!(
+                destination = %sub.destination(),
+                "MESSAGE without message-id cannot be acknowledged, skipping",
+            );
+            continue;
+        };
+        let content_type = if router.sniff_content_type {
+            msg.frame().sniff_content_type()
+        } else {
+            msg.header("content-type")
+        };
+        let settled = match router.select(sub.destination(), content_type) {
+            Some(route) => {
+                let policy = route.failure_policy.unwrap_or(router.failure_policy);
+                let started = Instant::now();
+                let deadline = router
+                    .processing_deadline
+                    .map(|limit| Deadline::new(started + limit));
+                let msg = match &deadline {
+                    Some(deadline) => msg.with_deadline(deadline.clone()),
+                    None => msg,
+                };
+                // Run the handler in its own unwind boundary so a panic
+                // settles this message instead of ending the router
+                let handler = AssertUnwindSafe(async { (route.handler)(msg).await }).catch_unwind();
+                let outcome = match &deadline {
+                    Some(deadline) => within(deadline, handler).await,
+                    None => Some(handler.await),
+                };
+                let Some(outcome) = outcome else {
+                    let elapsed = started.elapsed();
+                    tracing::warn!(
+                        destination = %sub.destination(),
+                        message_id = %message_id,
+                        elapsed = ?elapsed,
+                        "handler missed its processing deadline, sending NACK",
+                    );
+                    events::emit(
+                        &event_tx,
+                        ConnectionEvent::HandlerTimedOut {
+                            destination: sub.destination().to_string(),
+                            message_id: message_id.clone(),
+                            elapsed,
+                        },
+                    );
+                    if let Err(e) = sub.nack(&message_id).await {
+                        tracing::warn!(
+                            destination = %sub.destination(),
+                            message_id = %message_id,
+                            error = %e,
+                            "failed to settle routed message",
+                        );
+                    }
+                    continue;
+                };
+                let failure = match outcome {
+                    Ok(Ok(())) => None,
+                    Ok(Err(error)) => Some((error, false)),
+                    Err(panic) => Some((panic_message(&*panic), true)),
+                };
+                match failure {
+                    None => sub.ack(&message_id).await,
+                    Some((error, panicked)) => {
+                        tracing::warn!(
+                            destination = %sub.destination(),
+                            message_id = %message_id,
+                            error = %error,
+                            panicked,
+                            policy = ?policy,
+                            "handler failed",
+                        );
+                        events::emit(
+                            &event_tx,
+                            ConnectionEvent::HandlerFailed {
+                                destination: sub.destination().to_string(),
+                                message_id: message_id.clone(),
+                                error,
+                                panicked,
+                                policy,
+                            },
+                        );
+                        match policy {
+                            FailurePolicy::Nack => sub.nack(&message_id).await,
+                            FailurePolicy::Ack => sub.ack(&message_id).await,
+                        }
+                    }
+                }
+            }
+            None => {
+                tracing::warn!(
+                    destination = %sub.destination(),
+                    message_id = %message_id,
+                    content_type = content_type.unwrap_or(""),
+                    "no route for message, sending NACK",
+                );
+                sub.nack_with_options(&message_id, router.unrouted_options())
+                    .await
+            }
+        };
+        if let Err(e) = settled {
+            tracing::warn!(
+                destination = %sub.destination(),
+                message_id = %message_id,
+                error = %e,
+                "failed to settle routed message",
+            );
+        }
+    }
+}
+
+/// Run `fut` until it completes or `deadline` passes, whichever comes
+/// first. The deadline is read again whenever it comes up, so extensions
+/// made meanwhile count. Returns `None` if the deadline passed.
+async fn within<F: Future>(deadline: &Deadline, fut: F) -> Option<F::Output> {
+    tokio::pin!(fut);
+    loop {
+        let at = deadline.get();
+        tokio::select! {
+            output = &mut fut => return Some(output),
+            _ = tokio::time::sleep_until(at.into()) => {
+                if deadline.get() <= Instant::now() {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// The message of a caught panic, when it has one.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string payload");
+    format!("handler panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(_: Message) -> future::Ready<Result<(), String>> {
+        future::ready(Ok(()))
+    }
+
+    #[test]
+    fn most_specific_route_wins() {
+        let router = Router::new()
+            .route("/queue/a", ok)
+            .content_type("text/plain", ok)
+            .route_content_type("/queue/a", "application/json", ok);
+
+        let pick = |dest: &str, ct: Option<&str>| {
+            router
+                .select(dest, ct)
+                .map(|route| (route.destination.clone(), route.content_type.clone()))
+        };
+        assert_eq!(
+            pick("/queue/a", Some("application/json; charset=utf-8")),
+            Some((Some("/queue/a".into()), Some("application/json".into())))
+        );
+        assert_eq!(
+            pick("/queue/a", Some("Text/Plain")),
+            Some((None, Some("text/plain".into())))
+        );
+        assert_eq!(
+            pick("/queue/a", None),
+            Some((Some("/queue/a".into()), None))
+        );
+        assert_eq!(pick("/queue/b", Some("application/json")), None);
+    }
+
+    #[test]
+    fn destinations_are_deduplicated() {
+        let router = Router::new()
+            .route("/queue/a", ok)
+            .content_type("text/plain", ok)
+            .route_content_type("/queue/b", "text/plain", ok)
+            .route_content_type("/queue/a", "application/json", ok);
+        assert_eq!(router.destinations(), vec!["/queue/a", "/queue/b"]);
+    }
+}
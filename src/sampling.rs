@@ -0,0 +1,131 @@
+use crate::frame::Frame;
+use crate::task::{self, TaskLabels};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Callback invoked with a sampled inbound MESSAGE frame.
+pub type SampleCallback = Arc<dyn Fn(Frame) + Send + Sync>;
+
+/// Number of sampled frames that may wait for the callback before further
+/// samples are dropped.
+const SAMPLE_QUEUE_CAPACITY: usize = 64;
+
+/// Inspects a fraction of inbound MESSAGE frames.
+///
+/// Registered with `ConnectOptions::on_message_sample()`. Sampled frames
+/// are cloned and handed to a separate task that runs the callback, so a
+/// slow callback never delays message delivery. If the callback falls
+/// behind, further samples are dropped rather than queued without bound.
+#[derive(Clone)]
+pub struct MessageSampler {
+    rate: f64,
+    callback: SampleCallback,
+}
+
+impl MessageSampler {
+    /// Sample `rate` of inbound messages (clamped to `0.0..=1.0`) and pass
+    /// each sample to `callback`.
+    pub fn new<F>(rate: f64, callback: F) -> Self
+    where
+        F: Fn(Frame) + Send + Sync + 'static,
+    {
+        Self {
+            rate: if rate.is_nan() {
+                0.0
+            } else {
+                rate.clamp(0.0, 1.0)
+            },
+            callback: Arc::new(callback),
+        }
+    }
+
+    /// The fraction of messages sampled.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Start the task that runs the callback and return the handle used by
+    /// the connection to submit samples.
+    pub(crate) fn spawn(&self, labels: &TaskLabels) -> SampleGate {
+        let (tx, mut rx) = mpsc::channel::<Frame>(SAMPLE_QUEUE_CAPACITY);
+        let callback = self.callback.clone();
+        task::spawn("sampler", labels, async move {
+            while let Some(frame) = rx.recv().await {
+                callback(frame);
+            }
+        });
+        SampleGate {
+            rate: self.rate,
+            seen: 0,
+            tx,
+        }
+    }
+}
+
+impl std::fmt::Debug for MessageSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageSampler")
+            .field("rate", &self.rate)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decides which messages to sample and forwards them to the sampler task.
+///
+/// Sampling is deterministic: with a rate of `0.01` exactly every hundredth
+/// message is sampled, so no random number generator sits on the hot path.
+pub(crate) struct SampleGate {
+    rate: f64,
+    seen: u64,
+    tx: mpsc::Sender<Frame>,
+}
+
+impl SampleGate {
+    /// Count one message and return whether it should be sampled.
+    fn should_sample(&mut self) -> bool {
+        let before = (self.seen as f64 * self.rate).floor();
+        self.seen += 1;
+        (self.seen as f64 * self.rate).floor() > before
+    }
+
+    /// Offer an inbound MESSAGE; clones and submits it if it is sampled.
+    pub(crate) fn offer(&mut self, frame: &Frame) {
+        if self.should_sample() && self.tx.try_send(frame.clone()).is_err() {
+            tracing::debug!("message sampler is busy, dropping sample");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gate(rate: f64) -> SampleGate {
+        let (tx, _rx) = mpsc::channel(1);
+        SampleGate {
+            rate: MessageSampler::new(rate, |_| {}).rate,
+            seen: 0,
+            tx,
+        }
+    }
+
+    #[test]
+    fn samples_the_configured_fraction() {
+        let mut g = gate(0.1);
+        let sampled = (0..1000).filter(|_| g.should_sample()).count();
+        assert_eq!(sampled, 100);
+
+        let mut g = gate(1.0);
+        assert!((0..10).all(|_| g.should_sample()));
+
+        let mut g = gate(0.0);
+        assert!((0..10).all(|_| !g.should_sample()));
+    }
+
+    #[test]
+    fn rate_is_clamped() {
+        assert_eq!(MessageSampler::new(2.0, |_| {}).rate(), 1.0);
+        assert_eq!(MessageSampler::new(-1.0, |_| {}).rate(), 0.0);
+        assert_eq!(MessageSampler::new(f64::NAN, |_| {}).rate(), 0.0);
+    }
+}
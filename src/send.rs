@@ -0,0 +1,204 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::codec::ContentLengthPolicy;
+use crate::frame::Frame;
+
+/// Header stamped by [`SendOptions::auto_message_id`] unless another is
+/// chosen with [`SendOptions::id_header`].
+pub const MESSAGE_ID_HEADER: &str = "message-id";
+
+/// How [`SendOptions::auto_message_id`] generates ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdStyle {
+    /// A random (version 4) UUID such as
+    /// `3f2b8c1e-9a4d-4e7b-8c21-5d6f0a1b2c3d`. Unique across processes.
+    Uuid,
+    /// A decimal counter shared by every connection in the process,
+    /// starting at 1. Unique only within the process, but short and
+    /// ordered, which makes logs easy to follow.
+    Seq,
+}
+
+impl IdStyle {
+    /// Generate a new id in this style.
+    pub fn generate(self) -> String {
+        match self {
+            IdStyle::Uuid => uuid_v4(),
+            IdStyle::Seq => {
+                static MESSAGE_SEQ: AtomicU64 = AtomicU64::new(1);
+                MESSAGE_SEQ.fetch_add(1, Ordering::Relaxed).to_string()
+            }
+        }
+    }
+}
+
+/// A version 4 UUID from the standard library's randomly keyed hasher,
+/// which is unpredictable enough for identifiers without a `rand`
+/// dependency.
+fn uuid_v4() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let state = RandomState::new();
+    let bits = (u128::from(state.hash_one(n)) << 64) | u128::from(state.hash_one(!n));
+    // Version 4, variant RFC 4122
+    let bits = (bits & !(0xF << 76) | (0x4 << 76)) & !(0x3 << 62) | (0x2 << 62);
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Options for `Connection::send_with_options()`.
+///
+/// The default sends the frame as it is, like `Connection::send_frame()`.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::{IdStyle, SendOptions};
+///
+/// let options = SendOptions::new()
+///     .auto_message_id(IdStyle::Uuid)
+///     .id_header("correlation-id");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendOptions {
+    pub(crate) id_style: Option<IdStyle>,
+    pub(crate) id_header: String,
+    pub(crate) content_length_policy: Option<ContentLengthPolicy>,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            id_style: None,
+            id_header: MESSAGE_ID_HEADER.to_string(),
+            content_length_policy: None,
+        }
+    }
+}
+
+impl SendOptions {
+    /// Create options with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp a client-assigned id on SEND frames that do not carry the id
+    /// header yet. A frame that already has one keeps it, and that id is
+    /// the one reported in the [`SendResult`].
+    pub fn auto_message_id(mut self, style: IdStyle) -> Self {
+        self.id_style = Some(style);
+        self
+    }
+
+    /// Header carrying the id, `message-id` by default. Some brokers
+    /// replace `message-id` with their own on delivery; use a header such
+    /// as `correlation-id` to keep the client id visible to consumers.
+    pub fn id_header(mut self, name: impl Into<String>) -> Self {
+        self.id_header = name.into();
+        self
+    }
+
+    /// Decide when the encoder adds `content-length` to this frame,
+    /// overriding `ConnectOptions::content_length_policy()`. Useful when a
+    /// broker mishandles `content-length` on some destinations only.
+    pub fn content_length_policy(mut self, policy: ContentLengthPolicy) -> Self {
+        self.content_length_policy = Some(policy);
+        self
+    }
+
+    /// Apply the options to an outbound frame, returning the frame and the
+    /// id it carries when ids are requested.
+    pub(crate) fn apply(&self, frame: Frame) -> (Frame, Option<String>) {
+        let Some(style) = self.id_style else {
+            return (frame, None);
+        };
+        if frame.command != "SEND" {
+            return (frame, None);
+        }
+        if let Some(id) = frame.get_header(&self.id_header) {
+            let id = id.to_string();
+            return (frame, Some(id));
+        }
+        let id = style.generate();
+        (frame.header(&self.id_header, &id), Some(id))
+    }
+}
+
+/// Outcome of `Connection::send_with_options()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SendResult {
+    pub(crate) message_id: Option<String>,
+}
+
+impl SendResult {
+    /// The id the SEND carried in the configured header, when
+    /// [`SendOptions::auto_message_id`] was set, for logging or for
+    /// correlating with downstream processing.
+    pub fn message_id(&self) -> Option<&str> {
+        self.message_id.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuids_are_version_4_and_distinct() {
+        let a = IdStyle::Uuid.generate();
+        let b = IdStyle::Uuid.generate();
+        assert_ne!(a, b);
+        let groups: Vec<&str> = a.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|g| g.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('4'), "{}", a);
+        assert!(matches!(&groups[3][..1], "8" | "9" | "a" | "b"), "{}", a);
+    }
+
+    #[test]
+    fn seq_ids_increase() {
+        let a: u64 = IdStyle::Seq.generate().parse().unwrap();
+        let b: u64 = IdStyle::Seq.generate().parse().unwrap();
+        assert!(b > a);
+    }
+
+    #[test]
+    fn stamps_only_sends_without_an_id() {
+        let options = SendOptions::new().auto_message_id(IdStyle::Seq);
+        let (frame, id) = options.apply(Frame::new("SEND"));
+        assert_eq!(frame.get_header(MESSAGE_ID_HEADER), id.as_deref());
+        assert!(id.is_some());
+
+        let (frame, id) = options.apply(Frame::new("SEND").header("message-id", "mine"));
+        assert_eq!(id.as_deref(), Some("mine"));
+        assert_eq!(frame.get_header("message-id"), Some("mine"));
+
+        let (frame, id) = options.apply(Frame::new("BEGIN"));
+        assert_eq!(id, None);
+        assert_eq!(frame.get_header("message-id"), None);
+
+        let (_, id) = SendOptions::new().apply(Frame::new("SEND"));
+        assert_eq!(id, None);
+    }
+
+    #[test]
+    fn custom_id_header() {
+        let options = SendOptions::new()
+            .auto_message_id(IdStyle::Uuid)
+            .id_header("correlation-id");
+        let (frame, id) = options.apply(Frame::new("SEND"));
+        assert_eq!(frame.get_header("correlation-id"), id.as_deref());
+        assert_eq!(frame.get_header("message-id"), None);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::frame::Frame;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Header identifying the publisher that stamped a SEND frame.
+pub const PUBLISHER_ID_HEADER: &str = "publisher-id";
+
+/// Header carrying the publisher's sequence number for a SEND frame.
+pub const PUBLISHER_SEQ_HEADER: &str = "publisher-seq";
+
+/// Persistent storage for the last sequence number issued by a publisher.
+///
+/// Used by [`PublisherSequence`] so numbering continues across process
+/// restarts instead of starting again at 1, which would make new messages
+/// look like duplicates of old ones. Implementations must be durable enough
+/// for the application's needs; the library calls `save()` before any frame
+/// carrying a newly reserved number is sent. `save()` may block: the
+/// connection calls it on tokio's blocking thread pool.
+pub trait SequenceStore: Send + Sync {
+    /// The last sequence number reserved for `publisher_id`, or 0 if the
+    /// publisher has never sent anything.
+    fn load(&self, publisher_id: &str) -> io::Result<u64>;
+
+    /// Record `seq` as the last sequence number reserved for `publisher_id`.
+    fn save(&self, publisher_id: &str, seq: u64) -> io::Result<()>;
+}
+
+/// A `SequenceStore` that keeps numbers in memory only.
+///
+/// Numbering restarts at 1 with each process. Suitable when duplicates only
+/// need to be detected within a single process lifetime.
+#[derive(Debug, Default)]
+pub struct MemorySequenceStore {
+    last: Mutex<HashMap<String, u64>>,
+}
+
+impl MemorySequenceStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SequenceStore for MemorySequenceStore {
+    fn load(&self, publisher_id: &str) -> io::Result<u64> {
+        let last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(last.get(publisher_id).copied().unwrap_or(0))
+    }
+
+    fn save(&self, publisher_id: &str, seq: u64) -> io::Result<()> {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        last.insert(publisher_id.to_string(), seq);
+        Ok(())
+    }
+}
+
+/// A `SequenceStore` backed by a small text file.
+///
+/// The file holds one `publisher-id<TAB>seq` line per publisher. Each save
+/// writes a temporary file next to it, syncs it to disk and renames it into
+/// place, so a crash never leaves a half-written file behind or loses a
+/// reservation that frames were already sent under.
+#[derive(Debug, Clone)]
+pub struct FileSequenceStore {
+    path: PathBuf,
+}
+
+impl FileSequenceStore {
+    /// Store sequence numbers in the file at `path`. The file is created on
+    /// the first save.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The path of the backing file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_all(&self) -> io::Result<Vec<(String, u64)>> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        text.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.rsplit_once('\t')
+                    .and_then(|(id, seq)| Some((id.to_string(), seq.parse().ok()?)))
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("malformed sequence entry '{}'", line),
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+impl SequenceStore for FileSequenceStore {
+    fn load(&self, publisher_id: &str) -> io::Result<u64> {
+        Ok(self
+            .read_all()?
+            .into_iter()
+            .find(|(id, _)| id == publisher_id)
+            .map_or(0, |(_, seq)| seq))
+    }
+
+    fn save(&self, publisher_id: &str, seq: u64) -> io::Result<()> {
+        let mut entries = self.read_all()?;
+        match entries.iter_mut().find(|(id, _)| id == publisher_id) {
+            Some(entry) => entry.1 = seq,
+            None => entries.push((publisher_id.to_string(), seq)),
+        }
+        let text: String = entries
+            .iter()
+            .map(|(id, seq)| format!("{}\t{}\n", id, seq))
+            .collect();
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        // The rename is durable once the directory entry is
+        #[cfg(unix)]
+        {
+            let dir = match self.path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Stamps outbound SEND frames with a publisher id and sequence number.
+///
+/// Registered with `ConnectOptions::publisher_sequence()`. Every SEND frame
+/// gets `publisher-id` and `publisher-seq` headers; the number increases by
+/// one per frame and continues from the value held in the
+/// [`SequenceStore`] when the connection is opened. A frame is stamped
+/// once, when it is handed to `send_frame()`, so re-sending the same frame
+/// (for example after a reconnect) repeats its number and lets consumers or
+/// a broker-side dedup plugin discard the copy, e.g. with
+/// [`DuplicateFilter`]. Frames that already carry a `publisher-seq` header
+/// are left untouched.
+///
+/// By default every number is saved before use. With
+/// [`reserve`](Self::reserve) the store is written once per block of
+/// numbers instead; after a restart the sequence then skips the unused rest
+/// of the block, which keeps it increasing.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::{ConnectOptions, FileSequenceStore, PublisherSequence};
+///
+/// let sequence = PublisherSequence::new("orders-service-1")
+///     .store(FileSequenceStore::new("/var/lib/orders/publisher.seq"))
+///     .reserve(100);
+/// let options = ConnectOptions::default().publisher_sequence(sequence);
+/// ```
+#[derive(Clone)]
+pub struct PublisherSequence {
+    publisher_id: String,
+    store: Arc<dyn SequenceStore>,
+    block: u64,
+}
+
+impl PublisherSequence {
+    /// Number frames for `publisher_id`, keeping the counter in memory.
+    ///
+    /// The id should be stable for the publisher across restarts and unique
+    /// among publishers sending to the same destinations.
+    pub fn new(publisher_id: impl Into<String>) -> Self {
+        Self {
+            publisher_id: publisher_id.into(),
+            store: Arc::new(MemorySequenceStore::new()),
+            block: 1,
+        }
+    }
+
+    /// Persist the counter in `store`.
+    pub fn store(mut self, store: impl SequenceStore + 'static) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Reserve numbers from the store `block` at a time (minimum 1).
+    pub fn reserve(mut self, block: u64) -> Self {
+        self.block = block.max(1);
+        self
+    }
+
+    /// The publisher id stamped on outbound frames.
+    pub fn publisher_id(&self) -> &str {
+        &self.publisher_id
+    }
+
+    /// Load the last reserved number and return the stamper used by the
+    /// connection. Called once while the connection is set up.
+    pub(crate) fn open(&self) -> io::Result<Sequencer> {
+        let last = self.store.load(&self.publisher_id)?;
+        Ok(Sequencer {
+            config: self.clone(),
+            state: tokio::sync::Mutex::new(SequencerState {
+                last,
+                reserved: last,
+            }),
+        })
+    }
+}
+
+impl fmt::Debug for PublisherSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PublisherSequence")
+            .field("publisher_id", &self.publisher_id)
+            .field("block", &self.block)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Per-connection sequence state created by `PublisherSequence::open()`.
+pub(crate) struct Sequencer {
+    config: PublisherSequence,
+    /// Held across a store save, so numbers are stamped in order.
+    state: tokio::sync::Mutex<SequencerState>,
+}
+
+struct SequencerState {
+    /// Last number stamped on a frame.
+    last: u64,
+    /// Highest number recorded in the store.
+    reserved: u64,
+}
+
+impl Sequencer {
+    /// Stamp `frame` if it is a SEND without a sequence number, reserving a
+    /// new block from the store when the current one is used up.
+    pub(crate) async fn stamp(&self, frame: Frame) -> io::Result<Frame> {
+        if frame.command != "SEND" || frame.get_header(PUBLISHER_SEQ_HEADER).is_some() {
+            return Ok(frame);
+        }
+        let config = &self.config;
+        let mut state = self.state.lock().await;
+        let exhausted = || io::Error::other("publisher sequence numbers exhausted");
+        let seq = state.last.checked_add(1).ok_or_else(exhausted)?;
+        if seq > state.reserved {
+            let reserved = seq.checked_add(config.block - 1).ok_or_else(exhausted)?;
+            let store = config.store.clone();
+            let publisher_id = config.publisher_id.clone();
+            tokio::task::spawn_blocking(move || store.save(&publisher_id, reserved))
+                .await
+                .map_err(io::Error::other)??;
+            state.reserved = reserved;
+        }
+        state.last = seq;
+        Ok(frame
+            .header(PUBLISHER_ID_HEADER, config.publisher_id.as_str())
+            .header(PUBLISHER_SEQ_HEADER, seq.to_string()))
+    }
+}
+
+/// Consumer-side helper that detects re-delivered publisher sequence
+/// numbers.
+///
+/// Tracks the highest `publisher-seq` seen per `publisher-id` and reports a
+/// frame as a duplicate when its number is not above that. Frames without
+/// sequence headers are never duplicates. Because only the highest number
+/// is kept, this assumes each publisher's messages arrive in order, which
+/// holds for a single destination and a single consumer.
+#[derive(Debug, Default, Clone)]
+pub struct DuplicateFilter {
+    highest: HashMap<String, u64>,
+}
+
+impl DuplicateFilter {
+    /// Create a filter that has seen nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `frame` and return `true` if it repeats a number already seen
+    /// from the same publisher.
+    pub fn is_duplicate(&mut self, frame: &Frame) -> bool {
+        let (Some(id), Some(seq)) = (
+            frame.get_header(PUBLISHER_ID_HEADER),
+            frame
+                .get_header(PUBLISHER_SEQ_HEADER)
+                .and_then(|s| s.parse::<u64>().ok()),
+        ) else {
+            return false;
+        };
+        match self.highest.get_mut(id) {
+            Some(highest) if seq <= *highest => true,
+            Some(highest) => {
+                *highest = seq;
+                false
+            }
+            None => {
+                self.highest.insert(id.to_string(), seq);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send() -> Frame {
+        Frame::new("SEND").header("destination", "/queue/a")
+    }
+
+    #[tokio::test]
+    async fn stamps_increasing_numbers_and_skips_other_frames() {
+        let sequencer = PublisherSequence::new("p1").open().unwrap();
+        let first = sequencer.stamp(send()).await.unwrap();
+        let second = sequencer.stamp(send()).await.unwrap();
+        assert_eq!(first.get_header(PUBLISHER_ID_HEADER), Some("p1"));
+        assert_eq!(first.get_header(PUBLISHER_SEQ_HEADER), Some("1"));
+        assert_eq!(second.get_header(PUBLISHER_SEQ_HEADER), Some("2"));
+
+        let ack = sequencer.stamp(Frame::new("ACK")).await.unwrap();
+        assert!(ack.get_header(PUBLISHER_SEQ_HEADER).is_none());
+
+        // An already stamped frame keeps its number when sent again
+        let again = sequencer.stamp(first.clone()).await.unwrap();
+        assert_eq!(again, first);
+    }
+
+    #[tokio::test]
+    async fn reserved_blocks_continue_after_reopen() {
+        let store = Arc::new(MemorySequenceStore::new());
+        let sequence = PublisherSequence {
+            store: store.clone(),
+            ..PublisherSequence::new("p1").reserve(10)
+        };
+        let sequencer = sequence.open().unwrap();
+        for _ in 0..3 {
+            sequencer.stamp(send()).await.unwrap();
+        }
+        assert_eq!(store.load("p1").unwrap(), 10);
+
+        let reopened = sequence.open().unwrap();
+        let frame = reopened.stamp(send()).await.unwrap();
+        assert_eq!(frame.get_header(PUBLISHER_SEQ_HEADER), Some("11"));
+    }
+
+    #[tokio::test]
+    async fn exhausted_sequence_is_an_error() {
+        let store = MemorySequenceStore::new();
+        store.save("p1", u64::MAX - 1).unwrap();
+        let sequencer = PublisherSequence::new("p1")
+            .store(store)
+            .reserve(10)
+            .open()
+            .unwrap();
+        assert!(sequencer.stamp(send()).await.is_err());
+    }
+
+    #[test]
+    fn file_store_round_trips_several_publishers() {
+        let path = std::env::temp_dir().join(format!("iridium-seq-{}", std::process::id()));
+        let store = FileSequenceStore::new(&path);
+        assert_eq!(store.load("a").unwrap(), 0);
+        store.save("a", 5).unwrap();
+        store.save("b", 7).unwrap();
+        store.save("a", 6).unwrap();
+        assert_eq!(store.load("a").unwrap(), 6);
+        assert_eq!(store.load("b").unwrap(), 7);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn duplicate_filter_tracks_each_publisher() {
+        let stamp = |id: &str, seq: u64| {
+            send()
+                .header(PUBLISHER_ID_HEADER, id)
+                .header(PUBLISHER_SEQ_HEADER, seq.to_string())
+        };
+        let mut filter = DuplicateFilter::new();
+        assert!(!filter.is_duplicate(&stamp("a", 1)));
+        assert!(!filter.is_duplicate(&stamp("b", 1)));
+        assert!(!filter.is_duplicate(&stamp("a", 2)));
+        assert!(filter.is_duplicate(&stamp("a", 2)));
+        assert!(filter.is_duplicate(&stamp("a", 1)));
+        assert!(!filter.is_duplicate(&send()));
+    }
+}
@@ -1,17 +1,42 @@
+use crate::broker::BrokerProfile;
+use crate::connection::AckMode;
 use crate::connection::ConnError;
-use crate::connection::Connection;
+use crate::connection::NackOptions;
+use crate::connection::PendingAcks;
+use crate::connection::ServerError;
+use crate::connection::{Connection, WeakConnection};
+use crate::destination::Destination;
+use crate::diagnostics;
 use crate::frame::Frame;
 use futures::stream::Stream;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
-use std::task::{Context, Poll};
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker, ready};
+use std::time::Duration;
+use thiserror::Error;
+pub use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::ReusableBoxFuture;
 
 /// Options to configure a subscription. `headers` are forwarded to the
 /// broker as-is when sending the SUBSCRIBE frame and persisted locally so
 /// they can be re-sent on reconnect. This allows broker-specific durable
 /// subscription extensions to be used (for example ActiveMQ's durable
 /// subscription headers) while keeping the library generic.
+///
+/// Build it from `SubscriptionOptions::default()` with the builder methods:
+///
+/// ```
+/// use iridium_stomp::SubscriptionOptions;
+///
+/// let options = SubscriptionOptions::default()
+///     .headers(vec![("selector".to_string(), "region = 'EU'".to_string())])
+///     .ordered(true);
+/// ```
 #[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct SubscriptionOptions {
     /// Extra headers to include on the SUBSCRIBE frame.
     pub headers: Vec<(String, String)>,
@@ -19,6 +44,383 @@ pub struct SubscriptionOptions {
     /// Optional named queue to subscribe to (convenience; typically you can
     /// just put this in the `destination` argument). Kept for clarity.
     pub durable_queue: Option<String>,
+
+    /// Preserve message order end-to-end when the consumer falls behind.
+    ///
+    /// By default, a MESSAGE that arrives while this subscription's channel
+    /// is full is dropped so one slow consumer cannot stall the connection.
+    /// With `ordered` set, the connection instead waits for room, applying
+    /// backpressure to the socket reader: no gaps, no reordering relative to
+    /// the pending-ACK bookkeeping, but while it waits no frames are read
+    /// for any subscription on the connection, and a consumer stalled
+    /// longer than the heartbeat interval can get the connection dropped.
+    /// Prefer a dedicated connection for ordered consumers that may stall.
+    pub ordered: bool,
+
+    /// Most delivered messages the connection tracks for this subscription
+    /// while they wait for an ACK or NACK, or `None` for no limit. Only
+    /// `client` and `client-individual` subscriptions track messages. A
+    /// limit of 0 is treated as 1.
+    ///
+    /// Without a limit, a consumer that stops acknowledging grows the
+    /// tracked messages for as long as the broker keeps sending; set a
+    /// broker-side prefetch as well where the broker supports one.
+    pub max_pending: Option<usize>,
+
+    /// What happens to a message arriving while `max_pending` messages are
+    /// tracked.
+    pub pending_overflow: PendingOverflow,
+}
+
+impl SubscriptionOptions {
+    /// Extra headers for the SUBSCRIBE frame (builder style).
+    pub fn headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Subscribe to the named queue instead of the destination passed to
+    /// `subscribe_with_options()` (builder style).
+    pub fn durable_queue(mut self, queue: impl Into<String>) -> Self {
+        self.durable_queue = Some(queue.into());
+        self
+    }
+
+    /// Preserve message order end-to-end when the consumer falls behind
+    /// (builder style). See the `ordered` field.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Track at most `max` unacknowledged messages (builder style). See
+    /// the `max_pending` field.
+    pub fn max_pending(mut self, max: usize) -> Self {
+        self.max_pending = Some(max);
+        self
+    }
+
+    /// What to do with a message arriving while `max_pending` messages are
+    /// tracked (builder style).
+    pub fn pending_overflow(mut self, overflow: PendingOverflow) -> Self {
+        self.pending_overflow = overflow;
+        self
+    }
+}
+
+/// What a subscription does with a message that arrives while it already
+/// tracks its limit of unacknowledged messages
+/// (`SubscriptionOptions::max_pending`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PendingOverflow {
+    /// Wait for the application to ACK or NACK a message before delivering
+    /// this one. As with `SubscriptionOptions::ordered`, no frames are read
+    /// for any subscription on the connection meanwhile, so a consumer
+    /// stalled longer than the heartbeat interval can get the connection
+    /// dropped; the broker then redelivers what was unacknowledged.
+    #[default]
+    StopDelivering,
+    /// Stop tracking the oldest message, with a warning, and deliver this
+    /// one. The broker still considers the evicted message unacknowledged;
+    /// an ACK or NACK for it is still sent, and for `client` subscriptions a
+    /// later cumulative ACK covers it.
+    EvictOldest,
+    /// NACK the oldest message, with a warning, and deliver this one. The
+    /// broker redelivers or dead-letters it according to its own policy.
+    /// For `client` subscriptions brokers may apply the NACK cumulatively;
+    /// prefer `client-individual`. On STOMP 1.0, which has no NACK, the
+    /// message is evicted as with `EvictOldest`.
+    NackOldest,
+}
+
+impl PendingOverflow {
+    /// The policy's name, as used in logs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingOverflow::StopDelivering => "stop-delivering",
+            PendingOverflow::EvictOldest => "evict-oldest",
+            PendingOverflow::NackOldest => "nack-oldest",
+        }
+    }
+}
+
+/// A subscription's `max_pending` with its `pending_overflow` policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PendingLimit {
+    pub(crate) max: usize,
+    pub(crate) overflow: PendingOverflow,
+}
+
+impl PendingLimit {
+    pub(crate) fn new(max: Option<usize>, overflow: PendingOverflow) -> Option<Self> {
+        max.map(|max| Self {
+            max: max.max(1),
+            overflow,
+        })
+    }
+}
+
+/// How long `SubscriptionBuilder::start()` waits for the SUBSCRIBE receipt
+/// unless `receipt_timeout()` says otherwise.
+const DEFAULT_SUBSCRIBE_RECEIPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fluent builder for a subscription, returned by
+/// `Connection::subscription()`.
+///
+/// Collects every subscription setting by name instead of by position and
+/// subscribes on [`start`](Self::start). Broker-specific settings
+/// ([`prefetch`](Self::prefetch), [`durable`](Self::durable)) are turned
+/// into the headers of the broker chosen with [`broker`](Self::broker).
+/// Everything set here is kept for resubscribes after a reconnect, except
+/// the receipt request.
+///
+/// # Example
+///
+/// ```ignore
+/// use iridium_stomp::{AckMode, BrokerProfile};
+///
+/// let sub = conn
+///     .subscription("/topic/orders")
+///     .broker(BrokerProfile::ActiveMq)
+///     .ack(AckMode::Client)
+///     .prefetch(10)
+///     .durable("orders")
+///     .header("selector", "region = 'EU'")
+///     .receipt(true)
+///     .start()
+///     .await?;
+/// ```
+#[must_use = "nothing is subscribed until `start()` is called"]
+pub struct SubscriptionBuilder {
+    conn: Connection,
+    destination: String,
+    ack: AckMode,
+    broker: BrokerProfile,
+    headers: Vec<(String, String)>,
+    prefetch: Option<u32>,
+    durable: Option<String>,
+    queue: Option<String>,
+    ordered: bool,
+    max_pending: Option<usize>,
+    pending_overflow: PendingOverflow,
+    receipt: bool,
+    receipt_timeout: Duration,
+}
+
+impl SubscriptionBuilder {
+    pub(crate) fn new(conn: Connection, destination: &str) -> Self {
+        Self {
+            conn,
+            destination: destination.to_string(),
+            ack: AckMode::Auto,
+            broker: BrokerProfile::Generic,
+            headers: Vec::new(),
+            prefetch: None,
+            durable: None,
+            queue: None,
+            ordered: false,
+            max_pending: None,
+            pending_overflow: PendingOverflow::default(),
+            receipt: false,
+            receipt_timeout: DEFAULT_SUBSCRIBE_RECEIPT_TIMEOUT,
+        }
+    }
+
+    /// Acknowledgement mode (default `AckMode::Auto`).
+    pub fn ack(mut self, ack: AckMode) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    /// The broker whose conventions `prefetch()` and `durable()` follow
+    /// (default `BrokerProfile::Generic`, which supports neither).
+    pub fn broker(mut self, broker: BrokerProfile) -> Self {
+        self.broker = broker;
+        self
+    }
+
+    /// Let the broker have at most `count` unacknowledged messages in
+    /// flight to this subscription. Not available on Artemis, which limits
+    /// by bytes; set its `consumer-window-size` header instead.
+    pub fn prefetch(mut self, count: u32) -> Self {
+        self.prefetch = Some(count);
+        self
+    }
+
+    /// Make the subscription durable under `name`, so the broker keeps
+    /// messages for it while the subscriber is away. ActiveMQ and Artemis
+    /// also need `ConnectOptions::client_id()`. See
+    /// `durable_subscriptions.md` for the broker-side details.
+    pub fn durable(mut self, name: impl Into<String>) -> Self {
+        self.durable = Some(name.into());
+        self
+    }
+
+    /// Consume from the named queue `queue` instead of the destination, as
+    /// with `SubscriptionOptions::durable_queue`.
+    pub fn queue(mut self, queue: impl Into<String>) -> Self {
+        self.queue = Some(queue.into());
+        self
+    }
+
+    /// Add a header to the SUBSCRIBE frame, such as a `selector`.
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// Wait for the consumer instead of dropping messages when it falls
+    /// behind, as with `SubscriptionOptions::ordered`.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Track at most `max` unacknowledged messages, as with
+    /// `SubscriptionOptions::max_pending`.
+    pub fn max_pending(mut self, max: usize) -> Self {
+        self.max_pending = Some(max);
+        self
+    }
+
+    /// What to do when `max_pending()` is reached (default
+    /// `PendingOverflow::StopDelivering`).
+    pub fn pending_overflow(mut self, overflow: PendingOverflow) -> Self {
+        self.pending_overflow = overflow;
+        self
+    }
+
+    /// Ask the broker to confirm the SUBSCRIBE with a RECEIPT and make
+    /// `start()` wait for it, so messages published afterwards are known
+    /// to reach the subscription.
+    pub fn receipt(mut self, receipt: bool) -> Self {
+        self.receipt = receipt;
+        self
+    }
+
+    /// How long `start()` waits for the receipt requested with
+    /// `receipt(true)` (default 10 seconds).
+    pub fn receipt_timeout(mut self, timeout: Duration) -> Self {
+        self.receipt_timeout = timeout;
+        self
+    }
+
+    /// Subscribe.
+    ///
+    /// # Errors
+    ///
+    /// - `ConnError::Protocol` if `prefetch()` or `durable()` is not
+    ///   supported by the chosen broker, or the destination fails
+    ///   validation.
+    /// - `ConnError::ReceiptTimeout` if a requested receipt does not arrive
+    ///   in time; the subscription is then cancelled.
+    pub async fn start(self) -> Result<Subscription, ConnError> {
+        let mut headers = Vec::new();
+        if let Some(count) = self.prefetch {
+            headers.extend(
+                self.broker
+                    .prefetch_headers(count)
+                    .map_err(ConnError::Protocol)?,
+            );
+        }
+        if let Some(name) = &self.durable {
+            headers.extend(
+                self.broker
+                    .durable_subscription_headers(name)
+                    .map_err(ConnError::Protocol)?,
+            );
+        }
+        headers.extend(self.headers);
+        let destination = self.queue.as_deref().unwrap_or(&self.destination);
+        let receipt = self.receipt.then_some(self.receipt_timeout);
+        let pending_limit = PendingLimit::new(self.max_pending, self.pending_overflow);
+        self.conn
+            .subscribe_entry(
+                destination,
+                self.ack,
+                headers,
+                self.ordered,
+                pending_limit,
+                false,
+                receipt,
+            )
+            .await
+    }
+}
+
+impl std::fmt::Debug for SubscriptionBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubscriptionBuilder")
+            .field("destination", &self.destination)
+            .field("ack", &self.ack)
+            .field("broker", &self.broker)
+            .field("headers", &self.headers)
+            .field("prefetch", &self.prefetch)
+            .field("durable", &self.durable)
+            .field("queue", &self.queue)
+            .field("ordered", &self.ordered)
+            .field("max_pending", &self.max_pending)
+            .field("pending_overflow", &self.pending_overflow)
+            .field("receipt", &self.receipt)
+            .field("receipt_timeout", &self.receipt_timeout)
+            .finish()
+    }
+}
+
+/// A step added with `Subscription::map()`, `filter()` or `inspect()`.
+pub(crate) enum Stage {
+    Map(Box<dyn Fn(Frame) -> Frame + Send + Sync>),
+    Filter(Box<dyn Fn(&Frame) -> bool + Send + Sync>),
+    Inspect(Box<dyn Fn(&Frame) + Send + Sync>),
+}
+
+/// The steps of one subscription, shared between its handles and the
+/// connection task that runs them.
+pub(crate) type Stages = Arc<Mutex<Vec<Stage>>>;
+
+/// What became of a message after the steps of its subscription.
+pub(crate) enum StageOutcome {
+    /// Queue this frame for the subscriber.
+    Deliver(Frame),
+    /// A filter rejected the message.
+    Filtered,
+    /// A step panicked; the message is dropped.
+    Panicked,
+}
+
+/// Run `stages` on `frame` in the order they were added.
+pub(crate) fn run_stages(stages: &Stages, frame: Frame) -> StageOutcome {
+    let stages = stages.lock().unwrap_or_else(|e| e.into_inner());
+    let mut frame = frame;
+    for stage in stages.iter() {
+        // A step runs on the connection task, so a panic must not unwind
+        // into it
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| match stage {
+            Stage::Map(map) => Some(map(frame)),
+            Stage::Filter(filter) => filter(&frame).then_some(frame),
+            Stage::Inspect(inspect) => {
+                inspect(&frame);
+                Some(frame)
+            }
+        }));
+        frame = match result {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return StageOutcome::Filtered,
+            Err(_) => return StageOutcome::Panicked,
+        };
+    }
+    StageOutcome::Deliver(frame)
+}
+
+/// Error returned by `Subscription::recv_timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum RecvTimeoutError {
+    /// No message arrived before the timeout elapsed.
+    #[error("timed out waiting for a message")]
+    Timeout,
+    /// The subscription is closed and no messages remain.
+    #[error("subscription closed")]
+    Closed,
 }
 
 /// A lightweight handle returned from `Connection::subscribe` that packages the
@@ -26,29 +428,45 @@ pub struct SubscriptionOptions {
 ///
 /// The `Subscription` provides convenience helpers for acknowledging or
 /// negative-acknowledging messages; these delegate to the underlying
-/// `Connection` handle.
+/// connection. A `Subscription` does not keep the connection alive: once
+/// every `Connection` handle is dropped the helpers fail with
+/// `ConnError::ChannelClosed` and the message stream ends.
 pub struct Subscription {
     id: String,
-    destination: String,
+    destination: Destination,
     receiver: mpsc::Receiver<Frame>,
-    conn: Connection,
+    errors: broadcast::Receiver<ServerError>,
+    conn: WeakConnection,
+    stages: Stages,
+    gate: Arc<PauseGate>,
 }
 
 impl Subscription {
     pub(crate) fn new(
         id: String,
-        destination: String,
+        destination: Destination,
         receiver: mpsc::Receiver<Frame>,
-        conn: Connection,
+        errors: broadcast::Receiver<ServerError>,
+        conn: WeakConnection,
+        stages: Stages,
     ) -> Self {
         Self {
             id,
             destination,
             receiver,
+            errors,
             conn,
+            stages,
+            gate: Arc::default(),
         }
     }
 
+    /// The connection, unless every `Connection` handle has been dropped.
+    #[allow(clippy::result_large_err)]
+    fn conn(&self) -> Result<Connection, ConnError> {
+        self.conn.upgrade().ok_or(ConnError::ChannelClosed)
+    }
+
     /// Returns the local subscription id.
     pub fn id(&self) -> &str {
         &self.id
@@ -59,21 +477,413 @@ impl Subscription {
         &self.destination
     }
 
+    /// The interned destination, shared with the connection's dispatch map.
+    pub(crate) fn destination_name(&self) -> &Destination {
+        &self.destination
+    }
+
+    /// Transform every message with `f` before it is queued for this
+    /// subscription (builder style).
+    ///
+    /// Steps added with `map`, [`filter`](Self::filter) and
+    /// [`inspect`](Self::inspect) run in the order they were added, on the
+    /// connection task as each MESSAGE is dispatched, so no task of its own
+    /// is needed and a message dropped by a filter never takes up room in
+    /// the buffer. They apply to messages dispatched after the call,
+    /// including after a reconnect, and to every handle made by
+    /// [`into_shared`](Self::into_shared).
+    ///
+    /// Keep steps short: while one runs, no frame is read for any
+    /// subscription. A step that panics drops the message, without
+    /// acknowledging it, and logs a warning. Keep the `message-id` header
+    /// intact, since `ack`/`nack` look messages up by it.
+    ///
+    /// These shadow the `StreamExt` adapters of the same name; call
+    /// `StreamExt::map(sub, f)` for those.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut sub = conn
+    ///     .subscribe("/queue/events", AckMode::ClientIndividual)
+    ///     .await?
+    ///     .inspect(|frame| tracing::debug!(bytes = frame.body.len(), "event"))
+    ///     .filter(|frame| frame.get_header("type") != Some("ping"))
+    ///     .map(|frame| frame.header("received-at", now()));
+    /// ```
+    pub fn map<F>(self, f: F) -> Self
+    where
+        F: Fn(Frame) -> Frame + Send + Sync + 'static,
+    {
+        self.add_stage(Stage::Map(Box::new(f)))
+    }
+
+    /// Queue only the messages for which `predicate` returns `true`
+    /// (builder style). See [`map`](Self::map) for how steps run.
+    ///
+    /// A rejected message is settled so it does not stay unacknowledged:
+    /// it is ACKed in `client-individual` mode, and in `client` mode left
+    /// to the cumulative ACK of a later message.
+    pub fn filter<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&Frame) -> bool + Send + Sync + 'static,
+    {
+        self.add_stage(Stage::Filter(Box::new(predicate)))
+    }
+
+    /// Call `f` with every message before it is queued, for logging or
+    /// metrics (builder style). See [`map`](Self::map) for how steps run.
+    pub fn inspect<F>(self, f: F) -> Self
+    where
+        F: Fn(&Frame) + Send + Sync + 'static,
+    {
+        self.add_stage(Stage::Inspect(Box::new(f)))
+    }
+
+    fn add_stage(self, stage: Stage) -> Self {
+        self.stages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(stage);
+        self
+    }
+
+    /// Stop handing out messages until [`resume`](Self::resume), without
+    /// unsubscribing.
+    ///
+    /// The broker subscription, its durable state and the order of
+    /// messages are untouched: `recv()` and the other receive methods
+    /// wait, and messages that keep arriving are buffered in the
+    /// subscription's channel. Once it is full, the usual overflow policy
+    /// applies: further messages are dropped and reported with
+    /// `ConnectionEvent::SlowConsumer`, or, for an
+    /// [`ordered`](SubscriptionBuilder::ordered) subscription, the
+    /// connection stops reading until there is room. Limit how much the
+    /// broker sends ahead with a client ack mode and
+    /// [`prefetch`](SubscriptionBuilder::prefetch) to pause for long.
+    ///
+    /// Pausing applies to every handle made by
+    /// [`into_shared`](Self::into_shared), but not to the receiver returned
+    /// by [`into_receiver`](Self::into_receiver). ACKs and NACKs still go
+    /// through while paused.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pause = sub.pause_handle();
+    /// tokio::spawn(async move {
+    ///     maintenance_started().await;
+    ///     pause.pause();
+    ///     maintenance_finished().await;
+    ///     pause.resume();
+    /// });
+    /// while let Some(frame) = sub.recv().await {
+    ///     process(frame).await;
+    /// }
+    /// ```
+    pub fn pause(&self) {
+        self.gate.set_paused(true);
+    }
+
+    /// Hand out messages again after [`pause`](Self::pause), starting with
+    /// those buffered meanwhile.
+    pub fn resume(&self) {
+        self.gate.set_paused(false);
+    }
+
+    /// Whether the subscription is paused.
+    pub fn is_paused(&self) -> bool {
+        self.gate.is_paused()
+    }
+
+    /// A handle for pausing and resuming this subscription from another
+    /// task while this one waits in `recv()`.
+    pub fn pause_handle(&self) -> PauseHandle {
+        PauseHandle {
+            gate: self.gate.clone(),
+        }
+    }
+
+    /// Poll for the next message unless the subscription is paused.
+    fn poll_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Frame>> {
+        ready!(self.gate.poll_open(cx));
+        self.receiver.poll_recv(cx)
+    }
+
+    /// Wait for the next message, honoring `pause()`.
+    async fn next_message(&mut self) -> Option<Frame> {
+        std::future::poll_fn(|cx| self.poll_message(cx)).await
+    }
+
+    /// Wait for the next message.
+    ///
+    /// Returns `None` once the subscription is closed (unsubscribed or the
+    /// connection shut down) and no buffered messages remain.
+    pub async fn recv(&mut self) -> Option<Frame> {
+        self.next_message().await
+    }
+
+    /// Wait for the next message, or for a connection-level ERROR that
+    /// invalidated messages of this subscription.
+    ///
+    /// `recv()` and the `Stream` implementation only yield messages; for a
+    /// stream that also yields these errors, use
+    /// [`into_results`](Self::into_results). When
+    /// the broker sends an ERROR that is not about a particular destination
+    /// while messages of this subscription wait for an ACK or NACK, the
+    /// broker ends the session and will not accept those acknowledgements.
+    /// This returns that error, ahead of any messages still buffered; ACKs
+    /// and NACKs for the messages delivered before it then fail with
+    /// `ConnError::MessageInvalidated` without being sent. The subscription
+    /// itself is kept: it is sent again after the reconnect and the broker
+    /// redelivers the unacknowledged messages, which can be acknowledged
+    /// as usual. Returns `None` once the subscription is closed.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// while let Some(delivery) = sub.recv_result().await {
+    ///     match delivery {
+    ///         Ok(frame) => process(&sub, frame).await?,
+    ///         Err(err) => tracing::warn!("in-flight messages lost: {}", err.message),
+    ///     }
+    /// }
+    /// ```
+    pub async fn recv_result(&mut self) -> Option<Result<Frame, ServerError>> {
+        loop {
+            tokio::select! {
+                biased;
+                error = self.errors.recv() => match error {
+                    Ok(error) => return Some(Err(error)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    // Unsubscribed: only buffered messages remain
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return self.next_message().await.map(Ok);
+                    }
+                },
+                frame = std::future::poll_fn(|cx| {
+                    ready!(self.gate.poll_open(cx));
+                    self.receiver.poll_recv(cx)
+                }) => return frame.map(Ok),
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next message.
+    ///
+    /// Returns `RecvTimeoutError::Timeout` if nothing arrived in time, or
+    /// `RecvTimeoutError::Closed` if the subscription is closed.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// match sub.recv_timeout(Duration::from_secs(1)).await {
+    ///     Ok(frame) => process(frame),
+    ///     Err(RecvTimeoutError::Timeout) => println!("idle"),
+    ///     Err(RecvTimeoutError::Closed) => break,
+    /// }
+    /// ```
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Frame, RecvTimeoutError> {
+        match tokio::time::timeout(timeout, self.next_message()).await {
+            Ok(Some(frame)) => Ok(frame),
+            Ok(None) => Err(RecvTimeoutError::Closed),
+            Err(_) => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    /// Return a buffered message without waiting.
+    ///
+    /// Returns `TryRecvError::Empty` if no message is ready or the
+    /// subscription is paused, or `TryRecvError::Disconnected` if the
+    /// subscription is closed.
+    pub fn try_recv(&mut self) -> Result<Frame, TryRecvError> {
+        if self.gate.is_paused() {
+            return Err(TryRecvError::Empty);
+        }
+        self.receiver.try_recv()
+    }
+
+    /// Collect up to `max` messages, waiting at most `deadline` in total.
+    ///
+    /// Returns as soon as `max` messages have been collected, the deadline
+    /// passes, or the subscription closes, whichever comes first. The
+    /// result may be empty. Pair with [`ack_batch`](Self::ack_batch) to
+    /// acknowledge the whole batch once it has been processed.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let batch = sub.recv_batch(500, Duration::from_millis(200)).await;
+    /// db.insert_all(&batch).await?;
+    /// let ids: Vec<&str> = batch.iter().filter_map(|f| f.get_header("message-id")).collect();
+    /// sub.ack_batch(&ids).await?;
+    /// ```
+    pub async fn recv_batch(&mut self, max: usize, deadline: Duration) -> Vec<Frame> {
+        let mut batch = Vec::with_capacity(max.min(1024));
+        let until = tokio::time::Instant::now() + deadline;
+        while batch.len() < max {
+            match tokio::time::timeout_at(until, self.next_message()).await {
+                Ok(Some(frame)) => batch.push(frame),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        batch
+    }
+
+    /// Acknowledge a batch of messages. Delegates to `Connection::ack_batch`
+    /// using the local subscription id.
+    pub async fn ack_batch<S: AsRef<str>>(&self, message_ids: &[S]) -> Result<(), ConnError> {
+        self.conn()?.ack_batch(&self.id, message_ids).await
+    }
+
     /// Consume the `Subscription` and return the underlying receiver so the
-    /// caller can drive message handling directly.
+    /// caller can drive message handling directly. The receiver ignores
+    /// [`pause`](Self::pause).
     pub fn into_receiver(self) -> mpsc::Receiver<Frame> {
         self.receiver
     }
 
+    /// Turn the subscription into a `Stream` of `Result`s that yields the
+    /// connection-level ERRORs [`recv_result`](Self::recv_result) reports
+    /// as `Err` items, ahead of any messages still buffered.
+    ///
+    /// The returned `SubscriptionResults` dereferences to the subscription
+    /// for acknowledging and pausing.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut results = sub.into_results();
+    /// while let Some(delivery) = results.next().await {
+    ///     match delivery {
+    ///         Ok(frame) => process(&results, frame).await?,
+    ///         Err(err) => tracing::warn!("in-flight messages lost: {}", err.message),
+    ///     }
+    /// }
+    /// ```
+    pub fn into_results(mut self) -> SubscriptionResults {
+        // The stream owns the error receiver from now on; `recv_result()`
+        // on the inner subscription sees a closed channel and only messages
+        let (_, closed) = broadcast::channel(1);
+        let errors = std::mem::replace(&mut self.errors, closed);
+        SubscriptionResults {
+            subscription: self,
+            errors: Some(ReusableBoxFuture::new(next_error(errors))),
+        }
+    }
+
+    /// Split the subscription into `workers` handles that share it, for
+    /// processing messages in parallel.
+    ///
+    /// Messages are dealt out round-robin, skipping workers whose buffer is
+    /// full; when every worker is full, dispatch waits for the first one to
+    /// make room. There is still a single broker-side subscription, and
+    /// every handle has the same id, so `ack`/`nack` from any worker go
+    /// through the connection's shared pending-ACK bookkeeping. Note that
+    /// in `client` ack mode an ACK is cumulative across all workers.
+    ///
+    /// A worker that is dropped stops receiving messages; once all of them
+    /// are dropped the subscription is cleaned up as if it had been
+    /// dropped itself. Calling `unsubscribe()` on any worker ends the
+    /// subscription for all of them. `workers` is treated as at least 1.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let sub = conn.subscribe("/queue/jobs", AckMode::ClientIndividual).await?;
+    /// for mut worker in sub.into_shared(4) {
+    ///     tokio::spawn(async move {
+    ///         while let Some(job) = worker.recv().await {
+    ///             process(&job).await;
+    ///             if let Some(id) = job.get_header("message-id") {
+    ///                 let _ = worker.ack(id).await;
+    ///             }
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn into_shared(self, workers: usize) -> Vec<Subscription> {
+        let mut senders = Vec::with_capacity(workers.max(1));
+        let mut handles = Vec::with_capacity(workers.max(1));
+        for _ in 0..workers.max(1) {
+            let (tx, rx) = mpsc::channel(WORKER_CHANNEL_CAPACITY);
+            senders.push(tx);
+            let mut handle = Subscription::new(
+                self.id.clone(),
+                self.destination.clone(),
+                rx,
+                self.errors.resubscribe(),
+                self.conn.clone(),
+                self.stages.clone(),
+            );
+            handle.gate = self.gate.clone();
+            handles.push(handle);
+        }
+        let labels = self.conn.task_labels().clone();
+        crate::task::spawn(
+            "dispatch",
+            &labels,
+            dispatch_round_robin(self.receiver, senders),
+        );
+        handles
+    }
+
+    /// Returns how many messages delivered on this subscription are still
+    /// waiting for an ACK or NACK, and how long the oldest has waited.
+    pub async fn pending_acks(&self) -> PendingAcks {
+        match self.conn.upgrade() {
+            Some(conn) => conn.pending_acks_for(&self.id).await,
+            None => PendingAcks::default(),
+        }
+    }
+
     /// Acknowledge a message by its `message-id` header. Delegates to
     /// `Connection::ack` using the local subscription id.
     pub async fn ack(&self, message_id: &str) -> Result<(), ConnError> {
-        self.conn.ack(&self.id, message_id).await
+        self.conn()?.ack(&self.id, message_id).await
     }
 
     /// Negative-acknowledge a message by its `message-id` header.
     pub async fn nack(&self, message_id: &str) -> Result<(), ConnError> {
-        self.conn.nack(&self.id, message_id).await
+        self.conn()?.nack(&self.id, message_id).await
+    }
+
+    /// Negative-acknowledge a message with requeue controls. See
+    /// `Connection::nack_with_options` for details.
+    pub async fn nack_with_options(
+        &self,
+        message_id: &str,
+        options: NackOptions,
+    ) -> Result<(), ConnError> {
+        self.conn()?
+            .nack_with_options(&self.id, message_id, options)
+            .await
+    }
+
+    /// Acknowledge every message on this subscription still waiting for an
+    /// ACK. See `Connection::ack_all`.
+    pub async fn ack_all(&self) -> Result<usize, ConnError> {
+        self.conn()?.ack_all(&self.id).await
+    }
+
+    /// Negative-acknowledge every message on this subscription still
+    /// waiting for an ACK. See `Connection::nack_all`.
+    pub async fn nack_all(&self, options: NackOptions) -> Result<usize, ConnError> {
+        self.conn()?.nack_all(&self.id, options).await
+    }
+
+    /// Change the extra SUBSCRIBE headers (selector, prefetch, ...) without
+    /// dropping this handle.
+    ///
+    /// Performs UNSUBSCRIBE/SUBSCRIBE with the same id, destination and ack
+    /// mode, so messages keep arriving on this subscription. `headers`
+    /// replaces the previous extra headers, including for resubscribes after
+    /// a reconnect. Messages not yet ACKed are released by the broker on
+    /// UNSUBSCRIBE and will be redelivered, so they can no longer be ACKed.
+    pub async fn update_headers(&self, headers: Vec<(String, String)>) -> Result<(), ConnError> {
+        self.conn()?
+            .update_subscription_headers(&self.id, headers)
+            .await
     }
 
     /// Consume the subscription and unsubscribe from the server.
@@ -81,7 +891,252 @@ impl Subscription {
     /// This is a convenience that calls `Connection::unsubscribe` with the
     /// local subscription id and drops the receiver.
     pub async fn unsubscribe(self) -> Result<(), ConnError> {
-        self.conn.unsubscribe(&self.id).await
+        self.conn()?.unsubscribe(&self.id).await
+    }
+}
+
+/// Buffer size of each worker created by `Subscription::into_shared`,
+/// matching the buffer of a regular subscription.
+const WORKER_CHANNEL_CAPACITY: usize = 16;
+
+/// Deal messages from `receiver` out to `workers` in turn until the
+/// subscription closes or every worker has gone away.
+async fn dispatch_round_robin(
+    mut receiver: mpsc::Receiver<Frame>,
+    mut workers: Vec<mpsc::Sender<Frame>>,
+) {
+    let mut next = 0;
+    while let Some(frame) = receiver.recv().await {
+        // Offer the frame to each worker once, starting with the next in turn
+        let mut pending = Some(frame);
+        let mut tried = 0;
+        while tried < workers.len() {
+            let Some(frame) = pending.take() else { break };
+            let i = next % workers.len();
+            match workers[i].try_send(frame) {
+                Ok(()) => next = i + 1,
+                Err(mpsc::error::TrySendError::Full(f)) => {
+                    pending = Some(f);
+                    next = i + 1;
+                    tried += 1;
+                }
+                Err(mpsc::error::TrySendError::Closed(f)) => {
+                    pending = Some(f);
+                    workers.remove(i);
+                }
+            }
+        }
+
+        // Every live worker is busy: wait for the first to make room
+        while let Some(frame) = pending.take() {
+            if workers.is_empty() {
+                return;
+            }
+            let closed = {
+                let reservations = workers.iter().map(|w| Box::pin(w.reserve()));
+                match futures::future::select_all(reservations).await {
+                    (Ok(permit), i, _) => {
+                        permit.send(frame);
+                        next = i + 1;
+                        None
+                    }
+                    (Err(_), i, _) => {
+                        pending = Some(frame);
+                        Some(i)
+                    }
+                }
+            };
+            if let Some(i) = closed {
+                workers.remove(i);
+            }
+        }
+        if workers.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Pauses and resumes a subscription from anywhere, returned by
+/// `Subscription::pause_handle()`. Clones control the same subscription.
+#[derive(Debug, Clone)]
+pub struct PauseHandle {
+    gate: Arc<PauseGate>,
+}
+
+impl PauseHandle {
+    /// See `Subscription::pause()`.
+    pub fn pause(&self) {
+        self.gate.set_paused(true);
+    }
+
+    /// See `Subscription::resume()`.
+    pub fn resume(&self) {
+        self.gate.set_paused(false);
+    }
+
+    /// Whether the subscription is paused.
+    pub fn is_paused(&self) -> bool {
+        self.gate.is_paused()
+    }
+}
+
+/// Pause state shared by a subscription, its shared handles and its
+/// `PauseHandle`s, with the tasks waiting for it to be resumed.
+#[derive(Debug, Default)]
+struct PauseGate {
+    paused: AtomicBool,
+    waiting: Mutex<Vec<Waker>>,
+}
+
+impl PauseGate {
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let mut waiting = self.waiting.lock().unwrap_or_else(|e| e.into_inner());
+        self.paused.store(paused, Ordering::Release);
+        if !paused {
+            waiting.drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// Ready unless paused; otherwise wake `cx` on resume.
+    fn poll_open(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.is_paused() {
+            return Poll::Ready(());
+        }
+        let mut waiting = self.waiting.lock().unwrap_or_else(|e| e.into_inner());
+        // Resumed while taking the lock
+        if !self.is_paused() {
+            return Poll::Ready(());
+        }
+        if !waiting.iter().any(|w| w.will_wake(cx.waker())) {
+            waiting.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// A `Subscription` whose `Stream` yields connection-level ERRORs as `Err`
+/// items next to the messages, returned by
+/// [`Subscription::into_results`].
+///
+/// Dereferences to the underlying `Subscription`.
+pub struct SubscriptionResults {
+    subscription: Subscription,
+    /// Waits for the next ERROR; `None` once the error channel is closed.
+    errors: Option<ReusableBoxFuture<'static, ErrorWait>>,
+}
+
+type ErrorWait = (
+    Result<ServerError, broadcast::error::RecvError>,
+    broadcast::Receiver<ServerError>,
+);
+
+async fn next_error(mut errors: broadcast::Receiver<ServerError>) -> ErrorWait {
+    (errors.recv().await, errors)
+}
+
+impl std::ops::Deref for SubscriptionResults {
+    type Target = Subscription;
+
+    fn deref(&self) -> &Subscription {
+        &self.subscription
+    }
+}
+
+impl std::ops::DerefMut for SubscriptionResults {
+    fn deref_mut(&mut self) -> &mut Subscription {
+        &mut self.subscription
+    }
+}
+
+impl Stream for SubscriptionResults {
+    type Item = Result<Frame, ServerError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        // Errors first, as in `Subscription::recv_result`
+        while let Some(errors) = &mut this.errors {
+            let Poll::Ready((result, receiver)) = errors.poll(cx) else {
+                break;
+            };
+            match result {
+                Ok(error) => {
+                    errors.set(next_error(receiver));
+                    return Poll::Ready(Some(Err(error)));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => errors.set(next_error(receiver)),
+                // Unsubscribed: only buffered messages remain
+                Err(broadcast::error::RecvError::Closed) => this.errors = None,
+            }
+        }
+        this.subscription
+            .poll_message(cx)
+            .map(|frame| frame.map(Ok))
+    }
+}
+
+/// A subscription to a temporary reply queue, returned by
+/// `Connection::subscribe_temp`.
+///
+/// Dereferences to the underlying `Subscription` for receiving. Dropping
+/// it unsubscribes in the background, which lets the broker delete the
+/// queue.
+pub struct TempSubscription {
+    reply_to: String,
+    subscription: Subscription,
+}
+
+impl TempSubscription {
+    pub(crate) fn new(reply_to: String, subscription: Subscription) -> Self {
+        Self {
+            reply_to,
+            subscription,
+        }
+    }
+
+    /// The destination to put into the `reply-to` header of requests.
+    pub fn reply_to(&self) -> &str {
+        &self.reply_to
+    }
+}
+
+impl std::ops::Deref for TempSubscription {
+    type Target = Subscription;
+
+    fn deref(&self) -> &Subscription {
+        &self.subscription
+    }
+}
+
+impl std::ops::DerefMut for TempSubscription {
+    fn deref_mut(&mut self) -> &mut Subscription {
+        &mut self.subscription
+    }
+}
+
+impl Drop for TempSubscription {
+    fn drop(&mut self) {
+        // Without a runtime (e.g. dropped after it shut down) there is no
+        // connection left to unsubscribe from.
+        if let Ok(handle) = tokio::runtime::Handle::try_current()
+            && let Some(conn) = self.subscription.conn.upgrade()
+        {
+            let id = self.subscription.id.clone();
+            let destination = self.subscription.destination.clone();
+            handle.spawn(async move {
+                if let Err(e) = conn.unsubscribe(&id).await {
+                    diagnostics::swallowed(
+                        "unsubscribe dropped subscription",
+                        Some("UNSUBSCRIBE"),
+                        Some(destination.as_str()),
+                        &e,
+                    );
+                }
+            });
+        }
     }
 }
 
@@ -90,9 +1145,8 @@ impl Stream for Subscription {
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         // Safe to get a mutable reference because all fields of `Subscription`
-        // are `Unpin` (String, Receiver, Connection). We then delegate to the
-        // tokio mpsc receiver's `poll_recv` which returns `Poll<Option<T>>`.
-        let this = self.get_mut();
-        Pin::new(&mut this.receiver).poll_recv(cx)
+        // are `Unpin` (String, Receiver, WeakConnection). We then delegate to the
+        // tokio mpsc receiver's `poll_recv` once the pause gate is open.
+        self.get_mut().poll_message(cx)
     }
 }
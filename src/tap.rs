@@ -0,0 +1,80 @@
+//! Read-only mirror of inbound messages for auditing and metrics.
+//!
+//! See `Connection::tap()`.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::{Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::ReusableBoxFuture;
+
+use crate::frame::Frame;
+
+/// Messages a tap holds for a slow reader before the oldest are skipped.
+pub(crate) const TAP_CAPACITY: usize = 1024;
+
+type Next = (Result<Frame, RecvError>, broadcast::Receiver<Frame>);
+
+async fn next_frame(mut rx: broadcast::Receiver<Frame>) -> Next {
+    let result = rx.recv().await;
+    (result, rx)
+}
+
+/// A copy of every MESSAGE the connection receives, returned by
+/// `Connection::tap()`.
+///
+/// Each message is copied to the tap after it has been dispatched to its
+/// subscriptions, whether or not one of them took it. A tap only watches:
+/// messages are acknowledged, or not, by their subscriptions as usual, and
+/// a tap that falls behind never holds up delivery. Instead, once more
+/// than 1024 messages are waiting the oldest are skipped and counted in
+/// [`missed`](Self::missed).
+///
+/// The stream ends when the connection is closed.
+pub struct Tap {
+    next: ReusableBoxFuture<'static, Next>,
+    missed: u64,
+}
+
+impl Tap {
+    pub(crate) fn new(rx: broadcast::Receiver<Frame>) -> Self {
+        Self {
+            next: ReusableBoxFuture::new(next_frame(rx)),
+            missed: 0,
+        }
+    }
+
+    /// Wait for the next message, or `None` once the connection has closed.
+    pub async fn recv(&mut self) -> Option<Frame> {
+        self.next().await
+    }
+
+    /// Number of messages skipped because this tap fell behind.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+impl Stream for Tap {
+    type Item = Frame;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Frame>> {
+        let this = self.get_mut();
+        loop {
+            let (result, rx) = futures::ready!(this.next.poll(cx));
+            this.next.set(next_frame(rx));
+            match result {
+                Ok(frame) => return Poll::Ready(Some(frame)),
+                Err(RecvError::Lagged(skipped)) => this.missed += skipped,
+                Err(RecvError::Closed) => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Tap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tap").field("missed", &self.missed).finish()
+    }
+}
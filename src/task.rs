@@ -0,0 +1,84 @@
+//! Spawning the background tasks of a connection under descriptive names.
+//!
+//! Every task runs inside a `stomp` tracing span carrying the task's role
+//! and the connection's `addr` and `login`, so log lines from processes
+//! holding many connections say which one they came from.
+//!
+//! With the `task-names` feature and the `tokio_unstable` cfg
+//! (`RUSTFLAGS="--cfg tokio_unstable"`), tasks are also spawned through
+//! `tokio::task::Builder` with a name such as
+//! `iridium-stomp connection addr=broker:61613 login=guest`, which
+//! tokio-console and runtime dumps display.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// What identifies a connection's tasks: the broker address and login.
+#[derive(Debug, Clone)]
+pub(crate) struct TaskLabels {
+    addr: Arc<str>,
+    login: Option<Arc<str>>,
+}
+
+impl TaskLabels {
+    pub(crate) fn new(addr: &str, login: Option<&str>) -> Self {
+        Self {
+            addr: Arc::from(addr),
+            login: login.map(Arc::from),
+        }
+    }
+}
+
+impl fmt::Display for TaskLabels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "addr={}", self.addr)?;
+        if let Some(login) = &self.login {
+            write!(f, " login={}", login)?;
+        }
+        Ok(())
+    }
+}
+
+/// Spawn `future` as the connection's `role` task (`connection`,
+/// `outbox`, ...), labelled with `labels`.
+pub(crate) fn spawn<F>(role: &'static str, labels: &TaskLabels, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let span = tracing::info_span!(
+        "stomp",
+        task = role,
+        addr = %labels.addr,
+        login = labels.login.as_deref(),
+    );
+    let future = future.instrument(span);
+    #[cfg(all(tokio_unstable, feature = "task-names"))]
+    {
+        tokio::task::Builder::new()
+            .name(&format!("iridium-stomp {} {}", role, labels))
+            .spawn(future)
+            .expect("failed to spawn task")
+    }
+    #[cfg(not(all(tokio_unstable, feature = "task-names")))]
+    {
+        tokio::spawn(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_name_the_broker_and_login() {
+        let labels = TaskLabels::new("broker:61613", Some("guest"));
+        assert_eq!(labels.to_string(), "addr=broker:61613 login=guest");
+        let anonymous = TaskLabels::new("broker:61613", None);
+        assert_eq!(anonymous.to_string(), "addr=broker:61613");
+    }
+}
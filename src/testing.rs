@@ -0,0 +1,626 @@
+//! Scriptable in-process STOMP broker for tests (requires the `testing`
+//! feature).
+//!
+//! [`MockBroker`] listens on a local port and plays back a [`Script`]: one
+//! [`Session`] per accepted connection, each a list of steps such as
+//! "answer CONNECT after 500ms", "deliver 3 messages", "send an ERROR", or
+//! "drop the connection after 2 frames". This makes failure paths like
+//! reconnects, resubscription, and receipt timeouts deterministic enough
+//! for CI.
+//!
+//! While a session runs, the broker records every frame it receives,
+//! answers receipt requests (unless told to withhold them), and remembers
+//! the latest SUBSCRIBE so `deliver` knows where to send messages.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use iridium_stomp::testing::{MockBroker, Script, Session};
+//!
+//! let broker = MockBroker::start(
+//!     Script::new()
+//!         // First connection: accept, deliver two messages, then vanish
+//!         .session(Session::new().connected().deliver(2).close())
+//!         // Reconnect: accept and expect the subscription to be restored
+//!         .session(Session::new().connected().expect("SUBSCRIBE").deliver(1)),
+//! )
+//! .await?;
+//!
+//! let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0").await?;
+//! ```
+
+mod matcher;
+
+pub use matcher::{FrameMatcher, FrameMismatch};
+
+use crate::codec::{StompCodec, StompItem};
+use crate::frame::Frame;
+use futures::{SinkExt, StreamExt};
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::codec::Framed;
+
+/// One scripted action of a [`Session`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Step {
+    /// Wait for CONNECT (or STOMP), then reply CONNECTED after `delay`.
+    Connected {
+        /// Pause before answering.
+        delay: Duration,
+    },
+    /// Wait for CONNECT (or STOMP), then reply with this frame instead of
+    /// the default CONNECTED, e.g. to announce heart-beats or a server name.
+    ConnectedWith(Frame),
+    /// Wait for CONNECT (or STOMP), then reply with an ERROR frame carrying
+    /// `message` and close the connection.
+    Reject {
+        /// The ERROR frame's `message` header.
+        message: String,
+    },
+    /// Wait until a frame with this command arrives.
+    Expect(String),
+    /// Send `count` MESSAGE frames to the most recent subscription, waiting
+    /// for a SUBSCRIBE first if none has been seen.
+    Deliver {
+        /// Number of messages to send.
+        count: usize,
+    },
+    /// Send this frame as a MESSAGE to the most recent subscription, like
+    /// [`Step::Deliver`], filling in the `destination`, `subscription`,
+    /// `message-id`, and `ack` headers it lacks.
+    DeliverFrame(Frame),
+    /// Send an ERROR frame with this `message` header.
+    Error(String),
+    /// Send a frame as-is.
+    Send(Frame),
+    /// Write these bytes as-is, e.g. a heartbeat or a malformed frame.
+    SendRaw(Vec<u8>),
+    /// Keep serving for this long before the next step.
+    Wait(Duration),
+    /// Read this many more frames, then drop the connection.
+    DropAfterFrames(usize),
+    /// Stop answering receipt requests for the rest of the session.
+    WithholdReceipts,
+    /// Drop the connection.
+    Close,
+}
+
+/// The steps played back on one accepted connection.
+///
+/// After the last step the broker keeps serving (recording frames and
+/// answering receipts) until the client disconnects, unless a step closed
+/// the connection.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    steps: Vec<Step>,
+}
+
+impl Session {
+    /// An empty session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append any step.
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Accept the handshake immediately.
+    pub fn connected(self) -> Self {
+        self.connected_after(Duration::ZERO)
+    }
+
+    /// Accept the handshake after `delay`.
+    pub fn connected_after(self, delay: Duration) -> Self {
+        self.step(Step::Connected { delay })
+    }
+
+    /// Accept the handshake with `frame` instead of the default CONNECTED.
+    pub fn connected_with(self, frame: Frame) -> Self {
+        self.step(Step::ConnectedWith(frame))
+    }
+
+    /// Refuse the handshake with an ERROR frame.
+    pub fn reject(self, message: impl Into<String>) -> Self {
+        self.step(Step::Reject {
+            message: message.into(),
+        })
+    }
+
+    /// Wait for a frame with `command`.
+    pub fn expect(self, command: impl Into<String>) -> Self {
+        self.step(Step::Expect(command.into()))
+    }
+
+    /// Deliver `count` messages to the latest subscription.
+    pub fn deliver(self, count: usize) -> Self {
+        self.step(Step::Deliver { count })
+    }
+
+    /// Deliver `frame` to the latest subscription, filling in the headers
+    /// it lacks.
+    pub fn deliver_frame(self, frame: Frame) -> Self {
+        self.step(Step::DeliverFrame(frame))
+    }
+
+    /// Send an ERROR frame.
+    pub fn error(self, message: impl Into<String>) -> Self {
+        self.step(Step::Error(message.into()))
+    }
+
+    /// Send an arbitrary frame.
+    pub fn send(self, frame: Frame) -> Self {
+        self.step(Step::Send(frame))
+    }
+
+    /// Write raw bytes.
+    pub fn send_raw(self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.step(Step::SendRaw(bytes.into()))
+    }
+
+    /// Keep serving for `duration`.
+    pub fn wait(self, duration: Duration) -> Self {
+        self.step(Step::Wait(duration))
+    }
+
+    /// Drop the connection after `frames` more inbound frames.
+    pub fn drop_after_frames(self, frames: usize) -> Self {
+        self.step(Step::DropAfterFrames(frames))
+    }
+
+    /// Stop answering receipt requests.
+    pub fn withhold_receipts(self) -> Self {
+        self.step(Step::WithholdReceipts)
+    }
+
+    /// Drop the connection.
+    pub fn close(self) -> Self {
+        self.step(Step::Close)
+    }
+}
+
+/// The sessions played back by a [`MockBroker`], one per accepted
+/// connection in order. Connections beyond the last session replay it.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    sessions: Vec<Session>,
+}
+
+impl Script {
+    /// An empty script. With no sessions, every connection is accepted
+    /// immediately and served until it closes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the session for the next connection.
+    pub fn session(mut self, session: Session) -> Self {
+        self.sessions.push(session);
+        self
+    }
+
+    fn for_connection(&self, index: usize) -> Session {
+        self.sessions
+            .get(index)
+            .or(self.sessions.last())
+            .cloned()
+            .unwrap_or_else(|| Session::new().connected())
+    }
+}
+
+/// A local STOMP broker that plays back a [`Script`].
+///
+/// Besides the scripted steps, a test can act on the most recent
+/// connection at any time with [`send`](Self::send),
+/// [`deliver`](Self::deliver), [`send_raw`](Self::send_raw), and
+/// [`disconnect`](Self::disconnect); the session carries them out the next
+/// time it waits for a frame.
+///
+/// The broker runs on the current tokio runtime and stops when dropped.
+pub struct MockBroker {
+    addr: SocketAddr,
+    state: Arc<Mutex<BrokerState>>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct BrokerState {
+    /// Every frame received, across all connections.
+    received: Vec<Frame>,
+    /// Number of connections accepted.
+    connections: usize,
+    /// Number of accepted connections still being served.
+    open: usize,
+    /// Actions requested by the test, for the most recent connection.
+    control: Option<mpsc::UnboundedSender<Control>>,
+}
+
+/// An action requested through the [`MockBroker`] handle.
+enum Control {
+    Send(Frame),
+    Deliver(Frame),
+    SendRaw(Vec<u8>),
+    Disconnect,
+}
+
+impl MockBroker {
+    /// Bind to an ephemeral local port and start serving `script`.
+    pub async fn start(script: Script) -> io::Result<Self> {
+        Self::start_at(SocketAddr::from(([127, 0, 0, 1], 0)), script).await
+    }
+
+    /// Like [`start`](Self::start), but listen on `addr`, e.g. to bring a
+    /// broker up late at an address a client is already retrying.
+    pub async fn start_at(addr: SocketAddr, script: Script) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(BrokerState::default()));
+        let task_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (control_tx, control_rx) = mpsc::unbounded_channel();
+                let index = {
+                    let mut state = lock(&task_state);
+                    state.connections += 1;
+                    state.open += 1;
+                    state.control = Some(control_tx);
+                    state.connections - 1
+                };
+                let session = SessionRunner::new(stream, task_state.clone(), control_rx);
+                let script = script.for_connection(index);
+                let session_state = task_state.clone();
+                tokio::spawn(async move {
+                    session.run(script).await;
+                    lock(&session_state).open -= 1;
+                });
+            }
+        });
+        Ok(Self { addr, state, task })
+    }
+
+    /// The socket address the broker listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The address as a `host:port` string for `Connection::connect`.
+    pub fn address(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Number of connections accepted so far.
+    pub fn connections(&self) -> usize {
+        lock(&self.state).connections
+    }
+
+    /// Number of accepted connections still open, i.e. neither closed by
+    /// the client nor dropped by the script.
+    pub fn open_connections(&self) -> usize {
+        lock(&self.state).open
+    }
+
+    /// Every frame received so far, in arrival order.
+    pub fn received(&self) -> Vec<Frame> {
+        lock(&self.state).received.clone()
+    }
+
+    /// Received frames with the given command.
+    pub fn received_commands(&self, command: &str) -> Vec<Frame> {
+        lock(&self.state)
+            .received
+            .iter()
+            .filter(|f| f.command == command)
+            .cloned()
+            .collect()
+    }
+
+    /// Wait until at least `count` frames with `command` have been received.
+    /// Returns `false` if `timeout` expires first.
+    pub async fn wait_for(&self, command: &str, count: usize, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if self.received_commands(command).len() >= count {
+                return true;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// Wait for a received frame that `matcher` accepts and return the
+    /// first one, or `None` if `timeout` expires first.
+    pub async fn wait_for_match(&self, matcher: &FrameMatcher, timeout: Duration) -> Option<Frame> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let found = lock(&self.state)
+                .received
+                .iter()
+                .find(|f| matcher.matches(f))
+                .cloned();
+            if found.is_some() || tokio::time::Instant::now() >= deadline {
+                return found;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// Send `frame` on the most recent connection.
+    pub fn send(&self, frame: Frame) {
+        self.control(Control::Send(frame));
+    }
+
+    /// Deliver `frame` to the most recent subscription on the most recent
+    /// connection, filling in the headers it lacks as
+    /// [`Session::deliver_frame`] does. Wait for the SUBSCRIBE with
+    /// [`wait_for`](Self::wait_for) first, or the message has no
+    /// subscription to go to.
+    pub fn deliver(&self, frame: Frame) {
+        self.control(Control::Deliver(frame));
+    }
+
+    /// Write raw bytes on the most recent connection.
+    pub fn send_raw(&self, bytes: impl Into<Vec<u8>>) {
+        self.control(Control::SendRaw(bytes.into()));
+    }
+
+    /// Drop the most recent connection.
+    pub fn disconnect(&self) {
+        self.control(Control::Disconnect);
+    }
+
+    fn control(&self, control: Control) {
+        // A connection that is already gone has nothing left to act on
+        if let Some(tx) = &lock(&self.state).control {
+            let _ = tx.send(control);
+        }
+    }
+}
+
+impl Drop for MockBroker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn lock(state: &Mutex<BrokerState>) -> std::sync::MutexGuard<'_, BrokerState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Why a session stopped before its script finished.
+enum Stop {
+    /// The client closed the connection (or it failed).
+    Disconnected,
+    /// A step closed the connection.
+    Closed,
+}
+
+/// Plays one session on one accepted connection.
+struct SessionRunner {
+    framed: Framed<TcpStream, StompCodec>,
+    state: Arc<Mutex<BrokerState>>,
+    /// Frames read while waiting for something else, not yet consumed by a
+    /// step.
+    backlog: VecDeque<Frame>,
+    /// `(id, destination)` of the most recent SUBSCRIBE.
+    subscription: Option<(String, String)>,
+    answer_receipts: bool,
+    next_message_id: u64,
+    control: mpsc::UnboundedReceiver<Control>,
+}
+
+impl SessionRunner {
+    fn new(
+        stream: TcpStream,
+        state: Arc<Mutex<BrokerState>>,
+        control: mpsc::UnboundedReceiver<Control>,
+    ) -> Self {
+        Self {
+            framed: Framed::new(stream, StompCodec::new()),
+            state,
+            control,
+            backlog: VecDeque::new(),
+            subscription: None,
+            answer_receipts: true,
+            next_message_id: 1,
+        }
+    }
+
+    async fn run(mut self, session: Session) {
+        for step in session.steps {
+            if self.play(step).await.is_err() {
+                return;
+            }
+        }
+        // Script finished: keep serving until the client goes away
+        while self.next_frame().await.is_ok() {}
+    }
+
+    async fn play(&mut self, step: Step) -> Result<(), Stop> {
+        match step {
+            Step::Connected { delay } => {
+                self.expect_handshake().await?;
+                tokio::time::sleep(delay).await;
+                self.write(
+                    Frame::new("CONNECTED")
+                        .header("version", "1.2")
+                        .header("heart-beat", "0,0")
+                        .header("server", "iridium-mock"),
+                )
+                .await
+            }
+            Step::ConnectedWith(frame) => {
+                self.expect_handshake().await?;
+                self.write(frame).await
+            }
+            Step::Reject { message } => {
+                self.expect_handshake().await?;
+                self.write(Frame::new("ERROR").header("message", message))
+                    .await?;
+                Err(Stop::Closed)
+            }
+            Step::Expect(command) => {
+                while self.take_frame().await?.command != command {}
+                Ok(())
+            }
+            Step::Deliver { count } => {
+                self.await_subscription().await?;
+                for _ in 0..count {
+                    let message_id = format!("mock-{}", self.next_message_id);
+                    let frame = Frame::new("MESSAGE").set_body(message_id.into_bytes());
+                    self.deliver(frame).await?;
+                }
+                Ok(())
+            }
+            Step::DeliverFrame(frame) => {
+                self.await_subscription().await?;
+                self.deliver(frame).await
+            }
+            Step::Error(message) => {
+                self.write(Frame::new("ERROR").header("message", message))
+                    .await
+            }
+            Step::Send(frame) => self.write(frame).await,
+            Step::SendRaw(bytes) => self.write_raw(&bytes).await,
+            Step::Wait(duration) => {
+                let sleep = tokio::time::sleep(duration);
+                tokio::pin!(sleep);
+                loop {
+                    tokio::select! {
+                        _ = &mut sleep => return Ok(()),
+                        frame = self.next_frame() => self.backlog.push_back(frame?),
+                    }
+                }
+            }
+            Step::DropAfterFrames(frames) => {
+                for _ in 0..frames {
+                    self.take_frame().await?;
+                }
+                Err(Stop::Closed)
+            }
+            Step::WithholdReceipts => {
+                self.answer_receipts = false;
+                Ok(())
+            }
+            Step::Close => Err(Stop::Closed),
+        }
+    }
+
+    async fn await_subscription(&mut self) -> Result<(), Stop> {
+        while self.subscription.is_none() {
+            self.take_frame().await?;
+        }
+        Ok(())
+    }
+
+    /// Send `frame` as a MESSAGE to the latest subscription, filling in the
+    /// routing headers it lacks.
+    async fn deliver(&mut self, mut frame: Frame) -> Result<(), Stop> {
+        let (id, destination) = self.subscription.clone().unwrap_or_default();
+        let message_id = format!("mock-{}", self.next_message_id);
+        self.next_message_id += 1;
+        for (name, value) in [
+            ("destination", destination.as_str()),
+            ("subscription", id.as_str()),
+            ("message-id", message_id.as_str()),
+        ] {
+            if frame.get_header(name).is_none() {
+                frame = frame.header(name, value);
+            }
+        }
+        if frame.get_header("ack").is_none() {
+            let ack = frame
+                .get_header("message-id")
+                .unwrap_or_default()
+                .to_string();
+            frame = frame.header("ack", ack);
+        }
+        self.write(frame).await
+    }
+
+    async fn expect_handshake(&mut self) -> Result<(), Stop> {
+        loop {
+            let frame = self.take_frame().await?;
+            if frame.command == "CONNECT" || frame.command == "STOMP" {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The next frame not yet consumed by a step.
+    async fn take_frame(&mut self) -> Result<Frame, Stop> {
+        match self.backlog.pop_front() {
+            Some(frame) => Ok(frame),
+            None => self.next_frame().await,
+        }
+    }
+
+    /// Read the next frame from the socket, record it, and apply the
+    /// automatic behavior (receipts, subscription tracking).
+    async fn next_frame(&mut self) -> Result<Frame, Stop> {
+        loop {
+            let item = tokio::select! {
+                item = self.framed.next() => item,
+                Some(control) = self.control.recv() => {
+                    self.apply(control).await?;
+                    continue;
+                }
+            };
+            let frame = match item {
+                Some(Ok(StompItem::Frame(frame))) => frame,
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => return Err(Stop::Disconnected),
+            };
+            lock(&self.state).received.push(frame.clone());
+            if frame.command == "SUBSCRIBE"
+                && let (Some(id), Some(destination)) =
+                    (frame.get_header("id"), frame.get_header("destination"))
+            {
+                self.subscription = Some((id.to_string(), destination.to_string()));
+            }
+            if self.answer_receipts
+                && let Some(receipt) = frame.get_header("receipt")
+            {
+                let reply = Frame::new("RECEIPT").header("receipt-id", receipt);
+                self.write(reply).await?;
+            }
+            return Ok(frame);
+        }
+    }
+
+    async fn apply(&mut self, control: Control) -> Result<(), Stop> {
+        match control {
+            Control::Send(frame) => self.write(frame).await,
+            Control::Deliver(frame) => self.deliver(frame).await,
+            Control::SendRaw(bytes) => self.write_raw(&bytes).await,
+            Control::Disconnect => Err(Stop::Closed),
+        }
+    }
+
+    async fn write_raw(&mut self, bytes: &[u8]) -> Result<(), Stop> {
+        let stream = self.framed.get_mut();
+        stream
+            .write_all(bytes)
+            .await
+            .map_err(|_| Stop::Disconnected)?;
+        stream.flush().await.map_err(|_| Stop::Disconnected)
+    }
+
+    async fn write(&mut self, frame: Frame) -> Result<(), Stop> {
+        self.framed
+            .send(StompItem::Frame(frame))
+            .await
+            .map_err(|_| Stop::Disconnected)
+    }
+}
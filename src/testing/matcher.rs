@@ -0,0 +1,324 @@
+use crate::frame::Frame;
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+
+/// One expectation of a [`FrameMatcher`].
+#[derive(Debug, Clone)]
+enum Check {
+    Command(String),
+    HeaderPresent(String),
+    HeaderAbsent(String),
+    HeaderEquals(String, String),
+    HeaderMatches(String, String),
+    Body(Vec<u8>),
+    Json(String, Value),
+}
+
+/// Declarative expectations about a [`Frame`], for test assertions.
+///
+/// Build a matcher from the checks you care about, then use
+/// [`check`](Self::check) to get every mismatch at once,
+/// [`matches`](Self::matches) to filter frames, or
+/// [`assert`](Self::assert) (or the [`assert_frame!`](crate::assert_frame)
+/// macro) to panic with a readable report. Header names are compared
+/// case-sensitively, and the first occurrence of a repeated header counts,
+/// as `Frame::get_header` does.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::Frame;
+/// use iridium_stomp::testing::FrameMatcher;
+///
+/// let frame = Frame::new("SEND")
+///     .header("destination", "/queue/orders")
+///     .header("receipt", "rcpt-7")
+///     .set_body(br#"{"order":{"id":42}}"#.to_vec());
+///
+/// FrameMatcher::command("SEND")
+///     .header("destination", "/queue/orders")
+///     .header_matches("receipt", r"^rcpt-\d+$")
+///     .json("/order/id", 42)
+///     .assert(&frame);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FrameMatcher {
+    checks: Vec<Check>,
+}
+
+impl FrameMatcher {
+    /// A matcher without expectations; it matches every frame.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A matcher expecting the command `command`.
+    pub fn command(command: impl Into<String>) -> Self {
+        Self::new().with(Check::Command(command.into()))
+    }
+
+    /// Expect header `name` to be present, with any value.
+    pub fn has_header(self, name: impl Into<String>) -> Self {
+        self.with(Check::HeaderPresent(name.into()))
+    }
+
+    /// Expect header `name` to be absent.
+    pub fn lacks_header(self, name: impl Into<String>) -> Self {
+        self.with(Check::HeaderAbsent(name.into()))
+    }
+
+    /// Expect header `name` to equal `value`.
+    pub fn header(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.with(Check::HeaderEquals(name.into(), value.into()))
+    }
+
+    /// Expect header `name` to match the regular expression `pattern`.
+    /// An invalid pattern is reported as a mismatch.
+    pub fn header_matches(self, name: impl Into<String>, pattern: impl Into<String>) -> Self {
+        self.with(Check::HeaderMatches(name.into(), pattern.into()))
+    }
+
+    /// Expect the body to be exactly `body`.
+    pub fn body(self, body: impl AsRef<[u8]>) -> Self {
+        self.with(Check::Body(body.as_ref().to_vec()))
+    }
+
+    /// Expect the body to be JSON with `value` at `pointer`, a JSON Pointer
+    /// such as `/order/items/0/sku` (RFC 6901; `""` is the whole document).
+    pub fn json(self, pointer: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.with(Check::Json(pointer.into(), value.into()))
+    }
+
+    fn with(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Compare `frame` against every expectation.
+    ///
+    /// Returns all failed expectations, not just the first, so a single
+    /// run shows everything that differs.
+    pub fn check(&self, frame: &Frame) -> Result<(), FrameMismatch> {
+        let failures: Vec<String> = self
+            .checks
+            .iter()
+            .filter_map(|check| check_one(check, frame))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(FrameMismatch {
+                failures,
+                frame: Box::new(frame.clone()),
+            })
+        }
+    }
+
+    /// Whether `frame` meets every expectation.
+    pub fn matches(&self, frame: &Frame) -> bool {
+        self.check(frame).is_ok()
+    }
+
+    /// Panic with a report of every failed expectation unless `frame`
+    /// meets them all.
+    #[track_caller]
+    pub fn assert(&self, frame: &Frame) {
+        if let Err(mismatch) = self.check(frame) {
+            panic!("{}", mismatch);
+        }
+    }
+}
+
+/// Describe how `frame` fails `check`, or `None` if it passes.
+fn check_one(check: &Check, frame: &Frame) -> Option<String> {
+    match check {
+        Check::Command(expected) => (frame.command != *expected)
+            .then(|| format!("command: expected {:?}, got {:?}", expected, frame.command)),
+        Check::HeaderPresent(name) => frame
+            .get_header(name)
+            .is_none()
+            .then(|| format!("header '{}': expected present, missing", name)),
+        Check::HeaderAbsent(name) => frame
+            .get_header(name)
+            .map(|got| format!("header '{}': expected absent, got {:?}", name, got)),
+        Check::HeaderEquals(name, expected) => match frame.get_header(name) {
+            Some(got) if got == expected => None,
+            Some(got) => Some(format!(
+                "header '{}': expected {:?}, got {:?}",
+                name, expected, got
+            )),
+            None => Some(format!(
+                "header '{}': expected {:?}, missing",
+                name, expected
+            )),
+        },
+        Check::HeaderMatches(name, pattern) => {
+            let regex = match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(e) => return Some(format!("header '{}': invalid pattern: {}", name, e)),
+            };
+            match frame.get_header(name) {
+                Some(got) if regex.is_match(got) => None,
+                Some(got) => Some(format!(
+                    "header '{}': expected to match /{}/, got {:?}",
+                    name, pattern, got
+                )),
+                None => Some(format!(
+                    "header '{}': expected to match /{}/, missing",
+                    name, pattern
+                )),
+            }
+        }
+        Check::Body(expected) => (frame.body != *expected).then(|| {
+            format!(
+                "body: expected {:?}, got {:?}",
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(&frame.body)
+            )
+        }),
+        Check::Json(pointer, expected) => {
+            let document: Value = match serde_json::from_slice(&frame.body) {
+                Ok(document) => document,
+                Err(e) => return Some(format!("body: expected JSON, {}", e)),
+            };
+            match document.pointer(pointer) {
+                Some(got) if got == expected => None,
+                Some(got) => Some(format!(
+                    "json '{}': expected {}, got {}",
+                    pointer, expected, got
+                )),
+                None => Some(format!(
+                    "json '{}': expected {}, missing",
+                    pointer, expected
+                )),
+            }
+        }
+    }
+}
+
+/// The expectations a frame failed, returned by [`FrameMatcher::check`].
+///
+/// Its `Display` output lists each failure followed by the frame itself.
+#[derive(Debug, Clone)]
+pub struct FrameMismatch {
+    failures: Vec<String>,
+    frame: Box<Frame>,
+}
+
+impl FrameMismatch {
+    /// One line per failed expectation.
+    pub fn failures(&self) -> &[String] {
+        &self.failures
+    }
+
+    /// The frame that was checked.
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+impl fmt::Display for FrameMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "frame does not match:")?;
+        for failure in &self.failures {
+            writeln!(f, "  - {}", failure)?;
+        }
+        writeln!(f, "frame:")?;
+        write!(f, "{}", self.frame)?;
+        writeln!(f, "Body: {:?}", String::from_utf8_lossy(&self.frame.body))
+    }
+}
+
+impl std::error::Error for FrameMismatch {}
+
+/// Assert that a [`Frame`] matches expectations, panicking with a report
+/// of every difference (requires the `testing` feature).
+///
+/// Takes either a [`FrameMatcher`](crate::testing::FrameMatcher) or a
+/// command followed by `"header" => value` pairs:
+///
+/// ```
+/// use iridium_stomp::testing::FrameMatcher;
+/// use iridium_stomp::{Frame, assert_frame};
+///
+/// let frame = Frame::new("ACK").header("id", "m1").header("subscription", "1");
+///
+/// assert_frame!(frame, "ACK", "id" => "m1", "subscription" => "1");
+/// assert_frame!(frame, FrameMatcher::command("ACK").lacks_header("transaction"));
+/// ```
+#[macro_export]
+macro_rules! assert_frame {
+    ($frame:expr, $command:literal $(, $name:literal => $value:expr)* $(,)?) => {
+        $crate::testing::FrameMatcher::command($command)
+            $(.header($name, $value))*
+            .assert(&$frame)
+    };
+    ($frame:expr, $matcher:expr $(,)?) => {
+        $crate::testing::FrameMatcher::assert(&$matcher, &$frame)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send() -> Frame {
+        Frame::new("SEND")
+            .header("destination", "/queue/a")
+            .header("receipt", "rcpt-12")
+            .set_body(br#"{"items":[{"sku":"x1","qty":2}]}"#.to_vec())
+    }
+
+    #[test]
+    fn matching_frame_passes() {
+        let matcher = FrameMatcher::command("SEND")
+            .has_header("destination")
+            .lacks_header("transaction")
+            .header("destination", "/queue/a")
+            .header_matches("receipt", r"^rcpt-\d+$")
+            .json("/items/0/sku", "x1")
+            .json("/items/0/qty", 2);
+        assert!(matcher.check(&send()).is_ok());
+    }
+
+    #[test]
+    fn every_failure_is_reported() {
+        let mismatch = FrameMatcher::command("MESSAGE")
+            .header("destination", "/queue/b")
+            .has_header("message-id")
+            .header_matches("receipt", "^x")
+            .json("/items/0/qty", 3)
+            .json("/missing", true)
+            .check(&send())
+            .unwrap_err();
+        assert_eq!(
+            mismatch.failures(),
+            [
+                r#"command: expected "MESSAGE", got "SEND""#,
+                r#"header 'destination': expected "/queue/b", got "/queue/a""#,
+                "header 'message-id': expected present, missing",
+                r#"header 'receipt': expected to match /^x/, got "rcpt-12""#,
+                "json '/items/0/qty': expected 3, got 2",
+                "json '/missing': expected true, missing",
+            ]
+        );
+        assert!(mismatch.to_string().contains("Command: SEND"));
+    }
+
+    #[test]
+    fn body_and_invalid_inputs_are_mismatches() {
+        let frame = Frame::new("SEND").set_body(b"plain".to_vec());
+        assert!(FrameMatcher::new().body("plain").matches(&frame));
+        assert!(!FrameMatcher::new().body("other").matches(&frame));
+        assert!(!FrameMatcher::new().json("", "plain").matches(&frame));
+        assert!(!FrameMatcher::new().header_matches("x", "(").matches(&frame));
+    }
+
+    #[test]
+    #[should_panic(expected = "header 'id': expected \"m2\", got \"m1\"")]
+    fn macro_panics_with_report() {
+        let frame = Frame::new("ACK").header("id", "m1");
+        crate::assert_frame!(frame, "ACK", "id" => "m2");
+    }
+}
@@ -0,0 +1,665 @@
+//! Wire-level STOMP 1.2 test vectors (requires the `testing` feature).
+//!
+//! Each [`TestVector`] is a byte sequence as it arrives from the network
+//! together with what a conforming decoder makes of it: the frames and
+//! heartbeats it yields, or a rejection. The set covers the examples and
+//! rules of the STOMP 1.2 specification (escaping, repeated headers,
+//! `content-length` bodies containing NUL, CRLF line endings) and frames in
+//! the layout RabbitMQ, ActiveMQ Classic and ActiveMQ Artemis send them,
+//! with their own header order, id formats and ERROR bodies.
+//!
+//! The crate checks its own codec against them; use
+//! [`TestVector::check_decoder()`] to do the same for a custom decoder, or
+//! feed [`TestVector::bytes`] through middleware and compare with
+//! [`TestVector::expected`].
+//!
+//! # Example
+//!
+//! ```
+//! use iridium_stomp::StompCodec;
+//! use iridium_stomp::testvectors;
+//!
+//! for vector in testvectors::all() {
+//!     vector.check_decoder(StompCodec::new).unwrap();
+//! }
+//! ```
+
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+
+use crate::codec::StompItem;
+use crate::frame::Frame;
+
+/// Where a [`TestVector`]'s bytes come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Source {
+    /// An example or rule of the STOMP 1.2 specification.
+    Spec,
+    /// The frame layout of RabbitMQ's STOMP plugin.
+    RabbitMq,
+    /// The frame layout of ActiveMQ Classic.
+    ActiveMq,
+    /// The frame layout of ActiveMQ Artemis.
+    Artemis,
+}
+
+/// A frame a [`TestVector`] decodes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedFrame {
+    /// The command.
+    pub command: &'static str,
+    /// Every header in wire order, unescaped, repeated ones included.
+    pub headers: &'static [(&'static str, &'static str)],
+    /// The body.
+    pub body: &'static [u8],
+}
+
+impl ExpectedFrame {
+    /// What differs between `frame` and this expectation, if anything.
+    pub fn mismatch(&self, frame: &Frame) -> Option<String> {
+        if frame.command != self.command {
+            return Some(format!(
+                "command {:?}, expected {:?}",
+                frame.command, self.command
+            ));
+        }
+        let headers: Vec<(&str, &str)> = frame
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        if headers != self.headers {
+            return Some(format!(
+                "headers {:?}, expected {:?}",
+                headers, self.headers
+            ));
+        }
+        if frame.body != self.body {
+            return Some(format!(
+                "body {:?}, expected {:?}",
+                String::from_utf8_lossy(&frame.body),
+                String::from_utf8_lossy(self.body)
+            ));
+        }
+        None
+    }
+}
+
+/// One item a [`TestVector`] decodes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedItem {
+    /// A frame.
+    Frame(ExpectedFrame),
+    /// A heartbeat (a lone LF or CRLF).
+    Heartbeat,
+}
+
+/// What a conforming decoder does with a [`TestVector`]'s bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// Decodes to these items, in order, with no bytes left over.
+    Items(&'static [ExpectedItem]),
+    /// Fails with a decode error: the spec makes the input a fatal
+    /// protocol error.
+    Rejected,
+}
+
+/// A byte sequence and how it decodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestVector {
+    /// Short unique name, e.g. `spec/header-escaping`.
+    pub name: &'static str,
+    /// Where the bytes come from.
+    pub source: Source,
+    /// What the vector exercises.
+    pub description: &'static str,
+    /// The bytes as they arrive from the network.
+    pub bytes: &'static [u8],
+    /// How they decode.
+    pub expected: Expected,
+}
+
+/// Why a decoder failed a [`TestVector`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{vector}: {detail}")]
+pub struct VectorMismatch {
+    /// The vector's name.
+    pub vector: &'static str,
+    /// What went wrong.
+    pub detail: String,
+}
+
+impl TestVector {
+    /// Decode [`bytes`](Self::bytes) with decoders made by `new_decoder`
+    /// and compare with [`expected`](Self::expected).
+    ///
+    /// The bytes are decoded twice: from one buffer, and fed one byte at a
+    /// time as a slow network would deliver them. Both must give the
+    /// expected result.
+    pub fn check_decoder<D>(&self, mut new_decoder: impl FnMut() -> D) -> Result<(), VectorMismatch>
+    where
+        D: Decoder<Item = StompItem>,
+        D::Error: std::fmt::Display,
+    {
+        self.check_decoded("whole buffer", decode(new_decoder(), &[self.bytes]))?;
+        let bytewise: Vec<&[u8]> = self.bytes.chunks(1).collect();
+        self.check_decoded("byte by byte", decode(new_decoder(), &bytewise))
+    }
+
+    /// Compare the result of decoding [`bytes`](Self::bytes) with
+    /// [`expected`](Self::expected). `decoded` is the items decoded, or the
+    /// decode error as text.
+    pub fn check_items(&self, decoded: Result<&[StompItem], &str>) -> Result<(), VectorMismatch> {
+        let mismatch = |detail: String| VectorMismatch {
+            vector: self.name,
+            detail,
+        };
+        let items = match (self.expected, decoded) {
+            (Expected::Rejected, Err(_)) => return Ok(()),
+            (Expected::Rejected, Ok(items)) => {
+                return Err(mismatch(format!(
+                    "decoded {} items, expected a decode error",
+                    items.len()
+                )));
+            }
+            (Expected::Items(_), Err(e)) => return Err(mismatch(format!("decode error: {}", e))),
+            (Expected::Items(expected), Ok(items)) => (expected, items),
+        };
+        let (expected, items) = items;
+        if expected.len() != items.len() {
+            return Err(mismatch(format!(
+                "decoded {} items, expected {}",
+                items.len(),
+                expected.len()
+            )));
+        }
+        for (i, (want, got)) in expected.iter().zip(items).enumerate() {
+            match (want, got) {
+                (ExpectedItem::Heartbeat, StompItem::Heartbeat) => {}
+                (
+                    ExpectedItem::Frame(want),
+                    StompItem::Frame(frame) | StompItem::FrameWithContentLength(frame, _),
+                ) => {
+                    if let Some(detail) = want.mismatch(frame) {
+                        return Err(mismatch(format!("item {}: {}", i, detail)));
+                    }
+                }
+                (
+                    ExpectedItem::Heartbeat,
+                    StompItem::Frame(frame) | StompItem::FrameWithContentLength(frame, _),
+                ) => {
+                    return Err(mismatch(format!(
+                        "item {}: {} frame, expected a heartbeat",
+                        i, frame.command
+                    )));
+                }
+                (ExpectedItem::Frame(want), StompItem::Heartbeat) => {
+                    return Err(mismatch(format!(
+                        "item {}: heartbeat, expected a {} frame",
+                        i, want.command
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_decoded(
+        &self,
+        feed: &str,
+        decoded: Result<Vec<StompItem>, String>,
+    ) -> Result<(), VectorMismatch> {
+        self.check_items(decoded.as_deref().map_err(String::as_str))
+            .map_err(|e| VectorMismatch {
+                detail: format!("{} ({})", e.detail, feed),
+                ..e
+            })
+    }
+}
+
+/// Feed `chunks` to `decoder` one after the other, collecting every item.
+/// Bytes left over at the end count as an error.
+fn decode<D>(mut decoder: D, chunks: &[&[u8]]) -> Result<Vec<StompItem>, String>
+where
+    D: Decoder<Item = StompItem>,
+    D::Error: std::fmt::Display,
+{
+    let mut buf = BytesMut::new();
+    let mut items = Vec::new();
+    for chunk in chunks {
+        buf.extend_from_slice(chunk);
+        while let Some(item) = decoder.decode(&mut buf).map_err(|e| e.to_string())? {
+            items.push(item);
+        }
+    }
+    if !buf.is_empty() {
+        return Err(format!("{} bytes left undecoded", buf.len()));
+    }
+    Ok(items)
+}
+
+/// Every test vector.
+pub fn all() -> &'static [TestVector] {
+    VECTORS
+}
+
+/// The test vectors from `source`.
+pub fn from_source(source: Source) -> impl Iterator<Item = &'static TestVector> {
+    VECTORS.iter().filter(move |v| v.source == source)
+}
+
+/// The test vector called `name`.
+pub fn get(name: &str) -> Option<&'static TestVector> {
+    VECTORS.iter().find(|v| v.name == name)
+}
+
+const fn frame(
+    command: &'static str,
+    headers: &'static [(&'static str, &'static str)],
+    body: &'static [u8],
+) -> ExpectedItem {
+    ExpectedItem::Frame(ExpectedFrame {
+        command,
+        headers,
+        body,
+    })
+}
+
+static VECTORS: &[TestVector] = &[
+    TestVector {
+        name: "spec/connect",
+        source: Source::Spec,
+        description: "CONNECT frame from the spec's connecting example",
+        bytes: b"CONNECT\naccept-version:1.2\nhost:stomp.github.org\n\n\0",
+        expected: Expected::Items(&[frame(
+            "CONNECT",
+            &[("accept-version", "1.2"), ("host", "stomp.github.org")],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "spec/connected",
+        source: Source::Spec,
+        description: "Minimal CONNECTED frame",
+        bytes: b"CONNECTED\nversion:1.2\n\n\0",
+        expected: Expected::Items(&[frame("CONNECTED", &[("version", "1.2")], b"")]),
+    },
+    TestVector {
+        name: "spec/send",
+        source: Source::Spec,
+        description: "SEND frame from the spec, body without content-length",
+        bytes: b"SEND\ndestination:/queue/a\ncontent-type:text/plain\n\nhello queue a\0",
+        expected: Expected::Items(&[frame(
+            "SEND",
+            &[("destination", "/queue/a"), ("content-type", "text/plain")],
+            b"hello queue a",
+        )]),
+    },
+    TestVector {
+        name: "spec/heartbeat-lf",
+        source: Source::Spec,
+        description: "A heartbeat is a single LF",
+        bytes: b"\n",
+        expected: Expected::Items(&[ExpectedItem::Heartbeat]),
+    },
+    TestVector {
+        name: "spec/heartbeat-crlf",
+        source: Source::Spec,
+        description: "A heartbeat may also be CRLF",
+        bytes: b"\r\n",
+        expected: Expected::Items(&[ExpectedItem::Heartbeat]),
+    },
+    TestVector {
+        name: "spec/heartbeat-before-frame",
+        source: Source::Spec,
+        description: "Heartbeats between frames are separate items",
+        bytes: b"\n\nRECEIPT\nreceipt-id:77\n\n\0",
+        expected: Expected::Items(&[
+            ExpectedItem::Heartbeat,
+            ExpectedItem::Heartbeat,
+            frame("RECEIPT", &[("receipt-id", "77")], b""),
+        ]),
+    },
+    TestVector {
+        name: "spec/header-escaping",
+        source: Source::Spec,
+        description: "Header values unescape \\c, \\n, \\r and \\\\",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:1\nsubscription:0\nfoo:a\\cb\\nc\\\\d\\re\n\n\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "1"),
+                ("subscription", "0"),
+                ("foo", "a:b\nc\\d\re"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "spec/escaped-header-name",
+        source: Source::Spec,
+        description: "Header names are unescaped like values",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:1\nsubscription:0\nkey\\cpart:v\n\n\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "1"),
+                ("subscription", "0"),
+                ("key:part", "v"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "spec/undefined-escape",
+        source: Source::Spec,
+        description: "An undefined escape such as \\t is a fatal protocol error",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:1\nsubscription:0\npath:C:\\temp\n\n\0",
+        expected: Expected::Rejected,
+    },
+    TestVector {
+        name: "spec/repeated-header",
+        source: Source::Spec,
+        description: "Repeated headers are all kept in order; the first one counts",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:1\nsubscription:0\nfoo:World\nfoo:Hello\n\n\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "1"),
+                ("subscription", "0"),
+                ("foo", "World"),
+                ("foo", "Hello"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "spec/colon-in-value",
+        source: Source::Spec,
+        description: "Only the first colon separates name and value",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:1\nsubscription:0\nreply-url:http://host:8080/x\n\n\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "1"),
+                ("subscription", "0"),
+                ("reply-url", "http://host:8080/x"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "spec/empty-header-value",
+        source: Source::Spec,
+        description: "A header may have an empty value",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:1\nsubscription:0\ncorrelation-id:\n\n\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "1"),
+                ("subscription", "0"),
+                ("correlation-id", ""),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "spec/content-length-nul-body",
+        source: Source::Spec,
+        description: "With content-length the body may contain NUL bytes",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:2\nsubscription:0\ncontent-length:5\n\na\0b\0c\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "2"),
+                ("subscription", "0"),
+                ("content-length", "5"),
+            ],
+            b"a\0b\0c",
+        )]),
+    },
+    TestVector {
+        name: "spec/content-length-mismatch",
+        source: Source::Spec,
+        description: "The byte after content-length bytes of body must be NUL",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:2\nsubscription:0\ncontent-length:3\n\nabcdef\0",
+        expected: Expected::Rejected,
+    },
+    TestVector {
+        name: "spec/content-length-invalid",
+        source: Source::Spec,
+        description: "content-length must be a number of bytes",
+        bytes: b"MESSAGE\ndestination:/queue/a\nmessage-id:2\nsubscription:0\ncontent-length:five\n\nhello\0",
+        expected: Expected::Rejected,
+    },
+    TestVector {
+        name: "spec/crlf-line-endings",
+        source: Source::Spec,
+        description: "Lines may end in CRLF instead of LF",
+        bytes: b"MESSAGE\r\ndestination:/queue/a\r\nmessage-id:3\r\nsubscription:0\r\n\r\nbody\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("destination", "/queue/a"),
+                ("message-id", "3"),
+                ("subscription", "0"),
+            ],
+            b"body",
+        )]),
+    },
+    TestVector {
+        name: "spec/back-to-back-frames",
+        source: Source::Spec,
+        description: "Two frames in one read",
+        bytes: b"RECEIPT\nreceipt-id:1\n\n\0RECEIPT\nreceipt-id:2\n\n\0",
+        expected: Expected::Items(&[
+            frame("RECEIPT", &[("receipt-id", "1")], b""),
+            frame("RECEIPT", &[("receipt-id", "2")], b""),
+        ]),
+    },
+    TestVector {
+        name: "spec/error",
+        source: Source::Spec,
+        description: "ERROR frame from the spec, with a multi-line body",
+        bytes: b"ERROR\nreceipt-id:message-12345\ncontent-type:text/plain\ncontent-length:170\nmessage:malformed frame received\n\nThe message:\n-----\nMESSAGE\ndestined:/queue/a\nreceipt:message-12345\n\nHello queue a!\n-----\nDid not contain a destination header, which is REQUIRED\nfor message propagation.\n\0",
+        expected: Expected::Items(&[frame(
+            "ERROR",
+            &[
+                ("receipt-id", "message-12345"),
+                ("content-type", "text/plain"),
+                ("content-length", "170"),
+                ("message", "malformed frame received"),
+            ],
+            b"The message:\n-----\nMESSAGE\ndestined:/queue/a\nreceipt:message-12345\n\nHello queue a!\n-----\nDid not contain a destination header, which is REQUIRED\nfor message propagation.\n",
+        )]),
+    },
+    TestVector {
+        name: "rabbitmq/connected",
+        source: Source::RabbitMq,
+        description: "CONNECTED with server, session and heart-beat headers",
+        bytes: b"CONNECTED\nserver:RabbitMQ/3.13.7\nsession:session-5nbOK0b8W_bJP4sOgW6wbw\nheart-beat:10000,10000\nversion:1.2\n\n\0",
+        expected: Expected::Items(&[frame(
+            "CONNECTED",
+            &[
+                ("server", "RabbitMQ/3.13.7"),
+                ("session", "session-5nbOK0b8W_bJP4sOgW6wbw"),
+                ("heart-beat", "10000,10000"),
+                ("version", "1.2"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "rabbitmq/message",
+        source: Source::RabbitMq,
+        description: "MESSAGE with a session-scoped message id and a JSON body",
+        bytes: b"MESSAGE\nsubscription:sub-0\ndestination:/queue/orders\nmessage-id:T_sub-0@@session-5nbOK0b8W_bJP4sOgW6wbw@@1\nredelivered:false\ncontent-type:application/json\ncontent-length:11\n\n{\"id\":1234}\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("subscription", "sub-0"),
+                ("destination", "/queue/orders"),
+                (
+                    "message-id",
+                    "T_sub-0@@session-5nbOK0b8W_bJP4sOgW6wbw@@1",
+                ),
+                ("redelivered", "false"),
+                ("content-type", "application/json"),
+                ("content-length", "11"),
+            ],
+            b"{\"id\":1234}",
+        )]),
+    },
+    TestVector {
+        name: "rabbitmq/error-not-found",
+        source: Source::RabbitMq,
+        description: "ERROR for a missing queue, the body ending in a newline",
+        bytes: b"ERROR\nmessage:not_found\ncontent-type:text/plain\nversion:1.0,1.1,1.2\ncontent-length:44\n\nNOT_FOUND - no queue 'missing' in vhost '/'\n\0",
+        expected: Expected::Items(&[frame(
+            "ERROR",
+            &[
+                ("message", "not_found"),
+                ("content-type", "text/plain"),
+                ("version", "1.0,1.1,1.2"),
+                ("content-length", "44"),
+            ],
+            b"NOT_FOUND - no queue 'missing' in vhost '/'\n",
+        )]),
+    },
+    TestVector {
+        name: "activemq/connected",
+        source: Source::ActiveMq,
+        description: "CONNECTED whose session id contains colons",
+        bytes: b"CONNECTED\nserver:ActiveMQ/5.18.3\nheart-beat:10000,10000\nsession:ID:broker-40301-1700000000000-3:1\nversion:1.2\n\n\0",
+        expected: Expected::Items(&[frame(
+            "CONNECTED",
+            &[
+                ("server", "ActiveMQ/5.18.3"),
+                ("heart-beat", "10000,10000"),
+                ("session", "ID:broker-40301-1700000000000-3:1"),
+                ("version", "1.2"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "activemq/message",
+        source: Source::ActiveMq,
+        description: "MESSAGE with JMS headers and content-length first",
+        bytes: b"MESSAGE\ncontent-length:5\nexpires:0\ndestination:/queue/orders\nsubscription:1\npriority:4\nmessage-id:ID:broker-40301-1700000000000-3:1:1:1:1\npersistent:true\ntimestamp:1700000000123\n\nhello\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("content-length", "5"),
+                ("expires", "0"),
+                ("destination", "/queue/orders"),
+                ("subscription", "1"),
+                ("priority", "4"),
+                ("message-id", "ID:broker-40301-1700000000000-3:1:1:1:1"),
+                ("persistent", "true"),
+                ("timestamp", "1700000000123"),
+            ],
+            b"hello",
+        )]),
+    },
+    TestVector {
+        name: "activemq/error",
+        source: Source::ActiveMq,
+        description: "ERROR with a Java stack trace body and no content-length",
+        bytes: b"ERROR\ncontent-type:text/plain\nmessage:Unknown STOMP action: FOO\n\norg.apache.activemq.transport.stomp.ProtocolException: Unknown STOMP action: FOO\n\tat org.apache.activemq.transport.stomp.ProtocolConverter.onStompCommand(ProtocolConverter.java:267)\n\0",
+        expected: Expected::Items(&[frame(
+            "ERROR",
+            &[
+                ("content-type", "text/plain"),
+                ("message", "Unknown STOMP action: FOO"),
+            ],
+            b"org.apache.activemq.transport.stomp.ProtocolException: Unknown STOMP action: FOO\n\tat org.apache.activemq.transport.stomp.ProtocolConverter.onStompCommand(ProtocolConverter.java:267)\n",
+        )]),
+    },
+    TestVector {
+        name: "artemis/connected",
+        source: Source::Artemis,
+        description: "CONNECTED with a server header containing spaces",
+        bytes: b"CONNECTED\nversion:1.2\nsession:3f4e2a1b\nserver:ActiveMQ-Artemis/2.31.2 ActiveMQ Artemis Messaging Engine\nheart-beat:10000,10000\n\n\0",
+        expected: Expected::Items(&[frame(
+            "CONNECTED",
+            &[
+                ("version", "1.2"),
+                ("session", "3f4e2a1b"),
+                (
+                    "server",
+                    "ActiveMQ-Artemis/2.31.2 ActiveMQ Artemis Messaging Engine",
+                ),
+                ("heart-beat", "10000,10000"),
+            ],
+            b"",
+        )]),
+    },
+    TestVector {
+        name: "artemis/message",
+        source: Source::Artemis,
+        description: "MESSAGE for a bare address name with internal __AMQ_CID header",
+        bytes: b"MESSAGE\nsubscription:1\ncontent-length:5\nmessage-id:1234\ndestination:orders\nexpires:0\nredelivered:false\npriority:4\npersistent:true\n__AMQ_CID:3f4e2a1b\ntimestamp:1700000000123\n\nhello\0",
+        expected: Expected::Items(&[frame(
+            "MESSAGE",
+            &[
+                ("subscription", "1"),
+                ("content-length", "5"),
+                ("message-id", "1234"),
+                ("destination", "orders"),
+                ("expires", "0"),
+                ("redelivered", "false"),
+                ("priority", "4"),
+                ("persistent", "true"),
+                ("__AMQ_CID", "3f4e2a1b"),
+                ("timestamp", "1700000000123"),
+            ],
+            b"hello",
+        )]),
+    },
+    TestVector {
+        name: "artemis/error-subscribe",
+        source: Source::Artemis,
+        description: "ERROR refusing a subscription, with only a message header",
+        bytes: b"ERROR\nmessage:AMQ339016: Error creating subscription 1\n\n\0",
+        expected: Expected::Items(&[frame(
+            "ERROR",
+            &[("message", "AMQ339016: Error creating subscription 1")],
+            b"",
+        )]),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn names_are_unique() {
+        let names: HashSet<&str> = all().iter().map(|v| v.name).collect();
+        assert_eq!(names.len(), all().len());
+        assert!(get("spec/header-escaping").is_some());
+        assert!(from_source(Source::Artemis).count() >= 3);
+    }
+
+    #[test]
+    fn mismatches_name_the_vector_and_the_difference() {
+        let vector = get("spec/connected").unwrap();
+        let wrong = [StompItem::Frame(
+            Frame::new("CONNECTED").header("version", "1.1"),
+        )];
+        let err = vector.check_items(Ok(&wrong)).unwrap_err();
+        assert_eq!(err.vector, "spec/connected");
+        assert!(err.to_string().contains("headers"), "{}", err);
+        let err = vector.check_items(Err("boom")).unwrap_err();
+        assert!(err.to_string().contains("boom"), "{}", err);
+    }
+}
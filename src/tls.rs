@@ -0,0 +1,393 @@
+//! STOMP over TLS (the `tls` feature).
+//!
+//! Attach [`TlsOptions`] to a connection with `ConnectOptions::tls()` to
+//! wrap the TCP socket in TLS before the STOMP handshake. Certificates are
+//! verified against the Mozilla root store bundled by `webpki-roots`, or
+//! against the CA certificates given with
+//! [`TlsOptions::ca_certificates_pem()`], and the server name sent via SNI
+//! (and checked against the certificate) defaults to the host part of the
+//! connection address.
+//!
+//! Once connected, `ServerInfo::tls` reports the negotiated protocol
+//! version, cipher suite, ALPN protocol, and the broker's certificate.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{CryptoProvider, ring};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+
+use crate::address::host_of;
+
+/// TLS settings for a connection.
+///
+/// # Example
+///
+/// ```ignore
+/// use iridium_stomp::{ConnectOptions, TlsOptions};
+///
+/// let options = ConnectOptions::default().tls(
+///     TlsOptions::new()
+///         .server_name("broker.internal.example.com")
+///         .alpn_protocols(["stomp"]),
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct TlsOptions {
+    server_name: Option<String>,
+    alpn_protocols: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+    /// PEM blobs of the CA certificates to trust instead of the Mozilla
+    /// roots.
+    ca_certificates: Vec<Vec<u8>>,
+    /// PEM certificate chain and private key presented to the broker.
+    client_certificate: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl TlsOptions {
+    /// TLS with certificate verification and the default server name.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the server name sent via SNI and verified against the
+    /// certificate. Needed when connecting by IP address or through a
+    /// proxy whose address differs from the name on the certificate.
+    pub fn server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Protocols to offer via ALPN, in order of preference. Some load
+    /// balancers route on the ALPN protocol. None are offered by default.
+    pub fn alpn_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        self.alpn_protocols = protocols.into_iter().map(|p| p.as_ref().to_vec()).collect();
+        self
+    }
+
+    /// Trust the CA certificates in `pem` (one or more `CERTIFICATE`
+    /// blocks) instead of the bundled Mozilla roots, for brokers whose
+    /// certificate comes from a private CA. Certificates from several calls
+    /// are all trusted.
+    ///
+    /// The PEM is parsed when connecting; a file without certificates or
+    /// with a malformed one fails the connection with
+    /// `ErrorKind::InvalidInput`.
+    pub fn ca_certificates_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.ca_certificates.push(pem.into());
+        self
+    }
+
+    /// Present a client certificate to brokers that authenticate clients
+    /// by certificate (mutual TLS). `cert_chain` holds the client
+    /// certificate, optionally followed by its intermediates, and `key` its
+    /// private key (PKCS#8, PKCS#1 or SEC1), both in PEM.
+    pub fn client_certificate_pem(
+        mut self,
+        cert_chain: impl Into<Vec<u8>>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.client_certificate = Some((cert_chain.into(), key.into()));
+        self
+    }
+
+    /// Accept any server certificate, including self-signed and expired
+    /// ones, and skip the server name check.
+    ///
+    /// This removes all protection against impersonation and is meant for
+    /// local development brokers only. A warning is logged on every
+    /// connection made with it.
+    pub fn danger_accept_invalid_certs(mut self) -> Self {
+        self.accept_invalid_certs = true;
+        self
+    }
+
+    /// Build the connector used for every connection attempt, or describe
+    /// why the settings are unusable.
+    pub(crate) fn setup(&self, addr: &str) -> Result<TlsSetup, String> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("TLS configuration error: {}", e))?;
+
+        let builder = if self.accept_invalid_certs {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        } else {
+            builder.with_root_certificates(self.root_store()?)
+        };
+        let mut config = match &self.client_certificate {
+            Some((cert_chain, key)) => {
+                let certs = parse_certificates(cert_chain)
+                    .map_err(|e| format!("invalid client certificate: {}", e))?;
+                let key = PrivateKeyDer::from_pem_slice(key)
+                    .map_err(|e| format!("invalid client key: {}", e))?;
+                builder
+                    .with_client_auth_cert(certs, key)
+                    .map_err(|e| format!("invalid client certificate: {}", e))?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = self.alpn_protocols.clone();
+
+        let name = match &self.server_name {
+            Some(name) => name.as_str(),
+            None => host_of(addr),
+        };
+        let server_name = ServerName::try_from(name.to_string())
+            .map_err(|_| format!("invalid TLS server name '{}'", name))?;
+
+        Ok(TlsSetup {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name,
+            insecure: self.accept_invalid_certs,
+        })
+    }
+
+    /// The certificates given with `ca_certificates_pem()`, or the Mozilla
+    /// roots if there are none.
+    fn root_store(&self) -> Result<RootCertStore, String> {
+        if self.ca_certificates.is_empty() {
+            return Ok(RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            });
+        }
+        let mut roots = RootCertStore::empty();
+        for pem in &self.ca_certificates {
+            for cert in
+                parse_certificates(pem).map_err(|e| format!("invalid CA certificate: {}", e))?
+            {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("invalid CA certificate: {}", e))?;
+            }
+        }
+        Ok(roots)
+    }
+}
+
+/// The certificates of a PEM blob, failing if there are none.
+fn parse_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if certs.is_empty() {
+        return Err("no CERTIFICATE found in PEM".to_string());
+    }
+    Ok(certs)
+}
+
+impl std::fmt::Debug for TlsOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let alpn: Vec<_> = self
+            .alpn_protocols
+            .iter()
+            .map(|p| String::from_utf8_lossy(p))
+            .collect();
+        f.debug_struct("TlsOptions")
+            .field("server_name", &self.server_name)
+            .field("alpn_protocols", &alpn)
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("ca_certificates", &self.ca_certificates.len())
+            .field("client_certificate", &self.client_certificate.is_some())
+            .finish()
+    }
+}
+
+/// A ready-to-use TLS client configuration.
+#[derive(Clone)]
+pub(crate) struct TlsSetup {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+    insecure: bool,
+}
+
+impl TlsSetup {
+    /// Run the TLS handshake over an established TCP connection.
+    pub(crate) async fn handshake(
+        &self,
+        stream: TcpStream,
+    ) -> std::io::Result<TlsStream<TcpStream>> {
+        if self.insecure {
+            tracing::warn!(
+                server_name = ?self.server_name,
+                "TLS certificate verification is disabled (danger_accept_invalid_certs)"
+            );
+        }
+        self.connector
+            .connect(self.server_name.clone(), stream)
+            .await
+    }
+}
+
+/// Details of the TLS session a connection negotiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Negotiated protocol version, e.g. "TLSv1_3".
+    pub protocol_version: String,
+    /// Negotiated cipher suite, e.g. "TLS13_AES_256_GCM_SHA384".
+    pub cipher_suite: String,
+    /// Protocol selected via ALPN, if any.
+    pub alpn_protocol: Option<String>,
+    /// The certificate the broker presented, if any.
+    pub peer_certificate: Option<PeerCertificate>,
+}
+
+impl TlsInfo {
+    pub(crate) fn of(stream: &TlsStream<TcpStream>) -> Self {
+        let (_, session) = stream.get_ref();
+        Self {
+            protocol_version: session
+                .protocol_version()
+                .map(|v| format!("{:?}", v))
+                .unwrap_or_default(),
+            cipher_suite: session
+                .negotiated_cipher_suite()
+                .map(|c| format!("{:?}", c.suite()))
+                .unwrap_or_default(),
+            alpn_protocol: session
+                .alpn_protocol()
+                .map(|p| String::from_utf8_lossy(p).into_owned()),
+            peer_certificate: session
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| PeerCertificate::parse(cert.as_ref())),
+        }
+    }
+}
+
+/// Summary of the certificate presented by the broker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    /// Subject distinguished name, e.g. "CN=broker.example.com".
+    /// Empty if the certificate could not be parsed.
+    pub subject: String,
+    /// Issuer distinguished name. Empty if the certificate could not be
+    /// parsed.
+    pub issuer: String,
+    /// End of the validity period. `None` if the certificate could not be
+    /// parsed.
+    pub not_after: Option<SystemTime>,
+    /// The DER-encoded certificate.
+    pub der: Vec<u8>,
+}
+
+impl PeerCertificate {
+    fn parse(der: &[u8]) -> Self {
+        let mut cert = Self {
+            subject: String::new(),
+            issuer: String::new(),
+            not_after: None,
+            der: der.to_vec(),
+        };
+        if let Ok((_, parsed)) = x509_parser::parse_x509_certificate(der) {
+            cert.subject = parsed.subject().to_string();
+            cert.issuer = parsed.issuer().to_string();
+            let secs = parsed.validity().not_after.timestamp();
+            cert.not_after = if secs >= 0 {
+                SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+            } else {
+                SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+            };
+        }
+        cert
+    }
+}
+
+/// Verifier for `danger_accept_invalid_certs()`: trusts any certificate
+/// but still checks handshake signatures so the session itself is sound.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_rejects_invalid_server_name() {
+        assert!(TlsOptions::new().setup("localhost:61614").is_ok());
+        assert!(TlsOptions::new().setup("127.0.0.1:61614").is_ok());
+        let err = TlsOptions::new()
+            .server_name("not a hostname")
+            .setup("localhost:61614")
+            .err()
+            .unwrap();
+        assert!(err.contains("not a hostname"), "{}", err);
+    }
+
+    #[test]
+    fn setup_rejects_pem_without_certificates() {
+        let err = TlsOptions::new()
+            .ca_certificates_pem("not a certificate")
+            .setup("localhost:61614")
+            .err()
+            .unwrap();
+        assert!(err.contains("invalid CA certificate"), "{}", err);
+        let err = TlsOptions::new()
+            .client_certificate_pem("", "")
+            .setup("localhost:61614")
+            .err()
+            .unwrap();
+        assert!(err.contains("invalid client certificate"), "{}", err);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::frame::Frame;
+use std::sync::Arc;
+
+/// Header carrying the W3C trace context (`version-traceid-parentid-flags`).
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Header carrying vendor-specific W3C trace state.
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// Callback that returns the trace context of the caller's current span.
+///
+/// Registered with `ConnectOptions::with_trace_context()`. It is invoked on
+/// the task calling `send_frame()`, so it can read task-local or
+/// span-local state (for example the active OpenTelemetry context).
+pub type TraceContextProvider = Arc<dyn Fn() -> Option<TraceContext> + Send + Sync>;
+
+/// A W3C Trace Context (`traceparent` plus optional `tracestate`).
+///
+/// Outbound, a `TraceContext` is injected as headers on SEND frames when a
+/// provider is registered via `ConnectOptions::with_trace_context()`.
+/// Inbound, `Message::trace_context()` extracts it from a MESSAGE so the
+/// consumer can continue the trace.
+///
+/// The library does not depend on a tracing backend; convert to and from
+/// your tracer's span context using `trace_id()`, `parent_id()`, and
+/// `is_sampled()`.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::TraceContext;
+///
+/// let ctx = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+///     .expect("valid traceparent");
+/// assert_eq!(ctx.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+/// assert_eq!(ctx.parent_id(), "00f067aa0ba902b7");
+/// assert!(ctx.is_sampled());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    traceparent: String,
+    tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` value.
+    ///
+    /// Returns `None` if the value is not a valid W3C traceparent (wrong
+    /// field lengths, non-hex characters, the reserved version `ff`, or an
+    /// all-zero trace or parent id).
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+        if parts.len() < 4 {
+            return None;
+        }
+        let (version, trace_id, parent_id, flags) = (parts[0], parts[1], parts[2], parts[3]);
+
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        if !is_hex(version, 2) || version == "ff" {
+            return None;
+        }
+        // Version 00 has exactly four fields; later versions may append more
+        if version == "00" && parts.len() != 4 {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || trace_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+        if !is_hex(parent_id, 16) || parent_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+        if !is_hex(flags, 2) {
+            return None;
+        }
+
+        Some(Self {
+            traceparent: traceparent.trim().to_string(),
+            tracestate: None,
+        })
+    }
+
+    /// Build a version-00 context from its parts.
+    ///
+    /// `trace_id` must be 32 and `parent_id` 16 lowercase hex characters.
+    /// Returns `None` if they are invalid.
+    pub fn new(trace_id: &str, parent_id: &str, sampled: bool) -> Option<Self> {
+        let flags = if sampled { "01" } else { "00" };
+        Self::parse(&format!("00-{}-{}-{}", trace_id, parent_id, flags))
+    }
+
+    /// Attach a `tracestate` value (builder style).
+    pub fn with_tracestate(mut self, tracestate: impl Into<String>) -> Self {
+        self.tracestate = Some(tracestate.into());
+        self
+    }
+
+    /// Extract the trace context from a frame's `traceparent` and
+    /// `tracestate` headers. Returns `None` if `traceparent` is missing or
+    /// invalid.
+    pub fn from_frame(frame: &Frame) -> Option<Self> {
+        let ctx = Self::parse(frame.get_header(TRACEPARENT_HEADER)?)?;
+        Some(match frame.get_header(TRACESTATE_HEADER) {
+            Some(state) => ctx.with_tracestate(state),
+            None => ctx,
+        })
+    }
+
+    /// The full `traceparent` header value.
+    pub fn traceparent(&self) -> &str {
+        &self.traceparent
+    }
+
+    /// The `tracestate` header value, if any.
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    /// The 32-character hex trace id.
+    pub fn trace_id(&self) -> &str {
+        &self.traceparent[3..35]
+    }
+
+    /// The 16-character hex id of the parent span.
+    pub fn parent_id(&self) -> &str {
+        &self.traceparent[36..52]
+    }
+
+    /// Whether the `sampled` trace flag is set.
+    pub fn is_sampled(&self) -> bool {
+        u8::from_str_radix(&self.traceparent[53..55], 16).is_ok_and(|flags| flags & 0x01 != 0)
+    }
+
+    /// Add this context's headers to `frame`.
+    pub(crate) fn inject(&self, frame: Frame) -> Frame {
+        let frame = frame.header(TRACEPARENT_HEADER, self.traceparent.clone());
+        match &self.tracestate {
+            Some(state) => frame.header(TRACESTATE_HEADER, state.clone()),
+            None => frame,
+        }
+    }
+}
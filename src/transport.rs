@@ -0,0 +1,460 @@
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpSocket, TcpStream, lookup_host};
+
+#[cfg(feature = "tls")]
+use crate::tls::TlsSetup;
+
+/// The byte stream a connection speaks STOMP over: plain TCP, or TCP
+/// wrapped in TLS when `ConnectOptions::tls()` is set.
+pub(crate) enum Transport {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+impl Transport {
+    /// The underlying TCP socket.
+    pub(crate) fn tcp(&self) -> &TcpStream {
+        match self {
+            Transport::Plain(s) => s,
+            #[cfg(feature = "tls")]
+            Transport::Tls(s) => s.get_ref().0,
+        }
+    }
+}
+
+/// Socket settings applied to every TCP connection to the broker, set with
+/// `ConnectOptions::tcp()`.
+///
+/// By default `TCP_NODELAY` is set, so small frames such as ACKs and
+/// heartbeats are sent at once instead of waiting for Nagle's algorithm,
+/// and everything else is left to the operating system.
+///
+/// # Example
+///
+/// ```
+/// use iridium_stomp::{ConnectOptions, TcpOptions};
+/// use std::time::Duration;
+///
+/// let options = ConnectOptions::default().tcp(
+///     TcpOptions::new()
+///         .keepalive(Duration::from_secs(60))
+///         .keepalive_interval(Duration::from_secs(10))
+///         .recv_buffer_size(256 * 1024),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    send_buffer_size: Option<u32>,
+    recv_buffer_size: Option<u32>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            keepalive_interval: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+}
+
+impl TcpOptions {
+    /// `TCP_NODELAY` set, everything else left to the operating system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set or clear `TCP_NODELAY` (default set). Clearing it lets the
+    /// operating system coalesce small writes, trading latency for fewer
+    /// packets.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Enable `SO_KEEPALIVE`, sending the first probe after the connection
+    /// has been idle for `idle`. Detects a dead peer even when heartbeats
+    /// are disabled, at the operating system's pace.
+    pub fn keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Time between keepalive probes once they started. Only used with
+    /// `keepalive()`, and ignored on platforms that cannot set it.
+    pub fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// `SO_SNDBUF` in bytes. The operating system may round or double it.
+    pub fn send_buffer_size(mut self, bytes: u32) -> Self {
+        self.send_buffer_size = Some(bytes);
+        self
+    }
+
+    /// `SO_RCVBUF` in bytes, set before connecting so the TCP window can
+    /// scale to it. The operating system may round or double it.
+    pub fn recv_buffer_size(mut self, bytes: u32) -> Self {
+        self.recv_buffer_size = Some(bytes);
+        self
+    }
+
+    /// A socket for connecting to `addr` with these settings.
+    fn socket(&self, addr: &SocketAddr) -> io::Result<TcpSocket> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.set_nodelay(self.nodelay)?;
+        if let Some(bytes) = self.send_buffer_size {
+            socket.set_send_buffer_size(bytes)?;
+        }
+        if let Some(bytes) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(bytes)?;
+        }
+        if let Some(idle) = self.keepalive {
+            #[allow(unused_mut)]
+            let mut keepalive = socket2::TcpKeepalive::new().with_time(idle);
+            #[cfg(any(
+                target_os = "android",
+                target_os = "freebsd",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+                target_os = "windows",
+            ))]
+            if let Some(interval) = self.keepalive_interval {
+                keepalive = keepalive.with_interval(interval);
+            }
+            socket.set_keepalive(true)?;
+            socket2::SockRef::from(&socket).set_tcp_keepalive(&keepalive)?;
+        }
+        Ok(socket)
+    }
+
+    /// Connect to `addr` with these settings.
+    async fn connect(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        self.socket(&addr)?.connect(addr).await
+    }
+}
+
+/// Opens transports to the broker. Built once per `Connection` and reused
+/// for every reconnect attempt.
+#[derive(Clone)]
+pub(crate) struct Connector {
+    /// How long each resolved address gets to accept the connection.
+    attempt_timeout: Duration,
+    /// Socket settings from `ConnectOptions::tcp()`.
+    pub(crate) tcp: TcpOptions,
+    /// The address that accepted the last connection, tried first next time.
+    last_good: Arc<Mutex<Option<SocketAddr>>>,
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<TlsSetup>,
+}
+
+impl Connector {
+    pub(crate) fn new(attempt_timeout: Duration) -> Self {
+        Self {
+            attempt_timeout,
+            tcp: TcpOptions::default(),
+            last_good: Arc::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Connect to `addr` and, if configured, complete the TLS handshake.
+    ///
+    /// TLS handshake failures (untrusted or mismatched certificates,
+    /// protocol errors) are reported with `io::ErrorKind::InvalidData`.
+    pub(crate) async fn connect(&self, addr: &str) -> io::Result<Transport> {
+        let stream = self.connect_tcp(addr).await?;
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            let stream = tls.handshake(stream).await?;
+            return Ok(Transport::Tls(Box::new(stream)));
+        }
+        Ok(Transport::Plain(stream))
+    }
+
+    /// Resolve `addr` and open a TCP connection to the first of its
+    /// addresses that accepts within the attempt timeout.
+    async fn connect_tcp(&self, addr: &str) -> io::Result<TcpStream> {
+        let resolved = lookup_host(addr).await?.collect();
+        self.connect_any(addr, resolved).await
+    }
+
+    /// Open a TCP connection to the first of `resolved`, the addresses of
+    /// `addr`, that accepts within the attempt timeout.
+    async fn connect_any(&self, addr: &str, resolved: Vec<SocketAddr>) -> io::Result<TcpStream> {
+        let last_good = *self.last_good.lock().unwrap_or_else(|e| e.into_inner());
+        let candidates = order_addresses(resolved, last_good);
+        let mut last_err = None;
+        for candidate in &candidates {
+            match tokio::time::timeout(self.attempt_timeout, self.tcp.connect(*candidate)).await {
+                Ok(Ok(stream)) => {
+                    *self.last_good.lock().unwrap_or_else(|e| e.into_inner()) = Some(*candidate);
+                    return Ok(stream);
+                }
+                Ok(Err(e)) => {
+                    tracing::debug!(addr = %addr, candidate = %candidate, error = %e, "address refused the connection");
+                    last_err = Some(e);
+                }
+                Err(_) => {
+                    tracing::debug!(
+                        addr = %addr,
+                        candidate = %candidate,
+                        timeout_ms = self.attempt_timeout.as_millis() as u64,
+                        "address did not accept the connection in time",
+                    );
+                    last_err = Some(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!(
+                            "connecting to {} timed out after {:?}",
+                            candidate, self.attempt_timeout
+                        ),
+                    ));
+                }
+            }
+        }
+        Err(match last_err {
+            None => io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} did not resolve to any address", addr),
+            ),
+            Some(e) if candidates.len() == 1 => e,
+            Some(e) => io::Error::new(
+                e.kind(),
+                format!(
+                    "none of the {} addresses of {} accepted the connection, last error: {}",
+                    candidates.len(),
+                    addr,
+                    e
+                ),
+            ),
+        })
+    }
+}
+
+/// Order resolved addresses for connection attempts: alternating between
+/// address families, starting with the family of the first one (as in RFC
+/// 8305), with the address that worked last time moved to the front.
+/// Addresses resolved more than once are tried only at their first place.
+fn order_addresses(resolved: Vec<SocketAddr>, last_good: Option<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_v6 = resolved.first().is_some_and(SocketAddr::is_ipv6);
+    let mut seen = HashSet::new();
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) = resolved
+        .into_iter()
+        .filter(|addr| seen.insert(*addr))
+        .partition(|addr| addr.is_ipv6() == first_is_v6);
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    while !first.is_empty() || !second.is_empty() {
+        ordered.extend(first.pop_front());
+        ordered.extend(second.pop_front());
+    }
+    if let Some(pos) = last_good.and_then(|good| ordered.iter().position(|addr| *addr == good)) {
+        let good = ordered.remove(pos);
+        ordered.insert(0, good);
+    }
+    ordered
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Transport::Tls(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Transport::Tls(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Transport::Tls(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Transport::Tls(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn addresses_alternate_families_and_prefer_the_last_good_one() {
+        let resolved = addrs(&[
+            "[::1]:1",
+            "[::2]:1",
+            "10.0.0.1:1",
+            "10.0.0.2:1",
+            "10.0.0.3:1",
+        ]);
+        assert_eq!(
+            order_addresses(resolved.clone(), None),
+            addrs(&[
+                "[::1]:1",
+                "10.0.0.1:1",
+                "[::2]:1",
+                "10.0.0.2:1",
+                "10.0.0.3:1"
+            ])
+        );
+        assert_eq!(
+            order_addresses(resolved, Some("10.0.0.2:1".parse().unwrap())),
+            addrs(&[
+                "10.0.0.2:1",
+                "[::1]:1",
+                "10.0.0.1:1",
+                "[::2]:1",
+                "10.0.0.3:1"
+            ])
+        );
+    }
+
+    #[test]
+    fn repeated_addresses_are_tried_once() {
+        let resolved = addrs(&[
+            "10.0.0.1:1",
+            "[::1]:1",
+            "10.0.0.2:1",
+            "10.0.0.1:1",
+            "[::1]:1",
+        ]);
+        assert_eq!(
+            order_addresses(resolved, None),
+            addrs(&["10.0.0.1:1", "[::1]:1", "10.0.0.2:1"])
+        );
+    }
+
+    #[tokio::test]
+    async fn unreachable_address_is_skipped_after_the_attempt_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+        // TEST-NET-1 (RFC 5737) is never routed: SYNs to it are dropped, or
+        // the attempt fails at once where there is no route at all
+        let black_hole: SocketAddr = "192.0.2.1:61613".parse().unwrap();
+        let connector = Connector::new(Duration::from_secs(1));
+
+        let started = Instant::now();
+        let stream = connector
+            .connect_any("broker", vec![black_hole, good])
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // The address that worked is tried first from now on, well within
+        // the attempt timeout the unreachable one would cost
+        let started = Instant::now();
+        connector
+            .connect_any("broker", vec![black_hole, good])
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn failure_names_every_address_tried() {
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let connector = Connector::new(Duration::from_millis(200));
+        let err = connector
+            .connect_any(
+                "broker",
+                vec![closed, closed, "127.0.0.1:1".parse().unwrap()],
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("none of the 2 addresses of broker"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn socket_options_are_applied_to_the_stream() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut connector = Connector::new(Duration::from_secs(1));
+        let stream = connector.connect_any("broker", vec![addr]).await.unwrap();
+        assert!(stream.nodelay().unwrap());
+
+        connector.tcp = TcpOptions::new()
+            .nodelay(false)
+            .keepalive(Duration::from_secs(30))
+            .keepalive_interval(Duration::from_secs(5))
+            .send_buffer_size(64 * 1024)
+            .recv_buffer_size(64 * 1024);
+        let stream = connector.connect_any("broker", vec![addr]).await.unwrap();
+        assert!(!stream.nodelay().unwrap());
+        let socket = socket2::SockRef::from(&stream);
+        assert!(socket.keepalive().unwrap());
+        // The kernel may double the requested sizes
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(
+                socket.tcp_keepalive_time().unwrap(),
+                Duration::from_secs(30)
+            );
+            assert_eq!(
+                socket.tcp_keepalive_interval().unwrap(),
+                Duration::from_secs(5)
+            );
+        }
+    }
+}
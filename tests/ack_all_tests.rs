@@ -0,0 +1,139 @@
+//! Tests for `Subscription::ack_all()` and `Subscription::nack_all()`.
+//!
+//! A mock broker delivers a handful of messages and records the ACK and
+//! NACK frames it reads, so the tests can check how many frames each ack
+//! mode produces.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{
+    AckMode, ConnError, ConnectOptions, Connection, Frame, NackOptions, Subscription,
+};
+use std::time::Duration;
+
+/// Start a broker negotiating `version` that sends `count` messages `m0`,
+/// `m1`, ... after the SUBSCRIBE.
+async fn start_broker(version: &str, count: usize) -> MockBroker {
+    let connected = Frame::new("CONNECTED")
+        .header("version", version)
+        .header("heart-beat", "0,0");
+    let mut session = Session::new().connected_with(connected);
+    for i in 0..count {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", format!("m{}", i))
+                .set_body(format!("body {}", i)),
+        );
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+/// Connect, subscribe with `ack` and receive all `count` messages.
+async fn receive_all(
+    version: &str,
+    ack: AckMode,
+    count: usize,
+) -> (Connection, Subscription, MockBroker) {
+    let broker = start_broker(version, count).await;
+    let options = ConnectOptions::default().accept_version("1.0,1.1,1.2");
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    let mut sub = conn
+        .subscription("/queue/work")
+        .ack(ack)
+        .start()
+        .await
+        .expect("subscribe failed");
+    for _ in 0..count {
+        sub.recv_timeout(Duration::from_secs(2))
+            .await
+            .expect("message not delivered");
+    }
+    (conn, sub, broker)
+}
+
+/// Close the connection and return the ACKs and NACKs the broker read as
+/// `(command, id, requeue)`.
+async fn finish(conn: Connection, broker: MockBroker) -> Vec<(String, String, Option<String>)> {
+    conn.close_after_flush(Duration::from_secs(2))
+        .await
+        .expect("DISCONNECT not confirmed");
+    broker
+        .received()
+        .into_iter()
+        .filter(|f| f.command == "ACK" || f.command == "NACK")
+        .map(|f| {
+            let id = f.get_header("id").unwrap_or_default().to_string();
+            let requeue = f.get_header("requeue").map(str::to_string);
+            (f.command, id, requeue)
+        })
+        .collect()
+}
+
+fn frame(command: &str, id: &str, requeue: Option<&str>) -> (String, String, Option<String>) {
+    (command.into(), id.into(), requeue.map(str::to_string))
+}
+
+#[tokio::test]
+async fn ack_all_sends_one_cumulative_ack_in_client_mode() {
+    let (conn, sub, broker) = receive_all("1.2", AckMode::Client, 3).await;
+
+    assert_eq!(sub.ack_all().await.unwrap(), 3);
+    assert_eq!(sub.pending_acks().await.count, 0);
+    assert_eq!(sub.ack_all().await.unwrap(), 0);
+
+    assert_eq!(finish(conn, broker).await, [frame("ACK", "m2", None)]);
+}
+
+#[tokio::test]
+async fn ack_all_acks_each_message_in_client_individual_mode() {
+    let (conn, sub, broker) = receive_all("1.2", AckMode::ClientIndividual, 3).await;
+
+    assert_eq!(sub.ack_all().await.unwrap(), 3);
+    assert_eq!(sub.pending_acks().await.count, 0);
+
+    assert_eq!(
+        finish(conn, broker).await,
+        [
+            frame("ACK", "m0", None),
+            frame("ACK", "m1", None),
+            frame("ACK", "m2", None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn nack_all_adds_options_to_every_nack() {
+    let (conn, sub, broker) = receive_all("1.2", AckMode::ClientIndividual, 2).await;
+    sub.ack("m0").await.unwrap();
+
+    let options = NackOptions {
+        requeue: false,
+        reason: None,
+    };
+    assert_eq!(sub.nack_all(options).await.unwrap(), 1);
+    assert_eq!(sub.pending_acks().await.count, 0);
+
+    assert_eq!(
+        finish(conn, broker).await,
+        [frame("ACK", "m0", None), frame("NACK", "m1", Some("false"))]
+    );
+}
+
+#[tokio::test]
+async fn nack_all_is_refused_on_stomp_1_0() {
+    let (conn, sub, broker) = receive_all("1.0", AckMode::Client, 2).await;
+
+    let result = sub.nack_all(NackOptions::default()).await;
+    assert!(
+        matches!(result, Err(ConnError::Protocol(_))),
+        "{:?}",
+        result
+    );
+    assert_eq!(sub.pending_acks().await.count, 2);
+
+    assert!(finish(conn, broker).await.is_empty());
+}
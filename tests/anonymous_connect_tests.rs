@@ -0,0 +1,56 @@
+//! Tests for `ConnectOptions::omit_credentials()`.
+//!
+//! The mock broker records the CONNECT frames it reads and drops the first
+//! session, so the reconnect is checked as well.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, Frame, assert_frame};
+use std::time::Duration;
+
+/// Connect to a mock broker and return the CONNECT frames of `sessions`
+/// sessions; every session but the last is dropped right away.
+async fn connect_frames(options: ConnectOptions, sessions: usize) -> Vec<Frame> {
+    let mut script = Script::new();
+    for _ in 1..sessions {
+        script = script.session(Session::new().connected().close());
+    }
+    let broker = MockBroker::start(script.session(Session::new().connected()))
+        .await
+        .unwrap();
+
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "secret", "0,0", options)
+            .await
+            .expect("connect failed");
+    assert!(
+        broker
+            .wait_for("CONNECT", sessions, Duration::from_secs(5))
+            .await
+    );
+    conn.close().await;
+    broker.received_commands("CONNECT")
+}
+
+#[tokio::test]
+async fn credentials_are_sent_by_default() {
+    let frames = connect_frames(ConnectOptions::default(), 1).await;
+    assert_frame!(frames[0], "CONNECT", "login" => "guest", "passcode" => "secret");
+}
+
+#[tokio::test]
+async fn omitted_credentials_stay_omitted_on_reconnect() {
+    let options = ConnectOptions::default()
+        .client_id("cert-client")
+        .omit_credentials();
+    let frames = connect_frames(options, 2).await;
+    for connect in &frames {
+        assert_frame!(
+            connect,
+            FrameMatcher::command("CONNECT")
+                .lacks_header("login")
+                .lacks_header("passcode")
+                .header("client-id", "cert-client")
+                .has_header("accept-version")
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Tests for `Bridge`.
+//!
+//! One mock broker delivers messages on the source connection and records
+//! the ACK/NACK frames it receives; another records the SEND frames
+//! forwarded to it and, optionally, answers their receipts.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{Bridge, BridgeMetrics, Connection, Frame, NackOptions, assert_frame};
+use std::time::Duration;
+
+/// Start a source broker that delivers `(message-id, type, body)` messages
+/// after the SUBSCRIBE.
+async fn start_source(messages: &[(&str, &str, &str)]) -> MockBroker {
+    let mut session = Session::new().connected();
+    for (message_id, kind, body) in messages {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", *message_id)
+                .header("type", *kind)
+                .set_body(body.to_string()),
+        );
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+/// Start a target broker, answering receipts if `answer_receipts` is set.
+async fn start_target(answer_receipts: bool) -> MockBroker {
+    let session = if answer_receipts {
+        Session::new().connected()
+    } else {
+        Session::new().connected().withhold_receipts()
+    };
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+async fn connect_pair(source: &MockBroker, target: &MockBroker) -> (Connection, Connection) {
+    let source = Connection::connect(&source.address(), "guest", "guest", "0,0")
+        .await
+        .expect("source connect failed");
+    let target = Connection::connect(&target.address(), "guest", "guest", "0,0")
+        .await
+        .expect("target connect failed");
+    // Let the connection tasks finish starting their first sessions
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (source, target)
+}
+
+/// The ACK and NACK frames a source broker received, once there are `n`.
+async fn settled(source: &MockBroker, n: usize) -> Vec<Frame> {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    loop {
+        let settled: Vec<Frame> = source
+            .received()
+            .into_iter()
+            .filter(|f| f.command == "ACK" || f.command == "NACK")
+            .collect();
+        if settled.len() >= n || tokio::time::Instant::now() >= deadline {
+            return settled;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn forwards_mapped_messages_and_acks_them() {
+    let source_broker = start_source(&[
+        ("m1", "order", "first"),
+        ("m2", "noise", "ignored"),
+        ("m3", "order", "third"),
+    ])
+    .await;
+    let target_broker = start_target(true).await;
+    let (source, target) = connect_pair(&source_broker, &target_broker).await;
+
+    let bridge = Bridge::new("/queue/old", "/queue/new")
+        .rename_header("message-id", "x-original-id")
+        .rename_header("type", "kind")
+        .set_header("x-bridged", "true")
+        .filter(|msg| msg.header("type") != Some("noise"))
+        .confirm(Duration::from_secs(2));
+    let running = {
+        let bridge = bridge.clone();
+        let (source, target) = (source.clone(), target.clone());
+        tokio::spawn(async move { bridge.run(&source, &target).await })
+    };
+
+    assert!(
+        target_broker
+            .wait_for("SEND", 2, Duration::from_secs(3))
+            .await
+    );
+    let sends = target_broker.received_commands("SEND");
+    assert_eq!(sends.len(), 2);
+    for (send, (id, body)) in sends.iter().zip([("m1", "first"), ("m3", "third")]) {
+        assert_frame!(
+            send,
+            FrameMatcher::command("SEND")
+                .header("destination", "/queue/new")
+                .header("x-original-id", id)
+                .header("kind", "order")
+                .header("x-bridged", "true")
+                .lacks_header("subscription")
+                .lacks_header("message-id")
+                .body(body)
+        );
+    }
+
+    let settled = settled(&source_broker, 3).await;
+    let acked: Vec<_> = settled
+        .iter()
+        .map(|frame| {
+            assert_eq!(frame.command, "ACK", "unexpected {:?}", frame);
+            frame.get_header("id").unwrap()
+        })
+        .collect();
+    assert_eq!(acked, ["m1", "m2", "m3"]);
+
+    assert_eq!(
+        bridge.metrics(),
+        BridgeMetrics {
+            forwarded: 2,
+            skipped: 1,
+            failed: 0,
+            bytes: ("first".len() + "third".len()) as u64,
+        }
+    );
+
+    source.close().await;
+    tokio::time::timeout(Duration::from_secs(2), running)
+        .await
+        .expect("bridge did not stop when the source closed")
+        .unwrap()
+        .unwrap();
+    target.close().await;
+}
+
+#[tokio::test]
+async fn unconfirmed_forward_is_nacked_on_the_source() {
+    let source_broker = start_source(&[("m1", "order", "lost")]).await;
+    let target_broker = start_target(false).await;
+    let (source, target) = connect_pair(&source_broker, &target_broker).await;
+
+    let bridge = Bridge::new("/queue/old", "/queue/new").confirm(Duration::from_millis(200));
+    let running = {
+        let bridge = bridge.clone();
+        let (source, target) = (source.clone(), target.clone());
+        tokio::spawn(async move { bridge.run(&source, &target).await })
+    };
+
+    let settled = settled(&source_broker, 1).await;
+    assert_frame!(settled[0], "NACK", "id" => "m1", "requeue" => "true");
+    assert_eq!(bridge.metrics().failed, 1);
+    assert_eq!(bridge.metrics().forwarded, 0);
+
+    target.close().await;
+    tokio::time::timeout(Duration::from_secs(2), running)
+        .await
+        .expect("bridge did not stop when the target closed")
+        .unwrap()
+        .unwrap();
+    source.close().await;
+}
+
+#[tokio::test]
+async fn failed_forward_can_be_dead_lettered() {
+    let source_broker = start_source(&[("m1", "order", "poison")]).await;
+    let target_broker = start_target(false).await;
+    let (source, target) = connect_pair(&source_broker, &target_broker).await;
+
+    let bridge = Bridge::new("/queue/old", "/queue/new")
+        .confirm(Duration::from_millis(200))
+        .failure_nack(NackOptions {
+            requeue: false,
+            reason: Some("target rejected".to_string()),
+        });
+    let running = {
+        let (source, target) = (source.clone(), target.clone());
+        tokio::spawn(async move { bridge.run(&source, &target).await })
+    };
+
+    let settled = settled(&source_broker, 1).await;
+    assert_frame!(
+        settled[0],
+        "NACK",
+        "id" => "m1",
+        "requeue" => "false",
+        "reason" => "target rejected"
+    );
+
+    target.close().await;
+    let _ = tokio::time::timeout(Duration::from_secs(2), running).await;
+    source.close().await;
+}
@@ -0,0 +1,104 @@
+//! Tests for `Connection::browse()`.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{BrowseEnd, BrowseOptions, Connection, Frame, assert_frame};
+use std::time::Duration;
+
+/// Start a broker that answers the browse SUBSCRIBE with `messages`,
+/// followed by the end marker if `end_marker` is set.
+async fn start_browse_broker(messages: usize, end_marker: bool) -> MockBroker {
+    let mut session = Session::new().connected();
+    for n in 1..=messages {
+        session = session.deliver_frame(Frame::new("MESSAGE").set_body(format!("order {}", n)));
+    }
+    if end_marker {
+        session = session.deliver_frame(Frame::new("MESSAGE").header("browser", "end"));
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn browse_ends_at_the_broker_marker() {
+    let broker = start_browse_broker(2, true).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let options = BrowseOptions {
+        headers: vec![("selector".to_string(), "priority > 4".to_string())],
+        ..Default::default()
+    };
+    let mut browse = conn.browse("/queue/orders", options).await.unwrap();
+    let mut bodies = Vec::new();
+    while let Some(frame) = tokio::time::timeout(Duration::from_secs(2), browse.recv())
+        .await
+        .expect("browse did not end")
+    {
+        bodies.push(String::from_utf8(frame.body).unwrap());
+    }
+    assert_eq!(bodies, ["order 1", "order 2"]);
+    assert_eq!(browse.end(), Some(BrowseEnd::Exhausted));
+    assert_eq!(browse.received(), 2);
+    assert!(browse.recv().await.is_none());
+
+    let subscribe = &broker.received_commands("SUBSCRIBE")[0];
+    assert_frame!(
+        subscribe,
+        "SUBSCRIBE",
+        "browser" => "true",
+        "ack" => "auto",
+        "selector" => "priority > 4",
+    );
+
+    // The browse subscription is removed once it ends
+    let id = subscribe.get_header("id").unwrap();
+    let unsubscribe = broker
+        .wait_for_match(
+            &FrameMatcher::command("UNSUBSCRIBE"),
+            Duration::from_secs(2),
+        )
+        .await
+        .expect("no UNSUBSCRIBE");
+    assert_frame!(unsubscribe, "UNSUBSCRIBE", "id" => id);
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn browse_stops_at_limit_or_when_idle() {
+    let broker = start_browse_broker(3, false).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let options = BrowseOptions {
+        max_messages: Some(2),
+        ..Default::default()
+    };
+    let mut limited = conn.browse("/queue/orders", options).await.unwrap();
+    assert!(limited.recv().await.is_some());
+    assert!(limited.recv().await.is_some());
+    assert!(limited.recv().await.is_none());
+    assert_eq!(limited.end(), Some(BrowseEnd::Limit));
+    conn.close().await;
+
+    let broker = start_browse_broker(1, false).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let options = BrowseOptions {
+        idle_timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let mut idle = conn.browse("/queue/orders", options).await.unwrap();
+    assert!(idle.recv().await.is_some());
+    assert!(idle.recv().await.is_none());
+    assert_eq!(idle.end(), Some(BrowseEnd::IdleTimeout));
+
+    conn.close().await;
+}
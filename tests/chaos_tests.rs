@@ -0,0 +1,59 @@
+//! Tests for `ConnectOptions::chaos()`.
+//!
+//! The mock broker answers the first SUBSCRIBE with three MESSAGE frames.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{AckMode, Chaos, ConnectOptions, Connection, Frame, RecvTimeoutError};
+use std::time::Duration;
+
+/// Connect with `chaos`, subscribe and return the message ids received.
+async fn received(chaos: Chaos) -> Vec<String> {
+    let mut session = Session::new().connected();
+    for n in 1..=3 {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", format!("m{}", n))
+                .header("ack", format!("a{}", n))
+                .set_body("body"),
+        );
+    }
+    let broker = MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap();
+    let options = ConnectOptions::default().chaos(chaos);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    let mut sub = conn
+        .subscribe("/queue/orders", AckMode::ClientIndividual)
+        .await
+        .expect("subscribe failed");
+
+    let mut ids = Vec::new();
+    loop {
+        match sub.recv_timeout(Duration::from_millis(300)).await {
+            Ok(frame) => ids.push(frame.get_header("message-id").unwrap().to_string()),
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(e) => panic!("{}", e),
+        }
+    }
+    conn.close().await;
+    ids
+}
+
+#[tokio::test]
+async fn without_chaos_every_message_arrives_once() {
+    assert_eq!(received(Chaos::new()).await, ["m1", "m2", "m3"]);
+}
+
+#[tokio::test]
+async fn duplicated_messages_arrive_twice() {
+    let ids = received(Chaos::new().duplicate_rate(1.0)).await;
+    assert_eq!(ids, ["m1", "m1", "m2", "m2", "m3", "m3"]);
+}
+
+#[tokio::test]
+async fn dropped_messages_never_arrive() {
+    assert!(received(Chaos::new().drop_rate(1.0)).await.is_empty());
+}
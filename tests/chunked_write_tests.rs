@@ -0,0 +1,180 @@
+//! Tests for `ConnectOptions::write_chunk_size()`.
+//!
+//! The mock broker stops reading while a frame far larger than the socket
+//! buffers is being written, so the client's write stalls part-way, and
+//! checks that inbound frames are still delivered meanwhile.
+//!
+//! The broker stays hand-rolled rather than using `testing::MockBroker`,
+//! which always reads, so it can stall the socket on purpose.
+
+use futures::StreamExt;
+use iridium_stomp::{AckMode, ConfigError, ConnectOptions, Connection, Frame};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Helper to find an available port
+fn get_available_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Read one NUL-terminated frame, skipping heartbeat newlines.
+fn read_frame(stream: &mut TcpStream) -> String {
+    let mut frame = Vec::new();
+    let mut byte = [0u8; 1];
+    while let Ok(1) = stream.read(&mut byte) {
+        if byte[0] == 0 {
+            break;
+        }
+        frame.push(byte[0]);
+    }
+    String::from_utf8_lossy(&frame)
+        .trim_start_matches('\n')
+        .to_string()
+}
+
+fn header<'a>(frame: &'a str, name: &str) -> Option<&'a str> {
+    frame
+        .lines()
+        .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))
+}
+
+/// Bind a listener whose connections have a small receive buffer, which
+/// also turns off receive buffer autotuning. Must run inside the runtime.
+fn small_buffer_listener(addr: &str) -> TcpListener {
+    let socket = tokio::net::TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(64 * 1024).unwrap();
+    socket.bind(addr.parse().unwrap()).unwrap();
+    let listener = socket.listen(1).unwrap().into_std().unwrap();
+    listener.set_nonblocking(false).unwrap();
+    listener
+}
+
+/// Start a broker that, after the SUBSCRIBE, leaves the socket unread,
+/// delivers one MESSAGE, waits for `go`, and then reports the bodies of
+/// the first `expected` SEND frames.
+fn spawn_stalling_broker(
+    listener: TcpListener,
+    expected: usize,
+    go: std_mpsc::Receiver<()>,
+    bodies_tx: std_mpsc::Sender<Vec<Vec<u8>>>,
+) {
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(false).unwrap();
+        read_frame(&mut stream);
+        stream
+            .write_all(b"CONNECTED\nversion:1.2\nheart-beat:0,0\n\n\0")
+            .unwrap();
+        let subscribe = read_frame(&mut stream);
+        let id = header(&subscribe, "id").unwrap_or("").to_string();
+
+        // Let the client fill the socket buffers before delivering
+        thread::sleep(Duration::from_millis(300));
+        let message = format!(
+            "MESSAGE\ndestination:/queue/status\nmessage-id:m1\nsubscription:{}\n\nstill here\0",
+            id
+        );
+        stream.write_all(message.as_bytes()).unwrap();
+        let _ = go.recv_timeout(Duration::from_secs(5));
+
+        let mut wire = Vec::new();
+        let mut bodies = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        while bodies.len() < expected {
+            let n = match stream.read(&mut buf) {
+                Ok(n) if n > 0 => n,
+                _ => break,
+            };
+            // Only the new bytes can hold a terminator
+            let scanned = wire.len();
+            wire.extend_from_slice(&buf[..n]);
+            let mut from = scanned;
+            while let Some(end) = wire[from..].iter().position(|&b| b == 0) {
+                let frame: Vec<u8> = wire.drain(..=from + end).collect();
+                from = 0;
+                let frame = &frame[..frame.len() - 1];
+                let start = frame.iter().position(|&b| b != b'\n').unwrap_or(0);
+                let frame = &frame[start..];
+                if frame.starts_with(b"SEND\n")
+                    && let Some(split) = frame.windows(2).position(|w| w == b"\n\n")
+                {
+                    bodies.push(frame[split + 2..].to_vec());
+                }
+            }
+        }
+        let _ = bodies_tx.send(bodies);
+        while matches!(stream.read(&mut buf), Ok(n) if n > 0) {}
+    });
+}
+
+// The broker's report is awaited with a blocking receive
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn inbound_frames_are_delivered_during_a_large_write() {
+    // Far larger than the socket buffers, so the write stalls
+    const LARGE: usize = 16 * 1024 * 1024;
+
+    let addr = format!("127.0.0.1:{}", get_available_port());
+    let (go_tx, go_rx) = std_mpsc::channel();
+    let (bodies_tx, bodies_rx) = std_mpsc::channel();
+    spawn_stalling_broker(small_buffer_listener(&addr), 2, go_rx, bodies_tx);
+
+    let options = ConnectOptions::new()
+        .write_chunk_size(64 * 1024)
+        .max_inflight_sends(4);
+    let conn = Connection::connect_with_options(&addr, "guest", "guest", "0,0", options)
+        .await
+        .expect("connect failed");
+    let mut sub = conn
+        .subscribe("/queue/status", AckMode::Auto)
+        .await
+        .unwrap();
+
+    let large: Vec<u8> = (0..LARGE).map(|i| b'a' + (i % 26) as u8).collect();
+    conn.send_frame(
+        Frame::new("SEND")
+            .header("destination", "/queue/upload")
+            .set_body(large.clone()),
+    )
+    .await
+    .unwrap();
+    conn.send_frame(
+        Frame::new("SEND")
+            .header("destination", "/queue/upload")
+            .set_body("after"),
+    )
+    .await
+    .unwrap();
+
+    let message = tokio::time::timeout(Duration::from_secs(2), sub.next())
+        .await
+        .expect("MESSAGE not delivered while the large frame was being written")
+        .unwrap();
+    assert_eq!(message.body, b"still here");
+    go_tx.send(()).unwrap();
+
+    let bodies = bodies_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(bodies.len(), 2);
+    assert!(bodies[0] == large, "large body corrupted");
+    assert_eq!(bodies[1], b"after");
+
+    tokio::time::timeout(Duration::from_secs(2), conn.drain_sends())
+        .await
+        .expect("permits of written frames were not released")
+        .unwrap();
+    conn.close().await;
+}
+
+#[test]
+fn zero_write_chunk_size_is_rejected() {
+    assert_eq!(
+        ConnectOptions::new().write_chunk_size(0).validate(),
+        Err(ConfigError::ZeroWriteChunkSize)
+    );
+}
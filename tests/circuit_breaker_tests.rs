@@ -0,0 +1,68 @@
+//! Tests for `ConnectOptions::circuit_breaker()`.
+//!
+//! The mock broker drops the first session at once and rejects the next
+//! CONNECT with an ERROR frame, which opens a breaker set to two failures.
+//! `retry_now()` then ends the cool-down and the third session succeeds.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{CircuitBreaker, ConnectOptions, Connection, ConnectionEvent};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+async fn next_event(rx: &mut mpsc::Receiver<ConnectionEvent>) -> ConnectionEvent {
+    tokio::time::timeout(Duration::from_secs(10), rx.recv())
+        .await
+        .expect("no event")
+        .expect("event channel closed")
+}
+
+#[tokio::test]
+async fn circuit_opens_after_failed_sessions_and_retry_now_closes_it() {
+    let broker = MockBroker::start(
+        Script::new()
+            .session(Session::new().connected().close())
+            .session(Session::new().reject("access denied"))
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
+    let (tx, mut rx) = mpsc::channel(16);
+    let cool_down = Duration::from_secs(3600);
+    let options = ConnectOptions::default()
+        .with_event_notify(tx)
+        .circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60), cool_down));
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    assert!(!conn.retry_now());
+
+    assert_eq!(
+        next_event(&mut rx).await,
+        ConnectionEvent::Reconnecting { attempt: 1 }
+    );
+    assert_eq!(
+        next_event(&mut rx).await,
+        ConnectionEvent::CircuitOpened {
+            failures: 2,
+            cool_down
+        }
+    );
+    assert!(conn.circuit_open());
+
+    assert!(conn.retry_now());
+    assert_eq!(
+        next_event(&mut rx).await,
+        ConnectionEvent::CircuitClosed { manual: true }
+    );
+    assert_eq!(
+        next_event(&mut rx).await,
+        ConnectionEvent::Reconnecting { attempt: 2 }
+    );
+    assert!(matches!(
+        next_event(&mut rx).await,
+        ConnectionEvent::Reconnected { attempts: 2, .. }
+    ));
+    assert!(!conn.circuit_open());
+    conn.close().await;
+}
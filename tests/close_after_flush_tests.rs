@@ -0,0 +1,135 @@
+//! Tests for `Connection::close_after_flush()` and for `close()` stopping
+//! the background task.
+//!
+//! The mock broker delivers one message and records every frame it reads;
+//! the tests also check that the client did not connect again after
+//! closing.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{AckMode, ConnError, Connection, Frame};
+use std::time::{Duration, Instant};
+
+/// Start a broker that delivers message `m1` to the first SUBSCRIBE,
+/// answering receipts only if `confirm_disconnect` is set.
+async fn start_broker(confirm_disconnect: bool) -> MockBroker {
+    let mut session = Session::new().connected();
+    if !confirm_disconnect {
+        session = session.withhold_receipts();
+    }
+    let session = session.deliver_frame(
+        Frame::new("MESSAGE")
+            .header("message-id", "m1")
+            .set_body("job"),
+    );
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+/// The commands the broker read, once the client has had `watch` to
+/// connect again; a second connection shows up as `<reconnected>`.
+async fn commands(broker: &MockBroker, watch: Duration) -> Vec<String> {
+    tokio::time::sleep(watch).await;
+    let mut commands: Vec<String> = broker.received().into_iter().map(|f| f.command).collect();
+    if broker.connections() > 1 {
+        commands.push("<reconnected>".to_string());
+    }
+    commands
+}
+
+#[tokio::test]
+async fn queued_ack_is_written_before_the_disconnect() {
+    let broker = start_broker(true).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut sub = conn
+        .subscribe("/queue/jobs", AckMode::ClientIndividual)
+        .await
+        .unwrap();
+    let job = tokio::time::timeout(Duration::from_secs(2), sub.recv())
+        .await
+        .expect("message not delivered")
+        .unwrap();
+
+    sub.ack(job.get_header("message-id").unwrap())
+        .await
+        .unwrap();
+    let other = conn.clone();
+    conn.close_after_flush(Duration::from_secs(2))
+        .await
+        .expect("DISCONNECT not confirmed");
+
+    // New frames are refused once the connection is closing
+    let refused = other
+        .send_frame(Frame::new("SEND").header("destination", "/queue/jobs"))
+        .await;
+    assert!(
+        matches!(refused, Err(ConnError::ChannelClosed)),
+        "{:?}",
+        refused
+    );
+
+    let commands = commands(&broker, Duration::from_millis(1500)).await;
+    let ack = commands.iter().position(|c| *c == "ACK");
+    let disconnect = commands.iter().position(|c| *c == "DISCONNECT");
+    assert!(
+        matches!((ack, disconnect), (Some(a), Some(d)) if a < d),
+        "ACK not written before DISCONNECT: {:?}",
+        commands
+    );
+    assert!(
+        !commands.iter().any(|c| c == "<reconnected>"),
+        "{:?}",
+        commands
+    );
+}
+
+#[tokio::test]
+async fn unconfirmed_disconnect_times_out() {
+    let broker = start_broker(false).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let started = Instant::now();
+    let result = conn.close_after_flush(Duration::from_millis(300)).await;
+    assert!(
+        matches!(result, Err(ConnError::CloseTimeout(t)) if t == Duration::from_millis(300)),
+        "{:?}",
+        result
+    );
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    let commands = commands(&broker, Duration::from_millis(1500)).await;
+    assert!(commands.iter().any(|c| c == "DISCONNECT"), "{:?}", commands);
+    assert!(
+        !commands.iter().any(|c| c == "<reconnected>"),
+        "{:?}",
+        commands
+    );
+}
+
+#[tokio::test]
+async fn close_does_not_reconnect() {
+    let broker = start_broker(true).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    conn.close().await;
+
+    // A session that ends this quickly is retried after two seconds
+    let commands = commands(&broker, Duration::from_millis(2500)).await;
+    assert!(
+        !commands.iter().any(|c| c == "<reconnected>"),
+        "client reconnected after close(): {:?}",
+        commands
+    );
+}
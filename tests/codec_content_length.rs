@@ -1,5 +1,5 @@
 use bytes::BytesMut;
-use iridium_stomp::StompCodec;
+use iridium_stomp::{ContentLengthPolicy, StompCodec};
 use tokio_util::codec::Decoder;
 
 #[test]
@@ -46,3 +46,74 @@ fn invalid_content_length_is_error() {
     let res = codec.decode(&mut buf);
     assert!(res.is_err(), "invalid content-length should produce error");
 }
+
+fn encode_with(codec: &mut StompCodec, frame: iridium_stomp::Frame) -> Vec<u8> {
+    use tokio_util::codec::Encoder;
+
+    let mut buf = BytesMut::new();
+    codec
+        .encode(iridium_stomp::StompItem::Frame(frame), &mut buf)
+        .expect("encode error");
+    buf.to_vec()
+}
+
+fn has_content_length(wire: &[u8]) -> bool {
+    wire.windows(b"content-length:".len())
+        .any(|w| w == b"content-length:")
+}
+
+#[test]
+fn policy_auto_emits_only_for_binary_bodies() {
+    let mut codec = StompCodec::new();
+    let text = iridium_stomp::Frame::new("SEND").set_body(b"hello".to_vec());
+    let binary = iridium_stomp::Frame::new("SEND").set_body(b"a\0b".to_vec());
+    assert!(!has_content_length(&encode_with(&mut codec, text)));
+    assert!(has_content_length(&encode_with(&mut codec, binary)));
+}
+
+#[test]
+fn policy_always_emit_adds_header_to_text_frames() {
+    let mut codec = StompCodec::new().with_content_length_policy(ContentLengthPolicy::AlwaysEmit);
+    let wire = encode_with(
+        &mut codec,
+        iridium_stomp::Frame::new("SEND").set_body(b"hello".to_vec()),
+    );
+    assert_eq!(wire, b"SEND\ncontent-length:5\n\nhello\0");
+}
+
+#[test]
+fn policy_never_omits_header_but_keeps_explicit_one() {
+    let mut codec = StompCodec::new().with_content_length_policy(ContentLengthPolicy::Never);
+    let binary = iridium_stomp::Frame::new("SEND").set_body(vec![0xffu8, 0xfe]);
+    assert!(!has_content_length(&encode_with(&mut codec, binary)));
+
+    let explicit = iridium_stomp::Frame::new("SEND")
+        .header("content-length", "2")
+        .set_body(vec![0xffu8, 0xfe]);
+    let wire = encode_with(&mut codec, explicit);
+    assert_eq!(
+        wire.windows(b"content-length:".len())
+            .filter(|w| *w == b"content-length:")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn item_policy_overrides_codec_policy() {
+    use tokio_util::codec::Encoder;
+
+    let mut codec = StompCodec::new().with_content_length_policy(ContentLengthPolicy::Never);
+    let frame = iridium_stomp::Frame::new("SEND").set_body(b"hi".to_vec());
+    let mut buf = BytesMut::new();
+    codec
+        .encode(
+            iridium_stomp::StompItem::FrameWithContentLength(
+                frame,
+                ContentLengthPolicy::AlwaysEmit,
+            ),
+            &mut buf,
+        )
+        .expect("encode error");
+    assert!(has_content_length(&buf));
+}
@@ -1,5 +1,5 @@
 use bytes::BytesMut;
-use iridium_stomp::codec::{StompCodec, StompItem};
+use iridium_stomp::codec::{DecodeErrorStats, DecodeStats, StompCodec, StompItem, TerminatorStats};
 use iridium_stomp::frame::Frame;
 use tokio_util::codec::{Decoder, Encoder};
 
@@ -71,3 +71,102 @@ fn encode_includes_content_length_for_binary_body() {
         s
     );
 }
+
+#[test]
+fn decode_counts_terminator_styles() {
+    let mut codec = StompCodec::new();
+    let raw = b"MESSAGE\n\na\0MESSAGE\n\nb\0\nMESSAGE\n\nc\0\r\nMESSAGE\n\nd\0\r\n";
+    let mut buf = BytesMut::from(&raw[..]);
+    let mut bodies = Vec::new();
+    while let Some(item) = codec.decode(&mut buf).expect("decode failed") {
+        match item {
+            StompItem::Frame(f) => bodies.push(f.body),
+            _ => panic!("CRLF after a frame is not a heartbeat"),
+        }
+    }
+    assert_eq!(
+        bodies,
+        vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+    );
+    assert!(buf.is_empty());
+    assert_eq!(
+        codec.terminator_stats(),
+        TerminatorStats {
+            nul: 1,
+            nul_lf: 1,
+            nul_crlf: 2,
+        }
+    );
+}
+
+#[test]
+fn decode_crlf_terminator_split_across_reads() {
+    let mut codec = StompCodec::new();
+    let mut buf = BytesMut::from(&b"MESSAGE\n\nhi\0\r"[..]);
+    let item = codec.decode(&mut buf).expect("decode failed");
+    assert!(matches!(item, Some(StompItem::Frame(_))));
+    assert!(buf.is_empty());
+
+    // The LF that completes the terminator must not be read as a heartbeat
+    buf.extend_from_slice(b"\n");
+    assert!(codec.decode(&mut buf).expect("decode failed").is_none());
+    assert!(buf.is_empty());
+
+    // A later LF is a real heartbeat
+    buf.extend_from_slice(b"\n");
+    let item = codec.decode(&mut buf).expect("decode failed");
+    assert!(matches!(item, Some(StompItem::Heartbeat)));
+    assert_eq!(codec.terminator_stats().nul_crlf, 1);
+}
+
+#[test]
+fn decode_stats_count_traffic_and_lenient_frames() {
+    let mut codec = StompCodec::new();
+    let input = b"\nMESSAGE\ndestination:/q\n\nhi\0\nMESSAGE\nContent-Length: 2\n\nab\0bare\0";
+    let mut buf = BytesMut::from(&input[..]);
+    let mut frames = 0;
+    while let Some(item) = codec.decode(&mut buf).expect("decode failed") {
+        if let StompItem::Frame(_) = item {
+            frames += 1;
+        }
+    }
+    assert_eq!(frames, 3);
+    assert_eq!(
+        codec.decode_stats(),
+        DecodeStats {
+            frames: 3,
+            heartbeats: 1,
+            bytes: input.len() as u64,
+            // the mixed-case, padded content-length and the command-less body
+            permissive: 2,
+            errors: DecodeErrorStats::default(),
+        }
+    );
+}
+
+#[test]
+fn decode_stats_count_errors_by_kind() {
+    let cases: [&[u8]; 4] = [
+        b"MESSAGE\nbad:\xff\n\n\0",
+        b"MESSAGE\nbad:\\x\n\n\0",
+        b"MESSAGE\nno-colon\n\n\0",
+        b"MESSAGE\ncontent-length:1\n\nab\0",
+    ];
+    let mut codec = StompCodec::new();
+    for case in cases {
+        assert!(codec.decode(&mut BytesMut::from(case)).is_err());
+    }
+    let stats = codec.decode_stats();
+    assert_eq!(
+        stats.errors,
+        DecodeErrorStats {
+            invalid_utf8: 1,
+            invalid_escape: 1,
+            malformed_header: 1,
+            content_length: 1,
+            interleaved_heartbeat: 0,
+        }
+    );
+    assert_eq!(stats.errors.total(), 4);
+    assert_eq!(stats.frames, 0);
+}
@@ -54,7 +54,7 @@ fn randomized_splits_multiple_frames() {
                     assert!(s == b"alpha" || s == b"omega" || s == [0u8, 1, 2, 3, 4].as_slice());
                     decoded_count += 1;
                 }
-                Ok(Some(StompItem::Heartbeat)) => { /* ignore */ }
+                Ok(Some(_)) => { /* ignore */ }
                 Ok(None) => break,
                 Err(e) => panic!("decoder error: {}", e),
             }
@@ -98,7 +98,7 @@ fn streaming_many_small_frames() {
         loop {
             match dec.decode(&mut feed) {
                 Ok(Some(StompItem::Frame(_f))) => decoded += 1,
-                Ok(Some(StompItem::Heartbeat)) => {}
+                Ok(Some(_)) => {}
                 Ok(None) => break,
                 Err(e) => panic!("decoder error: {}", e),
             }
@@ -265,3 +265,77 @@ fn encode_frame_then_heartbeat() {
     assert_eq!(dst[len - 2], 0x00); // NUL terminator
     assert_eq!(dst[len - 1], 0x0A); // Heartbeat LF
 }
+
+#[test]
+fn decode_crlf_as_one_heartbeat() {
+    let mut codec = StompCodec::new();
+    let mut buf = BytesMut::from(&b"\r\n\r\n"[..]);
+    for remaining in [2, 0] {
+        let item = codec
+            .decode(&mut buf)
+            .expect("decode failed")
+            .expect("no item");
+        assert_eq!(item, StompItem::Heartbeat);
+        assert_eq!(buf.len(), remaining);
+    }
+    assert_eq!(codec.decode_stats().heartbeats, 2);
+    assert_eq!(codec.decode_stats().bytes, 4);
+}
+
+#[test]
+fn decode_crlf_heartbeat_split_across_reads() {
+    let mut codec = StompCodec::new();
+    let mut buf = BytesMut::from(&b"\r"[..]);
+    assert_eq!(codec.decode(&mut buf).expect("decode failed"), None);
+    assert_eq!(&buf[..], b"\r", "the CR must wait for its LF");
+
+    buf.extend_from_slice(b"\nSEND\ndestination:/queue/a\n\nhi\0");
+    let item = codec
+        .decode(&mut buf)
+        .expect("decode failed")
+        .expect("no item");
+    assert_eq!(item, StompItem::Heartbeat);
+    match codec.decode(&mut buf).expect("decode failed") {
+        Some(StompItem::Frame(f)) => assert_eq!(f.body, b"hi"),
+        other => panic!("expected frame, got {:?}", other),
+    }
+}
+
+#[test]
+fn mixed_eol_heartbeats_and_frames() {
+    let mut codec = StompCodec::new();
+    // CRLF heartbeat, frame ending NUL CRLF, LF heartbeat, CRLF heartbeat,
+    // frame ending NUL LF, CRLF heartbeat
+    let data = b"\r\nMESSAGE\nmessage-id:1\n\none\0\r\n\n\r\nMESSAGE\nmessage-id:2\n\ntwo\0\n\r\n";
+    let mut buf = BytesMut::from(&data[..]);
+
+    let mut items = Vec::new();
+    while let Some(item) = codec.decode(&mut buf).expect("decode failed") {
+        items.push(match item {
+            StompItem::Heartbeat => "HB".to_string(),
+            StompItem::Frame(f) => String::from_utf8(f.body).unwrap(),
+            other => panic!("unexpected item: {:?}", other),
+        });
+    }
+    assert_eq!(items, ["HB", "one", "HB", "HB", "two", "HB"]);
+    assert!(buf.is_empty());
+    assert_eq!(codec.decode_stats().errors.total(), 0);
+}
+
+#[test]
+fn encode_crlf_heartbeat() {
+    let mut codec = StompCodec::new().with_crlf_heartbeats(true);
+    let mut dst = BytesMut::new();
+    codec
+        .encode(StompItem::Heartbeat, &mut dst)
+        .expect("encode failed");
+    assert_eq!(&dst[..], b"\r\n");
+
+    // And back
+    let decoded = codec
+        .decode(&mut dst)
+        .expect("decode failed")
+        .expect("no item");
+    assert_eq!(decoded, StompItem::Heartbeat);
+    assert!(dst.is_empty());
+}
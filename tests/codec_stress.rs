@@ -75,7 +75,7 @@ async fn codec_concurrent_stress() {
             loop {
                 match dec.decode(&mut buf) {
                     Ok(Some(StompItem::Frame(_))) => decoded += 1,
-                    Ok(Some(StompItem::Heartbeat)) => {}
+                    Ok(Some(_)) => {}
                     Ok(None) => break,
                     Err(e) => {
                         eprintln!("decoder error: {}", e);
@@ -107,7 +107,7 @@ async fn codec_concurrent_stress() {
         loop {
             match dec.decode(&mut buf) {
                 Ok(Some(StompItem::Frame(_))) => decoded += 1,
-                Ok(Some(StompItem::Heartbeat)) => {}
+                Ok(Some(_)) => {}
                 Ok(None) => break,
                 Err(e) => {
                     eprintln!("decoder error during drain: {}", e);
@@ -0,0 +1,71 @@
+//! Runs the `testvectors` module against the crate's own codec, and checks
+//! that the frames the codec encodes decode back to the same vectors.
+
+use iridium_stomp::testvectors::{self, Expected, ExpectedItem, Source};
+use iridium_stomp::{Frame, StompCodec, StompItem};
+use tokio_util::bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn codec_passes_every_vector() {
+    let failures: Vec<String> = testvectors::all()
+        .iter()
+        .filter_map(|vector| vector.check_decoder(StompCodec::new).err())
+        .map(|e| e.to_string())
+        .collect();
+    assert!(failures.is_empty(), "{:#?}", failures);
+}
+
+#[test]
+fn every_source_is_covered() {
+    for source in [
+        Source::Spec,
+        Source::RabbitMq,
+        Source::ActiveMq,
+        Source::Artemis,
+    ] {
+        assert!(
+            testvectors::from_source(source).count() >= 3,
+            "{:?}",
+            source
+        );
+    }
+}
+
+/// Frames the encoder writes decode back to what the vector expects.
+#[test]
+fn encoded_frames_round_trip() {
+    for vector in testvectors::all() {
+        let Expected::Items(items) = vector.expected else {
+            continue;
+        };
+        for item in items {
+            let ExpectedItem::Frame(expected) = item else {
+                continue;
+            };
+            let mut frame = Frame::new(expected.command).set_body(expected.body.to_vec());
+            for (name, value) in expected.headers {
+                frame = frame.header(*name, *value);
+            }
+            let mut buf = BytesMut::new();
+            let mut codec = StompCodec::new();
+            codec
+                .encode(StompItem::Frame(frame), &mut buf)
+                .expect("encode failed");
+            let decoded = codec.decode(&mut buf).unwrap();
+            let Some(StompItem::Frame(decoded)) = decoded else {
+                panic!("{}: no frame decoded", vector.name);
+            };
+            assert_eq!(decoded.command, expected.command, "{}", vector.name);
+            assert_eq!(decoded.body, expected.body, "{}", vector.name);
+            for (name, value) in expected.headers {
+                assert!(
+                    decoded.headers.iter().any(|(k, v)| k == name && v == value),
+                    "{}: header {} lost",
+                    vector.name,
+                    name
+                );
+            }
+        }
+    }
+}
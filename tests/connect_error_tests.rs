@@ -3,54 +3,32 @@
 //! These tests verify that ERROR frames and connection failures during
 //! the STOMP handshake are properly reported to the caller.
 
-use iridium_stomp::Connection;
 use iridium_stomp::connection::ConnError;
-use std::io::{Read, Write};
-use std::net::TcpListener;
-use std::thread;
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, Frame, ReceivedFrame};
 use std::time::Duration;
 
-/// Helper to find an available port
-fn get_available_port() -> u16 {
-    TcpListener::bind("127.0.0.1:0")
+/// Start a broker that plays `session` on every connection.
+async fn start_broker(session: Session) -> MockBroker {
+    MockBroker::start(Script::new().session(session))
+        .await
         .unwrap()
-        .local_addr()
-        .unwrap()
-        .port()
 }
 
 /// Test that server sending ERROR frame during CONNECT returns ServerRejected
 #[tokio::test]
 async fn connect_error_frame_returns_server_rejected() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-
-    // Spawn a mock server that sends an ERROR frame
-    let server_addr = addr.clone();
-    let server = thread::spawn(move || {
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        listener.set_nonblocking(false).unwrap();
-
-        if let Ok((mut stream, _)) = listener.accept() {
-            // Read the CONNECT frame (we don't need to parse it fully)
-            let mut buf = [0u8; 1024];
-            let _ = stream.read(&mut buf);
-
-            // Send ERROR frame
-            let error_frame = "ERROR\nmessage:Authentication failed\n\nInvalid credentials\0";
-            stream.write_all(error_frame.as_bytes()).unwrap();
-            stream.flush().unwrap();
-
-            // Keep connection open briefly so client can read
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
-
-    // Give server time to start
-    thread::sleep(Duration::from_millis(50));
+    let broker = start_broker(
+        Session::new().expect("CONNECT").send(
+            Frame::new("ERROR")
+                .header("message", "Authentication failed")
+                .set_body("Invalid credentials"),
+        ),
+    )
+    .await;
 
     // Attempt connection
-    let result = Connection::connect(&addr, "user", "wrongpass", "0,0").await;
+    let result = Connection::connect(&broker.address(), "user", "wrongpass", "0,0").await;
 
     // Should get ServerRejected error
     match result {
@@ -61,45 +39,19 @@ async fn connect_error_frame_returns_server_rejected() {
         Err(other) => panic!("Expected ServerRejected, got: {:?}", other),
         Ok(_) => panic!("Expected error, got successful connection"),
     }
-
-    server.join().unwrap();
 }
 
 /// Test that server closing connection before CONNECTED causes a retry
 /// (not an immediate failure). Protocol errors during handshake are transient.
 #[tokio::test]
 async fn connect_closed_before_connected_retries() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-
-    // Spawn a mock server that closes immediately after receiving CONNECT
-    let server_addr = addr.clone();
-    let server = thread::spawn(move || {
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        listener.set_nonblocking(true).unwrap();
-        let deadline = std::time::Instant::now() + Duration::from_secs(2);
-        while std::time::Instant::now() < deadline {
-            match listener.accept() {
-                Ok((mut stream, _)) => {
-                    let mut buf = [0u8; 1024];
-                    let _ = stream.read(&mut buf);
-                    drop(stream);
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    thread::sleep(Duration::from_millis(50));
-                }
-                Err(_) => break,
-            }
-        }
-    });
-
-    // Give server time to start
-    thread::sleep(Duration::from_millis(50));
+    // Every connection is closed right after its CONNECT
+    let broker = start_broker(Session::new().expect("CONNECT").close()).await;
 
     // Should keep retrying, not return an error
     let result = tokio::time::timeout(
         Duration::from_millis(500),
-        Connection::connect(&addr, "user", "pass", "0,0"),
+        Connection::connect(&broker.address(), "user", "pass", "0,0"),
     )
     .await;
 
@@ -107,8 +59,29 @@ async fn connect_closed_before_connected_retries() {
         result.is_err(),
         "Expected connect to keep retrying when server closes during handshake"
     );
+    assert!(broker.connections() >= 1);
+}
 
-    server.join().unwrap();
+/// Test that a broker which never answers CONNECT is given up on after
+/// `handshake_timeout` and tried again
+#[tokio::test]
+async fn connect_unanswered_times_out_and_retries() {
+    let broker = start_broker(Session::new()).await;
+
+    let options = ConnectOptions::default().handshake_timeout(Duration::from_millis(100));
+    // One timed-out attempt, one second of backoff, then a second attempt
+    let result = tokio::time::timeout(
+        Duration::from_millis(1600),
+        Connection::connect_with_options(&broker.address(), "user", "pass", "0,0", options),
+    )
+    .await;
+
+    assert!(result.is_err(), "Expected connect to keep retrying");
+    assert!(
+        broker.connections() >= 2,
+        "Expected a second attempt, got {} connection(s)",
+        broker.connections()
+    );
 }
 
 /// Test that connection refused retries (does not fail immediately).
@@ -118,8 +91,8 @@ async fn connect_closed_before_connected_retries() {
 /// then cancel the attempt.
 #[tokio::test]
 async fn connect_refused_retries_instead_of_failing() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
+    // The broker stops listening when dropped, leaving a free port
+    let addr = start_broker(Session::new()).await.address();
 
     // No server listening — connect should retry, not return an error.
     let result = tokio::time::timeout(
@@ -134,3 +107,182 @@ async fn connect_refused_retries_instead_of_failing() {
         "Expected connect to keep retrying, but it returned"
     );
 }
+
+/// Test that a CONNECTED frame with a version the client did not offer fails
+/// immediately with VersionMismatch instead of retrying.
+#[tokio::test]
+async fn connect_version_mismatch_fails_immediately() {
+    // A STOMP 1.0 server omits the version header entirely
+    let broker = start_broker(
+        Session::new().connected_with(Frame::new("CONNECTED").header("heart-beat", "0,0")),
+    )
+    .await;
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(2),
+        Connection::connect(&broker.address(), "user", "pass", "0,0"),
+    )
+    .await
+    .expect("connect should fail fast, not retry");
+
+    match result {
+        Err(ConnError::VersionMismatch { accepted, server }) => {
+            assert_eq!(accepted, "1.2");
+            assert_eq!(server, "1.0");
+        }
+        Err(other) => panic!("Expected VersionMismatch, got: {:?}", other),
+        Ok(_) => panic!("Expected error, got successful connection"),
+    }
+}
+
+/// Test that the negotiated version, server details, and socket endpoints
+/// are exposed via `server_info()` after a successful handshake.
+#[tokio::test]
+async fn connect_exposes_server_info() {
+    let broker = start_broker(
+        Session::new().connected_with(
+            Frame::new("CONNECTED")
+                .header("version", "1.1")
+                .header("server", "MockBroker/1.0")
+                .header("session", "s-42")
+                .header("heart-beat", "0,0"),
+        ),
+    )
+    .await;
+
+    let options = ConnectOptions::default().accept_version("1.1,1.2");
+    let conn = Connection::connect_with_options(&broker.address(), "user", "pass", "0,0", options)
+        .await
+        .expect("connect failed");
+
+    let info = conn.server_info().await;
+    assert_eq!(info.version, "1.1");
+    assert_eq!(info.server.as_deref(), Some("MockBroker/1.0"));
+    assert_eq!(info.session.as_deref(), Some("s-42"));
+    assert!(info.supports_nack());
+    assert_eq!(info.peer_addr, Some(broker.addr()));
+    assert!(info.local_addr.is_some_and(|a| a.ip().is_loopback()));
+
+    conn.close().await;
+}
+
+/// Test that a STOMP 1.0 session reads and writes header backslashes as
+/// plain text instead of as 1.1+ escape sequences.
+#[tokio::test]
+async fn connect_1_0_session_does_not_escape_headers() {
+    let broker = start_broker(
+        Session::new()
+            .connected_with(Frame::new("CONNECTED").header("heart-beat", "0,0"))
+            .send_raw(
+                &b"MESSAGE\ndestination:/queue/a\nmessage-id:m1\nsubscription:1\n\
+                   path:C:\\new\\temp\n\nhi\0"[..],
+            ),
+    )
+    .await;
+
+    let options = ConnectOptions::default().accept_version("1.0,1.1,1.2");
+    let conn = Connection::connect_with_options(&broker.address(), "user", "pass", "0,0", options)
+        .await
+        .expect("connect failed");
+    assert_eq!(conn.server_info().await.version, "1.0");
+
+    match tokio::time::timeout(Duration::from_secs(2), conn.next_frame()).await {
+        Ok(Some(ReceivedFrame::Frame(f))) => {
+            assert_eq!(f.get_header("path"), Some("C:\\new\\temp"));
+        }
+        other => panic!("expected MESSAGE frame, got {:?}", other),
+    }
+
+    conn.send_frame(
+        Frame::new("SEND")
+            .header("destination", "/queue/a")
+            .header("path", "C:\\new"),
+    )
+    .await
+    .expect("send failed");
+    assert!(broker.wait_for("SEND", 1, Duration::from_secs(2)).await);
+    // The broker reads headers as STOMP 1.2, so the backslash sent as it
+    // is comes back as the start of a `\n` escape
+    let sent = broker.received_commands("SEND");
+    assert_eq!(sent[0].get_header("path"), Some("C:\new"));
+
+    conn.close().await;
+}
+
+/// Run a broker that answers CONNECT with the raw `error_frame` and
+/// return what the client makes of it.
+async fn connect_with_error_frame(error_frame: Vec<u8>, body_limit: usize) -> ConnError {
+    let broker = start_broker(Session::new().expect("CONNECT").send_raw(error_frame)).await;
+
+    let options = ConnectOptions::default().handshake_error_body_limit(body_limit);
+    let result =
+        Connection::connect_with_options(&broker.address(), "user", "pass", "0,0", options).await;
+    match result {
+        Err(err) => err,
+        Ok(_) => panic!("Expected error, got successful connection"),
+    }
+}
+
+/// Test that a huge ERROR body during CONNECT is cut at the limit while
+/// the headers are kept whole
+#[tokio::test]
+async fn connect_error_body_is_truncated_at_limit() {
+    let detail = "d".repeat(8 * 1024);
+    let mut error_frame = format!("ERROR\nmessage:Rejected\ndetail:{}\n\n", detail).into_bytes();
+    error_frame.extend(std::iter::repeat_n(b'x', 1024 * 1024));
+    error_frame.push(0);
+
+    match connect_with_error_frame(error_frame, 100).await {
+        ConnError::ServerRejected(err) => {
+            assert_eq!(err.message, "Rejected");
+            assert_eq!(err.frame.get_header("detail"), Some(detail.as_str()));
+            assert_eq!(err.frame.body.len(), 100);
+            assert_eq!(
+                err.body,
+                Some(format!(
+                    "{}... [truncated after 100 bytes]",
+                    "x".repeat(100)
+                ))
+            );
+        }
+        other => panic!("Expected ServerRejected, got: {:?}", other),
+    }
+}
+
+/// Test that a truncated content-length body is skipped to its end
+#[tokio::test]
+async fn connect_error_content_length_body_is_truncated() {
+    let len = 256 * 1024;
+    let mut error_frame =
+        format!("ERROR\nmessage:Rejected\ncontent-length:{}\n\n", len).into_bytes();
+    // NUL bytes inside the body do not end it
+    error_frame.extend((0..len).map(|i| if i % 1000 == 0 { 0 } else { b'y' }));
+    error_frame.push(0);
+
+    match connect_with_error_frame(error_frame, 10).await {
+        ConnError::ServerRejected(err) => {
+            assert_eq!(err.message, "Rejected");
+            assert_eq!(err.frame.body.len(), 10);
+            assert!(
+                err.body
+                    .as_deref()
+                    .is_some_and(|body| body.ends_with("[truncated after 10 bytes]")),
+                "{:?}",
+                err.body
+            );
+        }
+        other => panic!("Expected ServerRejected, got: {:?}", other),
+    }
+}
+
+/// Test that a body within the limit is kept as sent
+#[tokio::test]
+async fn connect_error_body_within_limit_is_whole() {
+    let error_frame = b"ERROR\nmessage:Rejected\n\nshort\0".to_vec();
+    match connect_with_error_frame(error_frame, 5).await {
+        ConnError::ServerRejected(err) => {
+            assert_eq!(err.body, Some("short".to_string()));
+        }
+        other => panic!("Expected ServerRejected, got: {:?}", other),
+    }
+}
@@ -6,6 +6,7 @@
 //! - Custom headers
 
 use iridium_stomp::ConnectOptions;
+use std::time::Duration;
 
 // ============================================================================
 // ConnectOptions builder tests
@@ -233,14 +234,176 @@ fn connect_options_heartbeat_notify_chainable() {
 }
 
 // ============================================================================
-// Documentation: Critical header protection
+// Validation
 // ============================================================================
-//
-// Note: Custom headers that would override critical STOMP CONNECT headers
-// (accept-version, host, login, passcode, heart-beat, client-id) are silently
-// ignored at connection time. This is enforced in Connection::connect_with_options(),
-// not in ConnectOptions itself. The builder accepts any header but the connection
-// logic filters them.
-//
-// This design allows ConnectOptions to be a simple data container while the
-// Connection enforces protocol safety.
+
+#[test]
+fn validate_rejects_protected_header_override() {
+    use iridium_stomp::ConfigError;
+
+    for name in ["login", "Passcode", "heart-beat", "HOST", "client-id"] {
+        let err = ConnectOptions::new()
+            .header(name, "x")
+            .validate()
+            .unwrap_err();
+        assert_eq!(err, ConfigError::ProtectedHeaderOverride(name.to_string()));
+    }
+    assert!(
+        ConnectOptions::new()
+            .header("x-custom", "x")
+            .validate()
+            .is_ok()
+    );
+}
+
+#[test]
+fn validate_rejects_empty_host() {
+    use iridium_stomp::ConfigError;
+
+    assert_eq!(
+        ConnectOptions::new().host("").validate(),
+        Err(ConfigError::EmptyHost)
+    );
+    assert!(ConnectOptions::new().host("/").validate().is_ok());
+}
+
+#[test]
+fn require_tls_needs_tls_options() {
+    use iridium_stomp::ConfigError;
+
+    assert_eq!(
+        ConnectOptions::new().require_tls().build().unwrap_err(),
+        ConfigError::MissingTlsConfig
+    );
+    #[cfg(feature = "tls")]
+    assert!(
+        ConnectOptions::new()
+            .require_tls()
+            .tls(iridium_stomp::TlsOptions::new())
+            .build()
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn connect_fails_fast_on_invalid_config() {
+    use iridium_stomp::{ConfigError, ConnError, Connection};
+
+    // Nothing listens here; a valid configuration would retry forever
+    let addr = "127.0.0.1:1";
+    let result = tokio::time::timeout(
+        Duration::from_secs(1),
+        Connection::connect_with_options(
+            addr,
+            "guest",
+            "guest",
+            "0,0",
+            ConnectOptions::new().header("login", "admin"),
+        ),
+    )
+    .await
+    .expect("validation should not wait for the broker");
+    assert!(matches!(
+        result,
+        Err(ConnError::Config(ConfigError::ProtectedHeaderOverride(_)))
+    ));
+
+    for heartbeat in ["", "1000", "1000,abc", "1,2,3", "-1,0"] {
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            Connection::connect(addr, "guest", "guest", heartbeat),
+        )
+        .await
+        .expect("validation should not wait for the broker");
+        assert!(
+            matches!(
+                &result,
+                Err(ConnError::Config(ConfigError::InvalidHeartbeat(value))) if value == heartbeat
+            ),
+            "heart-beat {:?} was accepted",
+            heartbeat
+        );
+    }
+}
+
+#[test]
+fn connect_options_content_length_policy() {
+    use iridium_stomp::ContentLengthPolicy;
+
+    assert_eq!(
+        ConnectOptions::default().content_length_policy,
+        ContentLengthPolicy::Auto
+    );
+    let opts = ConnectOptions::new().content_length_policy(ContentLengthPolicy::AlwaysEmit);
+    assert_eq!(opts.content_length_policy, ContentLengthPolicy::AlwaysEmit);
+    let opts = ConnectOptions::new().disable_content_length_auto();
+    assert_eq!(opts.content_length_policy, ContentLengthPolicy::Never);
+}
+
+#[test]
+fn connect_options_strict_protocol() {
+    assert!(!ConnectOptions::default().strict_protocol);
+    assert!(ConnectOptions::new().strict_protocol(true).strict_protocol);
+}
+
+#[test]
+fn connect_options_validate_destinations() {
+    use iridium_stomp::BrokerProfile;
+
+    assert!(ConnectOptions::default().destination_validation.is_none());
+    let opts = ConnectOptions::new().validate_destinations(BrokerProfile::ActiveMq);
+    assert_eq!(opts.destination_validation, Some(BrokerProfile::ActiveMq));
+}
+
+#[test]
+fn connect_options_use_stomp_command() {
+    assert!(!ConnectOptions::default().stomp_command);
+    assert!(ConnectOptions::new().use_stomp_command(true).stomp_command);
+}
+
+#[test]
+fn connect_options_read_timeout() {
+    assert_eq!(ConnectOptions::default().read_timeout, None);
+    let options = ConnectOptions::new().read_timeout(Duration::from_secs(30));
+    assert_eq!(options.read_timeout, Some(Duration::from_secs(30)));
+}
+
+#[test]
+fn connect_options_connect_attempt_timeout() {
+    use iridium_stomp::ConfigError;
+
+    assert_eq!(ConnectOptions::default().connect_attempt_timeout, None);
+    let options = ConnectOptions::new().connect_attempt_timeout(Duration::from_secs(2));
+    assert_eq!(
+        options.connect_attempt_timeout,
+        Some(Duration::from_secs(2))
+    );
+    assert!(options.validate().is_ok());
+    assert_eq!(
+        ConnectOptions::new()
+            .connect_attempt_timeout(Duration::ZERO)
+            .validate(),
+        Err(ConfigError::ZeroConnectAttemptTimeout)
+    );
+}
+
+#[test]
+fn connect_options_circuit_breaker() {
+    use iridium_stomp::{CircuitBreaker, ConfigError};
+
+    assert_eq!(ConnectOptions::default().circuit_breaker, None);
+    let breaker = CircuitBreaker::new(5, Duration::from_secs(60), Duration::from_secs(600));
+    let options = ConnectOptions::new().circuit_breaker(breaker);
+    assert_eq!(options.circuit_breaker, Some(breaker));
+    assert!(options.validate().is_ok());
+    assert_eq!(
+        ConnectOptions::new()
+            .circuit_breaker(CircuitBreaker::new(
+                0,
+                Duration::from_secs(60),
+                Duration::ZERO
+            ))
+            .validate(),
+        Err(ConfigError::ZeroCircuitBreakerFailures)
+    );
+}
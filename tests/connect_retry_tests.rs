@@ -8,52 +8,37 @@
 
 use iridium_stomp::Connection;
 use iridium_stomp::connection::ConnError;
-use std::io::{Read, Write};
-use std::net::TcpListener;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::thread;
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use std::net::SocketAddr;
 use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
 
-/// Helper to find an available port.
-fn get_available_port() -> u16 {
-    TcpListener::bind("127.0.0.1:0")
-        .unwrap()
-        .local_addr()
-        .unwrap()
-        .port()
+/// An address nobody listens on: the broker stops listening when dropped.
+async fn unused_address() -> SocketAddr {
+    MockBroker::start(Script::new()).await.unwrap().addr()
+}
+
+/// Start a broker playing `session` at `addr` after `delay`.
+fn start_late(addr: SocketAddr, delay: Duration, session: Session) -> JoinHandle<MockBroker> {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        MockBroker::start_at(addr, Script::new().session(session))
+            .await
+            .unwrap()
+    })
 }
 
 /// Broker comes up after a delay — connect should succeed after retrying.
 #[tokio::test]
 async fn connect_succeeds_after_broker_starts_late() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-
-    // Start a mock STOMP server after a delay
-    let server_addr = addr.clone();
-    let server = thread::spawn(move || {
-        // Wait so the client hits at least one retry
-        thread::sleep(Duration::from_secs(2));
-
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        if let Ok((mut stream, _)) = listener.accept() {
-            let mut buf = [0u8; 1024];
-            let _ = stream.read(&mut buf);
-
-            let connected = "CONNECTED\nversion:1.2\nheart-beat:0,0\n\n\0";
-            stream.write_all(connected.as_bytes()).unwrap();
-            stream.flush().unwrap();
-
-            // Keep alive so the connection doesn't drop immediately
-            thread::sleep(Duration::from_secs(1));
-        }
-    });
+    let addr = unused_address().await;
+    // Wait so the client hits at least one retry
+    let broker = start_late(addr, Duration::from_secs(2), Session::new().connected());
 
     let start = Instant::now();
     let result = tokio::time::timeout(
         Duration::from_secs(10),
-        Connection::connect(&addr, "guest", "guest", "0,0"),
+        Connection::connect(&addr.to_string(), "guest", "guest", "0,0"),
     )
     .await;
 
@@ -69,33 +54,18 @@ async fn connect_succeeds_after_broker_starts_late() {
     );
 
     conn.unwrap().close().await;
-    server.join().unwrap();
+    assert_eq!(broker.await.unwrap().connections(), 1);
 }
 
 /// Bad credentials fail immediately — no retry.
 #[tokio::test]
 async fn connect_fails_immediately_on_auth_error() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-
-    let server_addr = addr.clone();
-    let server = thread::spawn(move || {
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        if let Ok((mut stream, _)) = listener.accept() {
-            let mut buf = [0u8; 1024];
-            let _ = stream.read(&mut buf);
-
-            let error = "ERROR\nmessage:Bad credentials\n\nAccess refused\0";
-            stream.write_all(error.as_bytes()).unwrap();
-            stream.flush().unwrap();
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
-
-    thread::sleep(Duration::from_millis(50));
+    let broker = MockBroker::start(Script::new().session(Session::new().reject("Bad credentials")))
+        .await
+        .unwrap();
 
     let start = Instant::now();
-    let result = Connection::connect(&addr, "bad", "creds", "0,0").await;
+    let result = Connection::connect(&broker.address(), "bad", "creds", "0,0").await;
     let elapsed = start.elapsed();
 
     match result {
@@ -112,43 +82,20 @@ async fn connect_fails_immediately_on_auth_error() {
         "auth error should fail before retry backoff, took {:?}",
         elapsed
     );
-
-    server.join().unwrap();
 }
 
 /// Server closes without CONNECTED — this is retried (could be a transient
 /// broker crash), not a fast failure.
 #[tokio::test]
 async fn connect_retries_on_server_close_during_handshake() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-
-    let server_addr = addr.clone();
-    let server = thread::spawn(move || {
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        listener.set_nonblocking(true).unwrap();
-        let deadline = Instant::now() + Duration::from_secs(2);
-        while Instant::now() < deadline {
-            match listener.accept() {
-                Ok((mut stream, _)) => {
-                    let mut buf = [0u8; 1024];
-                    let _ = stream.read(&mut buf);
-                    drop(stream);
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    thread::sleep(Duration::from_millis(50));
-                }
-                Err(_) => break,
-            }
-        }
-    });
-
-    thread::sleep(Duration::from_millis(50));
+    let broker = MockBroker::start(Script::new().session(Session::new().expect("CONNECT").close()))
+        .await
+        .unwrap();
 
     // Should keep retrying, not fail immediately
     let result = tokio::time::timeout(
         Duration::from_millis(500),
-        Connection::connect(&addr, "guest", "guest", "0,0"),
+        Connection::connect(&broker.address(), "guest", "guest", "0,0"),
     )
     .await;
 
@@ -156,8 +103,6 @@ async fn connect_retries_on_server_close_during_handshake() {
         result.is_err(),
         "Expected connect to keep retrying on protocol error, but it returned"
     );
-
-    server.join().unwrap();
 }
 
 /// Verify backoff increases between retries.
@@ -167,15 +112,14 @@ async fn connect_retries_on_server_close_during_handshake() {
 /// successive retry intervals grow.
 #[tokio::test]
 async fn connect_retry_uses_exponential_backoff() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
+    let addr = unused_address().await;
 
     // No server — every TCP connect will fail and be retried.
     // The first retry is after 1s, second after 2s, so after 3.5s we should
     // still be retrying (total: 1 + 2 = 3s of sleep plus attempt time).
     let result = tokio::time::timeout(
         Duration::from_millis(3500),
-        Connection::connect(&addr, "guest", "guest", "0,0"),
+        Connection::connect(&addr.to_string(), "guest", "guest", "0,0"),
     )
     .await;
 
@@ -190,30 +134,18 @@ async fn connect_retry_uses_exponential_backoff() {
 /// broker is reachable.
 #[tokio::test]
 async fn connect_retry_then_auth_error_fails_fast() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-
-    let server_addr = addr.clone();
-    let server = thread::spawn(move || {
-        // Delay so client hits at least one TCP retry
-        thread::sleep(Duration::from_millis(1500));
-
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        if let Ok((mut stream, _)) = listener.accept() {
-            let mut buf = [0u8; 1024];
-            let _ = stream.read(&mut buf);
-
-            let error = "ERROR\nmessage:Access denied\n\n\0";
-            stream.write_all(error.as_bytes()).unwrap();
-            stream.flush().unwrap();
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
+    let addr = unused_address().await;
+    // Delay so client hits at least one TCP retry
+    let _broker = start_late(
+        addr,
+        Duration::from_millis(1500),
+        Session::new().reject("Access denied"),
+    );
 
     let start = Instant::now();
     let result = tokio::time::timeout(
         Duration::from_secs(10),
-        Connection::connect(&addr, "bad", "creds", "0,0"),
+        Connection::connect(&addr.to_string(), "bad", "creds", "0,0"),
     )
     .await;
 
@@ -236,49 +168,26 @@ async fn connect_retry_then_auth_error_fails_fast() {
         "unexpected timing: {:?}",
         elapsed
     );
-
-    server.join().unwrap();
 }
 
 /// Multiple retry attempts actually happen (counted by a mock server).
 #[tokio::test]
 async fn connect_retry_makes_multiple_attempts() {
-    let port = get_available_port();
-    let addr = format!("127.0.0.1:{}", port);
-    let attempt_count = Arc::new(AtomicU32::new(0));
-
-    // Mock server that accepts connections but immediately closes them,
-    // causing I/O errors during the STOMP handshake. On the 3rd attempt
-    // it responds with CONNECTED.
-    let server_addr = addr.clone();
-    let count = attempt_count.clone();
-    let server = thread::spawn(move || {
-        let listener = TcpListener::bind(&server_addr).unwrap();
-        for _ in 0..3 {
-            if let Ok((mut stream, _)) = listener.accept() {
-                let n = count.fetch_add(1, Ordering::SeqCst) + 1;
-                let mut buf = [0u8; 1024];
-                let _ = stream.read(&mut buf);
-
-                if n < 3 {
-                    // Close without responding — triggers I/O retry
-                    drop(stream);
-                } else {
-                    // Third attempt — send CONNECTED
-                    let connected = "CONNECTED\nversion:1.2\nheart-beat:0,0\n\n\0";
-                    stream.write_all(connected.as_bytes()).unwrap();
-                    stream.flush().unwrap();
-                    thread::sleep(Duration::from_secs(1));
-                }
-            }
-        }
-    });
-
-    thread::sleep(Duration::from_millis(50));
+    // The first two connections are closed without a response, causing
+    // I/O errors during the STOMP handshake; the third gets CONNECTED
+    let close = Session::new().expect("CONNECT").close();
+    let broker = MockBroker::start(
+        Script::new()
+            .session(close.clone())
+            .session(close)
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
 
     let result = tokio::time::timeout(
         Duration::from_secs(15),
-        Connection::connect(&addr, "guest", "guest", "0,0"),
+        Connection::connect(&broker.address(), "guest", "guest", "0,0"),
     )
     .await;
 
@@ -286,7 +195,7 @@ async fn connect_retry_makes_multiple_attempts() {
     let conn = result.unwrap();
     assert!(conn.is_ok(), "should connect on third attempt");
 
-    let attempts = attempt_count.load(Ordering::SeqCst);
+    let attempts = broker.connections();
     assert_eq!(
         attempts, 3,
         "expected 3 connection attempts, got {}",
@@ -294,5 +203,4 @@ async fn connect_retry_makes_multiple_attempts() {
     );
 
     conn.unwrap().close().await;
-    server.join().unwrap();
 }
@@ -93,6 +93,50 @@ fn conn_error_receipt_timeout_display() {
     assert!(display.contains("msg-123"));
 }
 
+#[test]
+fn conn_error_codes_are_stable() {
+    use std::time::Duration;
+
+    let cases = [
+        (ConnError::Protocol("x".to_string()), "protocol"),
+        (
+            ConnError::HandshakeTimeout(Duration::from_secs(30)),
+            "handshake_timeout",
+        ),
+        (
+            ConnError::SubscriptionNotFound("7".to_string()),
+            "subscription_not_found",
+        ),
+        (ConnError::ChannelClosed, "channel_closed"),
+        (
+            ConnError::ReceiptTimeout("r".to_string()),
+            "receipt_timeout",
+        ),
+        (
+            ConnError::CloseTimeout(Duration::from_secs(5)),
+            "close_timeout",
+        ),
+        (ConnError::Io(io::Error::other("x")), "io"),
+    ];
+    for (err, code) in cases {
+        assert_eq!(err.code(), code, "{}", err);
+    }
+}
+
+#[test]
+fn conn_error_decode_keeps_source() {
+    use std::error::Error;
+
+    let conn_err = ConnError::Decode {
+        source: io::Error::new(io::ErrorKind::InvalidData, "invalid header escape"),
+    };
+    assert_eq!(conn_err.code(), "decode");
+    assert!(conn_err.to_string().contains("invalid header escape"));
+    let source = conn_err.source().expect("source is kept");
+    let source = source.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(source.kind(), io::ErrorKind::InvalidData);
+}
+
 // =============================================================================
 // ConnError::ServerRejected Tests
 // =============================================================================
@@ -0,0 +1,78 @@
+//! Tests for `Connection::decode_stats()`.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, DecodeErrorStats, EscapePolicy, ReceivedFrame};
+use std::time::Duration;
+
+/// Start a broker that answers CONNECT with the raw `connected` and then
+/// writes `traffic` as-is.
+async fn start_broker(connected: &[u8], traffic: &[u8]) -> MockBroker {
+    let session = Session::new()
+        .expect("CONNECT")
+        .send_raw(connected)
+        .send_raw(traffic);
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn connection_reports_decode_stats() {
+    const CONNECTED: &[u8] = b"CONNECTED\nversion:1.2\nheart-beat:0,0\n\n\0";
+    // The LF right after a NUL ends the frame; the second one is a heartbeat
+    const TRAFFIC: &[u8] =
+        b"MESSAGE\ndestination:/queue/a\nmessage-id:m1\nsubscription:9\n\nhi\0\n\n\
+        MESSAGE\ndestination:/queue/a\nmessage-id:m2\nsubscription:9\nCONTENT-LENGTH:2\n\nho\0";
+
+    let broker = start_broker(CONNECTED, TRAFFIC).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    for body in [b"hi", b"ho"] {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_frame()).await {
+            Ok(Some(ReceivedFrame::Frame(f))) => assert_eq!(f.body, body),
+            other => panic!("expected MESSAGE frame, got {:?}", other),
+        }
+    }
+
+    // The handshake is decoded by the same counters as the session
+    let stats = conn.decode_stats();
+    assert_eq!(stats.frames, 3);
+    assert_eq!(stats.heartbeats, 1);
+    assert_eq!(stats.bytes, (CONNECTED.len() + TRAFFIC.len()) as u64);
+    assert_eq!(stats.permissive, 1);
+    assert_eq!(stats.errors, DecodeErrorStats::default());
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn lenient_escapes_keep_the_session_alive() {
+    const CONNECTED: &[u8] = b"CONNECTED\nversion:1.2\nheart-beat:0,0\n\n\0";
+    // `\t` and `\d` are not STOMP 1.2 escapes
+    const MESSAGE: &[u8] =
+        b"MESSAGE\ndestination:/queue/a\nmessage-id:m1\nsubscription:9\npath:C:\\temp\\data\n\nhi\0";
+
+    let broker = start_broker(CONNECTED, MESSAGE).await;
+
+    let options = ConnectOptions::new().escape_policy(EscapePolicy::Lenient);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    match tokio::time::timeout(Duration::from_secs(2), conn.next_frame()).await {
+        Ok(Some(ReceivedFrame::Frame(f))) => {
+            assert_eq!(f.get_header("path"), Some("C:\\temp\\data"));
+        }
+        other => panic!("expected MESSAGE frame, got {:?}", other),
+    }
+
+    let stats = conn.decode_stats();
+    assert_eq!(stats.permissive, 1);
+    assert_eq!(stats.errors, DecodeErrorStats::default());
+
+    conn.close().await;
+}
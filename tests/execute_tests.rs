@@ -0,0 +1,136 @@
+//! Tests for `Connection::execute()` and `Connection::execute_with_options()`.
+//!
+//! The mock broker records every frame it reads and answers receipts if
+//! told to.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{ConnError, Connection, ExecuteOptions, Frame, assert_frame};
+use std::time::Duration;
+
+/// Start a broker that answers receipts if `confirm` is set.
+async fn start_broker(confirm: bool) -> MockBroker {
+    let session = if confirm {
+        Session::new().connected()
+    } else {
+        Session::new().connected().withhold_receipts()
+    };
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+fn send(destination: &str, body: &str) -> Frame {
+    Frame::new("SEND")
+        .header("destination", destination)
+        .set_body(body.as_bytes().to_vec())
+}
+
+/// The frames `broker` received after the handshake.
+fn received(broker: &MockBroker) -> Vec<Frame> {
+    let mut frames = broker.received();
+    frames.retain(|f| f.command != "CONNECT");
+    frames
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batch_is_not_interleaved_with_other_sends() {
+    let broker = start_broker(false).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let mut others = Vec::new();
+    for task in 0..4 {
+        let conn = conn.clone();
+        others.push(tokio::spawn(async move {
+            for n in 0..50 {
+                conn.send("/queue/other", format!("other-{}-{}", task, n))
+                    .await
+                    .unwrap();
+                tokio::task::yield_now().await;
+            }
+        }));
+    }
+    let batch: Vec<Frame> = (0..50)
+        .map(|n| send("/queue/setup", &format!("step-{}", n)))
+        .collect();
+    conn.execute(batch).await.expect("execute failed");
+    for task in others {
+        task.await.unwrap();
+    }
+
+    assert!(broker.wait_for("SEND", 250, Duration::from_secs(2)).await);
+    let sent = received(&broker);
+    assert_eq!(sent.len(), 250);
+    let first = sent
+        .iter()
+        .position(|f| f.body == b"step-0")
+        .expect("batch not sent");
+    let steps: Vec<String> = sent[first..first + 50]
+        .iter()
+        .map(|f| String::from_utf8_lossy(&f.body).into_owned())
+        .collect();
+    let expected: Vec<String> = (0..50).map(|n| format!("step-{}", n)).collect();
+    assert_eq!(steps, expected);
+    conn.close().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transaction_is_wrapped_and_confirmed_once() {
+    let broker = start_broker(true).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let options = ExecuteOptions {
+        transaction: Some("setup-1".into()),
+        receipt_timeout: Some(Duration::from_secs(2)),
+    };
+    conn.execute_with_options(
+        vec![send("/queue/setup", "reset"), send("/queue/setup", "load")],
+        options,
+    )
+    .await
+    .expect("batch not confirmed");
+
+    let sent = received(&broker);
+    let commands: Vec<&str> = sent.iter().map(|f| f.command.as_str()).collect();
+    assert_eq!(commands, ["BEGIN", "SEND", "SEND", "COMMIT"]);
+    for frame in &sent[..3] {
+        assert_frame!(
+            frame,
+            FrameMatcher::new()
+                .header("transaction", "setup-1")
+                .lacks_header("receipt")
+        );
+    }
+    assert_frame!(
+        sent[3],
+        FrameMatcher::command("COMMIT")
+            .header("transaction", "setup-1")
+            .has_header("receipt")
+    );
+    conn.close().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unconfirmed_batch_times_out() {
+    let broker = start_broker(false).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let options = ExecuteOptions {
+        receipt_timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let result = conn
+        .execute_with_options(vec![send("/queue/setup", "reset")], options)
+        .await;
+    assert!(
+        matches!(result, Err(ConnError::ReceiptTimeout(_))),
+        "{:?}",
+        result
+    );
+    conn.close().await;
+}
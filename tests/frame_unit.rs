@@ -1,6 +1,6 @@
 //! Unit tests for the Frame struct.
 
-use iridium_stomp::Frame;
+use iridium_stomp::{Frame, frame};
 
 // =============================================================================
 // Construction Tests
@@ -88,6 +88,40 @@ fn frame_builder_chain() {
     assert_eq!(frame.body, b"{\"key\": \"value\"}");
 }
 
+// =============================================================================
+// frame! Macro Tests
+// =============================================================================
+
+#[test]
+fn frame_macro_command_only() {
+    assert_eq!(frame!(DISCONNECT), Frame::new("DISCONNECT"));
+    assert_eq!(frame!(DISCONNECT,), Frame::new("DISCONNECT"));
+}
+
+#[test]
+fn frame_macro_maps_header_names_in_order() {
+    let frame = frame!(ACK, id: "m1", subscription: 1, message_id: "m1", "x-trace": "t");
+    assert_eq!(
+        frame.headers,
+        [
+            ("id".to_string(), "m1".to_string()),
+            ("subscription".to_string(), "1".to_string()),
+            ("message-id".to_string(), "m1".to_string()),
+            ("x-trace".to_string(), "t".to_string()),
+        ]
+    );
+    assert!(frame.body.is_empty());
+}
+
+#[test]
+fn frame_macro_body() {
+    let frame = frame!(SEND, destination: "/queue/a"; body: vec![0u8, 1, 2]);
+    assert_eq!(frame.body, [0, 1, 2]);
+    let frame = frame!(SEND; body: "hi";);
+    assert!(frame.headers.is_empty());
+    assert_eq!(frame.body, b"hi");
+}
+
 // =============================================================================
 // Display Trait Tests
 // =============================================================================
@@ -267,3 +301,186 @@ fn frame_header_special_characters() {
         "http://example.com:8080/path?query=value&other=123"
     );
 }
+
+// =============================================================================
+// Body Preview Tests
+// =============================================================================
+
+#[test]
+fn body_preview_returns_text_unchanged() {
+    let frame = Frame::new("MESSAGE").set_body(b"hello".to_vec());
+    assert_eq!(frame.body_preview(100), "hello");
+}
+
+#[test]
+fn body_preview_truncates_on_char_boundary() {
+    // "é" is two bytes; cutting at 2 would split it
+    let frame = Frame::new("MESSAGE").set_body("aé b".as_bytes().to_vec());
+    assert_eq!(frame.body_preview(2), "a...");
+}
+
+#[test]
+fn body_preview_hex_dumps_binary() {
+    let body: Vec<u8> = (0u8..20)
+        .map(|b| b.wrapping_add(0x40))
+        .chain([0xff])
+        .collect();
+    let frame = Frame::new("MESSAGE").set_body(body);
+    let preview = frame.body_preview(100);
+    let lines: Vec<&str> = preview.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        "00000000  40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|"
+    );
+    assert!(lines[1].starts_with("00000010  50 51 52 53 ff"));
+    assert!(lines[1].ends_with("|PQRS.|"));
+}
+
+#[test]
+fn body_preview_reports_truncated_binary_size() {
+    let frame = Frame::new("MESSAGE").set_body(vec![0xffu8; 40]);
+    let preview = frame.body_preview(16);
+    assert_eq!(preview.lines().count(), 2);
+    assert!(preview.ends_with("... (40 bytes total)"));
+}
+
+#[test]
+fn body_base64_matches_rfc4648_vectors() {
+    let cases: [(&[u8], &str); 7] = [
+        (b"", ""),
+        (b"f", "Zg=="),
+        (b"fo", "Zm8="),
+        (b"foo", "Zm9v"),
+        (b"foob", "Zm9vYg=="),
+        (b"fooba", "Zm9vYmE="),
+        (b"foobar", "Zm9vYmFy"),
+    ];
+    for (input, expected) in cases {
+        let frame = Frame::new("MESSAGE").set_body(input.to_vec());
+        assert_eq!(frame.body_base64(), expected);
+    }
+}
+
+// =============================================================================
+// Semantic Equality Tests
+// =============================================================================
+
+#[test]
+fn semantically_eq_ignores_header_order_and_case() {
+    let a = Frame::new("SEND")
+        .header("destination", "/queue/a")
+        .header("content-type", "text/plain")
+        .set_body(b"x".to_vec());
+    let b = Frame::new("SEND")
+        .header("Content-Type", "text/plain")
+        .header("DESTINATION", "/queue/a")
+        .set_body(b"x".to_vec());
+    assert_ne!(a, b);
+    assert!(a.semantically_eq(&b));
+}
+
+#[test]
+fn semantically_eq_uses_first_duplicate() {
+    let a = Frame::new("MESSAGE").header("foo", "1");
+    let b = Frame::new("MESSAGE").header("foo", "1").header("foo", "2");
+    let c = Frame::new("MESSAGE").header("foo", "2").header("foo", "1");
+    assert!(a.semantically_eq(&b));
+    assert!(!a.semantically_eq(&c));
+}
+
+#[test]
+fn semantically_eq_compares_command_and_body() {
+    let a = Frame::new("SEND").set_body(b"x".to_vec());
+    assert!(!a.semantically_eq(&Frame::new("MESSAGE").set_body(b"x".to_vec())));
+    assert!(!a.semantically_eq(&Frame::new("SEND").set_body(b"y".to_vec())));
+}
+
+#[test]
+fn canonical_frames_deduplicate_in_hash_set() {
+    use std::collections::HashSet;
+
+    let frames = [
+        Frame::new("SEND").header("a", "1").header("b", "2"),
+        Frame::new("SEND").header("B", "2").header("a", "1"),
+        Frame::new("SEND").header("a", "1").header("b", "3"),
+    ];
+    let unique: HashSet<Frame> = frames.iter().map(Frame::canonicalize).collect();
+    assert_eq!(unique.len(), 2);
+}
+
+// =============================================================================
+// Encoded Length Tests
+// =============================================================================
+
+fn encode(frame: &Frame) -> Vec<u8> {
+    use bytes::BytesMut;
+    use iridium_stomp::{StompCodec, StompItem};
+    use tokio_util::codec::Encoder;
+
+    let mut buf = BytesMut::new();
+    StompCodec::new()
+        .encode(StompItem::Frame(frame.clone()), &mut buf)
+        .unwrap();
+    buf.to_vec()
+}
+
+#[test]
+fn encoded_len_matches_encoder_output() {
+    let frames = [
+        Frame::new("DISCONNECT"),
+        Frame::new("SEND")
+            .header("destination", "/queue/a")
+            .set_body(b"hello".to_vec()),
+        // Escaped header name and value
+        Frame::new("SEND")
+            .header("key:with\\colon", "line\nbreak\r:")
+            .set_body(b"x".to_vec()),
+        // Binary body gets an automatic content-length
+        Frame::new("SEND").set_body(vec![0u8, 1, 2, 0xff]),
+        // Explicit content-length is not duplicated
+        Frame::new("SEND")
+            .header("Content-Length", "3")
+            .set_body(vec![0u8, 0, 0]),
+        // Multi-byte UTF-8
+        Frame::new("SEND")
+            .header("name", "héllo")
+            .set_body("wörld".as_bytes().to_vec()),
+    ];
+    for frame in &frames {
+        assert_eq!(frame.encoded_len(), encode(frame).len(), "{:?}", frame);
+    }
+}
+
+// =============================================================================
+// Content Type Sniffing Tests
+// =============================================================================
+
+#[test]
+fn sniff_content_type_prefers_the_header() {
+    let frame = Frame::new("MESSAGE")
+        .header("content-type", "text/csv")
+        .set_body(b"{}".to_vec());
+    assert_eq!(frame.sniff_content_type(), Some("text/csv"));
+    assert_eq!(Frame::new("MESSAGE").sniff_content_type(), None);
+}
+
+#[test]
+fn sniff_content_type_guesses_from_the_body() {
+    let cases: [(&[u8], &str); 10] = [
+        (b"  {\"id\": 1}\n", "application/json"),
+        (b"\xef\xbb\xbf[1, 2]", "application/json"),
+        (b"<?xml version=\"1.0\"?><order/>", "application/xml"),
+        (b"<order id=\"1\"/>", "application/xml"),
+        (b"hello\tworld\r\n", "text/plain"),
+        (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", "image/png"),
+        (b"\xff\xd8\xff\xe0", "image/jpeg"),
+        (b"%PDF-1.7", "application/pdf"),
+        (b"\x1f\x8b\x08\0", "application/gzip"),
+        (b"\x00\x01\x02", "application/octet-stream"),
+    ];
+    for (body, expected) in cases {
+        let frame = Frame::new("MESSAGE").set_body(body.to_vec());
+        assert_eq!(frame.sniff_content_type(), Some(expected), "{:?}", body);
+    }
+}
@@ -8,7 +8,8 @@
 
 use bytes::BytesMut;
 use iridium_stomp::Frame;
-use iridium_stomp::codec::{StompCodec, StompItem};
+use iridium_stomp::codec::{EscapePolicy, HeaderPolicy, StompCodec, StompItem};
+use iridium_stomp::parser::unescape_header_value_lenient;
 use tokio_util::codec::{Decoder, Encoder};
 
 // ============================================================================
@@ -137,6 +138,52 @@ fn unescape_incomplete_sequence() {
     assert!(err.to_string().contains("incomplete escape"));
 }
 
+#[test]
+fn lenient_policy_keeps_undefined_escapes() {
+    let raw = b"MESSAGE\npath:C:\\temp\\data\\nnext\nregex:\\d+\\\n\n\0";
+    let mut codec = StompCodec::new().with_escape_policy(EscapePolicy::Lenient);
+    let mut buf = BytesMut::from(&raw[..]);
+
+    let item = codec.decode(&mut buf).unwrap().unwrap();
+    match item {
+        StompItem::Frame(frame) => {
+            // Defined sequences are still unescaped
+            assert_eq!(frame.get_header("path"), Some("C:\\temp\\data\nnext"));
+            assert_eq!(frame.get_header("regex"), Some("\\d+\\"));
+        }
+        _ => panic!("expected frame"),
+    }
+    // Counted once per frame, however many sequences it kept
+    assert_eq!(codec.decode_stats().permissive, 1);
+    assert_eq!(codec.decode_stats().errors.invalid_escape, 0);
+}
+
+#[test]
+fn lenient_policy_does_not_count_valid_frames() {
+    let raw = b"MESSAGE\nheader:a\\cb\\\\c\n\n\0";
+    let mut codec = StompCodec::new().with_escape_policy(EscapePolicy::Lenient);
+    let mut buf = BytesMut::from(&raw[..]);
+
+    let item = codec.decode(&mut buf).unwrap().unwrap();
+    match item {
+        StompItem::Frame(frame) => assert_eq!(frame.get_header("header"), Some("a:b\\c")),
+        _ => panic!("expected frame"),
+    }
+    assert_eq!(codec.decode_stats().permissive, 0);
+}
+
+#[test]
+fn lenient_unescape_reports_kept_backslashes() {
+    assert_eq!(
+        unescape_header_value_lenient(b"a\\tb\\\\c\\"),
+        (b"a\\tb\\c\\".to_vec(), 2)
+    );
+    assert_eq!(
+        unescape_header_value_lenient(b"plain"),
+        (b"plain".to_vec(), 0)
+    );
+}
+
 // ============================================================================
 // Escape tests (encoding outgoing frames)
 // ============================================================================
@@ -447,3 +494,88 @@ fn destination_with_colon() {
         _ => panic!("expected frame"),
     }
 }
+
+// ============================================================================
+// Header policy tests (encoding outgoing header names)
+// ============================================================================
+
+fn encode_with(policy: HeaderPolicy, frame: Frame) -> std::io::Result<String> {
+    let mut codec = StompCodec::new().with_header_policy(policy);
+    let mut buf = BytesMut::new();
+    codec.encode(StompItem::Frame(frame), &mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+#[test]
+fn header_policy_default_writes_frames_as_built() {
+    let frame = Frame::new("SEND")
+        .header("Destination", "/queue/a")
+        .header("destination", "/queue/b");
+    let encoded = encode_with(HeaderPolicy::default(), frame).unwrap();
+    assert!(encoded.starts_with("SEND\nDestination:/queue/a\ndestination:/queue/b\n"));
+}
+
+#[test]
+fn header_policy_lowercases_names() {
+    let policy = HeaderPolicy {
+        lowercase_names: true,
+        ..Default::default()
+    };
+    let frame = Frame::new("SEND")
+        .header("Destination", "/queue/A")
+        .header("X-Trace:Id", "Abc");
+    let encoded = encode_with(policy, frame).unwrap();
+    assert!(
+        encoded.starts_with("SEND\ndestination:/queue/A\nx-trace\\cid:Abc\n"),
+        "{}",
+        encoded
+    );
+}
+
+#[test]
+fn header_policy_dedupes_protected_headers() {
+    let policy = HeaderPolicy {
+        dedupe_protected: true,
+        ..Default::default()
+    };
+    let frame = Frame::new("SEND")
+        .header("destination", "/queue/a")
+        .header("tag", "1")
+        .header("Destination", "/queue/b")
+        .header("tag", "2");
+    let encoded = encode_with(policy, frame).unwrap();
+    assert!(
+        encoded.starts_with("SEND\ndestination:/queue/a\ntag:1\ntag:2\n\n"),
+        "{}",
+        encoded
+    );
+}
+
+#[test]
+fn header_policy_rejects_control_characters_in_names() {
+    let frame = Frame::new("SEND").header("bad\tname", "x");
+    let err = encode_with(HeaderPolicy::strict(), frame.clone()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    // Values are not names, and the check is off by default
+    assert!(encode_with(HeaderPolicy::default(), frame).is_ok());
+    let value = Frame::new("SEND").header("name", "tab\there");
+    assert!(encode_with(HeaderPolicy::strict(), value).is_ok());
+}
+
+#[test]
+fn escaping_off_writes_and_reads_headers_as_they_are() {
+    // STOMP 1.0 has no escapes: backslashes and colons are plain text
+    let mut codec = StompCodec::new().with_header_escaping(false);
+    let frame = Frame::new("SEND").header("path", "C:\\temp\\new");
+    let mut buf = BytesMut::new();
+    codec.encode(StompItem::Frame(frame), &mut buf).unwrap();
+    assert!(buf.starts_with(b"SEND\npath:C:\\temp\\new\n"), "{:?}", buf);
+
+    let item = codec.decode(&mut buf).unwrap().unwrap();
+    match item {
+        StompItem::Frame(frame) => {
+            assert_eq!(frame.get_header("path"), Some("C:\\temp\\new"));
+        }
+        _ => panic!("Expected frame"),
+    }
+}
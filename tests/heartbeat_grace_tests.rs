@@ -0,0 +1,300 @@
+//! Tests for `ConnectOptions::heartbeat_grace_multiplier()` and
+//! `ConnectOptions::heartbeat_jitter()`.
+//!
+//! The mock brokers negotiate short heartbeat intervals, then either send
+//! heartbeats on a fixed schedule and report whether the client dropped
+//! the connection, or time the heartbeats the client sends. Heartbeats only
+//! flow one way in each test, and the broker may send them as CRLF.
+//!
+//! These brokers stay hand-rolled rather than using `testing::MockBroker`:
+//! they time the raw heartbeat bytes and the moment the client drops the
+//! socket, which the frame-level mock does not expose.
+
+use iridium_stomp::{ConfigError, ConnectOptions, Connection};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper to find an available port.
+fn get_available_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Read one NUL-terminated frame, skipping heartbeat newlines.
+fn read_frame(stream: &mut TcpStream) -> String {
+    let mut frame = Vec::new();
+    let mut byte = [0u8; 1];
+    while let Ok(1) = stream.read(&mut byte) {
+        if byte[0] == 0 {
+            break;
+        }
+        frame.push(byte[0]);
+    }
+    String::from_utf8_lossy(&frame)
+        .trim_start_matches('\n')
+        .to_string()
+}
+
+/// How long the watched session lasted.
+#[derive(Debug)]
+enum Outcome {
+    /// Still open when the broker stopped watching.
+    Open,
+    /// Closed by the client after this long.
+    Closed(Duration),
+}
+
+/// Start a broker that offers heartbeats every 200 ms, then sends `beat`
+/// every `beat_every` for `watch` and reports whether the client dropped
+/// the connection meanwhile.
+fn spawn_beating_broker(
+    addr: String,
+    beat: &'static [u8],
+    beat_every: Duration,
+    watch: Duration,
+) -> std_mpsc::Receiver<Outcome> {
+    let (outcome_tx, outcome_rx) = std_mpsc::channel();
+    thread::spawn(move || {
+        let listener = TcpListener::bind(&addr).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        read_frame(&mut stream);
+        stream
+            .write_all(b"CONNECTED\nversion:1.2\nheart-beat:200,0\n\n\0")
+            .unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(5)))
+            .unwrap();
+        let start = Instant::now();
+        let mut next_beat = start + beat_every;
+        let mut buf = [0u8; 256];
+        let outcome = loop {
+            if start.elapsed() >= watch {
+                break Outcome::Open;
+            }
+            if Instant::now() >= next_beat {
+                if stream.write_all(beat).is_err() {
+                    break Outcome::Closed(start.elapsed());
+                }
+                next_beat += beat_every;
+            }
+            match stream.read(&mut buf) {
+                Ok(0) => break Outcome::Closed(start.elapsed()),
+                Err(e) if !matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    break Outcome::Closed(start.elapsed());
+                }
+                _ => {}
+            }
+        };
+        let _ = outcome_tx.send(outcome);
+    });
+    thread::sleep(Duration::from_millis(50));
+    outcome_rx
+}
+
+/// Connect wanting heartbeats every 200 ms and wait for the broker's verdict.
+async fn watch_session(beat_every: Duration, options: ConnectOptions) -> (Connection, Outcome) {
+    watch_session_with(b"\n", beat_every, options).await
+}
+
+/// `watch_session()` with the broker sending `beat` as its heartbeat.
+async fn watch_session_with(
+    beat: &'static [u8],
+    beat_every: Duration,
+    options: ConnectOptions,
+) -> (Connection, Outcome) {
+    let addr = format!("127.0.0.1:{}", get_available_port());
+    let outcome = spawn_beating_broker(addr.clone(), beat, beat_every, Duration::from_millis(1200));
+    let conn = Connection::connect_with_options(&addr, "guest", "guest", "0,200", options)
+        .await
+        .expect("connect failed");
+    let outcome =
+        tokio::task::spawn_blocking(move || outcome.recv_timeout(Duration::from_secs(5)).unwrap())
+            .await
+            .unwrap();
+    (conn, outcome)
+}
+
+#[tokio::test]
+async fn default_grace_tolerates_a_late_heartbeat() {
+    // 1.5 intervals between heartbeats, inside the default 2.0
+    let (conn, outcome) = watch_session(Duration::from_millis(300), ConnectOptions::new()).await;
+    assert!(matches!(outcome, Outcome::Open), "{:?}", outcome);
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn tighter_grace_drops_a_late_heartbeat() {
+    let options = ConnectOptions::new().heartbeat_grace_multiplier(1.25);
+    let (conn, outcome) = watch_session(Duration::from_millis(300), options).await;
+    match outcome {
+        // Dropped once 250 ms passed without data, normally before the
+        // first beat and at the latest before the second
+        Outcome::Closed(after) => assert!(
+            after >= Duration::from_millis(200) && after < Duration::from_millis(600),
+            "closed after {:?}",
+            after
+        ),
+        Outcome::Open => panic!("connection survived a heartbeat beyond its grace period"),
+    }
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn tighter_grace_keeps_heartbeats_at_the_negotiated_interval() {
+    let options = ConnectOptions::new().heartbeat_grace_multiplier(1.25);
+    let (conn, outcome) = watch_session(Duration::from_millis(200), options).await;
+    assert!(matches!(outcome, Outcome::Open), "{:?}", outcome);
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn jitter_spreads_outgoing_heartbeats_around_the_interval() {
+    const BEATS: usize = 30;
+
+    let addr = format!("127.0.0.1:{}", get_available_port());
+    let (gaps_tx, gaps_rx) = std_mpsc::channel();
+    let server_addr = addr.clone();
+    thread::spawn(move || {
+        let listener = TcpListener::bind(&server_addr).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        read_frame(&mut stream);
+        // The broker wants a heartbeat every 50 ms
+        stream
+            .write_all(b"CONNECTED\nversion:1.2\nheart-beat:0,50\n\n\0")
+            .unwrap();
+        let mut gaps = Vec::new();
+        let mut last = Instant::now();
+        let mut byte = [0u8; 1];
+        while gaps.len() < BEATS {
+            match stream.read(&mut byte) {
+                Ok(1) if byte[0] == b'\n' => {
+                    gaps.push(last.elapsed());
+                    last = Instant::now();
+                }
+                Ok(1) => {}
+                _ => break,
+            }
+        }
+        let _ = gaps_tx.send(gaps);
+    });
+    thread::sleep(Duration::from_millis(50));
+
+    let options = ConnectOptions::new().heartbeat_jitter(0.5);
+    let conn = Connection::connect_with_options(&addr, "guest", "guest", "50,0", options)
+        .await
+        .expect("connect failed");
+    let gaps =
+        tokio::task::spawn_blocking(move || gaps_rx.recv_timeout(Duration::from_secs(10)).unwrap())
+            .await
+            .unwrap();
+    assert_eq!(gaps.len(), BEATS);
+
+    // Each gap is within 50 ms ± 50%, allowing for scheduling delays. The
+    // first one also covers the end of the handshake.
+    let gaps = &gaps[1..];
+    for gap in gaps {
+        assert!(
+            *gap >= Duration::from_millis(20) && *gap <= Duration::from_millis(150),
+            "gap {:?} outside the jitter range",
+            gap
+        );
+    }
+    let min = gaps.iter().min().unwrap();
+    let max = gaps.iter().max().unwrap();
+    assert!(
+        *max - *min >= Duration::from_millis(15),
+        "heartbeats were not jittered: {:?}",
+        gaps
+    );
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn crlf_heartbeats_from_the_broker_keep_the_connection_alive() {
+    let (conn, outcome) =
+        watch_session_with(b"\r\n", Duration::from_millis(200), ConnectOptions::new()).await;
+    assert!(matches!(outcome, Outcome::Open), "{:?}", outcome);
+    let stats = conn.decode_stats();
+    assert!(stats.heartbeats >= 4, "{:?}", stats);
+    assert_eq!(stats.errors.total(), 0, "{:?}", stats);
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn crlf_heartbeats_option_sends_crlf() {
+    let addr = format!("127.0.0.1:{}", get_available_port());
+    let (bytes_tx, bytes_rx) = std_mpsc::channel();
+    let server_addr = addr.clone();
+    thread::spawn(move || {
+        let listener = TcpListener::bind(&server_addr).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        read_frame(&mut stream);
+        // The broker wants a heartbeat every 50 ms and sends none
+        stream
+            .write_all(b"CONNECTED\nversion:1.2\nheart-beat:0,50\n\n\0")
+            .unwrap();
+        let mut beats = [0u8; 6];
+        let _ = bytes_tx.send(stream.read_exact(&mut beats).map(|_| beats));
+    });
+    thread::sleep(Duration::from_millis(50));
+
+    let options = ConnectOptions::new().crlf_heartbeats();
+    let conn = Connection::connect_with_options(&addr, "guest", "guest", "50,0", options)
+        .await
+        .expect("connect failed");
+    let beats =
+        tokio::task::spawn_blocking(move || bytes_rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .await
+            .unwrap()
+            .expect("no heartbeats");
+    assert_eq!(&beats, b"\r\n\r\n\r\n");
+    conn.close().await;
+}
+
+#[test]
+fn grace_and_jitter_are_validated() {
+    assert_eq!(
+        ConnectOptions::new()
+            .heartbeat_grace_multiplier(0.9)
+            .validate(),
+        Err(ConfigError::InvalidHeartbeatGrace)
+    );
+    assert_eq!(
+        ConnectOptions::new()
+            .heartbeat_grace_multiplier(f64::NAN)
+            .validate(),
+        Err(ConfigError::InvalidHeartbeatGrace)
+    );
+    assert_eq!(
+        ConnectOptions::new()
+            .heartbeat_grace_multiplier(1e300)
+            .validate(),
+        Err(ConfigError::InvalidHeartbeatGrace)
+    );
+    assert!(
+        ConnectOptions::new()
+            .heartbeat_grace_multiplier(1.0)
+            .validate()
+            .is_ok()
+    );
+    assert_eq!(
+        ConnectOptions::new().heartbeat_jitter(-0.1).validate(),
+        Err(ConfigError::InvalidHeartbeatJitter)
+    );
+    assert_eq!(
+        ConnectOptions::new().heartbeat_jitter(0.6).validate(),
+        Err(ConfigError::InvalidHeartbeatJitter)
+    );
+    assert!(
+        ConnectOptions::new()
+            .heartbeat_jitter(0.5)
+            .validate()
+            .is_ok()
+    );
+}
@@ -0,0 +1,172 @@
+//! Regression tests for heartbeats and read boundaries.
+//!
+//! STOMP only allows heartbeats between frames. Frames split across reads
+//! at any byte, with heartbeats around them, must decode unchanged; an LF
+//! a broker slips into a frame where a read ended, making the frame
+//! malformed, must be reported as an interleaved heartbeat instead of as a
+//! malformed frame.
+
+use bytes::BytesMut;
+use iridium_stomp::Frame;
+use iridium_stomp::codec::{StompCodec, StompItem};
+use iridium_stomp::parser::parse_frame_slice;
+use tokio_util::codec::Decoder;
+
+const SAMPLE: &[u8] =
+    b"MESSAGE\ndestination:/queue/a\nmessage-id:7\nsubscription:0\n\nhello\nworld\0";
+
+const SAMPLE_WITH_LENGTH: &[u8] =
+    b"MESSAGE\ndestination:/queue/a\nmessage-id:7\ncontent-length:11\n\nhello\nworld\0";
+
+/// A body that looks like a header block of its own.
+const HEADER_LIKE_BODY: &[u8] =
+    b"MESSAGE\ndestination:/q\nmessage-id:1\n\nsubscription:abc\n\nbody\0";
+
+/// Feed `reads` to a fresh codec one after the other, decoding all it can
+/// after each, as `Framed` does.
+fn decode_reads(reads: &[&[u8]]) -> Result<Vec<StompItem>, std::io::Error> {
+    let mut codec = StompCodec::new();
+    let mut buf = BytesMut::new();
+    let mut items = Vec::new();
+    for read in reads {
+        buf.extend_from_slice(read);
+        while let Some(item) = codec.decode(&mut buf)? {
+            items.push(item);
+        }
+    }
+    Ok(items)
+}
+
+fn frames(items: &[StompItem]) -> Vec<&Frame> {
+    items.iter().filter_map(StompItem::frame).collect()
+}
+
+fn heartbeats(items: &[StompItem]) -> usize {
+    items
+        .iter()
+        .filter(|item| matches!(item, StompItem::Heartbeat))
+        .count()
+}
+
+#[test]
+fn split_at_every_offset_decodes_the_same_frame() {
+    for sample in [SAMPLE, SAMPLE_WITH_LENGTH, HEADER_LIKE_BODY] {
+        let expected = decode_reads(&[sample]).unwrap();
+        for at in 1..sample.len() {
+            let items = decode_reads(&[&sample[..at], &sample[at..]])
+                .unwrap_or_else(|e| panic!("split at {}: {}", at, e));
+            assert_eq!(items, expected, "split at {}", at);
+        }
+    }
+}
+
+#[test]
+fn heartbeats_between_split_frames_are_skipped() {
+    let mut stream = Vec::new();
+    stream.extend_from_slice(b"\n");
+    stream.extend_from_slice(SAMPLE);
+    stream.extend_from_slice(b"\n\r\n");
+    stream.extend_from_slice(SAMPLE_WITH_LENGTH);
+    stream.extend_from_slice(b"\n");
+    for at in 1..stream.len() {
+        let items = decode_reads(&[&stream[..at], &stream[at..]])
+            .unwrap_or_else(|e| panic!("split at {}: {}", at, e));
+        // Whether an LF right after a NUL ends the frame or is a heartbeat
+        // depends on the split, so only the frames are compared
+        let frames = frames(&items);
+        assert_eq!(frames.len(), 2, "split at {}", at);
+        assert_eq!(frames[0].body, b"hello\nworld", "split at {}", at);
+        assert_eq!(frames[1].body, b"hello\nworld", "split at {}", at);
+    }
+}
+
+#[test]
+fn byte_at_a_time_decodes_the_same_frames() {
+    let mut stream = Vec::new();
+    stream.extend_from_slice(SAMPLE);
+    stream.extend_from_slice(b"\n");
+    stream.extend_from_slice(SAMPLE_WITH_LENGTH);
+    let reads: Vec<&[u8]> = stream.chunks(1).collect();
+    let items = decode_reads(&reads).unwrap();
+    assert_eq!(frames(&items).len(), 2);
+    assert_eq!(heartbeats(&items), 1);
+}
+
+#[test]
+fn heartbeat_inside_the_head_is_reported() {
+    let head_end = SAMPLE.windows(2).position(|w| w == b"\n\n").unwrap();
+    for sample in [SAMPLE, SAMPLE_WITH_LENGTH] {
+        // Offsets up to the LF ending the last header. An LF next to a line
+        // break makes a blank line that ends the head early and leaves a
+        // valid frame, which is decoded as it arrived
+        let mid_line = |&at: &usize| sample[at - 1] != b'\n' && sample[at] != b'\n';
+        for at in (1..head_end).filter(mid_line) {
+            let result = decode_reads(&[&sample[..at], b"\n", &sample[at..]]);
+            let error = result.expect_err(&format!("LF at {} not detected", at));
+            assert!(
+                error.to_string().contains("heartbeat inside a frame"),
+                "LF at {}: {}",
+                at,
+                error
+            );
+        }
+    }
+}
+
+#[test]
+fn heartbeat_inside_a_content_length_body_is_reported() {
+    let body_start = SAMPLE_WITH_LENGTH.len() - "hello\nworld\0".len();
+    for at in body_start + 1..SAMPLE_WITH_LENGTH.len() - 1 {
+        let reads: [&[u8]; 3] = [&SAMPLE_WITH_LENGTH[..at], b"\n", &SAMPLE_WITH_LENGTH[at..]];
+        let error = decode_reads(&reads).expect_err(&format!("LF at {} not detected", at));
+        assert!(
+            error.to_string().contains("heartbeat inside a frame"),
+            "LF at {}: {}",
+            at,
+            error
+        );
+    }
+}
+
+#[test]
+fn header_like_body_split_before_the_blank_line() {
+    let at = HEADER_LIKE_BODY
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .unwrap()
+        + 1;
+    let items = decode_reads(&[&HEADER_LIKE_BODY[..at], &HEADER_LIKE_BODY[at..]]).unwrap();
+    let frames = frames(&items);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].get_header("subscription"), None);
+    assert_eq!(frames[0].body, b"subscription:abc\n\nbody");
+}
+
+#[test]
+fn interleaved_heartbeats_are_counted_as_errors() {
+    let mut codec = StompCodec::new();
+    let mut buf = BytesMut::from(&SAMPLE[..4]);
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    buf.extend_from_slice(b"\n");
+    buf.extend_from_slice(&SAMPLE[4..]);
+    assert!(codec.decode(&mut buf).is_err());
+    let errors = codec.decode_stats().errors;
+    assert_eq!(errors.interleaved_heartbeat, 1);
+    assert_eq!(errors.malformed_header, 0);
+}
+
+#[test]
+fn a_malformed_frame_is_not_blamed_on_a_heartbeat() {
+    let error = decode_reads(&[b"MESSAGE\n", b"no-colon\n\n\0"]).unwrap_err();
+    assert!(!error.to_string().contains("heartbeat"), "{}", error);
+}
+
+#[test]
+fn parse_frame_slice_skips_lf_and_crlf_heartbeats() {
+    let input = b"\n\r\n\nSEND\ndestination:/q\n\nbody\0";
+    let (command, headers, body, consumed) = parse_frame_slice(input).unwrap().unwrap();
+    assert_eq!(command, b"SEND");
+    assert_eq!(headers.len(), 1);
+    assert_eq!(body.as_deref(), Some(&b"body"[..]));
+    assert_eq!(consumed, input.len());
+}
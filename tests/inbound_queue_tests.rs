@@ -0,0 +1,54 @@
+//! Tests for `Connection::inbound_len()` and `Connection::peek_frame()`.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{Connection, Frame, ReceivedFrame};
+use std::time::Duration;
+
+fn message_id(received: Option<ReceivedFrame>) -> String {
+    match received {
+        Some(ReceivedFrame::Frame(f)) => f.get_header("message-id").unwrap_or("").to_string(),
+        other => panic!("expected MESSAGE frame, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn peek_does_not_consume_and_len_tracks_queue() {
+    let mut session = Session::new().connected();
+    for id in ["m1", "m2", "m3"] {
+        session = session.send(
+            Frame::new("MESSAGE")
+                .header("destination", "/queue/a")
+                .header("message-id", id)
+                .header("subscription", "9")
+                .set_body("hi"),
+        );
+    }
+    let broker = MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    assert_eq!(conn.inbound_capacity(), 32);
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while conn.inbound_len() < 3 {
+        assert!(tokio::time::Instant::now() < deadline, "frames not queued");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Peeking returns the head of the queue and leaves it there
+    assert_eq!(message_id(conn.peek_frame().await), "m1");
+    assert_eq!(message_id(conn.peek_frame().await), "m1");
+    assert_eq!(conn.inbound_len(), 3);
+
+    assert_eq!(message_id(conn.next_frame().await), "m1");
+    assert_eq!(conn.inbound_len(), 2);
+    assert_eq!(message_id(conn.next_frame().await), "m2");
+    assert_eq!(message_id(conn.peek_frame().await), "m3");
+    assert_eq!(message_id(conn.next_frame().await), "m3");
+    assert_eq!(conn.inbound_len(), 0);
+
+    conn.close().await;
+}
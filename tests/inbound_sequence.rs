@@ -0,0 +1,140 @@
+//! Tests for `Frame::seq()` / `Message::seq()`.
+//!
+//! A mock broker interleaves messages for two subscriptions. Each
+//! subscription must see strictly increasing sequence numbers, and
+//! together they must cover every number without reordering in the
+//! dispatch path.
+
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{ConnectOptions, Connection, Frame, Message};
+use std::time::Duration;
+
+/// Wait for `subscriptions` SUBSCRIBE frames, then have `broker` send
+/// `count` messages `m0`, `m1`, ... to them in turn.
+async fn deliver_in_turn(broker: &MockBroker, subscriptions: usize, count: usize) {
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", subscriptions, Duration::from_secs(2))
+            .await
+    );
+    let subs = broker.received_commands("SUBSCRIBE");
+    for i in 0..count {
+        let sub = &subs[i % subs.len()];
+        broker.send(
+            Frame::new("MESSAGE")
+                .header("destination", sub.get_header("destination").unwrap())
+                .header("message-id", format!("m{}", i))
+                .header("subscription", sub.get_header("id").unwrap())
+                .set_body(format!("body {}", i)),
+        );
+    }
+}
+
+fn message_number(frame: &Frame) -> usize {
+    frame.get_header("message-id").unwrap()[1..]
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn subscriptions_receive_frames_in_arrival_order() {
+    const COUNT: usize = 200;
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    // The test never reads `next_frame()`
+    let options = ConnectOptions::default().best_effort_mirror(true);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    let mut subs = Vec::new();
+    for dest in ["/queue/a", "/queue/b"] {
+        let sub = conn
+            .subscription(dest)
+            .ordered(true)
+            .start()
+            .await
+            .expect("subscribe failed");
+        subs.push(sub);
+    }
+    deliver_in_turn(&broker, 2, COUNT).await;
+
+    let mut readers = Vec::new();
+    for mut sub in subs {
+        readers.push(tokio::spawn(async move {
+            let mut frames = Vec::new();
+            for _ in 0..COUNT / 2 {
+                let frame = sub
+                    .recv_timeout(Duration::from_secs(5))
+                    .await
+                    .expect("message not delivered");
+                frames.push(frame);
+            }
+            frames
+        }));
+    }
+    let mut all = Vec::new();
+    for reader in readers {
+        let frames = reader.await.unwrap();
+        let seqs: Vec<u64> = frames
+            .iter()
+            .map(|f| f.seq().expect("not numbered"))
+            .collect();
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]), "{:?}", seqs);
+        let numbers: Vec<usize> = frames.iter().map(message_number).collect();
+        assert!(numbers.windows(2).all(|w| w[0] < w[1]), "{:?}", numbers);
+        all.extend(frames);
+    }
+
+    // Merging by sequence number restores the order the broker sent in,
+    // and no number is skipped or repeated
+    let mut merged: Vec<Message> = all.into_iter().map(Message::from).collect();
+    merged.sort_by_key(|m| m.seq());
+    let first = merged[0].seq().unwrap();
+    for (i, message) in merged.iter().enumerate() {
+        assert_eq!(message.seq(), Some(first + i as u64));
+        assert_eq!(message_number(message.frame()), i);
+    }
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn shared_workers_can_restore_arrival_order() {
+    const COUNT: usize = 60;
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    // The test never reads `next_frame()`
+    let options = ConnectOptions::default().best_effort_mirror(true);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    let sub = conn
+        .subscription("/queue/jobs")
+        .ordered(true)
+        .start()
+        .await
+        .expect("subscribe failed");
+    deliver_in_turn(&broker, 1, COUNT).await;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(COUNT);
+    for mut worker in sub.into_shared(3) {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while let Some(frame) = worker.recv().await {
+                let _ = tx.send(Message::from(frame)).await;
+            }
+        });
+    }
+    let mut done = Vec::new();
+    while done.len() < COUNT {
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("message not delivered")
+            .unwrap();
+        done.push(message);
+    }
+
+    done.sort_by_key(|m| m.seq());
+    let numbers: Vec<usize> = done.iter().map(|m| message_number(m.frame())).collect();
+    assert_eq!(numbers, (0..COUNT).collect::<Vec<_>>());
+    conn.close().await;
+}
@@ -0,0 +1,136 @@
+//! Tests for `ConnectOptions::max_inflight_sends()`.
+//!
+//! The mock broker withholds RECEIPT frames until the test sends them, so
+//! the tests can hold sends in flight.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{ConfigError, ConnError, ConnectOptions, Connection, Frame, assert_frame};
+use std::time::Duration;
+
+/// Start a broker that answers receipts only when the test confirms them.
+async fn start_broker() -> MockBroker {
+    MockBroker::start(Script::new().session(Session::new().connected().withhold_receipts()))
+        .await
+        .unwrap()
+}
+
+/// Confirm the receipt requested by `frame`.
+fn confirm(broker: &MockBroker, frame: &Frame) {
+    let receipt = frame.get_header("receipt").expect("no receipt header");
+    broker.send(Frame::new("RECEIPT").header("receipt-id", receipt));
+}
+
+fn order(n: u32) -> Frame {
+    Frame::new("SEND")
+        .header("destination", "/queue/orders")
+        .set_body(format!("order {}", n))
+}
+
+#[tokio::test]
+async fn receipts_hold_permits_until_confirmed() {
+    let broker = start_broker().await;
+
+    let options = ConnectOptions::new().max_inflight_sends(2);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let publisher = {
+        let conn = conn.clone();
+        tokio::spawn(async move {
+            for n in 1..=3 {
+                conn.send_frame_with_receipt(order(n)).await.unwrap();
+            }
+        })
+    };
+
+    assert!(broker.wait_for("SEND", 2, Duration::from_secs(2)).await);
+
+    // The third send waits for a permit
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        broker.received_commands("SEND").len(),
+        2,
+        "third SEND written while two receipts were outstanding"
+    );
+    assert_eq!(conn.inflight_sends(), 2);
+    assert!(!publisher.is_finished());
+
+    confirm(&broker, &broker.received_commands("SEND")[0]);
+    assert!(broker.wait_for("SEND", 3, Duration::from_secs(2)).await);
+    let sends = broker.received_commands("SEND");
+    assert_frame!(sends[2], FrameMatcher::command("SEND").body("order 3"));
+    publisher.await.unwrap();
+
+    for frame in &sends[1..] {
+        confirm(&broker, frame);
+    }
+    tokio::time::timeout(Duration::from_secs(2), conn.drain_sends())
+        .await
+        .expect("drain did not finish")
+        .unwrap();
+    assert_eq!(conn.inflight_sends(), 0);
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn sends_without_receipt_release_on_flush() {
+    let broker = start_broker().await;
+
+    let options = ConnectOptions::new().max_inflight_sends(1);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    for n in 1..=5 {
+        tokio::time::timeout(Duration::from_secs(2), conn.send_frame(order(n)))
+            .await
+            .expect("send waited for a permit that was never released")
+            .unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(2), conn.drain_sends())
+        .await
+        .expect("drain did not finish")
+        .unwrap();
+    assert_eq!(conn.inflight_sends(), 0);
+    assert!(broker.wait_for("SEND", 5, Duration::from_secs(2)).await);
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn close_wakes_sends_waiting_for_a_permit() {
+    let broker = start_broker().await;
+
+    let options = ConnectOptions::new().max_inflight_sends(1);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    conn.send_frame_with_receipt(order(1)).await.unwrap();
+    let waiting = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.send_frame(order(2)).await })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!waiting.is_finished());
+
+    conn.close().await;
+    let result = tokio::time::timeout(Duration::from_secs(2), waiting)
+        .await
+        .expect("waiting send was not woken")
+        .unwrap();
+    assert!(matches!(result, Err(ConnError::ChannelClosed)));
+}
+
+#[test]
+fn zero_inflight_sends_is_rejected() {
+    assert_eq!(
+        ConnectOptions::new().max_inflight_sends(0).validate(),
+        Err(ConfigError::ZeroInflightSends)
+    );
+}
@@ -0,0 +1,95 @@
+//! Tests for the broker-specific metadata accessors on `Message`.
+
+use iridium_stomp::{BrokerProfile, Expiration, Frame, Message};
+use std::time::{Duration, UNIX_EPOCH};
+
+fn message(headers: &[(&str, &str)]) -> Message {
+    let mut frame = Frame::new("MESSAGE")
+        .header("destination", "/queue/orders")
+        .header("message-id", "m1");
+    for (name, value) in headers {
+        frame = frame.header(*name, *value);
+    }
+    Message::from_frame(frame)
+}
+
+#[test]
+fn redelivered_per_broker() {
+    let rabbit = message(&[("redelivered", "true")]);
+    assert_eq!(rabbit.redelivered(BrokerProfile::RabbitMq), Some(true));
+    assert_eq!(rabbit.redelivered(BrokerProfile::Artemis), Some(true));
+    assert_eq!(rabbit.redelivered(BrokerProfile::ActiveMq), None);
+
+    let activemq = message(&[("redeliveryCounter", "2")]);
+    assert_eq!(activemq.redelivered(BrokerProfile::ActiveMq), Some(true));
+    assert_eq!(activemq.redelivered(BrokerProfile::Generic), Some(true));
+    assert_eq!(activemq.redelivered(BrokerProfile::RabbitMq), None);
+
+    let first = message(&[("redeliveryCounter", "0")]);
+    assert_eq!(first.redelivered(BrokerProfile::ActiveMq), Some(false));
+
+    assert_eq!(message(&[]).redelivered(BrokerProfile::Generic), None);
+}
+
+#[test]
+fn expiration_per_broker() {
+    let rabbit = message(&[("expiration", "60000")]);
+    assert_eq!(
+        rabbit.expiration(BrokerProfile::RabbitMq),
+        Some(Expiration::After(Duration::from_secs(60)))
+    );
+    assert_eq!(rabbit.expiration(BrokerProfile::ActiveMq), None);
+
+    let activemq = message(&[("expires", "1700000000000")]);
+    let at = Some(Expiration::At(
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+    ));
+    assert_eq!(activemq.expiration(BrokerProfile::ActiveMq), at);
+    assert_eq!(activemq.expiration(BrokerProfile::Artemis), at);
+    assert_eq!(activemq.expiration(BrokerProfile::Generic), at);
+
+    let never = message(&[("expires", "0")]);
+    assert_eq!(never.expiration(BrokerProfile::ActiveMq), None);
+}
+
+#[test]
+fn persistent_and_priority() {
+    let msg = message(&[("persistent", "true"), ("priority", "7")]);
+    assert_eq!(msg.persistent(), Some(true));
+    assert_eq!(msg.priority(), Some(7));
+
+    let msg = message(&[("persistent", "yes"), ("priority", "high")]);
+    assert_eq!(msg.persistent(), None);
+    assert_eq!(msg.priority(), None);
+}
+
+#[test]
+fn original_destination_of_dead_letters() {
+    let rabbit = message(&[("x-first-death-queue", "orders")]);
+    assert_eq!(
+        rabbit.original_destination(BrokerProfile::RabbitMq),
+        Some("orders")
+    );
+    assert_eq!(rabbit.original_destination(BrokerProfile::ActiveMq), None);
+
+    let activemq = message(&[("original-destination", "/queue/orders")]);
+    assert_eq!(
+        activemq.original_destination(BrokerProfile::ActiveMq),
+        Some("/queue/orders")
+    );
+
+    let artemis = message(&[("_AMQ_ORIG_ADDRESS", "orders")]);
+    assert_eq!(
+        artemis.original_destination(BrokerProfile::Artemis),
+        Some("orders")
+    );
+    assert_eq!(
+        artemis.original_destination(BrokerProfile::Generic),
+        Some("orders")
+    );
+
+    assert_eq!(
+        message(&[]).original_destination(BrokerProfile::Generic),
+        None
+    );
+}
@@ -0,0 +1,64 @@
+//! Tests for the inbound message sampling hook.
+//!
+//! A mock broker pushes MESSAGE frames right after the handshake and the
+//! tests check which of them reach the sampling callback.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, Frame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Start a broker that sends `count` MESSAGE frames with message ids
+/// 1..=count right after the handshake.
+async fn start_publishing_broker(count: usize) -> MockBroker {
+    let mut session = Session::new().connected();
+    for i in 1..=count {
+        session = session.send(
+            Frame::new("MESSAGE")
+                .header("destination", "/queue/audit")
+                .header("message-id", i.to_string())
+                .header("subscription", "1")
+                .set_body(format!("payload-{}", i)),
+        );
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+async fn collect_samples(rate: f64, published: usize) -> Vec<String> {
+    let broker = start_publishing_broker(published).await;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let options = ConnectOptions::default().on_message_sample(rate, move |frame| {
+        let _ = tx.send(
+            frame
+                .get_header("message-id")
+                .unwrap_or_default()
+                .to_string(),
+        );
+    });
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let mut samples = Vec::new();
+    while let Ok(Some(id)) = tokio::time::timeout(Duration::from_millis(500), rx.recv()).await {
+        samples.push(id);
+    }
+    conn.close().await;
+    samples
+}
+
+#[tokio::test]
+async fn samples_configured_fraction_of_messages() {
+    let samples = collect_samples(0.5, 10).await;
+    assert_eq!(samples, vec!["2", "4", "6", "8", "10"]);
+}
+
+#[tokio::test]
+async fn zero_rate_samples_nothing() {
+    let samples = collect_samples(0.0, 10).await;
+    assert!(samples.is_empty(), "unexpected samples: {:?}", samples);
+}
@@ -37,7 +37,7 @@ fn minimized_replay_should_decode_all_frames() {
                     decoded += 1;
                     bodies.push(f.body);
                 }
-                Ok(Some(StompItem::Heartbeat)) => {
+                Ok(Some(_)) => {
                     eprintln!("decoded heartbeat");
                 }
                 Ok(None) => {
@@ -53,7 +53,7 @@ fn minimized_replay_should_decode_all_frames() {
     loop {
         match dec.decode(&mut buf) {
             Ok(Some(StompItem::Frame(_))) => decoded += 1,
-            Ok(Some(StompItem::Heartbeat)) => {}
+            Ok(Some(_)) => {}
             Ok(None) => break,
             Err(e) => panic!("decoder returned error during drain: {}", e),
         }
@@ -0,0 +1,264 @@
+//! Tests for the scriptable `testing::MockBroker`, exercising the
+//! connection's failure handling through it: reconnect with resubscribe,
+//! receipt timeouts, rejected handshakes, and delayed handshakes.
+
+use iridium_stomp::connection::AckMode;
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{ConnError, Connection, Frame, assert_frame};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn reconnect_restores_subscription() {
+    let broker = MockBroker::start(
+        Script::new()
+            .session(Session::new().connected().deliver(2).close())
+            .session(Session::new().connected().expect("SUBSCRIBE").deliver(1)),
+    )
+    .await
+    .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let mut sub = conn
+        .subscribe("/queue/jobs", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+
+    let mut bodies = Vec::new();
+    for _ in 0..3 {
+        let frame = tokio::time::timeout(Duration::from_secs(10), sub.recv())
+            .await
+            .expect("timed out waiting for a message")
+            .expect("subscription closed");
+        bodies.push(String::from_utf8(frame.body).unwrap());
+    }
+    // Message ids continue per session, so the reconnect restarts at 1
+    assert_eq!(bodies, vec!["mock-1", "mock-2", "mock-1"]);
+    assert_eq!(broker.connections(), 2);
+    let subscribes = broker.received_commands("SUBSCRIBE");
+    assert_eq!(subscribes.len(), 2);
+    for subscribe in &subscribes {
+        assert_frame!(subscribe, "SUBSCRIBE", "destination" => "/queue/jobs", "ack" => "auto", "id" => sub.id());
+    }
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn withheld_receipt_times_out() {
+    let broker =
+        MockBroker::start(Script::new().session(Session::new().connected().withhold_receipts()))
+            .await
+            .unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let frame = Frame::new("SEND").header("destination", "/queue/a");
+    let result = conn
+        .send_frame_confirmed(frame, Duration::from_millis(200))
+        .await;
+    assert!(matches!(result, Err(ConnError::ReceiptTimeout(_))));
+    assert!(broker.wait_for("SEND", 1, Duration::from_secs(1)).await);
+    assert_frame!(
+        broker.received_commands("SEND")[0],
+        FrameMatcher::command("SEND")
+            .header("destination", "/queue/a")
+            .header_matches("receipt", r"^rcpt-\d+$")
+    );
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn receipts_are_answered_by_default() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let frame = Frame::new("SEND").header("destination", "/queue/a");
+    conn.send_frame_confirmed(frame, Duration::from_secs(2))
+        .await
+        .expect("receipt not confirmed");
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn rejected_handshake_fails_connect() {
+    let broker = MockBroker::start(Script::new().session(Session::new().reject("bad credentials")))
+        .await
+        .unwrap();
+
+    match Connection::connect(&broker.address(), "guest", "wrong", "0,0").await {
+        Err(ConnError::ServerRejected(err)) => assert_eq!(err.message, "bad credentials"),
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("connect should have failed"),
+    }
+}
+
+#[tokio::test]
+async fn delayed_handshake_and_error_after_messages() {
+    let broker = MockBroker::start(
+        Script::new().session(
+            Session::new()
+                .connected_after(Duration::from_millis(300))
+                .deliver(1)
+                .error("queue deleted"),
+        ),
+    )
+    .await
+    .unwrap();
+
+    let started = Instant::now();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    let mut sub = conn
+        .subscribe("/queue/a", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+    assert!(sub.recv().await.is_some());
+    let error = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match conn.next_frame().await {
+                Some(iridium_stomp::ReceivedFrame::Error(err)) => return err,
+                Some(_) => continue,
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .expect("no ERROR received");
+    assert_eq!(error.message, "queue deleted");
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn drop_after_frames_forces_reconnect() {
+    let broker = MockBroker::start(
+        Script::new()
+            .session(Session::new().connected().drop_after_frames(2))
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    conn.send("/queue/a", "one").await.unwrap();
+    conn.send("/queue/a", "two").await.unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while broker.connections() < 2 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(broker.connections(), 2);
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn header_policy_rewrites_and_refuses_header_names() {
+    use iridium_stomp::{ConnectOptions, HeaderPolicy};
+
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    let options = ConnectOptions::default().header_policy(HeaderPolicy::strict());
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let bad = Frame::new("SEND")
+        .header("destination", "/queue/a")
+        .header("x\0id", "1");
+    assert!(matches!(
+        conn.send_frame(bad).await,
+        Err(ConnError::Protocol(_))
+    ));
+
+    let frame = Frame::new("SEND")
+        .header("Destination", "/queue/a")
+        .header("destination", "/queue/b")
+        .header("X-Tag", "keep");
+    conn.send_frame_confirmed(frame, Duration::from_secs(2))
+        .await
+        .expect("send failed");
+    let sends = broker.received_commands("SEND");
+    assert_eq!(sends.len(), 1);
+    let names: Vec<&str> = sends[0].headers.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(names, ["destination", "x-tag", "receipt"]);
+    assert_eq!(sends[0].get_header("destination"), Some("/queue/a"));
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn test_driven_actions_reach_the_client() {
+    let broker = MockBroker::start(
+        Script::new()
+            .session(
+                Session::new().connected_with(
+                    Frame::new("CONNECTED")
+                        .header("version", "1.2")
+                        .header("server", "custom/1.0"),
+                ),
+            )
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    assert_eq!(
+        conn.server_info().await.server.as_deref(),
+        Some("custom/1.0")
+    );
+    let mut sub = conn
+        .subscribe("/queue/a", AckMode::Client)
+        .await
+        .expect("subscribe failed");
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", 1, Duration::from_secs(2))
+            .await
+    );
+
+    broker.deliver(
+        Frame::new("MESSAGE")
+            .header("message-id", "m1")
+            .set_body("one"),
+    );
+    // A heartbeat, then a whole frame written as-is
+    broker.send_raw("\n");
+    broker.send_raw(format!(
+        "MESSAGE\ndestination:/queue/a\nmessage-id:m2\nsubscription:{}\n\ntwo\0",
+        sub.id()
+    ));
+    for expected in ["one", "two"] {
+        let frame = sub.recv_timeout(Duration::from_secs(2)).await.unwrap();
+        assert_eq!(frame.body, expected.as_bytes());
+    }
+    sub.ack("m2").await.unwrap();
+    let ack = broker
+        .wait_for_match(
+            &FrameMatcher::command("ACK").header("id", "m2"),
+            Duration::from_secs(2),
+        )
+        .await;
+    assert!(ack.is_some(), "ACK not received");
+
+    broker.disconnect();
+    assert!(broker.wait_for("CONNECT", 2, Duration::from_secs(10)).await);
+    assert_eq!(broker.connections(), 2);
+    assert_eq!(broker.open_connections(), 1);
+
+    conn.close().await;
+}
@@ -0,0 +1,113 @@
+//! Tests for `Connection::monitor()`.
+//!
+//! The mock broker sends heartbeats every 100 ms, falls silent, resumes,
+//! and finally drops the connection, accepting a second session. The
+//! monitor must report each change of health and never send anything but
+//! CONNECT frames and heartbeats.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{
+    ConfigError, ConnError, ConnectOptions, Connection, Frame, Health, Heartbeat, MonitorOptions,
+    assert_frame,
+};
+use std::time::Duration;
+
+fn connected() -> Frame {
+    Frame::new("CONNECTED")
+        .header("version", "1.2")
+        .header("server", "mock/1.0")
+        .header("heart-beat", "100,0")
+}
+
+fn heartbeats(mut session: Session, count: usize) -> Session {
+    for _ in 0..count {
+        session = session.send_raw("\n").wait(Duration::from_millis(100));
+    }
+    session
+}
+
+/// Start a broker that sends heartbeats, pauses for `silence`, sends more,
+/// then ends the session and serves a second one that keeps sending
+/// heartbeats until the client leaves.
+async fn start_broker(silence: Duration) -> MockBroker {
+    let first = heartbeats(Session::new().connected_with(connected()), 5).wait(silence);
+    let first = heartbeats(first, 5).close();
+    let second = heartbeats(Session::new().connected_with(connected()), 600);
+    MockBroker::start(Script::new().session(first).session(second))
+        .await
+        .unwrap()
+}
+
+async fn next_health(monitor: &mut iridium_stomp::Monitor) -> iridium_stomp::MonitorStatus {
+    tokio::time::timeout(Duration::from_secs(5), monitor.changed())
+        .await
+        .expect("no change reported")
+        .expect("monitor closed")
+}
+
+#[tokio::test]
+async fn monitor_reports_late_down_and_up() {
+    let broker = start_broker(Duration::from_millis(400)).await;
+    let options = MonitorOptions {
+        heartbeat: Heartbeat::new(0, 100),
+        // Stay connected through the silence; only `Late` is expected
+        connect: ConnectOptions::default().heartbeat_grace_multiplier(10.0),
+        ..Default::default()
+    };
+    let mut monitor = Connection::monitor(&broker.address(), options)
+        .await
+        .expect("connect failed");
+
+    let status = monitor.status();
+    assert_eq!(status.health, Health::Up);
+    assert_eq!(status.heartbeat_interval, Some(Duration::from_millis(100)));
+    assert_eq!(status.server.server.as_deref(), Some("mock/1.0"));
+    assert_eq!(status.reconnects, 0);
+
+    let late = next_health(&mut monitor).await;
+    assert_eq!(late.health, Health::Late);
+    assert!(late.silent_for > Duration::from_millis(150), "{:?}", late);
+    assert!(late.heartbeats >= 4, "{:?}", late);
+    assert_eq!(next_health(&mut monitor).await.health, Health::Up);
+
+    // The broker falls silent during the backoff before the first
+    // reconnect attempt, so it may be reported late before it is down
+    let mut down = next_health(&mut monitor).await;
+    if down.health == Health::Late {
+        down = next_health(&mut monitor).await;
+    }
+    assert_eq!(down.health, Health::Down);
+    let back = next_health(&mut monitor).await;
+    assert_eq!(back.health, Health::Up);
+    assert_eq!(back.reconnects, 1);
+    assert_eq!(monitor.status().health, Health::Up);
+    monitor.close().await;
+
+    let sent = broker.received();
+    let commands: Vec<&str> = sent.iter().map(|f| f.command.as_str()).collect();
+    assert_eq!(commands, ["CONNECT", "CONNECT"]);
+    assert_frame!(
+        sent[0],
+        FrameMatcher::command("CONNECT").lacks_header("login")
+    );
+}
+
+#[tokio::test]
+async fn monitor_rejects_out_of_range_late_after() {
+    for late_after in [0.5, 1e300, f64::NAN] {
+        let options = MonitorOptions {
+            late_after: Some(late_after),
+            ..Default::default()
+        };
+        // Rejected before any address is contacted
+        let result = Connection::monitor("127.0.0.1:1", options).await;
+        assert!(
+            matches!(
+                result,
+                Err(ConnError::Config(ConfigError::InvalidLateAfter))
+            ),
+            "late_after {} accepted",
+            late_after
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Tests for `SubscriptionOptions::ordered`.
+//!
+//! A mock broker floods a subscription with more messages than its channel
+//! holds while the consumer is not reading. An ordered subscription must
+//! still deliver every message, in order.
+
+use iridium_stomp::connection::AckMode;
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, Frame, SubscriptionOptions};
+use std::time::Duration;
+
+/// Start a broker that waits for a SUBSCRIBE and sends `count` MESSAGE
+/// frames to it.
+async fn start_flooding_broker(count: usize) -> MockBroker {
+    let mut session = Session::new().connected();
+    for i in 0..count {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", i.to_string())
+                .set_body(i.to_string()),
+        );
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn ordered_subscription_delivers_every_message_in_order() {
+    const COUNT: usize = 200;
+
+    let broker = start_flooding_broker(COUNT).await;
+
+    // Only the subscription is read, not `next_frame()`
+    let options = ConnectOptions::default().best_effort_mirror(true);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    let options = SubscriptionOptions::default().ordered(true);
+    let mut sub = conn
+        .subscribe_with_options("/queue/ordered", AckMode::Auto, options)
+        .await
+        .expect("subscribe failed");
+
+    // Let the broker overrun the subscription channel before reading
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut received = Vec::new();
+    while received.len() < COUNT {
+        match sub.recv_timeout(Duration::from_secs(2)).await {
+            Ok(frame) => received.push(String::from_utf8(frame.body).unwrap()),
+            Err(e) => panic!("after {} messages: {}", received.len(), e),
+        }
+    }
+    let expected: Vec<String> = (0..COUNT).map(|i| i.to_string()).collect();
+    assert_eq!(received, expected);
+
+    conn.close().await;
+}
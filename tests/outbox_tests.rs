@@ -0,0 +1,147 @@
+//! Tests for `ConnectOptions::outbox()` and `Connection::send_durable()`.
+//!
+//! The mock broker answers the receipt of every SEND frame, except that it
+//! can hang up on the first SEND instead, so the entry has to be sent again
+//! on the next session.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnError, ConnectOptions, Connection, Frame, Outbox};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+fn temp_outbox(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "iridium-outbox-test-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Start a broker that answers receipts. If `drop_first_send` is set, the
+/// first SEND ends its session without a receipt.
+async fn start_broker(drop_first_send: bool) -> MockBroker {
+    let mut script = Script::new();
+    if drop_first_send {
+        script = script.session(
+            Session::new()
+                .connected()
+                .withhold_receipts()
+                .expect("SEND")
+                .close(),
+        );
+    }
+    MockBroker::start(script.session(Session::new().connected()))
+        .await
+        .unwrap()
+}
+
+/// The bodies of the SEND frames `broker` received.
+fn send_bodies(broker: &MockBroker) -> Vec<String> {
+    broker
+        .received_commands("SEND")
+        .into_iter()
+        .map(|f| String::from_utf8(f.body).unwrap())
+        .collect()
+}
+
+/// Wait until `outbox` is empty, or panic after `limit`.
+async fn wait_until_empty(outbox: &Outbox, limit: Duration) {
+    let start = Instant::now();
+    while !outbox.is_empty() {
+        assert!(
+            start.elapsed() < limit,
+            "outbox not drained: {:?}",
+            outbox.backlog()
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn leftover_and_new_entries_are_sent_and_removed() {
+    let path = temp_outbox("drain");
+    // An entry left over from a previous run
+    let outbox = Outbox::open(&path).unwrap();
+    outbox
+        .push(
+            Frame::new("SEND")
+                .header("destination", "/queue/orders")
+                .set_body(b"old".to_vec()),
+        )
+        .unwrap();
+    drop(outbox);
+
+    let broker = start_broker(false).await;
+    let outbox = Outbox::open(&path).unwrap();
+    assert_eq!(outbox.len(), 1);
+    let options = ConnectOptions::default().outbox(outbox.clone());
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    wait_until_empty(&outbox, Duration::from_secs(2)).await;
+
+    let id = conn
+        .send_durable(
+            Frame::new("SEND")
+                .header("destination", "/queue/orders")
+                .set_body(b"new".to_vec()),
+        )
+        .await
+        .unwrap();
+    assert!(id > 1);
+    wait_until_empty(&outbox, Duration::from_secs(2)).await;
+    conn.close().await;
+
+    assert_eq!(send_bodies(&broker), vec!["old", "new"]);
+    // Nothing is left for the next run
+    assert!(Outbox::open(&path).unwrap().is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn unconfirmed_entry_is_sent_again_after_reconnecting() {
+    let path = temp_outbox("resend");
+    let broker = start_broker(true).await;
+    let outbox = Outbox::open(&path).unwrap();
+    let options = ConnectOptions::default().outbox(outbox.clone());
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    conn.send_durable(
+        Frame::new("SEND")
+            .header("destination", "/queue/orders")
+            .set_body(b"once".to_vec()),
+    )
+    .await
+    .unwrap();
+    // The reconnect backoff after a short session is two seconds
+    wait_until_empty(&outbox, Duration::from_secs(5)).await;
+    conn.close().await;
+
+    assert_eq!(send_bodies(&broker), vec!["once", "once"]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn send_durable_requires_an_outbox() {
+    let broker = start_broker(false).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let result = conn
+        .send_durable(Frame::new("SEND").header("destination", "/queue/orders"))
+        .await;
+    assert!(
+        matches!(result, Err(ConnError::Protocol(_))),
+        "{:?}",
+        result
+    );
+    conn.close().await;
+}
@@ -238,6 +238,23 @@ fn parse_body_with_trailing_lf() {
     assert_eq!(result.3, raw.len());
 }
 
+#[test]
+fn parse_body_with_trailing_crlf() {
+    let raw = b"SEND\n\nhello\0\r\nMESSAGE\n\n\0";
+    let result = parse_frame_slice(raw).unwrap().unwrap();
+    assert_eq!(result.2, Some(b"hello".to_vec()));
+    // Consumed should include the CRLF but not the next frame
+    assert_eq!(result.3, b"SEND\n\nhello\0\r\n".len());
+}
+
+#[test]
+fn parse_content_length_body_with_trailing_crlf() {
+    let raw = b"SEND\ncontent-length:2\n\nhi\0\r\n";
+    let result = parse_frame_slice(raw).unwrap().unwrap();
+    assert_eq!(result.2, Some(b"hi".to_vec()));
+    assert_eq!(result.3, raw.len());
+}
+
 #[test]
 fn parse_body_binary_with_content_length() {
     // Binary body with multiple NULs
@@ -421,3 +438,13 @@ fn parse_only_nul() {
     assert!(result.1.is_empty());
     assert!(result.2.is_none());
 }
+
+#[test]
+fn parse_crlf_blank_line() {
+    let raw = b"SEND\r\ndestination:/q\r\n\r\nhi\0";
+    let (cmd, headers, body, consumed) = parse_frame_slice(raw).unwrap().unwrap();
+    assert_eq!(cmd, b"SEND");
+    assert_eq!(headers, vec![(b"destination".to_vec(), b"/q".to_vec())]);
+    assert_eq!(body, Some(b"hi".to_vec()));
+    assert_eq!(consumed, raw.len());
+}
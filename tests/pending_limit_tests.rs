@@ -0,0 +1,186 @@
+//! Tests for `SubscriptionOptions::max_pending` and its overflow policies.
+//!
+//! A mock broker sends a batch of MESSAGE frames to a consumer that never
+//! acknowledges them (a stuck consumer), and the tests check the ACK and
+//! NACK frames it recorded. The pending map must stay within the limit
+//! whatever the policy.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{
+    AckMode, ConnectOptions, Connection, ConnectionEvent, Frame, PendingOverflow, Subscription,
+};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Start a broker sending `count` messages `m0`, `m1`, ... after the
+/// SUBSCRIBE.
+async fn start_broker(count: usize) -> MockBroker {
+    let mut session = Session::new().connected();
+    for i in 0..count {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", format!("m{}", i))
+                .set_body(format!("body {}", i)),
+        );
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+async fn subscribe(
+    count: usize,
+    max_pending: usize,
+    overflow: PendingOverflow,
+) -> (
+    Connection,
+    Subscription,
+    mpsc::Receiver<ConnectionEvent>,
+    MockBroker,
+) {
+    let broker = start_broker(count).await;
+    let (event_tx, event_rx) = mpsc::channel(16);
+    // The tests never read `next_frame()`
+    let options = ConnectOptions::default()
+        .with_event_notify(event_tx)
+        .best_effort_mirror(true);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    let sub = conn
+        .subscription("/queue/work")
+        .ack(AckMode::ClientIndividual)
+        .max_pending(max_pending)
+        .pending_overflow(overflow)
+        .start()
+        .await
+        .expect("subscribe failed");
+    (conn, sub, event_rx, broker)
+}
+
+/// Receive `count` messages and return their ids.
+async fn receive(sub: &mut Subscription, count: usize) -> Vec<String> {
+    let mut ids = Vec::new();
+    for _ in 0..count {
+        let frame = sub
+            .recv_timeout(Duration::from_secs(2))
+            .await
+            .expect("message not delivered");
+        ids.push(frame.get_header("message-id").unwrap_or("").to_string());
+    }
+    ids
+}
+
+/// Close the connection and return `(command, id)` of the ACKs and NACKs
+/// the broker read.
+async fn finish(conn: Connection, broker: MockBroker) -> Vec<(String, String)> {
+    conn.close_after_flush(Duration::from_secs(2))
+        .await
+        .expect("DISCONNECT not confirmed");
+    broker
+        .received()
+        .into_iter()
+        .filter(|frame| frame.command == "ACK" || frame.command == "NACK")
+        .map(|frame| {
+            let id = frame.get_header("id").unwrap_or("").to_string();
+            (frame.command, id)
+        })
+        .collect()
+}
+
+fn limit_events(events: &mut mpsc::Receiver<ConnectionEvent>) -> Vec<ConnectionEvent> {
+    let mut found = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if matches!(event, ConnectionEvent::PendingLimitReached { .. }) {
+            found.push(event);
+        }
+    }
+    found
+}
+
+/// Wait until `evicted` messages were evicted or NACKed, never reading the
+/// subscription: a stuck consumer.
+async fn wait_for_evicted(sub: &Subscription, evicted: u64) -> iridium_stomp::PendingAcks {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let pending = sub.pending_acks().await;
+            if pending.evicted >= evicted {
+                return pending;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("messages not evicted")
+}
+
+#[tokio::test]
+async fn evict_oldest_keeps_pending_bounded() {
+    const COUNT: usize = 200;
+    let (conn, sub, mut events, broker) = subscribe(COUNT, 10, PendingOverflow::EvictOldest).await;
+
+    let pending = wait_for_evicted(&sub, (COUNT - 10) as u64).await;
+    assert_eq!(pending.count, 10);
+    assert_eq!(pending.evicted, (COUNT - 10) as u64);
+
+    let events = limit_events(&mut events);
+    assert_eq!(events.len(), 1, "{:?}", events);
+    assert!(matches!(
+        &events[0],
+        ConnectionEvent::PendingLimitReached {
+            max: 10,
+            policy: PendingOverflow::EvictOldest,
+            evicted: 0,
+            ..
+        }
+    ));
+
+    // Evicted messages can still be acknowledged
+    sub.ack("m0").await.unwrap();
+    let acks = finish(conn, broker).await;
+    assert_eq!(acks, [("ACK".to_string(), "m0".to_string())]);
+}
+
+#[tokio::test]
+async fn nack_oldest_hands_messages_back_to_the_broker() {
+    const COUNT: usize = 30;
+    let (conn, sub, _events, broker) = subscribe(COUNT, 5, PendingOverflow::NackOldest).await;
+
+    let pending = wait_for_evicted(&sub, (COUNT - 5) as u64).await;
+    assert_eq!(pending.count, 5);
+
+    let acks = finish(conn, broker).await;
+    let expected: Vec<(String, String)> = (0..COUNT - 5)
+        .map(|i| ("NACK".to_string(), format!("m{}", i)))
+        .collect();
+    assert_eq!(acks, expected);
+}
+
+#[tokio::test]
+async fn stop_delivering_waits_for_acks() {
+    let (conn, mut sub, mut events, broker) =
+        subscribe(20, 5, PendingOverflow::StopDelivering).await;
+
+    assert_eq!(receive(&mut sub, 5).await, ["m0", "m1", "m2", "m3", "m4"]);
+    assert!(
+        sub.recv_timeout(Duration::from_millis(300)).await.is_err(),
+        "delivered past the pending limit"
+    );
+    let pending = sub.pending_acks().await;
+    assert_eq!(pending.count, 5);
+    assert_eq!(pending.evicted, 0);
+    assert_eq!(limit_events(&mut events).len(), 1);
+
+    sub.ack("m0").await.unwrap();
+    assert_eq!(receive(&mut sub, 1).await, ["m5"]);
+    sub.ack_batch(&["m1", "m2", "m3", "m4", "m5"])
+        .await
+        .unwrap();
+    assert_eq!(receive(&mut sub, 5).await, ["m6", "m7", "m8", "m9", "m10"]);
+    assert_eq!(sub.pending_acks().await.count, 5);
+
+    let acks = finish(conn, broker).await;
+    assert_eq!(acks.len(), 6);
+    assert!(acks.iter().all(|(command, _)| command == "ACK"));
+}
@@ -0,0 +1,126 @@
+//! Tests for `Connection::producer()`.
+//!
+//! The mock broker confirms receipts and records every SEND frame it reads.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script};
+use iridium_stomp::{BrokerProfile, Connection, Frame, assert_frame};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+async fn connect() -> (Connection, MockBroker) {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    (conn, broker)
+}
+
+/// The first `count` SEND frames the broker read.
+async fn sends(broker: &MockBroker, count: usize) -> Vec<Frame> {
+    assert!(
+        broker.wait_for("SEND", count, Duration::from_secs(2)).await,
+        "SEND not received"
+    );
+    broker.received_commands("SEND")
+}
+
+#[tokio::test]
+async fn sends_carry_the_preset_headers() {
+    let (conn, broker) = connect().await;
+    let orders = conn
+        .producer("/queue/orders")
+        .content_type("text/plain")
+        .persistent(true)
+        .header("priority", "4")
+        .header("priority", "9")
+        .header("destination", "/queue/elsewhere");
+    assert_eq!(orders.destination(), "/queue/orders");
+
+    orders.send("first").await.unwrap();
+    orders
+        .send_confirmed("second", Duration::from_secs(2))
+        .await
+        .expect("receipt not confirmed");
+
+    let sent = sends(&broker, 2).await;
+    assert_frame!(
+        sent[0],
+        FrameMatcher::command("SEND")
+            .header("destination", "/queue/orders")
+            .header("content-type", "text/plain")
+            .header("persistent", "true")
+            .header("priority", "9")
+            .body("first")
+    );
+    assert_frame!(
+        sent[1],
+        FrameMatcher::command("SEND")
+            .has_header("receipt")
+            .header("destination", "/queue/orders")
+            .body("second")
+    );
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn ttl_uses_the_broker_header() {
+    let (conn, _broker) = connect().await;
+    let ttl = Duration::from_secs(30);
+
+    let rabbit = conn
+        .producer("/queue/a")
+        .ttl(ttl, BrokerProfile::RabbitMq)
+        .frame("");
+    assert_eq!(rabbit.get_header("expiration"), Some("30000"));
+    assert_eq!(rabbit.get_header("expires"), None);
+
+    let artemis = conn
+        .producer("/queue/a")
+        .ttl(ttl, BrokerProfile::Artemis)
+        .frame("");
+    assert_eq!(artemis.get_header("expiration"), None);
+    let expires: u128 = artemis.get_header("expires").unwrap().parse().unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    assert!(expires > now && expires <= now + 30_000);
+
+    let generic = conn
+        .producer("/queue/a")
+        .ttl(ttl, BrokerProfile::Generic)
+        .frame("");
+    assert!(generic.get_header("expiration").is_some());
+    assert!(generic.get_header("expires").is_some());
+    conn.close().await;
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn send_json_sets_the_content_type() {
+    let (conn, broker) = connect().await;
+    let orders = conn.producer("/queue/orders");
+    let typed = conn
+        .producer("/queue/orders")
+        .content_type("application/vnd.order+json");
+
+    orders
+        .send_json(&serde_json::json!({"id": 42}))
+        .await
+        .unwrap();
+    typed.send_json(&[1, 2]).await.unwrap();
+
+    let sent = sends(&broker, 2).await;
+    assert_frame!(
+        sent[0],
+        FrameMatcher::command("SEND")
+            .header("content-type", "application/json")
+            .body(r#"{"id":42}"#)
+    );
+    assert_frame!(
+        sent[1],
+        FrameMatcher::command("SEND")
+            .header("content-type", "application/vnd.order+json")
+            .body("[1,2]")
+    );
+    conn.close().await;
+}
@@ -0,0 +1,59 @@
+//! Tests for `ConnectOptions::publisher_sequence()`.
+//!
+//! A mock broker records the SEND frames it receives. Each must carry the
+//! publisher id and a sequence number continuing from the value held in the
+//! store when the connection was opened.
+
+use iridium_stomp::assert_frame;
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{
+    ConnectOptions, Connection, DuplicateFilter, FileSequenceStore, Frame, Message,
+    PublisherSequence, SequenceStore,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn sequence_continues_from_the_store() {
+    let path = std::env::temp_dir().join(format!("iridium-pubseq-{}", std::process::id()));
+    let store = FileSequenceStore::new(&path);
+    store.save("orders-1", 41).unwrap();
+
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let options = ConnectOptions::default()
+        .publisher_sequence(PublisherSequence::new("orders-1").store(store.clone()));
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    for body in ["a", "b", "c"] {
+        conn.send("/queue/orders", body).await.expect("send failed");
+    }
+
+    assert!(broker.wait_for("SEND", 3, Duration::from_secs(5)).await);
+    for (frame, seq) in broker
+        .received_commands("SEND")
+        .iter()
+        .zip(["42", "43", "44"])
+    {
+        assert_frame!(frame, "SEND", "publisher-id" => "orders-1", "publisher-seq" => seq);
+    }
+    assert_eq!(store.load("orders-1").unwrap(), 44);
+
+    conn.close().await;
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn consumer_reads_sequence_and_drops_duplicates() {
+    let message = Frame::new("MESSAGE")
+        .header("publisher-id", "orders-1")
+        .header("publisher-seq", "7");
+    let msg = Message::from(message.clone());
+    assert_eq!(msg.publisher_id(), Some("orders-1"));
+    assert_eq!(msg.publisher_seq(), Some(7));
+
+    let mut filter = DuplicateFilter::new();
+    assert!(!filter.is_duplicate(&message));
+    assert!(filter.is_duplicate(&message));
+}
@@ -0,0 +1,40 @@
+//! Tests for `ConnectOptions::read_timeout()`.
+//!
+//! The mock broker completes the handshake with heartbeats disabled and
+//! then goes silent without closing the socket. The client must notice the
+//! silence, report it, and reconnect.
+
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{ConnectOptions, Connection, ConnectionEvent};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn silent_broker_triggers_reconnect() {
+    // The default session keeps the socket open but never sends anything
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let (event_tx, mut event_rx) = mpsc::channel(16);
+    let options = ConnectOptions::default()
+        .read_timeout(Duration::from_millis(300))
+        .with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let event = tokio::time::timeout(Duration::from_secs(5), event_rx.recv())
+        .await
+        .expect("no read timeout reported")
+        .unwrap();
+    match event {
+        ConnectionEvent::ReadTimeout { idle } => assert!(idle >= Duration::from_millis(300)),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // Initial session plus the reconnect
+    let reconnected = broker.wait_for("CONNECT", 2, Duration::from_secs(10)).await;
+    assert!(reconnected, "client did not reconnect after read timeout");
+
+    conn.close().await;
+}
@@ -0,0 +1,136 @@
+//! Tests for tracking receipts the broker has not confirmed.
+//!
+//! These tests run against a mock broker that accepts SEND frames but never
+//! answers receipts, and verify that stalled receipts are reported.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, ConnectionEvent, Frame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Start a broker that completes the handshake and then silently reads
+/// frames until the client disconnects.
+async fn start_silent_broker() -> MockBroker {
+    MockBroker::start(Script::new().session(Session::new().connected().withhold_receipts()))
+        .await
+        .unwrap()
+}
+
+// ============================================================================
+// Stalled receipt warnings
+// ============================================================================
+
+#[tokio::test]
+async fn stalled_receipts_emit_warning_event() {
+    let broker = start_silent_broker().await;
+
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let options = ConnectOptions::default()
+        .with_event_notify(event_tx)
+        .receipt_warning_after(Duration::from_millis(100));
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    conn.send_frame_with_receipt(Frame::new("SEND").header("destination", "/queue/a"))
+        .await
+        .expect("send failed");
+
+    let event = tokio::time::timeout(Duration::from_secs(3), event_rx.recv())
+        .await
+        .expect("timed out waiting for event")
+        .expect("event channel closed");
+    match event {
+        ConnectionEvent::ReceiptsStalled { count, oldest_age } => {
+            assert_eq!(count, 1);
+            assert!(oldest_age > Duration::from_millis(100));
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // Only one warning per stall
+    assert!(
+        tokio::time::timeout(Duration::from_millis(1500), event_rx.recv())
+            .await
+            .is_err()
+    );
+
+    conn.close().await;
+}
+
+// ============================================================================
+// Cleanup of abandoned receipts
+// ============================================================================
+
+#[tokio::test]
+async fn abandoned_receipts_are_expired() {
+    let broker = start_silent_broker().await;
+
+    let options = ConnectOptions::default().receipt_ttl(Duration::from_millis(100));
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    // Requested but never waited for
+    conn.send_frame_with_receipt(Frame::new("SEND").header("destination", "/queue/a"))
+        .await
+        .expect("send failed");
+    assert_eq!(conn.outstanding_receipts().await.count, 1);
+
+    // Cleanup runs once per second
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let outstanding = conn.outstanding_receipts().await;
+    assert_eq!(outstanding.count, 0);
+    assert_eq!(outstanding.expired, 1);
+
+    conn.close().await;
+}
+
+// ============================================================================
+// Receipts outstanding when the connection drops
+// ============================================================================
+
+#[tokio::test]
+async fn lost_connection_fails_outstanding_receipts() {
+    use iridium_stomp::{ConnError, ReceiptError};
+    use std::time::Instant;
+
+    let broker = MockBroker::start(
+        Script::new()
+            // Drops the connection right after the SEND, without a RECEIPT
+            .session(
+                Session::new()
+                    .connected()
+                    .withhold_receipts()
+                    .drop_after_frames(1),
+            )
+            // Never answers CONNECT, so the reconnect attempts do not succeed
+            .session(Session::new()),
+    )
+    .await
+    .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let started = Instant::now();
+    let result = conn
+        .send_frame_confirmed(
+            Frame::new("SEND").header("destination", "/queue/a"),
+            Duration::from_secs(10),
+        )
+        .await;
+    match result {
+        Err(ConnError::Receipt(ReceiptError::ConnectionLost { receipt_id })) => {
+            assert!(receipt_id.starts_with("rcpt-"));
+        }
+        other => panic!("expected ConnectionLost, got {:?}", other),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(conn.outstanding_receipts().await.count, 0);
+
+    conn.close().await;
+}
@@ -0,0 +1,122 @@
+//! Tests for `ConnectionEvent::Reconnecting` and `ConnectionEvent::Reconnected`.
+//!
+//! The mock broker ends the first session once the client has subscribed
+//! and answers the receipts of the second if told to.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{AckMode, ConnectOptions, Connection, ConnectionEvent, assert_frame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Start a broker whose first session ends after `subscriptions` SUBSCRIBE
+/// frames and whose second answers receipts if `confirm` is set.
+async fn start_broker(subscriptions: usize, confirm: bool) -> MockBroker {
+    let second = if confirm {
+        Session::new().connected()
+    } else {
+        Session::new().connected().withhold_receipts()
+    };
+    MockBroker::start(
+        Script::new()
+            .session(Session::new().connected().drop_after_frames(subscriptions))
+            .session(second),
+    )
+    .await
+    .unwrap()
+}
+
+/// Events received within `wait`, skipping those unrelated to reconnects.
+async fn reconnect_events(
+    events: &mut mpsc::Receiver<ConnectionEvent>,
+    wait: Duration,
+) -> Vec<ConnectionEvent> {
+    let mut seen = Vec::new();
+    while let Ok(Some(event)) = tokio::time::timeout(wait, events.recv()).await {
+        if matches!(
+            event,
+            ConnectionEvent::Reconnecting { .. } | ConnectionEvent::Reconnected { .. }
+        ) {
+            seen.push(event);
+        }
+    }
+    seen
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnect_is_reported_once_subscriptions_are_confirmed() {
+    let broker = start_broker(3, true).await;
+    let (event_tx, mut event_rx) = mpsc::channel(16);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let _orders = conn
+        .subscribe("/queue/orders", AckMode::Auto)
+        .await
+        .unwrap();
+    let _prices = conn
+        .subscribe("/topic/prices", AckMode::Auto)
+        .await
+        .unwrap();
+    let _again = conn
+        .subscribe("/queue/orders", AckMode::Auto)
+        .await
+        .unwrap();
+
+    // The reconnect backoff after a short session is two seconds
+    let mut events = reconnect_events(&mut event_rx, Duration::from_secs(4)).await;
+    // Subscriptions are sent again in no particular order
+    if let Some(ConnectionEvent::Reconnected { resubscribed, .. }) = events.last_mut() {
+        resubscribed.sort();
+    }
+    assert_eq!(
+        events,
+        vec![
+            ConnectionEvent::Reconnecting { attempt: 1 },
+            ConnectionEvent::Reconnected {
+                attempts: 1,
+                resubscribed: vec!["/queue/orders".to_string(), "/topic/prices".to_string()],
+            },
+        ]
+    );
+
+    // The SUBSCRIBE frames of the second session
+    let sent = broker.received_commands("SUBSCRIBE").split_off(3);
+    assert_eq!(sent.len(), 3, "{:?}", sent);
+    // Only the last one asks for a receipt
+    assert_frame!(
+        sent[0],
+        FrameMatcher::command("SUBSCRIBE").lacks_header("receipt")
+    );
+    assert_frame!(
+        sent[1],
+        FrameMatcher::command("SUBSCRIBE").lacks_header("receipt")
+    );
+    assert_frame!(
+        sent[2],
+        FrameMatcher::command("SUBSCRIBE").has_header("receipt")
+    );
+    conn.close().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unconfirmed_resubscribe_is_not_reported_as_reconnected() {
+    let broker = start_broker(1, false).await;
+    let (event_tx, mut event_rx) = mpsc::channel(16);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let _orders = conn
+        .subscribe("/queue/orders", AckMode::Auto)
+        .await
+        .unwrap();
+
+    let events = reconnect_events(&mut event_rx, Duration::from_secs(4)).await;
+    assert_eq!(events, vec![ConnectionEvent::Reconnecting { attempt: 1 }]);
+    conn.close().await;
+}
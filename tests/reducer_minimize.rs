@@ -53,7 +53,7 @@ fn minimize_failing_chunk_sequence() {
             loop {
                 match dec.decode(&mut buf) {
                     Ok(Some(StompItem::Frame(_))) => decoded += 1,
-                    Ok(Some(StompItem::Heartbeat)) => {}
+                    Ok(Some(_)) => {}
                     Ok(None) => break,
                     Err(_) => return false, // parse error alone is not the original symptom
                 }
@@ -63,7 +63,7 @@ fn minimize_failing_chunk_sequence() {
         loop {
             match dec.decode(&mut buf) {
                 Ok(Some(StompItem::Frame(_))) => decoded += 1,
-                Ok(Some(StompItem::Heartbeat)) => {}
+                Ok(Some(_)) => {}
                 Ok(None) => break,
                 Err(_) => return false,
             }
@@ -56,7 +56,7 @@ fn replay_problematic_chunk_sequence() {
                     decoded += 1;
                     bodies.push(f.body);
                 }
-                Ok(Some(StompItem::Heartbeat)) => {}
+                Ok(Some(_)) => {}
                 Ok(None) => break,
                 Err(e) => panic!("decoder returned error on replayed chunks: {}", e),
             }
@@ -67,7 +67,7 @@ fn replay_problematic_chunk_sequence() {
     loop {
         match dec.decode(&mut buf) {
             Ok(Some(StompItem::Frame(_))) => decoded += 1,
-            Ok(Some(StompItem::Heartbeat)) => {}
+            Ok(Some(_)) => {}
             Ok(None) => break,
             Err(e) => panic!("decoder returned error during drain: {}", e),
         }
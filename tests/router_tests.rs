@@ -0,0 +1,408 @@
+//! Tests for `Router` and `Connection::serve()`.
+//!
+//! The mock broker delivers messages with different content types and
+//! records the ACK/NACK frames it receives, so the tests can check which
+//! handler ran and how each message was settled.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{
+    ConnError, ConnectOptions, Connection, ConnectionEvent, FailurePolicy, Frame, Message,
+    NackOptions, Router, assert_frame,
+};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Start a broker that answers the handshake and otherwise waits for the
+/// test to act.
+async fn start_broker() -> MockBroker {
+    MockBroker::start(Script::new()).await.unwrap()
+}
+
+/// Once `ids.len()` subscriptions exist, send one message with each id to
+/// the subscriptions in the order they were made.
+async fn deliver_in_turn(broker: &MockBroker, ids: &[&str]) {
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", ids.len(), Duration::from_secs(2))
+            .await
+    );
+    for (message_id, subscribe) in ids.iter().zip(broker.received_commands("SUBSCRIBE")) {
+        broker.send(
+            Frame::new("MESSAGE")
+                .header("destination", subscribe.get_header("destination").unwrap())
+                .header("message-id", *message_id)
+                .header("subscription", subscribe.get_header("id").unwrap())
+                .set_body("x"),
+        );
+    }
+}
+
+/// Wait for `count` ACK and NACK frames and return them in the order the
+/// broker read them.
+async fn settlements(broker: &MockBroker, count: usize) -> Vec<Frame> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let frames: Vec<Frame> = broker
+                .received()
+                .into_iter()
+                .filter(|frame| frame.command == "ACK" || frame.command == "NACK")
+                .collect();
+            if frames.len() >= count {
+                return frames;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("messages not settled")
+}
+
+#[tokio::test]
+async fn routes_by_content_type_and_settles_messages() {
+    let mut session = Session::new().connected();
+    for (message_id, content_type, body) in [
+        ("m1", Some("application/json; charset=utf-8"), "{}"),
+        ("m2", Some("text/plain"), "bad"),
+        ("m3", None, "raw"),
+    ] {
+        let mut frame = Frame::new("MESSAGE").header("message-id", message_id);
+        if let Some(content_type) = content_type {
+            frame = frame.header("content-type", content_type);
+        }
+        session = session.deliver_frame(frame.set_body(body));
+    }
+    let broker = MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (handled_tx, mut handled_rx) = mpsc::unbounded_channel::<(&'static str, String)>();
+    let json_tx = handled_tx.clone();
+    let text_tx = handled_tx.clone();
+    let router = Router::new()
+        .route("/queue/orders", move |msg: Message| {
+            let tx = handled_tx.clone();
+            async move {
+                tx.send(("any", msg.message_id().unwrap().to_string()))
+                    .unwrap();
+                Ok::<_, String>(())
+            }
+        })
+        .route_content_type("/queue/orders", "application/json", move |msg: Message| {
+            let tx = json_tx.clone();
+            async move {
+                tx.send(("json", msg.message_id().unwrap().to_string()))
+                    .unwrap();
+                Ok::<_, String>(())
+            }
+        })
+        .content_type("text/plain", move |msg: Message| {
+            let tx = text_tx.clone();
+            async move {
+                tx.send(("text", msg.message_id().unwrap().to_string()))
+                    .unwrap();
+                Err("cannot parse text")
+            }
+        });
+
+    let serving = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.serve(router).await })
+    };
+
+    let frames = settlements(&broker, 3).await;
+
+    let mut handled = Vec::new();
+    while let Ok(entry) = handled_rx.try_recv() {
+        handled.push(entry);
+    }
+    assert_eq!(
+        handled,
+        vec![
+            ("json", "m1".to_string()),
+            ("text", "m2".to_string()),
+            ("any", "m3".to_string()),
+        ]
+    );
+
+    assert_frame!(
+        broker.received_commands("SUBSCRIBE")[0],
+        "SUBSCRIBE",
+        "ack" => "client-individual"
+    );
+    assert_frame!(frames[0], "ACK", "id" => "m1");
+    assert_frame!(frames[1], "NACK", "id" => "m2");
+    assert_frame!(frames[2], "ACK", "id" => "m3");
+
+    conn.close().await;
+    let result = tokio::time::timeout(Duration::from_secs(5), serving)
+        .await
+        .expect("serve did not return after close")
+        .unwrap();
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn failed_handlers_are_settled_by_policy() {
+    let broker = start_broker().await;
+
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let router = Router::new()
+        .failure_policy(FailurePolicy::Ack)
+        .route("/queue/panics", |_msg: Message| async move {
+            if true {
+                panic!("boom");
+            }
+            Ok::<_, String>(())
+        })
+        .on_failure(FailurePolicy::Nack)
+        .route("/queue/errors", |_msg: Message| async move {
+            Err("invalid order")
+        });
+
+    let serving = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.serve(router).await })
+    };
+
+    deliver_in_turn(&broker, &["p1", "e1"]).await;
+    let mut frames = settlements(&broker, 2).await;
+    frames.sort_by_key(|frame| frame.get_header("id").map(str::to_string));
+
+    // The panicking route overrides the router's policy and NACKs
+    assert_frame!(frames[0], "ACK", "id" => "e1");
+    assert_frame!(frames[1], "NACK", "id" => "p1");
+
+    let mut failures = Vec::new();
+    while failures.len() < 2 {
+        let event = tokio::time::timeout(Duration::from_secs(2), event_rx.recv())
+            .await
+            .expect("timed out waiting for event")
+            .expect("event channel closed");
+        if let ConnectionEvent::HandlerFailed {
+            message_id,
+            error,
+            panicked,
+            policy,
+            ..
+        } = event
+        {
+            failures.push((message_id, error, panicked, policy));
+        }
+    }
+    failures.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        failures,
+        vec![
+            (
+                "e1".to_string(),
+                "invalid order".to_string(),
+                false,
+                FailurePolicy::Ack
+            ),
+            (
+                "p1".to_string(),
+                "handler panicked: boom".to_string(),
+                true,
+                FailurePolicy::Nack
+            ),
+        ]
+    );
+
+    // The router survived the panic
+    assert!(!serving.is_finished());
+    conn.close().await;
+    let result = tokio::time::timeout(Duration::from_secs(5), serving)
+        .await
+        .expect("serve did not return after close")
+        .unwrap();
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn handlers_past_their_deadline_are_cancelled() {
+    let broker = start_broker().await;
+
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let router = Router::new()
+        .processing_deadline(Duration::from_millis(150))
+        .route("/queue/stuck", |_msg: Message| async move {
+            std::future::pending::<()>().await;
+            Ok::<_, String>(())
+        })
+        .route("/queue/extended", |msg: Message| async move {
+            assert!(msg.deadline().is_some());
+            // Twice the deadline in total, extended as it goes
+            for _ in 0..3 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                msg.extend(Duration::from_millis(150));
+            }
+            Ok::<_, String>(())
+        });
+
+    let serving = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.serve(router).await })
+    };
+
+    deliver_in_turn(&broker, &["s1", "x1"]).await;
+    let mut frames = settlements(&broker, 2).await;
+    frames.sort_by_key(|frame| frame.get_header("id").map(str::to_string));
+
+    assert_frame!(frames[0], "NACK", "id" => "s1");
+    assert_frame!(frames[1], "ACK", "id" => "x1");
+
+    let timed_out = loop {
+        let event = tokio::time::timeout(Duration::from_secs(2), event_rx.recv())
+            .await
+            .expect("timed out waiting for event")
+            .expect("event channel closed");
+        if let ConnectionEvent::HandlerTimedOut {
+            destination,
+            message_id,
+            elapsed,
+        } = event
+        {
+            break (destination, message_id, elapsed);
+        }
+    };
+    assert_eq!(timed_out.0, "/queue/stuck");
+    assert_eq!(timed_out.1, "s1");
+    assert!(timed_out.2 >= Duration::from_millis(150));
+
+    conn.close().await;
+    let _ = tokio::time::timeout(Duration::from_secs(5), serving).await;
+}
+
+#[tokio::test]
+async fn untyped_messages_are_routed_by_sniffed_content_type() {
+    let mut session = Session::new().connected();
+    for (message_id, body) in [("m1", r#"{"order": 1}"#), ("m2", "plain text")] {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", message_id)
+                .set_body(body),
+        );
+    }
+    let broker = MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (handled_tx, mut handled_rx) = mpsc::unbounded_channel::<(&'static str, String)>();
+    let json_tx = handled_tx.clone();
+    let router = Router::new()
+        .route("/queue/orders", move |msg: Message| {
+            let tx = handled_tx.clone();
+            async move {
+                tx.send(("any", msg.message_id().unwrap().to_string()))
+                    .unwrap();
+                Ok::<_, String>(())
+            }
+        })
+        .content_type("application/json", move |msg: Message| {
+            let tx = json_tx.clone();
+            async move {
+                tx.send(("json", msg.message_id().unwrap().to_string()))
+                    .unwrap();
+                Ok::<_, String>(())
+            }
+        })
+        .sniff_content_type(true);
+
+    let serving = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.serve(router).await })
+    };
+
+    let frames = settlements(&broker, 2).await;
+
+    let mut handled = Vec::new();
+    while let Ok(entry) = handled_rx.try_recv() {
+        handled.push(entry);
+    }
+    assert_eq!(
+        handled,
+        vec![("json", "m1".to_string()), ("any", "m2".to_string())]
+    );
+    assert!(frames.iter().all(|f| f.command == "ACK"));
+
+    conn.close().await;
+    let _ = tokio::time::timeout(Duration::from_secs(5), serving).await;
+}
+
+/// Serve `router` until one message of each id in `ids` has been settled
+/// and return the settling frames.
+async fn settle_unrouted(router: Router, ids: &[&str]) -> Vec<Frame> {
+    let broker = start_broker().await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let serving = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.serve(router).await })
+    };
+    deliver_in_turn(&broker, ids).await;
+    let frames = settlements(&broker, ids.len()).await;
+    conn.close().await;
+    let _ = tokio::time::timeout(Duration::from_secs(5), serving).await;
+    frames
+}
+
+#[tokio::test]
+async fn unrouted_messages_are_not_requeued() {
+    let json_only = || {
+        Router::new().route_content_type(
+            "/queue/orders",
+            "application/json",
+            |_msg: Message| async { Ok::<_, String>(()) },
+        )
+    };
+
+    let frames = settle_unrouted(json_only(), &["u1"]).await;
+    assert_frame!(frames[0], "NACK", "id" => "u1", "requeue" => "false", "reason" => "no route");
+
+    let frames = settle_unrouted(json_only().unrouted(NackOptions::default()), &["u2"]).await;
+    assert_frame!(frames[0], "NACK", "id" => "u2", "requeue" => "true");
+}
+
+#[tokio::test]
+async fn serve_requires_a_destination_route() {
+    let broker = start_broker().await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let router =
+        Router::new().content_type("text/plain", |_msg: Message| async { Ok::<_, String>(()) });
+    assert!(matches!(
+        conn.serve(router).await,
+        Err(ConnError::Protocol(_))
+    ));
+
+    conn.close().await;
+}
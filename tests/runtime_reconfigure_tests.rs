@@ -0,0 +1,139 @@
+//! Tests for `Connection::update_heartbeat()` and
+//! `Subscription::update_headers()`.
+//!
+//! Each mock broker records the frames it receives, so the assertions can
+//! check the exact handshake and subscription traffic produced by a
+//! runtime change.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{AckMode, Connection, Frame, Heartbeat, assert_frame};
+use std::time::Duration;
+
+#[tokio::test]
+async fn update_heartbeat_reconnects_with_new_values_and_resubscribes() {
+    let broker = MockBroker::start(
+        Script::new()
+            // First session: handshake, SUBSCRIBE, then a DISCONNECT to confirm
+            .session(
+                Session::new()
+                    .connected()
+                    .expect("SUBSCRIBE")
+                    .expect("DISCONNECT")
+                    .close(),
+            )
+            // Second session: handshake and resubscribe, then one message
+            .session(
+                Session::new().connected().deliver_frame(
+                    Frame::new("MESSAGE")
+                        .header("message-id", "m1")
+                        .set_body("hello"),
+                ),
+            ),
+    )
+    .await
+    .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut sub = conn.subscribe("/queue/a", AckMode::Auto).await.unwrap();
+
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        conn.update_heartbeat(Heartbeat::new(2000, 3000)),
+    )
+    .await
+    .expect("update_heartbeat did not finish")
+    .expect("update_heartbeat failed");
+
+    let message = sub
+        .recv_timeout(Duration::from_secs(5))
+        .await
+        .expect("no message after reconfiguring");
+    assert_eq!(message.body, b"hello");
+
+    let frames = broker.received();
+    assert_frame!(frames[0], "CONNECT", "heart-beat" => "0,0");
+    assert_frame!(frames[1], FrameMatcher::command("SUBSCRIBE"));
+    assert_frame!(frames[2], FrameMatcher::command("DISCONNECT"));
+    assert_frame!(frames[3], "CONNECT", "heart-beat" => "2000,3000");
+    assert_frame!(
+        frames[4],
+        "SUBSCRIBE",
+        "id" => frames[1].get_header("id").unwrap()
+    );
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn update_headers_resubscribes_and_survives_reconnect() {
+    let broker = MockBroker::start(
+        Script::new()
+            // First session: SUBSCRIBE, UNSUBSCRIBE, SUBSCRIBE, then drop
+            .session(Session::new().connected().drop_after_frames(3))
+            // Second session: the resubscribe must carry the new headers
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let sub = conn
+        .subscribe_with_headers(
+            "/queue/a",
+            AckMode::Client,
+            vec![("selector".to_string(), "color = 'red'".to_string())],
+        )
+        .await
+        .unwrap();
+    sub.update_headers(vec![("selector".to_string(), "color = 'blue'".to_string())])
+        .await
+        .unwrap();
+
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", 3, Duration::from_secs(5))
+            .await
+    );
+    let frames: Vec<Frame> = broker
+        .received()
+        .into_iter()
+        .filter(|frame| frame.command != "CONNECT")
+        .collect();
+
+    assert_frame!(frames[0], "SUBSCRIBE", "selector" => "color = 'red'");
+    assert_frame!(frames[1], "UNSUBSCRIBE", "id" => sub.id());
+    for frame in &frames[2..4] {
+        assert_frame!(
+            frame,
+            "SUBSCRIBE",
+            "id" => sub.id(),
+            "destination" => "/queue/a",
+            "ack" => "client",
+            "selector" => "color = 'blue'",
+        );
+    }
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn update_headers_rejects_unknown_subscription() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let sub = conn.subscribe("/queue/a", AckMode::Auto).await.unwrap();
+    conn.unsubscribe(sub.id()).await.unwrap();
+
+    assert!(sub.update_headers(Vec::new()).await.is_err());
+    conn.close().await;
+}
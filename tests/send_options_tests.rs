@@ -0,0 +1,49 @@
+//! Tests for `Connection::send_with_options()`.
+//!
+//! The mock broker records every SEND frame it reads.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script};
+use iridium_stomp::{Connection, Frame, IdStyle, SendOptions, assert_frame};
+use std::time::Duration;
+
+fn order() -> Frame {
+    Frame::new("SEND")
+        .header("destination", "/queue/orders")
+        .set_body(b"order".to_vec())
+}
+
+#[tokio::test]
+async fn generated_ids_are_sent_and_reported() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let uuid = SendOptions::new().auto_message_id(IdStyle::Uuid);
+    let correlated = SendOptions::new()
+        .auto_message_id(IdStyle::Seq)
+        .id_header("correlation-id");
+    let first = conn.send_with_options(order(), &uuid).await.unwrap();
+    let second = conn.send_with_options(order(), &correlated).await.unwrap();
+    let plain = conn
+        .send_with_options(order(), &SendOptions::new())
+        .await
+        .unwrap();
+
+    assert!(broker.wait_for("SEND", 3, Duration::from_secs(2)).await);
+    let sends = broker.received_commands("SEND");
+    assert_eq!(first.message_id().map(str::len), Some(36));
+    assert_frame!(sends[0], "SEND", "message-id" => first.message_id().unwrap());
+    assert_frame!(
+        sends[1],
+        FrameMatcher::command("SEND")
+            .header("correlation-id", second.message_id().unwrap())
+            .lacks_header("message-id")
+    );
+    assert_eq!(plain.message_id(), None);
+    assert_frame!(
+        sends[2],
+        FrameMatcher::command("SEND").lacks_header("message-id")
+    );
+    conn.close().await;
+}
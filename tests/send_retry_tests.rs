@@ -0,0 +1,153 @@
+//! Tests for `Connection::send_with_retry()`.
+//!
+//! The mock broker withholds receipts until the test confirms them, so the
+//! client has to retry, and records every SEND it sees.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{
+    BrokerProfile, ConnError, ConnectOptions, Connection, ConnectionEvent, Frame, RetryPolicy,
+};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Start a broker that answers receipts only when the test confirms them.
+async fn start_broker() -> MockBroker {
+    MockBroker::start(Script::new().session(Session::new().connected().withhold_receipts()))
+        .await
+        .unwrap()
+}
+
+/// Confirm the receipt of the `attempt`th SEND once it arrives.
+async fn confirm_attempt(broker: &MockBroker, attempt: usize) {
+    assert!(
+        broker
+            .wait_for("SEND", attempt, Duration::from_secs(2))
+            .await
+    );
+    let send = &broker.received_commands("SEND")[attempt - 1];
+    let receipt = send.get_header("receipt").expect("no receipt header");
+    broker.send(Frame::new("RECEIPT").header("receipt-id", receipt));
+}
+
+fn order() -> Frame {
+    Frame::new("SEND")
+        .header("destination", "/queue/orders")
+        .set_body(b"order-1".to_vec())
+}
+
+#[tokio::test]
+async fn retries_until_receipt_arrives() {
+    let broker = start_broker().await;
+
+    let (event_tx, mut event_rx) = mpsc::channel(16);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let policy = RetryPolicy::new()
+        .backoff(Duration::from_millis(20), Duration::from_millis(50))
+        .confirm(Duration::from_millis(200));
+    let (result, ()) = tokio::join!(
+        conn.send_with_retry(order(), policy),
+        confirm_attempt(&broker, 3)
+    );
+    result.expect("send should succeed on the third attempt");
+
+    let sends = broker.received_commands("SEND");
+    assert_eq!(sends.len(), 3);
+    assert!(sends.iter().all(|f| f.body == b"order-1"));
+    let receipts: std::collections::HashSet<_> = sends
+        .iter()
+        .filter_map(|f| f.get_header("receipt"))
+        .collect();
+    assert_eq!(receipts.len(), 3, "each attempt uses a fresh receipt");
+
+    for (attempt, delay) in [(1, 20), (2, 40)] {
+        match event_rx.try_recv() {
+            Ok(ConnectionEvent::SendRetry {
+                destination,
+                attempt: a,
+                delay: d,
+                reason,
+            }) => {
+                assert_eq!(destination.as_deref(), Some("/queue/orders"));
+                assert_eq!(a, attempt);
+                assert_eq!(d, Duration::from_millis(delay));
+                assert!(reason.contains("receipt"), "reason: {}", reason);
+            }
+            other => panic!("expected SendRetry, got {:?}", other),
+        }
+    }
+    assert!(event_rx.try_recv().is_err());
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let broker = start_broker().await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let policy = RetryPolicy::new()
+        .max_attempts(3)
+        .backoff(Duration::from_millis(10), Duration::from_millis(10))
+        .confirm(Duration::from_millis(100));
+    let result = conn.send_with_retry(order(), policy).await;
+    assert!(matches!(result, Err(ConnError::ReceiptTimeout(_))));
+    assert_eq!(broker.received_commands("SEND").len(), 3);
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn deadline_bounds_the_retries() {
+    let broker = start_broker().await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let policy = RetryPolicy::new()
+        .max_attempts(100)
+        .backoff(Duration::from_millis(50), Duration::from_millis(50))
+        .deadline(Duration::from_millis(500))
+        .confirm(Duration::from_millis(200));
+    let started = Instant::now();
+    let result = conn.send_with_retry(order(), policy).await;
+    assert!(matches!(result, Err(ConnError::ReceiptTimeout(_))));
+    assert!(started.elapsed() < Duration::from_millis(900));
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn refused_frames_are_not_retried() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let (event_tx, mut event_rx) = mpsc::channel(16);
+    let options = ConnectOptions::default()
+        .validate_destinations(BrokerProfile::Generic)
+        .with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let frame = Frame::new("SEND").header("destination", "no-leading-slash");
+    let result = conn
+        .send_with_retry(
+            frame,
+            RetryPolicy::new().confirm(Duration::from_millis(100)),
+        )
+        .await;
+    assert!(matches!(result, Err(ConnError::Protocol(_))));
+    assert!(event_rx.try_recv().is_err());
+    assert!(broker.received_commands("SEND").is_empty());
+
+    conn.close().await;
+}
@@ -0,0 +1,84 @@
+//! Tests for `ConnectOptions::recorder()`.
+//!
+//! A mock broker pushes messages and a heartbeat after the handshake and
+//! confirms receipts; the tests check what the recorder counted and kept.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, Frame, SessionRecorder};
+use std::time::Duration;
+
+/// Start a broker that sends two messages to `/queue/a`, one to
+/// `/queue/b` and a heartbeat, then answers every `receipt` header until
+/// the client leaves.
+async fn start_broker() -> MockBroker {
+    let mut session = Session::new().connected();
+    for (i, destination) in ["/queue/a", "/queue/a", "/queue/b"].iter().enumerate() {
+        session = session.send(
+            Frame::new("MESSAGE")
+                .header("destination", *destination)
+                .header("message-id", i.to_string())
+                .header("subscription", "1")
+                .set_body(format!("body-{}", i)),
+        );
+    }
+    // The first LF ends the last MESSAGE, the second is a heartbeat
+    session = session.send_raw("\n\n");
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+async fn wait_for(recorder: &SessionRecorder, check: impl Fn(&SessionRecorder) -> bool) {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while !check(recorder) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("recorder did not catch up");
+}
+
+#[tokio::test]
+async fn connection_records_traffic() {
+    let broker = start_broker().await;
+    let recorder = SessionRecorder::new().history(2);
+    let options = ConnectOptions::default().recorder(recorder.clone());
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    wait_for(&recorder, |r| r.stats().heartbeats == 1).await;
+    let frame = Frame::new("SEND")
+        .header("destination", "/queue/out")
+        .set_body(b"hello".to_vec());
+    let encoded = frame.encoded_len();
+    conn.send_frame(frame).await.unwrap();
+    conn.send_frame_confirmed(
+        Frame::new("SEND").header("destination", "/queue/out"),
+        Duration::from_secs(2),
+    )
+    .await
+    .unwrap();
+    conn.close().await;
+
+    let report = recorder.report();
+    assert_eq!(report.stats.received, 3);
+    assert_eq!(report.stats.received_bytes, 18);
+    assert_eq!(report.stats.heartbeats, 1);
+    assert_eq!(report.stats.sent, 2);
+    assert!(report.stats.sent_bytes > encoded as u64);
+    assert_eq!(report.stats.receipts, 1);
+    assert!(report.stats.last_receipt_rtt.is_some());
+    assert_eq!(report.destinations["/queue/a"], 2);
+    assert_eq!(report.destinations["/queue/b"], 1);
+    let kept: Vec<&str> = report.history.iter().map(|f| f.body.as_str()).collect();
+    assert_eq!(kept, ["body-1", "body-2"]);
+
+    assert!(report.to_text().contains("/queue/a"));
+    #[cfg(feature = "json")]
+    {
+        let json = report.to_json();
+        assert!(json.contains("\"received\":3"), "{}", json);
+    }
+}
@@ -0,0 +1,68 @@
+//! Tests for `Subscription::into_shared`.
+//!
+//! A mock broker sends a batch of messages to one subscription. The
+//! workers created by `into_shared` must split them round-robin, and ACKs
+//! sent from any worker must reach the broker for the shared subscription.
+
+use iridium_stomp::connection::AckMode;
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{Connection, Frame};
+use std::time::Duration;
+
+#[tokio::test]
+async fn workers_share_messages_and_ack_bookkeeping() {
+    const COUNT: usize = 12;
+    const WORKERS: usize = 3;
+
+    let mut session = Session::new().connected();
+    for i in 0..COUNT {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", format!("m{}", i))
+                .set_body(i.to_string()),
+        );
+    }
+    let broker = MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let sub = conn
+        .subscribe("/queue/jobs", AckMode::ClientIndividual)
+        .await
+        .expect("subscribe failed");
+
+    let mut tasks = Vec::new();
+    for mut worker in sub.into_shared(WORKERS) {
+        assert_eq!(worker.id(), "1");
+        tasks.push(tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for _ in 0..COUNT / WORKERS {
+                let frame = tokio::time::timeout(Duration::from_secs(5), worker.recv())
+                    .await
+                    .expect("worker timed out")
+                    .expect("worker closed");
+                worker
+                    .ack(frame.get_header("message-id").unwrap())
+                    .await
+                    .expect("ack failed");
+                bodies.push(String::from_utf8(frame.body).unwrap());
+            }
+            bodies
+        }));
+    }
+
+    // Round-robin: worker k gets messages k, k + WORKERS, ...
+    for (k, task) in tasks.into_iter().enumerate() {
+        let bodies = task.await.unwrap();
+        let expected: Vec<String> = (k..COUNT).step_by(WORKERS).map(|i| i.to_string()).collect();
+        assert_eq!(bodies, expected);
+    }
+
+    // The ACKs sent by the workers
+    assert!(broker.wait_for("ACK", COUNT, Duration::from_secs(5)).await);
+
+    conn.close().await;
+}
@@ -0,0 +1,61 @@
+//! Tests for `ConnectionEvent::SlowConsumer`.
+//!
+//! A mock broker floods a subscription whose receiver is not being read.
+//! Once its buffer fills, the connection must report the subscription as
+//! slow exactly once, with the drop count at that point.
+
+use iridium_stomp::connection::AckMode;
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, ConnectionEvent, Frame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn full_subscription_is_reported_once() {
+    const COUNT: usize = 40;
+
+    let mut session = Session::new().connected();
+    for i in 0..COUNT {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", format!("m{}", i))
+                .set_body(i.to_string()),
+        );
+    }
+    let broker = MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap();
+
+    let (event_tx, mut event_rx) = mpsc::channel(16);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    // Never read from the subscription so its buffer fills up
+    let sub = conn
+        .subscribe("/queue/flood", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+
+    let event = tokio::time::timeout(Duration::from_secs(5), event_rx.recv())
+        .await
+        .expect("no slow consumer reported")
+        .unwrap();
+    assert_eq!(
+        event,
+        ConnectionEvent::SlowConsumer {
+            subscription_id: "1".to_string(),
+            destination: "/queue/flood".to_string(),
+            dropped: 1,
+            buffered: 16,
+        }
+    );
+
+    // The rest of the flood is dropped without further reports
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(event_rx.try_recv().is_err(), "expected a single report");
+
+    drop(sub);
+    conn.close().await;
+}
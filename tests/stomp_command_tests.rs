@@ -0,0 +1,58 @@
+//! Tests for `ConnectOptions::use_stomp_command`.
+//!
+//! The mock broker records the command of each handshake frame. With the
+//! option set, both the initial handshake and the one after a reconnect
+//! must use `STOMP`.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection};
+use std::time::Duration;
+
+/// The commands of the handshake frames the broker read.
+fn handshakes(broker: &MockBroker) -> Vec<String> {
+    broker
+        .received()
+        .into_iter()
+        .map(|frame| frame.command)
+        .filter(|command| command == "CONNECT" || command == "STOMP")
+        .collect()
+}
+
+#[tokio::test]
+async fn handshake_uses_connect_by_default() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    assert_eq!(handshakes(&broker), vec!["CONNECT"]);
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn stomp_command_is_used_for_connect_and_reconnect() {
+    // Drops the first session after the handshake
+    let broker = MockBroker::start(
+        Script::new()
+            .session(Session::new().connected().close())
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
+
+    let options = ConnectOptions::default().use_stomp_command(true);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    // The reconnect must also use STOMP
+    assert!(
+        broker.wait_for("STOMP", 2, Duration::from_secs(10)).await,
+        "client did not reconnect"
+    );
+    assert_eq!(handshakes(&broker), vec!["STOMP", "STOMP"]);
+
+    conn.close().await;
+}
@@ -70,10 +70,7 @@ fn test_stream_api_usage_pattern() {
 #[test]
 fn test_subscription_options_durable_queue() {
     // RabbitMQ durable subscription pattern
-    let opts = SubscriptionOptions {
-        durable_queue: Some("/queue/durable-events".to_string()),
-        headers: vec![],
-    };
+    let opts = SubscriptionOptions::default().durable_queue("/queue/durable-events");
 
     assert_eq!(
         opts.durable_queue,
@@ -88,17 +85,14 @@ fn test_subscription_options_durable_queue() {
 #[test]
 fn test_subscription_options_broker_specific_headers() {
     // ActiveMQ durable subscription pattern
-    let opts = SubscriptionOptions {
-        durable_queue: None,
-        headers: vec![
-            (
-                "activemq.subscriptionName".to_string(),
-                "my-durable-sub".to_string(),
-            ),
-            ("selector".to_string(), "priority > 5".to_string()),
-            ("activemq.noLocal".to_string(), "true".to_string()),
-        ],
-    };
+    let opts = SubscriptionOptions::default().headers(vec![
+        (
+            "activemq.subscriptionName".to_string(),
+            "my-durable-sub".to_string(),
+        ),
+        ("selector".to_string(), "priority > 5".to_string()),
+        ("activemq.noLocal".to_string(), "true".to_string()),
+    ]);
 
     assert_eq!(
         opts.headers.len(),
@@ -137,10 +131,9 @@ fn test_subscription_options_ergonomics() {
     assert!(default_opts.durable_queue.is_none());
 
     // Clone should preserve all fields
-    let opts = SubscriptionOptions {
-        durable_queue: Some("/queue/test".to_string()),
-        headers: vec![("key".to_string(), "value".to_string())],
-    };
+    let opts = SubscriptionOptions::default()
+        .durable_queue("/queue/test")
+        .headers(vec![("key".to_string(), "value".to_string())]);
 
     let cloned = opts.clone();
     assert_eq!(opts.durable_queue, cloned.durable_queue);
@@ -292,13 +285,10 @@ fn test_durable_subscription_example_compiles() {
     async fn durable_example() {
         // This is adapted from the README durable subscription example
 
-        // let opts = SubscriptionOptions {
-        //     headers: vec![
-        //         ("activemq.subscriptionName".into(), "my-durable-sub".into()),
-        //         ("selector".into(), "priority > 5".into()),
-        //     ],
-        //     durable_queue: None,
-        // };
+        // let opts = SubscriptionOptions::default().headers(vec![
+        //     ("activemq.subscriptionName".into(), "my-durable-sub".into()),
+        //     ("selector".into(), "priority > 5".into()),
+        // ]);
         //
         // let sub = conn.subscribe_with_options(
         //     "/topic/events",
@@ -0,0 +1,145 @@
+//! Tests for `Connection::subscription()` and `SubscriptionBuilder`.
+//!
+//! The mock broker records the SUBSCRIBE and UNSUBSCRIBE frames it reads
+//! and answers SUBSCRIBE receipts if told to.
+
+use futures::StreamExt;
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{AckMode, BrokerProfile, ConnError, Connection, Frame, Message, assert_frame};
+use std::time::Duration;
+
+/// Start a broker that answers receipts if `confirm` is set.
+async fn start_broker(confirm: bool) -> MockBroker {
+    let mut session = Session::new().connected();
+    if !confirm {
+        session = session.withhold_receipts();
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn builder_settings_end_up_on_the_subscribe_frame() {
+    let broker = start_broker(true).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let sub = conn
+        .subscription("/topic/orders")
+        .broker(BrokerProfile::RabbitMq)
+        .ack(AckMode::Client)
+        .prefetch(10)
+        .durable("orders")
+        .header("selector", "region = 'EU'")
+        .receipt(true)
+        .start()
+        .await
+        .expect("subscribe not confirmed");
+
+    assert_frame!(
+        broker.received_commands("SUBSCRIBE")[0],
+        FrameMatcher::command("SUBSCRIBE")
+            .header("id", sub.id())
+            .header("destination", "/topic/orders")
+            .header("ack", "client")
+            .header("prefetch-count", "10")
+            .header("x-queue-name", "orders")
+            .header("durable", "true")
+            .header("selector", "region = 'EU'")
+            .has_header("receipt")
+    );
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn unconfirmed_subscribe_is_cancelled() {
+    let broker = start_broker(false).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let result = conn
+        .subscription("/queue/orders")
+        .receipt(true)
+        .receipt_timeout(Duration::from_millis(200))
+        .start()
+        .await;
+    assert!(
+        matches!(result, Err(ConnError::ReceiptTimeout(_))),
+        "{:?}",
+        result.map(|sub| sub.id().to_string())
+    );
+
+    assert!(
+        broker
+            .wait_for("UNSUBSCRIBE", 1, Duration::from_secs(2))
+            .await
+    );
+    let subscribe = &broker.received_commands("SUBSCRIBE")[0];
+    assert_frame!(
+        broker.received_commands("UNSUBSCRIBE")[0],
+        "UNSUBSCRIBE",
+        "id" => subscribe.get_header("id").unwrap()
+    );
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn broker_specific_settings_need_a_broker() {
+    let broker = start_broker(true).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let result = conn
+        .subscription("/topic/orders")
+        .durable("orders")
+        .start()
+        .await;
+    assert!(
+        matches!(result, Err(ConnError::Protocol(_))),
+        "{:?}",
+        result.map(|sub| sub.id().to_string())
+    );
+    // Nothing was sent
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(broker.received_commands("SUBSCRIBE").is_empty());
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn messages_share_the_subscribed_destination() {
+    let broker = start_broker(true).await;
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let mut sub = conn
+        .subscribe("/queue/orders", AckMode::Client)
+        .await
+        .expect("subscribe failed");
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", 1, Duration::from_secs(2))
+            .await
+    );
+
+    broker.deliver(Frame::new("MESSAGE").set_body("one"));
+    broker.deliver(Frame::new("MESSAGE").set_body("two"));
+    for _ in 0..2 {
+        let frame = tokio::time::timeout(Duration::from_secs(2), sub.next())
+            .await
+            .expect("no message")
+            .expect("stream ended");
+        let message = Message::from(frame);
+        let destination = message.destination().expect("no destination");
+        assert_eq!(destination, "/queue/orders");
+        assert!(std::ptr::eq(destination.as_str(), sub.destination()));
+    }
+    conn.close().await;
+}
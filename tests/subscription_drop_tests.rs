@@ -0,0 +1,60 @@
+//! Tests for automatic cleanup of subscriptions whose receiver was dropped.
+//!
+//! When the application drops a `Subscription`, the next MESSAGE for it
+//! should cause the connection to remove the subscription, send UNSUBSCRIBE
+//! to the broker, and emit a `ConnectionEvent::SubscriptionDropped`.
+
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{AckMode, ConnectOptions, Connection, ConnectionEvent, Frame, assert_frame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn dropped_subscription_is_unsubscribed() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let sub = conn
+        .subscribe("/queue/drop", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+    assert_eq!(sub.id(), "1");
+    drop(sub);
+
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", 1, Duration::from_secs(2))
+            .await
+    );
+    broker.deliver(Frame::new("MESSAGE").set_body("hello"));
+
+    let event = tokio::time::timeout(Duration::from_secs(2), event_rx.recv())
+        .await
+        .expect("timed out waiting for event")
+        .expect("event channel closed");
+    assert_eq!(
+        event,
+        ConnectionEvent::SubscriptionDropped {
+            subscription_id: "1".to_string(),
+            destination: "/queue/drop".to_string(),
+        }
+    );
+
+    assert!(
+        broker
+            .wait_for("UNSUBSCRIBE", 1, Duration::from_secs(2))
+            .await
+    );
+    assert_frame!(broker.received_commands("UNSUBSCRIBE")[0], "UNSUBSCRIBE", "id" => "1");
+
+    // The subscription is gone locally, so a manual unsubscribe now fails
+    assert!(conn.unsubscribe("1").await.is_err());
+
+    conn.close().await;
+}
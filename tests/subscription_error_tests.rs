@@ -0,0 +1,124 @@
+//! Tests for connection-level ERRORs reaching subscriptions through
+//! `Subscription::recv_result()`.
+//!
+//! The mock broker delivers one message, sends an ERROR about no particular
+//! destination and closes the session, as brokers do; in the second
+//! session it redelivers the message and records the ACK frames it reads.
+
+use futures::StreamExt;
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{AckMode, ConnError, Connection, Frame};
+use std::time::Duration;
+
+/// A session that delivers message m1 once the client subscribes.
+fn deliver_on_subscribe() -> Session {
+    Session::new().connected().deliver_frame(
+        Frame::new("MESSAGE")
+            .header("message-id", "m1")
+            .set_body("body"),
+    )
+}
+
+#[tokio::test]
+async fn connection_error_invalidates_delivered_messages_until_redelivery() {
+    let broker = MockBroker::start(
+        Script::new()
+            .session(deliver_on_subscribe())
+            .session(deliver_on_subscribe()),
+    )
+    .await
+    .unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let mut sub = conn
+        .subscribe("/queue/orders", AckMode::ClientIndividual)
+        .await
+        .expect("subscribe failed");
+
+    let first = sub.recv().await.expect("no message");
+    assert_eq!(first.get_header("message-id"), Some("m1"));
+    // Now that the client has the message
+    broker.send(
+        Frame::new("ERROR")
+            .header("message", "internal error")
+            .set_body("broker restarting"),
+    );
+    broker.disconnect();
+
+    let error = tokio::time::timeout(Duration::from_secs(2), sub.recv_result())
+        .await
+        .expect("error not reported")
+        .expect("subscription closed")
+        .expect_err("expected the ERROR");
+    assert_eq!(error.message, "internal error");
+    assert_eq!(error.body.as_deref(), Some("broker restarting"));
+
+    let result = sub.ack("m1").await;
+    assert!(
+        matches!(&result, Err(ConnError::MessageInvalidated(id)) if id == "m1"),
+        "{:?}",
+        result
+    );
+
+    // The subscription is sent again and the message redelivered; the
+    // reconnect backoff after a short session is two seconds
+    let again = tokio::time::timeout(Duration::from_secs(5), sub.recv_result())
+        .await
+        .expect("message not redelivered")
+        .expect("subscription closed")
+        .expect("expected the message");
+    assert_eq!(again.get_header("message-id"), Some("m1"));
+    sub.ack("m1").await.expect("ack of the redelivery failed");
+
+    let ack = FrameMatcher::command("ACK").header("id", "m1");
+    assert!(
+        broker
+            .wait_for_match(&ack, Duration::from_secs(2))
+            .await
+            .is_some(),
+        "ACK not sent"
+    );
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn results_stream_yields_the_error_between_messages() {
+    let broker = MockBroker::start(
+        Script::new()
+            .session(deliver_on_subscribe())
+            .session(deliver_on_subscribe()),
+    )
+    .await
+    .unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    let mut results = conn
+        .subscribe("/queue/orders", AckMode::ClientIndividual)
+        .await
+        .expect("subscribe failed")
+        .into_results();
+
+    let mut next = async || {
+        tokio::time::timeout(Duration::from_secs(5), results.next())
+            .await
+            .expect("nothing delivered")
+            .expect("subscription closed")
+    };
+    let first = next().await.expect("expected the message");
+    assert_eq!(first.get_header("message-id"), Some("m1"));
+    broker.send(Frame::new("ERROR").header("message", "internal error"));
+    broker.disconnect();
+
+    let error = next().await.expect_err("expected the ERROR");
+    assert_eq!(error.message, "internal error");
+    let again = next().await.expect("expected the redelivery");
+    assert_eq!(again.get_header("message-id"), Some("m1"));
+
+    results
+        .ack("m1")
+        .await
+        .expect("ack of the redelivery failed");
+    conn.close().await;
+}
@@ -0,0 +1,144 @@
+//! Tests for `Subscription::pause()` and `resume()`.
+//!
+//! The mock broker sends a MESSAGE for every id the test hands it and
+//! records the frames it reads after the SUBSCRIBE, so the tests can check
+//! that pausing never unsubscribes.
+
+use futures::StreamExt;
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{AckMode, Connection, Frame, RecvTimeoutError, TryRecvError};
+use std::time::{Duration, Instant};
+
+async fn connect() -> (Connection, MockBroker) {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    (conn, broker)
+}
+
+/// Deliver a MESSAGE with each of `ids` once the client has subscribed.
+async fn deliver(broker: &MockBroker, ids: &[&str]) {
+    assert!(
+        broker
+            .wait_for("SUBSCRIBE", 1, Duration::from_secs(2))
+            .await
+    );
+    for id in ids {
+        broker.deliver(
+            Frame::new("MESSAGE")
+                .header("message-id", *id)
+                .set_body(*id),
+        );
+    }
+}
+
+/// Close the connection and return the commands the broker read after the
+/// SUBSCRIBE.
+async fn finish(conn: Connection, broker: MockBroker) -> Vec<String> {
+    conn.close_after_flush(Duration::from_secs(2))
+        .await
+        .expect("DISCONNECT not confirmed");
+    broker
+        .received()
+        .into_iter()
+        .map(|frame| frame.command)
+        .skip_while(|command| command != "SUBSCRIBE")
+        .skip(1)
+        .collect()
+}
+
+fn message_id(frame: &Frame) -> &str {
+    frame.get_header("message-id").unwrap_or("")
+}
+
+#[tokio::test]
+async fn paused_subscription_buffers_until_resumed() {
+    let (conn, broker) = connect().await;
+    let mut sub = conn
+        .subscribe("/queue/work", AckMode::Client)
+        .await
+        .unwrap();
+
+    sub.pause();
+    assert!(sub.is_paused());
+    deliver(&broker, &["m1", "m2", "m3"]).await;
+    assert_eq!(
+        sub.recv_timeout(Duration::from_millis(200)).await.err(),
+        Some(RecvTimeoutError::Timeout)
+    );
+    assert_eq!(sub.try_recv().err(), Some(TryRecvError::Empty));
+    assert!(
+        sub.recv_batch(10, Duration::from_millis(50))
+            .await
+            .is_empty()
+    );
+
+    sub.resume();
+    assert!(!sub.is_paused());
+    for expected in ["m1", "m2", "m3"] {
+        let frame = sub.recv_timeout(Duration::from_secs(2)).await.unwrap();
+        assert_eq!(message_id(&frame), expected);
+    }
+    sub.ack("m3").await.unwrap();
+
+    let commands = finish(conn, broker).await;
+    assert!(
+        !commands.iter().any(|c| c == "UNSUBSCRIBE"),
+        "{:?}",
+        commands
+    );
+    assert!(commands.iter().any(|c| c == "ACK"), "{:?}", commands);
+}
+
+#[tokio::test]
+async fn pause_handle_resumes_a_waiting_receiver() {
+    let (conn, broker) = connect().await;
+    let mut sub = conn.subscribe("/queue/work", AckMode::Auto).await.unwrap();
+    let pause = sub.pause_handle();
+    pause.pause();
+    assert!(sub.is_paused());
+    deliver(&broker, &["m1"]).await;
+
+    let started = Instant::now();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        pause.resume();
+    });
+    let frame = tokio::time::timeout(Duration::from_secs(2), sub.next())
+        .await
+        .expect("not resumed")
+        .unwrap();
+    assert_eq!(message_id(&frame), "m1");
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    finish(conn, broker).await;
+}
+
+#[tokio::test]
+async fn pausing_one_shared_handle_pauses_all() {
+    let (conn, broker) = connect().await;
+    let sub = conn.subscribe("/queue/work", AckMode::Auto).await.unwrap();
+    let pause = sub.pause_handle();
+    let mut workers = sub.into_shared(2);
+    workers[0].pause();
+    assert!(pause.is_paused() && workers[1].is_paused());
+    deliver(&broker, &["m1", "m2"]).await;
+    for worker in &mut workers {
+        assert!(
+            worker
+                .recv_timeout(Duration::from_millis(100))
+                .await
+                .is_err()
+        );
+    }
+
+    pause.resume();
+    let mut received = Vec::new();
+    for worker in &mut workers {
+        let frame = worker.recv_timeout(Duration::from_secs(2)).await.unwrap();
+        received.push(message_id(&frame).to_string());
+    }
+    received.sort();
+    assert_eq!(received, ["m1", "m2"]);
+    finish(conn, broker).await;
+}
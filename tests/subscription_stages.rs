@@ -0,0 +1,101 @@
+//! Tests for `Subscription::map()`, `filter()` and `inspect()`.
+//!
+//! The mock broker delivers a few messages to the first SUBSCRIBE and
+//! records the ACK frames it receives, so the tests can check that a
+//! filtered message is acknowledged without reaching the subscriber.
+
+use iridium_stomp::testing::{FrameMatcher, MockBroker, Script, Session};
+use iridium_stomp::{AckMode, Connection, Frame, assert_frame};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Start a broker that answers the SUBSCRIBE with `(message-id, type)`
+/// messages.
+async fn start_broker(messages: &[(&str, &str)]) -> MockBroker {
+    let mut session = Session::new().connected();
+    for (message_id, kind) in messages {
+        session = session.deliver_frame(
+            Frame::new("MESSAGE")
+                .header("message-id", *message_id)
+                .header("type", *kind)
+                .set_body(*message_id),
+        );
+    }
+    MockBroker::start(Script::new().session(session))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn steps_transform_and_filter_before_delivery() {
+    let broker = start_broker(&[("m1", "order"), ("m2", "ping"), ("m3", "order")]).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counter = seen.clone();
+    let mut sub = conn
+        .subscribe("/queue/events", AckMode::ClientIndividual)
+        .await
+        .unwrap()
+        .inspect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .filter(|frame| frame.get_header("type") != Some("ping"))
+        .map(|frame| frame.header("x-seen", "yes"));
+
+    for expected in ["m1", "m3"] {
+        let frame = tokio::time::timeout(Duration::from_secs(2), sub.recv())
+            .await
+            .expect("message not delivered")
+            .unwrap();
+        assert_eq!(frame.get_header("message-id"), Some(expected));
+        assert_eq!(frame.get_header("x-seen"), Some("yes"));
+    }
+    assert_eq!(seen.load(Ordering::SeqCst), 3);
+
+    // The filtered message is acknowledged by the connection
+    assert!(broker.wait_for("ACK", 1, Duration::from_secs(2)).await);
+    assert_frame!(
+        broker.received_commands("ACK")[0],
+        FrameMatcher::command("ACK")
+            .header("id", "m2")
+            .header("subscription", sub.id())
+    );
+
+    // m1 and m3 are still pending and can be acknowledged by the consumer
+    sub.ack("m1").await.unwrap();
+    sub.ack("m3").await.unwrap();
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn a_panicking_step_drops_only_that_message() {
+    let broker = start_broker(&[("m1", "bad"), ("m2", "good")]).await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut sub = conn
+        .subscribe("/queue/events", AckMode::Auto)
+        .await
+        .unwrap()
+        .map(|frame| {
+            assert_ne!(frame.get_header("type"), Some("bad"), "cannot transform");
+            frame
+        });
+
+    let frame = tokio::time::timeout(Duration::from_secs(2), sub.recv())
+        .await
+        .expect("connection did not survive the panic")
+        .unwrap();
+    assert_eq!(frame.get_header("message-id"), Some("m2"));
+    conn.close().await;
+}
@@ -19,16 +19,13 @@ fn subscription_options_default() {
 
 #[test]
 fn subscription_options_with_headers() {
-    let opts = SubscriptionOptions {
-        headers: vec![
-            (
-                "activemq.subscriptionName".to_string(),
-                "my-durable-sub".to_string(),
-            ),
-            ("selector".to_string(), "priority > 5".to_string()),
-        ],
-        durable_queue: None,
-    };
+    let opts = SubscriptionOptions::default().headers(vec![
+        (
+            "activemq.subscriptionName".to_string(),
+            "my-durable-sub".to_string(),
+        ),
+        ("selector".to_string(), "priority > 5".to_string()),
+    ]);
     assert_eq!(opts.headers.len(), 2);
     assert_eq!(opts.headers[0].0, "activemq.subscriptionName");
     assert_eq!(opts.headers[1].0, "selector");
@@ -36,19 +33,15 @@ fn subscription_options_with_headers() {
 
 #[test]
 fn subscription_options_with_durable_queue() {
-    let opts = SubscriptionOptions {
-        headers: vec![],
-        durable_queue: Some("/queue/durable-test".to_string()),
-    };
+    let opts = SubscriptionOptions::default().durable_queue("/queue/durable-test");
     assert_eq!(opts.durable_queue, Some("/queue/durable-test".to_string()));
 }
 
 #[test]
 fn subscription_options_clone() {
-    let original = SubscriptionOptions {
-        headers: vec![("key".to_string(), "value".to_string())],
-        durable_queue: Some("/queue/test".to_string()),
-    };
+    let original = SubscriptionOptions::default()
+        .headers(vec![("key".to_string(), "value".to_string())])
+        .durable_queue("/queue/test");
     let cloned = original.clone();
 
     assert_eq!(original.headers, cloned.headers);
@@ -57,10 +50,8 @@ fn subscription_options_clone() {
 
 #[test]
 fn subscription_options_debug() {
-    let opts = SubscriptionOptions {
-        headers: vec![("test".to_string(), "value".to_string())],
-        durable_queue: None,
-    };
+    let opts =
+        SubscriptionOptions::default().headers(vec![("test".to_string(), "value".to_string())]);
     let debug_str = format!("{:?}", opts);
     assert!(debug_str.contains("SubscriptionOptions"));
     assert!(debug_str.contains("test"));
@@ -69,17 +60,16 @@ fn subscription_options_debug() {
 
 #[test]
 fn subscription_options_full_config() {
-    let opts = SubscriptionOptions {
-        headers: vec![
+    let opts = SubscriptionOptions::default()
+        .headers(vec![
             (
                 "activemq.subscriptionName".to_string(),
                 "durable-sub-1".to_string(),
             ),
             ("activemq.noLocal".to_string(), "true".to_string()),
             ("selector".to_string(), "type = 'important'".to_string()),
-        ],
-        durable_queue: Some("/queue/events".to_string()),
-    };
+        ])
+        .durable_queue("/queue/events");
 
     assert_eq!(opts.headers.len(), 3);
     assert_eq!(opts.durable_queue.as_deref(), Some("/queue/events"));
@@ -91,26 +81,22 @@ fn subscription_options_full_config() {
 
 #[test]
 fn subscription_options_empty_header_values() {
-    let opts = SubscriptionOptions {
-        headers: vec![
-            ("empty-value".to_string(), "".to_string()),
-            ("".to_string(), "empty-key".to_string()),
-        ],
-        durable_queue: None,
-    };
+    let opts = SubscriptionOptions::default().headers(vec![
+        ("empty-value".to_string(), "".to_string()),
+        ("".to_string(), "empty-key".to_string()),
+    ]);
     assert_eq!(opts.headers[0].1, "");
     assert_eq!(opts.headers[1].0, "");
 }
 
 #[test]
 fn subscription_options_special_characters() {
-    let opts = SubscriptionOptions {
-        headers: vec![(
+    let opts = SubscriptionOptions::default()
+        .headers(vec![(
             "selector".to_string(),
             "id > 100 AND type = 'test'".to_string(),
-        )],
-        durable_queue: Some("/queue/test?param=value&other=123".to_string()),
-    };
+        )])
+        .durable_queue("/queue/test?param=value&other=123");
     assert!(opts.headers[0].1.contains("'test'"));
     assert!(opts.durable_queue.as_ref().unwrap().contains("?param="));
 }
@@ -0,0 +1,125 @@
+//! Tests for `Connection::tap()`.
+//!
+//! A mock broker delivers messages to a subscription (and one to a
+//! destination nobody subscribed to); the tests check that a tap sees all
+//! of them, counts what it missed when it falls behind, and ends with the
+//! connection.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{AckMode, ConnectOptions, Connection, Frame};
+use std::time::Duration;
+
+/// Start a broker that confirms receipts only when told to, and connect to
+/// it.
+async fn connect(options: ConnectOptions) -> (Connection, MockBroker) {
+    let broker =
+        MockBroker::start(Script::new().session(Session::new().connected().withhold_receipts()))
+            .await
+            .unwrap();
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+    (conn, broker)
+}
+
+/// Send a frame with a receipt and, before confirming it, have the broker
+/// send a message to `/queue/other` and `count` to the subscription
+/// `/queue/work`.
+async fn send_confirmed(conn: &Connection, broker: &MockBroker, count: usize, timeout: Duration) {
+    let sending = {
+        let conn = conn.clone();
+        tokio::spawn(async move {
+            let frame = Frame::new("SEND").header("destination", "/queue/in");
+            conn.send_frame_confirmed(frame, timeout).await
+        })
+    };
+    assert!(broker.wait_for("SEND", 1, Duration::from_secs(2)).await);
+    let send = &broker.received_commands("SEND")[0];
+
+    // Unclaimed messages queue up for `next_frame()`, which nobody reads
+    // here, so this one goes first
+    broker.send(
+        Frame::new("MESSAGE")
+            .header("destination", "/queue/other")
+            .header("message-id", "x")
+            .header("subscription", "99")
+            .set_body("other"),
+    );
+    for i in 0..count {
+        broker.deliver(
+            Frame::new("MESSAGE")
+                .header("message-id", format!("m{}", i))
+                .set_body(format!("body {}", i)),
+        );
+    }
+    let receipt = send.get_header("receipt").expect("no receipt requested");
+    broker.send(Frame::new("RECEIPT").header("receipt-id", receipt));
+    sending.await.unwrap().expect("no receipt");
+}
+
+#[tokio::test]
+async fn tap_mirrors_every_message() {
+    let (conn, broker) = connect(ConnectOptions::default()).await;
+    let mut tap = conn.tap();
+    let mut sub = conn
+        .subscription("/queue/work")
+        .ack(AckMode::ClientIndividual)
+        .start()
+        .await
+        .expect("subscribe failed");
+    send_confirmed(&conn, &broker, 2, Duration::from_secs(2)).await;
+
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        let frame = tokio::time::timeout(Duration::from_secs(2), tap.recv())
+            .await
+            .expect("tap fell silent")
+            .expect("tap ended");
+        ids.push(frame.get_header("message-id").unwrap().to_string());
+    }
+    assert_eq!(ids, ["x", "m0", "m1"]);
+    assert_eq!(tap.missed(), 0);
+
+    // The subscription still gets its messages and owes the ACKs
+    for _ in 0..2 {
+        sub.recv_timeout(Duration::from_secs(2))
+            .await
+            .expect("message not delivered");
+    }
+    assert_eq!(sub.ack_all().await.unwrap(), 2);
+
+    conn.close().await;
+    let end = tokio::time::timeout(Duration::from_secs(2), tap.recv()).await;
+    assert_eq!(end.expect("tap did not end"), None);
+}
+
+#[tokio::test]
+async fn lagging_tap_counts_missed_messages() {
+    // Nothing reads `next_frame()` either
+    let options = ConnectOptions::default().best_effort_mirror(true);
+    let (conn, broker) = connect(options).await;
+    let mut tap = conn.tap();
+    let _sub = conn
+        .subscribe("/queue/work", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+    // The RECEIPT follows the messages, so all of them have been tapped
+    send_confirmed(&conn, &broker, 1099, Duration::from_secs(5)).await;
+    conn.close().await;
+
+    let mut received = 0;
+    while let Some(_frame) = tap.recv().await {
+        received += 1;
+    }
+    assert_eq!(received, 1024);
+    assert_eq!(tap.missed(), 1100 - 1024);
+}
+
+#[tokio::test]
+async fn tap_after_close_ends_at_once() {
+    let (conn, _broker) = connect(ConnectOptions::default()).await;
+    conn.clone().close().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(conn.tap().recv().await, None);
+}
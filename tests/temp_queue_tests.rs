@@ -0,0 +1,135 @@
+//! Tests for `Connection::subscribe_temp()`.
+//!
+//! The mock brokers record every frame they receive, so the assertions can
+//! check which SUBSCRIBE/UNSUBSCRIBE traffic each broker profile produces.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{BrokerProfile, ConnError, Connection, Frame, assert_frame};
+use std::time::Duration;
+
+#[tokio::test]
+async fn activemq_subscribes_and_unsubscribes_on_drop() {
+    let broker = MockBroker::start(
+        Script::new().session(
+            Session::new().connected().deliver_frame(
+                Frame::new("MESSAGE")
+                    .header("message-id", "m1")
+                    .set_body("pong"),
+            ),
+        ),
+    )
+    .await
+    .unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // Let the connection task finish starting its first session
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut replies = conn
+        .subscribe_temp("rpc", BrokerProfile::ActiveMq)
+        .await
+        .unwrap();
+    assert!(replies.reply_to().starts_with("/temp-queue/rpc-"));
+    let id = replies.id().to_string();
+
+    let message = replies
+        .recv_timeout(Duration::from_secs(5))
+        .await
+        .expect("no reply delivered");
+    assert_eq!(message.body, b"pong");
+
+    assert_frame!(
+        broker.received_commands("SUBSCRIBE")[0],
+        "SUBSCRIBE",
+        "destination" => replies.reply_to(),
+        "ack" => "auto",
+    );
+
+    drop(replies);
+    assert!(
+        broker
+            .wait_for("UNSUBSCRIBE", 1, Duration::from_secs(5))
+            .await
+    );
+    assert_frame!(
+        broker.received_commands("UNSUBSCRIBE")[0],
+        "UNSUBSCRIBE",
+        "id" => id.as_str()
+    );
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn rabbitmq_delivers_without_subscribe() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut replies = conn
+        .subscribe_temp("rpc", BrokerProfile::RabbitMq)
+        .await
+        .unwrap();
+    assert!(replies.reply_to().starts_with("/temp-queue/rpc-"));
+    // RabbitMQ names the temporary queue's subscription after it
+    broker.send(
+        Frame::new("MESSAGE")
+            .header("destination", "/reply-queue/amq.gen-1")
+            .header("message-id", "m1")
+            .header("subscription", replies.reply_to())
+            .set_body("pong"),
+    );
+
+    let message = replies
+        .recv_timeout(Duration::from_secs(5))
+        .await
+        .expect("no reply delivered");
+    assert_eq!(message.body, b"pong");
+
+    drop(replies);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(
+        broker
+            .received()
+            .iter()
+            .all(|f| f.command != "SUBSCRIBE" && f.command != "UNSUBSCRIBE"),
+        "no SUBSCRIBE or UNSUBSCRIBE expected"
+    );
+
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn temp_queue_names_are_unique() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    let first = conn
+        .subscribe_temp("rpc", BrokerProfile::RabbitMq)
+        .await
+        .unwrap();
+    let second = conn
+        .subscribe_temp("rpc", BrokerProfile::RabbitMq)
+        .await
+        .unwrap();
+    assert_ne!(first.reply_to(), second.reply_to());
+
+    assert!(matches!(
+        conn.subscribe_temp("rpc", BrokerProfile::Generic).await,
+        Err(ConnError::Protocol(_))
+    ));
+    assert!(matches!(
+        conn.subscribe_temp("", BrokerProfile::RabbitMq).await,
+        Err(ConnError::Protocol(_))
+    ));
+
+    conn.close().await;
+}
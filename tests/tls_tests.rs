@@ -0,0 +1,189 @@
+//! Tests for STOMP over TLS (`tls` feature).
+//!
+//! The mock broker uses the certificate in `tests/data`, which is valid for
+//! `localhost` and `127.0.0.1` and issued by the test CA `tls-ca.pem`.
+//! Brokers requiring client certificates accept those the same CA issued.
+#![cfg(feature = "tls")]
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use iridium_stomp::{ConnError, ConnectOptions, Connection, TlsOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+
+const CERT: &[u8] = include_bytes!("data/tls-cert.pem");
+const KEY: &[u8] = include_bytes!("data/tls-key.pem");
+const CA: &[u8] = include_bytes!("data/tls-ca.pem");
+const CLIENT_CERT: &[u8] = include_bytes!("data/tls-client-cert.pem");
+const CLIENT_KEY: &[u8] = include_bytes!("data/tls-client-key.pem");
+
+/// Start a TLS broker that completes one STOMP handshake, requiring a
+/// client certificate when `client_auth` is set. Returns its address and a
+/// handle resolving to the SNI name the client sent.
+async fn tls_broker(client_auth: bool) -> (String, JoinHandle<Option<String>>) {
+    let provider = Arc::new(ring::default_provider());
+    let cert = CertificateDer::from_pem_slice(CERT).unwrap();
+    let key = PrivateKeyDer::from_pem_slice(KEY).unwrap();
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = if client_auth {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(CA).unwrap())
+            .unwrap();
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .unwrap();
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut config = builder.with_single_cert(vec![cert], key).unwrap();
+    config.alpn_protocols = vec![b"stomp".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    let server = tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut stream = match acceptor.accept(tcp).await {
+            Ok(s) => s,
+            Err(_) => return None,
+        };
+        let sni = stream.get_ref().1.server_name().map(str::to_string);
+
+        // Read the CONNECT frame
+        let mut byte = [0u8; 1];
+        while stream.read(&mut byte).await.unwrap_or(0) == 1 && byte[0] != 0 {}
+
+        stream
+            .write_all(b"CONNECTED\nversion:1.2\nheart-beat:0,0\n\n\0")
+            .await
+            .unwrap();
+
+        // Hold the connection open until the client leaves
+        let mut buf = [0u8; 256];
+        while stream.read(&mut buf).await.unwrap_or(0) > 0 {}
+        sni
+    });
+
+    (addr, server)
+}
+
+#[tokio::test]
+async fn insecure_mode_connects_with_sni_and_alpn() {
+    let (addr, server) = tls_broker(false).await;
+
+    let tls = TlsOptions::new()
+        .server_name("stomp.test")
+        .alpn_protocols(["stomp"])
+        .danger_accept_invalid_certs();
+    let options = ConnectOptions::default().tls(tls);
+    let conn = tokio::time::timeout(
+        Duration::from_secs(5),
+        Connection::connect_with_options(&addr, "guest", "guest", "0,0", options),
+    )
+    .await
+    .expect("connect timed out")
+    .expect("connect failed");
+
+    let info = conn.server_info().await;
+    assert_eq!(info.version, "1.2");
+    let tls = info.tls.expect("TLS details missing");
+    assert_eq!(tls.protocol_version, "TLSv1_3");
+    assert!(!tls.cipher_suite.is_empty());
+    assert_eq!(tls.alpn_protocol.as_deref(), Some("stomp"));
+    let cert = tls.peer_certificate.expect("peer certificate missing");
+    assert_eq!(cert.subject, "CN=localhost");
+    assert!(cert.not_after.unwrap() > SystemTime::now());
+
+    conn.close().await;
+    let sni = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not finish")
+        .unwrap();
+    assert_eq!(sni.as_deref(), Some("stomp.test"));
+}
+
+#[tokio::test]
+async fn untrusted_certificate_fails_without_retrying() {
+    let (addr, _server) = tls_broker(false).await;
+
+    let options = ConnectOptions::default().tls(TlsOptions::new());
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        Connection::connect_with_options(&addr, "guest", "guest", "0,0", options),
+    )
+    .await
+    .expect("connect should fail immediately");
+
+    match result {
+        Err(ConnError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidData),
+        Err(other) => panic!("expected an I/O error, got {:?}", other),
+        Ok(_) => panic!("connected despite an untrusted certificate"),
+    }
+}
+
+async fn connect(addr: &str, tls: TlsOptions) -> Result<Connection, ConnError> {
+    let options = ConnectOptions::default().tls(tls);
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        Connection::connect_with_options(addr, "guest", "guest", "0,0", options),
+    )
+    .await
+    .expect("connect timed out")
+}
+
+#[tokio::test]
+async fn ca_certificate_verifies_the_broker() {
+    let (addr, _server) = tls_broker(false).await;
+
+    let tls = TlsOptions::new().ca_certificates_pem(CA);
+    let conn = connect(&addr, tls).await.expect("connect failed");
+    assert!(conn.server_info().await.tls.is_some());
+    conn.close().await;
+
+    // Trusting only a certificate that did not issue the broker's fails
+    let (addr, _server) = tls_broker(false).await;
+    let tls = TlsOptions::new().ca_certificates_pem(CLIENT_CERT);
+    match connect(&addr, tls).await {
+        Err(ConnError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidData),
+        Err(other) => panic!("expected an I/O error, got {:?}", other),
+        Ok(_) => panic!("connected despite an untrusted certificate"),
+    }
+}
+
+#[tokio::test]
+async fn client_certificate_is_presented() {
+    let (addr, _server) = tls_broker(true).await;
+
+    let tls = TlsOptions::new()
+        .ca_certificates_pem(CA)
+        .client_certificate_pem(CLIENT_CERT, CLIENT_KEY);
+    let conn = connect(&addr, tls).await.expect("connect failed");
+    assert_eq!(conn.server_info().await.version, "1.2");
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn invalid_pem_fails_before_connecting() {
+    let tls = TlsOptions::new().client_certificate_pem(CLIENT_CERT, "no key here");
+    match connect("127.0.0.1:1", tls).await {
+        Err(ConnError::Io(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(e.to_string().contains("invalid client key"), "{}", e);
+        }
+        Err(other) => panic!("expected an I/O error, got {:?}", other),
+        Ok(_) => panic!("connected with an invalid key"),
+    }
+}
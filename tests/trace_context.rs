@@ -0,0 +1,86 @@
+//! Tests for W3C trace context parsing and extraction from messages.
+
+use iridium_stomp::{Frame, Message, TraceContext};
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+// =============================================================================
+// Parsing
+// =============================================================================
+
+#[test]
+fn parse_valid_traceparent() {
+    let ctx = TraceContext::parse(TRACEPARENT).expect("valid");
+    assert_eq!(ctx.traceparent(), TRACEPARENT);
+    assert_eq!(ctx.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(ctx.parent_id(), "00f067aa0ba902b7");
+    assert!(ctx.is_sampled());
+    assert_eq!(ctx.tracestate(), None);
+}
+
+#[test]
+fn parse_rejects_malformed_values() {
+    let invalid = [
+        "",
+        "garbage",
+        // reserved version
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        // all-zero trace id
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        // all-zero parent id
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        // uppercase hex
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        // short trace id
+        "00-4bf92f3577b34da6-00f067aa0ba902b7-01",
+        // extra field on version 00
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+    ];
+    for value in invalid {
+        assert!(TraceContext::parse(value).is_none(), "accepted {value:?}");
+    }
+}
+
+#[test]
+fn new_builds_unsampled_context() {
+    let ctx = TraceContext::new(
+        "4bf92f3577b34da6a3ce929d0e0e4736",
+        "00f067aa0ba902b7",
+        false,
+    )
+    .expect("valid");
+    assert_eq!(
+        ctx.traceparent(),
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+    );
+    assert!(!ctx.is_sampled());
+}
+
+// =============================================================================
+// Extraction from MESSAGE frames
+// =============================================================================
+
+#[test]
+fn message_extracts_trace_context() {
+    let frame = Frame::new("MESSAGE")
+        .header("destination", "/queue/a")
+        .header("message-id", "m1")
+        .header("traceparent", TRACEPARENT)
+        .header("tracestate", "congo=t61rcWkgMzE");
+    let msg = Message::from(frame);
+
+    assert_eq!(msg.destination().map(|d| d.as_str()), Some("/queue/a"));
+    assert_eq!(msg.message_id(), Some("m1"));
+    let ctx = msg.trace_context().expect("trace context");
+    assert_eq!(ctx.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(ctx.tracestate(), Some("congo=t61rcWkgMzE"));
+}
+
+#[test]
+fn message_without_valid_traceparent_has_no_context() {
+    let msg = Message::from_frame(Frame::new("MESSAGE"));
+    assert!(msg.trace_context().is_none());
+
+    let msg = Message::from_frame(Frame::new("MESSAGE").header("traceparent", "bogus"));
+    assert!(msg.trace_context().is_none());
+}
@@ -0,0 +1,155 @@
+//! Tests for `Connection::builder()` and `ConnectionBuilder::start()`.
+//!
+//! The mock broker answers the first SUBSCRIBE with a MESSAGE right away
+//! and records the SUBSCRIBE frames it reads, so a message sent before the
+//! application has a consumer, or a SUBSCRIBE sent twice, shows up.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{AckMode, ConnError, ConnectOptions, Connection, Frame, assert_frame};
+use std::time::Duration;
+
+/// Start a broker that answers the first SUBSCRIBE with a MESSAGE.
+async fn start_broker() -> MockBroker {
+    MockBroker::start(
+        Script::new().session(
+            Session::new().connected().deliver_frame(
+                Frame::new("MESSAGE")
+                    .header("message-id", "m1")
+                    .set_body("hello"),
+            ),
+        ),
+    )
+    .await
+    .unwrap()
+}
+
+/// SUBSCRIBE frames the broker read within `wait`.
+async fn subscribes(broker: &MockBroker, wait: Duration) -> Vec<Frame> {
+    tokio::time::sleep(wait).await;
+    broker.received_commands("SUBSCRIBE")
+}
+
+#[tokio::test]
+async fn subscriptions_made_before_start_are_sent_once() {
+    let broker = start_broker().await;
+
+    let builder = Connection::builder(
+        &broker.address(),
+        "guest",
+        "guest",
+        "0,0",
+        ConnectOptions::default(),
+    )
+    .expect("invalid options");
+    let mut sub = builder
+        .connection()
+        .subscribe("/queue/orders", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+    let conn = builder.start().await.expect("connect failed");
+
+    let frame = sub
+        .recv_timeout(Duration::from_secs(2))
+        .await
+        .expect("message lost");
+    assert_eq!(frame.body, b"hello");
+
+    let sent = subscribes(&broker, Duration::from_millis(300)).await;
+    assert_eq!(sent.len(), 1, "{:?}", sent);
+    assert_frame!(sent[0], "SUBSCRIBE", "id" => sub.id());
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn subscribing_right_after_connect_sends_one_subscribe() {
+    let broker = start_broker().await;
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    // No pause: the first session may still be starting
+    let _sub = conn
+        .subscribe("/queue/orders", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+
+    let sent = subscribes(&broker, Duration::from_millis(300)).await;
+    assert_eq!(sent.len(), 1, "{:?}", sent);
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn subscribe_receipts_need_a_started_connection() {
+    let broker = start_broker().await;
+
+    let builder = Connection::builder(
+        &broker.address(),
+        "guest",
+        "guest",
+        "0,0",
+        ConnectOptions::default(),
+    )
+    .expect("invalid options");
+    let result = builder
+        .connection()
+        .subscription("/queue/orders")
+        .receipt(true)
+        .start()
+        .await;
+    assert!(
+        matches!(result, Err(ConnError::Protocol(_))),
+        "{:?}",
+        result.map(|sub| sub.id().to_string())
+    );
+
+    let conn = builder.start().await.expect("connect failed");
+    let sent = subscribes(&broker, Duration::from_millis(300)).await;
+    assert!(sent.is_empty(), "{:?}", sent);
+    conn.close().await;
+}
+
+#[tokio::test]
+async fn sends_before_start_fail_fast_instead_of_blocking() {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let builder = Connection::builder(
+        &broker.address(),
+        "guest",
+        "guest",
+        "0,0",
+        ConnectOptions::default(),
+    )
+    .expect("invalid options");
+    let conn = builder.connection().clone();
+    for _ in 0..32 {
+        conn.send("/queue/early", "queued")
+            .await
+            .expect("not queued");
+    }
+    let result = tokio::time::timeout(Duration::from_secs(1), conn.send("/queue/early", "more"))
+        .await
+        .expect("send blocked before start()");
+    assert!(
+        matches!(result, Err(ConnError::Protocol(_))),
+        "{:?}",
+        result
+    );
+
+    let confirmed = tokio::time::timeout(
+        Duration::from_secs(1),
+        conn.send_frame_confirmed(
+            Frame::new("SEND").header("destination", "/queue/early"),
+            Duration::from_secs(5),
+        ),
+    )
+    .await
+    .expect("receipt wait blocked before start()");
+    assert!(
+        matches!(confirmed, Err(ConnError::Protocol(_))),
+        "{:?}",
+        confirmed
+    );
+
+    let conn = builder.start().await.expect("connect failed");
+    conn.close().await;
+}
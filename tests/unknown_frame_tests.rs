@@ -0,0 +1,63 @@
+//! Tests for reporting frames with commands the client does not handle.
+//!
+//! Such frames are still forwarded to `next_frame()`, but they also bump
+//! `Connection::unknown_frame_count()` and emit a
+//! `ConnectionEvent::UnknownFrame`.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConnectOptions, Connection, ConnectionEvent, Frame, ReceivedFrame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn unknown_command_is_reported_and_forwarded() {
+    let broker = MockBroker::start(
+        Script::new().session(
+            Session::new()
+                .connected()
+                .send(Frame::new("PING").header("x-ext", "1"))
+                .send(
+                    Frame::new("MESSAGE")
+                        .header("destination", "/queue/a")
+                        .header("message-id", "m1")
+                        .header("subscription", "9")
+                        .set_body("hi"),
+                ),
+        ),
+    )
+    .await
+    .unwrap();
+
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let options = ConnectOptions::default().with_event_notify(event_tx);
+    let conn =
+        Connection::connect_with_options(&broker.address(), "guest", "guest", "0,0", options)
+            .await
+            .expect("connect failed");
+
+    let event = tokio::time::timeout(Duration::from_secs(2), event_rx.recv())
+        .await
+        .expect("timed out waiting for event")
+        .expect("event channel closed");
+    assert_eq!(
+        event,
+        ConnectionEvent::UnknownFrame {
+            command: "PING".to_string(),
+        }
+    );
+
+    match tokio::time::timeout(Duration::from_secs(2), conn.next_frame()).await {
+        Ok(Some(ReceivedFrame::Frame(f))) => assert_eq!(f.command, "PING"),
+        other => panic!("expected forwarded PING frame, got {:?}", other),
+    }
+    match tokio::time::timeout(Duration::from_secs(2), conn.next_frame()).await {
+        Ok(Some(ReceivedFrame::Frame(f))) => assert_eq!(f.command, "MESSAGE"),
+        other => panic!("expected MESSAGE frame, got {:?}", other),
+    }
+
+    // Only the unhandled command is counted
+    assert_eq!(conn.unknown_frame_count(), 1);
+    assert!(event_rx.try_recv().is_err());
+
+    conn.close().await;
+}
@@ -0,0 +1,73 @@
+//! Tests for the CONNECT `host` header: `ConnectOptions::host()`, the vhost
+//! of a `stomp://` address, the address's host name, then "/".
+//!
+//! The mock broker drops the first session so each test also checks that
+//! the reconnect sends the same `host`.
+
+use iridium_stomp::testing::{MockBroker, Script, Session};
+use iridium_stomp::{ConfigError, ConnError, ConnectOptions, Connection};
+use std::time::Duration;
+
+/// Connect to a mock broker at `addr` (with `{port}` replaced) and return
+/// the `host` header of the first CONNECT and of the reconnect.
+async fn connect_hosts(addr: &str, options: ConnectOptions) -> (String, String) {
+    let broker = MockBroker::start(
+        Script::new()
+            // Dropping the first session forces a reconnect
+            .session(Session::new().connected().close())
+            .session(Session::new().connected()),
+    )
+    .await
+    .unwrap();
+    let addr = addr.replace("{port}", &broker.addr().port().to_string());
+
+    let conn = Connection::connect_with_options(&addr, "guest", "guest", "0,0", options)
+        .await
+        .expect("connect failed");
+    assert!(broker.wait_for("CONNECT", 2, Duration::from_secs(5)).await);
+    conn.close().await;
+    let hosts: Vec<String> = broker
+        .received_commands("CONNECT")
+        .iter()
+        .map(|connect| connect.get_header("host").unwrap_or("").to_string())
+        .collect();
+    (hosts[0].clone(), hosts[1].clone())
+}
+
+#[tokio::test]
+async fn explicit_host_wins() {
+    let options = ConnectOptions::default().host("explicit");
+    let hosts = connect_hosts("stomp://localhost:{port}/from-uri", options).await;
+    assert_eq!(hosts, ("explicit".to_string(), "explicit".to_string()));
+}
+
+#[tokio::test]
+async fn uri_vhost_beats_host_name() {
+    let hosts = connect_hosts(
+        "stomp://localhost:{port}/%2Fprod",
+        ConnectOptions::default(),
+    )
+    .await;
+    assert_eq!(hosts, ("/prod".to_string(), "/prod".to_string()));
+}
+
+#[tokio::test]
+async fn host_name_is_derived_from_the_address() {
+    let hosts = connect_hosts("localhost:{port}", ConnectOptions::default()).await;
+    assert_eq!(hosts, ("localhost".to_string(), "localhost".to_string()));
+}
+
+#[tokio::test]
+async fn ip_address_falls_back_to_root_vhost() {
+    let hosts = connect_hosts("127.0.0.1:{port}", ConnectOptions::default()).await;
+    assert_eq!(hosts, ("/".to_string(), "/".to_string()));
+}
+
+#[tokio::test]
+async fn unsupported_uri_is_rejected() {
+    let result = Connection::connect("http://localhost:61613", "guest", "guest", "0,0").await;
+    assert!(matches!(
+        result,
+        Err(ConnError::Config(ConfigError::InvalidAddress(_)))
+    ));
+}
@@ -0,0 +1,79 @@
+//! Tests for `WeakConnection` and for subscriptions outliving their
+//! connection.
+//!
+//! The mock broker reads frames until the client closes the socket, and
+//! counts the connections still open.
+
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{AckMode, ConnError, Connection, RecvTimeoutError};
+use std::time::Duration;
+
+async fn connect() -> (Connection, MockBroker) {
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+    (conn, broker)
+}
+
+/// Whether the client closes the socket within `timeout`.
+async fn socket_closed(broker: &MockBroker, timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, async {
+        while broker.open_connections() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .is_ok()
+}
+
+#[tokio::test]
+async fn dropping_the_last_connection_shuts_it_down() {
+    let (conn, broker) = connect().await;
+    let mut sub = conn
+        .subscribe("/queue/orders", AckMode::Client)
+        .await
+        .expect("subscribe failed");
+    let weak = conn.downgrade();
+    assert!(weak.upgrade().is_some());
+
+    drop(conn);
+
+    assert!(weak.upgrade().is_none());
+    assert!(
+        socket_closed(&broker, Duration::from_secs(2)).await,
+        "socket left open"
+    );
+    let result = sub.recv_timeout(Duration::from_secs(2)).await;
+    assert!(
+        matches!(result, Err(RecvTimeoutError::Closed)),
+        "{:?}",
+        result
+    );
+    let ack = sub.ack("m1").await;
+    assert!(matches!(ack, Err(ConnError::ChannelClosed)), "{:?}", ack);
+}
+
+#[tokio::test]
+async fn a_remaining_clone_keeps_the_connection_alive() {
+    let (conn, broker) = connect().await;
+    let mut sub = conn
+        .subscribe("/queue/orders", AckMode::Auto)
+        .await
+        .expect("subscribe failed");
+    let other = conn.clone();
+
+    drop(conn);
+
+    assert!(
+        !socket_closed(&broker, Duration::from_millis(300)).await,
+        "socket closed early"
+    );
+    let result = sub.recv_timeout(Duration::from_millis(100)).await;
+    assert!(
+        matches!(result, Err(RecvTimeoutError::Timeout)),
+        "{:?}",
+        result
+    );
+    other.close().await;
+}
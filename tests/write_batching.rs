@@ -0,0 +1,40 @@
+//! Tests for the writer loop batching queued frames into a single flush.
+//!
+//! A burst of frames is written through a mock broker, which verifies that
+//! every frame arrives intact and in order.
+
+use iridium_stomp::testing::{MockBroker, Script};
+use iridium_stomp::{Connection, Frame};
+use std::time::Duration;
+
+#[tokio::test]
+async fn burst_of_frames_arrives_complete_and_in_order() {
+    const COUNT: usize = 500;
+
+    let broker = MockBroker::start(Script::new()).await.unwrap();
+
+    let conn = Connection::connect(&broker.address(), "guest", "guest", "0,0")
+        .await
+        .expect("connect failed");
+
+    for i in 0..COUNT {
+        let frame = Frame::new("SEND")
+            .header("destination", "/queue/burst")
+            .set_body(i.to_string().into_bytes());
+        conn.send_frame(frame).await.expect("send failed");
+    }
+
+    assert!(
+        broker.wait_for("SEND", COUNT, Duration::from_secs(5)).await,
+        "broker did not receive all frames"
+    );
+    let bodies: Vec<String> = broker
+        .received_commands("SEND")
+        .into_iter()
+        .map(|frame| String::from_utf8(frame.body).unwrap())
+        .collect();
+    let expected: Vec<String> = (0..COUNT).map(|i| i.to_string()).collect();
+    assert_eq!(bodies, expected);
+
+    conn.close().await;
+}